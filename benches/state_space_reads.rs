@@ -0,0 +1,206 @@
+//Latency of state space reads while blocks are applied, run with `cargo bench --bench state_space_reads`.
+//"before" is the single RwLock<StateSpace> that was locked for writing while a block was applied, "after" is the ShardedStateSpace
+//that is locked shard by shard. Both are read by the same readers while the same writer applies the same blocks.
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use amms::{
+    amm::{uniswap_v2::UniswapV2Pool, AutomatedMarketMaker, AMM},
+    state_space::{sharded::ShardedStateSpace, state::StateSpace},
+};
+use ethers::types::H160;
+use tokio::sync::RwLock;
+
+const AMMS: usize = 10_000;
+const AMMS_PER_BLOCK: usize = 500;
+const READERS: usize = 4;
+const DURATION: Duration = Duration::from_secs(3);
+
+//Xorshift, the addresses only have to be spread over the shards
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn address(&mut self) -> H160 {
+        let mut address = [0; 20];
+        for chunk in address.chunks_mut(8) {
+            chunk.copy_from_slice(&self.next_u64().to_be_bytes()[..chunk.len()]);
+        }
+
+        H160(address)
+    }
+}
+
+fn amms() -> (Vec<H160>, Vec<AMM>) {
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    let addresses = (0..AMMS).map(|_| rng.address()).collect::<Vec<H160>>();
+    let amms = addresses
+        .iter()
+        .map(|address| {
+            AMM::UniswapV2Pool(UniswapV2Pool {
+                address: *address,
+                reserve_0: 1,
+                reserve_1: 1,
+                ..Default::default()
+            })
+        })
+        .collect();
+
+    (addresses, amms)
+}
+
+fn sync(amm: &mut AMM) {
+    if let AMM::UniswapV2Pool(pool) = amm {
+        pool.reserve_0 += 1;
+        pool.reserve_1 += 1;
+    }
+}
+
+//Reads random AMMs until the deadline, returns the latency of every read
+async fn read_latencies<F, Fut>(addresses: Arc<Vec<H160>>, seed: u64, read: F) -> Vec<Duration>
+where
+    F: Fn(H160) -> Fut,
+    Fut: std::future::Future<Output = Option<AMM>>,
+{
+    let mut rng = Rng(seed);
+    let mut latencies = vec![];
+    let deadline = Instant::now() + DURATION;
+
+    while Instant::now() < deadline {
+        let address = addresses[rng.next_u64() as usize % addresses.len()];
+        let start = Instant::now();
+        read(address).await;
+        latencies.push(start.elapsed());
+        tokio::task::yield_now().await;
+    }
+
+    latencies
+}
+
+fn report(name: &str, mut latencies: Vec<Duration>) {
+    latencies.sort();
+    let percentile =
+        |percentile: usize| latencies[(latencies.len() - 1) * percentile / 100].as_micros();
+
+    println!(
+        "{name:<6} reads: {:>9}  p50: {:>6}us  p99: {:>6}us  max: {:>6}us",
+        latencies.len(),
+        percentile(50),
+        percentile(99),
+        latencies.last().map_or(0, |latency| latency.as_micros()),
+    );
+}
+
+async fn before(addresses: Arc<Vec<H160>>, amms: Vec<AMM>) -> Vec<Duration> {
+    let state: Arc<RwLock<StateSpace>> = Arc::new(RwLock::new(
+        amms.into_iter().map(|amm| (amm.address(), amm)).collect(),
+    ));
+
+    let writer = {
+        let (state, addresses) = (state.clone(), addresses.clone());
+        tokio::spawn(async move {
+            let mut rng = Rng(1);
+            let deadline = Instant::now() + DURATION;
+            while Instant::now() < deadline {
+                //The state space is locked for the whole block
+                let mut state = state.write().await;
+                for _ in 0..AMMS_PER_BLOCK {
+                    let address = addresses[rng.next_u64() as usize % addresses.len()];
+                    if let Some(amm) = state.get_mut(&address) {
+                        sync(amm);
+                    }
+                }
+                drop(state);
+                tokio::task::yield_now().await;
+            }
+        })
+    };
+
+    let readers = (0..READERS as u64)
+        .map(|seed| {
+            let (state, addresses) = (state.clone(), addresses.clone());
+            tokio::spawn(async move {
+                read_latencies(addresses, seed + 2, |address| {
+                    let state = state.clone();
+                    async move { state.read().await.get(&address).cloned() }
+                })
+                .await
+            })
+        })
+        .collect::<Vec<_>>();
+
+    collect(writer, readers).await
+}
+
+async fn after(addresses: Arc<Vec<H160>>, amms: Vec<AMM>) -> Vec<Duration> {
+    let state = Arc::new(ShardedStateSpace::new(amms));
+
+    let writer = {
+        let (state, addresses) = (state.clone(), addresses.clone());
+        tokio::spawn(async move {
+            let mut rng = Rng(1);
+            let deadline = Instant::now() + DURATION;
+            while Instant::now() < deadline {
+                //Only the shard of the AMM that is written is locked
+                for _ in 0..AMMS_PER_BLOCK {
+                    let address = addresses[rng.next_u64() as usize % addresses.len()];
+                    state.update(address, sync).await;
+                }
+                tokio::task::yield_now().await;
+            }
+        })
+    };
+
+    let readers = (0..READERS as u64)
+        .map(|seed| {
+            let (state, addresses) = (state.clone(), addresses.clone());
+            tokio::spawn(async move {
+                read_latencies(addresses, seed + 2, |address| {
+                    let state = state.clone();
+                    async move { state.get(address).await }
+                })
+                .await
+            })
+        })
+        .collect::<Vec<_>>();
+
+    collect(writer, readers).await
+}
+
+async fn collect(
+    writer: tokio::task::JoinHandle<()>,
+    readers: Vec<tokio::task::JoinHandle<Vec<Duration>>>,
+) -> Vec<Duration> {
+    let mut latencies = vec![];
+    for reader in readers {
+        latencies.extend(reader.await.expect("reader panicked"));
+    }
+    writer.await.expect("writer panicked");
+
+    latencies
+}
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(READERS + 1)
+        .enable_all()
+        .build()
+        .expect("runtime is built");
+
+    let (addresses, amms) = amms();
+    let addresses = Arc::new(addresses);
+
+    report(
+        "before",
+        runtime.block_on(before(addresses.clone(), amms.clone())),
+    );
+    report("after", runtime.block_on(after(addresses, amms)));
+}
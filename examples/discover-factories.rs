@@ -1,4 +1,7 @@
-use amms::discovery::factory::{discover_factories, DiscoverableFactory};
+use amms::discovery::{
+    chain::ETHEREUM_MAINNET,
+    factory::{discover_factories, DiscoverableFactory},
+};
 use ethers::providers::{Http, Provider};
 use std::sync::Arc;
 
@@ -18,7 +21,10 @@ async fn main() -> eyre::Result<()> {
         ],
         number_of_amms_threshold,
         provider,
-        50000,
+        ETHEREUM_MAINNET.default_step,
+        None,
+        Some(ETHEREUM_MAINNET),
+        true,
     )
     .await?;
 
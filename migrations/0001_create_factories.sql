@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS factories (
+    address TEXT PRIMARY KEY,
+    kind TEXT NOT NULL,
+    creation_block BIGINT NOT NULL,
+    data JSONB NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
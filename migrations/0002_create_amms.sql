@@ -0,0 +1,10 @@
+-- AMMs that live in a singleton contract are keyed by their pool id, all other AMMs by their address
+CREATE TABLE IF NOT EXISTS amms (
+    id TEXT PRIMARY KEY,
+    kind TEXT NOT NULL,
+    address TEXT NOT NULL,
+    data JSONB NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS amms_kind_idx ON amms (kind);
@@ -0,0 +1,125 @@
+use std::sync::Arc;
+
+use ethers::{
+    contract::Multicall,
+    providers::Middleware,
+    types::{BlockNumber, Bytes, TransactionRequest, U256},
+};
+
+use crate::{amm::uniswap_v3::IErc20, errors::AMMError};
+
+use super::{decode_global_state, AlgebraPool, IAlgebraPool, GLOBAL_STATE_SELECTOR};
+
+pub async fn get_algebra_pool_data_batch_request<M: Middleware>(
+    pool: &mut AlgebraPool,
+    block_number: Option<u64>,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    //The pool data and the global state are read at the same block
+    let block_number = match block_number {
+        Some(block_number) => block_number,
+        None => middleware
+            .get_block_number()
+            .await
+            .map_err(AMMError::MiddlewareError)?
+            .as_u64(),
+    };
+
+    let algebra_pool = IAlgebraPool::new(pool.address, middleware.clone());
+    let mut multicall = Multicall::new(middleware.clone(), None)
+        .await?
+        .block(BlockNumber::from(block_number));
+
+    multicall.add_call(algebra_pool.token_0(), false);
+    multicall.add_call(algebra_pool.token_1(), false);
+    multicall.add_call(algebra_pool.tick_spacing(), false);
+
+    let results = multicall.call_raw().await?;
+    let mut results = results.into_iter().map(|result| result.ok());
+
+    pool.token_a = results
+        .next()
+        .flatten()
+        .and_then(|token| token.into_address())
+        .ok_or(AMMError::BatchRequestError(pool.address))?;
+    pool.token_b = results
+        .next()
+        .flatten()
+        .and_then(|token| token.into_address())
+        .ok_or(AMMError::BatchRequestError(pool.address))?;
+    pool.tick_spacing = results
+        .next()
+        .flatten()
+        .and_then(|tick_spacing| tick_spacing.into_int())
+        .ok_or(AMMError::BatchRequestError(pool.address))?
+        .low_u32() as i32;
+
+    multicall.clear_calls();
+    for token in [pool.token_a, pool.token_b] {
+        multicall.add_call(IErc20::new(token, middleware.clone()).decimals(), false);
+    }
+
+    let decimals = multicall
+        .call_raw()
+        .await?
+        .into_iter()
+        .map(|result| {
+            result
+                .ok()
+                .and_then(|decimals| decimals.into_uint())
+                .map(|decimals| decimals.as_u32() as u8)
+        })
+        .collect::<Option<Vec<u8>>>()
+        .ok_or(AMMError::BatchRequestError(pool.address))?;
+
+    pool.token_a_decimals = decimals[0];
+    pool.token_b_decimals = decimals[1];
+
+    sync_algebra_pool_batch_request(pool, Some(block_number), middleware).await
+}
+
+//Syncs the global state, including the current dynamic fee, and the active liquidity
+pub async fn sync_algebra_pool_batch_request<M: Middleware>(
+    pool: &mut AlgebraPool,
+    block_number: Option<u64>,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    let block_number = match block_number {
+        Some(block_number) => block_number,
+        None => middleware
+            .get_block_number()
+            .await
+            .map_err(AMMError::MiddlewareError)?
+            .as_u64(),
+    };
+
+    //The global state is decoded from the raw return data since its layout differs between Algebra versions
+    let global_state_call = TransactionRequest::new()
+        .to(pool.address)
+        .data(Bytes::from(GLOBAL_STATE_SELECTOR.to_vec()));
+
+    let global_state = middleware
+        .call(&global_state_call.into(), Some(block_number.into()))
+        .await
+        .map_err(AMMError::MiddlewareError)?;
+
+    let words = global_state
+        .chunks(32)
+        .map(U256::from_big_endian)
+        .collect::<Vec<U256>>();
+
+    (
+        pool.sqrt_price,
+        pool.tick,
+        pool.fee_zero_for_one,
+        pool.fee_one_for_zero,
+    ) = decode_global_state(&words).ok_or(AMMError::SyncError(pool.address))?;
+
+    pool.liquidity = IAlgebraPool::new(pool.address, middleware)
+        .liquidity()
+        .block(block_number)
+        .call()
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,221 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use ethers::{
+    abi::RawLog,
+    prelude::{abigen, EthEvent},
+    providers::Middleware,
+    types::{Filter, Log, H160, H256, U64},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    amm::{
+        factory::{AutomatedMarketMakerFactory, TASK_LIMIT},
+        uniswap_v4::process_logs_from_handles,
+        AutomatedMarketMaker, AMM,
+    },
+    errors::AMMError,
+    middleware::logs::{get_logs_with_adaptive_range, MAX_LOG_RANGE_SPLITS},
+};
+
+use super::{batch_request, AlgebraPool, IAlgebraPool, BURN_EVENT_SIGNATURE, MINT_EVENT_SIGNATURE};
+
+abigen!(
+    IAlgebraFactory,
+    r#"[
+        function poolByPair(address tokenA, address tokenB) external view returns (address pool)
+        event Pool(address indexed token0, address indexed token1, address pool)
+    ]"#;
+);
+
+pub const POOL_EVENT_SIGNATURE: H256 = H256([
+    145, 204, 170, 122, 39, 129, 48, 182, 81, 104, 195, 160, 200, 211, 188, 174, 132, 207, 94, 67,
+    112, 67, 66, 189, 62, 192, 181, 158, 89, 192, 54, 219,
+]);
+
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct AlgebraFactory {
+    pub address: H160,
+    pub creation_block: u64,
+}
+
+#[async_trait]
+impl AutomatedMarketMakerFactory for AlgebraFactory {
+    fn address(&self) -> H160 {
+        self.address
+    }
+
+    fn creation_block(&self) -> u64 {
+        self.creation_block
+    }
+
+    fn amm_created_event_signature(&self) -> H256 {
+        POOL_EVENT_SIGNATURE
+    }
+
+    async fn new_amm_from_log<M: 'static + Middleware>(
+        &self,
+        log: Log,
+        middleware: Arc<M>,
+    ) -> Result<AMM, AMMError<M>> {
+        if let Some(block_number) = log.block_number {
+            let pool_event = PoolFilter::decode_log(&RawLog::from(log))?;
+
+            Ok(AMM::AlgebraPool(
+                AlgebraPool::new_from_address(pool_event.pool, block_number.as_u64(), middleware)
+                    .await?,
+            ))
+        } else {
+            return Err(AMMError::BlockNumberNotFound);
+        }
+    }
+
+    fn new_empty_amm_from_log(&self, log: Log) -> Result<AMM, ethers::abi::Error> {
+        let pool_event = PoolFilter::decode_log(&RawLog::from(log))?;
+
+        Ok(AMM::AlgebraPool(AlgebraPool {
+            address: pool_event.pool,
+            token_a: pool_event.token_0,
+            token_b: pool_event.token_1,
+            ..Default::default()
+        }))
+    }
+
+    async fn get_all_amms<M: 'static + Middleware>(
+        &self,
+        to_block: Option<u64>,
+        middleware: Arc<M>,
+        step: u64,
+    ) -> Result<Vec<AMM>, AMMError<M>> {
+        if let Some(block) = to_block {
+            self.get_all_pools_from_logs(block, step, middleware).await
+        } else {
+            return Err(AMMError::BlockNumberNotFound);
+        }
+    }
+
+    async fn populate_amm_data<M: Middleware>(
+        &self,
+        amms: &mut [AMM],
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        for amm in amms {
+            if let AMM::AlgebraPool(pool) = amm {
+                batch_request::get_algebra_pool_data_batch_request(
+                    pool,
+                    block_number,
+                    middleware.clone(),
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl AlgebraFactory {
+    pub fn new(address: H160, creation_block: u64) -> AlgebraFactory {
+        AlgebraFactory {
+            address,
+            creation_block,
+        }
+    }
+
+    //Gets all pool events from the factory along with the mint and burn logs of the pools to build the tick data of each pool
+    pub async fn get_all_pools_from_logs<M: 'static + Middleware>(
+        self,
+        to_block: u64,
+        step: u64,
+        middleware: Arc<M>,
+    ) -> Result<Vec<AMM>, AMMError<M>> {
+        if step == 0 {
+            return Err(AMMError::ZeroBatchSize("step"));
+        }
+
+        let mut from_block = self.creation_block;
+        let mut aggregated_amms: HashMap<H160, AMM> = HashMap::new();
+        let mut ordered_logs: BTreeMap<U64, Vec<Log>> = BTreeMap::new();
+
+        tracing::info!(from_block, to_block, step, "getting all pools from logs");
+
+        let mut handles = vec![];
+
+        let mut tasks = 0;
+        while from_block < to_block {
+            let middleware = middleware.clone();
+
+            let mut target_block = from_block + step - 1;
+            if target_block > to_block {
+                target_block = to_block;
+            }
+
+            handles.push(tokio::spawn(async move {
+                let logs = get_logs_with_adaptive_range(
+                    middleware.as_ref(),
+                    &Filter::new().topic0(vec![
+                        POOL_EVENT_SIGNATURE,
+                        BURN_EVENT_SIGNATURE,
+                        MINT_EVENT_SIGNATURE,
+                    ]),
+                    from_block,
+                    target_block,
+                    MAX_LOG_RANGE_SPLITS,
+                )
+                .await
+                .map_err(AMMError::MiddlewareError)?;
+
+                Ok::<Vec<Log>, AMMError<M>>(logs)
+            }));
+
+            from_block += step;
+
+            tasks += 1;
+            //Here we are limiting the number of green threads that can be spun up to not have the node time out
+            if tasks == TASK_LIMIT {
+                process_logs_from_handles(handles, &mut ordered_logs).await?;
+                handles = vec![];
+                tasks = 0;
+            }
+        }
+
+        process_logs_from_handles(handles, &mut ordered_logs).await?;
+
+        for (_, log_group) in ordered_logs {
+            for log in log_group {
+                let event_signature = log.topics[0];
+
+                //Pool events come from the factory, mint and burn events come from the pools
+                if event_signature == POOL_EVENT_SIGNATURE {
+                    if log.address == self.address {
+                        let mut new_pool = self.new_empty_amm_from_log(log)?;
+
+                        if let AMM::AlgebraPool(ref mut pool) = new_pool {
+                            pool.tick_spacing = IAlgebraPool::new(pool.address, middleware.clone())
+                                .tick_spacing()
+                                .call()
+                                .await?;
+                        }
+
+                        aggregated_amms.insert(new_pool.address(), new_pool);
+                    }
+                } else if event_signature == BURN_EVENT_SIGNATURE {
+                    if let Some(AMM::AlgebraPool(pool)) = aggregated_amms.get_mut(&log.address) {
+                        pool.sync_from_burn_log(log)?;
+                    }
+                } else if event_signature == MINT_EVENT_SIGNATURE {
+                    if let Some(AMM::AlgebraPool(pool)) = aggregated_amms.get_mut(&log.address) {
+                        pool.sync_from_mint_log(log)?;
+                    }
+                }
+            }
+        }
+
+        Ok(aggregated_amms.into_values().collect::<Vec<AMM>>())
+    }
+}
@@ -0,0 +1,665 @@
+pub mod batch_request;
+pub mod factory;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use ethers::{
+    abi::RawLog,
+    prelude::{abigen, AbiError, EthEvent},
+    providers::Middleware,
+    types::{BlockNumber, Filter, Log, H160, H256, I256, U256, U64},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    amm::{price::Price, AutomatedMarketMaker},
+    errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
+};
+
+use super::{
+    factory::TASK_LIMIT,
+    uniswap_v3::{
+        BurnFilter, Info, MintFilter, StepComputations, SwapFilter, BURN_EVENT_SIGNATURE,
+        MAX_SQRT_RATIO, MAX_TICK, MINT_EVENT_SIGNATURE, MIN_SQRT_RATIO, MIN_TICK,
+        POPULATE_TICK_DATA_STEP, SWAP_EVENT_SIGNATURE,
+    },
+    uniswap_v4::process_logs_from_handles,
+};
+
+abigen!(
+    IAlgebraPool,
+    r#"[
+        function token0() external view returns (address)
+        function token1() external view returns (address)
+        function liquidity() external view returns (uint128)
+        function tickSpacing() external view returns (int24)
+    ]"#;
+);
+
+//Algebra pools emit Swap, Mint and Burn with the same signatures as Uniswap V3 pools
+
+//Emitted by pools before Algebra 1.9 when the dynamic fee changes
+pub const FEE_EVENT_SIGNATURE: H256 = H256([
+    89, 139, 159, 4, 60, 129, 58, 166, 190, 52, 38, 202, 96, 209, 198, 93, 23, 37, 99, 18, 137, 11,
+    229, 17, 141, 171, 85, 176, 119, 94, 190, 42,
+]);
+
+//Emitted by Algebra 1.9 pools, which charge a separate fee in each direction
+pub const DIRECTIONAL_FEE_EVENT_SIGNATURE: H256 = H256([
+    138, 137, 222, 112, 133, 107, 204, 236, 9, 102, 97, 56, 143, 48, 91, 154, 117, 245, 246, 92,
+    176, 216, 160, 225, 232, 3, 195, 157, 171, 237, 181, 127,
+]);
+
+//Selector of `globalState()`, the layout of the returned state depends on the Algebra version
+pub const GLOBAL_STATE_SELECTOR: [u8; 4] = [231, 108, 1, 228];
+
+//Algebra pools have a single pool per pair and their fee is adjusted by the pool based on volatility.
+//Fees are in hundredths of a bip like Uniswap V3 fees.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AlgebraPool {
+    pub address: H160,
+    pub token_a: H160,
+    pub token_a_decimals: u8,
+    pub token_b: H160,
+    pub token_b_decimals: u8,
+    pub liquidity: u128,
+    pub sqrt_price: U256,
+    pub fee_zero_for_one: u32,
+    pub fee_one_for_zero: u32,
+    pub tick: i32,
+    pub tick_spacing: i32,
+    pub tick_bitmap: HashMap<i16, U256>,
+    pub ticks: HashMap<i32, Info>,
+}
+
+#[async_trait]
+impl AutomatedMarketMaker for AlgebraPool {
+    fn address(&self) -> H160 {
+        self.address
+    }
+
+    async fn sync<M: Middleware>(&mut self, middleware: Arc<M>) -> Result<(), AMMError<M>> {
+        batch_request::sync_algebra_pool_batch_request(self, None, middleware).await
+    }
+
+    fn sync_on_event_signatures(&self) -> Vec<H256> {
+        vec![
+            SWAP_EVENT_SIGNATURE,
+            MINT_EVENT_SIGNATURE,
+            BURN_EVENT_SIGNATURE,
+            FEE_EVENT_SIGNATURE,
+            DIRECTIONAL_FEE_EVENT_SIGNATURE,
+        ]
+    }
+
+    fn sync_from_log(&mut self, log: Log) -> Result<(), EventLogError> {
+        let event_signature = log.topics[0];
+
+        if event_signature == SWAP_EVENT_SIGNATURE {
+            self.sync_from_swap_log(log)?;
+        } else if event_signature == MINT_EVENT_SIGNATURE {
+            self.sync_from_mint_log(log)?;
+        } else if event_signature == BURN_EVENT_SIGNATURE {
+            self.sync_from_burn_log(log)?;
+        } else if event_signature == FEE_EVENT_SIGNATURE
+            || event_signature == DIRECTIONAL_FEE_EVENT_SIGNATURE
+        {
+            self.sync_from_fee_log(log)?;
+        } else {
+            Err(EventLogError::InvalidEventSignature)?
+        }
+
+        Ok(())
+    }
+
+    fn tokens(&self) -> Vec<H160> {
+        vec![self.token_a, self.token_b]
+    }
+
+    fn calculate_price(&self, base_token: H160) -> Result<Price, ArithmeticError> {
+        self.check_liquidity()?;
+
+        let price = Price::from_sqrt_price_x96(
+            self.sqrt_price,
+            self.token_a_decimals,
+            self.token_b_decimals,
+        )?;
+
+        if base_token == self.token_a {
+            Ok(price)
+        } else {
+            price.invert()
+        }
+    }
+
+    // NOTE: This function will not populate the tick_bitmap and ticks, if you want to populate those, you must call populate_tick_data on an initialized pool
+    async fn populate_data<M: Middleware>(
+        &mut self,
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        batch_request::get_algebra_pool_data_batch_request(self, block_number, middleware).await
+    }
+
+    //The fee is recomputed by the pool on the first swap of a block, so simulations use the fee of the last synced state
+    fn simulate_swap(&self, token_in: H160, amount_in: U256) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        self.check_liquidity()?;
+
+        let (amount_out, _) = self.swap(token_in == self.token_a, amount_in)?;
+
+        tracing::trace!(?amount_out);
+
+        Ok(amount_out)
+    }
+
+    fn simulate_swap_mut(
+        &mut self,
+        token_in: H160,
+        amount_in: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        self.check_liquidity()?;
+
+        let (amount_out, (sqrt_price, tick, liquidity)) =
+            self.swap(token_in == self.token_a, amount_in)?;
+
+        //Update the pool state
+        self.sqrt_price = sqrt_price;
+        self.tick = tick;
+        self.liquidity = liquidity;
+
+        tracing::trace!(?amount_out);
+
+        Ok(amount_out)
+    }
+
+    fn swap_fee(&self, token_in: H160) -> f64 {
+        self.fee(token_in == self.token_a) as f64 / 1e6
+    }
+
+    fn token_decimals(&self) -> Vec<u8> {
+        vec![self.token_a_decimals, self.token_b_decimals]
+    }
+
+    fn get_token_out(&self, token_in: H160) -> H160 {
+        if self.token_a == token_in {
+            self.token_b
+        } else {
+            self.token_a
+        }
+    }
+}
+
+impl AlgebraPool {
+    //Creates a new instance of the pool from the pool address and replays the liquidity of the pool from its creation block
+    pub async fn new_from_address<M: 'static + Middleware>(
+        pool_address: H160,
+        creation_block: u64,
+        middleware: Arc<M>,
+    ) -> Result<Self, AMMError<M>> {
+        let mut pool = AlgebraPool {
+            address: pool_address,
+            ..Default::default()
+        };
+
+        //Tick spacing is needed to update the tick bitmap when replaying mint and burn logs
+        pool.tick_spacing = IAlgebraPool::new(pool_address, middleware.clone())
+            .tick_spacing()
+            .call()
+            .await?;
+
+        let synced_block = pool
+            .populate_tick_data(creation_block, middleware.clone())
+            .await?;
+
+        pool.populate_data(Some(synced_block), middleware).await?;
+
+        if !pool.data_is_populated() {
+            return Err(AMMError::PoolDataError);
+        }
+
+        Ok(pool)
+    }
+
+    pub fn data_is_populated(&self) -> bool {
+        !(self.token_a.is_zero() || self.token_b.is_zero())
+    }
+
+    pub fn fee(&self, zero_for_one: bool) -> u32 {
+        if zero_for_one {
+            self.fee_zero_for_one
+        } else {
+            self.fee_one_for_zero
+        }
+    }
+
+    pub async fn populate_tick_data<M: 'static + Middleware>(
+        &mut self,
+        mut from_block: u64,
+        middleware: Arc<M>,
+    ) -> Result<u64, AMMError<M>> {
+        let current_block = middleware
+            .get_block_number()
+            .await
+            .map_err(AMMError::MiddlewareError)?
+            .as_u64();
+        let mut ordered_logs: BTreeMap<U64, Vec<Log>> = BTreeMap::new();
+
+        let pool_address = self.address;
+
+        let mut handles = vec![];
+        let mut tasks = 0;
+
+        while from_block < current_block {
+            let middleware = middleware.clone();
+
+            let mut target_block = from_block + POPULATE_TICK_DATA_STEP - 1;
+            if target_block > current_block {
+                target_block = current_block;
+            }
+
+            handles.push(tokio::spawn(async move {
+                let logs = middleware
+                    .get_logs(
+                        &Filter::new()
+                            .topic0(vec![BURN_EVENT_SIGNATURE, MINT_EVENT_SIGNATURE])
+                            .address(pool_address)
+                            .from_block(BlockNumber::Number(U64([from_block])))
+                            .to_block(BlockNumber::Number(U64([target_block]))),
+                    )
+                    .await
+                    .map_err(AMMError::MiddlewareError)?;
+
+                Ok::<Vec<Log>, AMMError<M>>(logs)
+            }));
+
+            from_block += POPULATE_TICK_DATA_STEP;
+            tasks += 1;
+            //Here we are limiting the number of green threads that can be spun up to not have the node time out
+            if tasks == TASK_LIMIT {
+                process_logs_from_handles(handles, &mut ordered_logs).await?;
+                handles = vec![];
+                tasks = 0;
+            }
+        }
+
+        process_logs_from_handles(handles, &mut ordered_logs).await?;
+
+        for (_, log_group) in ordered_logs {
+            for log in log_group {
+                self.sync_from_log(log)?;
+            }
+        }
+
+        Ok(current_block)
+    }
+
+    pub fn sync_from_swap_log(&mut self, log: Log) -> Result<(), AbiError> {
+        let swap_event = SwapFilter::decode_log(&RawLog::from(log))?;
+
+        self.sqrt_price = swap_event.sqrt_price_x96;
+        self.liquidity = swap_event.liquidity;
+        self.tick = swap_event.tick;
+
+        Ok(())
+    }
+
+    pub fn sync_from_mint_log(&mut self, log: Log) -> Result<(), AbiError> {
+        let mint_event = MintFilter::decode_log(&RawLog::from(log))?;
+
+        self.modify_position(
+            mint_event.tick_lower,
+            mint_event.tick_upper,
+            mint_event.amount as i128,
+        );
+
+        Ok(())
+    }
+
+    pub fn sync_from_burn_log(&mut self, log: Log) -> Result<(), AbiError> {
+        let burn_event = BurnFilter::decode_log(&RawLog::from(log))?;
+
+        self.modify_position(
+            burn_event.tick_lower,
+            burn_event.tick_upper,
+            -(burn_event.amount as i128),
+        );
+
+        Ok(())
+    }
+
+    //The fee events only hold uint16 fees, a single fee applies to both directions
+    pub fn sync_from_fee_log(&mut self, log: Log) -> Result<(), EventLogError> {
+        let fees = log
+            .data
+            .chunks(32)
+            .map(|word| U256::from_big_endian(word).low_u32())
+            .collect::<Vec<u32>>();
+
+        match fees[..] {
+            [fee] => {
+                self.fee_zero_for_one = fee;
+                self.fee_one_for_zero = fee;
+            }
+            [fee_zero_for_one, fee_one_for_zero] => {
+                self.fee_zero_for_one = fee_zero_for_one;
+                self.fee_one_for_zero = fee_one_for_zero;
+            }
+            _ => return Err(EventLogError::EthABIError(ethers::abi::Error::InvalidData)),
+        }
+
+        Ok(())
+    }
+
+    pub fn modify_position(&mut self, tick_lower: i32, tick_upper: i32, liquidity_delta: i128) {
+        if liquidity_delta == 0 {
+            return;
+        }
+
+        let flipped_lower = self.update_tick(tick_lower, liquidity_delta, false);
+        let flipped_upper = self.update_tick(tick_upper, liquidity_delta, true);
+
+        if flipped_lower {
+            self.flip_tick(tick_lower);
+        }
+        if flipped_upper {
+            self.flip_tick(tick_upper);
+        }
+
+        if liquidity_delta < 0 {
+            if flipped_lower {
+                self.ticks.remove(&tick_lower);
+            }
+            if flipped_upper {
+                self.ticks.remove(&tick_upper);
+            }
+        }
+
+        //The position is only active if the current tick is within [tick_lower, tick_upper)
+        if self.tick >= tick_lower && self.tick < tick_upper {
+            self.liquidity = self.liquidity.saturating_add_signed(liquidity_delta);
+        }
+    }
+
+    //Returns true if the tick was flipped from initialized to uninitialized or vice versa
+    fn update_tick(&mut self, tick: i32, liquidity_delta: i128, upper: bool) -> bool {
+        let info = self.ticks.entry(tick).or_default();
+
+        let liquidity_gross_before = info.liquidity_gross;
+        let liquidity_gross_after = liquidity_gross_before.saturating_add_signed(liquidity_delta);
+
+        info.liquidity_gross = liquidity_gross_after;
+        info.initialized = liquidity_gross_after != 0;
+        info.liquidity_net = if upper {
+            info.liquidity_net - liquidity_delta
+        } else {
+            info.liquidity_net + liquidity_delta
+        };
+
+        (liquidity_gross_after == 0) != (liquidity_gross_before == 0)
+    }
+
+    fn flip_tick(&mut self, tick: i32) {
+        let (word_pos, bit_pos) = uniswap_v3_math::tick_bitmap::position(tick / self.tick_spacing);
+        let mask = U256::one() << bit_pos;
+
+        *self.tick_bitmap.entry(word_pos).or_default() ^= mask;
+    }
+
+    //A pool that is not initialized has no price, a pool without active liquidity or initialized ticks has no liquidity to swap through
+    fn check_liquidity(&self) -> Result<(), ArithmeticError> {
+        if self.sqrt_price.is_zero() || (self.liquidity == 0 && self.ticks.is_empty()) {
+            return Err(ArithmeticError::ZeroLiquidity(self.address));
+        }
+
+        Ok(())
+    }
+
+    //Runs the concentrated liquidity swap loop with the fee of the swap direction and returns the amount out along with the resulting (sqrt_price, tick, liquidity)
+    fn swap(
+        &self,
+        zero_for_one: bool,
+        amount_in: U256,
+    ) -> Result<(U256, (U256, i32, u128)), SwapSimulationError> {
+        let mut sqrt_price_x_96 = self.sqrt_price;
+        let mut tick = self.tick;
+        let mut liquidity = self.liquidity;
+
+        if amount_in.is_zero() {
+            return Ok((U256::zero(), (sqrt_price_x_96, tick, liquidity)));
+        }
+
+        let fee = self.fee(zero_for_one);
+
+        //Set sqrt_price_limit_x_96 to the max or min sqrt price in the pool depending on zero_for_one
+        let sqrt_price_limit_x_96 = if zero_for_one {
+            MIN_SQRT_RATIO + 1
+        } else {
+            MAX_SQRT_RATIO - 1
+        };
+
+        let mut amount_specified_remaining = I256::from_raw(amount_in);
+        let mut amount_calculated = I256::zero();
+
+        while amount_specified_remaining != I256::zero() && sqrt_price_x_96 != sqrt_price_limit_x_96
+        {
+            let mut step = StepComputations {
+                sqrt_price_start_x_96: sqrt_price_x_96,
+                ..Default::default()
+            };
+
+            (step.tick_next, step.initialized) =
+                uniswap_v3_math::tick_bitmap::next_initialized_tick_within_one_word(
+                    &self.tick_bitmap,
+                    tick,
+                    self.tick_spacing,
+                    zero_for_one,
+                )?;
+
+            // ensure that we do not overshoot the min/max tick, as the tick bitmap is not aware of these bounds
+            step.tick_next = step.tick_next.clamp(MIN_TICK, MAX_TICK);
+
+            step.sqrt_price_next_x96 =
+                uniswap_v3_math::tick_math::get_sqrt_ratio_at_tick(step.tick_next)?;
+
+            let swap_target_sqrt_ratio = if zero_for_one {
+                step.sqrt_price_next_x96.max(sqrt_price_limit_x_96)
+            } else {
+                step.sqrt_price_next_x96.min(sqrt_price_limit_x_96)
+            };
+
+            (
+                sqrt_price_x_96,
+                step.amount_in,
+                step.amount_out,
+                step.fee_amount,
+            ) = uniswap_v3_math::swap_math::compute_swap_step(
+                sqrt_price_x_96,
+                swap_target_sqrt_ratio,
+                liquidity,
+                amount_specified_remaining,
+                fee,
+            )?;
+
+            amount_specified_remaining = amount_specified_remaining
+                .overflowing_sub(I256::from_raw(
+                    step.amount_in.overflowing_add(step.fee_amount).0,
+                ))
+                .0;
+
+            amount_calculated -= I256::from_raw(step.amount_out);
+
+            //If the price moved all the way to the next price, cross the tick and apply the liquidity change
+            if sqrt_price_x_96 == step.sqrt_price_next_x96 {
+                if step.initialized {
+                    let mut liquidity_net = self
+                        .ticks
+                        .get(&step.tick_next)
+                        .map(|info| info.liquidity_net)
+                        .unwrap_or_default();
+
+                    if zero_for_one {
+                        liquidity_net = -liquidity_net;
+                    }
+
+                    liquidity = liquidity
+                        .checked_add_signed(liquidity_net)
+                        .ok_or(SwapSimulationError::LiquidityUnderflow)?;
+                }
+
+                tick = if zero_for_one {
+                    step.tick_next.wrapping_sub(1)
+                } else {
+                    step.tick_next
+                };
+            } else if sqrt_price_x_96 != step.sqrt_price_start_x_96 {
+                tick = uniswap_v3_math::tick_math::get_tick_at_sqrt_ratio(sqrt_price_x_96)?;
+            }
+        }
+
+        Ok((
+            (-amount_calculated).into_raw(),
+            (sqrt_price_x_96, tick, liquidity),
+        ))
+    }
+}
+
+//Decodes the (sqrt price, tick, fee zero for one, fee one for zero) from the words returned by `globalState()`.
+//Algebra 1.9 pools return 8 words with a fee for each direction, earlier pools return 7 words with a single fee.
+pub fn decode_global_state(words: &[U256]) -> Option<(U256, i32, u32, u32)> {
+    let sqrt_price = *words.first()?;
+    let tick = I256::from_raw(*words.get(1)?).as_i32();
+
+    match words.len() {
+        7 => {
+            let fee = words[2].low_u32();
+            Some((sqrt_price, tick, fee, fee))
+        }
+        8 => Some((sqrt_price, tick, words[2].low_u32(), words[3].low_u32())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use ethers::{
+        abi::{self, Token},
+        types::{Log, H160, I256, U256},
+    };
+
+    use crate::amm::AutomatedMarketMaker;
+
+    use super::{
+        decode_global_state, AlgebraPool, DIRECTIONAL_FEE_EVENT_SIGNATURE, FEE_EVENT_SIGNATURE,
+    };
+
+    fn algebra_pool() -> AlgebraPool {
+        AlgebraPool {
+            address: H160::repeat_byte(0x01),
+            token_a: H160::repeat_byte(0x02),
+            token_a_decimals: 18,
+            token_b: H160::repeat_byte(0x03),
+            token_b_decimals: 18,
+            liquidity: 0,
+            //sqrt(1) * 2^96
+            sqrt_price: U256::one() << 96,
+            fee_zero_for_one: 500,
+            fee_one_for_zero: 500,
+            tick: 0,
+            tick_spacing: 60,
+            tick_bitmap: HashMap::new(),
+            ticks: HashMap::new(),
+        }
+    }
+
+    fn fee_log(fees: &[u64]) -> Log {
+        let topic = if fees.len() == 1 {
+            FEE_EVENT_SIGNATURE
+        } else {
+            DIRECTIONAL_FEE_EVENT_SIGNATURE
+        };
+
+        Log {
+            topics: vec![topic],
+            data: abi::encode(
+                &fees
+                    .iter()
+                    .map(|fee| Token::Uint(U256::from(*fee)))
+                    .collect::<Vec<Token>>(),
+            )
+            .into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_dynamic_fee() {
+        let mut pool = algebra_pool();
+        pool.modify_position(-600, 600, 10_u128.pow(24) as i128);
+
+        let amount_in = U256::exp10(18);
+        let amount_out_low_fee = pool.simulate_swap(pool.token_a, amount_in).unwrap();
+
+        //The output is the input minus the 0.05% fee and a small amount of slippage
+        assert!(amount_out_low_fee < U256::from(9995) * U256::exp10(14));
+        assert!(amount_out_low_fee > U256::from(9994) * U256::exp10(14));
+
+        pool.sync_from_log(fee_log(&[3000])).unwrap();
+        assert_eq!(pool.fee(true), 3000);
+        assert_eq!(pool.fee(false), 3000);
+
+        let amount_out_high_fee = pool.simulate_swap(pool.token_a, amount_in).unwrap();
+        assert!(amount_out_high_fee < amount_out_low_fee);
+
+        //Directional fees only apply to their own swap direction
+        pool.sync_from_log(fee_log(&[100, 3000])).unwrap();
+        assert_eq!(pool.fee(true), 100);
+        assert_eq!(pool.fee(false), 3000);
+        assert!(pool.simulate_swap(pool.token_a, amount_in).unwrap() > amount_out_low_fee);
+        assert!(pool.simulate_swap(pool.token_b, amount_in).unwrap() < amount_out_low_fee);
+    }
+
+    #[test]
+    fn test_decode_global_state() {
+        let sqrt_price = U256::one() << 96;
+        let tick = I256::from(-10).into_raw();
+
+        let (_, decoded_tick, fee_zero_for_one, fee_one_for_zero) = decode_global_state(&[
+            sqrt_price,
+            tick,
+            U256::from(500),
+            U256::from(12),
+            U256::zero(),
+            U256::zero(),
+            U256::one(),
+        ])
+        .unwrap();
+        assert_eq!(decoded_tick, -10);
+        assert_eq!((fee_zero_for_one, fee_one_for_zero), (500, 500));
+
+        let (decoded_sqrt_price, _, fee_zero_for_one, fee_one_for_zero) = decode_global_state(&[
+            sqrt_price,
+            tick,
+            U256::from(100),
+            U256::from(3000),
+            U256::from(12),
+            U256::zero(),
+            U256::zero(),
+            U256::one(),
+        ])
+        .unwrap();
+        assert_eq!(decoded_sqrt_price, sqrt_price);
+        assert_eq!((fee_zero_for_one, fee_one_for_zero), (100, 3000));
+
+        assert!(decode_global_state(&[sqrt_price, tick]).is_none());
+    }
+}
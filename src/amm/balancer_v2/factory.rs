@@ -0,0 +1,276 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::{
+    abi::RawLog,
+    prelude::{abigen, EthEvent},
+    providers::Middleware,
+    types::{Log, H160, H256},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    amm::{
+        factory::{AutomatedMarketMakerFactory, Factory},
+        AutomatedMarketMaker, AMM,
+    },
+    errors::AMMError,
+};
+
+use super::{stable::BalancerStablePool, BalancerV2WeightedPool};
+
+abigen!(
+    IBalancerV2WeightedPoolFactory,
+    r#"[
+        event PoolCreated(address indexed pool)
+    ]"#;
+
+    IBalancerV2VaultRegistry,
+    r#"[
+        event PoolRegistered(bytes32 indexed poolId, address indexed poolAddress, uint8 specialization)
+    ]"#;
+);
+
+pub const WEIGHTED_POOL_CREATED_EVENT_SIGNATURE: H256 = H256([
+    131, 164, 143, 188, 252, 153, 19, 53, 49, 78, 116, 208, 73, 106, 171, 106, 25, 135, 233, 146,
+    221, 200, 93, 221, 188, 196, 214, 221, 110, 242, 233, 252,
+]);
+
+//Emitted by the Balancer V2 Vault when any pool is registered, regardless of the pool type
+pub const POOL_REGISTERED_EVENT_SIGNATURE: H256 = H256([
+    60, 19, 188, 48, 184, 232, 120, 197, 63, 210, 163, 107, 103, 148, 9, 192, 115, 175, 215, 89,
+    80, 190, 67, 216, 133, 135, 104, 233, 86, 251, 194, 14,
+]);
+
+// Pools with the general specialization are never weighted pools, composable stable pools are registered with it
+pub const GENERAL_POOL_SPECIALIZATION: u8 = 0;
+
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct BalancerV2Factory {
+    pub address: H160,
+    pub creation_block: u64,
+}
+
+#[async_trait]
+impl AutomatedMarketMakerFactory for BalancerV2Factory {
+    fn address(&self) -> H160 {
+        self.address
+    }
+
+    fn creation_block(&self) -> u64 {
+        self.creation_block
+    }
+
+    fn amm_created_event_signature(&self) -> H256 {
+        WEIGHTED_POOL_CREATED_EVENT_SIGNATURE
+    }
+
+    async fn new_amm_from_log<M: 'static + Middleware>(
+        &self,
+        log: Log,
+        middleware: Arc<M>,
+    ) -> Result<AMM, AMMError<M>> {
+        let pool_created_event = PoolCreatedFilter::decode_log(&RawLog::from(log))?;
+
+        Ok(AMM::BalancerV2WeightedPool(
+            BalancerV2WeightedPool::new_from_address(pool_created_event.pool, middleware).await?,
+        ))
+    }
+
+    fn new_empty_amm_from_log(&self, log: Log) -> Result<AMM, ethers::abi::Error> {
+        let pool_created_event = PoolCreatedFilter::decode_log(&RawLog::from(log))?;
+
+        Ok(AMM::BalancerV2WeightedPool(BalancerV2WeightedPool {
+            address: pool_created_event.pool,
+            ..Default::default()
+        }))
+    }
+
+    async fn get_all_amms<M: 'static + Middleware>(
+        &self,
+        to_block: Option<u64>,
+        middleware: Arc<M>,
+        step: u64,
+    ) -> Result<Vec<AMM>, AMMError<M>> {
+        if let Some(block) = to_block {
+            Factory::BalancerV2Factory(*self)
+                .get_all_pools_from_logs(self.creation_block, block, step, middleware)
+                .await
+        } else {
+            return Err(AMMError::BlockNumberNotFound);
+        }
+    }
+
+    async fn populate_amm_data<M: Middleware>(
+        &self,
+        amms: &mut [AMM],
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        for amm in amms {
+            if let AMM::BalancerV2WeightedPool(pool) = amm {
+                pool.populate_data(block_number, middleware.clone()).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl BalancerV2Factory {
+    pub fn new(address: H160, creation_block: u64) -> BalancerV2Factory {
+        BalancerV2Factory {
+            address,
+            creation_block,
+        }
+    }
+}
+
+//The Balancer V2 Vault registers every pool, so it can be used in place of the individual pool factories to find all weighted pools
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct BalancerV2Vault {
+    pub address: H160,
+    pub creation_block: u64,
+}
+
+#[async_trait]
+impl AutomatedMarketMakerFactory for BalancerV2Vault {
+    fn address(&self) -> H160 {
+        self.address
+    }
+
+    fn creation_block(&self) -> u64 {
+        self.creation_block
+    }
+
+    fn amm_created_event_signature(&self) -> H256 {
+        POOL_REGISTERED_EVENT_SIGNATURE
+    }
+
+    async fn new_amm_from_log<M: 'static + Middleware>(
+        &self,
+        log: Log,
+        middleware: Arc<M>,
+    ) -> Result<AMM, AMMError<M>> {
+        let pool_registered_event = PoolRegisteredFilter::decode_log(&RawLog::from(log))?;
+
+        if pool_registered_event.specialization == GENERAL_POOL_SPECIALIZATION {
+            return Ok(AMM::BalancerStablePool(
+                BalancerStablePool::new_from_address(
+                    pool_registered_event.pool_address,
+                    middleware,
+                )
+                .await?,
+            ));
+        }
+
+        Ok(AMM::BalancerV2WeightedPool(
+            BalancerV2WeightedPool::new_from_address(
+                pool_registered_event.pool_address,
+                middleware,
+            )
+            .await?,
+        ))
+    }
+
+    fn new_empty_amm_from_log(&self, log: Log) -> Result<AMM, ethers::abi::Error> {
+        let vault = log.address;
+        let pool_registered_event = PoolRegisteredFilter::decode_log(&RawLog::from(log))?;
+
+        if pool_registered_event.specialization == GENERAL_POOL_SPECIALIZATION {
+            return Ok(AMM::BalancerStablePool(BalancerStablePool {
+                address: pool_registered_event.pool_address,
+                pool_id: H256::from(pool_registered_event.pool_id),
+                vault,
+                ..Default::default()
+            }));
+        }
+
+        Ok(AMM::BalancerV2WeightedPool(BalancerV2WeightedPool {
+            address: pool_registered_event.pool_address,
+            pool_id: H256::from(pool_registered_event.pool_id),
+            vault,
+            ..Default::default()
+        }))
+    }
+
+    async fn get_all_amms<M: 'static + Middleware>(
+        &self,
+        to_block: Option<u64>,
+        middleware: Arc<M>,
+        step: u64,
+    ) -> Result<Vec<AMM>, AMMError<M>> {
+        if let Some(block) = to_block {
+            Factory::BalancerV2Vault(*self)
+                .get_all_pools_from_logs(self.creation_block, block, step, middleware)
+                .await
+        } else {
+            return Err(AMMError::BlockNumberNotFound);
+        }
+    }
+
+    async fn populate_amm_data<M: Middleware>(
+        &self,
+        amms: &mut [AMM],
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        for amm in amms.iter_mut() {
+            match amm {
+                AMM::BalancerV2WeightedPool(pool) => {
+                    if pool
+                        .populate_data(block_number, middleware.clone())
+                        .await
+                        .is_ok()
+                    {
+                        continue;
+                    }
+
+                    //Two token stable pools share the specialization of weighted pools, so they are retried as stable pools
+                    let mut stable_pool = BalancerStablePool {
+                        address: pool.address,
+                        pool_id: pool.pool_id,
+                        vault: pool.vault,
+                        ..Default::default()
+                    };
+
+                    if stable_pool
+                        .populate_data(block_number, middleware.clone())
+                        .await
+                        .is_ok()
+                    {
+                        *amm = AMM::BalancerStablePool(stable_pool);
+                    } else {
+                        //Pools of other types are left empty so that they are removed by remove_empty_amms
+                        tracing::debug!(?pool.address, "pool is not a weighted or stable pool, skipping");
+                        pool.tokens = vec![];
+                    }
+                }
+
+                AMM::BalancerStablePool(pool) => {
+                    if pool
+                        .populate_data(block_number, middleware.clone())
+                        .await
+                        .is_err()
+                    {
+                        tracing::debug!(?pool.address, "pool is not a stable pool, skipping");
+                        pool.tokens = vec![];
+                    }
+                }
+
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl BalancerV2Vault {
+    pub fn new(address: H160, creation_block: u64) -> BalancerV2Vault {
+        BalancerV2Vault {
+            address,
+            creation_block,
+        }
+    }
+}
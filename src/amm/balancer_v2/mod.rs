@@ -0,0 +1,577 @@
+pub mod factory;
+pub mod stable;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::{
+    abi::RawLog,
+    prelude::EthEvent,
+    providers::Middleware,
+    types::{BlockId, BlockNumber, Log, H160, H256, U256},
+};
+use num_bigfloat::BigFloat;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    amm::{price::Price, AutomatedMarketMaker},
+    errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
+};
+
+use ethers::prelude::abigen;
+
+abigen!(
+    IBalancerV2Vault,
+    r#"[
+        function getPoolTokens(bytes32 poolId) external view returns (address[] tokens, uint256[] balances, uint256 lastChangeBlock)
+        event Swap(bytes32 indexed poolId, address indexed tokenIn, address indexed tokenOut, uint256 amountIn, uint256 amountOut)
+        event PoolBalanceChanged(bytes32 indexed poolId, address indexed liquidityProvider, address[] tokens, int256[] deltas, uint256[] protocolFeeAmounts)
+    ]"#;
+
+    IBalancerV2WeightedPool,
+    r#"[
+        function getPoolId() external view returns (bytes32)
+        function getVault() external view returns (address)
+        function getNormalizedWeights() external view returns (uint256[])
+        function getSwapFeePercentage() external view returns (uint256)
+    ]"#;
+
+    IErc20,
+    r#"[
+        function decimals() external view returns (uint8)
+    ]"#;
+);
+
+pub const SWAP_EVENT_SIGNATURE: H256 = H256([
+    33, 112, 199, 65, 196, 21, 49, 174, 194, 14, 124, 16, 124, 36, 238, 207, 221, 21, 230, 156,
+    155, 176, 168, 221, 55, 177, 132, 11, 158, 11, 32, 123,
+]);
+
+pub const POOL_BALANCE_CHANGED_EVENT_SIGNATURE: H256 = H256([
+    229, 206, 36, 144, 135, 206, 4, 240, 90, 149, 113, 146, 67, 84, 0, 253, 151, 134, 141, 186, 14,
+    106, 75, 76, 4, 154, 191, 138, 248, 13, 174, 120,
+]);
+
+// Fixed point one, Balancer represents all weights, fees and upscaled balances with 18 decimals
+pub const ONE: U256 = U256([1000000000000000000, 0, 0, 0]);
+// Swaps can not exceed 30% of the balance of the token in
+pub const MAX_IN_RATIO: U256 = U256([300000000000000000, 0, 0, 0]);
+
+//Alias kept for the name the weighted pool was first introduced under
+pub type BalancerV2Pool = BalancerV2WeightedPool;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BalancerV2WeightedPool {
+    pub address: H160,
+    pub pool_id: H256,
+    pub vault: H160,
+    pub tokens: Vec<H160>,
+    pub token_decimals: Vec<u8>,
+    pub balances: Vec<U256>,
+    pub weights: Vec<U256>, // normalized weights, scaled by 1e18
+    pub swap_fee: U256,     // swap fee percentage, scaled by 1e18
+}
+
+#[async_trait]
+impl AutomatedMarketMaker for BalancerV2WeightedPool {
+    fn address(&self) -> H160 {
+        self.address
+    }
+
+    async fn sync<M: Middleware>(&mut self, middleware: Arc<M>) -> Result<(), AMMError<M>> {
+        let vault = IBalancerV2Vault::new(self.vault, middleware.clone());
+        let (_, balances, _) = vault.get_pool_tokens(self.pool_id.0).call().await?;
+        self.balances = balances;
+
+        let pool = IBalancerV2WeightedPool::new(self.address, middleware);
+        self.swap_fee = pool.get_swap_fee_percentage().call().await?;
+
+        Ok(())
+    }
+
+    //All Balancer V2 state changes are emitted from the Vault, the pool id is used to route the log to the pool
+    fn sync_on_event_signatures(&self) -> Vec<H256> {
+        vec![SWAP_EVENT_SIGNATURE, POOL_BALANCE_CHANGED_EVENT_SIGNATURE]
+    }
+
+    fn sync_from_log(&mut self, log: Log) -> Result<(), EventLogError> {
+        sync_balances_from_vault_log(self.pool_id, &self.tokens, &mut self.balances, log)
+    }
+
+    fn tokens(&self) -> Vec<H160> {
+        self.tokens.clone()
+    }
+
+    //Calculates the spot price of the base token denominated in the token returned by get_token_out(base_token)
+    fn calculate_price(&self, base_token: H160) -> Result<Price, ArithmeticError> {
+        self.check_liquidity()?;
+
+        let quote_token = self.get_token_out(base_token);
+
+        let base_idx = self
+            .token_index(base_token)
+            .ok_or(ArithmeticError::TokenNotInPool(base_token))?;
+        let quote_idx = self
+            .token_index(quote_token)
+            .ok_or(ArithmeticError::TokenNotInPool(quote_token))?;
+
+        let base_balance = u256_to_big_float(self.balances[base_idx]).div(&BigFloat::from(
+            10_u128.pow(self.token_decimals[base_idx] as u32),
+        ));
+        let quote_balance = u256_to_big_float(self.balances[quote_idx]).div(&BigFloat::from(
+            10_u128.pow(self.token_decimals[quote_idx] as u32),
+        ));
+
+        let base_weight = u256_to_big_float(self.weights[base_idx]);
+        let quote_weight = u256_to_big_float(self.weights[quote_idx]);
+
+        if quote_weight.is_zero() {
+            return Err(ArithmeticError::YIsZero);
+        }
+
+        // spot price = (balance_quote / weight_quote) / (balance_base / weight_base)
+        Price::from_f64(
+            quote_balance
+                .div(&quote_weight)
+                .div(&base_balance.div(&base_weight))
+                .to_f64(),
+        )
+    }
+
+    async fn populate_data<M: Middleware>(
+        &mut self,
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        let block: BlockId = block_number
+            .map(BlockId::from)
+            .unwrap_or(BlockId::Number(BlockNumber::Latest));
+
+        let pool = IBalancerV2WeightedPool::new(self.address, middleware.clone());
+
+        self.pool_id = H256::from(pool.get_pool_id().block(block).call().await?);
+        self.vault = pool.get_vault().block(block).call().await?;
+        self.weights = pool.get_normalized_weights().block(block).call().await?;
+        self.swap_fee = pool.get_swap_fee_percentage().block(block).call().await?;
+
+        let vault = IBalancerV2Vault::new(self.vault, middleware.clone());
+        let (tokens, balances, _) = vault
+            .get_pool_tokens(self.pool_id.0)
+            .block(block)
+            .call()
+            .await?;
+
+        let mut token_decimals = vec![];
+        for token in tokens.iter() {
+            token_decimals.push(
+                IErc20::new(*token, middleware.clone())
+                    .decimals()
+                    .call()
+                    .await?,
+            );
+        }
+
+        self.tokens = tokens;
+        self.token_decimals = token_decimals;
+        self.balances = balances;
+
+        tracing::trace!(?self.address, ?self.tokens, ?self.balances, ?self.weights, "populated pool data");
+
+        Ok(())
+    }
+
+    fn simulate_swap(&self, token_in: H160, amount_in: U256) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        self.calculate_amount_out(token_in, self.get_token_out(token_in), amount_in)
+    }
+
+    fn simulate_swap_mut(
+        &mut self,
+        token_in: H160,
+        amount_in: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        let token_out = self.get_token_out(token_in);
+        let amount_out = self.calculate_amount_out(token_in, token_out, amount_in)?;
+
+        let token_in_idx = self
+            .token_index(token_in)
+            .ok_or(SwapSimulationError::TokenNotInPool(token_in))?;
+        let token_out_idx = self
+            .token_index(token_out)
+            .ok_or(SwapSimulationError::TokenNotInPool(token_out))?;
+
+        tracing::trace!(?self.balances, "pool balances before");
+
+        self.balances[token_in_idx] += amount_in;
+        self.balances[token_out_idx] -= amount_out;
+
+        tracing::trace!(?self.balances, "pool balances after");
+
+        Ok(amount_out)
+    }
+
+    fn swap_fee(&self, _token_in: H160) -> f64 {
+        self.swap_fee.as_u128() as f64 / 1e18
+    }
+
+    //The swap fee percentage is scaled by 1e18
+    fn fee(&self) -> u32 {
+        (self.swap_fee / U256::exp10(12)).as_u32()
+    }
+
+    fn token_decimals(&self) -> Vec<u8> {
+        self.token_decimals.clone()
+    }
+
+    //Returns the first token in the pool that is not the token in. To swap into a specific token of a pool with more than two tokens, use calculate_amount_out
+    fn get_token_out(&self, token_in: H160) -> H160 {
+        self.tokens
+            .iter()
+            .find(|token| **token != token_in)
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+impl BalancerV2WeightedPool {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        address: H160,
+        pool_id: H256,
+        vault: H160,
+        tokens: Vec<H160>,
+        token_decimals: Vec<u8>,
+        balances: Vec<U256>,
+        weights: Vec<U256>,
+        swap_fee: U256,
+    ) -> BalancerV2WeightedPool {
+        BalancerV2WeightedPool {
+            address,
+            pool_id,
+            vault,
+            tokens,
+            token_decimals,
+            balances,
+            weights,
+            swap_fee,
+        }
+    }
+
+    //Creates a new instance of the pool from the pool address, and syncs the pool data
+    pub async fn new_from_address<M: Middleware>(
+        pool_address: H160,
+        middleware: Arc<M>,
+    ) -> Result<Self, AMMError<M>> {
+        let mut pool = BalancerV2WeightedPool {
+            address: pool_address,
+            ..Default::default()
+        };
+
+        pool.populate_data(None, middleware).await?;
+
+        if !pool.data_is_populated() {
+            return Err(AMMError::PoolDataError);
+        }
+
+        Ok(pool)
+    }
+
+    pub fn data_is_populated(&self) -> bool {
+        !(self.tokens.is_empty()
+            || self.tokens.len() != self.balances.len()
+            || self.tokens.len() != self.weights.len()
+            || self.balances.iter().any(|balance| balance.is_zero()))
+    }
+
+    pub fn token_index(&self, token: H160) -> Option<usize> {
+        self.tokens.iter().position(|t| *t == token)
+    }
+
+    //The weighted invariant is zero as soon as one of the balances is, the pool has no price and can not swap
+    fn check_liquidity(&self) -> Result<(), ArithmeticError> {
+        if self.balances.is_empty() || self.balances.iter().any(|balance| balance.is_zero()) {
+            return Err(ArithmeticError::ZeroLiquidity(self.address));
+        }
+
+        Ok(())
+    }
+
+    //Calculates the amount out for a swap between any two tokens in the pool using the weighted math outGivenIn formula
+    pub fn calculate_amount_out(
+        &self,
+        token_in: H160,
+        token_out: H160,
+        amount_in: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        self.check_liquidity()?;
+
+        let token_in_idx = self
+            .token_index(token_in)
+            .ok_or(SwapSimulationError::TokenNotInPool(token_in))?;
+        let token_out_idx = self
+            .token_index(token_out)
+            .ok_or(SwapSimulationError::TokenNotInPool(token_out))?;
+
+        if amount_in.is_zero() {
+            return Ok(U256::zero());
+        }
+
+        let scaling_factor_in = scaling_factor(self.token_decimals[token_in_idx])?;
+        let scaling_factor_out = scaling_factor(self.token_decimals[token_out_idx])?;
+
+        //Fees are subtracted before scaling, matching the order of operations in the pool
+        let amount_in = amount_in - mul_up(amount_in, self.swap_fee);
+
+        let balance_in = self.balances[token_in_idx] * scaling_factor_in;
+        let balance_out = self.balances[token_out_idx] * scaling_factor_out;
+        let amount_in = amount_in * scaling_factor_in;
+
+        tracing::trace!(?balance_in, ?balance_out, ?amount_in);
+
+        if amount_in > mul_down(balance_in, MAX_IN_RATIO) {
+            return Err(SwapSimulationError::MaxInRatioExceeded);
+        }
+
+        // out = balance_out * (1 - (balance_in / (balance_in + amount_in)) ^ (weight_in / weight_out))
+        let base = div_up(balance_in, balance_in + amount_in);
+        let exponent = div_down(self.weights[token_in_idx], self.weights[token_out_idx]);
+        let power = pow(base, exponent);
+
+        let amount_out = mul_down(balance_out, complement(power));
+
+        //Amounts leaving the pool are rounded down
+        Ok(amount_out / scaling_factor_out)
+    }
+}
+
+//Applies a Vault `Swap` or `PoolBalanceChanged` log to the balances of the pool with the given pool id,
+//shared by every pool type that is registered in the Vault
+pub fn sync_balances_from_vault_log(
+    pool_id: H256,
+    tokens: &[H160],
+    balances: &mut [U256],
+    log: Log,
+) -> Result<(), EventLogError> {
+    let event_signature = log.topics[0];
+    let token_index = |token: H160| tokens.iter().position(|t| *t == token);
+
+    if event_signature == SWAP_EVENT_SIGNATURE {
+        let swap_event = SwapFilter::decode_log(&RawLog::from(log))?;
+
+        if H256::from(swap_event.pool_id) == pool_id {
+            if let Some(token_in_idx) = token_index(swap_event.token_in) {
+                balances[token_in_idx] += swap_event.amount_in;
+            }
+
+            if let Some(token_out_idx) = token_index(swap_event.token_out) {
+                balances[token_out_idx] -= swap_event.amount_out;
+            }
+        }
+    } else if event_signature == POOL_BALANCE_CHANGED_EVENT_SIGNATURE {
+        let balance_changed_event = PoolBalanceChangedFilter::decode_log(&RawLog::from(log))?;
+
+        if H256::from(balance_changed_event.pool_id) == pool_id {
+            for (i, token) in balance_changed_event.tokens.iter().enumerate() {
+                if let Some(token_idx) = token_index(*token) {
+                    let delta = balance_changed_event.deltas[i];
+                    let protocol_fee = balance_changed_event.protocol_fee_amounts[i];
+
+                    //Joins increase the balance and exits decrease it, protocol fees are always paid out of the pool
+                    if delta.is_negative() {
+                        balances[token_idx] -= delta.unsigned_abs();
+                    } else {
+                        balances[token_idx] += delta.into_raw();
+                    }
+
+                    balances[token_idx] -= protocol_fee;
+                }
+            }
+        }
+    } else {
+        return Err(EventLogError::InvalidEventSignature);
+    }
+
+    Ok(())
+}
+
+//Multiplier that scales an amount of a token to 18 decimals, the vault does not register tokens with more decimals
+pub fn scaling_factor(decimals: u8) -> Result<U256, ArithmeticError> {
+    18_usize
+        .checked_sub(decimals as usize)
+        .map(U256::exp10)
+        .ok_or(ArithmeticError::UnsupportedDecimals(decimals))
+}
+
+//The pool address is encoded in the first 20 bytes of the pool id
+pub fn pool_address_from_pool_id(pool_id: H256) -> H160 {
+    H160::from_slice(&pool_id.as_bytes()[..20])
+}
+
+pub fn mul_down(a: U256, b: U256) -> U256 {
+    a * b / ONE
+}
+
+pub fn mul_up(a: U256, b: U256) -> U256 {
+    let product = a * b;
+
+    if product.is_zero() {
+        U256::zero()
+    } else {
+        (product - 1) / ONE + 1
+    }
+}
+
+pub fn div_down(a: U256, b: U256) -> U256 {
+    a * ONE / b
+}
+
+pub fn div_up(a: U256, b: U256) -> U256 {
+    if a.is_zero() {
+        U256::zero()
+    } else {
+        (a * ONE - 1) / b + 1
+    }
+}
+
+pub fn complement(x: U256) -> U256 {
+    if x < ONE {
+        ONE - x
+    } else {
+        U256::zero()
+    }
+}
+
+//Raises a fixed point base to a fixed point exponent. Since the base is always <= 1 in the weighted math, the result is bounded by ONE
+pub fn pow(base: U256, exponent: U256) -> U256 {
+    let one = u256_to_big_float(ONE);
+
+    let power = u256_to_big_float(base)
+        .div(&one)
+        .pow(&u256_to_big_float(exponent).div(&one))
+        .mul(&one);
+
+    power.to_u128().map(U256::from).unwrap_or(ONE)
+}
+
+pub fn u256_to_big_float(x: U256) -> BigFloat {
+    let low = BigFloat::from(x.low_u128());
+    let high = BigFloat::from((x >> 128).low_u128());
+
+    high.mul(&BigFloat::from(u128::MAX).add(&BigFloat::from(1)))
+        .add(&low)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use ethers::types::{H160, U256};
+
+    use crate::{
+        amm::AutomatedMarketMaker,
+        errors::{ArithmeticError, SwapSimulationError},
+    };
+
+    use super::BalancerV2WeightedPool;
+
+    fn weighted_pool(
+        weights: Vec<U256>,
+        balances: Vec<U256>,
+    ) -> eyre::Result<BalancerV2WeightedPool> {
+        let tokens = vec![
+            H160::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2")?,
+            H160::from_str("0x6B175474E89094C44Da98b954EedeAC495271d0F")?,
+            H160::from_str("0xba100000625a3754423978a60c9317c58a424e3D")?,
+        ];
+
+        let tokens = tokens[..balances.len()].to_vec();
+
+        Ok(BalancerV2WeightedPool {
+            address: H160::from_str("0x5c6Ee304399DBdB9C8Ef030aB642B10820DB8F56")?,
+            token_decimals: vec![18; tokens.len()],
+            tokens,
+            balances,
+            weights,
+            swap_fee: U256::from_dec_str("3000000000000000")?,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_simulate_swap_equal_weights() -> eyre::Result<()> {
+        let half = U256::from_dec_str("500000000000000000")?;
+        let balance = U256::from_dec_str("1000000000000000000000")?;
+        let pool = weighted_pool(vec![half, half], vec![balance, balance])?;
+
+        let amount_in = U256::from_dec_str("1000000000000000000")?;
+        let amount_out = pool.simulate_swap(pool.tokens[0], amount_in)?;
+
+        //With equal weights the weighted math reduces to the constant product formula
+        let amount_in_with_fee = amount_in - amount_in * 3 / 1000;
+        let expected = balance * amount_in_with_fee / (balance + amount_in_with_fee);
+
+        let difference = if amount_out > expected {
+            amount_out - expected
+        } else {
+            expected - amount_out
+        };
+
+        assert!(difference < U256::from(1000000));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulate_swap_unsupported_decimals() -> eyre::Result<()> {
+        let half = U256::from_dec_str("500000000000000000")?;
+        let balance = U256::from_dec_str("1000000000000000000000")?;
+        let mut pool = weighted_pool(vec![half, half], vec![balance, balance])?;
+        pool.token_decimals[1] = 24;
+
+        assert!(matches!(
+            pool.simulate_swap(pool.tokens[0], U256::exp10(18)),
+            Err(SwapSimulationError::ArithmeticError(
+                ArithmeticError::UnsupportedDecimals(24)
+            ))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulate_swap_multi_token() -> eyre::Result<()> {
+        let weights = vec![
+            U256::from_dec_str("500000000000000000")?,
+            U256::from_dec_str("300000000000000000")?,
+            U256::from_dec_str("200000000000000000")?,
+        ];
+        let balances = vec![
+            U256::from_dec_str("1000000000000000000000")?,
+            U256::from_dec_str("600000000000000000000")?,
+            U256::from_dec_str("400000000000000000000")?,
+        ];
+        let mut pool = weighted_pool(weights, balances)?;
+
+        let amount_in = U256::from_dec_str("1000000000000000000")?;
+        let amount_out = pool.calculate_amount_out(pool.tokens[0], pool.tokens[2], amount_in)?;
+        assert!(!amount_out.is_zero());
+
+        //The spot price is 1:1 for every pair, the output is reduced by the fee and slippage
+        assert!(amount_out < amount_in);
+
+        let balance_before = pool.balances[1];
+        pool.simulate_swap_mut(pool.tokens[0], amount_in)?;
+        assert!(pool.balances[1] < balance_before);
+
+        let non_pool_token = H160::from_str("0x41c36f504BE664982e7519480409Caf36EE4f008")?;
+        assert!(matches!(
+            pool.calculate_amount_out(non_pool_token, pool.tokens[0], amount_in),
+            Err(SwapSimulationError::TokenNotInPool(_))
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,645 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::{
+    prelude::abigen,
+    providers::Middleware,
+    types::{BlockId, BlockNumber, Log, H160, H256, U256},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    amm::{price::Price, AutomatedMarketMaker},
+    errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
+};
+
+use super::{
+    div_down, mul_down, mul_up, sync_balances_from_vault_log, u256_to_big_float, IBalancerV2Vault,
+    IErc20, ONE, POOL_BALANCE_CHANGED_EVENT_SIGNATURE, SWAP_EVENT_SIGNATURE,
+};
+
+abigen!(
+    IBalancerV2StablePool,
+    r#"[
+        function getPoolId() external view returns (bytes32)
+        function getVault() external view returns (address)
+        function getBptIndex() external view returns (uint256)
+        function getAmplificationParameter() external view returns (uint256 value, bool isUpdating, uint256 precision)
+        function getSwapFeePercentage() external view returns (uint256)
+        function getRateProviders() external view returns (address[])
+        function getTokenRate(address token) external view returns (uint256)
+    ]"#;
+);
+
+//The amplification parameter is stored and returned multiplied by this precision
+pub const AMP_PRECISION: U256 = U256([1000, 0, 0, 0]);
+//Newton's method is bounded to the same number of iterations as StableMath
+pub const MAX_STABLE_MATH_ITERATIONS: usize = 255;
+
+//Balancer V2 stable pool, including composable stable pools that register their own BPT as a pool token.
+//`tokens`, `balances` and `rates` are indexed by the tokens registered in the Vault, the BPT is excluded from `tokens()` and can not be swapped through.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BalancerStablePool {
+    pub address: H160,
+    pub pool_id: H256,
+    pub vault: H160,
+    pub bpt_index: Option<usize>, // None for stable pools that do not hold their own BPT
+    pub tokens: Vec<H160>,
+    pub token_decimals: Vec<u8>,
+    pub balances: Vec<U256>,
+    pub rate_providers: Vec<H160>,
+    pub rates: Vec<U256>, // token rates scaled by 1e18, ONE for tokens without a rate provider
+    pub amp: U256,        // amplification parameter multiplied by AMP_PRECISION
+    pub swap_fee: U256,   // swap fee percentage, scaled by 1e18
+}
+
+#[async_trait]
+impl AutomatedMarketMaker for BalancerStablePool {
+    fn address(&self) -> H160 {
+        self.address
+    }
+
+    //Token rates and the amplification parameter change without a Vault event, so they are refreshed along with the balances
+    async fn sync<M: Middleware>(&mut self, middleware: Arc<M>) -> Result<(), AMMError<M>> {
+        let vault = IBalancerV2Vault::new(self.vault, middleware.clone());
+        let (_, balances, _) = vault.get_pool_tokens(self.pool_id.0).call().await?;
+        self.balances = balances;
+
+        let pool = IBalancerV2StablePool::new(self.address, middleware);
+        self.swap_fee = pool.get_swap_fee_percentage().call().await?;
+        (self.amp, _, _) = pool.get_amplification_parameter().call().await?;
+
+        for (i, rate_provider) in self.rate_providers.iter().enumerate() {
+            if !rate_provider.is_zero() {
+                self.rates[i] = pool.get_token_rate(self.tokens[i]).call().await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn sync_on_event_signatures(&self) -> Vec<H256> {
+        vec![SWAP_EVENT_SIGNATURE, POOL_BALANCE_CHANGED_EVENT_SIGNATURE]
+    }
+
+    fn sync_from_log(&mut self, log: Log) -> Result<(), EventLogError> {
+        sync_balances_from_vault_log(self.pool_id, &self.tokens, &mut self.balances, log)
+    }
+
+    fn tokens(&self) -> Vec<H160> {
+        self.swappable_indices().map(|i| self.tokens[i]).collect()
+    }
+
+    //Calculates the spot price of the base token denominated in the token returned by get_token_out(base_token)
+    fn calculate_price(&self, base_token: H160) -> Result<Price, ArithmeticError> {
+        self.check_liquidity()?;
+
+        let quote_token = self.get_token_out(base_token);
+
+        let i = self
+            .swappable_index(base_token)
+            .ok_or(ArithmeticError::TokenNotInPool(base_token))?;
+        let j = self
+            .swappable_index(quote_token)
+            .ok_or(ArithmeticError::TokenNotInPool(quote_token))?;
+
+        let (xp, _) = self.upscaled_balances();
+
+        let position = |idx: usize| self.swappable_indices().position(|k| k == idx);
+        let (x_i, x_j) = match (position(i), position(j)) {
+            (Some(x_i), Some(x_j)) => (xp[x_i], xp[x_j]),
+            _ => return Err(ArithmeticError::InvalidTokenIndex),
+        };
+
+        let n = U256::from(xp.len());
+        let invariant =
+            calculate_invariant(self.amp, &xp).map_err(|_| ArithmeticError::RoundingError)?;
+
+        // D_P = D^(n+1) / (n^n * prod(x))
+        let mut d_p = invariant;
+        for x in xp.iter() {
+            d_p = d_p * invariant / (*x * n);
+        }
+
+        //The marginal price between upscaled balances, converted back to token units by the ratio of the token rates
+        let amp_times_total =
+            u256_to_big_float(self.amp * n).div(&u256_to_big_float(AMP_PRECISION));
+        let d_p = u256_to_big_float(d_p);
+
+        let price = amp_times_total
+            .add(&d_p.div(&u256_to_big_float(x_i)))
+            .div(&amp_times_total.add(&d_p.div(&u256_to_big_float(x_j))))
+            .mul(&u256_to_big_float(self.rates[i]))
+            .div(&u256_to_big_float(self.rates[j]));
+
+        Price::from_f64(price.to_f64())
+    }
+
+    async fn populate_data<M: Middleware>(
+        &mut self,
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        let block: BlockId = block_number
+            .map(BlockId::from)
+            .unwrap_or(BlockId::Number(BlockNumber::Latest));
+
+        let pool = IBalancerV2StablePool::new(self.address, middleware.clone());
+
+        self.pool_id = H256::from(pool.get_pool_id().block(block).call().await?);
+        self.vault = pool.get_vault().block(block).call().await?;
+        (self.amp, _, _) = pool
+            .get_amplification_parameter()
+            .block(block)
+            .call()
+            .await?;
+        self.swap_fee = pool.get_swap_fee_percentage().block(block).call().await?;
+
+        //Legacy stable pools neither register their BPT nor use rate providers
+        self.bpt_index = pool
+            .get_bpt_index()
+            .block(block)
+            .call()
+            .await
+            .ok()
+            .map(|bpt_index| bpt_index.as_usize());
+
+        let vault = IBalancerV2Vault::new(self.vault, middleware.clone());
+        let (tokens, balances, _) = vault
+            .get_pool_tokens(self.pool_id.0)
+            .block(block)
+            .call()
+            .await?;
+
+        let rate_providers = if self.bpt_index.is_some() {
+            pool.get_rate_providers().block(block).call().await?
+        } else {
+            vec![H160::zero(); tokens.len()]
+        };
+
+        let mut token_decimals = vec![];
+        let mut rates = vec![];
+        for (token, rate_provider) in tokens.iter().zip(rate_providers.iter()) {
+            token_decimals.push(
+                IErc20::new(*token, middleware.clone())
+                    .decimals()
+                    .block(block)
+                    .call()
+                    .await?,
+            );
+
+            //The pool swaps with its cached rate, which is only refreshed from the rate provider once the cache expires
+            rates.push(if rate_provider.is_zero() {
+                ONE
+            } else {
+                pool.get_token_rate(*token).block(block).call().await?
+            });
+        }
+
+        self.tokens = tokens;
+        self.token_decimals = token_decimals;
+        self.balances = balances;
+        self.rate_providers = rate_providers;
+        self.rates = rates;
+
+        tracing::trace!(?self.address, ?self.tokens, ?self.balances, ?self.rates, ?self.amp, "populated pool data");
+
+        Ok(())
+    }
+
+    fn simulate_swap(&self, token_in: H160, amount_in: U256) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        self.calculate_amount_out(token_in, self.get_token_out(token_in), amount_in)
+    }
+
+    fn simulate_swap_mut(
+        &mut self,
+        token_in: H160,
+        amount_in: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        let token_out = self.get_token_out(token_in);
+        let amount_out = self.calculate_amount_out(token_in, token_out, amount_in)?;
+
+        let token_in_idx = self
+            .swappable_index(token_in)
+            .ok_or(SwapSimulationError::TokenNotInPool(token_in))?;
+        let token_out_idx = self
+            .swappable_index(token_out)
+            .ok_or(SwapSimulationError::TokenNotInPool(token_out))?;
+
+        tracing::trace!(?self.balances, "pool balances before");
+
+        self.balances[token_in_idx] += amount_in;
+        self.balances[token_out_idx] -= amount_out;
+
+        tracing::trace!(?self.balances, "pool balances after");
+
+        Ok(amount_out)
+    }
+
+    fn swap_fee(&self, _token_in: H160) -> f64 {
+        self.swap_fee.as_u128() as f64 / 1e18
+    }
+
+    fn token_decimals(&self) -> Vec<u8> {
+        self.swappable_indices()
+            .map(|i| self.token_decimals[i])
+            .collect()
+    }
+
+    //Returns the first token in the pool that is neither the token in nor the BPT
+    fn get_token_out(&self, token_in: H160) -> H160 {
+        self.swappable_indices()
+            .map(|i| self.tokens[i])
+            .find(|token| *token != token_in)
+            .unwrap_or_default()
+    }
+}
+
+impl BalancerStablePool {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        address: H160,
+        pool_id: H256,
+        vault: H160,
+        bpt_index: Option<usize>,
+        tokens: Vec<H160>,
+        token_decimals: Vec<u8>,
+        balances: Vec<U256>,
+        rate_providers: Vec<H160>,
+        rates: Vec<U256>,
+        amp: U256,
+        swap_fee: U256,
+    ) -> BalancerStablePool {
+        BalancerStablePool {
+            address,
+            pool_id,
+            vault,
+            bpt_index,
+            tokens,
+            token_decimals,
+            balances,
+            rate_providers,
+            rates,
+            amp,
+            swap_fee,
+        }
+    }
+
+    //Creates a new instance of the pool from the pool address, and syncs the pool data
+    pub async fn new_from_address<M: Middleware>(
+        pool_address: H160,
+        middleware: Arc<M>,
+    ) -> Result<Self, AMMError<M>> {
+        let mut pool = BalancerStablePool {
+            address: pool_address,
+            ..Default::default()
+        };
+
+        pool.populate_data(None, middleware).await?;
+
+        if !pool.data_is_populated() {
+            return Err(AMMError::PoolDataError);
+        }
+
+        Ok(pool)
+    }
+
+    pub fn data_is_populated(&self) -> bool {
+        !(self.tokens.is_empty()
+            || self.amp.is_zero()
+            || self.tokens.len() != self.balances.len()
+            || self.tokens.len() != self.rates.len()
+            || self.tokens.len() != self.token_decimals.len()
+            || self.swappable_indices().any(|i| self.balances[i].is_zero()))
+    }
+
+    //Index of the token in the registered tokens of the pool, the BPT is not swappable
+    pub fn swappable_index(&self, token: H160) -> Option<usize> {
+        self.swappable_indices().find(|i| self.tokens[*i] == token)
+    }
+
+    fn swappable_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.tokens.len()).filter(|i| Some(*i) != self.bpt_index)
+    }
+
+    //Scaling factors normalize every token to 18 decimals and multiply by the token rate
+    pub fn scaling_factor(&self, idx: usize) -> U256 {
+        U256::exp10(18 - self.token_decimals[idx] as usize) * self.rates[idx]
+    }
+
+    //The stable invariant of the swappable tokens is zero as soon as one of their balances is, the BPT is not part of it
+    fn check_liquidity(&self) -> Result<(), ArithmeticError> {
+        if self.balances.is_empty() || self.swappable_indices().any(|i| self.balances[i].is_zero())
+        {
+            return Err(ArithmeticError::ZeroLiquidity(self.address));
+        }
+
+        Ok(())
+    }
+
+    //Returns the upscaled balances without the BPT, along with the registered index of each balance
+    fn upscaled_balances(&self) -> (Vec<U256>, Vec<usize>) {
+        self.swappable_indices()
+            .map(|i| (mul_down(self.balances[i], self.scaling_factor(i)), i))
+            .unzip()
+    }
+
+    //Calculates the amount out for a swap between any two tokens in the pool using the stable math outGivenIn formula
+    pub fn calculate_amount_out(
+        &self,
+        token_in: H160,
+        token_out: H160,
+        amount_in: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        self.check_liquidity()?;
+
+        let token_in_idx = self
+            .swappable_index(token_in)
+            .ok_or(SwapSimulationError::TokenNotInPool(token_in))?;
+        let token_out_idx = self
+            .swappable_index(token_out)
+            .ok_or(SwapSimulationError::TokenNotInPool(token_out))?;
+
+        if amount_in.is_zero() || token_in_idx == token_out_idx {
+            return Ok(U256::zero());
+        }
+
+        let (mut balances, indices) = self.upscaled_balances();
+
+        let index_in = indices
+            .iter()
+            .position(|i| *i == token_in_idx)
+            .ok_or(SwapSimulationError::TokenNotInPool(token_in))?;
+        let index_out = indices
+            .iter()
+            .position(|i| *i == token_out_idx)
+            .ok_or(SwapSimulationError::TokenNotInPool(token_out))?;
+
+        //Fees are subtracted before scaling, matching the order of operations in the pool
+        let amount_in = amount_in - mul_up(amount_in, self.swap_fee);
+        let amount_in = mul_down(amount_in, self.scaling_factor(token_in_idx));
+
+        tracing::trace!(?balances, ?amount_in, ?self.amp);
+
+        let invariant = calculate_invariant(self.amp, &balances)?;
+        let amount_out = calc_out_given_in(
+            self.amp,
+            &mut balances,
+            index_in,
+            index_out,
+            amount_in,
+            invariant,
+        )?;
+
+        //Amounts leaving the pool are rounded down
+        Ok(div_down(amount_out, self.scaling_factor(token_out_idx)))
+    }
+}
+
+//StableMath._calculateInvariant, balances must be upscaled and exclude the BPT
+pub fn calculate_invariant(amp: U256, balances: &[U256]) -> Result<U256, SwapSimulationError> {
+    let sum = balances
+        .iter()
+        .fold(U256::zero(), |sum, balance| sum + *balance);
+    if sum.is_zero() {
+        return Ok(U256::zero());
+    }
+
+    let num_tokens = U256::from(balances.len());
+    let amp_times_total = amp * num_tokens;
+
+    let mut invariant = sum;
+    for _ in 0..MAX_STABLE_MATH_ITERATIONS {
+        let mut d_p = invariant;
+        for balance in balances {
+            d_p = d_p * invariant / (*balance * num_tokens);
+        }
+
+        let prev_invariant = invariant;
+
+        invariant = ((amp_times_total * sum / AMP_PRECISION + d_p * num_tokens) * invariant)
+            / ((amp_times_total - AMP_PRECISION) * invariant / AMP_PRECISION
+                + (num_tokens + 1) * d_p);
+
+        if abs_diff(invariant, prev_invariant) <= U256::one() {
+            return Ok(invariant);
+        }
+    }
+
+    Err(SwapSimulationError::DidNotConverge)
+}
+
+//StableMath._calcOutGivenIn, the amount in must already have the swap fee removed and be upscaled
+pub fn calc_out_given_in(
+    amp: U256,
+    balances: &mut [U256],
+    index_in: usize,
+    index_out: usize,
+    amount_in: U256,
+    invariant: U256,
+) -> Result<U256, SwapSimulationError> {
+    balances[index_in] += amount_in;
+    let final_balance_out = get_token_balance_given_invariant_and_all_other_balances(
+        amp, balances, invariant, index_out,
+    );
+    balances[index_in] -= amount_in;
+
+    let final_balance_out = final_balance_out?;
+
+    //The result is rounded down by one wei in favor of the pool
+    if balances[index_out] <= final_balance_out + 1 {
+        return Ok(U256::zero());
+    }
+
+    Ok(balances[index_out] - final_balance_out - 1)
+}
+
+//Solves the invariant for the balance of the token at `token_index`, rounding up
+pub fn get_token_balance_given_invariant_and_all_other_balances(
+    amp: U256,
+    balances: &[U256],
+    invariant: U256,
+    token_index: usize,
+) -> Result<U256, SwapSimulationError> {
+    let num_tokens = U256::from(balances.len());
+    let amp_times_total = amp * num_tokens;
+
+    let mut sum = balances[0];
+    let mut p_d = balances[0] * num_tokens;
+    for balance in balances.iter().skip(1) {
+        p_d = p_d * *balance * num_tokens / invariant;
+        sum += *balance;
+    }
+    sum -= balances[token_index];
+
+    let invariant_squared = invariant * invariant;
+    let c = div_up_raw(invariant_squared, amp_times_total * p_d)
+        * AMP_PRECISION
+        * balances[token_index];
+    let b = sum + invariant / amp_times_total * AMP_PRECISION;
+
+    let mut token_balance = div_up_raw(invariant_squared + c, invariant + b);
+    for _ in 0..MAX_STABLE_MATH_ITERATIONS {
+        let prev_token_balance = token_balance;
+
+        let denominator = token_balance * 2 + b;
+        if denominator <= invariant {
+            return Err(SwapSimulationError::DidNotConverge);
+        }
+
+        token_balance = div_up_raw(token_balance * token_balance + c, denominator - invariant);
+
+        if abs_diff(token_balance, prev_token_balance) <= U256::one() {
+            return Ok(token_balance);
+        }
+    }
+
+    Err(SwapSimulationError::DidNotConverge)
+}
+
+//Math.divUp, integer division rounding up without fixed point scaling
+fn div_up_raw(a: U256, b: U256) -> U256 {
+    if a.is_zero() {
+        U256::zero()
+    } else {
+        (a - 1) / b + 1
+    }
+}
+
+fn abs_diff(a: U256, b: U256) -> U256 {
+    if a > b {
+        a - b
+    } else {
+        b - a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, sync::Arc};
+
+    use ethers::{
+        providers::{Http, Provider},
+        types::{H160, U256},
+    };
+
+    use crate::{amm::AutomatedMarketMaker, errors::SwapSimulationError};
+
+    use super::{calculate_invariant, BalancerStablePool, ONE};
+
+    //Mirrors the wstETH/WETH composable stable pool, the BPT is registered at index 1
+    fn composable_stable_pool(rate: U256) -> eyre::Result<BalancerStablePool> {
+        let tokens = vec![
+            H160::from_str("0x7f39C581F595B53c5cb19bD0b3f8dA6c935E2Ca0")?,
+            H160::from_str("0x93d199263632a4EF4Bb438F1feB99e57b4b5f0BD")?,
+            H160::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2")?,
+        ];
+
+        Ok(BalancerStablePool {
+            address: tokens[1],
+            bpt_index: Some(1),
+            token_decimals: vec![18; 3],
+            balances: vec![
+                U256::from_dec_str("1000000000000000000000")?,
+                U256::from_dec_str("2596148429267413814265248164610048")?,
+                U256::from_dec_str("1100000000000000000000")?,
+            ],
+            rate_providers: vec![tokens[0], H160::zero(), H160::zero()],
+            rates: vec![rate, ONE, ONE],
+            amp: U256::from(50000),
+            swap_fee: U256::from_dec_str("100000000000000")?,
+            tokens,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_bpt_is_filtered_from_swap_paths() -> eyre::Result<()> {
+        let pool = composable_stable_pool(U256::from_dec_str("1100000000000000000")?)?;
+        let bpt = pool.address;
+
+        assert_eq!(pool.tokens(), vec![pool.tokens[0], pool.tokens[2]]);
+        assert_eq!(pool.token_decimals().len(), 2);
+        assert_eq!(pool.get_token_out(pool.tokens[0]), pool.tokens[2]);
+        assert!(
+            matches!(pool.simulate_swap(bpt, ONE), Err(SwapSimulationError::TokenNotInPool(token)) if token == bpt)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulate_swap_with_rate_scaling() -> eyre::Result<()> {
+        let rate = U256::from_dec_str("1100000000000000000")?;
+        let mut pool = composable_stable_pool(rate)?;
+        let (wsteth, weth) = (pool.tokens[0], pool.tokens[2]);
+
+        //With the rate applied both balances are worth 1100 WETH, so the pool trades close to the rate
+        let amount_in = U256::from_dec_str("1000000000000000000")?;
+        let amount_out = pool.simulate_swap(wsteth, amount_in)?;
+        assert!(amount_out < U256::from_dec_str("1100000000000000000")?);
+        assert!(amount_out > U256::from_dec_str("1099000000000000000")?);
+
+        let amount_back = pool.simulate_swap(weth, amount_out)?;
+        assert!(amount_back < amount_in);
+        assert!(amount_back > U256::from_dec_str("999000000000000000")?);
+
+        let price = pool.calculate_price(wsteth)?.to_f64();
+        assert!((price - 1.1).abs() < 1e-6);
+
+        let bpt_balance = pool.balances[1];
+        pool.simulate_swap_mut(wsteth, amount_in)?;
+        assert_eq!(pool.balances[1], bpt_balance);
+        assert_eq!(
+            pool.balances[2],
+            U256::from_dec_str("1100000000000000000000")? - amount_out
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_calculate_invariant() -> eyre::Result<()> {
+        let balance = U256::from_dec_str("1000000000000000000000")?;
+
+        //The invariant of a balanced pool is the sum of the balances
+        assert_eq!(
+            calculate_invariant(U256::from(200000), &[balance, balance, balance])?,
+            balance * 3
+        );
+        assert_eq!(calculate_invariant(U256::from(200000), &[])?, U256::zero());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_populate_data() -> eyre::Result<()> {
+        let rpc_endpoint = std::env::var("ETHEREUM_RPC_ENDPOINT")?;
+        let middleware = Arc::new(Provider::<Http>::try_from(rpc_endpoint)?);
+
+        //wstETH/WETH composable stable pool
+        let pool = BalancerStablePool::new_from_address(
+            H160::from_str("0x93d199263632a4EF4Bb438F1feB99e57b4b5f0BD")?,
+            middleware,
+        )
+        .await?;
+
+        assert_eq!(pool.tokens().len(), 2);
+        assert!(!pool.tokens().contains(&pool.address));
+        assert!(pool.bpt_index.is_some());
+
+        //wstETH trades at its stETH rate which is above 1 WETH
+        let wsteth = H160::from_str("0x7f39C581F595B53c5cb19bD0b3f8dA6c935E2Ca0")?;
+        let price = pool.calculate_price(wsteth)?.to_f64();
+        assert!(price > 1.0 && price < 1.5);
+
+        let amount_out = pool.simulate_swap(wsteth, ONE)?;
+        assert!(amount_out > ONE);
+
+        Ok(())
+    }
+}
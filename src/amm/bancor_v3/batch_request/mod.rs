@@ -0,0 +1,118 @@
+use std::sync::Arc;
+
+use ethers::{
+    abi::Token,
+    contract::Multicall,
+    providers::Middleware,
+    types::{BlockNumber, Bytes, H160},
+};
+
+use crate::{amm::uniswap_v3::IErc20, errors::AMMError};
+
+use super::{BancorV3Pool, IBancorNetworkInfo, INetworkSettings, NATIVE_TOKEN};
+
+pub async fn get_bancor_v3_pool_data_batch_request<M: Middleware>(
+    mut pools: Vec<&mut BancorV3Pool>,
+    block_number: Option<u64>,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    let block = block_number
+        .map(BlockNumber::from)
+        .unwrap_or(BlockNumber::Latest);
+
+    let mut multicall = Multicall::new(middleware.clone(), None).await?.block(block);
+
+    for pool in pools.iter() {
+        let network_info = IBancorNetworkInfo::new(pool.network_info, middleware.clone());
+        multicall.add_call(network_info.bnt(), true);
+        multicall.add_call(network_info.network_settings(), true);
+        //The native token does not implement decimals, the call is expected to fail
+        multicall.add_call(IErc20::new(pool.token, middleware.clone()).decimals(), true);
+        multicall.add_call(network_info.trading_liquidity(pool.token), true);
+        multicall.add_call(network_info.trading_fee_ppm(pool.token), true);
+    }
+
+    let results = multicall.call_raw().await?;
+    multicall.clear_calls();
+
+    //Tokens without a pool are left empty so that they are removed by remove_empty_amms
+    let mut populated_pools = vec![];
+    for (pool, results) in pools.iter_mut().zip(results.chunks(5)) {
+        if let Some(network_settings) = decode_pool_data(pool, results) {
+            multicall.add_call(
+                INetworkSettings::new(network_settings, middleware.clone()).network_fee_ppm(),
+                true,
+            );
+            populated_pools.push(pool);
+        } else {
+            tracing::debug!(?pool.token, "token does not have a bancor v3 pool");
+        }
+    }
+
+    if populated_pools.is_empty() {
+        return Ok(());
+    }
+
+    let network_fees = multicall.call_raw().await?;
+    for (pool, network_fee) in populated_pools.into_iter().zip(network_fees) {
+        pool.network_fee = network_fee
+            .ok()
+            .and_then(|network_fee| network_fee.into_uint())
+            .ok_or(AMMError::BatchRequestError(pool.token))?
+            .as_u32();
+    }
+
+    Ok(())
+}
+
+//Syncs the trading liquidity and the trading fee of each pool, both are read from the network info
+pub async fn sync_bancor_v3_pools_batch_request<M: Middleware>(
+    mut pools: Vec<&mut BancorV3Pool>,
+    block_number: Option<u64>,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    let block = block_number
+        .map(BlockNumber::from)
+        .unwrap_or(BlockNumber::Latest);
+
+    let mut multicall = Multicall::new(middleware.clone(), None).await?.block(block);
+
+    for pool in pools.iter() {
+        let network_info = IBancorNetworkInfo::new(pool.network_info, middleware.clone());
+        multicall.add_call(network_info.trading_liquidity(pool.token), false);
+        multicall.add_call(network_info.trading_fee_ppm(pool.token), false);
+    }
+
+    let results = multicall.call_raw().await?;
+    for (pool, results) in pools.iter_mut().zip(results.chunks(2)) {
+        decode_trading_state(pool, results).ok_or(AMMError::SyncError(pool.token))?;
+    }
+
+    Ok(())
+}
+
+//Returns the address of the network settings, which hold the network fee
+fn decode_pool_data(pool: &mut BancorV3Pool, results: &[Result<Token, Bytes>]) -> Option<H160> {
+    pool.bnt = results.first()?.as_ref().ok()?.clone().into_address()?;
+    let network_settings = results.get(1)?.as_ref().ok()?.clone().into_address()?;
+
+    pool.token_decimals = match results.get(2)?.as_ref().ok() {
+        Some(decimals) => decimals.clone().into_uint()?.as_u32() as u8,
+        None if pool.token == NATIVE_TOKEN => 18,
+        None => return None,
+    };
+
+    decode_trading_state(pool, results.get(3..)?)?;
+
+    Some(network_settings)
+}
+
+fn decode_trading_state(pool: &mut BancorV3Pool, results: &[Result<Token, Bytes>]) -> Option<()> {
+    let trading_liquidity = results.first()?.as_ref().ok()?.clone().into_tuple()?;
+
+    pool.bnt_trading_liquidity = trading_liquidity.first()?.clone().into_uint()?;
+    pool.base_token_trading_liquidity = trading_liquidity.get(1)?.clone().into_uint()?;
+    pool.trading_fee = results.get(1)?.as_ref().ok()?.clone().into_uint()?.as_u32();
+
+    Some(())
+}
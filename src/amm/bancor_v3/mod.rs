@@ -0,0 +1,461 @@
+pub mod batch_request;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::{
+    abi::RawLog,
+    prelude::{abigen, EthEvent},
+    providers::Middleware,
+    types::{Log, H160, H256, U256},
+};
+use serde::{Deserialize, Serialize};
+use uniswap_v3_math::full_math::mul_div;
+
+use crate::{
+    amm::{price::Price, u256_to_f64, AutomatedMarketMaker},
+    errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
+};
+
+abigen!(
+    IBancorNetworkInfo,
+    r#"[
+        function bnt() external view returns (address)
+        function networkSettings() external view returns (address)
+        function tradingLiquidity(address pool) external view returns (uint128 bntTradingLiquidity, uint128 baseTokenTradingLiquidity)
+        function tradingFeePPM(address pool) external view returns (uint32)
+        function tradingEnabled(address pool) external view returns (bool)
+        function tradeOutputBySourceAmount(address sourceToken, address targetToken, uint256 sourceAmount) external view returns (uint256)
+    ]"#;
+
+    INetworkSettings,
+    r#"[
+        function networkFeePPM() external view returns (uint32)
+    ]"#;
+
+    IBancorNetwork,
+    r#"[
+        event TokensTraded(bytes32 indexed contextId, address indexed sourceToken, address indexed targetToken, uint256 sourceAmount, uint256 targetAmount, uint256 bntAmount, uint256 targetFeeAmount, uint256 bntFeeAmount, address trader)
+    ]"#;
+);
+
+pub const TOKENS_TRADED_EVENT_SIGNATURE: H256 = H256([
+    92, 2, 194, 187, 45, 29, 8, 35, 23, 235, 35, 145, 108, 162, 123, 62, 124, 41, 67, 152, 182, 0,
+    97, 162, 173, 84, 241, 195, 192, 24, 195, 24,
+]);
+
+//Fees are expressed in parts per million
+pub const PPM_RESOLUTION: u32 = 1000000;
+pub const BNT_DECIMALS: u8 = 18;
+//Bancor represents the native token with this address
+pub const NATIVE_TOKEN: H160 = H160([
+    238, 238, 238, 238, 238, 238, 238, 238, 238, 238, 238, 238, 238, 238, 238, 238, 238, 238, 238,
+    238,
+]);
+
+//Bancor V3 is an omnipool, every pool trades its base token against BNT and the state of all pools lives in the pool collection.
+//Pools are identified by their base token, so the base token is used as the address of the AMM.
+//Trades between two base tokens go through both pools, which can be quoted by composing the swaps of the two pools.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BancorV3Pool {
+    pub token: H160,
+    pub token_decimals: u8,
+    pub bnt: H160,
+    pub network_info: H160,
+    pub bnt_trading_liquidity: U256,
+    pub base_token_trading_liquidity: U256,
+    pub trading_fee: u32,
+    pub network_fee: u32,
+}
+
+#[async_trait]
+impl AutomatedMarketMaker for BancorV3Pool {
+    fn address(&self) -> H160 {
+        self.token
+    }
+
+    async fn sync<M: Middleware>(&mut self, middleware: Arc<M>) -> Result<(), AMMError<M>> {
+        batch_request::sync_bancor_v3_pools_batch_request(vec![self], None, middleware).await
+    }
+
+    async fn populate_data<M: Middleware>(
+        &mut self,
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        batch_request::get_bancor_v3_pool_data_batch_request(vec![self], block_number, middleware)
+            .await
+    }
+
+    fn sync_on_event_signatures(&self) -> Vec<H256> {
+        vec![TOKENS_TRADED_EVENT_SIGNATURE]
+    }
+
+    //The network emits a single event for trades between two base tokens, each pool applies the hop that went through it
+    fn sync_from_log(&mut self, log: Log) -> Result<(), EventLogError> {
+        let event_signature = log.topics[0];
+
+        if event_signature == TOKENS_TRADED_EVENT_SIGNATURE {
+            let tokens_traded_event = TokensTradedFilter::decode_log(&RawLog::from(log))?;
+
+            if tokens_traded_event.source_token == self.token {
+                self.process_trade(
+                    false,
+                    tokens_traded_event.source_amount,
+                    tokens_traded_event.bnt_amount,
+                    tokens_traded_event.bnt_fee_amount,
+                )?;
+            } else if tokens_traded_event.target_token == self.token {
+                self.process_trade(
+                    true,
+                    tokens_traded_event.bnt_amount,
+                    tokens_traded_event.target_amount,
+                    tokens_traded_event.target_fee_amount,
+                )?;
+            }
+
+            Ok(())
+        } else {
+            Err(EventLogError::InvalidEventSignature)
+        }
+    }
+
+    //Calculates the marginal price of the base token in terms of the quote token
+    fn calculate_price(&self, base_token: H160) -> Result<Price, ArithmeticError> {
+        self.check_liquidity()?;
+
+        let base_token_liquidity = u256_to_f64(self.base_token_trading_liquidity)
+            / 10_f64.powi(self.token_decimals as i32);
+        let bnt_liquidity =
+            u256_to_f64(self.bnt_trading_liquidity) / 10_f64.powi(BNT_DECIMALS as i32);
+
+        if base_token == self.token {
+            Price::from_f64(bnt_liquidity / base_token_liquidity)
+        } else {
+            Price::from_f64(base_token_liquidity / bnt_liquidity)
+        }
+    }
+
+    fn tokens(&self) -> Vec<H160> {
+        vec![self.token, self.bnt]
+    }
+
+    fn simulate_swap(&self, token_in: H160, amount_in: U256) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        let (amount_out, _) = self.trade(token_in, amount_in)?;
+
+        tracing::trace!(?amount_out);
+
+        Ok(amount_out)
+    }
+
+    fn simulate_swap_mut(
+        &mut self,
+        token_in: H160,
+        amount_in: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        let (amount_out, trading_fee_amount) = self.trade(token_in, amount_in)?;
+
+        tracing::trace!(?amount_out);
+        tracing::trace!(?self.bnt_trading_liquidity, ?self.base_token_trading_liquidity, "trading liquidity before");
+
+        self.process_trade(
+            token_in == self.bnt,
+            amount_in,
+            amount_out,
+            trading_fee_amount,
+        )?;
+
+        tracing::trace!(?self.bnt_trading_liquidity, ?self.base_token_trading_liquidity, "trading liquidity after");
+
+        Ok(amount_out)
+    }
+
+    fn swap_fee(&self, _token_in: H160) -> f64 {
+        self.trading_fee as f64 / PPM_RESOLUTION as f64
+    }
+
+    fn token_decimals(&self) -> Vec<u8> {
+        vec![self.token_decimals, BNT_DECIMALS]
+    }
+
+    fn get_token_out(&self, token_in: H160) -> H160 {
+        if self.token == token_in {
+            self.bnt
+        } else {
+            self.token
+        }
+    }
+}
+
+impl BancorV3Pool {
+    //Creates a new instance of the pool of the base token, and syncs the pool data
+    pub async fn new_from_token<M: Middleware>(
+        token: H160,
+        network_info: H160,
+        middleware: Arc<M>,
+    ) -> Result<Self, AMMError<M>> {
+        let mut pool = BancorV3Pool {
+            token,
+            network_info,
+            ..Default::default()
+        };
+
+        pool.populate_data(None, middleware).await?;
+
+        if !pool.data_is_populated() {
+            return Err(AMMError::PoolDataError);
+        }
+
+        Ok(pool)
+    }
+
+    pub fn data_is_populated(&self) -> bool {
+        !(self.token.is_zero()
+            || self.bnt.is_zero()
+            || self.bnt_trading_liquidity.is_zero()
+            || self.base_token_trading_liquidity.is_zero())
+    }
+
+    //Pools without trading liquidity, e.g. pools that trading is disabled for, have no price and can not be traded with
+    fn check_liquidity(&self) -> Result<(), ArithmeticError> {
+        if self.bnt_trading_liquidity.is_zero() || self.base_token_trading_liquidity.is_zero() {
+            return Err(ArithmeticError::ZeroLiquidity(self.token));
+        }
+
+        Ok(())
+    }
+
+    //Mirrors `tradeOutputBySourceAmount` of the network info for a trade through this pool, returns the amount out and the trading fee
+    pub fn trade(
+        &self,
+        token_in: H160,
+        amount_in: U256,
+    ) -> Result<(U256, U256), SwapSimulationError> {
+        self.check_liquidity()?;
+
+        let (source_balance, target_balance) = if token_in == self.bnt {
+            (
+                self.bnt_trading_liquidity,
+                self.base_token_trading_liquidity,
+            )
+        } else if token_in == self.token {
+            (
+                self.base_token_trading_liquidity,
+                self.bnt_trading_liquidity,
+            )
+        } else {
+            return Err(SwapSimulationError::TokenNotInPool(token_in));
+        };
+
+        if amount_in.is_zero() {
+            return Ok((U256::zero(), U256::zero()));
+        }
+
+        trade_amount_and_fee(source_balance, target_balance, self.trading_fee, amount_in)
+    }
+
+    //Mirrors `_processTrade` of the pool collection. The trading fee stays in the pool except for the network fee,
+    //which is always kept in BNT so the share taken from the base token is traded to BNT within the pool.
+    pub fn process_trade(
+        &mut self,
+        source_is_bnt: bool,
+        source_amount: U256,
+        target_amount: U256,
+        trading_fee_amount: U256,
+    ) -> Result<(), SwapSimulationError> {
+        let (source_balance, target_balance) = if source_is_bnt {
+            (
+                &mut self.bnt_trading_liquidity,
+                &mut self.base_token_trading_liquidity,
+            )
+        } else {
+            (
+                &mut self.base_token_trading_liquidity,
+                &mut self.bnt_trading_liquidity,
+            )
+        };
+
+        *source_balance += source_amount;
+        *target_balance = target_balance.saturating_sub(target_amount);
+
+        if self.network_fee == 0 {
+            return Ok(());
+        }
+
+        let target_network_fee_amount = mul_div(
+            trading_fee_amount,
+            U256::from(self.network_fee),
+            U256::from(PPM_RESOLUTION),
+        )?;
+        *target_balance = target_balance.saturating_sub(target_network_fee_amount);
+
+        if !source_is_bnt {
+            return Ok(());
+        }
+
+        let (network_fee_amount, _) = trade_amount_and_fee(
+            *target_balance,
+            *source_balance,
+            0,
+            target_network_fee_amount,
+        )?;
+
+        *target_balance += target_network_fee_amount;
+        *source_balance = source_balance.saturating_sub(network_fee_amount);
+
+        Ok(())
+    }
+}
+
+//Mirrors `_tradeAmountAndFeeBySourceAmount` of the pool collection, the fee is taken from the target amount
+pub fn trade_amount_and_fee(
+    source_balance: U256,
+    target_balance: U256,
+    fee_ppm: u32,
+    source_amount: U256,
+) -> Result<(U256, U256), SwapSimulationError> {
+    let target_amount = mul_div(
+        target_balance,
+        source_amount,
+        source_balance + source_amount,
+    )?;
+    let trading_fee_amount = mul_div(
+        target_amount,
+        U256::from(fee_ppm),
+        U256::from(PPM_RESOLUTION),
+    )?;
+
+    Ok((target_amount - trading_fee_amount, trading_fee_amount))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, sync::Arc};
+
+    use ethers::{
+        providers::{Http, Provider},
+        types::{H160, U256},
+    };
+
+    use crate::{
+        amm::{AutomatedMarketMaker, AMM},
+        router::simulate_multihop_swap,
+    };
+
+    use super::{BancorV3Pool, IBancorNetworkInfo};
+
+    fn bnt() -> H160 {
+        H160::from_low_u64_be(1)
+    }
+
+    fn eth_pool() -> BancorV3Pool {
+        BancorV3Pool {
+            token: H160::from_low_u64_be(2),
+            token_decimals: 18,
+            bnt: bnt(),
+            network_info: H160::zero(),
+            bnt_trading_liquidity: U256::from(2_000_000) * U256::exp10(18),
+            base_token_trading_liquidity: U256::from(1000) * U256::exp10(18),
+            trading_fee: 2000,
+            network_fee: 200000,
+        }
+    }
+
+    #[test]
+    fn test_simulate_swap() -> eyre::Result<()> {
+        let pool = eth_pool();
+
+        let amount_out = pool.simulate_swap(pool.token, U256::exp10(18))?;
+        assert_eq!(amount_out, U256::from_dec_str("1994005994005994005995")?);
+
+        let amount_out = pool.simulate_swap(pool.bnt, U256::from(2000) * U256::exp10(18))?;
+        assert_eq!(amount_out, U256::from(997002997002997002_u64));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulate_swap_mut() -> eyre::Result<()> {
+        //The network fee share of the trading fee leaves the pool
+        let mut pool = eth_pool();
+        let amount_out = pool.simulate_swap_mut(pool.token, U256::exp10(18))?;
+        assert_eq!(amount_out, U256::from_dec_str("1994005994005994005995")?);
+        assert_eq!(
+            pool.bnt_trading_liquidity,
+            U256::from_dec_str("1998005194805194805194805")?
+        );
+        assert_eq!(
+            pool.base_token_trading_liquidity,
+            U256::from(1001) * U256::exp10(18)
+        );
+
+        //When the trading fee is taken in the base token, the network fee is traded to BNT
+        let mut pool = eth_pool();
+        let amount_out = pool.simulate_swap_mut(pool.bnt, U256::from(2000) * U256::exp10(18))?;
+        assert_eq!(amount_out, U256::from(997002997002997002_u64));
+        assert_eq!(
+            pool.bnt_trading_liquidity,
+            U256::from_dec_str("2001999199201601596798010")?
+        );
+        assert_eq!(
+            pool.base_token_trading_liquidity,
+            U256::from_dec_str("999002997002997002998")?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulate_swap_between_base_tokens() -> eyre::Result<()> {
+        let eth_pool = eth_pool();
+        let usdc_pool = BancorV3Pool {
+            token: H160::from_low_u64_be(3),
+            token_decimals: 6,
+            bnt_trading_liquidity: U256::from(4_000_000) * U256::exp10(18),
+            base_token_trading_liquidity: U256::from(4_000_000) * U256::exp10(6),
+            trading_fee: 1000,
+            ..eth_pool.clone()
+        };
+
+        let path = [eth_pool.token, bnt(), usdc_pool.token];
+        let amount_out = simulate_multihop_swap(
+            &[AMM::BancorV3Pool(eth_pool), AMM::BancorV3Pool(usdc_pool)],
+            &path,
+            U256::exp10(18),
+        )?;
+        assert_eq!(amount_out, U256::from(1991019462));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_simulate_swap_matches_network_info() -> eyre::Result<()> {
+        let rpc_endpoint = std::env::var("ETHEREUM_RPC_ENDPOINT")?;
+        let middleware = Arc::new(Provider::<Http>::try_from(rpc_endpoint)?);
+
+        let network_info = H160::from_str("0x8E303D296851B320e6a697bAcB979d13c9D6E760")?;
+        let link = H160::from_str("0x514910771AF9Ca656af840dff83E8264EcF986CA")?;
+
+        let pool = BancorV3Pool::new_from_token(link, network_info, middleware.clone()).await?;
+        let network_info = IBancorNetworkInfo::new(network_info, middleware);
+
+        for (token_in, token_out) in [(pool.token, pool.bnt), (pool.bnt, pool.token)] {
+            let amount_in = U256::exp10(18);
+            let expected_amount_out = network_info
+                .trade_output_by_source_amount(token_in, token_out, amount_in)
+                .call()
+                .await?;
+
+            assert_eq!(
+                pool.simulate_swap(token_in, amount_in)?,
+                expected_amount_out
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+use ethers::{
+    contract::{Multicall, MULTICALL_ADDRESS},
+    providers::Middleware,
+    types::{BlockId, Bytes, H160, U256},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::AMMError;
+
+//Address of the canonical Multicall3 deployment, which is the same on most chains
+pub const MULTICALL3_ADDRESS: H160 = MULTICALL_ADDRESS;
+
+//How pool data is read in batches. The batch contracts are deployed inside an eth_call and read the pools in their constructor,
+//which fails on providers that cap the calldata or initcode size of a call or that use a low gas limit for eth_call.
+//Multicall3 reads the same data through aggregate3 calls to a deployed contract and returns the same pools.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BatchBackend {
+    #[default]
+    BatchContract,
+    //Address of the Multicall3 contract, MULTICALL3_ADDRESS unless it is deployed elsewhere on the chain
+    Multicall3(H160),
+}
+
+impl BatchBackend {
+    pub fn multicall3() -> BatchBackend {
+        BatchBackend::Multicall3(MULTICALL3_ADDRESS)
+    }
+}
+
+//Return data of each call of the multicall, None for calls that reverted. Calls are allowed to fail so that a single pool
+//can not revert the whole batch.
+pub(crate) async fn aggregate_3<M: Middleware>(
+    multicall: &mut Multicall<M>,
+    block: Option<BlockId>,
+) -> Result<Vec<Option<Bytes>>, AMMError<M>> {
+    let mut aggregate_3 = multicall.as_aggregate_3();
+    if let Some(block) = block {
+        aggregate_3 = aggregate_3.block(block);
+    }
+
+    let results = aggregate_3.call().await?;
+    multicall.clear_calls();
+
+    Ok(results
+        .into_iter()
+        .map(|result| result.success.then_some(result.return_data))
+        .collect())
+}
+
+//Decimals of a token as they are validated by the batch contracts, tokens that return anything else than a single word
+//with decimals in [1, 255] are skipped, as are addresses without code since their calls return no data
+pub(crate) fn decode_decimals(return_data: Option<&[u8]>) -> Option<u8> {
+    let return_data = return_data?;
+    if return_data.len() != 32 {
+        return None;
+    }
+
+    let decimals = U256::from_big_endian(return_data);
+    if decimals.is_zero() || decimals > U256::from(u8::MAX) {
+        None
+    } else {
+        Some(decimals.as_u32() as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::{
+        abi::{encode, Token},
+        types::U256,
+    };
+
+    use super::{decode_decimals, BatchBackend, MULTICALL3_ADDRESS};
+
+    #[test]
+    fn test_decode_decimals() {
+        let decimals = |decimals: u64| encode(&[Token::Uint(U256::from(decimals))]);
+
+        assert_eq!(decode_decimals(Some(&decimals(18))), Some(18));
+        assert_eq!(decode_decimals(Some(&decimals(255))), Some(255));
+        assert_eq!(decode_decimals(Some(&decimals(0))), None);
+        assert_eq!(decode_decimals(Some(&decimals(256))), None);
+        assert_eq!(decode_decimals(Some(&[])), None);
+        assert_eq!(decode_decimals(None), None);
+
+        assert_eq!(
+            BatchBackend::multicall3(),
+            BatchBackend::Multicall3(MULTICALL3_ADDRESS)
+        );
+        assert_eq!(BatchBackend::default(), BatchBackend::BatchContract);
+    }
+}
@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use ethers::{
+    abi::Token,
+    contract::Multicall,
+    providers::Middleware,
+    types::{BlockNumber, Bytes, H160},
+};
+
+use crate::{amm::uniswap_v3::IErc20, errors::AMMError};
+
+use super::{factory::ICamelotFactory, CamelotPool, ICamelotPair};
+
+pub async fn get_camelot_pool_data_batch_request<M: Middleware>(
+    mut pools: Vec<&mut CamelotPool>,
+    block_number: Option<u64>,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    let block = block_number
+        .map(BlockNumber::from)
+        .unwrap_or(BlockNumber::Latest);
+
+    let mut multicall = Multicall::new(middleware.clone(), None).await?.block(block);
+
+    for pool in pools.iter() {
+        let pair = ICamelotPair::new(pool.address, middleware.clone());
+        multicall.add_call(pair.token_0(), true);
+        multicall.add_call(pair.token_1(), true);
+        multicall.add_call(pair.get_reserves(), true);
+        multicall.add_call(pair.stable_swap(), true);
+        multicall.add_call(pair.factory(), true);
+    }
+
+    let results = multicall.call_raw().await?;
+    multicall.clear_calls();
+
+    //Pairs that do not implement the interface are left empty so that they are removed by remove_empty_amms
+    let mut populated_pools = vec![];
+    for (pool, results) in pools.iter_mut().zip(results.chunks(5)) {
+        if let Some(factory) = decode_pair_data(pool, results) {
+            let factory = ICamelotFactory::new(factory, middleware.clone());
+            multicall.add_call(factory.owner_fee_share(), true);
+            multicall.add_call(factory.fee_to(), true);
+            for token in [pool.token_a, pool.token_b] {
+                multicall.add_call(IErc20::new(token, middleware.clone()).decimals(), true);
+            }
+            populated_pools.push(pool);
+        } else {
+            tracing::debug!(?pool.address, "pair does not implement the camelot interface");
+        }
+    }
+
+    if populated_pools.is_empty() {
+        return Ok(());
+    }
+
+    let results = multicall.call_raw().await?;
+    for (pool, results) in populated_pools.into_iter().zip(results.chunks(4)) {
+        decode_factory_data(pool, results).ok_or(AMMError::BatchRequestError(pool.address))?;
+    }
+
+    Ok(())
+}
+
+//Returns the factory of the pair if the pair data could be decoded
+fn decode_pair_data(pool: &mut CamelotPool, results: &[Result<Token, Bytes>]) -> Option<H160> {
+    pool.token_a = results.first()?.as_ref().ok()?.clone().into_address()?;
+    pool.token_b = results.get(1)?.as_ref().ok()?.clone().into_address()?;
+
+    let reserves = results.get(2)?.as_ref().ok()?.clone().into_tuple()?;
+    pool.reserve_0 = reserves.first()?.clone().into_uint()?;
+    pool.reserve_1 = reserves.get(1)?.clone().into_uint()?;
+    pool.token_0_fee_percent = reserves.get(2)?.clone().into_uint()?.as_u32();
+    pool.token_1_fee_percent = reserves.get(3)?.clone().into_uint()?.as_u32();
+
+    pool.stable = results.get(3)?.as_ref().ok()?.clone().into_bool()?;
+
+    results.get(4)?.as_ref().ok()?.clone().into_address()
+}
+
+//The owner share of the fee only leaves the pair when the factory has a fee recipient
+fn decode_factory_data(pool: &mut CamelotPool, results: &[Result<Token, Bytes>]) -> Option<()> {
+    let owner_fee_share = results.first()?.as_ref().ok()?.clone().into_uint()?;
+    let fee_to = results.get(1)?.as_ref().ok()?.clone().into_address()?;
+
+    pool.owner_fee_share = if fee_to.is_zero() {
+        0
+    } else {
+        owner_fee_share.as_u32()
+    };
+
+    pool.token_a_decimals = results.get(2)?.as_ref().ok()?.clone().into_uint()?.as_u32() as u8;
+    pool.token_b_decimals = results.get(3)?.as_ref().ok()?.clone().into_uint()?.as_u32() as u8;
+
+    Some(())
+}
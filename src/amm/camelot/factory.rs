@@ -0,0 +1,128 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::{
+    abi::RawLog,
+    prelude::{abigen, EthEvent},
+    providers::Middleware,
+    types::{Log, H160, H256},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    amm::{
+        factory::{AutomatedMarketMakerFactory, Factory},
+        uniswap_v2::factory::{PairCreatedFilter, PAIR_CREATED_EVENT_SIGNATURE},
+        AMM,
+    },
+    errors::AMMError,
+};
+
+use super::{batch_request, CamelotPool};
+
+abigen!(
+    ICamelotFactory,
+    r#"[
+        function allPairs(uint256 index) external view returns (address)
+        function allPairsLength() external view returns (uint256)
+        function ownerFeeShare() external view returns (uint256)
+        function feeTo() external view returns (address)
+    ]"#;
+);
+
+//The Camelot factory emits `PairCreated` with the same signature as the Uniswap V2 factory
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CamelotFactory {
+    pub address: H160,
+    pub creation_block: u64,
+}
+
+#[async_trait]
+impl AutomatedMarketMakerFactory for CamelotFactory {
+    fn address(&self) -> H160 {
+        self.address
+    }
+
+    fn creation_block(&self) -> u64 {
+        self.creation_block
+    }
+
+    fn amm_created_event_signature(&self) -> H256 {
+        PAIR_CREATED_EVENT_SIGNATURE
+    }
+
+    async fn new_amm_from_log<M: 'static + Middleware>(
+        &self,
+        log: Log,
+        middleware: Arc<M>,
+    ) -> Result<AMM, AMMError<M>> {
+        let pair_created_event = PairCreatedFilter::decode_log(&RawLog::from(log))?;
+
+        Ok(AMM::CamelotPool(
+            CamelotPool::new_from_address(pair_created_event.pair, middleware).await?,
+        ))
+    }
+
+    fn new_empty_amm_from_log(&self, log: Log) -> Result<AMM, ethers::abi::Error> {
+        let pair_created_event = PairCreatedFilter::decode_log(&RawLog::from(log))?;
+
+        Ok(AMM::CamelotPool(CamelotPool {
+            address: pair_created_event.pair,
+            token_a: pair_created_event.token_0,
+            token_b: pair_created_event.token_1,
+            ..Default::default()
+        }))
+    }
+
+    async fn get_all_amms<M: 'static + Middleware>(
+        &self,
+        to_block: Option<u64>,
+        middleware: Arc<M>,
+        step: u64,
+    ) -> Result<Vec<AMM>, AMMError<M>> {
+        if let Some(block) = to_block {
+            Factory::CamelotFactory(*self)
+                .get_all_pools_from_logs(self.creation_block, block, step, middleware)
+                .await
+        } else {
+            return Err(AMMError::BlockNumberNotFound);
+        }
+    }
+
+    //Both fee percents of each pair are synced along with the reserves
+    async fn populate_amm_data<M: Middleware>(
+        &self,
+        amms: &mut [AMM],
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        let step = 127; //Max batch size for call
+        for amm_chunk in amms.chunks_mut(step) {
+            let pools = amm_chunk
+                .iter_mut()
+                .filter_map(|amm| match amm {
+                    AMM::CamelotPool(pool) => Some(pool),
+                    _ => None,
+                })
+                .collect::<Vec<&mut CamelotPool>>();
+
+            batch_request::get_camelot_pool_data_batch_request(
+                pools,
+                block_number,
+                middleware.clone(),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl CamelotFactory {
+    pub fn new(address: H160, creation_block: u64) -> CamelotFactory {
+        CamelotFactory {
+            address,
+            creation_block,
+        }
+    }
+}
@@ -0,0 +1,417 @@
+pub mod batch_request;
+pub mod factory;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::{
+    abi::RawLog,
+    prelude::{abigen, EthEvent},
+    providers::Middleware,
+    types::{Log, H160, H256, U256},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    amm::{price::Price, u256_to_f64, AutomatedMarketMaker},
+    errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
+};
+
+use super::{solidly::get_y, uniswap_v2::SYNC_EVENT_SIGNATURE};
+
+abigen!(
+    ICamelotPair,
+    r#"[
+        function token0() external view returns (address)
+        function token1() external view returns (address)
+        function getReserves() external view returns (uint112 reserve0, uint112 reserve1, uint16 token0FeePercent, uint16 token1FeePercent)
+        function stableSwap() external view returns (bool)
+        function factory() external view returns (address)
+        function getAmountOut(uint256 amountIn, address tokenIn) external view returns (uint256)
+        event Sync(uint112 reserve0, uint112 reserve1)
+        event FeePercentUpdated(uint16 token0FeePercent, uint16 token1FeePercent)
+        event SetStableSwap(bool prevStableSwap, bool stableSwap)
+    ]"#;
+);
+
+pub const FEE_PERCENT_UPDATED_EVENT_SIGNATURE: H256 = H256([
+    164, 135, 123, 142, 203, 90, 0, 186, 39, 126, 75, 206, 238, 177, 135, 166, 105, 231, 17, 54,
+    73, 119, 77, 251, 234, 5, 194, 89, 206, 39, 241, 123,
+]);
+
+pub const SET_STABLE_SWAP_EVENT_SIGNATURE: H256 = H256([
+    182, 168, 103, 16, 189, 229, 58, 167, 251, 27, 56, 86, 39, 158, 42, 245, 180, 118, 213, 62, 45,
+    208, 144, 44, 241, 122, 9, 17, 181, 164, 58, 139,
+]);
+
+//Fee percents and fee shares are expressed in thousandths of a percent
+pub const FEE_DENOMINATOR: u32 = 100000;
+
+const PRECISION: U256 = U256([1000000000000000000, 0, 0, 0]);
+
+//Camelot pairs charge a separate fee for each input token and can be switched between the x * y = k and the x^3 * y + x * y^3 = k curves.
+//Referral fees are paid out of the fee of the input token, so they change the reserves of the pair but not the amount out.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CamelotPool {
+    pub address: H160,
+    pub token_a: H160,
+    pub token_a_decimals: u8,
+    pub token_b: H160,
+    pub token_b_decimals: u8,
+    pub reserve_0: U256,
+    pub reserve_1: U256,
+    pub token_0_fee_percent: u32,
+    pub token_1_fee_percent: u32,
+    pub stable: bool,
+    //Share of the fee that is sent to the fee recipient of the factory, zero when the factory has no fee recipient
+    pub owner_fee_share: u32,
+}
+
+#[async_trait]
+impl AutomatedMarketMaker for CamelotPool {
+    fn address(&self) -> H160 {
+        self.address
+    }
+
+    async fn sync<M: Middleware>(&mut self, middleware: Arc<M>) -> Result<(), AMMError<M>> {
+        let pair = ICamelotPair::new(self.address, middleware);
+        let (reserve_0, reserve_1, token_0_fee_percent, token_1_fee_percent) =
+            pair.get_reserves().call().await?;
+
+        self.reserve_0 = U256::from(reserve_0);
+        self.reserve_1 = U256::from(reserve_1);
+        self.token_0_fee_percent = token_0_fee_percent as u32;
+        self.token_1_fee_percent = token_1_fee_percent as u32;
+
+        Ok(())
+    }
+
+    async fn populate_data<M: Middleware>(
+        &mut self,
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        batch_request::get_camelot_pool_data_batch_request(vec![self], block_number, middleware)
+            .await
+    }
+
+    fn sync_on_event_signatures(&self) -> Vec<H256> {
+        vec![
+            SYNC_EVENT_SIGNATURE,
+            FEE_PERCENT_UPDATED_EVENT_SIGNATURE,
+            SET_STABLE_SWAP_EVENT_SIGNATURE,
+        ]
+    }
+
+    fn sync_from_log(&mut self, log: Log) -> Result<(), EventLogError> {
+        let event_signature = log.topics[0];
+
+        if event_signature == SYNC_EVENT_SIGNATURE {
+            let sync_event = SyncFilter::decode_log(&RawLog::from(log))?;
+
+            self.reserve_0 = U256::from(sync_event.reserve_0);
+            self.reserve_1 = U256::from(sync_event.reserve_1);
+        } else if event_signature == FEE_PERCENT_UPDATED_EVENT_SIGNATURE {
+            let fee_percent_updated_event =
+                FeePercentUpdatedFilter::decode_log(&RawLog::from(log))?;
+
+            self.token_0_fee_percent = fee_percent_updated_event.token_0_fee_percent as u32;
+            self.token_1_fee_percent = fee_percent_updated_event.token_1_fee_percent as u32;
+        } else if event_signature == SET_STABLE_SWAP_EVENT_SIGNATURE {
+            let set_stable_swap_event = SetStableSwapFilter::decode_log(&RawLog::from(log))?;
+
+            self.stable = set_stable_swap_event.stable_swap;
+        } else {
+            return Err(EventLogError::InvalidEventSignature);
+        }
+
+        Ok(())
+    }
+
+    //Calculates the marginal price of the base token in terms of the quote token
+    fn calculate_price(&self, base_token: H160) -> Result<Price, ArithmeticError> {
+        self.check_liquidity()?;
+
+        let x = u256_to_f64(self.reserve_0) / 10_f64.powi(self.token_a_decimals as i32);
+        let y = u256_to_f64(self.reserve_1) / 10_f64.powi(self.token_b_decimals as i32);
+
+        //-dy/dx of the invariant
+        let price = if self.stable {
+            (3.0 * x * x * y + y * y * y) / (x * x * x + 3.0 * x * y * y)
+        } else {
+            y / x
+        };
+
+        if base_token == self.token_a {
+            Price::from_f64(price)
+        } else {
+            Price::from_f64(1.0 / price)
+        }
+    }
+
+    fn tokens(&self) -> Vec<H160> {
+        vec![self.token_a, self.token_b]
+    }
+
+    fn simulate_swap(&self, token_in: H160, amount_in: U256) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        let amount_out = self.get_amount_out(amount_in, token_in)?;
+
+        tracing::trace!(?amount_out);
+
+        Ok(amount_out)
+    }
+
+    fn simulate_swap_mut(
+        &mut self,
+        token_in: H160,
+        amount_in: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        let amount_out = self.get_amount_out(amount_in, token_in)?;
+
+        tracing::trace!(?amount_out);
+        tracing::trace!(?self.reserve_0, ?self.reserve_1, "pool reserves before");
+
+        //The owner share of the fee is moved out of the pair, the swap is assumed to have no referrer
+        let owner_fee = self.fee_amount(amount_in, token_in) * U256::from(self.owner_fee_share)
+            / U256::from(FEE_DENOMINATOR);
+        let amount_in = amount_in - owner_fee;
+        if self.token_a == token_in {
+            self.reserve_0 += amount_in;
+            self.reserve_1 -= amount_out;
+        } else {
+            self.reserve_0 -= amount_out;
+            self.reserve_1 += amount_in;
+        }
+
+        tracing::trace!(?self.reserve_0, ?self.reserve_1, "pool reserves after");
+
+        Ok(amount_out)
+    }
+
+    fn swap_fee(&self, token_in: H160) -> f64 {
+        self.fee_percent(token_in) as f64 / FEE_DENOMINATOR as f64
+    }
+
+    fn token_decimals(&self) -> Vec<u8> {
+        vec![self.token_a_decimals, self.token_b_decimals]
+    }
+
+    fn get_token_out(&self, token_in: H160) -> H160 {
+        if self.token_a == token_in {
+            self.token_b
+        } else {
+            self.token_a
+        }
+    }
+}
+
+impl CamelotPool {
+    //Creates a new instance of the pool from the pair address, and syncs the pool data
+    pub async fn new_from_address<M: Middleware>(
+        pair_address: H160,
+        middleware: Arc<M>,
+    ) -> Result<Self, AMMError<M>> {
+        let mut pool = CamelotPool {
+            address: pair_address,
+            ..Default::default()
+        };
+
+        pool.populate_data(None, middleware).await?;
+
+        if !pool.data_is_populated() {
+            return Err(AMMError::PoolDataError);
+        }
+
+        Ok(pool)
+    }
+
+    pub fn data_is_populated(&self) -> bool {
+        !(self.token_a.is_zero()
+            || self.token_b.is_zero()
+            || self.reserve_0.is_zero()
+            || self.reserve_1.is_zero())
+    }
+
+    //Fee percent charged on the input token
+    pub fn fee_percent(&self, token_in: H160) -> u32 {
+        if token_in == self.token_a {
+            self.token_0_fee_percent
+        } else {
+            self.token_1_fee_percent
+        }
+    }
+
+    fn fee_amount(&self, amount_in: U256, token_in: H160) -> U256 {
+        amount_in * U256::from(self.fee_percent(token_in)) / U256::from(FEE_DENOMINATOR)
+    }
+
+    //A pair that was just created, or whose liquidity was removed, has no price and can not swap
+    fn check_liquidity(&self) -> Result<(), ArithmeticError> {
+        if self.reserve_0.is_zero() || self.reserve_1.is_zero() {
+            return Err(ArithmeticError::ZeroLiquidity(self.address));
+        }
+
+        Ok(())
+    }
+
+    //Mirrors `getAmountOut` of the pair contract
+    pub fn get_amount_out(
+        &self,
+        amount_in: U256,
+        token_in: H160,
+    ) -> Result<U256, SwapSimulationError> {
+        self.check_liquidity()?;
+
+        if amount_in.is_zero() {
+            return Ok(U256::zero());
+        }
+
+        let zero_for_one = token_in == self.token_a;
+
+        if self.stable {
+            let amount_in = amount_in - self.fee_amount(amount_in, token_in);
+            let decimals_0 = U256::exp10(self.token_a_decimals as usize);
+            let decimals_1 = U256::exp10(self.token_b_decimals as usize);
+
+            let xy = self.k(self.reserve_0, self.reserve_1);
+            let reserve_0 = self.reserve_0 * PRECISION / decimals_0;
+            let reserve_1 = self.reserve_1 * PRECISION / decimals_1;
+
+            let (reserve_in, reserve_out, decimals_in, decimals_out) = if zero_for_one {
+                (reserve_0, reserve_1, decimals_0, decimals_1)
+            } else {
+                (reserve_1, reserve_0, decimals_1, decimals_0)
+            };
+
+            let amount_in = amount_in * PRECISION / decimals_in;
+            let y = reserve_out - get_y(amount_in + reserve_in, xy, reserve_out)?;
+
+            Ok(y * decimals_out / PRECISION)
+        } else {
+            let (reserve_in, reserve_out) = if zero_for_one {
+                (self.reserve_0, self.reserve_1)
+            } else {
+                (self.reserve_1, self.reserve_0)
+            };
+
+            let amount_in_with_fee =
+                amount_in * U256::from(FEE_DENOMINATOR - self.fee_percent(token_in));
+
+            Ok(amount_in_with_fee * reserve_out
+                / (reserve_in * U256::from(FEE_DENOMINATOR) + amount_in_with_fee))
+        }
+    }
+
+    //Invariant of the pair, reserves of stable pairs are scaled to 18 decimals
+    pub fn k(&self, x: U256, y: U256) -> U256 {
+        if self.stable {
+            let x = x * PRECISION / U256::exp10(self.token_a_decimals as usize);
+            let y = y * PRECISION / U256::exp10(self.token_b_decimals as usize);
+            let a = x * y / PRECISION;
+            let b = x * x / PRECISION + y * y / PRECISION;
+
+            a * b / PRECISION
+        } else {
+            x * y
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, sync::Arc};
+
+    use ethers::{
+        providers::{Http, Provider},
+        types::{H160, U256},
+    };
+
+    use crate::amm::AutomatedMarketMaker;
+
+    use super::{CamelotPool, ICamelotPair};
+
+    fn weth_usdc_pool() -> CamelotPool {
+        CamelotPool {
+            address: H160::zero(),
+            token_a: H160::from_low_u64_be(1),
+            token_a_decimals: 18,
+            token_b: H160::from_low_u64_be(2),
+            token_b_decimals: 6,
+            reserve_0: U256::from(500) * U256::exp10(18),
+            reserve_1: U256::from(1_000_000) * U256::exp10(6),
+            token_0_fee_percent: 300,
+            token_1_fee_percent: 100,
+            stable: false,
+            owner_fee_share: 50000,
+        }
+    }
+
+    #[test]
+    fn test_simulate_swap_directional_fees() -> eyre::Result<()> {
+        let mut pool = weth_usdc_pool();
+
+        let amount_out = pool.simulate_swap(pool.token_a, U256::exp10(18))?;
+        assert_eq!(amount_out, U256::from(1990031876));
+
+        let amount_out = pool.simulate_swap(pool.token_b, U256::from(2000) * U256::exp10(6))?;
+        assert_eq!(amount_out, U256::from(997007978059836446_u64));
+
+        assert_eq!(pool.swap_fee(pool.token_a), 0.003);
+        assert_eq!(pool.swap_fee(pool.token_b), 0.001);
+
+        //Half of the fee leaves the pair
+        let amount_out = pool.simulate_swap_mut(pool.token_a, U256::exp10(18))?;
+        assert_eq!(amount_out, U256::from(1990031876));
+        assert_eq!(pool.reserve_0, U256::from_dec_str("500998500000000000000")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulate_swap_stable() -> eyre::Result<()> {
+        //Same curve as the stable solidly pairs, a fee of 20 is 0.02%
+        let pool = CamelotPool {
+            token_a_decimals: 6,
+            token_b_decimals: 18,
+            reserve_0: U256::from(1_000_000) * U256::exp10(6),
+            reserve_1: U256::from(1_050_000) * U256::exp10(18),
+            token_0_fee_percent: 20,
+            stable: true,
+            ..weth_usdc_pool()
+        };
+
+        let amount_out = pool.simulate_swap(pool.token_a, U256::from(1000) * U256::exp10(6))?;
+        assert_eq!(amount_out, U256::from_dec_str("999827318789763837201")?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_simulate_swap_matches_pair() -> eyre::Result<()> {
+        let rpc_endpoint = std::env::var("ARBITRUM_RPC_ENDPOINT")?;
+        let middleware = Arc::new(Provider::<Http>::try_from(rpc_endpoint)?);
+
+        //Camelot WETH/USDC.e
+        let pair_address = H160::from_str("0x84652bb2539513BAf36e225c930Fdd8eaa63CE27")?;
+        let pool = CamelotPool::new_from_address(pair_address, middleware.clone()).await?;
+
+        let pair = ICamelotPair::new(pair_address, middleware.clone());
+        for (token_in, amount_in) in [
+            (pool.token_a, U256::exp10(18)),
+            (pool.token_b, U256::from(1000) * U256::exp10(6)),
+        ] {
+            let expected_amount_out = pair.get_amount_out(amount_in, token_in).call().await?;
+
+            assert_eq!(
+                pool.simulate_swap(token_in, amount_in)?,
+                expected_amount_out
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,272 @@
+use std::sync::Arc;
+
+use ethers::{
+    abi::Token,
+    contract::Multicall,
+    providers::Middleware,
+    types::{BlockNumber, Bytes, H160, U256},
+};
+
+use crate::errors::AMMError;
+
+use super::{
+    crypto::{CurveCryptoPool, ICurveCryptoPool, ICurveTwoCryptoPool},
+    CurveStableSwapPool, ICurvePool, IErc20, A_PRECISION, MAX_COINS,
+};
+
+//Curve pools are batched through Multicall3 instead of a batch request contract, since the number of coins differs between pools
+
+pub async fn get_curve_stable_swap_pool_data_batch_request<M: Middleware>(
+    pool: &mut CurveStableSwapPool,
+    block_number: Option<u64>,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    let block = block_number
+        .map(BlockNumber::from)
+        .unwrap_or(BlockNumber::Latest);
+
+    let curve_pool = ICurvePool::new(pool.address, middleware.clone());
+    let mut multicall = Multicall::new(middleware.clone(), None).await?.block(block);
+
+    let tokens = get_coins(&mut multicall, &curve_pool).await?;
+
+    for (i, token) in tokens.iter().enumerate() {
+        multicall.add_call(IErc20::new(*token, middleware.clone()).decimals(), false);
+        multicall.add_call(curve_pool.balances(U256::from(i)), false);
+    }
+    add_amplification_coefficient_and_fee_calls(&mut multicall, &curve_pool);
+    multicall.add_call(curve_pool.admin_fee(), false);
+
+    let results = multicall.call_raw().await?;
+
+    let mut token_decimals = vec![];
+    let mut balances = vec![];
+    for i in 0..tokens.len() {
+        let decimals =
+            get_uint(&results, 2 * i).ok_or(AMMError::BatchRequestError(pool.address))?;
+        token_decimals.push(decimals.as_u32() as u8);
+
+        balances
+            .push(get_uint(&results, 2 * i + 1).ok_or(AMMError::BatchRequestError(pool.address))?);
+    }
+
+    let (a, fee) = decode_amplification_coefficient_and_fee(&results, 2 * tokens.len())
+        .ok_or(AMMError::BatchRequestError(pool.address))?;
+
+    pool.tokens = tokens;
+    pool.token_decimals = token_decimals;
+    pool.balances = balances;
+    pool.a = a;
+    pool.fee = fee;
+    pool.admin_fee = get_uint(&results, 2 * pool.tokens.len() + 3)
+        .ok_or(AMMError::BatchRequestError(pool.address))?;
+
+    Ok(())
+}
+
+pub async fn sync_curve_stable_swap_pool_batch_request<M: Middleware>(
+    pool: &mut CurveStableSwapPool,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    let curve_pool = ICurvePool::new(pool.address, middleware.clone());
+    let mut multicall = Multicall::new(middleware, None).await?;
+
+    for i in 0..pool.tokens.len() {
+        multicall.add_call(curve_pool.balances(U256::from(i)), false);
+    }
+    //The amplification coefficient can be ramped and the fee can be changed by the admin
+    add_amplification_coefficient_and_fee_calls(&mut multicall, &curve_pool);
+
+    let results = multicall.call_raw().await?;
+
+    let mut balances = vec![];
+    for i in 0..pool.tokens.len() {
+        balances.push(get_uint(&results, i).ok_or(AMMError::SyncError(pool.address))?);
+    }
+
+    let (a, fee) = decode_amplification_coefficient_and_fee(&results, pool.tokens.len())
+        .ok_or(AMMError::SyncError(pool.address))?;
+
+    pool.balances = balances;
+    pool.a = a;
+    pool.fee = fee;
+
+    Ok(())
+}
+
+pub async fn get_curve_crypto_pool_data_batch_request<M: Middleware>(
+    pool: &mut CurveCryptoPool,
+    block_number: Option<u64>,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    let block = block_number
+        .map(BlockNumber::from)
+        .unwrap_or(BlockNumber::Latest);
+
+    //Crypto pools share the coins and balances getters with stableswap pools
+    let curve_pool = ICurvePool::new(pool.address, middleware.clone());
+    let mut multicall = Multicall::new(middleware.clone(), None).await?.block(block);
+
+    let tokens = get_coins(&mut multicall, &curve_pool).await?;
+
+    for (i, token) in tokens.iter().enumerate() {
+        multicall.add_call(IErc20::new(*token, middleware.clone()).decimals(), false);
+        multicall.add_call(curve_pool.balances(U256::from(i)), false);
+    }
+    add_crypto_pool_state_calls(&mut multicall, pool.address, tokens.len(), middleware);
+
+    let results = multicall.call_raw().await?;
+
+    let mut token_decimals = vec![];
+    let mut balances = vec![];
+    for i in 0..tokens.len() {
+        let decimals =
+            get_uint(&results, 2 * i).ok_or(AMMError::BatchRequestError(pool.address))?;
+        token_decimals.push(decimals.as_u32() as u8);
+
+        balances
+            .push(get_uint(&results, 2 * i + 1).ok_or(AMMError::BatchRequestError(pool.address))?);
+    }
+
+    pool.tokens = tokens;
+    pool.token_decimals = token_decimals;
+    pool.balances = balances;
+
+    decode_crypto_pool_state(pool, &results, 2 * pool.tokens.len())
+        .ok_or(AMMError::BatchRequestError(pool.address))?;
+
+    Ok(())
+}
+
+pub async fn sync_curve_crypto_pool_batch_request<M: Middleware>(
+    pool: &mut CurveCryptoPool,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    let curve_pool = ICurvePool::new(pool.address, middleware.clone());
+    let mut multicall = Multicall::new(middleware.clone(), None).await?;
+
+    for i in 0..pool.tokens.len() {
+        multicall.add_call(curve_pool.balances(U256::from(i)), false);
+    }
+    //Exchanges and liquidity events can rebalance the price scale and change D and the fees are ramped by the admin
+    add_crypto_pool_state_calls(&mut multicall, pool.address, pool.tokens.len(), middleware);
+
+    let results = multicall.call_raw().await?;
+
+    let mut balances = vec![];
+    for i in 0..pool.tokens.len() {
+        balances.push(get_uint(&results, i).ok_or(AMMError::SyncError(pool.address))?);
+    }
+    pool.balances = balances;
+
+    decode_crypto_pool_state(pool, &results, pool.tokens.len())
+        .ok_or(AMMError::SyncError(pool.address))?;
+
+    Ok(())
+}
+
+//The number of coins is not exposed by the pool, coins(i) reverts once i is out of range
+async fn get_coins<M: Middleware>(
+    multicall: &mut Multicall<M>,
+    curve_pool: &ICurvePool<M>,
+) -> Result<Vec<H160>, AMMError<M>> {
+    for i in 0..MAX_COINS {
+        multicall.add_call(curve_pool.coins(U256::from(i)), true);
+    }
+
+    let tokens = multicall
+        .call_raw()
+        .await?
+        .into_iter()
+        .map_while(|result| result.ok().and_then(|token| token.into_address()))
+        .collect::<Vec<H160>>();
+
+    multicall.clear_calls();
+
+    Ok(tokens)
+}
+
+//Adds calls for A, gamma, D, mid_fee, out_fee, fee_gamma followed by the price scale and price oracle of each coin after coins[0]
+fn add_crypto_pool_state_calls<M: Middleware>(
+    multicall: &mut Multicall<M>,
+    address: H160,
+    n_coins: usize,
+    middleware: Arc<M>,
+) {
+    let crypto_pool = ICurveCryptoPool::new(address, middleware.clone());
+
+    multicall.add_call(crypto_pool.a(), false);
+    multicall.add_call(crypto_pool.gamma(), false);
+    multicall.add_call(crypto_pool.d(), false);
+    multicall.add_call(crypto_pool.mid_fee(), false);
+    multicall.add_call(crypto_pool.out_fee(), false);
+    multicall.add_call(crypto_pool.fee_gamma(), false);
+
+    if n_coins == 2 {
+        let two_crypto_pool = ICurveTwoCryptoPool::new(address, middleware);
+        multicall.add_call(two_crypto_pool.price_scale(), false);
+        multicall.add_call(two_crypto_pool.price_oracle(), false);
+    } else {
+        for k in 0..n_coins.saturating_sub(1) {
+            multicall.add_call(crypto_pool.price_scale(U256::from(k)), false);
+            multicall.add_call(crypto_pool.price_oracle(U256::from(k)), false);
+        }
+    }
+}
+
+fn decode_crypto_pool_state(
+    pool: &mut CurveCryptoPool,
+    results: &[Result<Token, Bytes>],
+    offset: usize,
+) -> Option<()> {
+    pool.a = get_uint(results, offset)?;
+    pool.gamma = get_uint(results, offset + 1)?;
+    pool.d = get_uint(results, offset + 2)?;
+    pool.mid_fee = get_uint(results, offset + 3)?;
+    pool.out_fee = get_uint(results, offset + 4)?;
+    pool.fee_gamma = get_uint(results, offset + 5)?;
+
+    let mut price_scale = vec![];
+    let mut price_oracle = vec![];
+    for k in 0..pool.tokens.len().saturating_sub(1) {
+        price_scale.push(get_uint(results, offset + 6 + 2 * k)?);
+        price_oracle.push(get_uint(results, offset + 7 + 2 * k)?);
+    }
+
+    pool.price_scale = price_scale;
+    pool.price_oracle = price_oracle;
+
+    Some(())
+}
+
+//Older pools do not implement A_precise, so both values are requested and A is scaled by the precision when A_precise reverts
+fn add_amplification_coefficient_and_fee_calls<M: Middleware>(
+    multicall: &mut Multicall<M>,
+    curve_pool: &ICurvePool<M>,
+) {
+    multicall.add_call(curve_pool.a_precise(), true);
+    multicall.add_call(curve_pool.a(), true);
+    multicall.add_call(curve_pool.fee(), false);
+}
+
+fn decode_amplification_coefficient_and_fee(
+    results: &[Result<Token, Bytes>],
+    offset: usize,
+) -> Option<(U256, U256)> {
+    let a = match get_uint(results, offset) {
+        Some(a_precise) => a_precise,
+        None => get_uint(results, offset + 1)? * A_PRECISION,
+    };
+
+    let fee = get_uint(results, offset + 2)?;
+
+    Some((a, fee))
+}
+
+fn get_uint(results: &[Result<Token, Bytes>], index: usize) -> Option<U256> {
+    results
+        .get(index)?
+        .as_ref()
+        .ok()
+        .and_then(|token| token.clone().into_uint())
+}
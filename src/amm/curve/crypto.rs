@@ -0,0 +1,637 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::{
+    abi::RawLog,
+    prelude::{abigen, EthEvent},
+    providers::Middleware,
+    types::{Log, H160, H256, U256},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    amm::{price::Price, AutomatedMarketMaker},
+    errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
+};
+
+use super::batch_request;
+
+abigen!(
+    ICurveCryptoPool,
+    r#"[
+        function coins(uint256 i) external view returns (address)
+        function balances(uint256 i) external view returns (uint256)
+        function price_scale(uint256 k) external view returns (uint256)
+        function price_oracle(uint256 k) external view returns (uint256)
+        function A() external view returns (uint256)
+        function gamma() external view returns (uint256)
+        function D() external view returns (uint256)
+        function mid_fee() external view returns (uint256)
+        function out_fee() external view returns (uint256)
+        function fee_gamma() external view returns (uint256)
+        event TokenExchange(address indexed buyer, uint256 sold_id, uint256 tokens_sold, uint256 bought_id, uint256 tokens_bought)
+    ]"#;
+
+    //Two coin crypto pools only store a single price, so the price getters do not take an index
+    ICurveTwoCryptoPool,
+    r#"[
+        function price_scale() external view returns (uint256)
+        function price_oracle() external view returns (uint256)
+    ]"#;
+);
+
+pub const CRYPTO_TOKEN_EXCHANGE_EVENT_SIGNATURE: H256 = H256([
+    178, 231, 106, 233, 151, 97, 220, 19, 110, 89, 141, 74, 98, 155, 179, 71, 236, 203, 149, 50,
+    165, 248, 187, 215, 46, 24, 70, 124, 60, 52, 204, 152,
+]);
+
+// A is stored multiplied by N^N and by the A multiplier
+pub const A_MULTIPLIER: U256 = U256([10000, 0, 0, 0]);
+// Prices, gamma and fee_gamma are denominated in 1e18
+pub const PRECISION: U256 = U256([1000000000000000000, 0, 0, 0]);
+// mid_fee and out_fee are denominated in 1e10
+pub const FEE_DENOMINATOR: U256 = U256([10000000000, 0, 0, 0]);
+// Maximum number of newton iterations used by the pool contract
+const MAX_ITERATIONS: usize = 255;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CurveCryptoPool {
+    pub address: H160,
+    pub tokens: Vec<H160>,
+    pub token_decimals: Vec<u8>,
+    pub balances: Vec<U256>,
+    pub price_scale: Vec<U256>, // price of coins[k + 1] denominated in coins[0], scaled by 1e18
+    pub price_oracle: Vec<U256>, // ema price of coins[k + 1] denominated in coins[0], scaled by 1e18
+    pub a: U256,                 // A * N^N * A_MULTIPLIER
+    pub gamma: U256,
+    pub d: U256,
+    pub mid_fee: U256,
+    pub out_fee: U256,
+    pub fee_gamma: U256,
+}
+
+#[async_trait]
+impl AutomatedMarketMaker for CurveCryptoPool {
+    fn address(&self) -> H160 {
+        self.address
+    }
+
+    async fn sync<M: Middleware>(&mut self, middleware: Arc<M>) -> Result<(), AMMError<M>> {
+        batch_request::sync_curve_crypto_pool_batch_request(self, middleware).await
+    }
+
+    fn sync_on_event_signatures(&self) -> Vec<H256> {
+        vec![CRYPTO_TOKEN_EXCHANGE_EVENT_SIGNATURE]
+    }
+
+    //Exchanges can also rebalance the price scale of the pool, which can not be reproduced from the event.
+    //Balances and D are kept exact while the price scale is unchanged, pools should be resynced periodically to pick up repegs
+    fn sync_from_log(&mut self, log: Log) -> Result<(), EventLogError> {
+        let event_signature = log.topics[0];
+
+        if event_signature == CRYPTO_TOKEN_EXCHANGE_EVENT_SIGNATURE {
+            let token_exchange_event = TokenExchangeFilter::decode_log(&RawLog::from(log))?;
+
+            let i = token_exchange_event.sold_id.as_usize();
+            let j = token_exchange_event.bought_id.as_usize();
+
+            if i < self.balances.len() && j < self.balances.len() {
+                self.balances[i] += token_exchange_event.tokens_sold;
+                self.balances[j] -= token_exchange_event.tokens_bought;
+
+                match newton_d(self.a, self.gamma, &self.xp()) {
+                    Ok(d) => self.d = d,
+                    Err(_) => {
+                        tracing::warn!(?self.address, "could not recalculate D, pool state may be stale")
+                    }
+                }
+            }
+        } else {
+            return Err(EventLogError::InvalidEventSignature);
+        }
+
+        Ok(())
+    }
+
+    fn tokens(&self) -> Vec<H160> {
+        self.tokens.clone()
+    }
+
+    //Calculates the price of the base token denominated in the token returned by get_token_out(base_token), using the price oracle of the pool
+    fn calculate_price(&self, base_token: H160) -> Result<Price, ArithmeticError> {
+        self.check_liquidity()?;
+
+        let quote_token = self.get_token_out(base_token);
+
+        let i = self
+            .token_index(base_token)
+            .ok_or(ArithmeticError::TokenNotInPool(base_token))?;
+        let j = self
+            .token_index(quote_token)
+            .ok_or(ArithmeticError::TokenNotInPool(quote_token))?;
+
+        let base_price = self.oracle_price(i)?;
+        let quote_price = self.oracle_price(j)?;
+
+        if quote_price == 0.0 {
+            return Err(ArithmeticError::YIsZero);
+        }
+
+        Price::from_f64(base_price / quote_price)
+    }
+
+    async fn populate_data<M: Middleware>(
+        &mut self,
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        batch_request::get_curve_crypto_pool_data_batch_request(self, block_number, middleware)
+            .await?;
+
+        tracing::trace!(?self.address, ?self.tokens, ?self.balances, ?self.price_scale, ?self.d, "populated pool data");
+
+        Ok(())
+    }
+
+    fn simulate_swap(&self, token_in: H160, amount_in: U256) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        self.calculate_amount_out(token_in, self.get_token_out(token_in), amount_in)
+    }
+
+    fn simulate_swap_mut(
+        &mut self,
+        token_in: H160,
+        amount_in: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        let token_out = self.get_token_out(token_in);
+
+        let i = self
+            .token_index(token_in)
+            .ok_or(SwapSimulationError::TokenNotInPool(token_in))?;
+        let j = self
+            .token_index(token_out)
+            .ok_or(SwapSimulationError::TokenNotInPool(token_out))?;
+
+        tracing::trace!(?self.balances, "pool balances before");
+
+        let amount_out = self.get_dy(i, j, amount_in)?;
+
+        self.balances[i] += amount_in;
+        self.balances[j] -= amount_out;
+        self.d = newton_d(self.a, self.gamma, &self.xp())?;
+
+        tracing::trace!(?self.balances, "pool balances after");
+
+        Ok(amount_out)
+    }
+
+    //Dynamic fee at the current balances of the pool
+    fn swap_fee(&self, _token_in: H160) -> f64 {
+        self.fee(&self.xp()).as_u128() as f64 / FEE_DENOMINATOR.as_u128() as f64
+    }
+
+    fn token_decimals(&self) -> Vec<u8> {
+        self.token_decimals.clone()
+    }
+
+    //Returns the first token in the pool that is not the token in. To swap into a specific token of a pool with more than two tokens, use calculate_amount_out
+    fn get_token_out(&self, token_in: H160) -> H160 {
+        self.tokens
+            .iter()
+            .find(|token| **token != token_in)
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+impl CurveCryptoPool {
+    //Creates a new instance of the pool from the pool address, and syncs the pool data
+    pub async fn new_from_address<M: Middleware>(
+        pool_address: H160,
+        middleware: Arc<M>,
+    ) -> Result<Self, AMMError<M>> {
+        let mut pool = CurveCryptoPool {
+            address: pool_address,
+            ..Default::default()
+        };
+
+        pool.populate_data(None, middleware).await?;
+
+        if !pool.data_is_populated() {
+            return Err(AMMError::PoolDataError);
+        }
+
+        Ok(pool)
+    }
+
+    pub fn data_is_populated(&self) -> bool {
+        !(self.tokens.len() < 2
+            || self.tokens.len() != self.balances.len()
+            || self.price_scale.len() != self.tokens.len() - 1
+            || self.a.is_zero()
+            || self.gamma.is_zero()
+            || self.d.is_zero())
+    }
+
+    pub fn token_index(&self, token: H160) -> Option<usize> {
+        self.tokens.iter().position(|t| *t == token)
+    }
+
+    //Calculates the amount out for a swap between any two tokens in the pool, matching the output of get_dy on the pool contract
+    pub fn calculate_amount_out(
+        &self,
+        token_in: H160,
+        token_out: H160,
+        amount_in: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        let i = self
+            .token_index(token_in)
+            .ok_or(SwapSimulationError::TokenNotInPool(token_in))?;
+        let j = self
+            .token_index(token_out)
+            .ok_or(SwapSimulationError::TokenNotInPool(token_out))?;
+
+        self.get_dy(i, j, amount_in)
+    }
+
+    fn get_dy(&self, i: usize, j: usize, amount_in: U256) -> Result<U256, SwapSimulationError> {
+        self.check_liquidity()?;
+
+        if amount_in.is_zero() || i == j {
+            return Ok(U256::zero());
+        }
+
+        let mut balances = self.balances.clone();
+        balances[i] += amount_in;
+
+        let mut xp = self.scale_balances(&balances);
+        let y = newton_y(self.a, self.gamma, &xp, self.d, j)?;
+
+        if xp[j] <= y + 1 {
+            return Err(SwapSimulationError::LiquidityUnderflow);
+        }
+        let mut dy = xp[j] - y - 1;
+        xp[j] = y;
+
+        if j > 0 {
+            dy = dy * PRECISION / self.price_scale[j - 1];
+        }
+        dy /= precision(self.token_decimals[j]);
+
+        let fee = self.fee(&xp);
+        dy -= fee * dy / FEE_DENOMINATOR;
+
+        Ok(dy)
+    }
+
+    //Dynamic fee, moves from mid_fee to out_fee as the pool moves away from balance
+    fn fee(&self, xp: &[U256]) -> U256 {
+        let f = reduction_coefficient(xp, self.fee_gamma);
+        (self.mid_fee * f + self.out_fee * (PRECISION - f)) / PRECISION
+    }
+
+    //The oracle keeps a price after the liquidity is removed, but a pool with an empty balance or no invariant can not swap
+    fn check_liquidity(&self) -> Result<(), ArithmeticError> {
+        if self.d.is_zero()
+            || self.balances.is_empty()
+            || self.balances.iter().any(|balance| balance.is_zero())
+        {
+            return Err(ArithmeticError::ZeroLiquidity(self.address));
+        }
+
+        Ok(())
+    }
+
+    //Balances normalized to 18 decimals and denominated in coins[0]
+    fn xp(&self) -> Vec<U256> {
+        self.scale_balances(&self.balances)
+    }
+
+    fn scale_balances(&self, balances: &[U256]) -> Vec<U256> {
+        balances
+            .iter()
+            .enumerate()
+            .map(|(k, balance)| {
+                let balance = *balance * precision(self.token_decimals[k]);
+
+                if k > 0 {
+                    balance * self.price_scale[k - 1] / PRECISION
+                } else {
+                    balance
+                }
+            })
+            .collect()
+    }
+
+    fn oracle_price(&self, k: usize) -> Result<f64, ArithmeticError> {
+        if k == 0 {
+            return Ok(1.0);
+        }
+
+        let price = self
+            .price_oracle
+            .get(k - 1)
+            .ok_or(ArithmeticError::InvalidTokenIndex)?;
+
+        Ok(price.as_u128() as f64 / PRECISION.as_u128() as f64)
+    }
+}
+
+//Multiplier used to normalize a balance with the given decimals to 18 decimals
+fn precision(decimals: u8) -> U256 {
+    U256::exp10(18 - decimals as usize)
+}
+
+fn reduction_coefficient(x: &[U256], fee_gamma: U256) -> U256 {
+    let n = U256::from(x.len());
+    let s = x.iter().fold(U256::zero(), |acc, x| acc + x);
+
+    if s.is_zero() {
+        return U256::zero();
+    }
+
+    let mut k = PRECISION;
+    for x_i in x.iter() {
+        k = k * n * x_i / s;
+    }
+
+    if !fee_gamma.is_zero() {
+        k = fee_gamma * PRECISION / (fee_gamma + PRECISION - k);
+    }
+
+    k
+}
+
+fn geometric_mean(x: &[U256]) -> Result<U256, SwapSimulationError> {
+    let n = U256::from(x.len());
+
+    let mut d = x[0];
+    for _ in 0..MAX_ITERATIONS {
+        let d_prev = d;
+
+        let mut tmp = PRECISION;
+        for x_i in x.iter() {
+            tmp = tmp * x_i / d;
+        }
+
+        d = d * ((n - 1) * PRECISION + tmp) / (n * PRECISION);
+
+        let diff = abs_diff(d, d_prev);
+        if diff <= U256::one() || diff * PRECISION < d {
+            return Ok(d);
+        }
+    }
+
+    Err(SwapSimulationError::DidNotConverge)
+}
+
+//Calculates the invariant D from the balances normalized by the price scale
+pub fn newton_d(ann: U256, gamma: U256, x_unsorted: &[U256]) -> Result<U256, SwapSimulationError> {
+    let n = U256::from(x_unsorted.len());
+
+    if x_unsorted.iter().any(|x| x.is_zero()) {
+        return Err(SwapSimulationError::LiquidityUnderflow);
+    }
+
+    let mut x = x_unsorted.to_vec();
+    x.sort_by(|a, b| b.cmp(a));
+
+    let mut d = n * geometric_mean(&x)?;
+    let s = x.iter().fold(U256::zero(), |acc, x| acc + x);
+
+    for _ in 0..MAX_ITERATIONS {
+        let d_prev = d;
+
+        let mut k0 = PRECISION;
+        for x_i in x.iter() {
+            k0 = k0 * x_i * n / d;
+        }
+
+        let mut g1k0 = gamma + PRECISION;
+        g1k0 = if g1k0 > k0 {
+            g1k0 - k0 + 1
+        } else {
+            k0 - g1k0 + 1
+        };
+
+        // D / (A * N^N) * g1k0^2 / gamma^2
+        let mul1 = PRECISION * d / gamma * g1k0 / gamma * g1k0 * A_MULTIPLIER / ann;
+        // 2 * N * K0 / g1k0
+        let mul2 = U256::from(2) * PRECISION * n * k0 / g1k0;
+
+        let neg_fprime = (s + s * mul2 / PRECISION) + mul1 * n / k0 - mul2 * d / PRECISION;
+
+        let d_plus = d * (neg_fprime + s) / neg_fprime;
+        let mut d_minus = d * d / neg_fprime;
+        if PRECISION > k0 {
+            d_minus += d * (mul1 / neg_fprime) / PRECISION * (PRECISION - k0) / k0;
+        } else {
+            d_minus -= d * (mul1 / neg_fprime) / PRECISION * (k0 - PRECISION) / k0;
+        }
+
+        d = if d_plus > d_minus {
+            d_plus - d_minus
+        } else {
+            (d_minus - d_plus) / 2
+        };
+
+        let diff = abs_diff(d, d_prev);
+        if diff * U256::exp10(14) < U256::exp10(16).max(d) {
+            return Ok(d);
+        }
+    }
+
+    Err(SwapSimulationError::DidNotConverge)
+}
+
+//Calculates the normalized balance of coin i that satisfies the invariant D given the other balances
+pub fn newton_y(
+    ann: U256,
+    gamma: U256,
+    x: &[U256],
+    d: U256,
+    i: usize,
+) -> Result<U256, SwapSimulationError> {
+    let n_coins = x.len();
+    let n = U256::from(n_coins);
+
+    let mut x_sorted = x.to_vec();
+    x_sorted[i] = U256::zero();
+    x_sorted.sort_by(|a, b| b.cmp(a));
+
+    if x_sorted[..n_coins - 1].iter().any(|x| x.is_zero()) {
+        return Err(SwapSimulationError::LiquidityUnderflow);
+    }
+
+    let convergence_limit = (x_sorted[0] / U256::exp10(14))
+        .max(d / U256::exp10(14))
+        .max(U256::from(100));
+
+    let mut y = d / n;
+    let mut k0_i = PRECISION;
+    let mut s_i = U256::zero();
+
+    //Small balances first
+    for j in 2..=n_coins {
+        let x_j = x_sorted[n_coins - j];
+        y = y * d / (x_j * n);
+        s_i += x_j;
+    }
+
+    //Large balances first
+    for x_j in x_sorted.iter().take(n_coins - 1) {
+        k0_i = k0_i * x_j * n / d;
+    }
+
+    for _ in 0..MAX_ITERATIONS {
+        let y_prev = y;
+
+        let k0 = k0_i * y * n / d;
+        let s = s_i + y;
+
+        let mut g1k0 = gamma + PRECISION;
+        g1k0 = if g1k0 > k0 {
+            g1k0 - k0 + 1
+        } else {
+            k0 - g1k0 + 1
+        };
+
+        // D / (A * N^N) * g1k0^2 / gamma^2
+        let mul1 = PRECISION * d / gamma * g1k0 / gamma * g1k0 * A_MULTIPLIER / ann;
+        // 2 * K0 / g1k0
+        let mul2 = PRECISION + U256::from(2) * PRECISION * k0 / g1k0;
+
+        let mut yfprime = PRECISION * y + s * mul2 + mul1;
+        let dyfprime = d * mul2;
+        if yfprime < dyfprime {
+            y = y_prev / 2;
+            continue;
+        } else {
+            yfprime -= dyfprime;
+        }
+        let fprime = yfprime / y;
+
+        let mut y_minus = mul1 / fprime;
+        let y_plus = (yfprime + PRECISION * d) / fprime + y_minus * PRECISION / k0;
+        y_minus += PRECISION * s / fprime;
+
+        y = if y_plus < y_minus {
+            y_prev / 2
+        } else {
+            y_plus - y_minus
+        };
+
+        let diff = abs_diff(y, y_prev);
+        if diff < convergence_limit.max(y / U256::exp10(14)) {
+            let frac = y * PRECISION / d;
+
+            //The pool reverts on unsafe values for y
+            if frac < U256::exp10(16) - 1 || frac > U256::exp10(20) + 1 {
+                return Err(SwapSimulationError::LiquidityUnderflow);
+            }
+
+            return Ok(y);
+        }
+    }
+
+    Err(SwapSimulationError::DidNotConverge)
+}
+
+fn abs_diff(a: U256, b: U256) -> U256 {
+    if a > b {
+        a - b
+    } else {
+        b - a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use ethers::types::{H160, U256};
+
+    use crate::amm::AutomatedMarketMaker;
+
+    use super::{newton_d, CurveCryptoPool};
+
+    fn tricrypto() -> eyre::Result<CurveCryptoPool> {
+        //USDT, WBTC, WETH balanced at 30000 USDT/WBTC and 2000 USDT/WETH
+        let price_scale = vec![
+            U256::from_dec_str("30000000000000000000000")?,
+            U256::from_dec_str("2000000000000000000000")?,
+        ];
+
+        let mut pool = CurveCryptoPool {
+            address: H160::from_str("0xD51a44d3FaE010294C616388b506AcdA1bfAAE46")?,
+            tokens: vec![
+                H160::from_str("0xdAC17F958D2ee523a2206206994597C13D831ec7")?,
+                H160::from_str("0x2260FAC5E5542a773Aa44fBCfeDf7C193bc2C599")?,
+                H160::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2")?,
+            ],
+            token_decimals: vec![6, 8, 18],
+            balances: vec![
+                U256::from_dec_str("30000000000000")?,
+                U256::from_dec_str("100000000000")?,
+                U256::from_dec_str("15000000000000000000000")?,
+            ],
+            price_oracle: price_scale.clone(),
+            price_scale,
+            a: U256::from(1707629),
+            gamma: U256::from_dec_str("11809167828997")?,
+            mid_fee: U256::from(3000000),
+            out_fee: U256::from(30000000),
+            fee_gamma: U256::from_dec_str("500000000000000")?,
+            ..Default::default()
+        };
+
+        pool.d = newton_d(pool.a, pool.gamma, &pool.xp())?;
+
+        Ok(pool)
+    }
+
+    #[test]
+    fn test_simulate_swap() -> eyre::Result<()> {
+        let pool = tricrypto()?;
+
+        //Swap 1 WETH for USDT, the output should be close to the 2000 USDT price scale minus the fee
+        let amount_in = U256::from_dec_str("1000000000000000000")?;
+        let amount_out = pool.calculate_amount_out(pool.tokens[2], pool.tokens[0], amount_in)?;
+
+        assert!(amount_out < U256::from(2000000000));
+        assert!(amount_out > U256::from(1990000000));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulate_swap_mut() -> eyre::Result<()> {
+        let mut pool = tricrypto()?;
+
+        let amount_in = U256::from(100000000000_u64);
+        let amount_out = pool.simulate_swap(pool.tokens[0], amount_in)?;
+        let amount_out_mut = pool.simulate_swap_mut(pool.tokens[0], amount_in)?;
+        assert_eq!(amount_out, amount_out_mut);
+
+        let second_amount_out = pool.simulate_swap(pool.tokens[0], amount_in)?;
+        assert!(second_amount_out < amount_out);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_calculate_price() -> eyre::Result<()> {
+        let pool = tricrypto()?;
+
+        let weth_usdt_price = pool.calculate_price(pool.tokens[2])?.to_f64();
+        assert!((weth_usdt_price - 2000.0).abs() < 1e-9);
+
+        let usdt_wbtc_price = pool.calculate_price(pool.tokens[0])?.to_f64();
+        assert!((usdt_wbtc_price - 1.0 / 30000.0).abs() < 1e-12);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::{
+    abi::RawLog,
+    prelude::{abigen, EthEvent},
+    providers::Middleware,
+    types::{Log, H160, H256},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    amm::{
+        factory::{AutomatedMarketMakerFactory, Factory},
+        AutomatedMarketMaker, AMM,
+    },
+    errors::AMMError,
+};
+
+use super::CurveStableSwapPool;
+
+abigen!(
+    ICurveRegistry,
+    r#"[
+        event PoolAdded(address indexed pool, bytes rate_method_id)
+    ]"#;
+);
+
+//Emitted by the Curve registries that are listed in the Curve AddressProvider when a new pool is registered
+pub const POOL_ADDED_EVENT_SIGNATURE: H256 = H256([
+    228, 133, 193, 100, 121, 171, 112, 146, 192, 179, 252, 70, 73, 132, 60, 6, 190, 127, 7, 33,
+    148, 103, 82, 97, 89, 12, 132, 71, 58, 176, 174, 169,
+]);
+
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CurveFactory {
+    pub address: H160,
+    pub creation_block: u64,
+}
+
+#[async_trait]
+impl AutomatedMarketMakerFactory for CurveFactory {
+    fn address(&self) -> H160 {
+        self.address
+    }
+
+    fn creation_block(&self) -> u64 {
+        self.creation_block
+    }
+
+    fn amm_created_event_signature(&self) -> H256 {
+        POOL_ADDED_EVENT_SIGNATURE
+    }
+
+    async fn new_amm_from_log<M: 'static + Middleware>(
+        &self,
+        log: Log,
+        middleware: Arc<M>,
+    ) -> Result<AMM, AMMError<M>> {
+        let pool_added_event = PoolAddedFilter::decode_log(&RawLog::from(log))?;
+
+        Ok(AMM::CurveStableSwapPool(
+            CurveStableSwapPool::new_from_address(pool_added_event.pool, middleware).await?,
+        ))
+    }
+
+    fn new_empty_amm_from_log(&self, log: Log) -> Result<AMM, ethers::abi::Error> {
+        let pool_added_event = PoolAddedFilter::decode_log(&RawLog::from(log))?;
+
+        Ok(AMM::CurveStableSwapPool(CurveStableSwapPool {
+            address: pool_added_event.pool,
+            ..Default::default()
+        }))
+    }
+
+    async fn get_all_amms<M: 'static + Middleware>(
+        &self,
+        to_block: Option<u64>,
+        middleware: Arc<M>,
+        step: u64,
+    ) -> Result<Vec<AMM>, AMMError<M>> {
+        if let Some(block) = to_block {
+            Factory::CurveFactory(*self)
+                .get_all_pools_from_logs(self.creation_block, block, step, middleware)
+                .await
+        } else {
+            return Err(AMMError::BlockNumberNotFound);
+        }
+    }
+
+    async fn populate_amm_data<M: Middleware>(
+        &self,
+        amms: &mut [AMM],
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        for amm in amms {
+            if let AMM::CurveStableSwapPool(_) | AMM::CurveCryptoPool(_) | AMM::CurveMetaPool(_) =
+                amm
+            {
+                amm.populate_data(block_number, middleware.clone()).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl CurveFactory {
+    pub fn new(address: H160, creation_block: u64) -> CurveFactory {
+        CurveFactory {
+            address,
+            creation_block,
+        }
+    }
+}
@@ -0,0 +1,899 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::{
+    abi::RawLog,
+    prelude::{abigen, EthEvent},
+    providers::Middleware,
+    types::{Log, H160, H256, U256},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    amm::{price::Price, u256_to_f64, AutomatedMarketMaker},
+    errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
+};
+
+use super::{
+    get_d, get_y, get_y_d, liquidity_event_signatures, rate, CurveStableSwapPool,
+    TokenExchangeFilter, A_PRECISION, FEE_DENOMINATOR, PRECISION, TOKEN_EXCHANGE_EVENT_SIGNATURE,
+};
+
+abigen!(
+    ICurveMetaPool,
+    r#"[
+        function base_pool() external view returns (address)
+        function get_dy_underlying(int128 i, int128 j, uint256 dx) external view returns (uint256)
+        event TokenExchangeUnderlying(address indexed buyer, int128 sold_id, uint256 tokens_sold, int128 bought_id, uint256 tokens_bought)
+    ]"#;
+
+    ICurveLpToken,
+    r#"[
+        function totalSupply() external view returns (uint256)
+    ]"#;
+);
+
+pub const TOKEN_EXCHANGE_UNDERLYING_EVENT_SIGNATURE: H256 = H256([
+    208, 19, 202, 35, 231, 122, 101, 0, 60, 44, 101, 156, 84, 66, 192, 12, 128, 83, 113, 183, 252,
+    30, 189, 76, 32, 108, 65, 209, 83, 107, 217, 11,
+]);
+
+//A metapool pairs a coin with the LP token of a base pool, i.e. FRAX/3CRV. Besides the two coins of the pool, the coin
+//can be swapped for the underlying coins of the base pool, which deposits into or withdraws from the base pool.
+//The state of the base pool is kept alongside the metapool and is synced from the logs of the base pool, since the
+//LP token is priced at the virtual price of the base pool.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CurveMetaPool {
+    pub pool: CurveStableSwapPool, // the metapool, coins are [coin, base pool LP token]
+    pub base_pool: CurveStableSwapPool,
+    pub base_lp_supply: U256, // total supply of the base pool LP token
+    //LP tokens minted by the base pool for an underlying exchange into the coin, the exchange is replayed once the
+    //TokenExchangeUnderlying log of the metapool follows the AddLiquidity log of the base pool
+    pub base_lp_minted: U256,
+}
+
+#[async_trait]
+impl AutomatedMarketMaker for CurveMetaPool {
+    fn address(&self) -> H160 {
+        self.pool.address
+    }
+
+    async fn sync<M: Middleware>(&mut self, middleware: Arc<M>) -> Result<(), AMMError<M>> {
+        self.pool.sync(middleware.clone()).await?;
+        self.base_pool.sync(middleware.clone()).await?;
+        self.base_lp_supply = self.get_base_lp_supply(None, middleware).await?;
+
+        Ok(())
+    }
+
+    fn sync_on_event_signatures(&self) -> Vec<H256> {
+        let mut event_signatures = self.pool.sync_on_event_signatures();
+        event_signatures.push(TOKEN_EXCHANGE_UNDERLYING_EVENT_SIGNATURE);
+
+        for event_signature in self.base_pool.sync_on_event_signatures() {
+            if !event_signatures.contains(&event_signature) {
+                event_signatures.push(event_signature);
+            }
+        }
+
+        event_signatures
+    }
+
+    //Logs of the base pool are applied to the base pool, which refreshes the virtual price of the LP token
+    fn sync_from_log(&mut self, log: Log) -> Result<(), EventLogError> {
+        if log.address == self.base_pool.address {
+            return self.sync_base_pool_from_log(log);
+        }
+
+        let event_signature = log.topics[0];
+
+        if event_signature == TOKEN_EXCHANGE_EVENT_SIGNATURE {
+            let token_exchange_event = TokenExchangeFilter::decode_log(&RawLog::from(log))?;
+
+            if self
+                .exchange(
+                    token_exchange_event.sold_id as usize,
+                    token_exchange_event.bought_id as usize,
+                    token_exchange_event.tokens_sold,
+                )
+                .is_err()
+            {
+                tracing::warn!(?self.pool.address, "could not replay token exchange, pool balances may be stale");
+            }
+        } else if event_signature == TOKEN_EXCHANGE_UNDERLYING_EVENT_SIGNATURE {
+            let token_exchange_event =
+                TokenExchangeUnderlyingFilter::decode_log(&RawLog::from(log))?;
+
+            if self
+                .replay_exchange_underlying(
+                    token_exchange_event.sold_id as usize,
+                    token_exchange_event.bought_id as usize,
+                    token_exchange_event.tokens_sold,
+                )
+                .is_err()
+            {
+                tracing::warn!(?self.pool.address, "could not replay underlying token exchange, pool balances may be stale");
+            }
+        } else {
+            //Liquidity events only move balances, they are replayed the same way as for any stableswap pool
+            self.pool.sync_from_log(log)?;
+        }
+
+        Ok(())
+    }
+
+    //Underlying coins are listed first, i.e. [FRAX, DAI, USDC, USDT, 3CRV]
+    fn tokens(&self) -> Vec<H160> {
+        let mut tokens = self.underlying_tokens();
+        tokens.push(self.lp_token());
+
+        tokens
+    }
+
+    //Calculates the marginal price of the base token denominated in the token returned by get_token_out(base_token)
+    fn calculate_price(&self, base_token: H160) -> Result<Price, ArithmeticError> {
+        self.check_liquidity()?;
+
+        let quote_token = self.get_token_out(base_token);
+
+        Price::from_f64(self.lp_value(base_token)? / self.lp_value(quote_token)?)
+    }
+
+    async fn populate_data<M: Middleware>(
+        &mut self,
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        self.pool
+            .populate_data(block_number, middleware.clone())
+            .await?;
+
+        //Older metapools do not expose their base pool, the base pool must be set before populating them
+        if self.base_pool.address.is_zero() {
+            self.base_pool.address = ICurveMetaPool::new(self.pool.address, middleware.clone())
+                .base_pool()
+                .call()
+                .await?;
+        }
+
+        self.base_pool
+            .populate_data(block_number, middleware.clone())
+            .await?;
+        self.base_lp_supply = self.get_base_lp_supply(block_number, middleware).await?;
+
+        Ok(())
+    }
+
+    fn simulate_swap(&self, token_in: H160, amount_in: U256) -> Result<U256, SwapSimulationError> {
+        self.calculate_amount_out(token_in, self.get_token_out(token_in), amount_in)
+    }
+
+    fn simulate_swap_mut(
+        &mut self,
+        token_in: H160,
+        amount_in: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        self.check_liquidity()?;
+
+        let token_out = self.get_token_out(token_in);
+
+        if token_in == self.lp_token() || token_out == self.lp_token() {
+            let (i, j) = self.coin_indices(token_in, token_out)?;
+            return self.exchange(i, j, amount_in);
+        }
+
+        let (i, j) = self.underlying_indices(token_in, token_out)?;
+        self.exchange_underlying(i, j, amount_in)
+    }
+
+    //Swaps between two coins of the base pool are routed through the base pool and are charged its fee
+    fn swap_fee(&self, token_in: H160) -> f64 {
+        let token_out = self.get_token_out(token_in);
+
+        let fee = if self.base_pool.token_index(token_in).is_some()
+            && self.base_pool.token_index(token_out).is_some()
+        {
+            self.base_pool.fee
+        } else {
+            self.pool.fee
+        };
+
+        fee.as_u128() as f64 / FEE_DENOMINATOR.as_u128() as f64
+    }
+
+    fn token_decimals(&self) -> Vec<u8> {
+        let mut token_decimals = vec![self.pool.token_decimals.first().copied().unwrap_or(18)];
+        token_decimals.extend(self.base_pool.token_decimals.iter());
+        token_decimals.push(self.pool.token_decimals.get(1).copied().unwrap_or(18));
+
+        token_decimals
+    }
+
+    fn get_token_out(&self, token_in: H160) -> H160 {
+        self.tokens()
+            .into_iter()
+            .find(|token| *token != token_in)
+            .unwrap_or_default()
+    }
+}
+
+impl CurveMetaPool {
+    pub fn new(
+        pool: CurveStableSwapPool,
+        base_pool: CurveStableSwapPool,
+        base_lp_supply: U256,
+    ) -> CurveMetaPool {
+        CurveMetaPool {
+            pool,
+            base_pool,
+            base_lp_supply,
+            base_lp_minted: U256::zero(),
+        }
+    }
+
+    //Creates a new instance of the metapool and its base pool from their addresses, and syncs the data of both pools
+    pub async fn new_from_address<M: Middleware>(
+        address: H160,
+        base_pool: H160,
+        middleware: Arc<M>,
+    ) -> Result<Self, AMMError<M>> {
+        let mut pool = CurveMetaPool {
+            pool: CurveStableSwapPool {
+                address,
+                ..Default::default()
+            },
+            base_pool: CurveStableSwapPool {
+                address: base_pool,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        pool.populate_data(None, middleware).await?;
+
+        if !pool.data_is_populated() {
+            return Err(AMMError::PoolDataError);
+        }
+
+        Ok(pool)
+    }
+
+    pub fn data_is_populated(&self) -> bool {
+        self.pool.tokens.len() == 2
+            && self.pool.data_is_populated()
+            && self.base_pool.data_is_populated()
+            && !self.base_lp_supply.is_zero()
+    }
+
+    //The LP token of the base pool is the second coin of the metapool
+    pub fn lp_token(&self) -> H160 {
+        self.pool.tokens.get(1).copied().unwrap_or_default()
+    }
+
+    //The coin of the metapool followed by the coins of the base pool, in the order of the underlying coin indices
+    pub fn underlying_tokens(&self) -> Vec<H160> {
+        let mut tokens = vec![self.pool.tokens.first().copied().unwrap_or_default()];
+        tokens.extend(self.base_pool.tokens.iter());
+
+        tokens
+    }
+
+    pub fn underlying_index(&self, token: H160) -> Option<usize> {
+        self.underlying_tokens().iter().position(|t| *t == token)
+    }
+
+    //Swaps through the metapool price the LP token at the virtual price of the base pool, so both pools need liquidity
+    fn check_liquidity(&self) -> Result<(), ArithmeticError> {
+        if self.base_lp_supply.is_zero()
+            || self.pool.check_liquidity().is_err()
+            || self.base_pool.check_liquidity().is_err()
+        {
+            return Err(ArithmeticError::ZeroLiquidity(self.pool.address));
+        }
+
+        Ok(())
+    }
+
+    //Virtual price of the base pool LP token, scaled by PRECISION
+    pub fn virtual_price(&self) -> Result<U256, ArithmeticError> {
+        if self.base_lp_supply.is_zero() {
+            return Err(ArithmeticError::YIsZero);
+        }
+
+        let d = get_d(&self.base_pool.xp()?, self.base_pool.a);
+
+        Ok(d * PRECISION / self.base_lp_supply)
+    }
+
+    pub async fn get_base_lp_supply<M: Middleware>(
+        &self,
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<U256, AMMError<M>> {
+        let mut total_supply = ICurveLpToken::new(self.lp_token(), middleware).total_supply();
+        if let Some(block_number) = block_number {
+            total_supply = total_supply.block(block_number);
+        }
+
+        Ok(total_supply.call().await?)
+    }
+
+    //Calculates the amount out for a swap between the coin, the underlying coins and the LP token, matching the output of
+    //get_dy_underlying on the pool contract, or get_dy when either token is the LP token
+    pub fn calculate_amount_out(
+        &self,
+        token_in: H160,
+        token_out: H160,
+        amount_in: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        self.check_liquidity()?;
+
+        if token_in == self.lp_token() || token_out == self.lp_token() {
+            let (i, j) = self.coin_indices(token_in, token_out)?;
+            let (amount_out, _) = self.get_dy(i, j, amount_in)?;
+
+            return Ok(amount_out);
+        }
+
+        let (i, j) = self.underlying_indices(token_in, token_out)?;
+        self.get_dy_underlying(i, j, amount_in)
+    }
+
+    fn coin_indices(
+        &self,
+        token_in: H160,
+        token_out: H160,
+    ) -> Result<(usize, usize), SwapSimulationError> {
+        match (
+            self.pool.token_index(token_in),
+            self.pool.token_index(token_out),
+        ) {
+            (Some(i), Some(j)) => Ok((i, j)),
+            //The LP token can only be swapped for the coin, depositing into the base pool is not a swap through the metapool
+            _ => Err(SwapSimulationError::InvalidHop(token_in, token_out)),
+        }
+    }
+
+    fn underlying_indices(
+        &self,
+        token_in: H160,
+        token_out: H160,
+    ) -> Result<(usize, usize), SwapSimulationError> {
+        let i = self
+            .underlying_index(token_in)
+            .ok_or(SwapSimulationError::TokenNotInPool(token_in))?;
+        let j = self
+            .underlying_index(token_out)
+            .ok_or(SwapSimulationError::TokenNotInPool(token_out))?;
+
+        Ok((i, j))
+    }
+
+    //The coin is normalized to 18 decimals and the LP token is valued at the virtual price of the base pool
+    fn rates(&self) -> Result<[U256; 2], ArithmeticError> {
+        Ok([rate(self.pool.token_decimals[0])?, self.virtual_price()?])
+    }
+
+    fn xp(&self, rates: &[U256; 2]) -> Vec<U256> {
+        self.pool
+            .balances
+            .iter()
+            .zip(rates.iter())
+            .map(|(balance, rate)| *balance * *rate / PRECISION)
+            .collect()
+    }
+
+    //Returns the amount out and the admin fee of an exchange between the coin and the LP token, both denominated in coin j
+    fn get_dy(
+        &self,
+        i: usize,
+        j: usize,
+        amount_in: U256,
+    ) -> Result<(U256, U256), SwapSimulationError> {
+        if amount_in.is_zero() || i == j {
+            return Ok((U256::zero(), U256::zero()));
+        }
+
+        let rates = self.rates()?;
+        let xp = self.xp(&rates);
+
+        let x = xp[i] + amount_in * rates[i] / PRECISION;
+        let y = get_y(i, j, x, &xp, self.pool.a);
+
+        if xp[j] <= y + 1 {
+            return Err(SwapSimulationError::LiquidityUnderflow);
+        }
+        let dy = xp[j] - y - 1;
+        let dy_fee = dy * self.pool.fee / FEE_DENOMINATOR;
+
+        let amount_out = (dy - dy_fee) * PRECISION / rates[j];
+        let admin_fee = dy_fee * self.pool.admin_fee / FEE_DENOMINATOR * PRECISION / rates[j];
+
+        Ok((amount_out, admin_fee))
+    }
+
+    //Mirrors get_dy_underlying, index 0 is the coin and index 1 + k is coin k of the base pool
+    fn get_dy_underlying(
+        &self,
+        i: usize,
+        j: usize,
+        amount_in: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        if amount_in.is_zero() || i == j {
+            return Ok(U256::zero());
+        }
+
+        //Both coins are in the base pool, the metapool is not involved
+        if i > 0 && j > 0 {
+            let (amount_out, _) = self.base_pool.get_dy(i - 1, j - 1, amount_in)?;
+            return Ok(amount_out);
+        }
+
+        let rates = self.rates()?;
+        let xp = self.xp(&rates);
+
+        let (meta_i, meta_j) = (i.min(1), j.min(1));
+
+        let x = if i == 0 {
+            xp[0] + amount_in * rates[0] / PRECISION
+        } else {
+            //The coin in is deposited into the base pool, the contract approximates the deposit fee as half of the swap fee
+            let mut amounts = vec![U256::zero(); self.base_pool.tokens.len()];
+            amounts[i - 1] = amount_in;
+
+            let x = self.calc_token_amount(&amounts)? * rates[1] / PRECISION;
+            x - x * self.base_pool.fee / (U256::from(2) * FEE_DENOMINATOR) + xp[1]
+        };
+
+        let y = get_y(meta_i, meta_j, x, &xp, self.pool.a);
+        if xp[meta_j] <= y + 1 {
+            return Err(SwapSimulationError::LiquidityUnderflow);
+        }
+        let dy = xp[meta_j] - y - 1;
+        let dy = dy - self.pool.fee * dy / FEE_DENOMINATOR;
+
+        if j == 0 {
+            Ok(dy * PRECISION / rates[0])
+        } else {
+            //The LP tokens out are withdrawn from the base pool as coin j
+            let (amount_out, _) = self.calc_withdraw_one_coin(dy * PRECISION / rates[1], j - 1)?;
+            Ok(amount_out)
+        }
+    }
+
+    //Exchanges amount_in of coin i for coin j, updating the balances the same way the pool contract does
+    fn exchange(
+        &mut self,
+        i: usize,
+        j: usize,
+        amount_in: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        let (amount_out, admin_fee) = self.get_dy(i, j, amount_in)?;
+
+        self.pool.balances[i] += amount_in;
+        self.pool.balances[j] -= amount_out + admin_fee;
+
+        Ok(amount_out)
+    }
+
+    //Mirrors exchange_underlying, which deposits into the base pool instead of using the deposit fee approximation of
+    //get_dy_underlying, so the amount out of a swap from a base pool coin can differ slightly from simulate_swap
+    fn exchange_underlying(
+        &mut self,
+        i: usize,
+        j: usize,
+        amount_in: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        if amount_in.is_zero() || i == j {
+            return Ok(U256::zero());
+        }
+
+        if i > 0 && j > 0 {
+            return self.base_pool.exchange(i - 1, j - 1, amount_in);
+        }
+
+        if i == 0 {
+            let lp_amount = self.exchange(0, 1, amount_in)?;
+            self.remove_liquidity_one_coin(lp_amount, j - 1)
+        } else {
+            let mut amounts = vec![U256::zero(); self.base_pool.tokens.len()];
+            amounts[i - 1] = amount_in;
+
+            let lp_amount = self.add_liquidity(&amounts)?;
+            self.exchange(1, 0, lp_amount)
+        }
+    }
+
+    //The base pool logs of an underlying exchange into a base pool coin are a RemoveLiquidityOne, which can not be replayed,
+    //so the withdrawal is replayed from the metapool log instead
+    fn replay_exchange_underlying(
+        &mut self,
+        i: usize,
+        j: usize,
+        amount_in: U256,
+    ) -> Result<(), SwapSimulationError> {
+        if i == 0 {
+            let lp_amount = self.exchange(0, 1, amount_in)?;
+            if j > 0 {
+                self.remove_liquidity_one_coin(lp_amount, j - 1)?;
+            }
+        } else if j == 0 {
+            let lp_amount = std::mem::take(&mut self.base_lp_minted);
+            self.exchange(1, 0, lp_amount)?;
+        }
+
+        Ok(())
+    }
+
+    fn sync_base_pool_from_log(&mut self, log: Log) -> Result<(), EventLogError> {
+        let event_signature = log.topics[0];
+        let provider = log.topics.get(1).copied().map(H160::from);
+        let token_supply = log
+            .data
+            .len()
+            .checked_sub(32)
+            .map(|offset| U256::from_big_endian(&log.data[offset..]));
+
+        self.base_pool.sync_from_log(log)?;
+
+        //All liquidity events end with the token supply after the deposit or withdrawal
+        if let Some([add_liquidity, remove_liquidity, remove_liquidity_imbalance]) =
+            liquidity_event_signatures(self.base_pool.tokens.len())
+        {
+            if event_signature == add_liquidity
+                || event_signature == remove_liquidity
+                || event_signature == remove_liquidity_imbalance
+            {
+                let token_supply = token_supply.ok_or(EventLogError::InvalidEventSignature)?;
+
+                if event_signature == add_liquidity && provider == Some(self.pool.address) {
+                    self.base_lp_minted = token_supply.saturating_sub(self.base_lp_supply);
+                }
+
+                self.base_lp_supply = token_supply;
+            }
+        }
+
+        Ok(())
+    }
+
+    //Mirrors calc_token_amount of the base pool for a deposit, fees are not included
+    fn calc_token_amount(&self, amounts: &[U256]) -> Result<U256, SwapSimulationError> {
+        let base_pool = &self.base_pool;
+
+        let d_0 = get_d(&base_pool.xp()?, base_pool.a);
+        if d_0.is_zero() {
+            return Err(ArithmeticError::YIsZero.into());
+        }
+
+        let new_balances = base_pool
+            .balances
+            .iter()
+            .zip(amounts.iter())
+            .map(|(balance, amount)| *balance + *amount)
+            .collect::<Vec<U256>>();
+        let d_1 = get_d(
+            &normalize(&new_balances, &base_pool.token_decimals)?,
+            base_pool.a,
+        );
+
+        Ok((d_1 - d_0) * self.base_lp_supply / d_0)
+    }
+
+    //Mirrors add_liquidity of the base pool, which charges a fee on the imbalance of the deposit, and returns the LP tokens minted
+    fn add_liquidity(&mut self, amounts: &[U256]) -> Result<U256, SwapSimulationError> {
+        let n_coins = self.base_pool.tokens.len();
+        let fee = self.base_pool.fee * U256::from(n_coins) / U256::from(4 * (n_coins - 1));
+        let admin_fee = self.base_pool.admin_fee;
+        let decimals = self.base_pool.token_decimals.clone();
+
+        let old_balances = self.base_pool.balances.clone();
+        let d_0 = get_d(&normalize(&old_balances, &decimals)?, self.base_pool.a);
+        if d_0.is_zero() {
+            return Err(ArithmeticError::YIsZero.into());
+        }
+
+        let mut new_balances = old_balances
+            .iter()
+            .zip(amounts.iter())
+            .map(|(balance, amount)| *balance + *amount)
+            .collect::<Vec<U256>>();
+        let d_1 = get_d(&normalize(&new_balances, &decimals)?, self.base_pool.a);
+
+        for i in 0..n_coins {
+            let ideal_balance = d_1 * old_balances[i] / d_0;
+            let difference = if ideal_balance > new_balances[i] {
+                ideal_balance - new_balances[i]
+            } else {
+                new_balances[i] - ideal_balance
+            };
+
+            let fee_i = fee * difference / FEE_DENOMINATOR;
+            self.base_pool.balances[i] = new_balances[i] - fee_i * admin_fee / FEE_DENOMINATOR;
+            new_balances[i] -= fee_i;
+        }
+
+        let d_2 = get_d(&normalize(&new_balances, &decimals)?, self.base_pool.a);
+        let minted = self.base_lp_supply * (d_2 - d_0) / d_0;
+        self.base_lp_supply += minted;
+
+        Ok(minted)
+    }
+
+    //Mirrors calc_withdraw_one_coin of the base pool, returns the amount of coin i out and the fee, both denominated in coin i
+    fn calc_withdraw_one_coin(
+        &self,
+        token_amount: U256,
+        i: usize,
+    ) -> Result<(U256, U256), SwapSimulationError> {
+        let base_pool = &self.base_pool;
+        if self.base_lp_supply.is_zero() {
+            return Err(ArithmeticError::YIsZero.into());
+        }
+
+        let n_coins = base_pool.tokens.len();
+        let fee = base_pool.fee * U256::from(n_coins) / U256::from(4 * (n_coins - 1));
+        let precision = rate(base_pool.token_decimals[i])? / PRECISION;
+
+        let xp = base_pool.xp()?;
+        let d_0 = get_d(&xp, base_pool.a);
+        let d_1 = d_0 - token_amount * d_0 / self.base_lp_supply;
+        let new_y = get_y_d(i, &xp, d_1, base_pool.a);
+        if xp[i] < new_y {
+            return Err(SwapSimulationError::LiquidityUnderflow);
+        }
+        let dy_0 = (xp[i] - new_y) / precision;
+
+        let mut xp_reduced = xp.clone();
+        for (k, x_k) in xp.iter().enumerate() {
+            let dx_expected = if k == i {
+                *x_k * d_1 / d_0 - new_y
+            } else {
+                *x_k - *x_k * d_1 / d_0
+            };
+
+            xp_reduced[k] -= fee * dx_expected / FEE_DENOMINATOR;
+        }
+
+        let y = get_y_d(i, &xp_reduced, d_1, base_pool.a);
+        if xp_reduced[i] <= y {
+            return Err(SwapSimulationError::LiquidityUnderflow);
+        }
+        //Withdraw less to account for rounding errors
+        let dy = (xp_reduced[i] - y - 1) / precision;
+
+        Ok((dy, dy_0.saturating_sub(dy)))
+    }
+
+    fn remove_liquidity_one_coin(
+        &mut self,
+        token_amount: U256,
+        i: usize,
+    ) -> Result<U256, SwapSimulationError> {
+        let (amount_out, fee) = self.calc_withdraw_one_coin(token_amount, i)?;
+
+        self.base_pool.balances[i] -= amount_out + fee * self.base_pool.admin_fee / FEE_DENOMINATOR;
+        self.base_lp_supply -= token_amount;
+
+        Ok(amount_out)
+    }
+
+    //Marginal value of one token in base pool LP tokens, excluding fees
+    fn lp_value(&self, token: H160) -> Result<f64, ArithmeticError> {
+        let virtual_price = u256_to_f64(self.virtual_price()?) / u256_to_f64(PRECISION);
+
+        if token == self.lp_token() {
+            Ok(1.0)
+        } else if let Some(k) = self.base_pool.token_index(token) {
+            Ok(invariant_derivative(&self.base_pool.xp()?, self.base_pool.a, k)? / virtual_price)
+        } else if self.pool.token_index(token) == Some(0) {
+            Ok(self.pool_marginal_price()? / virtual_price)
+        } else {
+            Err(ArithmeticError::TokenNotInPool(token))
+        }
+    }
+
+    //Normalized LP tokens per normalized coin at the margin
+    fn pool_marginal_price(&self) -> Result<f64, ArithmeticError> {
+        let xp = self.xp(&self.rates()?);
+
+        Ok(invariant_derivative(&xp, self.pool.a, 0)? / invariant_derivative(&xp, self.pool.a, 1)?)
+    }
+}
+
+//Balances normalized to 18 decimals
+fn normalize(balances: &[U256], token_decimals: &[u8]) -> Result<Vec<U256>, ArithmeticError> {
+    balances
+        .iter()
+        .zip(token_decimals.iter())
+        .map(|(balance, decimals)| Ok(*balance * rate(*decimals)? / PRECISION))
+        .collect()
+}
+
+//Increase of D per unit of coin k, differentiating the invariant Ann*S + D = Ann*D + D_P for x_k and D
+fn invariant_derivative(xp: &[U256], amp: U256, k: usize) -> Result<f64, ArithmeticError> {
+    if xp.iter().any(|x| x.is_zero()) {
+        return Err(ArithmeticError::YIsZero);
+    }
+
+    let n = U256::from(xp.len());
+    let d = get_d(xp, amp);
+
+    let mut d_p = d;
+    for x in xp.iter() {
+        d_p = d_p * d / (*x * n);
+    }
+
+    let ann = amp.as_u128() as f64 * xp.len() as f64 / A_PRECISION.as_u128() as f64;
+    let d_p = u256_to_f64(d_p);
+    let d = u256_to_f64(d);
+    let x_k = u256_to_f64(xp[k]);
+
+    Ok((ann + d_p / x_k) / (ann - 1.0 + (xp.len() as f64 + 1.0) * d_p / d))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, sync::Arc};
+
+    use ethers::{
+        abi::Token,
+        providers::{Http, Provider},
+        types::{Log, H160, H256, U256},
+    };
+
+    use crate::amm::{
+        curve::{CurveStableSwapPool, TOKEN_EXCHANGE_EVENT_SIGNATURE},
+        AutomatedMarketMaker,
+    };
+
+    use super::{CurveMetaPool, ICurveMetaPool};
+
+    fn frax_pool() -> eyre::Result<CurveMetaPool> {
+        let base_pool = CurveStableSwapPool {
+            address: H160::from_str("0xbEbc44782C7dB0a1A60Cb6fe97d0b483032FF1C7")?,
+            tokens: vec![
+                H160::from_str("0x6B175474E89094C44Da98b954EedeAC495271d0F")?,
+                H160::from_str("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48")?,
+                H160::from_str("0xdAC17F958D2ee523a2206206994597C13D831ec7")?,
+            ],
+            token_decimals: vec![18, 6, 6],
+            balances: vec![
+                U256::from_dec_str("100000000000000000000000000")?,
+                U256::from_dec_str("100000000000000")?,
+                U256::from_dec_str("100000000000000")?,
+            ],
+            a: U256::from(200000),
+            fee: U256::from(1000000),
+            admin_fee: U256::from(5000000000_u64),
+        };
+
+        let pool = CurveStableSwapPool {
+            address: H160::from_str("0xd632f22692FaC7611d2AA1C0D552930D43CAEd3B")?,
+            tokens: vec![
+                H160::from_str("0x853d955aCEf822Db058eb8505911ED77F175b99e")?,
+                H160::from_str("0x6c3F90f043a72FA612cbac8115EE7e52BDe6E490")?,
+            ],
+            token_decimals: vec![18, 18],
+            balances: vec![
+                U256::from_dec_str("50000000000000000000000000")?,
+                U256::from_dec_str("50000000000000000000000000")?,
+            ],
+            a: U256::from(150000),
+            fee: U256::from(4000000),
+            admin_fee: U256::from(5000000000_u64),
+        };
+
+        //A virtual price of 1.02
+        Ok(CurveMetaPool::new(
+            pool,
+            base_pool,
+            U256::from_dec_str("294117647058823529411764705")?,
+        ))
+    }
+
+    #[test]
+    fn test_simulate_underlying_swap() -> eyre::Result<()> {
+        let mut pool = frax_pool()?;
+        let frax = pool.pool.tokens[0];
+        let usdc = pool.base_pool.tokens[1];
+
+        //Swap 1000 FRAX for USDC, the output should be close to 1:1 minus the fees of both pools
+        let amount_in = U256::from_dec_str("1000000000000000000000")?;
+        let amount_out = pool.calculate_amount_out(frax, usdc, amount_in)?;
+        assert!(amount_out < U256::from(1000000000));
+        assert!(amount_out > U256::from(998000000));
+
+        //Swapping the coin for a base pool coin goes through the same math as the contract, so the mutable swap matches
+        let (i, j) = pool.underlying_indices(frax, usdc)?;
+        let balances = pool.base_pool.balances.clone();
+        assert_eq!(pool.exchange_underlying(i, j, amount_in)?, amount_out);
+        assert!(pool.base_pool.balances[1] < balances[1]);
+
+        //Swapping back deposits into the base pool
+        let amount_back = pool.calculate_amount_out(usdc, frax, amount_out)?;
+        assert!(amount_back < amount_in);
+        assert!(amount_back > amount_in * 99 / 100);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lp_token_swap() -> eyre::Result<()> {
+        let pool = frax_pool()?;
+        let frax = pool.pool.tokens[0];
+        let lp_token = pool.lp_token();
+
+        //The LP token is worth the virtual price, so a FRAX buys slightly less than one LP token
+        let amount_in = U256::from_dec_str("1000000000000000000000")?;
+        let amount_out = pool.calculate_amount_out(frax, lp_token, amount_in)?;
+        let expected = amount_in * U256::from(100) / U256::from(102);
+        assert!(amount_out < expected);
+        assert!(amount_out > expected * 99 / 100);
+
+        let price = pool.calculate_price(lp_token)?.to_f64();
+        assert!((price - 1.02).abs() < 1e-3);
+
+        //The LP token can not be swapped for a coin of the base pool through the metapool
+        assert!(pool
+            .calculate_amount_out(lp_token, pool.base_pool.tokens[0], amount_in)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_from_base_pool_log() -> eyre::Result<()> {
+        let mut pool = frax_pool()?;
+        let virtual_price = pool.virtual_price()?;
+
+        //The swap fee of a base pool exchange stays in the base pool and increases the virtual price
+        let log = Log {
+            address: pool.base_pool.address,
+            topics: vec![TOKEN_EXCHANGE_EVENT_SIGNATURE, H256::zero()],
+            data: ethers::abi::encode(&[
+                Token::Int(U256::zero()),
+                Token::Uint(U256::from_dec_str("10000000000000000000000000")?),
+                Token::Int(U256::one()),
+                Token::Uint(U256::zero()),
+            ])
+            .into(),
+            ..Default::default()
+        };
+        pool.sync_from_log(log)?;
+
+        assert!(pool.virtual_price()? > virtual_price);
+        assert!(pool.sync_on_event_signatures().len() > pool.pool.sync_on_event_signatures().len());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_dy_underlying() -> eyre::Result<()> {
+        let rpc_endpoint = std::env::var("ETHEREUM_RPC_ENDPOINT")?;
+        let middleware = Arc::new(Provider::<Http>::try_from(rpc_endpoint)?);
+
+        //FRAX/3CRV metapool
+        let address = H160::from_str("0xd632f22692FaC7611d2AA1C0D552930D43CAEd3B")?;
+        let base_pool = H160::from_str("0xbEbc44782C7dB0a1A60Cb6fe97d0b483032FF1C7")?;
+        let pool = CurveMetaPool::new_from_address(address, base_pool, middleware.clone()).await?;
+
+        let amount_in = U256::from_dec_str("1000000000000000000000")?;
+        let amount_out =
+            pool.calculate_amount_out(pool.pool.tokens[0], pool.base_pool.tokens[1], amount_in)?;
+        let expected = ICurveMetaPool::new(address, middleware)
+            .get_dy_underlying(0, 2, amount_in)
+            .call()
+            .await?;
+
+        //The contract caches the virtual price of the base pool for a few minutes
+        let difference = if amount_out > expected {
+            amount_out - expected
+        } else {
+            expected - amount_out
+        };
+        assert!(difference * 10000 < expected);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,701 @@
+pub mod batch_request;
+pub mod crypto;
+pub mod factory;
+pub mod meta;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::{
+    abi::{ParamType, RawLog},
+    prelude::EthEvent,
+    providers::Middleware,
+    types::{Log, H160, H256, U256},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    amm::{price::Price, u256_to_f64, AutomatedMarketMaker},
+    errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
+};
+
+use ethers::prelude::abigen;
+
+abigen!(
+    ICurvePool,
+    r#"[
+        function coins(uint256 i) external view returns (address)
+        function balances(uint256 i) external view returns (uint256)
+        function A() external view returns (uint256)
+        function A_precise() external view returns (uint256)
+        function fee() external view returns (uint256)
+        function admin_fee() external view returns (uint256)
+        event TokenExchange(address indexed buyer, int128 sold_id, uint256 tokens_sold, int128 bought_id, uint256 tokens_bought)
+    ]"#;
+
+    IErc20,
+    r#"[
+        function decimals() external view returns (uint8)
+    ]"#;
+);
+
+pub const TOKEN_EXCHANGE_EVENT_SIGNATURE: H256 = H256([
+    139, 62, 150, 242, 184, 137, 250, 119, 28, 83, 201, 129, 180, 13, 175, 0, 95, 99, 246, 55, 241,
+    134, 159, 112, 112, 82, 209, 90, 61, 217, 113, 64,
+]);
+
+pub const ADD_LIQUIDITY_2_EVENT_SIGNATURE: H256 = H256([
+    38, 245, 90, 133, 8, 29, 36, 151, 78, 133, 198, 192, 0, 69, 208, 240, 69, 57, 145, 233, 88,
+    115, 245, 43, 255, 13, 33, 175, 64, 121, 167, 104,
+]);
+
+pub const REMOVE_LIQUIDITY_2_EVENT_SIGNATURE: H256 = H256([
+    124, 54, 56, 84, 204, 247, 150, 35, 65, 31, 137, 149, 179, 98, 188, 229, 237, 223, 241, 140,
+    146, 126, 220, 111, 93, 187, 181, 224, 88, 25, 168, 44,
+]);
+
+pub const REMOVE_LIQUIDITY_IMBALANCE_2_EVENT_SIGNATURE: H256 = H256([
+    43, 85, 8, 55, 141, 126, 25, 224, 213, 250, 51, 132, 25, 3, 71, 49, 65, 108, 79, 91, 33, 154,
+    16, 55, 153, 86, 247, 100, 49, 127, 212, 126,
+]);
+
+pub const ADD_LIQUIDITY_3_EVENT_SIGNATURE: H256 = H256([
+    66, 63, 100, 149, 160, 143, 198, 82, 66, 92, 244, 237, 13, 31, 158, 55, 229, 113, 217, 185, 82,
+    155, 28, 28, 35, 204, 231, 128, 178, 231, 223, 13,
+]);
+
+pub const REMOVE_LIQUIDITY_3_EVENT_SIGNATURE: H256 = H256([
+    164, 157, 76, 240, 38, 86, 174, 191, 140, 119, 31, 90, 133, 133, 99, 138, 42, 21, 238, 108,
+    151, 207, 114, 5, 212, 32, 142, 215, 193, 223, 37, 45,
+]);
+
+pub const REMOVE_LIQUIDITY_IMBALANCE_3_EVENT_SIGNATURE: H256 = H256([
+    23, 53, 153, 219, 249, 198, 202, 111, 124, 59, 89, 13, 240, 122, 233, 138, 69, 215, 79, 245,
+    64, 101, 80, 81, 65, 231, 222, 108, 70, 166, 36, 194,
+]);
+
+pub const ADD_LIQUIDITY_4_EVENT_SIGNATURE: H256 = H256([
+    63, 25, 21, 119, 94, 12, 154, 56, 165, 122, 123, 183, 241, 249, 0, 95, 72, 111, 185, 4, 225,
+    248, 74, 162, 21, 54, 77, 86, 115, 25, 165, 141,
+]);
+
+pub const REMOVE_LIQUIDITY_4_EVENT_SIGNATURE: H256 = H256([
+    152, 120, 202, 55, 94, 16, 111, 42, 67, 195, 181, 153, 252, 98, 69, 104, 19, 28, 76, 154, 75,
+    166, 106, 20, 86, 55, 21, 118, 59, 233, 213, 157,
+]);
+
+pub const REMOVE_LIQUIDITY_IMBALANCE_4_EVENT_SIGNATURE: H256 = H256([
+    185, 100, 183, 47, 115, 245, 239, 91, 240, 253, 197, 89, 178, 250, 185, 167, 177, 42, 57, 228,
+    120, 23, 165, 71, 241, 240, 174, 228, 127, 235, 214, 2,
+]);
+
+// Stableswap pools hold at most 8 coins
+pub const MAX_COINS: usize = 8;
+// The amplification coefficient is stored with 2 decimals of precision
+pub const A_PRECISION: U256 = U256([100, 0, 0, 0]);
+// Fees are denominated in 1e10
+pub const FEE_DENOMINATOR: U256 = U256([10000000000, 0, 0, 0]);
+// All balances are normalized to 18 decimals before running the invariant math
+pub const PRECISION: U256 = U256([1000000000000000000, 0, 0, 0]);
+// Maximum number of newton iterations used by the pool contract
+const MAX_ITERATIONS: usize = 255;
+
+//Alias kept for the name the stableswap pool was first introduced under
+pub type CurvePool = CurveStableSwapPool;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CurveStableSwapPool {
+    pub address: H160,
+    pub tokens: Vec<H160>,
+    pub token_decimals: Vec<u8>,
+    pub balances: Vec<U256>,
+    pub a: U256,         // amplification coefficient, scaled by A_PRECISION
+    pub fee: U256,       // swap fee, scaled by FEE_DENOMINATOR
+    pub admin_fee: U256, // share of the swap fee that is sent to the admin, scaled by FEE_DENOMINATOR
+}
+
+#[async_trait]
+impl AutomatedMarketMaker for CurveStableSwapPool {
+    fn address(&self) -> H160 {
+        self.address
+    }
+
+    async fn sync<M: Middleware>(&mut self, middleware: Arc<M>) -> Result<(), AMMError<M>> {
+        batch_request::sync_curve_stable_swap_pool_batch_request(self, middleware).await
+    }
+
+    //Liquidity events encode one amount per coin, so their signatures depend on the number of coins in the pool.
+    //RemoveLiquidityOne does not include the index of the withdrawn coin and can not be replayed, pools should be resynced periodically to account for it
+    fn sync_on_event_signatures(&self) -> Vec<H256> {
+        let mut event_signatures = vec![TOKEN_EXCHANGE_EVENT_SIGNATURE];
+
+        if let Some(liquidity_event_signatures) = liquidity_event_signatures(self.tokens.len()) {
+            event_signatures.extend(liquidity_event_signatures);
+        }
+
+        event_signatures
+    }
+
+    fn sync_from_log(&mut self, log: Log) -> Result<(), EventLogError> {
+        let event_signature = log.topics[0];
+
+        if event_signature == TOKEN_EXCHANGE_EVENT_SIGNATURE {
+            let token_exchange_event = TokenExchangeFilter::decode_log(&RawLog::from(log))?;
+
+            let i = token_exchange_event.sold_id as usize;
+            let j = token_exchange_event.bought_id as usize;
+
+            //The admin fee is not included in the event, replaying the exchange reproduces the balances of the pool
+            if self
+                .exchange(i, j, token_exchange_event.tokens_sold)
+                .is_err()
+            {
+                tracing::warn!(?self.address, i, j, "could not replay token exchange, pool balances may be stale");
+            }
+        } else if let Some([add_liquidity, remove_liquidity, remove_liquidity_imbalance]) =
+            liquidity_event_signatures(self.tokens.len())
+        {
+            let n_coins = self.tokens.len();
+
+            if event_signature != add_liquidity
+                && event_signature != remove_liquidity
+                && event_signature != remove_liquidity_imbalance
+            {
+                return Err(EventLogError::InvalidEventSignature);
+            }
+
+            //The data of all liquidity events starts with uint256[n] token_amounts followed by uint256[n] fees
+            let words = ethers::abi::decode(&vec![ParamType::Uint(256); 2 * n_coins], &log.data)?
+                .into_iter()
+                .map(|token| token.into_uint().unwrap_or_default())
+                .collect::<Vec<U256>>();
+
+            let (token_amounts, fees) = words.split_at(n_coins);
+
+            for i in 0..n_coins {
+                let admin_fee = fees[i] * self.admin_fee / FEE_DENOMINATOR;
+
+                if event_signature == add_liquidity {
+                    self.balances[i] += token_amounts[i];
+                    self.balances[i] -= admin_fee;
+                } else if event_signature == remove_liquidity {
+                    self.balances[i] -= token_amounts[i];
+                } else {
+                    self.balances[i] -= token_amounts[i] + admin_fee;
+                }
+            }
+        } else {
+            return Err(EventLogError::InvalidEventSignature);
+        }
+
+        Ok(())
+    }
+
+    fn tokens(&self) -> Vec<H160> {
+        self.tokens.clone()
+    }
+
+    //Calculates the marginal price of the base token denominated in the token returned by get_token_out(base_token)
+    fn calculate_price(&self, base_token: H160) -> Result<Price, ArithmeticError> {
+        self.check_liquidity()?;
+
+        let quote_token = self.get_token_out(base_token);
+
+        let i = self
+            .token_index(base_token)
+            .ok_or(ArithmeticError::TokenNotInPool(base_token))?;
+        let j = self
+            .token_index(quote_token)
+            .ok_or(ArithmeticError::TokenNotInPool(quote_token))?;
+
+        Price::from_f64(self.calculate_marginal_price(i, j)?)
+    }
+
+    async fn populate_data<M: Middleware>(
+        &mut self,
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        batch_request::get_curve_stable_swap_pool_data_batch_request(
+            self,
+            block_number,
+            middleware,
+        )
+        .await?;
+
+        tracing::trace!(?self.address, ?self.tokens, ?self.balances, ?self.a, ?self.fee, "populated pool data");
+
+        Ok(())
+    }
+
+    fn simulate_swap(&self, token_in: H160, amount_in: U256) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        self.calculate_amount_out(token_in, self.get_token_out(token_in), amount_in)
+    }
+
+    fn simulate_swap_mut(
+        &mut self,
+        token_in: H160,
+        amount_in: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        let token_out = self.get_token_out(token_in);
+
+        let i = self
+            .token_index(token_in)
+            .ok_or(SwapSimulationError::TokenNotInPool(token_in))?;
+        let j = self
+            .token_index(token_out)
+            .ok_or(SwapSimulationError::TokenNotInPool(token_out))?;
+
+        tracing::trace!(?self.balances, "pool balances before");
+
+        let amount_out = self.exchange(i, j, amount_in)?;
+
+        tracing::trace!(?self.balances, "pool balances after");
+
+        Ok(amount_out)
+    }
+
+    //The fee is charged on the amount out, as a fraction it is the same as a fee on the amount in
+    fn swap_fee(&self, _token_in: H160) -> f64 {
+        self.fee.as_u128() as f64 / FEE_DENOMINATOR.as_u128() as f64
+    }
+
+    fn token_decimals(&self) -> Vec<u8> {
+        self.token_decimals.clone()
+    }
+
+    //Returns the first token in the pool that is not the token in. To swap into a specific token of a pool with more than two tokens, use calculate_amount_out
+    fn get_token_out(&self, token_in: H160) -> H160 {
+        self.tokens
+            .iter()
+            .find(|token| **token != token_in)
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+impl CurveStableSwapPool {
+    pub fn new(
+        address: H160,
+        tokens: Vec<H160>,
+        token_decimals: Vec<u8>,
+        balances: Vec<U256>,
+        a: U256,
+        fee: U256,
+        admin_fee: U256,
+    ) -> CurveStableSwapPool {
+        CurveStableSwapPool {
+            address,
+            tokens,
+            token_decimals,
+            balances,
+            a,
+            fee,
+            admin_fee,
+        }
+    }
+
+    //Creates a new instance of the pool from the pool address, and syncs the pool data
+    pub async fn new_from_address<M: Middleware>(
+        pool_address: H160,
+        middleware: Arc<M>,
+    ) -> Result<Self, AMMError<M>> {
+        let mut pool = CurveStableSwapPool {
+            address: pool_address,
+            ..Default::default()
+        };
+
+        pool.populate_data(None, middleware).await?;
+
+        if !pool.data_is_populated() {
+            return Err(AMMError::PoolDataError);
+        }
+
+        Ok(pool)
+    }
+
+    pub fn data_is_populated(&self) -> bool {
+        !(self.tokens.len() < 2
+            || self.tokens.len() != self.balances.len()
+            || self.a.is_zero()
+            || self.balances.iter().any(|balance| balance.is_zero()))
+    }
+
+    pub fn token_index(&self, token: H160) -> Option<usize> {
+        self.tokens.iter().position(|t| *t == token)
+    }
+
+    //Calculates the marginal price of coin i denominated in coin j, excluding fees
+    pub fn calculate_marginal_price(&self, i: usize, j: usize) -> Result<f64, ArithmeticError> {
+        if i >= self.tokens.len() || j >= self.tokens.len() {
+            return Err(ArithmeticError::InvalidTokenIndex);
+        }
+
+        self.check_liquidity()?;
+
+        let xp = self.xp()?;
+        let n = U256::from(xp.len());
+        let d = get_d(&xp, self.a);
+
+        // D_P = D^(n+1) / (n^n * prod(x))
+        let mut d_p = d;
+        for x in xp.iter() {
+            d_p = d_p * d / (*x * n);
+        }
+
+        //Differentiating the invariant Ann*S + D = Ann*D + D_P gives dx_j/dx_i = (Ann + D_P/x_i) / (Ann + D_P/x_j)
+        let ann = self.a.as_u128() as f64 * xp.len() as f64 / A_PRECISION.as_u128() as f64;
+        let d_p = u256_to_f64(d_p);
+        let x_i = u256_to_f64(xp[i]);
+        let x_j = u256_to_f64(xp[j]);
+
+        Ok((ann + d_p / x_i) / (ann + d_p / x_j))
+    }
+
+    //Calculates the amount out for a swap between any two tokens in the pool, matching the output of get_dy on the pool contract
+    pub fn calculate_amount_out(
+        &self,
+        token_in: H160,
+        token_out: H160,
+        amount_in: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        let i = self
+            .token_index(token_in)
+            .ok_or(SwapSimulationError::TokenNotInPool(token_in))?;
+        let j = self
+            .token_index(token_out)
+            .ok_or(SwapSimulationError::TokenNotInPool(token_out))?;
+
+        let (amount_out, _) = self.get_dy(i, j, amount_in)?;
+
+        Ok(amount_out)
+    }
+
+    //Exchanges amount_in of coin i for coin j, updating the pool balances the same way the pool contract does
+    fn exchange(
+        &mut self,
+        i: usize,
+        j: usize,
+        amount_in: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        let (amount_out, admin_fee) = self.get_dy(i, j, amount_in)?;
+
+        self.balances[i] += amount_in;
+        self.balances[j] -= amount_out + admin_fee;
+
+        Ok(amount_out)
+    }
+
+    //Returns the amount out and the admin fee, both denominated in coin j
+    fn get_dy(
+        &self,
+        i: usize,
+        j: usize,
+        amount_in: U256,
+    ) -> Result<(U256, U256), SwapSimulationError> {
+        self.check_liquidity()?;
+
+        if amount_in.is_zero() || i == j {
+            return Ok((U256::zero(), U256::zero()));
+        }
+
+        let xp = self.xp()?;
+
+        let rate_i = rate(self.token_decimals[i])?;
+        let rate_j = rate(self.token_decimals[j])?;
+
+        let x = xp[i] + amount_in * rate_i / PRECISION;
+        let y = get_y(i, j, x, &xp, self.a);
+
+        //Subtract 1 to round against the swapper, in case there were rounding errors
+        if xp[j] <= y + 1 {
+            return Err(SwapSimulationError::LiquidityUnderflow);
+        }
+        let dy = xp[j] - y - 1;
+        let dy_fee = dy * self.fee / FEE_DENOMINATOR;
+
+        let amount_out = (dy - dy_fee) * PRECISION / rate_j;
+        let admin_fee = dy_fee * self.admin_fee / FEE_DENOMINATOR * PRECISION / rate_j;
+
+        Ok((amount_out, admin_fee))
+    }
+
+    //The invariant of a pool with an empty balance is zero, the pool has no price and can not swap
+    fn check_liquidity(&self) -> Result<(), ArithmeticError> {
+        if self.balances.is_empty() || self.balances.iter().any(|balance| balance.is_zero()) {
+            return Err(ArithmeticError::ZeroLiquidity(self.address));
+        }
+
+        Ok(())
+    }
+
+    //Balances normalized to 18 decimals
+    fn xp(&self) -> Result<Vec<U256>, ArithmeticError> {
+        self.balances
+            .iter()
+            .zip(self.token_decimals.iter())
+            .map(|(balance, decimals)| Ok(*balance * rate(*decimals)? / PRECISION))
+            .collect()
+    }
+}
+
+//Returns the add liquidity, remove liquidity and remove liquidity imbalance event signatures for pools with n coins
+pub fn liquidity_event_signatures(n_coins: usize) -> Option<[H256; 3]> {
+    match n_coins {
+        2 => Some([
+            ADD_LIQUIDITY_2_EVENT_SIGNATURE,
+            REMOVE_LIQUIDITY_2_EVENT_SIGNATURE,
+            REMOVE_LIQUIDITY_IMBALANCE_2_EVENT_SIGNATURE,
+        ]),
+        3 => Some([
+            ADD_LIQUIDITY_3_EVENT_SIGNATURE,
+            REMOVE_LIQUIDITY_3_EVENT_SIGNATURE,
+            REMOVE_LIQUIDITY_IMBALANCE_3_EVENT_SIGNATURE,
+        ]),
+        4 => Some([
+            ADD_LIQUIDITY_4_EVENT_SIGNATURE,
+            REMOVE_LIQUIDITY_4_EVENT_SIGNATURE,
+            REMOVE_LIQUIDITY_IMBALANCE_4_EVENT_SIGNATURE,
+        ]),
+        _ => None,
+    }
+}
+
+//Rate used to normalize a balance with the given decimals to 18 decimals
+pub fn rate(decimals: u8) -> Result<U256, ArithmeticError> {
+    let exponent = 18_u8
+        .checked_sub(decimals)
+        .ok_or(ArithmeticError::UnsupportedDecimals(decimals))?;
+
+    Ok(PRECISION * U256::exp10(exponent as usize))
+}
+
+//Calculates the stableswap invariant D for the normalized balances, Ann*S + D = Ann*D + D^(n+1) / (n^n * prod(x))
+pub fn get_d(xp: &[U256], amp: U256) -> U256 {
+    let n = U256::from(xp.len());
+
+    let s = xp.iter().fold(U256::zero(), |acc, x| acc + x);
+    if s.is_zero() {
+        return U256::zero();
+    }
+
+    let mut d = s;
+    let ann = amp * n;
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut d_p = d;
+        for x in xp.iter() {
+            d_p = d_p * d / (*x * n);
+        }
+
+        let d_prev = d;
+        d = (ann * s / A_PRECISION + d_p * n) * d
+            / ((ann - A_PRECISION) * d / A_PRECISION + (n + 1) * d_p);
+
+        if abs_diff(d, d_prev) <= U256::one() {
+            break;
+        }
+    }
+
+    d
+}
+
+//Calculates the new normalized balance of coin j, given that the normalized balance of coin i is x
+pub fn get_y(i: usize, j: usize, x: U256, xp: &[U256], amp: U256) -> U256 {
+    let d = get_d(xp, amp);
+
+    let mut xp = xp.to_vec();
+    xp[i] = x;
+
+    get_y_d(j, &xp, d, amp)
+}
+
+//Calculates the normalized balance of coin i that keeps the invariant at D, given the normalized balances of the other coins
+pub fn get_y_d(i: usize, xp: &[U256], d: U256, amp: U256) -> U256 {
+    let n = U256::from(xp.len());
+    let ann = amp * n;
+
+    let mut c = d;
+    let mut s = U256::zero();
+
+    for (k, x_k) in xp.iter().enumerate() {
+        if k == i {
+            continue;
+        }
+
+        s += *x_k;
+        c = c * d / (*x_k * n);
+    }
+
+    c = c * d * A_PRECISION / (ann * n);
+    let b = s + d * A_PRECISION / ann;
+
+    let mut y = d;
+    for _ in 0..MAX_ITERATIONS {
+        let y_prev = y;
+        y = (y * y + c) / (U256::from(2) * y + b - d);
+
+        if abs_diff(y, y_prev) <= U256::one() {
+            break;
+        }
+    }
+
+    y
+}
+
+fn abs_diff(a: U256, b: U256) -> U256 {
+    if a > b {
+        a - b
+    } else {
+        b - a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use ethers::{
+        abi::Token,
+        types::{Log, H160, H256, U256},
+    };
+
+    use crate::{
+        amm::AutomatedMarketMaker,
+        errors::{ArithmeticError, SwapSimulationError},
+    };
+
+    use super::{CurveStableSwapPool, ADD_LIQUIDITY_3_EVENT_SIGNATURE};
+
+    fn three_pool() -> eyre::Result<CurveStableSwapPool> {
+        Ok(CurveStableSwapPool {
+            address: H160::from_str("0xbEbc44782C7dB0a1A60Cb6fe97d0b483032FF1C7")?,
+            tokens: vec![
+                H160::from_str("0x6B175474E89094C44Da98b954EedeAC495271d0F")?,
+                H160::from_str("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48")?,
+                H160::from_str("0xdAC17F958D2ee523a2206206994597C13D831ec7")?,
+            ],
+            token_decimals: vec![18, 6, 6],
+            balances: vec![
+                U256::from_dec_str("100000000000000000000000000")?,
+                U256::from_dec_str("100000000000000")?,
+                U256::from_dec_str("100000000000000")?,
+            ],
+            a: U256::from(200000),
+            fee: U256::from(1000000),
+            admin_fee: U256::from(5000000000_u64),
+        })
+    }
+
+    #[test]
+    fn test_simulate_swap() -> eyre::Result<()> {
+        let pool = three_pool()?;
+
+        //Swap 1000 DAI for USDC in a balanced pool, the output should be close to 1:1 minus the 0.01% fee
+        let amount_in = U256::from_dec_str("1000000000000000000000")?;
+        let amount_out = pool.simulate_swap(pool.tokens[0], amount_in)?;
+
+        assert!(amount_out < U256::from(1000000000));
+        assert!(amount_out > U256::from(999800000));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulate_swap_mut() -> eyre::Result<()> {
+        let mut pool = three_pool()?;
+
+        let amount_in = U256::from(1000000000000_u64);
+        let amount_out = pool.simulate_swap(pool.tokens[1], amount_in)?;
+        let amount_out_mut = pool.simulate_swap_mut(pool.tokens[1], amount_in)?;
+        assert_eq!(amount_out, amount_out_mut);
+
+        //After moving liquidity out of DAI, the next swap into DAI should return less
+        let second_amount_out = pool.simulate_swap(pool.tokens[1], amount_in)?;
+        assert!(second_amount_out < amount_out);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsupported_decimals() -> eyre::Result<()> {
+        let mut pool = three_pool()?;
+        pool.token_decimals[1] = 24;
+
+        assert!(matches!(
+            pool.simulate_swap(pool.tokens[0], U256::exp10(18)),
+            Err(SwapSimulationError::ArithmeticError(
+                ArithmeticError::UnsupportedDecimals(24)
+            ))
+        ));
+        assert!(matches!(
+            pool.calculate_price(pool.tokens[0]),
+            Err(ArithmeticError::UnsupportedDecimals(24))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_calculate_price() -> eyre::Result<()> {
+        let pool = three_pool()?;
+
+        let price = pool.calculate_price(pool.tokens[0])?.to_f64();
+        assert!((price - 1.0).abs() < 1e-9);
+
+        //Making USDT scarce should increase its price relative to the other coins
+        let mut pool = pool;
+        pool.balances[2] /= 2;
+        let usdt_dai_price = pool.calculate_marginal_price(2, 0)?;
+        let dai_usdt_price = pool.calculate_marginal_price(0, 2)?;
+        assert!(usdt_dai_price > 1.0);
+        assert!((usdt_dai_price * dai_usdt_price - 1.0).abs() < 1e-9);
+
+        assert!(pool.calculate_marginal_price(0, 3).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_from_add_liquidity_log() -> eyre::Result<()> {
+        let mut pool = three_pool()?;
+        let balances_before = pool.balances.clone();
+
+        let token_amounts = [
+            U256::from_dec_str("1000000000000000000000")?,
+            U256::from(1000000000),
+            U256::zero(),
+        ];
+        let fees = [U256::from(20000), U256::from(20000), U256::from(20000)];
+
+        let mut data = vec![];
+        for word in token_amounts.iter().chain(fees.iter()) {
+            data.push(Token::Uint(*word));
+        }
+        data.push(Token::Uint(U256::zero()));
+        data.push(Token::Uint(U256::zero()));
+
+        let log = Log {
+            address: pool.address,
+            topics: vec![ADD_LIQUIDITY_3_EVENT_SIGNATURE, H256::zero()],
+            data: ethers::abi::encode(&data).into(),
+            ..Default::default()
+        };
+
+        pool.sync_from_log(log)?;
+
+        //Half of the fees are admin fees, which are not added to the pool balances
+        for i in 0..3 {
+            assert_eq!(
+                pool.balances[i],
+                balances_before[i] + token_amounts[i] - fees[i] / 2
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,249 @@
+use ethers::types::U256;
+use serde::{Deserialize, Serialize};
+use uniswap_v3_math::{full_math::mul_div, tick_math::get_sqrt_ratio_at_tick};
+
+use crate::errors::ArithmeticError;
+
+use super::{
+    price::Price,
+    uniswap_v3::{MAX_SQRT_RATIO, MIN_SQRT_RATIO},
+};
+
+//Price step between two levels of a MarketDepth in basis points, the n-th level is n steps away from the spot price
+pub const MARKET_DEPTH_LEVEL_BPS: u32 = 100;
+
+const Q192: U256 = U256([0, 0, 0, 1]);
+
+//Cumulative amount of token a, in its smallest unit, that the AMM buys (bids) or sells (asks) until the price of token a
+//denominated in token b reaches each level. Levels are ordered from the spot price outwards and do not include fees.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MarketDepth {
+    pub bids: Vec<(Price, U256)>,
+    pub asks: Vec<(Price, U256)>,
+}
+
+impl MarketDepth {
+    //One row per level with the side, the price and the cumulative amount, bids first
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("side,price,amount\n");
+        for (side, levels) in [("bid", &self.bids), ("ask", &self.asks)] {
+            for (price, amount) in levels {
+                csv.push_str(&format!("{side},{price},{amount}\n"));
+            }
+        }
+
+        csv
+    }
+}
+
+//The reserves of a constant product pool are a single full range position of liquidity sqrt(reserve_0 * reserve_1)
+pub fn constant_product_depth(
+    reserve_0: U256,
+    reserve_1: U256,
+    token_a_decimals: u8,
+    token_b_decimals: u8,
+    num_levels: usize,
+) -> Result<MarketDepth, ArithmeticError> {
+    if reserve_0.is_zero() || reserve_1.is_zero() {
+        return Err(ArithmeticError::YIsZero);
+    }
+
+    let sqrt_price_x96 = mul_div(reserve_1, Q192, reserve_0)?.integer_sqrt();
+    let liquidity = U256::try_from(reserve_0.full_mul(reserve_1).integer_sqrt())
+        .ok()
+        .filter(|liquidity| *liquidity <= U256::from(u128::MAX))
+        .ok_or(ArithmeticError::U128ConversionError)?
+        .as_u128();
+
+    concentrated_liquidity_depth(
+        sqrt_price_x96,
+        liquidity,
+        0,
+        &[],
+        token_a_decimals,
+        token_b_decimals,
+        num_levels,
+    )
+}
+
+//Depth of a pool with `liquidity` active at the sqrt price of `tick`, `ticks` are the initialized ticks with their liquidity net.
+//The active liquidity changes at every tick between the spot price and a level, ticks that are not in `ticks` are not crossed.
+pub fn concentrated_liquidity_depth(
+    sqrt_price_x96: U256,
+    liquidity: u128,
+    tick: i32,
+    ticks: &[(i32, i128)],
+    token_a_decimals: u8,
+    token_b_decimals: u8,
+    num_levels: usize,
+) -> Result<MarketDepth, ArithmeticError> {
+    let mut ticks_below = ticks
+        .iter()
+        .filter(|(initialized_tick, _)| *initialized_tick <= tick)
+        .copied()
+        .collect::<Vec<(i32, i128)>>();
+    ticks_below.sort_unstable_by_key(|(initialized_tick, _)| -initialized_tick);
+
+    let mut ticks_above = ticks
+        .iter()
+        .filter(|(initialized_tick, _)| *initialized_tick > tick)
+        .copied()
+        .collect::<Vec<(i32, i128)>>();
+    ticks_above.sort_unstable_by_key(|(initialized_tick, _)| *initialized_tick);
+
+    let liquidity = i128::try_from(liquidity).unwrap_or(i128::MAX);
+    let decimals = (token_a_decimals, token_b_decimals);
+
+    Ok(MarketDepth {
+        bids: depth_side(
+            sqrt_price_x96,
+            liquidity,
+            ticks_below,
+            false,
+            decimals,
+            num_levels,
+        )?,
+        asks: depth_side(
+            sqrt_price_x96,
+            liquidity,
+            ticks_above,
+            true,
+            decimals,
+            num_levels,
+        )?,
+    })
+}
+
+//Walks from the spot price towards lower (bids) or higher (asks) prices, `ticks` are ordered in the direction of the walk
+fn depth_side(
+    sqrt_price_x96: U256,
+    mut liquidity: i128,
+    ticks: Vec<(i32, i128)>,
+    ascending: bool,
+    (token_a_decimals, token_b_decimals): (u8, u8),
+    num_levels: usize,
+) -> Result<Vec<(Price, U256)>, ArithmeticError> {
+    let mut levels = vec![];
+    let mut current_sqrt_price = sqrt_price_x96;
+    let mut amount = U256::zero();
+    let mut ticks = ticks.into_iter().peekable();
+
+    for level in 1..=num_levels {
+        let step = level as f64 * MARKET_DEPTH_LEVEL_BPS as f64 / 10_000.0;
+        let price_factor = if ascending { 1.0 + step } else { 1.0 - step };
+        if price_factor <= 0.0 {
+            break;
+        }
+
+        let target_sqrt_price = mul_div(
+            sqrt_price_x96,
+            U256::from((price_factor.sqrt() * 1e18) as u128),
+            U256::exp10(18),
+        )?
+        .clamp(MIN_SQRT_RATIO, MAX_SQRT_RATIO);
+
+        while let Some((initialized_tick, liquidity_net)) = ticks.peek().copied() {
+            let tick_sqrt_price = get_sqrt_ratio_at_tick(initialized_tick)?;
+            let crossed = if ascending {
+                tick_sqrt_price <= target_sqrt_price
+            } else {
+                tick_sqrt_price >= target_sqrt_price
+            };
+            if !crossed {
+                break;
+            }
+
+            amount += amount_0_delta(current_sqrt_price, tick_sqrt_price, liquidity)?;
+            current_sqrt_price = tick_sqrt_price;
+
+            //Crossing a tick downwards removes the liquidity that the tick added
+            liquidity = if ascending {
+                liquidity.saturating_add(liquidity_net)
+            } else {
+                liquidity.saturating_sub(liquidity_net)
+            };
+            ticks.next();
+        }
+
+        amount += amount_0_delta(current_sqrt_price, target_sqrt_price, liquidity)?;
+        current_sqrt_price = target_sqrt_price;
+
+        levels.push((
+            Price::from_sqrt_price_x96(target_sqrt_price, token_a_decimals, token_b_decimals)?,
+            amount,
+        ));
+
+        if target_sqrt_price == MIN_SQRT_RATIO || target_sqrt_price == MAX_SQRT_RATIO {
+            break;
+        }
+    }
+
+    Ok(levels)
+}
+
+//Amount of token 0 that moves the price between the two sqrt prices, L * (sqrt_b - sqrt_a) / (sqrt_a * sqrt_b)
+fn amount_0_delta(
+    sqrt_price_a: U256,
+    sqrt_price_b: U256,
+    liquidity: i128,
+) -> Result<U256, ArithmeticError> {
+    let (sqrt_price_lower, sqrt_price_upper) = if sqrt_price_a < sqrt_price_b {
+        (sqrt_price_a, sqrt_price_b)
+    } else {
+        (sqrt_price_b, sqrt_price_a)
+    };
+
+    if liquidity <= 0 || sqrt_price_lower.is_zero() {
+        return Ok(U256::zero());
+    }
+
+    Ok(mul_div(
+        U256::from(liquidity as u128) << 96,
+        sqrt_price_upper - sqrt_price_lower,
+        sqrt_price_upper,
+    )? / sqrt_price_lower)
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::U256;
+
+    use super::{concentrated_liquidity_depth, constant_product_depth};
+
+    #[test]
+    fn test_constant_product_depth() -> eyre::Result<()> {
+        let reserve = U256::exp10(21);
+        let depth = constant_product_depth(reserve, reserve, 18, 18, 2)?;
+        assert_eq!((depth.bids.len(), depth.asks.len()), (2, 2));
+
+        //Selling token a down to a price of 0.99 adds 1000 * (1 / sqrt(0.99) - 1) of it to the reserves,
+        //buying it up to a price of 1.01 takes 1000 * (1 - 1 / sqrt(1.01)) out of them
+        let amount = |level: &(_, U256)| level.1.as_u128() as f64 / 1e18;
+        assert!((amount(&depth.bids[0]) - 5.0378).abs() < 1e-3);
+        assert!((amount(&depth.asks[0]) - 4.9628).abs() < 1e-3);
+        assert!(depth.bids[1].1 > depth.bids[0].1);
+        assert!((depth.bids[0].0.to_f64() - 0.99).abs() < 1e-9);
+        assert!((depth.asks[1].0.to_f64() - 1.02).abs() < 1e-9);
+
+        let csv = depth.to_csv();
+        assert_eq!(csv.lines().count(), 5);
+        assert!(csv.starts_with("side,price,amount\nbid,"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_concentrated_liquidity_depth() -> eyre::Result<()> {
+        //A single position from tick -50 to tick 50 at a price of 1, a price of 1.01 is past the upper tick
+        let liquidity = 10_u128.pow(21);
+        let ticks = [(-50, liquidity as i128), (50, -(liquidity as i128))];
+        let depth =
+            concentrated_liquidity_depth(U256::one() << 96, liquidity, 0, &ticks, 18, 18, 2)?;
+
+        assert!(!depth.asks[0].1.is_zero());
+        assert_eq!(depth.asks[0].1, depth.asks[1].1);
+        assert_eq!(depth.bids[0].1, depth.bids[1].1);
+
+        Ok(())
+    }
+}
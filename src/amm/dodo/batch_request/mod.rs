@@ -0,0 +1,119 @@
+use std::sync::Arc;
+
+use ethers::{
+    abi::Token,
+    contract::Multicall,
+    providers::Middleware,
+    types::{BlockNumber, Bytes, H160},
+};
+
+use crate::{amm::uniswap_v3::IErc20, errors::AMMError};
+
+use super::{DodoPool, IDodoPool};
+
+//The fee rates are read for the zero address, DODO only charges a different maintainer fee to specific traders
+
+pub async fn get_dodo_pool_data_batch_request<M: Middleware>(
+    mut pools: Vec<&mut DodoPool>,
+    block_number: Option<u64>,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    let block = block_number
+        .map(BlockNumber::from)
+        .unwrap_or(BlockNumber::Latest);
+
+    let mut multicall = Multicall::new(middleware.clone(), None).await?.block(block);
+
+    for pool in pools.iter() {
+        let dodo_pool = IDodoPool::new(pool.address, middleware.clone());
+        multicall.add_call(dodo_pool.base_token(), true);
+        multicall.add_call(dodo_pool.quote_token(), true);
+        multicall.add_call(dodo_pool.get_pmm_state_for_call(), true);
+        multicall.add_call(dodo_pool.get_user_fee_rate(H160::zero()), true);
+    }
+
+    let results = multicall.call_raw().await?;
+    multicall.clear_calls();
+
+    //Pools that do not implement the interface are left empty so that they are removed by remove_empty_amms
+    let mut populated_pools = vec![];
+    for (pool, results) in pools.iter_mut().zip(results.chunks(4)) {
+        if decode_pool_data(pool, results).is_some() {
+            for token in [pool.base_token, pool.quote_token] {
+                multicall.add_call(IErc20::new(token, middleware.clone()).decimals(), true);
+            }
+            populated_pools.push(pool);
+        } else {
+            tracing::debug!(?pool.address, "pool does not implement the dodo v2 interface");
+        }
+    }
+
+    if populated_pools.is_empty() {
+        return Ok(());
+    }
+
+    let decimals = multicall.call_raw().await?;
+    for (pool, decimals) in populated_pools.into_iter().zip(decimals.chunks(2)) {
+        let decimals = decimals
+            .iter()
+            .map(|decimals| {
+                decimals
+                    .as_ref()
+                    .ok()
+                    .and_then(|decimals| decimals.clone().into_uint())
+                    .map(|decimals| decimals.as_u32() as u8)
+            })
+            .collect::<Option<Vec<u8>>>()
+            .ok_or(AMMError::BatchRequestError(pool.address))?;
+
+        pool.base_token_decimals = decimals[0];
+        pool.quote_token_decimals = decimals[1];
+    }
+
+    Ok(())
+}
+
+//Syncs the PMM state and the fee rates, which the owner of a private pool can reset at any time
+pub async fn sync_dodo_pool_batch_request<M: Middleware>(
+    pool: &mut DodoPool,
+    block_number: Option<u64>,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    let block = block_number
+        .map(BlockNumber::from)
+        .unwrap_or(BlockNumber::Latest);
+
+    let dodo_pool = IDodoPool::new(pool.address, middleware.clone());
+    let mut multicall = Multicall::new(middleware, None).await?.block(block);
+
+    multicall.add_call(dodo_pool.get_pmm_state_for_call(), false);
+    multicall.add_call(dodo_pool.get_user_fee_rate(H160::zero()), false);
+
+    let results = multicall.call_raw().await?;
+    decode_pmm_state(pool, &results).ok_or(AMMError::SyncError(pool.address))
+}
+
+fn decode_pool_data(pool: &mut DodoPool, results: &[Result<Token, Bytes>]) -> Option<()> {
+    pool.base_token = results.first()?.as_ref().ok()?.clone().into_address()?;
+    pool.quote_token = results.get(1)?.as_ref().ok()?.clone().into_address()?;
+
+    decode_pmm_state(pool, results.get(2..)?)
+}
+
+fn decode_pmm_state(pool: &mut DodoPool, results: &[Result<Token, Bytes>]) -> Option<()> {
+    let state = results.first()?.as_ref().ok()?.clone().into_tuple()?;
+    let fee_rates = results.get(1)?.as_ref().ok()?.clone().into_tuple()?;
+
+    pool.i = state.first()?.clone().into_uint()?;
+    pool.k = state.get(1)?.clone().into_uint()?;
+    pool.base_reserve = state.get(2)?.clone().into_uint()?;
+    pool.quote_reserve = state.get(3)?.clone().into_uint()?;
+    pool.base_target = state.get(4)?.clone().into_uint()?;
+    pool.quote_target = state.get(5)?.clone().into_uint()?;
+    pool.r_state = state.get(6)?.clone().into_uint()?.into();
+
+    pool.lp_fee_rate = fee_rates.first()?.clone().into_uint()?;
+    pool.mt_fee_rate = fee_rates.get(1)?.clone().into_uint()?;
+
+    Some(())
+}
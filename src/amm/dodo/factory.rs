@@ -0,0 +1,167 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::{
+    abi::RawLog,
+    prelude::EthEvent,
+    providers::Middleware,
+    types::{Log, H160, H256},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    amm::{
+        factory::{AutomatedMarketMakerFactory, Factory},
+        AMM,
+    },
+    errors::AMMError,
+};
+
+use super::{batch_request, DodoPool, DodoPoolType};
+
+//The DVM and DPP factories emit the same event layout under different names
+#[derive(Clone, Debug, EthEvent)]
+#[ethevent(name = "NewDVM", abi = "NewDVM(address,address,address,address)")]
+pub struct NewDVMFilter {
+    pub base_token: H160,
+    pub quote_token: H160,
+    pub creator: H160,
+    pub dvm: H160,
+}
+
+#[derive(Clone, Debug, EthEvent)]
+#[ethevent(name = "NewDPP", abi = "NewDPP(address,address,address,address)")]
+pub struct NewDPPFilter {
+    pub base_token: H160,
+    pub quote_token: H160,
+    pub creator: H160,
+    pub dpp: H160,
+}
+
+pub const NEW_DVM_EVENT_SIGNATURE: H256 = H256([
+    175, 92, 95, 18, 168, 15, 201, 55, 82, 13, 246, 252, 174, 214, 98, 98, 164, 204, 119, 94, 15,
+    63, 206, 175, 122, 124, 254, 71, 109, 154, 117, 29,
+]);
+
+pub const NEW_DPP_EVENT_SIGNATURE: H256 = H256([
+    132, 148, 254, 89, 76, 213, 8, 112, 33, 212, 177, 23, 88, 162, 187, 199, 190, 40, 164, 48, 233,
+    79, 43, 38, 141, 102, 142, 89, 145, 237, 59, 138,
+]);
+
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct DodoFactory {
+    pub address: H160,
+    pub creation_block: u64,
+    pub pool_type: DodoPoolType,
+}
+
+#[async_trait]
+impl AutomatedMarketMakerFactory for DodoFactory {
+    fn address(&self) -> H160 {
+        self.address
+    }
+
+    fn creation_block(&self) -> u64 {
+        self.creation_block
+    }
+
+    fn amm_created_event_signature(&self) -> H256 {
+        match self.pool_type {
+            DodoPoolType::DVM => NEW_DVM_EVENT_SIGNATURE,
+            DodoPoolType::DPP => NEW_DPP_EVENT_SIGNATURE,
+        }
+    }
+
+    async fn new_amm_from_log<M: 'static + Middleware>(
+        &self,
+        log: Log,
+        middleware: Arc<M>,
+    ) -> Result<AMM, AMMError<M>> {
+        let pool = self.new_empty_amm_from_log(log)?;
+
+        Ok(AMM::DodoPool(
+            DodoPool::new_from_address(pool.address(), self.pool_type, middleware).await?,
+        ))
+    }
+
+    fn new_empty_amm_from_log(&self, log: Log) -> Result<AMM, ethers::abi::Error> {
+        let (address, base_token, quote_token) = match self.pool_type {
+            DodoPoolType::DVM => {
+                let new_dvm_event = NewDVMFilter::decode_log(&RawLog::from(log))?;
+                (
+                    new_dvm_event.dvm,
+                    new_dvm_event.base_token,
+                    new_dvm_event.quote_token,
+                )
+            }
+            DodoPoolType::DPP => {
+                let new_dpp_event = NewDPPFilter::decode_log(&RawLog::from(log))?;
+                (
+                    new_dpp_event.dpp,
+                    new_dpp_event.base_token,
+                    new_dpp_event.quote_token,
+                )
+            }
+        };
+
+        Ok(AMM::DodoPool(DodoPool {
+            address,
+            base_token,
+            quote_token,
+            pool_type: self.pool_type,
+            ..Default::default()
+        }))
+    }
+
+    async fn get_all_amms<M: 'static + Middleware>(
+        &self,
+        to_block: Option<u64>,
+        middleware: Arc<M>,
+        step: u64,
+    ) -> Result<Vec<AMM>, AMMError<M>> {
+        if let Some(block) = to_block {
+            Factory::DodoFactory(*self)
+                .get_all_pools_from_logs(self.creation_block, block, step, middleware)
+                .await
+        } else {
+            return Err(AMMError::BlockNumberNotFound);
+        }
+    }
+
+    async fn populate_amm_data<M: Middleware>(
+        &self,
+        amms: &mut [AMM],
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        let step = 127; //Max batch size for call
+        for amm_chunk in amms.chunks_mut(step) {
+            let pools = amm_chunk
+                .iter_mut()
+                .filter_map(|amm| match amm {
+                    AMM::DodoPool(pool) => Some(pool),
+                    _ => None,
+                })
+                .collect::<Vec<&mut DodoPool>>();
+
+            batch_request::get_dodo_pool_data_batch_request(
+                pools,
+                block_number,
+                middleware.clone(),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl DodoFactory {
+    pub fn new(address: H160, creation_block: u64, pool_type: DodoPoolType) -> DodoFactory {
+        DodoFactory {
+            address,
+            creation_block,
+            pool_type,
+        }
+    }
+}
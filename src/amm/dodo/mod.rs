@@ -0,0 +1,704 @@
+pub mod batch_request;
+pub mod factory;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::{
+    abi::RawLog,
+    prelude::{abigen, EthEvent},
+    providers::Middleware,
+    types::{Log, H160, H256, U256},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    amm::{price::Price, u256_to_f64, AutomatedMarketMaker},
+    errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
+};
+
+abigen!(
+    IDodoPool,
+    r#"[
+        function _BASE_TOKEN_() external view returns (address)
+        function _QUOTE_TOKEN_() external view returns (address)
+        function getPMMStateForCall() external view returns (uint256 i, uint256 K, uint256 B, uint256 Q, uint256 B0, uint256 Q0, uint256 R)
+        function getUserFeeRate(address user) external view returns (uint256 lpFeeRate, uint256 mtFeeRate)
+        function querySellBase(address trader, uint256 payBaseAmount) external view returns (uint256 receiveQuoteAmount, uint256 mtFee)
+        function querySellQuote(address trader, uint256 payQuoteAmount) external view returns (uint256 receiveBaseAmount, uint256 mtFee)
+    ]"#;
+);
+
+#[derive(Clone, Debug, EthEvent)]
+#[ethevent(
+    name = "DODOSwap",
+    abi = "DODOSwap(address,address,uint256,uint256,address,address)"
+)]
+pub struct DodoSwapFilter {
+    pub from_token: H160,
+    pub to_token: H160,
+    pub from_amount: U256,
+    pub to_amount: U256,
+    pub trader: H160,
+    pub receiver: H160,
+}
+
+pub const DODO_SWAP_EVENT_SIGNATURE: H256 = H256([
+    194, 192, 36, 94, 5, 109, 95, 176, 149, 240, 76, 214, 55, 59, 199, 112, 128, 46, 189, 30, 108,
+    145, 142, 183, 143, 222, 248, 67, 205, 179, 123, 15,
+]);
+
+//Prices, k and fee rates are 18 decimal fixed point numbers
+pub const ONE: U256 = U256([1000000000000000000, 0, 0, 0]);
+const ONE2: U256 = U256([12919594847110692864, 54210108624275221, 0, 0]);
+
+//DODO V2 pool types, vending machines are curves around the current reserves while private pools keep stored targets
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DodoPoolType {
+    #[default]
+    DVM,
+    DPP,
+}
+
+//Whether the pool holds more (BelowOne) or less (AboveOne) base token than its target
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RState {
+    #[default]
+    One,
+    AboveOne,
+    BelowOne,
+}
+
+impl From<U256> for RState {
+    fn from(r: U256) -> Self {
+        match r.low_u32() {
+            1 => RState::AboveOne,
+            2 => RState::BelowOne,
+            _ => RState::One,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DodoPool {
+    pub address: H160,
+    pub base_token: H160,
+    pub base_token_decimals: u8,
+    pub quote_token: H160,
+    pub quote_token_decimals: u8,
+    pub pool_type: DodoPoolType,
+    pub i: U256,
+    pub k: U256,
+    pub base_reserve: U256,
+    pub quote_reserve: U256,
+    pub base_target: U256,
+    pub quote_target: U256,
+    pub r_state: RState,
+    pub lp_fee_rate: U256,
+    pub mt_fee_rate: U256,
+}
+
+//Snapshot of the pool passed to the pricing functions, same as `PMMPricing.PMMState`
+#[derive(Debug, Clone, Copy)]
+struct PMMState {
+    i: U256,
+    k: U256,
+    b: U256,
+    q: U256,
+    b0: U256,
+    q0: U256,
+    r: RState,
+}
+
+#[async_trait]
+impl AutomatedMarketMaker for DodoPool {
+    fn address(&self) -> H160 {
+        self.address
+    }
+
+    async fn sync<M: Middleware>(&mut self, middleware: Arc<M>) -> Result<(), AMMError<M>> {
+        batch_request::sync_dodo_pool_batch_request(self, None, middleware).await
+    }
+
+    async fn populate_data<M: Middleware>(
+        &mut self,
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        batch_request::get_dodo_pool_data_batch_request(vec![self], block_number, middleware).await
+    }
+
+    //Deposits and withdrawals do not emit a swap, the pool has to be synced to pick them up
+    fn sync_on_event_signatures(&self) -> Vec<H256> {
+        vec![DODO_SWAP_EVENT_SIGNATURE]
+    }
+
+    fn sync_from_log(&mut self, log: Log) -> Result<(), EventLogError> {
+        let event_signature = log.topics[0];
+
+        if event_signature == DODO_SWAP_EVENT_SIGNATURE {
+            let swap_event = DodoSwapFilter::decode_log(&RawLog::from(log))?;
+
+            //The event only has the amount sent to the receiver, the maintainer fee and the new R state are computed again
+            let (_, mt_fee, r_state) = self.sell(swap_event.from_token, swap_event.from_amount)?;
+            self.apply_swap(
+                swap_event.from_token,
+                swap_event.from_amount,
+                swap_event.to_amount + mt_fee,
+                r_state,
+            );
+
+            Ok(())
+        } else {
+            Err(EventLogError::InvalidEventSignature)
+        }
+    }
+
+    //Calculates the mid price of the base token in terms of the quote token, same as `getMidPrice`
+    fn calculate_price(&self, base_token: H160) -> Result<Price, ArithmeticError> {
+        self.check_liquidity()?;
+
+        let state = self.pmm_state().map_err(|_| ArithmeticError::YIsZero)?;
+
+        //The mid price is derived from the reserve that is below its target
+        let reserve = if state.r == RState::BelowOne {
+            state.q
+        } else {
+            state.b
+        };
+        if reserve.is_zero() {
+            return Err(ArithmeticError::ZeroLiquidity(self.address));
+        }
+
+        let mid_price = if state.r == RState::BelowOne {
+            let r = div_floor(state.q0 * state.q0 / state.q, state.q);
+            let r = ONE - state.k + mul_floor(state.k, r);
+            div_floor(state.i, r)
+        } else {
+            let r = div_floor(state.b0 * state.b0 / state.b, state.b);
+            let r = ONE - state.k + mul_floor(state.k, r);
+            mul_floor(state.i, r)
+        };
+
+        let price = u256_to_f64(mid_price) / 1e18
+            * 10_f64.powi(self.base_token_decimals as i32 - self.quote_token_decimals as i32);
+
+        if base_token == self.base_token {
+            Price::from_f64(price)
+        } else {
+            Price::from_f64(1.0 / price)
+        }
+    }
+
+    fn tokens(&self) -> Vec<H160> {
+        vec![self.base_token, self.quote_token]
+    }
+
+    fn simulate_swap(&self, token_in: H160, amount_in: U256) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        let (amount_out, _, _) = self.sell(token_in, amount_in)?;
+
+        tracing::trace!(?amount_out);
+
+        Ok(amount_out)
+    }
+
+    fn simulate_swap_mut(
+        &mut self,
+        token_in: H160,
+        amount_in: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        let (amount_out, mt_fee, r_state) = self.sell(token_in, amount_in)?;
+
+        tracing::trace!(?amount_out);
+        tracing::trace!(?self.base_reserve, ?self.quote_reserve, "pool reserves before");
+
+        self.apply_swap(token_in, amount_in, amount_out + mt_fee, r_state);
+
+        tracing::trace!(?self.base_reserve, ?self.quote_reserve, "pool reserves after");
+
+        Ok(amount_out)
+    }
+
+    fn swap_fee(&self, _token_in: H160) -> f64 {
+        u256_to_f64(self.lp_fee_rate + self.mt_fee_rate) / 1e18
+    }
+
+    fn token_decimals(&self) -> Vec<u8> {
+        vec![self.base_token_decimals, self.quote_token_decimals]
+    }
+
+    fn get_token_out(&self, token_in: H160) -> H160 {
+        if self.base_token == token_in {
+            self.quote_token
+        } else {
+            self.base_token
+        }
+    }
+}
+
+impl DodoPool {
+    //Creates a new instance of the pool from the pool address, and syncs the pool data
+    pub async fn new_from_address<M: Middleware>(
+        pool_address: H160,
+        pool_type: DodoPoolType,
+        middleware: Arc<M>,
+    ) -> Result<Self, AMMError<M>> {
+        let mut pool = DodoPool {
+            address: pool_address,
+            pool_type,
+            ..Default::default()
+        };
+
+        pool.populate_data(None, middleware).await?;
+
+        if !pool.data_is_populated() {
+            return Err(AMMError::PoolDataError);
+        }
+
+        Ok(pool)
+    }
+
+    pub fn data_is_populated(&self) -> bool {
+        !(self.base_token.is_zero()
+            || self.quote_token.is_zero()
+            || self.i.is_zero()
+            || self.base_reserve.is_zero()
+            || self.quote_reserve.is_zero())
+    }
+
+    //Same as `getPMMState`, the targets are adjusted to the current reserves
+    fn pmm_state(&self) -> Result<PMMState, SwapSimulationError> {
+        let mut state = PMMState {
+            i: self.i,
+            k: self.k,
+            b: self.base_reserve,
+            q: self.quote_reserve,
+            b0: self.base_target,
+            q0: self.quote_target,
+            r: self.r_state,
+        };
+
+        //Vending machines do not store targets, the base target is solved from the reserves every time
+        if self.pool_type == DodoPoolType::DVM {
+            state.b0 = U256::zero();
+            state.q0 = U256::zero();
+            state.r = RState::AboveOne;
+        }
+
+        match state.r {
+            RState::BelowOne => {
+                state.q0 = solve_quadratic_function_for_target(
+                    state.q,
+                    state.b - state.b0,
+                    state.i,
+                    state.k,
+                );
+            }
+            RState::AboveOne => {
+                state.b0 = solve_quadratic_function_for_target(
+                    state.b,
+                    state.q - state.q0,
+                    reciprocal_floor(state.i)?,
+                    state.k,
+                );
+            }
+            RState::One => {}
+        }
+
+        Ok(state)
+    }
+
+    //Pools can hold a single token, e.g. vending machines that are created with base tokens only, but not none
+    fn check_liquidity(&self) -> Result<(), ArithmeticError> {
+        if self.base_reserve.is_zero() && self.quote_reserve.is_zero() {
+            return Err(ArithmeticError::ZeroLiquidity(self.address));
+        }
+
+        Ok(())
+    }
+
+    //Mirrors `querySellBase` and `querySellQuote`, returns the amount out after fees, the maintainer fee and the new R state
+    pub fn sell(
+        &self,
+        token_in: H160,
+        amount_in: U256,
+    ) -> Result<(U256, U256, RState), SwapSimulationError> {
+        self.check_liquidity()?;
+
+        let state = self.pmm_state()?;
+
+        let (amount_out, r_state) = if token_in == self.base_token {
+            sell_base_token(&state, amount_in)?
+        } else if token_in == self.quote_token {
+            sell_quote_token(&state, amount_in)?
+        } else {
+            return Err(SwapSimulationError::TokenNotInPool(token_in));
+        };
+
+        let mt_fee = mul_floor(amount_out, self.mt_fee_rate);
+        let amount_out = amount_out - mul_floor(amount_out, self.lp_fee_rate) - mt_fee;
+
+        Ok((amount_out, mt_fee, r_state))
+    }
+
+    //The lp fee stays in the pool while `amount_out` includes the maintainer fee sent out of the pool
+    fn apply_swap(&mut self, token_in: H160, amount_in: U256, amount_out: U256, r_state: RState) {
+        //Private pools store the targets used for the swap when the R state changes
+        if let Ok(state) = self.pmm_state() {
+            self.base_target = state.b0;
+            self.quote_target = state.q0;
+        }
+
+        if token_in == self.base_token {
+            self.base_reserve += amount_in;
+            self.quote_reserve = self.quote_reserve.saturating_sub(amount_out);
+        } else {
+            self.quote_reserve += amount_in;
+            self.base_reserve = self.base_reserve.saturating_sub(amount_out);
+        }
+
+        if self.pool_type == DodoPoolType::DPP {
+            self.r_state = r_state;
+        }
+    }
+}
+
+//Same as `PMMPricing.sellBaseToken`
+fn sell_base_token(
+    state: &PMMState,
+    pay_base_amount: U256,
+) -> Result<(U256, RState), SwapSimulationError> {
+    match state.r {
+        RState::One => Ok((
+            r_one_sell_base_token(state, pay_base_amount)?,
+            RState::BelowOne,
+        )),
+        RState::AboveOne => {
+            let back_to_one_pay_base = state.b0 - state.b;
+            let back_to_one_receive_quote = state.q - state.q0;
+
+            if pay_base_amount < back_to_one_pay_base {
+                let receive_quote_amount = general_integrate(
+                    state.b0,
+                    state.b + pay_base_amount,
+                    state.b,
+                    state.i,
+                    state.k,
+                )?;
+
+                Ok((
+                    receive_quote_amount.min(back_to_one_receive_quote),
+                    RState::AboveOne,
+                ))
+            } else if pay_base_amount == back_to_one_pay_base {
+                Ok((back_to_one_receive_quote, RState::One))
+            } else {
+                let receive_quote_amount = back_to_one_receive_quote
+                    + r_one_sell_base_token(state, pay_base_amount - back_to_one_pay_base)?;
+
+                Ok((receive_quote_amount, RState::BelowOne))
+            }
+        }
+        RState::BelowOne => Ok((
+            solve_quadratic_function_for_trade(
+                state.q0,
+                state.q,
+                pay_base_amount,
+                state.i,
+                state.k,
+            )?,
+            RState::BelowOne,
+        )),
+    }
+}
+
+//Same as `PMMPricing.sellQuoteToken`
+fn sell_quote_token(
+    state: &PMMState,
+    pay_quote_amount: U256,
+) -> Result<(U256, RState), SwapSimulationError> {
+    match state.r {
+        RState::One => Ok((
+            r_one_sell_quote_token(state, pay_quote_amount)?,
+            RState::AboveOne,
+        )),
+        RState::AboveOne => Ok((
+            solve_quadratic_function_for_trade(
+                state.b0,
+                state.b,
+                pay_quote_amount,
+                reciprocal_floor(state.i)?,
+                state.k,
+            )?,
+            RState::AboveOne,
+        )),
+        RState::BelowOne => {
+            let back_to_one_pay_quote = state.q0 - state.q;
+            let back_to_one_receive_base = state.b - state.b0;
+
+            if pay_quote_amount < back_to_one_pay_quote {
+                let receive_base_amount = general_integrate(
+                    state.q0,
+                    state.q + pay_quote_amount,
+                    state.q,
+                    reciprocal_floor(state.i)?,
+                    state.k,
+                )?;
+
+                Ok((
+                    receive_base_amount.min(back_to_one_receive_base),
+                    RState::BelowOne,
+                ))
+            } else if pay_quote_amount == back_to_one_pay_quote {
+                Ok((back_to_one_receive_base, RState::One))
+            } else {
+                let receive_base_amount = back_to_one_receive_base
+                    + r_one_sell_quote_token(state, pay_quote_amount - back_to_one_pay_quote)?;
+
+                Ok((receive_base_amount, RState::AboveOne))
+            }
+        }
+    }
+}
+
+fn r_one_sell_base_token(
+    state: &PMMState,
+    pay_base_amount: U256,
+) -> Result<U256, SwapSimulationError> {
+    solve_quadratic_function_for_trade(state.q0, state.q0, pay_base_amount, state.i, state.k)
+}
+
+fn r_one_sell_quote_token(
+    state: &PMMState,
+    pay_quote_amount: U256,
+) -> Result<U256, SwapSimulationError> {
+    solve_quadratic_function_for_trade(
+        state.b0,
+        state.b0,
+        pay_quote_amount,
+        reciprocal_floor(state.i)?,
+        state.k,
+    )
+}
+
+//Integrates the price curve from v2 to v1, same as `DODOMath._GeneralIntegrate`
+// res = (1 - k + k * v0^2 / v1 / v2) * i * (v1 - v2)
+pub fn general_integrate(
+    v0: U256,
+    v1: U256,
+    v2: U256,
+    i: U256,
+    k: U256,
+) -> Result<U256, SwapSimulationError> {
+    if v0.is_zero() || v1.is_zero() || v2.is_zero() {
+        return Err(SwapSimulationError::LiquidityUnderflow);
+    }
+
+    let fair_amount = i * (v1 - v2);
+    if k.is_zero() {
+        return Ok(fair_amount / ONE);
+    }
+
+    let v0_v0_v1_v2 = div_floor(v0 * v0 / v1, v2);
+    let penalty = mul_floor(k, v0_v0_v1_v2);
+
+    Ok((ONE - k + penalty) * fair_amount / ONE2)
+}
+
+//Solves the amount of v1 paid out for `delta` of the other side, same as `DODOMath._SolveQuadraticFunctionForTrade`
+pub fn solve_quadratic_function_for_trade(
+    v0: U256,
+    v1: U256,
+    delta: U256,
+    i: U256,
+    k: U256,
+) -> Result<U256, SwapSimulationError> {
+    if v0.is_zero() || v1.is_zero() {
+        return Err(SwapSimulationError::LiquidityUnderflow);
+    }
+
+    if delta.is_zero() {
+        return Ok(U256::zero());
+    }
+
+    if k.is_zero() {
+        return Ok(mul_floor(i, delta).min(v1));
+    }
+
+    if k == ONE {
+        // v1 - v2 = v1 * (temp / (1 + temp)), temp = i * delta * v1 / v0^2
+        let i_delta = i * delta;
+        let temp = match i_delta.checked_mul(v1) {
+            Some(i_delta_v1) => i_delta_v1 / (v0 * v0),
+            None => delta * v1 / v0 * i / v0,
+        };
+
+        return Ok(v1 * temp / (temp + ONE));
+    }
+
+    // b = k * v0^2 / v1 - i * delta - (1 - k) * v1
+    let part_2 = k * v0 / v1 * v0 + i * delta;
+    let mut b_abs = (ONE - k) * v1;
+
+    let b_sig = if b_abs >= part_2 {
+        b_abs -= part_2;
+        false
+    } else {
+        b_abs = part_2 - b_abs;
+        true
+    };
+    b_abs /= ONE;
+
+    // sqrt(b^2 + 4 * (1 - k) * k * v0^2)
+    let square_root = mul_floor((ONE - k) * 4, mul_floor(k, v0) * v0);
+    let square_root = (b_abs * b_abs + square_root).integer_sqrt();
+
+    let denominator = (ONE - k) * 2;
+    let numerator = if b_sig {
+        if square_root <= b_abs {
+            return Err(SwapSimulationError::LiquidityUnderflow);
+        }
+        square_root - b_abs
+    } else {
+        b_abs + square_root
+    };
+
+    let v2 = div_ceil(numerator, denominator);
+    if v2 > v1 {
+        Ok(U256::zero())
+    } else {
+        Ok(v1 - v2)
+    }
+}
+
+//Solves the target v0 of the side with reserve v1 after `delta` was paid into the other side, same as `DODOMath._SolveQuadraticFunctionForTarget`
+// v0 = v1 * (1 + (sqrt(1 + 4 * k * i * delta / v1) - 1) / 2k)
+pub fn solve_quadratic_function_for_target(v1: U256, delta: U256, i: U256, k: U256) -> U256 {
+    if v1.is_zero() {
+        return U256::zero();
+    }
+
+    if k.is_zero() {
+        return v1 + mul_floor(i, delta);
+    }
+
+    let ki = k * 4 * i;
+    let sqrt = if ki.is_zero() {
+        ONE
+    } else {
+        match ki.checked_mul(delta) {
+            Some(ki_delta) => (ki_delta / v1 + ONE2).integer_sqrt(),
+            None => (ki / v1 * delta + ONE2).integer_sqrt(),
+        }
+    };
+
+    let premium = div_floor(sqrt - ONE, k * 2) + ONE;
+
+    mul_floor(v1, premium)
+}
+
+fn mul_floor(target: U256, d: U256) -> U256 {
+    target * d / ONE
+}
+
+fn div_floor(target: U256, d: U256) -> U256 {
+    target * ONE / d
+}
+
+fn div_ceil(target: U256, d: U256) -> U256 {
+    let numerator = target * ONE;
+    let quotient = numerator / d;
+
+    if quotient * d < numerator {
+        quotient + 1
+    } else {
+        quotient
+    }
+}
+
+fn reciprocal_floor(target: U256) -> Result<U256, SwapSimulationError> {
+    if target.is_zero() {
+        return Err(SwapSimulationError::LiquidityUnderflow);
+    }
+
+    Ok(ONE2 / target)
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::{H160, U256};
+
+    use crate::amm::AutomatedMarketMaker;
+
+    use super::{DodoPool, DodoPoolType, RState, ONE};
+
+    //A private pool at its targets quoting 2000 quote per base
+    fn dpp(k: U256) -> DodoPool {
+        DodoPool {
+            base_token: H160::from_low_u64_be(1),
+            base_token_decimals: 18,
+            quote_token: H160::from_low_u64_be(2),
+            quote_token_decimals: 18,
+            pool_type: DodoPoolType::DPP,
+            i: U256::from(2000) * ONE,
+            k,
+            base_reserve: U256::from(100) * ONE,
+            quote_reserve: U256::from(200_000) * ONE,
+            base_target: U256::from(100) * ONE,
+            quote_target: U256::from(200_000) * ONE,
+            r_state: RState::One,
+            lp_fee_rate: U256::exp10(15),
+            mt_fee_rate: U256::zero(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_simulate_swap_constant_price() -> eyre::Result<()> {
+        let pool = dpp(U256::zero());
+
+        //With k = 0 the pool quotes exactly the guide price minus the 0.1% fee
+        let amount_out = pool.simulate_swap(pool.base_token, ONE)?;
+        assert_eq!(amount_out, U256::from(1998) * ONE);
+
+        let amount_out = pool.simulate_swap(pool.quote_token, U256::from(2000) * ONE)?;
+        assert_eq!(amount_out, U256::from(999) * U256::exp10(15));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulate_swap_mut() -> eyre::Result<()> {
+        let mut pool = dpp(U256::exp10(17));
+
+        let amount_out = pool.simulate_swap(pool.base_token, U256::from(10) * ONE)?;
+        assert!(amount_out < U256::from(19_980) * ONE);
+
+        let amount_out_mut = pool.simulate_swap_mut(pool.base_token, U256::from(10) * ONE)?;
+        assert_eq!(amount_out, amount_out_mut);
+        assert_eq!(pool.r_state, RState::BelowOne);
+        assert_eq!(pool.base_reserve, U256::from(110) * ONE);
+
+        //Selling the quote back moves the pool towards its targets and pays less than was sold because of the fees
+        let amount_back = pool.simulate_swap_mut(pool.quote_token, amount_out)?;
+        assert!(amount_back < U256::from(10) * ONE);
+        assert!(amount_back > U256::from(9) * ONE);
+        assert_eq!(pool.r_state, RState::BelowOne);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_calculate_price() -> eyre::Result<()> {
+        let pool = dpp(U256::exp10(17));
+
+        assert!((pool.calculate_price(pool.base_token)?.to_f64() - 2000.0).abs() < 1e-9);
+        assert!((pool.calculate_price(pool.quote_token)?.to_f64() - 0.0005).abs() < 1e-12);
+
+        Ok(())
+    }
+}
@@ -9,7 +9,7 @@ use crate::{amm::AutomatedMarketMaker, errors::AMMError};
 
 use ethers::prelude::abigen;
 
-use super::ERC4626Vault;
+use super::{ERC4626Vault, BPS_DENOMINATOR};
 
 abigen!(
     IGetERC4626VaultDataBatchRequest,
@@ -34,37 +34,48 @@ fn populate_vault_data_from_tokens(
     let withdraw_fee_delta_2 = tokens[10].to_owned().into_uint()?;
     let withdraw_no_fee = tokens[11].to_owned().into_uint()?;
 
-    // If both deltas are zero, the fee is zero
-    if deposit_fee_delta_1.is_zero() && deposit_fee_delta_2.is_zero() {
-        vault.deposit_fee = 0;
-    // Assuming 18 decimals, if the delta of 1e20 is half the delta of 2e20, relative fee.
-    // Delta / (amount without fee / 10000) to give us the fee in basis points
-    } else if deposit_fee_delta_1 * 2 == deposit_fee_delta_2 {
-        vault.deposit_fee =
-            (deposit_fee_delta_1 / (deposit_no_fee / U256::from("0x2710"))).as_u32();
+    // If not a relative fee or zero, ignore vault
+    vault.deposit_fee = fee_from_deltas(deposit_fee_delta_1, deposit_fee_delta_2, deposit_no_fee)?;
+    vault.withdraw_fee =
+        fee_from_deltas(withdraw_fee_delta_1, withdraw_fee_delta_2, withdraw_no_fee)?;
+
+    Some(vault)
+}
+
+// The deltas are the difference between the naive conversion and the preview of 100 and 200 tokens.
+// A relative fee doubles the delta when the amount is doubled, up to the rounding of each preview.
+// Delta * 10000 / amount without fee gives us the fee in basis points, rounded to the nearest basis point
+pub fn fee_from_deltas(delta_1: U256, delta_2: U256, no_fee: U256) -> Option<u32> {
+    //Each preview can round the result by one unit
+    const ROUNDING_TOLERANCE: u64 = 2;
+
+    // If both deltas are within rounding of zero, the fee is zero
+    if delta_1 <= U256::one() && delta_2 <= U256::from(ROUNDING_TOLERANCE) {
+        return Some(0);
+    }
+
+    let expected_delta_2 = delta_1 * 2;
+    let rounding_error = if delta_2 > expected_delta_2 {
+        delta_2 - expected_delta_2
     } else {
-        // If not a relative fee or zero, ignore vault
+        expected_delta_2 - delta_2
+    };
+
+    if no_fee.is_zero() || rounding_error > U256::from(ROUNDING_TOLERANCE) {
         return None;
     }
 
-    // If both deltas are zero, the fee is zero
-    if withdraw_fee_delta_1.is_zero() && withdraw_fee_delta_2.is_zero() {
-        vault.withdraw_fee = 0;
-    // Assuming 18 decimals, if the delta of 1e20 is half the delta of 2e20, relative fee.
-    // Delta / (amount without fee / 10000) to give us the fee in basis points
-    } else if withdraw_fee_delta_1 * 2 == withdraw_fee_delta_2 {
-        vault.withdraw_fee =
-            (withdraw_fee_delta_1 / (withdraw_no_fee / U256::from("0x2710"))).as_u32();
-    } else {
-        // If not a relative fee or zero, ignore vault
+    let fee = (delta_1 * U256::from(BPS_DENOMINATOR) + no_fee / 2) / no_fee;
+    if fee > U256::from(BPS_DENOMINATOR) {
         return None;
     }
 
-    Some(vault)
+    Some(fee.as_u32())
 }
 
 pub async fn get_4626_vault_data_batch_request<M: Middleware>(
     vault: &mut ERC4626Vault,
+    block_number: Option<u64>,
     middleware: Arc<M>,
 ) -> Result<(), AMMError<M>> {
     let constructor_args =
@@ -72,7 +83,11 @@ pub async fn get_4626_vault_data_batch_request<M: Middleware>(
 
     let deployer = IGetERC4626VaultDataBatchRequest::deploy(middleware.clone(), constructor_args)?;
 
-    let return_data: Bytes = deployer.call_raw().await?;
+    let return_data: Bytes = if let Some(block_number) = block_number {
+        deployer.block(block_number).call_raw().await?
+    } else {
+        deployer.call_raw().await?
+    };
     let return_data_tokens = ethers::abi::decode(
         &[ParamType::Array(Box::new(ParamType::Tuple(vec![
             ParamType::Address,   // vault token
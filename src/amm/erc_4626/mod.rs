@@ -7,18 +7,18 @@ use ethers::{
     abi::RawLog,
     prelude::EthEvent,
     providers::Middleware,
-    types::{Log, H160, H256, U256},
+    types::{Log, H160, H256, U256, U512},
 };
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    amm::AutomatedMarketMaker,
+    amm::{price::Price, AutomatedMarketMaker},
     errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
 };
 
 use ethers::prelude::abigen;
 
-use super::uniswap_v2::{div_uu, q64_to_f64, U128_0X10000000000000000};
+use super::uniswap_v2::{div_uu, U128_0X10000000000000000};
 
 abigen!(
     IERC4626Vault,
@@ -26,6 +26,10 @@ abigen!(
         function totalAssets() external view returns (uint256)
         function totalSupply() external view returns (uint256)
         function decimals() external view returns (uint8)
+        function previewDeposit(uint256 assets) external view returns (uint256)
+        function previewMint(uint256 shares) external view returns (uint256)
+        function previewWithdraw(uint256 assets) external view returns (uint256)
+        function previewRedeem(uint256 shares) external view returns (uint256)
         event Withdraw(address indexed sender, address indexed receiver, address indexed owner, uint256 assets, uint256 shares)
         event Deposit(address indexed sender,address indexed owner, uint256 assets, uint256 shares)
 
@@ -42,6 +46,8 @@ pub const WITHDRAW_EVENT_SIGNATURE: H256 = H256([
     74, 44, 117, 192, 31, 201, 102, 114, 50, 200, 219,
 ]);
 
+pub const BPS_DENOMINATOR: u32 = 10000;
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ERC4626Vault {
     pub vault_token: H160, // token received from depositing, i.e. shares token
@@ -64,8 +70,21 @@ impl AutomatedMarketMaker for ERC4626Vault {
         vec![self.vault_token, self.asset_token]
     }
 
-    fn calculate_price(&self, base_token: H160) -> Result<f64, ArithmeticError> {
-        Ok(q64_to_f64(self.calculate_price_64_x_64(base_token)?))
+    //Exchange rate after the fee of the direction, i.e. redeeming when the base token is the vault token
+    fn calculate_price(&self, base_token: H160) -> Result<Price, ArithmeticError> {
+        self.check_liquidity()?;
+
+        let fee = if base_token == self.vault_token {
+            self.withdraw_fee
+        } else {
+            self.deposit_fee
+        };
+
+        let price = U256::from(self.calculate_price_64_x_64(base_token)?);
+
+        Ok(Price::from_x64(
+            price * U256::from(BPS_DENOMINATOR.saturating_sub(fee)) / BPS_DENOMINATOR,
+        ))
     }
 
     async fn sync<M: Middleware>(&mut self, middleware: Arc<M>) -> Result<(), AMMError<M>> {
@@ -98,19 +117,24 @@ impl AutomatedMarketMaker for ERC4626Vault {
 
     async fn populate_data<M: Middleware>(
         &mut self,
-        _block_number: Option<u64>,
+        block_number: Option<u64>,
         middleware: Arc<M>,
     ) -> Result<(), AMMError<M>> {
-        batch_request::get_4626_vault_data_batch_request(self, middleware.clone()).await?;
+        batch_request::get_4626_vault_data_batch_request(self, block_number, middleware.clone())
+            .await?;
 
         Ok(())
     }
 
+    //Swapping vault tokens redeems them for assets and swapping assets deposits them for vault tokens
     fn simulate_swap(&self, token_in: H160, amount_in: U256) -> Result<U256, SwapSimulationError> {
+        self.check_liquidity()?;
+
         if self.vault_token == token_in {
-            Ok(self.get_amount_out(amount_in, self.vault_reserve, self.asset_reserve))
+            self.check_redeemable()?;
+            Ok(self.preview_redeem(amount_in))
         } else {
-            Ok(self.get_amount_out(amount_in, self.asset_reserve, self.vault_reserve))
+            Ok(self.preview_deposit(amount_in))
         }
     }
 
@@ -119,15 +143,18 @@ impl AutomatedMarketMaker for ERC4626Vault {
         token_in: H160,
         amount_in: U256,
     ) -> Result<U256, SwapSimulationError> {
+        self.check_liquidity()?;
+
         if self.vault_token == token_in {
-            let amount_out = self.get_amount_out(amount_in, self.vault_reserve, self.asset_reserve);
+            self.check_redeemable()?;
+            let amount_out = self.preview_redeem(amount_in);
 
             self.vault_reserve -= amount_in;
             self.asset_reserve -= amount_out;
 
             Ok(amount_out)
         } else {
-            let amount_out = self.get_amount_out(amount_in, self.asset_reserve, self.vault_reserve);
+            let amount_out = self.preview_deposit(amount_in);
 
             self.asset_reserve += amount_in;
             self.vault_reserve += amount_out;
@@ -136,6 +163,19 @@ impl AutomatedMarketMaker for ERC4626Vault {
         }
     }
 
+    //Redeeming vault tokens is charged the withdraw fee, depositing assets is charged the deposit fee
+    fn swap_fee(&self, token_in: H160) -> f64 {
+        if token_in == self.vault_token {
+            self.withdraw_fee as f64 / 10000.0
+        } else {
+            self.deposit_fee as f64 / 10000.0
+        }
+    }
+
+    fn token_decimals(&self) -> Vec<u8> {
+        vec![self.vault_token_decimals, self.asset_token_decimals]
+    }
+
     fn get_token_out(&self, token_in: H160) -> H160 {
         if self.vault_token == token_in {
             self.asset_token
@@ -220,6 +260,7 @@ impl ERC4626Vault {
         Ok((total_supply, total_assets))
     }
 
+    //Exchange rate between shares and assets before fees
     pub fn calculate_price_64_x_64(&self, base_token: H160) -> Result<u128, ArithmeticError> {
         let decimal_shift = self.vault_token_decimals as i8 - self.asset_token_decimals as i8;
 
@@ -252,37 +293,333 @@ impl ERC4626Vault {
         }
     }
 
+    //An empty vault converts one to one, but a vault with shares and no assets can not be deposited into or redeemed from
+    fn check_liquidity(&self) -> Result<(), ArithmeticError> {
+        if !self.vault_reserve.is_zero() && self.asset_reserve.is_zero() {
+            return Err(ArithmeticError::ZeroLiquidity(self.vault_token));
+        }
+
+        Ok(())
+    }
+
+    //There is nothing to redeem from a vault without shares
+    fn check_redeemable(&self) -> Result<(), ArithmeticError> {
+        if self.vault_reserve.is_zero() {
+            return Err(ArithmeticError::ZeroLiquidity(self.vault_token));
+        }
+
+        Ok(())
+    }
+
+    //Mirrors `previewRedeem`, the withdraw fee is taken from the assets out. Like the EIP mandates for redeem,
+    //the conversion rounds down and the fee rounds up, both in favor of the vault
+    pub fn preview_redeem(&self, shares: U256) -> U256 {
+        let assets = self.convert_to_assets(shares, false);
+        assets - fee_on_amount(assets, self.withdraw_fee)
+    }
+
+    //Mirrors `previewDeposit`, the deposit fee is taken from the assets in before they are converted to shares
+    pub fn preview_deposit(&self, assets: U256) -> U256 {
+        self.convert_to_shares(assets - fee_on_amount(assets, self.deposit_fee), false)
+    }
+
+    //Mirrors `previewMint`, the assets in needed for an exact amount of shares out, rounding up
+    pub fn preview_mint(&self, shares: U256) -> U256 {
+        amount_before_fee(self.convert_to_assets(shares, true), self.deposit_fee)
+    }
+
+    //Mirrors `previewWithdraw`, the shares in needed for an exact amount of assets out, rounding up
+    pub fn preview_withdraw(&self, assets: U256) -> U256 {
+        let assets = amount_before_fee(assets, self.withdraw_fee);
+        if assets == U256::MAX {
+            return assets;
+        }
+
+        self.convert_to_shares(assets, true)
+    }
+
+    //An empty vault mints shares one to one, a vault with shares but no assets can not be deposited into
+    pub fn convert_to_shares(&self, assets: U256, round_up: bool) -> U256 {
+        if self.vault_reserve.is_zero() {
+            return assets;
+        }
+
+        if self.asset_reserve.is_zero() {
+            return if round_up { U256::MAX } else { U256::zero() };
+        }
+
+        mul_div(assets, self.vault_reserve, self.asset_reserve, round_up)
+    }
+
+    pub fn convert_to_assets(&self, shares: U256, round_up: bool) -> U256 {
+        if self.vault_reserve.is_zero() {
+            return shares;
+        }
+
+        mul_div(shares, self.asset_reserve, self.vault_reserve, round_up)
+    }
+
+    //Shares and assets are converted at the ratio of the total supply to the total assets, rounding down like the vault
     pub fn get_amount_out(&self, amount_in: U256, reserve_in: U256, reserve_out: U256) -> U256 {
         if amount_in.is_zero() {
             return U256::zero();
         }
 
+        //An empty vault mints shares one to one
         if self.vault_reserve.is_zero() {
             return amount_in;
         }
 
+        //A vault with shares but no assets can not be deposited into
+        if reserve_in.is_zero() {
+            return U256::zero();
+        }
+
         let fee = if reserve_in == self.vault_reserve {
             self.withdraw_fee
         } else {
             self.deposit_fee
         };
 
-        amount_in * reserve_out / reserve_in * (10000 - fee) / 10000
+        let amount_out = amount_in * reserve_out / reserve_in;
+        amount_out - fee_on_amount(amount_out, fee)
     }
 }
 
+fn mul_div(a: U256, b: U256, denominator: U256, round_up: bool) -> U256 {
+    let product = a.full_mul(b);
+    let denominator = denominator.into();
+    let mut result = product / denominator;
+
+    if round_up && !(product % denominator).is_zero() {
+        result += U512::one();
+    }
+
+    U256::try_from(result).unwrap_or(U256::MAX)
+}
+
+//Fee in basis points charged on `amount`, rounded up in favor of the vault
+fn fee_on_amount(amount: U256, fee: u32) -> U256 {
+    mul_div(
+        amount,
+        U256::from(fee.min(BPS_DENOMINATOR)),
+        U256::from(BPS_DENOMINATOR),
+        true,
+    )
+}
+
+//Amount that is left with `amount` once a fee in basis points is taken, or U256::MAX if the fee takes everything
+fn amount_before_fee(amount: U256, fee: u32) -> U256 {
+    if fee >= BPS_DENOMINATOR {
+        return U256::MAX;
+    }
+
+    mul_div(
+        amount,
+        U256::from(BPS_DENOMINATOR),
+        U256::from(BPS_DENOMINATOR - fee),
+        true,
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use std::{str::FromStr, sync::Arc};
 
     use ethers::{
+        prelude::abigen,
         providers::{Http, Provider},
         types::{H160, U256},
     };
 
-    use crate::amm::AutomatedMarketMaker;
+    use crate::{
+        amm::AutomatedMarketMaker,
+        errors::{ArithmeticError, SwapSimulationError},
+    };
+
+    use super::{batch_request::fee_from_deltas, ERC4626Vault, IERC4626Vault};
+
+    //Gearbox V3 pools are ERC4626 vaults that take a withdrawal fee in basis points from previewRedeem and previewWithdraw
+    abigen!(
+        IGearboxPoolV3,
+        r#"[
+            function withdrawFee() external view returns (uint16)
+        ]"#;
+    );
+
+    //Checks the fees detected for a Gearbox V3 pool against the withdrawal fee of the pool and the swaps against the previews
+    async fn assert_gearbox_pool_fees(pool: H160) -> eyre::Result<()> {
+        let rpc_endpoint = std::env::var("ETHEREUM_RPC_ENDPOINT")?;
+        let middleware = Arc::new(Provider::<Http>::try_from(rpc_endpoint)?);
 
-    use super::ERC4626Vault;
+        let mut vault = ERC4626Vault {
+            vault_token: pool,
+            ..Default::default()
+        };
+        vault.populate_data(None, middleware.clone()).await?;
+
+        let withdraw_fee = IGearboxPoolV3::new(pool, middleware.clone())
+            .withdraw_fee()
+            .call()
+            .await?;
+        assert_eq!(vault.deposit_fee, 0);
+        assert_eq!(vault.withdraw_fee, withdraw_fee as u32);
+
+        let on_chain_vault = IERC4626Vault::new(pool, middleware);
+        let assets = U256::from(1000) * U256::exp10(vault.asset_token_decimals as usize);
+        let shares = U256::from(1000) * U256::exp10(vault.vault_token_decimals as usize);
+
+        assert_eq!(
+            vault.simulate_swap(vault.asset_token, assets)?,
+            on_chain_vault.preview_deposit(assets).call().await?
+        );
+        assert_eq!(
+            vault.simulate_swap(vault.vault_token, shares)?,
+            on_chain_vault.preview_redeem(shares).call().await?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_preview_deposit_and_redeem() -> eyre::Result<()> {
+        let mut vault = ERC4626Vault {
+            vault_token: H160::from_low_u64_be(1),
+            vault_token_decimals: 18,
+            asset_token: H160::from_low_u64_be(2),
+            asset_token_decimals: 18,
+            vault_reserve: U256::from(1000) * U256::exp10(18),
+            asset_reserve: U256::from(1100) * U256::exp10(18),
+            deposit_fee: 0,
+            withdraw_fee: 10,
+        };
+
+        assert_eq!(
+            vault.simulate_swap(vault.vault_token, U256::exp10(18))?,
+            U256::from(10989) * U256::exp10(14)
+        );
+        assert_eq!(
+            vault.simulate_swap(vault.asset_token, U256::from(11) * U256::exp10(17))?,
+            U256::exp10(18)
+        );
+
+        //Deposits into an empty vault are one to one, but there is nothing to redeem
+        vault.vault_reserve = U256::zero();
+        vault.asset_reserve = U256::zero();
+        assert_eq!(vault.preview_deposit(U256::exp10(18)), U256::exp10(18));
+        assert!(matches!(
+            vault.simulate_swap_mut(vault.vault_token, U256::exp10(18)),
+            Err(SwapSimulationError::ArithmeticError(
+                ArithmeticError::ZeroLiquidity(_)
+            ))
+        ));
+
+        //A vault whose assets were lost has no exchange rate
+        vault.vault_reserve = U256::exp10(18);
+        assert!(matches!(
+            vault.calculate_price(vault.asset_token),
+            Err(ArithmeticError::ZeroLiquidity(_))
+        ));
+        assert!(matches!(
+            vault.simulate_swap(vault.asset_token, U256::exp10(18)),
+            Err(SwapSimulationError::ArithmeticError(
+                ArithmeticError::ZeroLiquidity(_)
+            ))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fee_charging_vault_rounding() -> eyre::Result<()> {
+        let vault = ERC4626Vault {
+            vault_token: H160::from_low_u64_be(1),
+            vault_token_decimals: 18,
+            asset_token: H160::from_low_u64_be(2),
+            asset_token_decimals: 18,
+            vault_reserve: U256::from(1000) * U256::exp10(18),
+            asset_reserve: U256::from(1100) * U256::exp10(18),
+            deposit_fee: 50,
+            withdraw_fee: 30,
+        };
+
+        //Deposits and redeems round down, mints and withdrawals round up
+        let shares = vault.preview_deposit(U256::exp10(18));
+        assert_eq!(shares, U256::from(904545454545454545_u64));
+        assert_eq!(vault.preview_mint(shares), U256::exp10(18));
+
+        let assets = vault.preview_redeem(U256::exp10(18));
+        assert_eq!(assets, U256::from(1096700000000000000_u64));
+        assert_eq!(vault.preview_withdraw(assets), U256::exp10(18));
+
+        //Minting a single share can not be cheaper than what a deposit of the same assets returns
+        let assets = vault.preview_mint(U256::one());
+        assert_eq!(assets, U256::from(3));
+        assert!(vault.preview_deposit(assets) >= U256::one());
+
+        let redeem_price = vault.calculate_price(vault.vault_token)?.to_f64();
+        assert!((redeem_price - 1.1 * 0.997).abs() < 1e-9);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fee_from_deltas() {
+        let no_fee = U256::from(100) * U256::exp10(18);
+
+        assert_eq!(fee_from_deltas(U256::zero(), U256::zero(), no_fee), Some(0));
+        assert_eq!(fee_from_deltas(U256::one(), U256::one(), no_fee), Some(0));
+
+        //A 0.5% fee where the preview of 200 tokens rounded differently than twice the preview of 100 tokens
+        let delta = U256::from(5) * U256::exp10(17);
+        assert_eq!(fee_from_deltas(delta, delta * 2 + 1, no_fee), Some(50));
+
+        //A flat fee does not scale with the amount
+        assert_eq!(fee_from_deltas(delta, delta, no_fee), None);
+    }
+
+    #[tokio::test]
+    async fn test_simulate_swap_matches_preview() -> eyre::Result<()> {
+        let rpc_endpoint = std::env::var("ETHEREUM_RPC_ENDPOINT")?;
+        let middleware = Arc::new(Provider::<Http>::try_from(rpc_endpoint)?);
+
+        let mut vault = ERC4626Vault {
+            vault_token: H160::from_str("0x163538E22F4d38c1eb21B79939f3d2ee274198Ff")?,
+            ..Default::default()
+        };
+
+        vault.populate_data(None, middleware.clone()).await?;
+
+        let amount = U256::from(3) * U256::exp10(18);
+        let on_chain_vault = IERC4626Vault::new(vault.vault_token, middleware);
+
+        assert_eq!(
+            vault.simulate_swap(vault.asset_token, amount)?,
+            on_chain_vault.preview_deposit(amount).call().await?
+        );
+        assert_eq!(
+            vault.simulate_swap(vault.vault_token, amount)?,
+            on_chain_vault.preview_redeem(amount).call().await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fee_charging_vault_gearbox_dusdc() -> eyre::Result<()> {
+        assert_gearbox_pool_fees(H160::from_str(
+            "0xda00000035fef4082F78dEF6A8903bee419FbF8E",
+        )?)
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_fee_charging_vault_gearbox_dweth() -> eyre::Result<()> {
+        assert_gearbox_pool_fees(H160::from_str(
+            "0xda0002859B2d05F66a753d8241fCDE8623f26F4f",
+        )?)
+        .await
+    }
 
     #[tokio::test]
     async fn test_get_vault_data() -> eyre::Result<()> {
@@ -324,8 +661,8 @@ mod tests {
         vault.asset_token_decimals = 6;
         vault.asset_reserve = U256::from_dec_str("505434849031")?;
 
-        let price_v_64_x = vault.calculate_price(vault.vault_token)?;
-        let price_a_64_x = vault.calculate_price(vault.asset_token)?;
+        let price_v_64_x = vault.calculate_price(vault.vault_token)?.to_f64();
+        let price_a_64_x = vault.calculate_price(vault.asset_token)?.to_f64();
 
         assert_eq!(price_v_64_x, 1.0070222372637234);
         assert_eq!(price_a_64_x, 0.99302673068789);
@@ -348,8 +685,8 @@ mod tests {
         vault.vault_reserve = U256::from_dec_str("0")?;
         vault.asset_reserve = U256::from_dec_str("0")?;
 
-        let price_v_64_x = vault.calculate_price(vault.vault_token)?;
-        let price_a_64_x = vault.calculate_price(vault.asset_token)?;
+        let price_v_64_x = vault.calculate_price(vault.vault_token)?.to_f64();
+        let price_a_64_x = vault.calculate_price(vault.asset_token)?.to_f64();
 
         assert_eq!(price_v_64_x, 1.0);
         assert_eq!(price_a_64_x, 1.0);
@@ -372,8 +709,8 @@ mod tests {
         vault.vault_reserve = U256::from_dec_str("501910315708981197269904")?;
         vault.asset_reserve = U256::from_dec_str("505434849031054568651911")?;
 
-        let price_v_64_x = vault.calculate_price(vault.vault_token)?;
-        let price_a_64_x = vault.calculate_price(vault.asset_token)?;
+        let price_v_64_x = vault.calculate_price(vault.vault_token)?.to_f64();
+        let price_a_64_x = vault.calculate_price(vault.asset_token)?.to_f64();
 
         assert_eq!(price_v_64_x, 1.0070222372638322);
         assert_eq!(price_a_64_x, 0.9930267306877828);
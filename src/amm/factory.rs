@@ -2,21 +2,65 @@ use std::sync::Arc;
 
 use async_trait::async_trait;
 use ethers::{
+    abi::Detokenize,
+    contract::{ContractCall, Multicall},
     providers::Middleware,
-    types::{BlockNumber, Filter, Log, ValueOrArray, H160, H256, U64},
+    types::{BlockId, BlockNumber, Filter, Log, ValueOrArray, H160, H256, U256, U64},
 };
 use serde::{Deserialize, Serialize};
 use tokio::task::JoinHandle;
 
-use crate::errors::{AMMError, EventLogError};
+use crate::{
+    errors::{AMMError, EventLogError},
+    middleware::{
+        logs::{get_logs_with_adaptive_range, MAX_LOG_RANGE_SPLITS},
+        retry::{RetryConfig, RetryMiddleware},
+    },
+};
 
 use super::{
-    uniswap_v2::factory::{UniswapV2Factory, PAIR_CREATED_EVENT_SIGNATURE},
+    algebra::factory::{AlgebraFactory, POOL_EVENT_SIGNATURE as ALGEBRA_POOL_EVENT_SIGNATURE},
+    balancer_v2::factory::{
+        BalancerV2Factory, BalancerV2Vault, POOL_REGISTERED_EVENT_SIGNATURE,
+        WEIGHTED_POOL_CREATED_EVENT_SIGNATURE,
+    },
+    camelot::{
+        factory::{CamelotFactory, ICamelotFactory},
+        CamelotPool,
+    },
+    curve::factory::{CurveFactory, POOL_ADDED_EVENT_SIGNATURE},
+    dodo::{
+        factory::{DodoFactory, NEW_DPP_EVENT_SIGNATURE, NEW_DVM_EVENT_SIGNATURE},
+        DodoPoolType,
+    },
+    kyber_elastic::factory::KyberElasticFactory,
+    maverick::factory::{
+        MaverickFactory, POOL_CREATED_EVENT_SIGNATURE as MAVERICK_POOL_CREATED_EVENT_SIGNATURE,
+    },
+    solidly::{
+        factory::{
+            ISolidlyFactory, ISolidlyV2Factory, SolidlyFactory, SolidlyFactoryVersion,
+            PAIR_CREATED_EVENT_SIGNATURE as SOLIDLY_PAIR_CREATED_EVENT_SIGNATURE,
+            POOL_CREATED_EVENT_SIGNATURE as SOLIDLY_POOL_CREATED_EVENT_SIGNATURE,
+        },
+        SolidlyPool,
+    },
+    trader_joe_lb::{
+        factory::{ILBFactory, LBFactory, LB_PAIR_CREATED_EVENT_SIGNATURE},
+        LBPair,
+    },
+    uniswap_v2::{
+        factory::{IUniswapV2Factory, UniswapV2Factory, PAIR_CREATED_EVENT_SIGNATURE},
+        UniswapV2Pool,
+    },
     uniswap_v3::factory::{UniswapV3Factory, POOL_CREATED_EVENT_SIGNATURE},
+    uniswap_v4::{factory::UniswapV4PoolManager, INITIALIZE_EVENT_SIGNATURE},
     AMM,
 };
 
 pub const TASK_LIMIT: usize = 10;
+//Number of calls aggregated into a single Multicall3 request when enumerating the pools of a factory
+pub const DEFAULT_MULTICALL_CHUNK_SIZE: usize = 100;
 
 #[async_trait]
 pub trait AutomatedMarketMakerFactory {
@@ -29,6 +73,21 @@ pub trait AutomatedMarketMakerFactory {
         step: u64,
     ) -> Result<Vec<AMM>, AMMError<M>>;
 
+    //Same as get_all_amms, but requests that are rate limited or dropped are retried according to `retry`
+    async fn get_all_amms_with_retry<M: 'static + Middleware>(
+        &self,
+        to_block: Option<u64>,
+        middleware: Arc<M>,
+        step: u64,
+        retry: RetryConfig,
+    ) -> Result<Vec<AMM>, AMMError<RetryMiddleware<M>>>
+    where
+        Self: Sync,
+    {
+        self.get_all_amms(to_block, retry.wrap(middleware), step)
+            .await
+    }
+
     async fn populate_amm_data<M: Middleware>(
         &self,
         amms: &mut [AMM],
@@ -53,6 +112,17 @@ pub trait AutomatedMarketMakerFactory {
 pub enum Factory {
     UniswapV2Factory(UniswapV2Factory),
     UniswapV3Factory(UniswapV3Factory),
+    BalancerV2Factory(BalancerV2Factory),
+    BalancerV2Vault(BalancerV2Vault),
+    CurveFactory(CurveFactory),
+    UniswapV4PoolManager(UniswapV4PoolManager),
+    SolidlyFactory(SolidlyFactory),
+    LBFactory(LBFactory),
+    MaverickFactory(MaverickFactory),
+    CamelotFactory(CamelotFactory),
+    KyberElasticFactory(KyberElasticFactory),
+    DodoFactory(DodoFactory),
+    AlgebraFactory(AlgebraFactory),
 }
 
 #[async_trait]
@@ -61,6 +131,17 @@ impl AutomatedMarketMakerFactory for Factory {
         match self {
             Factory::UniswapV2Factory(factory) => factory.address(),
             Factory::UniswapV3Factory(factory) => factory.address(),
+            Factory::BalancerV2Factory(factory) => factory.address(),
+            Factory::BalancerV2Vault(factory) => factory.address(),
+            Factory::CurveFactory(factory) => factory.address(),
+            Factory::UniswapV4PoolManager(factory) => factory.address(),
+            Factory::SolidlyFactory(factory) => factory.address(),
+            Factory::LBFactory(factory) => factory.address(),
+            Factory::MaverickFactory(factory) => factory.address(),
+            Factory::CamelotFactory(factory) => factory.address(),
+            Factory::KyberElasticFactory(factory) => factory.address(),
+            Factory::DodoFactory(factory) => factory.address(),
+            Factory::AlgebraFactory(factory) => factory.address(),
         }
     }
 
@@ -68,6 +149,17 @@ impl AutomatedMarketMakerFactory for Factory {
         match self {
             Factory::UniswapV2Factory(factory) => factory.amm_created_event_signature(),
             Factory::UniswapV3Factory(factory) => factory.amm_created_event_signature(),
+            Factory::BalancerV2Factory(factory) => factory.amm_created_event_signature(),
+            Factory::BalancerV2Vault(factory) => factory.amm_created_event_signature(),
+            Factory::CurveFactory(factory) => factory.amm_created_event_signature(),
+            Factory::UniswapV4PoolManager(factory) => factory.amm_created_event_signature(),
+            Factory::SolidlyFactory(factory) => factory.amm_created_event_signature(),
+            Factory::LBFactory(factory) => factory.amm_created_event_signature(),
+            Factory::MaverickFactory(factory) => factory.amm_created_event_signature(),
+            Factory::CamelotFactory(factory) => factory.amm_created_event_signature(),
+            Factory::KyberElasticFactory(factory) => factory.amm_created_event_signature(),
+            Factory::DodoFactory(factory) => factory.amm_created_event_signature(),
+            Factory::AlgebraFactory(factory) => factory.amm_created_event_signature(),
         }
     }
 
@@ -79,6 +171,21 @@ impl AutomatedMarketMakerFactory for Factory {
         match self {
             Factory::UniswapV2Factory(factory) => factory.new_amm_from_log(log, middleware).await,
             Factory::UniswapV3Factory(factory) => factory.new_amm_from_log(log, middleware).await,
+            Factory::BalancerV2Factory(factory) => factory.new_amm_from_log(log, middleware).await,
+            Factory::BalancerV2Vault(factory) => factory.new_amm_from_log(log, middleware).await,
+            Factory::CurveFactory(factory) => factory.new_amm_from_log(log, middleware).await,
+            Factory::UniswapV4PoolManager(factory) => {
+                factory.new_amm_from_log(log, middleware).await
+            }
+            Factory::SolidlyFactory(factory) => factory.new_amm_from_log(log, middleware).await,
+            Factory::LBFactory(factory) => factory.new_amm_from_log(log, middleware).await,
+            Factory::MaverickFactory(factory) => factory.new_amm_from_log(log, middleware).await,
+            Factory::CamelotFactory(factory) => factory.new_amm_from_log(log, middleware).await,
+            Factory::KyberElasticFactory(factory) => {
+                factory.new_amm_from_log(log, middleware).await
+            }
+            Factory::DodoFactory(factory) => factory.new_amm_from_log(log, middleware).await,
+            Factory::AlgebraFactory(factory) => factory.new_amm_from_log(log, middleware).await,
         }
     }
 
@@ -86,6 +193,17 @@ impl AutomatedMarketMakerFactory for Factory {
         match self {
             Factory::UniswapV2Factory(factory) => factory.new_empty_amm_from_log(log),
             Factory::UniswapV3Factory(factory) => factory.new_empty_amm_from_log(log),
+            Factory::BalancerV2Factory(factory) => factory.new_empty_amm_from_log(log),
+            Factory::BalancerV2Vault(factory) => factory.new_empty_amm_from_log(log),
+            Factory::CurveFactory(factory) => factory.new_empty_amm_from_log(log),
+            Factory::UniswapV4PoolManager(factory) => factory.new_empty_amm_from_log(log),
+            Factory::SolidlyFactory(factory) => factory.new_empty_amm_from_log(log),
+            Factory::LBFactory(factory) => factory.new_empty_amm_from_log(log),
+            Factory::MaverickFactory(factory) => factory.new_empty_amm_from_log(log),
+            Factory::CamelotFactory(factory) => factory.new_empty_amm_from_log(log),
+            Factory::KyberElasticFactory(factory) => factory.new_empty_amm_from_log(log),
+            Factory::DodoFactory(factory) => factory.new_empty_amm_from_log(log),
+            Factory::AlgebraFactory(factory) => factory.new_empty_amm_from_log(log),
         }
     }
 
@@ -102,6 +220,35 @@ impl AutomatedMarketMakerFactory for Factory {
             Factory::UniswapV3Factory(factory) => {
                 factory.get_all_amms(to_block, middleware, step).await
             }
+            Factory::BalancerV2Factory(factory) => {
+                factory.get_all_amms(to_block, middleware, step).await
+            }
+            Factory::BalancerV2Vault(factory) => {
+                factory.get_all_amms(to_block, middleware, step).await
+            }
+            Factory::CurveFactory(factory) => {
+                factory.get_all_amms(to_block, middleware, step).await
+            }
+            Factory::UniswapV4PoolManager(factory) => {
+                factory.get_all_amms(to_block, middleware, step).await
+            }
+            Factory::SolidlyFactory(factory) => {
+                factory.get_all_amms(to_block, middleware, step).await
+            }
+            Factory::LBFactory(factory) => factory.get_all_amms(to_block, middleware, step).await,
+            Factory::MaverickFactory(factory) => {
+                factory.get_all_amms(to_block, middleware, step).await
+            }
+            Factory::CamelotFactory(factory) => {
+                factory.get_all_amms(to_block, middleware, step).await
+            }
+            Factory::KyberElasticFactory(factory) => {
+                factory.get_all_amms(to_block, middleware, step).await
+            }
+            Factory::DodoFactory(factory) => factory.get_all_amms(to_block, middleware, step).await,
+            Factory::AlgebraFactory(factory) => {
+                factory.get_all_amms(to_block, middleware, step).await
+            }
         }
     }
 
@@ -120,6 +267,61 @@ impl AutomatedMarketMakerFactory for Factory {
                     .populate_amm_data(amms, block_number, middleware)
                     .await
             }
+            Factory::BalancerV2Factory(factory) => {
+                factory
+                    .populate_amm_data(amms, block_number, middleware)
+                    .await
+            }
+            Factory::BalancerV2Vault(factory) => {
+                factory
+                    .populate_amm_data(amms, block_number, middleware)
+                    .await
+            }
+            Factory::CurveFactory(factory) => {
+                factory
+                    .populate_amm_data(amms, block_number, middleware)
+                    .await
+            }
+            Factory::UniswapV4PoolManager(factory) => {
+                factory
+                    .populate_amm_data(amms, block_number, middleware)
+                    .await
+            }
+            Factory::SolidlyFactory(factory) => {
+                factory
+                    .populate_amm_data(amms, block_number, middleware)
+                    .await
+            }
+            Factory::LBFactory(factory) => {
+                factory
+                    .populate_amm_data(amms, block_number, middleware)
+                    .await
+            }
+            Factory::MaverickFactory(factory) => {
+                factory
+                    .populate_amm_data(amms, block_number, middleware)
+                    .await
+            }
+            Factory::CamelotFactory(factory) => {
+                factory
+                    .populate_amm_data(amms, block_number, middleware)
+                    .await
+            }
+            Factory::KyberElasticFactory(factory) => {
+                factory
+                    .populate_amm_data(amms, block_number, middleware)
+                    .await
+            }
+            Factory::DodoFactory(factory) => {
+                factory
+                    .populate_amm_data(amms, block_number, middleware)
+                    .await
+            }
+            Factory::AlgebraFactory(factory) => {
+                factory
+                    .populate_amm_data(amms, block_number, middleware)
+                    .await
+            }
         }
     }
 
@@ -127,11 +329,34 @@ impl AutomatedMarketMakerFactory for Factory {
         match self {
             Factory::UniswapV2Factory(uniswap_v2_factory) => uniswap_v2_factory.creation_block,
             Factory::UniswapV3Factory(uniswap_v3_factory) => uniswap_v3_factory.creation_block,
+            Factory::BalancerV2Factory(balancer_v2_factory) => balancer_v2_factory.creation_block,
+            Factory::BalancerV2Vault(balancer_v2_vault) => balancer_v2_vault.creation_block,
+            Factory::CurveFactory(curve_factory) => curve_factory.creation_block,
+            Factory::UniswapV4PoolManager(pool_manager) => pool_manager.creation_block,
+            Factory::SolidlyFactory(solidly_factory) => solidly_factory.creation_block,
+            Factory::LBFactory(lb_factory) => lb_factory.creation_block,
+            Factory::MaverickFactory(maverick_factory) => maverick_factory.creation_block,
+            Factory::CamelotFactory(factory) => factory.creation_block,
+            Factory::KyberElasticFactory(factory) => factory.creation_block,
+            Factory::DodoFactory(factory) => factory.creation_block,
+            Factory::AlgebraFactory(factory) => factory.creation_block,
         }
     }
 }
 
 impl Factory {
+    //Same as populate_amm_data, with `batch_size` pools in each batch request instead of the default batch size of the variant
+    pub async fn populate_amm_data_with_batch_size<M: Middleware>(
+        &self,
+        amms: &mut [AMM],
+        block_number: u64,
+        batch_size: usize,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        crate::sync::populate_amms_with_batch_size(amms, block_number, Some(batch_size), middleware)
+            .await
+    }
+
     pub async fn get_all_pools_from_logs<M: 'static + Middleware>(
         &self,
         mut from_block: u64,
@@ -139,6 +364,11 @@ impl Factory {
         step: u64,
         middleware: Arc<M>,
     ) -> Result<Vec<AMM>, AMMError<M>> {
+        //A step of zero would never advance from_block
+        if step == 0 {
+            return Err(AMMError::ZeroBatchSize("step"));
+        }
+
         let factory_address = self.address();
         let amm_created_event_signature = self.amm_created_event_signature();
         let mut log_group = vec![];
@@ -146,7 +376,8 @@ impl Factory {
         let mut tasks = 0;
         let mut aggregated_amms: Vec<AMM> = vec![];
 
-        while from_block < to_block {
+        //Both blocks are included, so that the pools created at the block a checkpoint is taken at are found by the next sync
+        while from_block <= to_block {
             let middleware = middleware.clone();
             let mut target_block = from_block + step - 1;
             if target_block > to_block {
@@ -154,16 +385,17 @@ impl Factory {
             }
 
             handles.push(tokio::spawn(async move {
-                let logs = middleware
-                    .get_logs(
-                        &Filter::new()
-                            .topic0(ValueOrArray::Value(amm_created_event_signature))
-                            .address(factory_address)
-                            .from_block(BlockNumber::Number(U64([from_block])))
-                            .to_block(BlockNumber::Number(U64([target_block]))),
-                    )
-                    .await
-                    .map_err(AMMError::MiddlewareError)?;
+                let logs = get_logs_with_adaptive_range(
+                    middleware.as_ref(),
+                    &Filter::new()
+                        .topic0(ValueOrArray::Value(amm_created_event_signature))
+                        .address(factory_address),
+                    from_block,
+                    target_block,
+                    MAX_LOG_RANGE_SPLITS,
+                )
+                .await
+                .map_err(AMMError::MiddlewareError)?;
 
                 Ok::<Vec<Log>, AMMError<M>>(logs)
             }));
@@ -202,6 +434,270 @@ impl Factory {
         }
         Ok(())
     }
+
+    //Enumerates every pool of the factory through its pool index getter instead of the creation logs and populates the pools at the same block.
+    //Only factories that expose their pools by index are supported.
+    pub async fn get_all_pools_via_multicall<M: Middleware>(
+        &self,
+        middleware: Arc<M>,
+        block: Option<BlockId>,
+    ) -> Result<Vec<AMM>, AMMError<M>> {
+        self.get_all_pools_via_multicall_with_chunk_size(
+            middleware,
+            block,
+            DEFAULT_MULTICALL_CHUNK_SIZE,
+        )
+        .await
+    }
+
+    pub async fn get_all_pools_via_multicall_with_chunk_size<M: Middleware>(
+        &self,
+        middleware: Arc<M>,
+        block: Option<BlockId>,
+        chunk_size: usize,
+    ) -> Result<Vec<AMM>, AMMError<M>> {
+        if chunk_size == 0 {
+            return Err(AMMError::ZeroBatchSize("chunk_size"));
+        }
+
+        //The pool count, the pool addresses and the pool data are all read at the same block
+        let block_number = match block {
+            Some(BlockId::Number(BlockNumber::Number(block_number))) => block_number,
+            Some(block) => middleware
+                .get_block(block)
+                .await
+                .map_err(AMMError::MiddlewareError)?
+                .and_then(|block| block.number)
+                .ok_or(AMMError::BlockNumberNotFound)?,
+            None => middleware
+                .get_block_number()
+                .await
+                .map_err(AMMError::MiddlewareError)?,
+        };
+
+        let mut amms = match self {
+            Factory::UniswapV2Factory(factory) => {
+                let contract = IUniswapV2Factory::new(factory.address, middleware.clone());
+                let pairs_length = contract
+                    .all_pairs_length()
+                    .block(BlockNumber::Number(block_number))
+                    .call()
+                    .await?;
+
+                get_pool_addresses_via_multicall(
+                    pairs_length,
+                    |index| contract.all_pairs(index),
+                    block_number,
+                    chunk_size,
+                    middleware.clone(),
+                )
+                .await?
+                .into_iter()
+                .map(|address| {
+                    AMM::UniswapV2Pool(UniswapV2Pool {
+                        address,
+                        fee: factory.fee,
+                        ..Default::default()
+                    })
+                })
+                .collect::<Vec<AMM>>()
+            }
+
+            Factory::SolidlyFactory(factory) => {
+                let pool_addresses = match factory.version {
+                    SolidlyFactoryVersion::V1 => {
+                        let contract = ISolidlyFactory::new(factory.address, middleware.clone());
+                        let pairs_length = contract
+                            .all_pairs_length()
+                            .block(BlockNumber::Number(block_number))
+                            .call()
+                            .await?;
+
+                        get_pool_addresses_via_multicall(
+                            pairs_length,
+                            |index| contract.all_pairs(index),
+                            block_number,
+                            chunk_size,
+                            middleware.clone(),
+                        )
+                        .await?
+                    }
+                    SolidlyFactoryVersion::V2 => {
+                        let contract = ISolidlyV2Factory::new(factory.address, middleware.clone());
+                        let pools_length = contract
+                            .all_pools_length()
+                            .block(BlockNumber::Number(block_number))
+                            .call()
+                            .await?;
+
+                        get_pool_addresses_via_multicall(
+                            pools_length,
+                            |index| contract.all_pools(index),
+                            block_number,
+                            chunk_size,
+                            middleware.clone(),
+                        )
+                        .await?
+                    }
+                };
+
+                pool_addresses
+                    .into_iter()
+                    .map(|address| {
+                        AMM::SolidlyPool(SolidlyPool {
+                            address,
+                            ..Default::default()
+                        })
+                    })
+                    .collect::<Vec<AMM>>()
+            }
+
+            Factory::CamelotFactory(factory) => {
+                let contract = ICamelotFactory::new(factory.address, middleware.clone());
+                let pairs_length = contract
+                    .all_pairs_length()
+                    .block(BlockNumber::Number(block_number))
+                    .call()
+                    .await?;
+
+                get_pool_addresses_via_multicall(
+                    pairs_length,
+                    |index| contract.all_pairs(index),
+                    block_number,
+                    chunk_size,
+                    middleware.clone(),
+                )
+                .await?
+                .into_iter()
+                .map(|address| {
+                    AMM::CamelotPool(CamelotPool {
+                        address,
+                        ..Default::default()
+                    })
+                })
+                .collect::<Vec<AMM>>()
+            }
+
+            Factory::LBFactory(factory) => {
+                let contract = ILBFactory::new(factory.address, middleware.clone());
+                let pairs_length = contract
+                    .get_number_of_lb_pairs()
+                    .block(BlockNumber::Number(block_number))
+                    .call()
+                    .await?;
+
+                get_pool_addresses_via_multicall(
+                    pairs_length,
+                    |index| contract.get_lb_pair_at_index(index),
+                    block_number,
+                    chunk_size,
+                    middleware.clone(),
+                )
+                .await?
+                .into_iter()
+                .map(|address| {
+                    AMM::LBPair(LBPair {
+                        address,
+                        ..Default::default()
+                    })
+                })
+                .collect::<Vec<AMM>>()
+            }
+
+            _ => return Err(AMMError::PoolEnumerationNotSupported(self.address())),
+        };
+
+        self.populate_amm_data(&mut amms, Some(block_number.as_u64()), middleware)
+            .await?;
+
+        Ok(amms)
+    }
+
+    //Number of pools created by factories that store their pools in an array. Uniswap V3 like factories only store
+    //their pools in a mapping, the pools can only be counted from their creation logs and PoolEnumerationNotSupported is returned
+    pub async fn pool_count<M: Middleware>(&self, middleware: Arc<M>) -> Result<u64, AMMError<M>> {
+        let pool_count = match self {
+            Factory::UniswapV2Factory(factory) => {
+                IUniswapV2Factory::new(factory.address, middleware)
+                    .all_pairs_length()
+                    .call()
+                    .await?
+            }
+            Factory::SolidlyFactory(factory) => match factory.version {
+                SolidlyFactoryVersion::V1 => {
+                    ISolidlyFactory::new(factory.address, middleware)
+                        .all_pairs_length()
+                        .call()
+                        .await?
+                }
+                SolidlyFactoryVersion::V2 => {
+                    ISolidlyV2Factory::new(factory.address, middleware)
+                        .all_pools_length()
+                        .call()
+                        .await?
+                }
+            },
+            Factory::CamelotFactory(factory) => {
+                ICamelotFactory::new(factory.address, middleware)
+                    .all_pairs_length()
+                    .call()
+                    .await?
+            }
+            Factory::LBFactory(factory) => {
+                ILBFactory::new(factory.address, middleware)
+                    .get_number_of_lb_pairs()
+                    .call()
+                    .await?
+            }
+            _ => return Err(AMMError::PoolEnumerationNotSupported(self.address())),
+        };
+
+        Ok(pool_count.as_u64())
+    }
+}
+
+//Calls the index getter of a factory for every index below `pools_length`, aggregating `chunk_size` calls into each Multicall3 request
+async fn get_pool_addresses_via_multicall<
+    M: Middleware,
+    D: Detokenize,
+    F: Fn(U256) -> ContractCall<M, D>,
+>(
+    pools_length: U256,
+    pool_at_index: F,
+    block_number: U64,
+    chunk_size: usize,
+    middleware: Arc<M>,
+) -> Result<Vec<H160>, AMMError<M>> {
+    let pools_length = pools_length.as_usize();
+
+    tracing::trace!(
+        pools_length,
+        chunk_size,
+        "getting pool addresses via multicall"
+    );
+
+    let mut multicall = Multicall::new(middleware, None)
+        .await?
+        .block(BlockNumber::Number(block_number));
+
+    let mut pools = vec![];
+    for chunk_start in (0..pools_length).step_by(chunk_size) {
+        for index in chunk_start..pools_length.min(chunk_start + chunk_size) {
+            multicall.add_call(pool_at_index(U256::from(index)), false);
+        }
+
+        for result in multicall.call_raw().await? {
+            if let Some(address) = result.ok().and_then(|token| token.into_address()) {
+                if !address.is_zero() {
+                    pools.push(address);
+                }
+            }
+        }
+
+        multicall.clear_calls();
+    }
+
+    Ok(pools)
 }
 
 impl TryFrom<H256> for Factory {
@@ -212,8 +708,133 @@ impl TryFrom<H256> for Factory {
             Ok(Factory::UniswapV2Factory(UniswapV2Factory::default()))
         } else if value == POOL_CREATED_EVENT_SIGNATURE {
             Ok(Factory::UniswapV3Factory(UniswapV3Factory::default()))
+        } else if value == WEIGHTED_POOL_CREATED_EVENT_SIGNATURE {
+            Ok(Factory::BalancerV2Factory(BalancerV2Factory::default()))
+        } else if value == POOL_REGISTERED_EVENT_SIGNATURE {
+            Ok(Factory::BalancerV2Vault(BalancerV2Vault::default()))
+        } else if value == POOL_ADDED_EVENT_SIGNATURE {
+            Ok(Factory::CurveFactory(CurveFactory::default()))
+        } else if value == INITIALIZE_EVENT_SIGNATURE {
+            Ok(Factory::UniswapV4PoolManager(
+                UniswapV4PoolManager::default(),
+            ))
+        } else if value == SOLIDLY_PAIR_CREATED_EVENT_SIGNATURE {
+            Ok(Factory::SolidlyFactory(SolidlyFactory::default()))
+        } else if value == SOLIDLY_POOL_CREATED_EVENT_SIGNATURE {
+            Ok(Factory::SolidlyFactory(SolidlyFactory::new_v2(
+                H160::zero(),
+                0,
+            )))
+        } else if value == LB_PAIR_CREATED_EVENT_SIGNATURE {
+            Ok(Factory::LBFactory(LBFactory::default()))
+        } else if value == MAVERICK_POOL_CREATED_EVENT_SIGNATURE {
+            Ok(Factory::MaverickFactory(MaverickFactory::default()))
+        } else if value == ALGEBRA_POOL_EVENT_SIGNATURE {
+            Ok(Factory::AlgebraFactory(AlgebraFactory::default()))
+        } else if value == NEW_DVM_EVENT_SIGNATURE {
+            Ok(Factory::DodoFactory(DodoFactory::default()))
+        } else if value == NEW_DPP_EVENT_SIGNATURE {
+            Ok(Factory::DodoFactory(DodoFactory {
+                pool_type: DodoPoolType::DPP,
+                ..Default::default()
+            }))
         } else {
             return Err(EventLogError::InvalidEventSignature);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, sync::Arc};
+
+    use ethers::{
+        providers::{Http, Provider},
+        types::H160,
+    };
+
+    use crate::amm::{
+        algebra::factory::AlgebraFactory,
+        balancer_v2::factory::{BalancerV2Factory, BalancerV2Vault},
+        camelot::factory::CamelotFactory,
+        curve::factory::CurveFactory,
+        dodo::{factory::DodoFactory, DodoPoolType},
+        kyber_elastic::factory::KyberElasticFactory,
+        maverick::factory::MaverickFactory,
+        solidly::factory::SolidlyFactory,
+        trader_joe_lb::factory::LBFactory,
+        uniswap_v2::factory::UniswapV2Factory,
+        uniswap_v3::factory::{UniswapV3Factory, PANCAKE_V3_FEE_TIERS},
+        uniswap_v4::{factory::UniswapV4PoolManager, hooks::HookPattern},
+    };
+    use crate::errors::AMMError;
+
+    use super::{AutomatedMarketMakerFactory, Factory};
+
+    #[test]
+    fn test_factory_serde_round_trip() -> eyre::Result<()> {
+        let address = H160::from_low_u64_be(0xfac);
+        let factories = vec![
+            Factory::UniswapV2Factory(UniswapV2Factory::new(address, 10000835, 300)),
+            Factory::UniswapV3Factory(UniswapV3Factory::new_with_fee_tiers(
+                address,
+                26956207,
+                PANCAKE_V3_FEE_TIERS.to_vec(),
+            )),
+            Factory::BalancerV2Factory(BalancerV2Factory::new(address, 1)),
+            Factory::BalancerV2Vault(BalancerV2Vault::new(address, 2)),
+            Factory::CurveFactory(CurveFactory::new(address, 3)),
+            Factory::UniswapV4PoolManager(UniswapV4PoolManager::new(
+                address,
+                4,
+                vec![HookPattern {
+                    mask: H160::from_low_u64_be(0xff),
+                    value: H160::from_low_u64_be(0x80),
+                }],
+            )),
+            Factory::SolidlyFactory(SolidlyFactory::new(address, 5)),
+            Factory::SolidlyFactory(SolidlyFactory::new_v2(address, 5)),
+            Factory::LBFactory(LBFactory::new(address, 6)),
+            Factory::MaverickFactory(MaverickFactory::new(address, 7)),
+            Factory::CamelotFactory(CamelotFactory::new(address, 8)),
+            Factory::KyberElasticFactory(KyberElasticFactory::new(address, 9)),
+            Factory::DodoFactory(DodoFactory::new(address, 10, DodoPoolType::DPP)),
+            Factory::AlgebraFactory(AlgebraFactory::new(address, 11)),
+        ];
+
+        for factory in factories {
+            let serialized = serde_json::to_string(&factory)?;
+            let deserialized: Factory = serde_json::from_str(&serialized)?;
+
+            assert_eq!(deserialized.address(), factory.address());
+            assert_eq!(deserialized.creation_block(), factory.creation_block());
+            assert_eq!(serde_json::to_string(&deserialized)?, serialized);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pool_count() -> eyre::Result<()> {
+        let rpc_endpoint = std::env::var("ETHEREUM_RPC_ENDPOINT")?;
+        let middleware = Arc::new(Provider::<Http>::try_from(rpc_endpoint)?);
+
+        let factory = Factory::UniswapV2Factory(UniswapV2Factory::new(
+            H160::from_str("0x5C69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f")?,
+            10000835,
+            300,
+        ));
+        assert!(factory.pool_count(middleware.clone()).await? > 300000);
+
+        let factory = Factory::UniswapV3Factory(UniswapV3Factory::new(
+            H160::from_str("0x1F98431c8aD98523631AE4a59f267346ea31F984")?,
+            12369621,
+        ));
+        assert!(matches!(
+            factory.pool_count(middleware).await,
+            Err(AMMError::PoolEnumerationNotSupported(_))
+        ));
+
+        Ok(())
+    }
+}
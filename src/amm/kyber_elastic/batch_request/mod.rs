@@ -0,0 +1,139 @@
+use std::sync::Arc;
+
+use ethers::{
+    contract::Multicall,
+    providers::Middleware,
+    types::{BlockNumber, I256},
+};
+
+use crate::{amm::uniswap_v3::IErc20, errors::AMMError};
+
+use super::{IKyberElasticPool, KyberElasticPool};
+
+pub async fn get_kyber_elastic_pool_data_batch_request<M: Middleware>(
+    pool: &mut KyberElasticPool,
+    block_number: Option<u64>,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    //The pool data and the pool state are read at the same block
+    let block_number = match block_number {
+        Some(block_number) => block_number,
+        None => middleware
+            .get_block_number()
+            .await
+            .map_err(AMMError::MiddlewareError)?
+            .as_u64(),
+    };
+
+    let elastic_pool = IKyberElasticPool::new(pool.address, middleware.clone());
+    let mut multicall = Multicall::new(middleware.clone(), None)
+        .await?
+        .block(BlockNumber::from(block_number));
+
+    multicall.add_call(elastic_pool.token_0(), false);
+    multicall.add_call(elastic_pool.token_1(), false);
+    multicall.add_call(elastic_pool.swap_fee_units(), false);
+    multicall.add_call(elastic_pool.tick_distance(), false);
+
+    let results = multicall.call_raw().await?;
+    let mut results = results.into_iter().map(|result| result.ok());
+
+    pool.token_a = results
+        .next()
+        .flatten()
+        .and_then(|token| token.into_address())
+        .ok_or(AMMError::BatchRequestError(pool.address))?;
+    pool.token_b = results
+        .next()
+        .flatten()
+        .and_then(|token| token.into_address())
+        .ok_or(AMMError::BatchRequestError(pool.address))?;
+    pool.fee = results
+        .next()
+        .flatten()
+        .and_then(|fee| fee.into_uint())
+        .ok_or(AMMError::BatchRequestError(pool.address))?
+        .as_u32();
+    pool.tick_distance = results
+        .next()
+        .flatten()
+        .and_then(|tick_distance| tick_distance.into_int())
+        .map(|tick_distance| I256::from_raw(tick_distance).as_i32())
+        .ok_or(AMMError::BatchRequestError(pool.address))?;
+
+    multicall.clear_calls();
+    for token in [pool.token_a, pool.token_b] {
+        multicall.add_call(IErc20::new(token, middleware.clone()).decimals(), false);
+    }
+
+    let decimals = multicall
+        .call_raw()
+        .await?
+        .into_iter()
+        .map(|result| {
+            result
+                .ok()
+                .and_then(|decimals| decimals.into_uint())
+                .map(|decimals| decimals.as_u32() as u8)
+        })
+        .collect::<Option<Vec<u8>>>()
+        .ok_or(AMMError::BatchRequestError(pool.address))?;
+
+    pool.token_a_decimals = decimals[0];
+    pool.token_b_decimals = decimals[1];
+
+    sync_kyber_elastic_pool_batch_request(pool, Some(block_number), middleware).await
+}
+
+//Syncs the price, the current tick and both the base and reinvestment liquidity
+pub async fn sync_kyber_elastic_pool_batch_request<M: Middleware>(
+    pool: &mut KyberElasticPool,
+    block_number: Option<u64>,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    let block = block_number
+        .map(BlockNumber::from)
+        .unwrap_or(BlockNumber::Latest);
+
+    let elastic_pool = IKyberElasticPool::new(pool.address, middleware.clone());
+    let mut multicall = Multicall::new(middleware, None).await?.block(block);
+
+    multicall.add_call(elastic_pool.get_pool_state(), false);
+    multicall.add_call(elastic_pool.get_liquidity_state(), false);
+
+    let results = multicall.call_raw().await?;
+    let mut results = results
+        .into_iter()
+        .map(|result| result.ok().and_then(|state| state.into_tuple()));
+
+    let pool_state = results
+        .next()
+        .flatten()
+        .ok_or(AMMError::SyncError(pool.address))?;
+    let liquidity_state = results
+        .next()
+        .flatten()
+        .ok_or(AMMError::SyncError(pool.address))?;
+
+    pool.sqrt_price = pool_state
+        .first()
+        .and_then(|sqrt_price| sqrt_price.clone().into_uint())
+        .ok_or(AMMError::SyncError(pool.address))?;
+    pool.tick = pool_state
+        .get(1)
+        .and_then(|tick| tick.clone().into_int())
+        .map(|tick| I256::from_raw(tick).as_i32())
+        .ok_or(AMMError::SyncError(pool.address))?;
+    pool.base_liquidity = liquidity_state
+        .first()
+        .and_then(|liquidity| liquidity.clone().into_uint())
+        .ok_or(AMMError::SyncError(pool.address))?
+        .as_u128();
+    pool.reinvestment_liquidity = liquidity_state
+        .get(1)
+        .and_then(|liquidity| liquidity.clone().into_uint())
+        .ok_or(AMMError::SyncError(pool.address))?
+        .as_u128();
+
+    Ok(())
+}
@@ -0,0 +1,773 @@
+pub mod batch_request;
+pub mod factory;
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use async_trait::async_trait;
+use ethers::{
+    abi::RawLog,
+    prelude::{abigen, EthEvent},
+    providers::Middleware,
+    types::{BlockNumber, Filter, Log, H160, H256, U256, U64},
+};
+use serde::{Deserialize, Serialize};
+use uniswap_v3_math::full_math::{mul_div, mul_div_rounding_up};
+
+use crate::{
+    amm::{price::Price, AutomatedMarketMaker},
+    errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
+};
+
+use super::{
+    factory::TASK_LIMIT,
+    uniswap_v3::{
+        BurnFilter, Info, MintFilter, SwapFilter, BURN_EVENT_SIGNATURE, MAX_SQRT_RATIO, MAX_TICK,
+        MINT_EVENT_SIGNATURE, MIN_SQRT_RATIO, MIN_TICK, POPULATE_TICK_DATA_STEP,
+        SWAP_EVENT_SIGNATURE,
+    },
+    uniswap_v4::process_logs_from_handles,
+};
+
+abigen!(
+    IKyberElasticPool,
+    r#"[
+        function token0() external view returns (address)
+        function token1() external view returns (address)
+        function swapFeeUnits() external view returns (uint24)
+        function tickDistance() external view returns (int24)
+        function getPoolState() external view returns (uint160 sqrtP, int24 currentTick, int24 nearestCurrentTick, bool locked)
+        function getLiquidityState() external view returns (uint128 baseL, uint128 reinvestL, uint128 reinvestLLast)
+        event BurnRTokens(address indexed owner, uint256 qty, uint256 qty0, uint256 qty1)
+    ]"#;
+);
+
+//Elastic pools emit Swap, Mint and Burn with the same signatures as Uniswap V3 pools, the liquidity of the swap event is the base liquidity
+
+pub const BURN_R_TOKENS_EVENT_SIGNATURE: H256 = H256([
+    50, 68, 135, 201, 154, 31, 127, 14, 49, 39, 73, 154, 84, 132, 82, 211, 161, 152, 231, 140, 205,
+    7, 173, 217, 19, 203, 147, 213, 159, 15, 3, 155,
+]);
+
+//Fees are expressed in units of 1e-5
+pub const FEE_UNITS: u32 = 100000;
+const TWO_FEE_UNITS: U256 = U256([200000, 0, 0, 0]);
+const Q96: U256 = U256([0, 4294967296, 0, 0]);
+//A single swap step never moves the price further than this many ticks
+pub const MAX_TICK_DISTANCE: i32 = 480;
+
+//Swap fees of Elastic pools are added to the reinvestment liquidity instead of being accrued outside of the curve,
+//so the pool quotes against base_liquidity + reinvestment_liquidity and the reinvestment liquidity grows with every swap
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KyberElasticPool {
+    pub address: H160,
+    pub token_a: H160,
+    pub token_a_decimals: u8,
+    pub token_b: H160,
+    pub token_b_decimals: u8,
+    pub base_liquidity: u128,
+    pub reinvestment_liquidity: u128,
+    pub sqrt_price: U256,
+    pub fee: u32,
+    pub tick: i32,
+    pub tick_distance: i32,
+    pub ticks: BTreeMap<i32, Info>,
+}
+
+#[async_trait]
+impl AutomatedMarketMaker for KyberElasticPool {
+    fn address(&self) -> H160 {
+        self.address
+    }
+
+    async fn sync<M: Middleware>(&mut self, middleware: Arc<M>) -> Result<(), AMMError<M>> {
+        batch_request::sync_kyber_elastic_pool_batch_request(self, None, middleware).await
+    }
+
+    fn sync_on_event_signatures(&self) -> Vec<H256> {
+        vec![
+            SWAP_EVENT_SIGNATURE,
+            MINT_EVENT_SIGNATURE,
+            BURN_EVENT_SIGNATURE,
+            BURN_R_TOKENS_EVENT_SIGNATURE,
+        ]
+    }
+
+    fn sync_from_log(&mut self, log: Log) -> Result<(), EventLogError> {
+        let event_signature = log.topics[0];
+
+        if event_signature == SWAP_EVENT_SIGNATURE {
+            self.sync_from_swap_log(log)?;
+        } else if event_signature == MINT_EVENT_SIGNATURE {
+            self.sync_from_mint_log(log)?;
+        } else if event_signature == BURN_EVENT_SIGNATURE {
+            self.sync_from_burn_log(log)?;
+        } else if event_signature == BURN_R_TOKENS_EVENT_SIGNATURE {
+            self.sync_from_burn_r_tokens_log(log)?;
+        } else {
+            Err(EventLogError::InvalidEventSignature)?
+        }
+
+        Ok(())
+    }
+
+    fn tokens(&self) -> Vec<H160> {
+        vec![self.token_a, self.token_b]
+    }
+
+    fn calculate_price(&self, base_token: H160) -> Result<Price, ArithmeticError> {
+        self.check_liquidity()?;
+
+        let price = Price::from_sqrt_price_x96(
+            self.sqrt_price,
+            self.token_a_decimals,
+            self.token_b_decimals,
+        )?;
+
+        if base_token == self.token_a {
+            Ok(price)
+        } else {
+            price.invert()
+        }
+    }
+
+    // NOTE: This function will not populate the ticks, if you want to populate those, you must call populate_tick_data on an initialized pool
+    async fn populate_data<M: Middleware>(
+        &mut self,
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        batch_request::get_kyber_elastic_pool_data_batch_request(self, block_number, middleware)
+            .await
+    }
+
+    fn simulate_swap(&self, token_in: H160, amount_in: U256) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        self.check_liquidity()?;
+
+        let (amount_out, _) = self.swap(token_in == self.token_a, amount_in)?;
+
+        tracing::trace!(?amount_out);
+
+        Ok(amount_out)
+    }
+
+    fn simulate_swap_mut(
+        &mut self,
+        token_in: H160,
+        amount_in: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        self.check_liquidity()?;
+
+        let (amount_out, (sqrt_price, tick, base_liquidity, reinvestment_liquidity)) =
+            self.swap(token_in == self.token_a, amount_in)?;
+
+        //Update the pool state
+        self.sqrt_price = sqrt_price;
+        self.tick = tick;
+        self.base_liquidity = base_liquidity;
+        self.reinvestment_liquidity = reinvestment_liquidity;
+
+        tracing::trace!(?amount_out);
+
+        Ok(amount_out)
+    }
+
+    fn swap_fee(&self, _token_in: H160) -> f64 {
+        self.fee as f64 / FEE_UNITS as f64
+    }
+
+    fn token_decimals(&self) -> Vec<u8> {
+        vec![self.token_a_decimals, self.token_b_decimals]
+    }
+
+    fn get_token_out(&self, token_in: H160) -> H160 {
+        if self.token_a == token_in {
+            self.token_b
+        } else {
+            self.token_a
+        }
+    }
+}
+
+impl KyberElasticPool {
+    //Creates a new instance of the pool from the pool address and replays the liquidity of the pool from its creation block
+    pub async fn new_from_address<M: 'static + Middleware>(
+        pool_address: H160,
+        creation_block: u64,
+        middleware: Arc<M>,
+    ) -> Result<Self, AMMError<M>> {
+        let mut pool = KyberElasticPool {
+            address: pool_address,
+            ..Default::default()
+        };
+
+        let synced_block = pool
+            .populate_tick_data(creation_block, middleware.clone())
+            .await?;
+
+        pool.populate_data(Some(synced_block), middleware).await?;
+
+        if !pool.data_is_populated() {
+            return Err(AMMError::PoolDataError);
+        }
+
+        Ok(pool)
+    }
+
+    pub fn data_is_populated(&self) -> bool {
+        !(self.token_a.is_zero() || self.token_b.is_zero())
+    }
+
+    pub async fn populate_tick_data<M: 'static + Middleware>(
+        &mut self,
+        mut from_block: u64,
+        middleware: Arc<M>,
+    ) -> Result<u64, AMMError<M>> {
+        let current_block = middleware
+            .get_block_number()
+            .await
+            .map_err(AMMError::MiddlewareError)?
+            .as_u64();
+        let mut ordered_logs: BTreeMap<U64, Vec<Log>> = BTreeMap::new();
+
+        let pool_address = self.address;
+
+        let mut handles = vec![];
+        let mut tasks = 0;
+
+        while from_block < current_block {
+            let middleware = middleware.clone();
+
+            let mut target_block = from_block + POPULATE_TICK_DATA_STEP - 1;
+            if target_block > current_block {
+                target_block = current_block;
+            }
+
+            handles.push(tokio::spawn(async move {
+                let logs = middleware
+                    .get_logs(
+                        &Filter::new()
+                            .topic0(vec![BURN_EVENT_SIGNATURE, MINT_EVENT_SIGNATURE])
+                            .address(pool_address)
+                            .from_block(BlockNumber::Number(U64([from_block])))
+                            .to_block(BlockNumber::Number(U64([target_block]))),
+                    )
+                    .await
+                    .map_err(AMMError::MiddlewareError)?;
+
+                Ok::<Vec<Log>, AMMError<M>>(logs)
+            }));
+
+            from_block += POPULATE_TICK_DATA_STEP;
+            tasks += 1;
+            //Here we are limiting the number of green threads that can be spun up to not have the node time out
+            if tasks == TASK_LIMIT {
+                process_logs_from_handles(handles, &mut ordered_logs).await?;
+                handles = vec![];
+                tasks = 0;
+            }
+        }
+
+        process_logs_from_handles(handles, &mut ordered_logs).await?;
+
+        for (_, log_group) in ordered_logs {
+            for log in log_group {
+                self.sync_from_log(log)?;
+            }
+        }
+
+        Ok(current_block)
+    }
+
+    //The swap event does not include the reinvestment liquidity, so the swap is simulated to get the fees added to it
+    pub fn sync_from_swap_log(&mut self, log: Log) -> Result<(), EventLogError> {
+        let swap_event = SwapFilter::decode_log(&RawLog::from(log))?;
+
+        let zero_for_one = swap_event.amount_0.is_positive();
+        let amount_in = if zero_for_one {
+            swap_event.amount_0.into_raw()
+        } else {
+            swap_event.amount_1.into_raw()
+        };
+
+        let (_, (_, _, _, reinvestment_liquidity)) = self.swap(zero_for_one, amount_in)?;
+
+        self.sqrt_price = swap_event.sqrt_price_x96;
+        self.base_liquidity = swap_event.liquidity;
+        self.reinvestment_liquidity = reinvestment_liquidity;
+        self.tick = swap_event.tick;
+
+        Ok(())
+    }
+
+    pub fn sync_from_mint_log(&mut self, log: Log) -> Result<(), EventLogError> {
+        let mint_event = MintFilter::decode_log(&RawLog::from(log))?;
+
+        self.modify_position(
+            mint_event.tick_lower,
+            mint_event.tick_upper,
+            mint_event.amount as i128,
+        );
+
+        Ok(())
+    }
+
+    pub fn sync_from_burn_log(&mut self, log: Log) -> Result<(), EventLogError> {
+        let burn_event = BurnFilter::decode_log(&RawLog::from(log))?;
+
+        self.modify_position(
+            burn_event.tick_lower,
+            burn_event.tick_upper,
+            -(burn_event.amount as i128),
+        );
+
+        Ok(())
+    }
+
+    //Burning reinvestment tokens pays out qty0 = delta_l / sqrt_price of the reinvestment liquidity
+    pub fn sync_from_burn_r_tokens_log(&mut self, log: Log) -> Result<(), EventLogError> {
+        let burn_event = BurnRTokensFilter::decode_log(&RawLog::from(log))?;
+
+        let delta_l =
+            mul_div(burn_event.qty_0, self.sqrt_price, Q96).map_err(SwapSimulationError::from)?;
+        self.reinvestment_liquidity = self
+            .reinvestment_liquidity
+            .saturating_sub(delta_l.min(U256::from(u128::MAX)).as_u128());
+
+        Ok(())
+    }
+
+    pub fn modify_position(&mut self, tick_lower: i32, tick_upper: i32, liquidity_delta: i128) {
+        if liquidity_delta == 0 {
+            return;
+        }
+
+        self.update_tick(tick_lower, liquidity_delta, false);
+        self.update_tick(tick_upper, liquidity_delta, true);
+
+        //The position is only active if the current tick is within [tick_lower, tick_upper)
+        if self.tick >= tick_lower && self.tick < tick_upper {
+            self.base_liquidity = self.base_liquidity.saturating_add_signed(liquidity_delta);
+        }
+    }
+
+    //Ticks without liquidity are removed so that the next initialized tick can be found with a range lookup
+    fn update_tick(&mut self, tick: i32, liquidity_delta: i128, upper: bool) {
+        let info = self.ticks.entry(tick).or_default();
+
+        info.liquidity_gross = info.liquidity_gross.saturating_add_signed(liquidity_delta);
+        info.initialized = info.liquidity_gross != 0;
+        info.liquidity_net = if upper {
+            info.liquidity_net - liquidity_delta
+        } else {
+            info.liquidity_net + liquidity_delta
+        };
+
+        if !info.initialized {
+            self.ticks.remove(&tick);
+        }
+    }
+
+    //Same as walking the linked list of initialized ticks of the pool, MIN_TICK and MAX_TICK are always initialized
+    fn next_initialized_tick(&self, tick: i32, will_up_tick: bool) -> i32 {
+        if will_up_tick {
+            self.ticks
+                .range(tick + 1..)
+                .next()
+                .map(|(tick, _)| *tick)
+                .unwrap_or(MAX_TICK)
+        } else {
+            self.ticks
+                .range(..=tick)
+                .next_back()
+                .map(|(tick, _)| *tick)
+                .unwrap_or(MIN_TICK)
+        }
+    }
+
+    //A pool that is not initialized has no price, a pool without active liquidity or initialized ticks has no liquidity to swap through
+    fn check_liquidity(&self) -> Result<(), ArithmeticError> {
+        if self.sqrt_price.is_zero()
+            || (self.base_liquidity == 0
+                && self.reinvestment_liquidity == 0
+                && self.ticks.is_empty())
+        {
+            return Err(ArithmeticError::ZeroLiquidity(self.address));
+        }
+
+        Ok(())
+    }
+
+    //Runs the Elastic exact input swap loop and returns the amount out along with the resulting (sqrt_price, tick, base_liquidity, reinvestment_liquidity)
+    fn swap(
+        &self,
+        zero_for_one: bool,
+        amount_in: U256,
+    ) -> Result<(U256, (U256, i32, u128, u128)), SwapSimulationError> {
+        let mut sqrt_price = self.sqrt_price;
+        let mut tick = self.tick;
+        let mut base_liquidity = self.base_liquidity;
+        let mut reinvestment_liquidity = U256::from(self.reinvestment_liquidity);
+
+        let mut amount_specified_remaining = amount_in;
+        let mut amount_out = U256::zero();
+
+        //Selling token0 moves the price down
+        let will_up_tick = !zero_for_one;
+        let sqrt_price_limit = if zero_for_one {
+            MIN_SQRT_RATIO + 1
+        } else {
+            MAX_SQRT_RATIO - 1
+        };
+
+        let mut next_tick = self.next_initialized_tick(tick, will_up_tick);
+
+        while !amount_specified_remaining.is_zero() && sqrt_price != sqrt_price_limit {
+            let temp_next_tick = if will_up_tick {
+                next_tick.min(tick + MAX_TICK_DISTANCE)
+            } else {
+                next_tick.max(tick - MAX_TICK_DISTANCE)
+            };
+
+            let start_sqrt_price = sqrt_price;
+            let next_sqrt_price =
+                uniswap_v3_math::tick_math::get_sqrt_ratio_at_tick(temp_next_tick)?;
+
+            let target_sqrt_price = if will_up_tick == (next_sqrt_price > sqrt_price_limit) {
+                sqrt_price_limit
+            } else {
+                next_sqrt_price
+            };
+
+            let used_amount;
+            let returned_amount;
+            let delta_l;
+            (used_amount, returned_amount, delta_l, sqrt_price) = compute_swap_step(
+                U256::from(base_liquidity) + reinvestment_liquidity,
+                start_sqrt_price,
+                target_sqrt_price,
+                self.fee,
+                amount_specified_remaining,
+                zero_for_one,
+            )?;
+
+            amount_specified_remaining -= used_amount;
+            amount_out += returned_amount;
+            reinvestment_liquidity += delta_l;
+
+            //The price stopped before the next tick
+            if sqrt_price != next_sqrt_price {
+                if sqrt_price != start_sqrt_price {
+                    tick = uniswap_v3_math::tick_math::get_tick_at_sqrt_ratio(sqrt_price)?;
+                }
+                break;
+            }
+
+            tick = if will_up_tick {
+                temp_next_tick
+            } else {
+                temp_next_tick - 1
+            };
+
+            //The step was capped by MAX_TICK_DISTANCE before an initialized tick
+            if temp_next_tick != next_tick {
+                continue;
+            }
+
+            let liquidity_net = self
+                .ticks
+                .get(&next_tick)
+                .map(|info| info.liquidity_net)
+                .unwrap_or_default();
+
+            base_liquidity = if will_up_tick {
+                base_liquidity.checked_add_signed(liquidity_net)
+            } else {
+                base_liquidity.checked_add_signed(-liquidity_net)
+            }
+            .ok_or(SwapSimulationError::LiquidityUnderflow)?;
+
+            next_tick = self.next_initialized_tick(tick, will_up_tick);
+        }
+
+        if reinvestment_liquidity > U256::from(u128::MAX) {
+            return Err(SwapSimulationError::LiquidityUnderflow);
+        }
+
+        Ok((
+            amount_out,
+            (
+                sqrt_price,
+                tick,
+                base_liquidity,
+                reinvestment_liquidity.as_u128(),
+            ),
+        ))
+    }
+}
+
+//Exact input version of `SwapMath.computeSwapStep`, returns (used_amount, returned_amount, delta_l, next_sqrt_price)
+pub fn compute_swap_step(
+    liquidity: U256,
+    current_sqrt_price: U256,
+    target_sqrt_price: U256,
+    fee: u32,
+    amount_remaining: U256,
+    is_token_0: bool,
+) -> Result<(U256, U256, U256, U256), SwapSimulationError> {
+    if current_sqrt_price == target_sqrt_price {
+        return Ok((U256::zero(), U256::zero(), U256::zero(), current_sqrt_price));
+    }
+
+    let fee = U256::from(fee);
+    let reach_amount = calc_reach_amount(
+        liquidity,
+        current_sqrt_price,
+        target_sqrt_price,
+        fee,
+        is_token_0,
+    )?;
+
+    let (used_amount, delta_l, next_sqrt_price) = if reach_amount > amount_remaining {
+        let delta_l =
+            estimate_incremental_liquidity(amount_remaining, current_sqrt_price, fee, is_token_0)?;
+        let next_sqrt_price = calc_final_price(
+            amount_remaining,
+            liquidity,
+            delta_l,
+            current_sqrt_price,
+            is_token_0,
+        )?;
+
+        (amount_remaining, delta_l, next_sqrt_price)
+    } else {
+        let delta_l = calc_incremental_liquidity(
+            reach_amount,
+            liquidity,
+            current_sqrt_price,
+            target_sqrt_price,
+            is_token_0,
+        )?;
+
+        (reach_amount, delta_l, target_sqrt_price)
+    };
+
+    let returned_amount = calc_returned_amount(
+        liquidity,
+        current_sqrt_price,
+        next_sqrt_price,
+        delta_l,
+        is_token_0,
+    )?;
+
+    Ok((used_amount, returned_amount, delta_l, next_sqrt_price))
+}
+
+//Amount in needed to move the price from current_sqrt_price to target_sqrt_price, rounded down
+fn calc_reach_amount(
+    liquidity: U256,
+    current_sqrt_price: U256,
+    target_sqrt_price: U256,
+    fee: U256,
+    is_token_0: bool,
+) -> Result<U256, SwapSimulationError> {
+    let abs_price_diff = if current_sqrt_price >= target_sqrt_price {
+        current_sqrt_price - target_sqrt_price
+    } else {
+        target_sqrt_price - current_sqrt_price
+    };
+
+    if is_token_0 {
+        // liquidity * 2 * abs_price_diff / (current_sqrt_price * (2 * target_sqrt_price - current_sqrt_price * fee))
+        let denominator = TWO_FEE_UNITS * target_sqrt_price - fee * current_sqrt_price;
+        let numerator = mul_div(liquidity, TWO_FEE_UNITS * abs_price_diff, denominator)?;
+
+        Ok(mul_div(numerator, Q96, current_sqrt_price)?)
+    } else {
+        // liquidity * 2 * abs_price_diff * current_sqrt_price / (2 * current_sqrt_price - target_sqrt_price * fee)
+        let denominator = TWO_FEE_UNITS * current_sqrt_price - fee * target_sqrt_price;
+        let numerator = mul_div(liquidity, TWO_FEE_UNITS * abs_price_diff, denominator)?;
+
+        Ok(mul_div(numerator, current_sqrt_price, Q96)?)
+    }
+}
+
+//Fees collected when the swap ends before the target price, rounded down
+fn estimate_incremental_liquidity(
+    abs_delta: U256,
+    current_sqrt_price: U256,
+    fee: U256,
+    is_token_0: bool,
+) -> Result<U256, SwapSimulationError> {
+    if is_token_0 {
+        Ok(mul_div(
+            current_sqrt_price,
+            abs_delta * fee,
+            TWO_FEE_UNITS << 96,
+        )?)
+    } else {
+        Ok(mul_div(
+            Q96,
+            abs_delta * fee,
+            TWO_FEE_UNITS * current_sqrt_price,
+        )?)
+    }
+}
+
+//Fees collected when the swap reaches the target price, rounded down
+fn calc_incremental_liquidity(
+    abs_delta: U256,
+    liquidity: U256,
+    current_sqrt_price: U256,
+    next_sqrt_price: U256,
+    is_token_0: bool,
+) -> Result<U256, SwapSimulationError> {
+    let liquidity_after = if is_token_0 {
+        // next_sqrt_price * (liquidity / current_sqrt_price + abs_delta)
+        let tmp = mul_div(liquidity, Q96, current_sqrt_price)? + abs_delta;
+        mul_div(next_sqrt_price, tmp, Q96)?
+    } else {
+        // (liquidity * current_sqrt_price + abs_delta) / next_sqrt_price
+        let tmp = mul_div(liquidity, current_sqrt_price, Q96)? + abs_delta;
+        mul_div(tmp, Q96, next_sqrt_price)?
+    };
+
+    Ok(liquidity_after.saturating_sub(liquidity))
+}
+
+//Price after swapping abs_delta with delta_l added to the liquidity, rounded away from the target
+fn calc_final_price(
+    abs_delta: U256,
+    liquidity: U256,
+    delta_l: U256,
+    current_sqrt_price: U256,
+    is_token_0: bool,
+) -> Result<U256, SwapSimulationError> {
+    if is_token_0 {
+        let tmp = mul_div(abs_delta, current_sqrt_price, Q96)?;
+        Ok(mul_div_rounding_up(
+            liquidity + delta_l,
+            current_sqrt_price,
+            liquidity + tmp,
+        )?)
+    } else {
+        let tmp = mul_div(abs_delta, Q96, current_sqrt_price)?;
+        Ok(mul_div(
+            liquidity + tmp,
+            current_sqrt_price,
+            liquidity + delta_l,
+        )?)
+    }
+}
+
+//Amount out of the step, rounded down
+fn calc_returned_amount(
+    liquidity: U256,
+    current_sqrt_price: U256,
+    next_sqrt_price: U256,
+    delta_l: U256,
+    is_token_0: bool,
+) -> Result<U256, SwapSimulationError> {
+    if is_token_0 {
+        // liquidity * (current_sqrt_price - next_sqrt_price) - delta_l * next_sqrt_price
+        let amount_out = mul_div(liquidity, current_sqrt_price - next_sqrt_price, Q96)?;
+        let fee_liquidity = mul_div_rounding_up(delta_l, next_sqrt_price, Q96)?;
+
+        Ok(amount_out.saturating_sub(fee_liquidity))
+    } else {
+        // liquidity / current_sqrt_price - (liquidity + delta_l) / next_sqrt_price
+        let amount_out = mul_div(liquidity, Q96, current_sqrt_price)?;
+        let fee_liquidity = mul_div_rounding_up(liquidity + delta_l, Q96, next_sqrt_price)?;
+
+        Ok(amount_out.saturating_sub(fee_liquidity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use ethers::types::{H160, U256};
+
+    use crate::amm::AutomatedMarketMaker;
+
+    use super::KyberElasticPool;
+
+    fn kyber_elastic_pool(fee: u32) -> KyberElasticPool {
+        let mut pool = KyberElasticPool {
+            address: H160::repeat_byte(0x01),
+            token_a: H160::repeat_byte(0x02),
+            token_a_decimals: 18,
+            token_b: H160::repeat_byte(0x03),
+            token_b_decimals: 18,
+            base_liquidity: 0,
+            reinvestment_liquidity: 0,
+            //sqrt(1) * 2^96
+            sqrt_price: U256::one() << 96,
+            fee,
+            tick: 0,
+            tick_distance: 10,
+            ticks: BTreeMap::new(),
+        };
+
+        pool.modify_position(-1000, 1000, 10_u128.pow(24) as i128);
+        pool
+    }
+
+    #[test]
+    fn test_simulate_swap() -> eyre::Result<()> {
+        let amount_in = U256::exp10(18);
+
+        //Without fees the pool quotes the constant product of the active liquidity
+        let pool = kyber_elastic_pool(0);
+        let amount_out = pool.simulate_swap(pool.token_a, amount_in)?;
+        assert!(amount_out < amount_in);
+        assert!(amount_out > amount_in - U256::exp10(13));
+
+        //The 0.3% fee is charged on the amount in
+        let pool = kyber_elastic_pool(300);
+        let amount_out_with_fee = pool.simulate_swap(pool.token_a, amount_in)?;
+        assert!(amount_out_with_fee < U256::from(997) * U256::exp10(15));
+        assert!(amount_out_with_fee > U256::from(9969) * U256::exp10(14));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulate_swap_mut_reinvests_fees() -> eyre::Result<()> {
+        let mut pool = kyber_elastic_pool(300);
+        let amount_in = U256::exp10(22);
+
+        let amount_out = pool.simulate_swap(pool.token_b, amount_in)?;
+        let amount_out_mut = pool.simulate_swap_mut(pool.token_b, amount_in)?;
+
+        assert_eq!(amount_out, amount_out_mut);
+        assert!(pool.reinvestment_liquidity > 0);
+        assert!(pool.tick > 0);
+        assert_eq!(pool.base_liquidity, 10_u128.pow(24));
+
+        //Swapping through the upper tick of the position leaves no liquidity in range
+        let amount_out = pool.simulate_swap_mut(pool.token_b, U256::exp10(24))?;
+        assert!(amount_out < U256::exp10(24));
+        assert_eq!(pool.base_liquidity, 0);
+        assert!(pool.tick >= 1000);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_modify_position() {
+        let mut pool = kyber_elastic_pool(300);
+
+        assert_eq!(pool.next_initialized_tick(0, true), 1000);
+        assert_eq!(pool.next_initialized_tick(0, false), -1000);
+
+        pool.modify_position(-1000, 1000, -(10_u128.pow(24) as i128));
+        assert!(pool.ticks.is_empty());
+        assert_eq!(pool.base_liquidity, 0);
+        assert_eq!(pool.next_initialized_tick(0, true), super::MAX_TICK);
+    }
+}
@@ -0,0 +1,152 @@
+use ethers::types::{H160, U256};
+
+use crate::{
+    amm::{AutomatedMarketMaker, AMM},
+    errors::SwapSimulationError,
+};
+
+//Amounts in are doubled up to this bound while looking for the range that contains the optimum
+const MAX_AMOUNT_IN_BITS: usize = 128;
+
+//Finds the amount of `token_in` that maximizes the profit of buying the other token on `pool_a` and selling it back on `pool_b`.
+//Returns `(optimal_amount_in, expected_profit)`, both are zero if there is no profitable arbitrage.
+//The profit is concave in the amount in for every supported AMM, so the optimum is found with a ternary search over swap simulations.
+pub fn calculate_arb_amount(
+    pool_a: &AMM,
+    pool_b: &AMM,
+    token_in: H160,
+) -> Result<(U256, U256), SwapSimulationError> {
+    if !pool_a.tokens().contains(&token_in) {
+        return Err(SwapSimulationError::TokenNotInPool(token_in));
+    }
+
+    let token_out = pool_a.get_token_out(token_in);
+    if !pool_b.tokens().contains(&token_out) {
+        return Err(SwapSimulationError::TokenNotInPool(token_out));
+    }
+
+    if pool_b.get_token_out(token_out) != token_in {
+        return Err(SwapSimulationError::InvalidHop(token_out, token_in));
+    }
+
+    //Swaps that can not be filled are treated as less profitable than any swap that can
+    let profit = |amount_in: U256| -> Option<U256> {
+        let amount_out = pool_a.simulate_swap(token_in, amount_in).ok()?;
+        let amount_out = pool_b.simulate_swap(token_out, amount_out).ok()?;
+
+        Some(amount_out.saturating_sub(amount_in))
+    };
+
+    //Double the amount in until the arbitrage becomes profitable and then unprofitable again
+    let mut upper = U256::one();
+    let mut profitable = false;
+    loop {
+        match profit(upper) {
+            Some(amount) if !amount.is_zero() => profitable = true,
+            _ if profitable => break,
+            _ => {}
+        }
+
+        if upper.bits() > MAX_AMOUNT_IN_BITS {
+            if profitable {
+                break;
+            }
+
+            return Ok((U256::zero(), U256::zero()));
+        }
+
+        upper = upper * 2;
+    }
+
+    let (mut low, mut high) = (U256::zero(), upper);
+    while high - low > U256::from(2) {
+        let third = (high - low) / 3;
+        let (mid_low, mid_high) = (low + third, high - third);
+
+        if profit(mid_low) < profit(mid_high) {
+            low = mid_low;
+        } else {
+            high = mid_high;
+        }
+    }
+
+    let mut best = (U256::zero(), U256::zero());
+    let mut amount_in = low;
+    while amount_in <= high {
+        if let Some(amount) = profit(amount_in) {
+            if amount > best.1 {
+                best = (amount_in, amount);
+            }
+        }
+
+        amount_in += U256::one();
+    }
+
+    Ok(best)
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::{H160, U256};
+
+    use crate::amm::{uniswap_v2::UniswapV2Pool, AMM};
+
+    use super::calculate_arb_amount;
+
+    fn abs_diff(a: U256, b: U256) -> U256 {
+        if a > b {
+            a - b
+        } else {
+            b - a
+        }
+    }
+
+    fn pool(reserve_0: u128, reserve_1: u128, fee: u32) -> AMM {
+        AMM::UniswapV2Pool(UniswapV2Pool {
+            token_a: H160::from_low_u64_be(1),
+            token_a_decimals: 18,
+            token_b: H160::from_low_u64_be(2),
+            token_b_decimals: 18,
+            reserve_0: reserve_0 * 10_u128.pow(18),
+            reserve_1: reserve_1 * 10_u128.pow(18),
+            fee,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_calculate_arb_amount() -> eyre::Result<()> {
+        //Without fees two constant product pools compose into one with reserves a = 1000 * 1000 / 2000 and b = 4000 * 1000 / 2000,
+        //so the optimal amount in is sqrt(a * b) - a = 500 and the profit is b * 500 / (a + 500) - 500 = 500
+        let pool_a = pool(1000, 1000, 0);
+        let pool_b = pool(4000, 1000, 0);
+
+        let (amount_in, profit) = calculate_arb_amount(&pool_a, &pool_b, H160::from_low_u64_be(1))?;
+
+        let expected = U256::from(500) * U256::exp10(18);
+        assert!(abs_diff(amount_in, expected) < U256::exp10(15));
+        assert!(abs_diff(profit, expected) < U256::exp10(6));
+
+        //Buying on the expensive pool is never profitable
+        assert_eq!(
+            calculate_arb_amount(&pool_b, &pool_a, H160::from_low_u64_be(1))?,
+            (U256::zero(), U256::zero())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_calculate_arb_amount_below_fees() -> eyre::Result<()> {
+        //A 0.2% price difference does not cover two 0.3% fees
+        let pool_a = pool(1000, 1000, 300);
+        let pool_b = pool(1002, 1000, 300);
+
+        assert_eq!(
+            calculate_arb_amount(&pool_a, &pool_b, H160::from_low_u64_be(1))?,
+            (U256::zero(), U256::zero())
+        );
+
+        Ok(())
+    }
+}
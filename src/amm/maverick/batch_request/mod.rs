@@ -0,0 +1,151 @@
+use std::{collections::HashMap, sync::Arc};
+
+use ethers::{
+    abi::Token,
+    contract::Multicall,
+    providers::Middleware,
+    types::{BlockNumber, Bytes},
+};
+
+use crate::{amm::uniswap_v3::IErc20, errors::AMMError};
+
+use super::{Bin, IMaverickPool, MaverickPool};
+
+//Max number of bins read in a single multicall
+const BIN_BATCH_SIZE: u128 = 500;
+
+pub async fn get_maverick_pool_data_batch_request<M: Middleware>(
+    pool: &mut MaverickPool,
+    block_number: Option<u64>,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    //The pool data and the bins are read at the same block
+    let block_number = match block_number {
+        Some(block_number) => block_number,
+        None => middleware
+            .get_block_number()
+            .await
+            .map_err(AMMError::MiddlewareError)?
+            .as_u64(),
+    };
+
+    let maverick_pool = IMaverickPool::new(pool.address, middleware.clone());
+    let mut multicall = Multicall::new(middleware.clone(), None)
+        .await?
+        .block(BlockNumber::from(block_number));
+
+    multicall.add_call(maverick_pool.token_a(), false);
+    multicall.add_call(maverick_pool.token_b(), false);
+    multicall.add_call(maverick_pool.fee(), false);
+    multicall.add_call(maverick_pool.tick_spacing(), false);
+
+    let results = multicall.call_raw().await?;
+    decode_pool_data(pool, &results).ok_or(AMMError::BatchRequestError(pool.address))?;
+
+    multicall.clear_calls();
+    for token in [pool.token_a, pool.token_b] {
+        multicall.add_call(IErc20::new(token, middleware.clone()).decimals(), false);
+    }
+
+    let decimals = multicall
+        .call_raw()
+        .await?
+        .into_iter()
+        .map(|result| {
+            result
+                .ok()
+                .and_then(|decimals| decimals.into_uint())
+                .map(|decimals| decimals.as_u32() as u8)
+        })
+        .collect::<Option<Vec<u8>>>()
+        .ok_or(AMMError::BatchRequestError(pool.address))?;
+
+    pool.token_a_decimals = decimals[0];
+    pool.token_b_decimals = decimals[1];
+
+    sync_maverick_pool_batch_request(pool, Some(block_number), middleware).await
+}
+
+//Syncs the fee, the active tick and every bin of the pool
+pub async fn sync_maverick_pool_batch_request<M: Middleware>(
+    pool: &mut MaverickPool,
+    block_number: Option<u64>,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    let block_number = match block_number {
+        Some(block_number) => block_number,
+        None => middleware
+            .get_block_number()
+            .await
+            .map_err(AMMError::MiddlewareError)?
+            .as_u64(),
+    };
+
+    let maverick_pool = IMaverickPool::new(pool.address, middleware.clone());
+    let mut multicall = Multicall::new(middleware.clone(), None)
+        .await?
+        .block(BlockNumber::from(block_number));
+
+    multicall.add_call(maverick_pool.get_state(), false);
+    multicall.add_call(maverick_pool.fee(), false);
+
+    let results = multicall.call_raw().await?;
+    let bin_counter = decode_state(pool, &results).ok_or(AMMError::SyncError(pool.address))?;
+
+    //Bin ids start at 1
+    let mut bins = HashMap::new();
+    let mut start = 1;
+    while start <= bin_counter {
+        let ids = start..=(start + BIN_BATCH_SIZE - 1).min(bin_counter);
+
+        multicall.clear_calls();
+        for id in ids.clone() {
+            multicall.add_call(maverick_pool.get_bin(id), false);
+        }
+
+        for (id, result) in ids.zip(multicall.call_raw().await?) {
+            let bin = decode_bin(result).ok_or(AMMError::SyncError(pool.address))?;
+            bins.insert(id, bin);
+        }
+
+        start += BIN_BATCH_SIZE;
+    }
+
+    pool.bins = bins;
+    pool.index_ticks();
+
+    Ok(())
+}
+
+fn decode_pool_data(pool: &mut MaverickPool, results: &[Result<Token, Bytes>]) -> Option<()> {
+    pool.token_a = results.first()?.as_ref().ok()?.clone().into_address()?;
+    pool.token_b = results.get(1)?.as_ref().ok()?.clone().into_address()?;
+    pool.fee = results.get(2)?.as_ref().ok()?.clone().into_uint()?;
+    pool.tick_spacing = results.get(3)?.as_ref().ok()?.clone().into_uint()?.as_u32();
+
+    Some(())
+}
+
+//Returns the number of bins in the pool
+fn decode_state(pool: &mut MaverickPool, results: &[Result<Token, Bytes>]) -> Option<u128> {
+    let state = results.first()?.as_ref().ok()?.clone().into_tuple()?;
+
+    pool.active_tick = state.first()?.clone().into_int()?.low_u32() as i32;
+    let bin_counter = state.get(2)?.clone().into_uint()?.as_u128();
+
+    pool.fee = results.get(1)?.as_ref().ok()?.clone().into_uint()?;
+
+    Some(bin_counter)
+}
+
+fn decode_bin(result: Result<Token, Bytes>) -> Option<Bin> {
+    let bin = result.ok()?.into_tuple()?;
+
+    Some(Bin {
+        reserve_a: bin.first()?.clone().into_uint()?.as_u128(),
+        reserve_b: bin.get(1)?.clone().into_uint()?.as_u128(),
+        merge_id: bin.get(3)?.clone().into_uint()?.as_u128(),
+        kind: bin.get(5)?.clone().into_uint()?.as_u32() as u8,
+        lower_tick: bin.get(6)?.clone().into_int()?.low_u32() as i32,
+    })
+}
@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::{
+    abi::RawLog,
+    prelude::{abigen, EthEvent},
+    providers::Middleware,
+    types::{Log, H160, H256},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    amm::{
+        factory::{AutomatedMarketMakerFactory, Factory},
+        AMM,
+    },
+    errors::AMMError,
+};
+
+use super::{batch_request, MaverickPool};
+
+abigen!(
+    IMaverickFactory,
+    r#"[
+        event PoolCreated(address poolAddress, uint256 fee, uint256 tickSpacing, int32 activeTick, int256 lookback, uint64 protocolFeeRatio, address tokenA, address tokenB)
+    ]"#;
+);
+
+pub const POOL_CREATED_EVENT_SIGNATURE: H256 = H256([
+    155, 63, 179, 161, 123, 78, 148, 235, 77, 18, 23, 37, 115, 114, 220, 199, 18, 33, 143, 205, 75,
+    193, 194, 132, 130, 189, 138, 104, 4, 167, 199, 117,
+]);
+
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct MaverickFactory {
+    pub address: H160,
+    pub creation_block: u64,
+}
+
+#[async_trait]
+impl AutomatedMarketMakerFactory for MaverickFactory {
+    fn address(&self) -> H160 {
+        self.address
+    }
+
+    fn creation_block(&self) -> u64 {
+        self.creation_block
+    }
+
+    fn amm_created_event_signature(&self) -> H256 {
+        POOL_CREATED_EVENT_SIGNATURE
+    }
+
+    async fn new_amm_from_log<M: 'static + Middleware>(
+        &self,
+        log: Log,
+        middleware: Arc<M>,
+    ) -> Result<AMM, AMMError<M>> {
+        let pool_created_event = PoolCreatedFilter::decode_log(&RawLog::from(log))?;
+
+        Ok(AMM::MaverickPool(
+            MaverickPool::new_from_address(pool_created_event.pool_address, middleware).await?,
+        ))
+    }
+
+    fn new_empty_amm_from_log(&self, log: Log) -> Result<AMM, ethers::abi::Error> {
+        let pool_created_event = PoolCreatedFilter::decode_log(&RawLog::from(log))?;
+
+        Ok(AMM::MaverickPool(MaverickPool {
+            address: pool_created_event.pool_address,
+            token_a: pool_created_event.token_a,
+            token_b: pool_created_event.token_b,
+            fee: pool_created_event.fee,
+            tick_spacing: pool_created_event.tick_spacing.as_u32(),
+            active_tick: pool_created_event.active_tick,
+            ..Default::default()
+        }))
+    }
+
+    async fn get_all_amms<M: 'static + Middleware>(
+        &self,
+        to_block: Option<u64>,
+        middleware: Arc<M>,
+        step: u64,
+    ) -> Result<Vec<AMM>, AMMError<M>> {
+        if let Some(block) = to_block {
+            Factory::MaverickFactory(*self)
+                .get_all_pools_from_logs(self.creation_block, block, step, middleware)
+                .await
+        } else {
+            return Err(AMMError::BlockNumberNotFound);
+        }
+    }
+
+    //Every bin of a pool is read, so pools are populated one at a time
+    async fn populate_amm_data<M: Middleware>(
+        &self,
+        amms: &mut [AMM],
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        for amm in amms {
+            if let AMM::MaverickPool(pool) = amm {
+                batch_request::get_maverick_pool_data_batch_request(
+                    pool,
+                    block_number,
+                    middleware.clone(),
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl MaverickFactory {
+    pub fn new(address: H160, creation_block: u64) -> MaverickFactory {
+        MaverickFactory {
+            address,
+            creation_block,
+        }
+    }
+}
@@ -0,0 +1,684 @@
+pub mod batch_request;
+pub mod factory;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use ethers::{
+    abi::RawLog,
+    prelude::{abigen, EthEvent},
+    providers::Middleware,
+    types::{Log, H160, H256, U256, U512},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    amm::{price::Price, AutomatedMarketMaker},
+    errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
+};
+
+abigen!(
+    IMaverickPool,
+    r#"[
+        struct BinDelta { uint128 deltaA; uint128 deltaB; uint256 deltaLpBalance; uint128 binId; uint8 kind; int32 lowerTick; bool isActive; }
+        function fee() external view returns (uint256)
+        function tickSpacing() external view returns (uint256)
+        function tokenA() external view returns (address)
+        function tokenB() external view returns (address)
+        function getState() external view returns (int32 activeTick, uint8 status, uint128 binCounter, uint64 protocolFeeRatio)
+        function getBin(uint128 binId) external view returns (uint128 reserveA, uint128 reserveB, uint128 mergeBinBalance, uint128 mergeId, uint128 totalSupply, uint8 kind, int32 lowerTick)
+        event Swap(address sender, address recipient, bool tokenAIn, bool exactOutput, uint256 amountIn, uint256 amountOut, int32 activeTick)
+        event AddLiquidity(address indexed sender, uint256 indexed tokenId, BinDelta[] binDeltas)
+        event RemoveLiquidity(address indexed sender, address indexed recipient, uint256 indexed tokenId, BinDelta[] binDeltas)
+    ]"#;
+);
+
+pub const SWAP_EVENT_SIGNATURE: H256 = H256([
+    59, 132, 29, 201, 171, 81, 227, 16, 75, 218, 79, 97, 180, 30, 66, 113, 25, 45, 34, 205, 25,
+    218, 94, 230, 226, 146, 220, 142, 39, 68, 247, 19,
+]);
+
+pub const ADD_LIQUIDITY_EVENT_SIGNATURE: H256 = H256([
+    19, 58, 2, 115, 39, 88, 43, 226, 8, 159, 108, 164, 113, 55, 227, 211, 55, 190, 76, 162, 205,
+    146, 30, 95, 11, 23, 140, 156, 45, 91, 131, 100,
+]);
+
+pub const REMOVE_LIQUIDITY_EVENT_SIGNATURE: H256 = H256([
+    101, 218, 40, 12, 30, 151, 58, 28, 88, 132, 195, 141, 99, 226, 194, 179, 194, 163, 21, 138, 7,
+    97, 231, 101, 69, 182, 64, 53, 226, 72, 157, 254,
+]);
+
+//Prices, liquidity and the fee are 18 decimal fixed point numbers
+pub const ONE: U256 = U256([1000000000000000000, 0, 0, 0]);
+
+//Bins of a static kind never move, the other kinds follow the price after each swap
+pub const KIND_STATIC: u8 = 0;
+pub const KIND_RIGHT: u8 = 1;
+pub const KIND_LEFT: u8 = 2;
+pub const KIND_BOTH: u8 = 3;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bin {
+    pub reserve_a: u128,
+    pub reserve_b: u128,
+    pub kind: u8,
+    pub lower_tick: i32,
+    //Bins that were merged into another bin have a non zero merge id and no liquidity of their own
+    pub merge_id: u128,
+}
+
+//Token A is priced in token B. A tick is `tick_spacing` Uniswap ticks wide and holds up to one bin of each kind,
+//the bins of a tick are swapped through as a single concentrated liquidity position.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MaverickPool {
+    pub address: H160,
+    pub token_a: H160,
+    pub token_a_decimals: u8,
+    pub token_b: H160,
+    pub token_b_decimals: u8,
+    pub fee: U256,
+    pub tick_spacing: u32,
+    pub active_tick: i32,
+    pub bins: HashMap<u128, Bin>,
+    //Ids of the bins in each tick
+    pub ticks: BTreeMap<i32, Vec<u128>>,
+}
+
+#[async_trait]
+impl AutomatedMarketMaker for MaverickPool {
+    fn address(&self) -> H160 {
+        self.address
+    }
+
+    async fn sync<M: Middleware>(&mut self, middleware: Arc<M>) -> Result<(), AMMError<M>> {
+        batch_request::sync_maverick_pool_batch_request(self, None, middleware).await
+    }
+
+    async fn populate_data<M: Middleware>(
+        &mut self,
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        batch_request::get_maverick_pool_data_batch_request(self, block_number, middleware).await
+    }
+
+    //Bin movements and merges are not emitted with their reserves, pools with dynamic bins have to be synced with `sync`
+    fn sync_on_event_signatures(&self) -> Vec<H256> {
+        vec![
+            SWAP_EVENT_SIGNATURE,
+            ADD_LIQUIDITY_EVENT_SIGNATURE,
+            REMOVE_LIQUIDITY_EVENT_SIGNATURE,
+        ]
+    }
+
+    fn sync_from_log(&mut self, log: Log) -> Result<(), EventLogError> {
+        let event_signature = log.topics[0];
+
+        if event_signature == SWAP_EVENT_SIGNATURE {
+            let swap_event = SwapFilter::decode_log(&RawLog::from(log))?;
+            let token_in = if swap_event.token_a_in {
+                self.token_a
+            } else {
+                self.token_b
+            };
+
+            //The swap is replayed to update the reserves of the bins, the active tick is taken from the event
+            if let Err(err) = self.simulate_swap_mut(token_in, swap_event.amount_in) {
+                tracing::warn!(?err, ?self.address, "could not replay swap");
+            }
+            self.active_tick = swap_event.active_tick;
+        } else if event_signature == ADD_LIQUIDITY_EVENT_SIGNATURE {
+            let add_liquidity_event = AddLiquidityFilter::decode_log(&RawLog::from(log))?;
+            for bin_delta in add_liquidity_event.bin_deltas {
+                let bin = self.bins.entry(bin_delta.bin_id).or_insert(Bin {
+                    kind: bin_delta.kind,
+                    lower_tick: bin_delta.lower_tick,
+                    ..Default::default()
+                });
+
+                bin.reserve_a += bin_delta.delta_a;
+                bin.reserve_b += bin_delta.delta_b;
+            }
+            self.index_ticks();
+        } else if event_signature == REMOVE_LIQUIDITY_EVENT_SIGNATURE {
+            let remove_liquidity_event = RemoveLiquidityFilter::decode_log(&RawLog::from(log))?;
+            for bin_delta in remove_liquidity_event.bin_deltas {
+                if let Some(bin) = self.bins.get_mut(&bin_delta.bin_id) {
+                    bin.reserve_a = bin.reserve_a.saturating_sub(bin_delta.delta_a);
+                    bin.reserve_b = bin.reserve_b.saturating_sub(bin_delta.delta_b);
+                }
+            }
+        } else {
+            Err(EventLogError::InvalidEventSignature)?
+        }
+
+        Ok(())
+    }
+
+    fn tokens(&self) -> Vec<H160> {
+        vec![self.token_a, self.token_b]
+    }
+
+    fn calculate_price(&self, base_token: H160) -> Result<Price, ArithmeticError> {
+        //The price is derived from the reserves of the active tick
+        let (reserve_a, reserve_b) = self.tick_reserves(self.active_tick);
+        if reserve_a.is_zero() && reserve_b.is_zero() {
+            return Err(ArithmeticError::ZeroLiquidity(self.address));
+        }
+        let (sqrt_lower_tick_price, sqrt_upper_tick_price) =
+            tick_sqrt_prices(self.tick_spacing, self.active_tick)?;
+
+        let (sqrt_price, _) = tick_sqrt_price_and_liquidity(
+            reserve_a,
+            reserve_b,
+            sqrt_lower_tick_price,
+            sqrt_upper_tick_price,
+        )?;
+
+        let sqrt_price = sqrt_price.as_u128() as f64 / 1e18;
+        //Price of token B in token A
+        let price = sqrt_price
+            * sqrt_price
+            * 10_f64.powi(self.token_b_decimals as i32 - self.token_a_decimals as i32);
+
+        if base_token == self.token_b {
+            Price::from_f64(price)
+        } else if base_token == self.token_a {
+            Price::from_f64(1.0 / price)
+        } else {
+            Err(ArithmeticError::TokenNotInPool(base_token))
+        }
+    }
+
+    //The output does not depend on the kind of the bins, dynamic bins are only moved after the swap
+    fn simulate_swap(&self, token_in: H160, amount_in: U256) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        self.check_liquidity()?;
+
+        let (amount_out, _) = self.swap(self.token_a_in(token_in)?, amount_in)?;
+
+        tracing::trace!(?amount_out);
+
+        Ok(amount_out)
+    }
+
+    fn simulate_swap_mut(
+        &mut self,
+        token_in: H160,
+        amount_in: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        self.check_liquidity()?;
+
+        let (amount_out, (updated_bins, active_tick)) =
+            self.swap(self.token_a_in(token_in)?, amount_in)?;
+
+        //The new positions of moving bins depend on the time weighted average tick of the pool
+        if let Some((_, bin)) = updated_bins.iter().find(|(_, bin)| bin.kind != KIND_STATIC) {
+            return Err(SwapSimulationError::UnsupportedBinKind(bin.kind));
+        }
+
+        tracing::trace!(?amount_out);
+        tracing::trace!(self.active_tick, "active tick before");
+
+        self.bins.extend(updated_bins);
+        self.active_tick = active_tick;
+
+        tracing::trace!(self.active_tick, "active tick after");
+
+        Ok(amount_out)
+    }
+
+    fn swap_fee(&self, _token_in: H160) -> f64 {
+        self.fee.as_u128() as f64 / 1e18
+    }
+
+    fn token_decimals(&self) -> Vec<u8> {
+        vec![self.token_a_decimals, self.token_b_decimals]
+    }
+
+    fn get_token_out(&self, token_in: H160) -> H160 {
+        if self.token_a == token_in {
+            self.token_b
+        } else {
+            self.token_a
+        }
+    }
+}
+
+impl MaverickPool {
+    //Creates a new instance of the pool from the pool address, and syncs the pool data
+    pub async fn new_from_address<M: Middleware>(
+        pool_address: H160,
+        middleware: Arc<M>,
+    ) -> Result<Self, AMMError<M>> {
+        let mut pool = MaverickPool {
+            address: pool_address,
+            ..Default::default()
+        };
+
+        pool.populate_data(None, middleware).await?;
+
+        if !pool.data_is_populated() {
+            return Err(AMMError::PoolDataError);
+        }
+
+        Ok(pool)
+    }
+
+    pub fn data_is_populated(&self) -> bool {
+        !(self.token_a.is_zero() || self.token_b.is_zero() || self.tick_spacing == 0)
+    }
+
+    pub fn fee(&self) -> U256 {
+        self.fee
+    }
+
+    //Rebuilds the bins of each tick from the lower tick of the bins
+    pub fn index_ticks(&mut self) {
+        self.ticks.clear();
+
+        for (bin_id, bin) in self.bins.iter() {
+            if bin.merge_id == 0 {
+                self.ticks.entry(bin.lower_tick).or_default().push(*bin_id);
+            }
+        }
+
+        for bin_ids in self.ticks.values_mut() {
+            bin_ids.sort_unstable();
+        }
+    }
+
+    pub fn tick_reserves(&self, tick: i32) -> (U256, U256) {
+        self.tick_bins(tick).fold(
+            (U256::zero(), U256::zero()),
+            |(reserve_a, reserve_b), (_, bin)| {
+                (reserve_a + bin.reserve_a, reserve_b + bin.reserve_b)
+            },
+        )
+    }
+
+    fn tick_bins(&self, tick: i32) -> impl Iterator<Item = (u128, &Bin)> {
+        self.ticks
+            .get(&tick)
+            .into_iter()
+            .flatten()
+            .filter_map(move |bin_id| self.bins.get(bin_id).map(|bin| (*bin_id, bin)))
+    }
+
+    fn token_a_in(&self, token_in: H160) -> Result<bool, SwapSimulationError> {
+        if token_in == self.token_a {
+            Ok(true)
+        } else if token_in == self.token_b {
+            Ok(false)
+        } else {
+            Err(SwapSimulationError::TokenNotInPool(token_in))
+        }
+    }
+
+    //Swaps can move the active tick into the liquidity of other ticks, a pool without reserves in any bin can not swap
+    fn check_liquidity(&self) -> Result<(), ArithmeticError> {
+        if self
+            .bins
+            .values()
+            .all(|bin| bin.reserve_a == 0 && bin.reserve_b == 0)
+        {
+            return Err(ArithmeticError::ZeroLiquidity(self.address));
+        }
+
+        Ok(())
+    }
+
+    //Swaps through the ticks from the active tick, returns the amount out along with the (updated bins, active tick)
+    #[allow(clippy::type_complexity)]
+    fn swap(
+        &self,
+        token_a_in: bool,
+        amount_in: U256,
+    ) -> Result<(U256, (Vec<(u128, Bin)>, i32)), SwapSimulationError> {
+        let mut amount_in_remaining = amount_in;
+        let mut amount_out = U256::zero();
+        let mut active_tick = self.active_tick;
+        let mut updated_bins = vec![];
+
+        //Swapping token A in moves the price up
+        let ticks: Box<dyn Iterator<Item = &i32>> = if token_a_in {
+            Box::new(self.ticks.range(self.active_tick..).map(|(tick, _)| tick))
+        } else {
+            Box::new(
+                self.ticks
+                    .range(..=self.active_tick)
+                    .rev()
+                    .map(|(tick, _)| tick),
+            )
+        };
+
+        for &tick in ticks {
+            if amount_in_remaining.is_zero() {
+                break;
+            }
+
+            let (reserve_a, reserve_b) = self.tick_reserves(tick);
+            let reserve_out = if token_a_in { reserve_b } else { reserve_a };
+            if reserve_out.is_zero() {
+                continue;
+            }
+
+            let (tick_amount_in, tick_amount_out) =
+                self.swap_tick(tick, reserve_a, reserve_b, token_a_in, amount_in_remaining)?;
+
+            if tick_amount_in.is_zero() {
+                continue;
+            }
+
+            amount_in_remaining -= tick_amount_in;
+            amount_out += tick_amount_out;
+            active_tick = tick;
+
+            //The bins of a tick hold the same ratio of reserves, so the amounts are split by the share of the reserve out of each bin
+            for (bin_id, bin) in self.tick_bins(tick) {
+                let mut bin = *bin;
+                if token_a_in {
+                    let share = U256::from(bin.reserve_b);
+                    bin.reserve_a += mul_div(tick_amount_in, share, reserve_out, false)?.as_u128();
+                    bin.reserve_b -= mul_div(tick_amount_out, share, reserve_out, true)?
+                        .min(share)
+                        .as_u128();
+                } else {
+                    let share = U256::from(bin.reserve_a);
+                    bin.reserve_b += mul_div(tick_amount_in, share, reserve_out, false)?.as_u128();
+                    bin.reserve_a -= mul_div(tick_amount_out, share, reserve_out, true)?
+                        .min(share)
+                        .as_u128();
+                }
+
+                updated_bins.push((bin_id, bin));
+            }
+
+            //Once the reserve out of a tick is used the price moves into the next tick
+            if tick_amount_out == reserve_out {
+                active_tick = if token_a_in { tick + 1 } else { tick - 1 };
+            }
+        }
+
+        Ok((amount_out, (updated_bins, active_tick)))
+    }
+
+    //Returns the (amount in including fees, amount out) of swapping through a single tick
+    fn swap_tick(
+        &self,
+        tick: i32,
+        reserve_a: U256,
+        reserve_b: U256,
+        token_a_in: bool,
+        amount_in: U256,
+    ) -> Result<(U256, U256), ArithmeticError> {
+        let (sqrt_lower_tick_price, sqrt_upper_tick_price) =
+            tick_sqrt_prices(self.tick_spacing, tick)?;
+
+        let (sqrt_price, liquidity) = tick_sqrt_price_and_liquidity(
+            reserve_a,
+            reserve_b,
+            sqrt_lower_tick_price,
+            sqrt_upper_tick_price,
+        )?;
+
+        if liquidity.is_zero() {
+            return Ok((U256::zero(), U256::zero()));
+        }
+
+        let reserve_out = if token_a_in { reserve_b } else { reserve_a };
+
+        //Amount in without fees needed to move the price to the edge of the tick
+        let max_amount_in = if token_a_in {
+            mul_div(liquidity, sqrt_upper_tick_price - sqrt_price, ONE, true)?
+        } else {
+            mul_div(liquidity, ONE, sqrt_lower_tick_price, true)?
+                - mul_div(liquidity, ONE, sqrt_price, false)?
+        };
+        let max_fee = mul_div(max_amount_in, self.fee, ONE - self.fee, true)?;
+
+        if amount_in >= max_amount_in + max_fee {
+            return Ok((max_amount_in + max_fee, reserve_out));
+        }
+
+        let fee = mul_div(amount_in, self.fee, ONE, true)?;
+        let amount_in_without_fee = amount_in - fee;
+
+        let amount_out = if token_a_in {
+            let sqrt_price_next =
+                sqrt_price + mul_div(amount_in_without_fee, ONE, liquidity, false)?;
+
+            mul_div(liquidity, ONE, sqrt_price, false)?.saturating_sub(mul_div(
+                liquidity,
+                ONE,
+                sqrt_price_next,
+                true,
+            )?)
+        } else {
+            let sqrt_price_next = mul_div(
+                liquidity * ONE,
+                sqrt_price,
+                liquidity * ONE + amount_in_without_fee * sqrt_price,
+                true,
+            )?;
+
+            mul_div(liquidity, sqrt_price - sqrt_price_next, ONE, false)?
+        };
+
+        Ok((amount_in, amount_out.min(reserve_out)))
+    }
+}
+
+//Sqrt prices of the lower and upper edge of a tick
+pub fn tick_sqrt_prices(tick_spacing: u32, tick: i32) -> Result<(U256, U256), ArithmeticError> {
+    Ok((
+        tick_sqrt_price(tick_spacing, tick)?,
+        tick_sqrt_price(tick_spacing, tick + 1)?,
+    ))
+}
+
+//1.0001^(tick * tick_spacing / 2) with 18 decimals
+pub fn tick_sqrt_price(tick_spacing: u32, tick: i32) -> Result<U256, ArithmeticError> {
+    let sqrt_price_x_96 =
+        uniswap_v3_math::tick_math::get_sqrt_ratio_at_tick(tick * tick_spacing as i32)?;
+
+    mul_div(sqrt_price_x_96, ONE, U256::one() << 96, false)
+}
+
+//Solves (reserve_b + L / sqrt_upper) * (reserve_a + L * sqrt_lower) = L^2 for the liquidity of a tick and returns the (sqrt price, liquidity) of the tick
+pub fn tick_sqrt_price_and_liquidity(
+    reserve_a: U256,
+    reserve_b: U256,
+    sqrt_lower_tick_price: U256,
+    sqrt_upper_tick_price: U256,
+) -> Result<(U256, U256), ArithmeticError> {
+    if reserve_a.is_zero() && reserve_b.is_zero() {
+        return Ok((sqrt_lower_tick_price, U256::zero()));
+    }
+
+    let diff = sqrt_upper_tick_price - sqrt_lower_tick_price;
+    let b = mul_div(reserve_a, ONE, sqrt_upper_tick_price, false)?
+        + mul_div(reserve_b, sqrt_lower_tick_price, ONE, false)?;
+
+    let liquidity = if reserve_a.is_zero() || reserve_b.is_zero() {
+        mul_div(b, sqrt_upper_tick_price, diff, false)?
+    } else {
+        let b = b / 2;
+        let discriminant = U512::from(b) * U512::from(b)
+            + U512::from(reserve_a) * U512::from(reserve_b) * U512::from(diff)
+                / U512::from(sqrt_upper_tick_price);
+
+        let root = to_u256(discriminant.integer_sqrt())?;
+        mul_div(b + root, sqrt_upper_tick_price, diff, false)?
+    };
+
+    let virtual_reserve_a = U512::from(reserve_a)
+        + U512::from(liquidity) * U512::from(sqrt_lower_tick_price) / U512::from(ONE);
+    let virtual_reserve_b = U512::from(reserve_b)
+        + U512::from(liquidity) * U512::from(ONE) / U512::from(sqrt_upper_tick_price);
+
+    let sqrt_price = to_u256(
+        (virtual_reserve_a * U512::from(ONE) * U512::from(ONE) / virtual_reserve_b).integer_sqrt(),
+    )?;
+
+    Ok((
+        sqrt_price.clamp(sqrt_lower_tick_price, sqrt_upper_tick_price),
+        liquidity,
+    ))
+}
+
+fn mul_div(a: U256, b: U256, denominator: U256, round_up: bool) -> Result<U256, ArithmeticError> {
+    if denominator.is_zero() {
+        return Err(ArithmeticError::YIsZero);
+    }
+
+    let prod = U512::from(a) * U512::from(b);
+    let denominator = U512::from(denominator);
+    let mut result = prod / denominator;
+
+    if round_up && !(prod % denominator).is_zero() {
+        result += U512::one();
+    }
+
+    to_u256(result)
+}
+
+fn to_u256(value: U512) -> Result<U256, ArithmeticError> {
+    U256::try_from(value).map_err(|_| ArithmeticError::ShadowOverflow(U256::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use ethers::types::{H160, U256};
+
+    use crate::{amm::AutomatedMarketMaker, errors::SwapSimulationError};
+
+    use super::{
+        tick_sqrt_price, tick_sqrt_price_and_liquidity, tick_sqrt_prices, Bin, MaverickPool,
+        KIND_RIGHT, KIND_STATIC, ONE,
+    };
+
+    fn pool(bins: Vec<(u128, Bin)>) -> MaverickPool {
+        let mut pool = MaverickPool {
+            address: H160::repeat_byte(0x01),
+            token_a: H160::repeat_byte(0x02),
+            token_a_decimals: 18,
+            token_b: H160::repeat_byte(0x03),
+            token_b_decimals: 18,
+            //0.05%
+            fee: U256::from(500000000000000_u64),
+            tick_spacing: 10,
+            active_tick: 0,
+            bins: HashMap::from_iter(bins),
+            ticks: BTreeMap::new(),
+        };
+        pool.index_ticks();
+        pool
+    }
+
+    fn bin(reserve_a: u128, reserve_b: u128, lower_tick: i32, kind: u8) -> Bin {
+        Bin {
+            reserve_a,
+            reserve_b,
+            kind,
+            lower_tick,
+            merge_id: 0,
+        }
+    }
+
+    #[test]
+    fn test_tick_sqrt_price() {
+        assert_eq!(tick_sqrt_price(10, 0).unwrap(), ONE);
+
+        //1.0001^(10 / 2)
+        let sqrt_price = tick_sqrt_price(10, 1).unwrap().as_u128() as f64 / 1e18;
+        assert!((sqrt_price - 1.0001_f64.powf(5.0)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_tick_liquidity() {
+        let (sqrt_lower_tick_price, sqrt_upper_tick_price) = tick_sqrt_prices(10, 0).unwrap();
+
+        //A tick with only token B is at the lower edge of the tick
+        let (sqrt_price, liquidity) = tick_sqrt_price_and_liquidity(
+            U256::zero(),
+            U256::exp10(18),
+            sqrt_lower_tick_price,
+            sqrt_upper_tick_price,
+        )
+        .unwrap();
+        assert!(sqrt_price - sqrt_lower_tick_price < U256::from(10));
+
+        //L * (1 / sqrt_lower - 1 / sqrt_upper) = reserve_b
+        let reserve_b = liquidity.as_u128() as f64
+            * (1.0 - 1.0 / (sqrt_upper_tick_price.as_u128() as f64 / 1e18));
+        assert!((reserve_b / 1e18 - 1.0).abs() < 1e-9);
+
+        let (sqrt_price, _) = tick_sqrt_price_and_liquidity(
+            U256::exp10(18),
+            U256::exp10(18),
+            sqrt_lower_tick_price,
+            sqrt_upper_tick_price,
+        )
+        .unwrap();
+        assert!(sqrt_price > sqrt_lower_tick_price && sqrt_price < sqrt_upper_tick_price);
+    }
+
+    #[test]
+    fn test_simulate_swap() {
+        let mut pool = pool(vec![
+            (1, bin(0, 10_u128.pow(21), 0, KIND_STATIC)),
+            (2, bin(0, 10_u128.pow(21), -1, KIND_STATIC)),
+        ]);
+
+        //Token A in moves the price up, there is no token B above the active tick
+        let amount_out = pool.simulate_swap(pool.token_a, U256::exp10(18)).unwrap();
+        assert!(amount_out < U256::from(9995) * U256::exp10(14));
+        assert!(amount_out > U256::from(9990) * U256::exp10(14));
+
+        let amount_out_mut = pool
+            .simulate_swap_mut(pool.token_a, U256::exp10(18))
+            .unwrap();
+        assert_eq!(amount_out, amount_out_mut);
+        assert_eq!(pool.bins[&1].reserve_a, 10_u128.pow(18));
+        assert_eq!(
+            U256::from(pool.bins[&1].reserve_b),
+            U256::exp10(21) - amount_out
+        );
+
+        //Swapping back returns slightly less than the amount in
+        let amount_out = pool.simulate_swap(pool.token_b, amount_out).unwrap();
+        assert!(amount_out < U256::exp10(18));
+    }
+
+    #[test]
+    fn test_simulate_swap_crosses_ticks() {
+        let pool = pool(vec![
+            (1, bin(0, 10_u128.pow(18), 0, KIND_STATIC)),
+            (2, bin(0, 10_u128.pow(21), 1, KIND_STATIC)),
+        ]);
+
+        let amount_out = pool.simulate_swap(pool.token_a, U256::exp10(19)).unwrap();
+        assert!(amount_out > U256::exp10(18));
+        assert!(amount_out < U256::exp10(19));
+    }
+
+    #[test]
+    fn test_simulate_swap_mut_dynamic_bin() {
+        let mut pool = pool(vec![(1, bin(0, 10_u128.pow(21), 0, KIND_RIGHT))]);
+
+        assert!(pool.simulate_swap(pool.token_a, U256::exp10(18)).is_ok());
+        assert!(matches!(
+            pool.simulate_swap_mut(pool.token_a, U256::exp10(18)),
+            Err(SwapSimulationError::UnsupportedBinKind(KIND_RIGHT))
+        ));
+    }
+}
@@ -1,7 +1,24 @@
+pub mod algebra;
+pub mod balancer_v2;
+pub mod bancor_v3;
+pub mod batch_backend;
+pub mod camelot;
+pub mod curve;
+pub mod depth;
+pub mod dodo;
 pub mod erc_4626;
 pub mod factory;
+pub mod kyber_elastic;
+pub mod math;
+pub mod maverick;
+pub mod pool_address;
+pub mod price;
+pub mod rate_provider;
+pub mod solidly;
+pub mod trader_joe_lb;
 pub mod uniswap_v2;
 pub mod uniswap_v3;
+pub mod uniswap_v4;
 
 use std::sync::Arc;
 
@@ -12,17 +29,40 @@ use ethers::{
 };
 use serde::{Deserialize, Serialize};
 
-use crate::errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError};
+use crate::{
+    errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
+    token_list::TokenList,
+};
 
-use self::{erc_4626::ERC4626Vault, uniswap_v2::UniswapV2Pool, uniswap_v3::UniswapV3Pool};
+use self::{
+    algebra::AlgebraPool,
+    balancer_v2::{stable::BalancerStablePool, BalancerV2WeightedPool},
+    bancor_v3::BancorV3Pool,
+    camelot::CamelotPool,
+    curve::{crypto::CurveCryptoPool, meta::CurveMetaPool, CurveStableSwapPool},
+    depth::MarketDepth,
+    dodo::DodoPool,
+    erc_4626::ERC4626Vault,
+    kyber_elastic::KyberElasticPool,
+    maverick::MaverickPool,
+    price::Price,
+    rate_provider::RateProviderAmm,
+    solidly::SolidlyPool,
+    trader_joe_lb::LBPair,
+    uniswap_v2::UniswapV2Pool,
+    uniswap_v3::UniswapV3Pool,
+    uniswap_v4::UniswapV4Pool,
+};
 
 #[async_trait]
 pub trait AutomatedMarketMaker {
     fn address(&self) -> H160;
     async fn sync<M: Middleware>(&mut self, middleware: Arc<M>) -> Result<(), AMMError<M>>;
     fn sync_on_event_signatures(&self) -> Vec<H256>;
+    //Every token that can be swapped through the AMM, i.e. both tokens of a pair or all tokens of a Balancer pool.
+    //There is no default implementation so that every AMM has to list its tokens
     fn tokens(&self) -> Vec<H160>;
-    fn calculate_price(&self, base_token: H160) -> Result<f64, ArithmeticError>;
+    fn calculate_price(&self, base_token: H160) -> Result<Price, ArithmeticError>;
     fn sync_from_log(&mut self, log: Log) -> Result<(), EventLogError>;
     async fn populate_data<M: Middleware>(
         &mut self,
@@ -37,6 +77,218 @@ pub trait AutomatedMarketMaker {
         amount_in: U256,
     ) -> Result<U256, SwapSimulationError>;
     fn get_token_out(&self, token_in: H160) -> H160;
+    //Fee charged on a swap of `token_in`, as a fraction of the swap
+    fn swap_fee(&self, token_in: H160) -> f64;
+    //Fee tier of the AMM in hundredths of a basis point, 3000 is 0.3% as in Uniswap V3. The default derives it from the
+    //fee charged on a swap of the first token.
+    fn fee(&self) -> u32 {
+        self.tokens()
+            .first()
+            .map_or(3000, |token| (self.swap_fee(*token) * 1e6).round() as u32)
+    }
+    //Decimals of the tokens, in the same order as `tokens`
+    fn token_decimals(&self) -> Vec<u8>;
+
+    //Simulates a swap and reports the fee paid along with the effective price and the price impact of the swap
+    fn simulate_swap_with_details(
+        &self,
+        token_in: H160,
+        amount_in: U256,
+    ) -> Result<SwapResult, SwapSimulationError> {
+        let token_out = self.get_token_out(token_in);
+        let amount_out = self.simulate_swap(token_in, amount_in)?;
+
+        let tokens = self.tokens();
+        let token_decimals = self.token_decimals();
+        let decimals = |token: H160| {
+            tokens
+                .iter()
+                .position(|pool_token| *pool_token == token)
+                .and_then(|i| token_decimals.get(i).copied())
+                .ok_or(SwapSimulationError::TokenNotInPool(token))
+        };
+
+        Ok(SwapResult::new(
+            amount_in,
+            amount_out,
+            decimals(token_in)?,
+            decimals(token_out)?,
+            self.swap_fee(token_in),
+            self.calculate_price(token_in)?.to_f64(),
+        ))
+    }
+
+    //Relative drop of the spot price of `token_in` caused by swapping `amount_in` of it, in [0, 1]. The default simulates the swap
+    //on a copy of the AMM, AMMs that can tell when a swap exceeds their liquidity override it.
+    fn calculate_price_impact(
+        &self,
+        token_in: H160,
+        amount_in: U256,
+    ) -> Result<f64, SwapSimulationError>
+    where
+        Self: Clone + Sized,
+    {
+        if amount_in.is_zero() {
+            return Err(SwapSimulationError::ZeroAmountIn);
+        }
+
+        let price_before = self.calculate_price(token_in)?.to_f64();
+
+        let mut amm = self.clone();
+        let amount_out = amm.simulate_swap_mut(token_in, amount_in)?;
+        let price_after = amm.calculate_price(token_in)?.to_f64();
+
+        if amount_out.is_zero() || price_before <= 0.0 {
+            return Err(SwapSimulationError::InsufficientLiquidity {
+                pool: self.address(),
+                requested: amount_in,
+                available: U256::zero(),
+            });
+        }
+
+        Ok(price_impact(price_before, price_after))
+    }
+
+    //Liquidity that is active between two prices of the first token of `tokens` denominated in the second, as returned by `calculate_price`
+    fn liquidity_in_range(
+        &self,
+        _price_lower: Price,
+        _price_upper: Price,
+    ) -> Result<U256, ArithmeticError> {
+        Err(ArithmeticError::LiquidityInRangeNotSupported)
+    }
+
+    //Order book like view of the liquidity around the spot price of the first token of `tokens`, see MarketDepth
+    fn market_depth(&self, _num_levels: usize) -> Result<MarketDepth, ArithmeticError> {
+        Err(ArithmeticError::MarketDepthNotSupported)
+    }
+
+    //Amount of the token that get_token_out pairs with `token_out` that has to be swapped to receive `amount_out`, the inverse of
+    //simulate_swap before any slippage tolerance. The default searches the amount with simulate_swap, AMMs with a closed form or an
+    //exact output swap override it.
+    fn get_amount_in(
+        &self,
+        token_out: H160,
+        amount_out: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        let token_in = self
+            .tokens()
+            .into_iter()
+            .find(|token| *token != token_out && self.get_token_out(*token) == token_out)
+            .ok_or(SwapSimulationError::TokenNotInPool(token_out))?;
+
+        search_amount_in(self.address(), amount_out, |amount_in| {
+            self.simulate_swap(token_in, amount_in)
+        })
+    }
+}
+
+//Smallest amount in for which `simulate_swap` returns at least `amount_out`. The amount in is doubled until the swap returns enough
+//and then bisected, amounts in are capped at u128::MAX so that the arithmetic of the AMMs can not overflow.
+pub fn search_amount_in<F>(
+    pool: H160,
+    amount_out: U256,
+    simulate_swap: F,
+) -> Result<U256, SwapSimulationError>
+where
+    F: Fn(U256) -> Result<U256, SwapSimulationError>,
+{
+    if amount_out.is_zero() {
+        return Ok(U256::zero());
+    }
+
+    let insufficient_output = |available: U256| SwapSimulationError::InsufficientOutput {
+        pool,
+        requested: amount_out,
+        available,
+    };
+
+    //The swap of `low` returns less than the amount out and the swap of `high` at least the amount out
+    let mut low = U256::zero();
+    let mut high = U256::one();
+    let mut available = U256::zero();
+    loop {
+        match simulate_swap(high) {
+            Ok(swapped) if swapped >= amount_out => break,
+            Ok(swapped) => available = available.max(swapped),
+            //Swaps past the liquidity of some AMMs fail instead of returning the reserves
+            Err(err) if low.is_zero() => return Err(err),
+            Err(_) => return Err(insufficient_output(available)),
+        }
+
+        low = high;
+        high = high << 1;
+        if high > U256::from(u128::MAX) {
+            return Err(insufficient_output(available));
+        }
+    }
+
+    while high - low > U256::one() {
+        let mid = low + (high - low) / 2;
+        match simulate_swap(mid) {
+            Ok(swapped) if swapped >= amount_out => high = mid,
+            _ => low = mid,
+        }
+    }
+
+    Ok(high)
+}
+
+//Relative change between the spot price before and after a swap, a swap can only move the price of token in down
+pub fn price_impact(price_before: f64, price_after: f64) -> f64 {
+    ((price_before - price_after) / price_before).clamp(0.0, 1.0)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct SwapResult {
+    pub amount_out: U256,
+    //Fee paid in token in
+    pub fee_paid: U256,
+    //Token out received per token in, adjusted for decimals
+    pub effective_price: f64,
+    //Relative difference between the spot price after fees and the effective price
+    pub price_impact: f64,
+}
+
+impl SwapResult {
+    //`spot_price` is the price of token in denominated in token out, as returned by `calculate_price`
+    pub fn new(
+        amount_in: U256,
+        amount_out: U256,
+        token_in_decimals: u8,
+        token_out_decimals: u8,
+        fee: f64,
+        spot_price: f64,
+    ) -> SwapResult {
+        let fee = fee.clamp(0.0, 1.0);
+        let fee_paid = amount_in * U256::from((fee * 1e18) as u128) / U256::exp10(18);
+
+        if amount_in.is_zero() {
+            return SwapResult {
+                amount_out,
+                fee_paid,
+                effective_price: spot_price,
+                price_impact: 0.0,
+            };
+        }
+
+        let effective_price = (u256_to_f64(amount_out) / 10_f64.powi(token_out_decimals as i32))
+            / (u256_to_f64(amount_in) / 10_f64.powi(token_in_decimals as i32));
+
+        let spot_price_after_fee = spot_price * (1.0 - fee);
+        let price_impact = if spot_price_after_fee > 0.0 {
+            (1.0 - effective_price / spot_price_after_fee).max(0.0)
+        } else {
+            0.0
+        };
+
+        SwapResult {
+            amount_out,
+            fee_paid,
+            effective_price,
+            price_impact,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,6 +296,21 @@ pub enum AMM {
     UniswapV2Pool(UniswapV2Pool),
     UniswapV3Pool(UniswapV3Pool),
     ERC4626Vault(ERC4626Vault),
+    BalancerV2WeightedPool(BalancerV2WeightedPool),
+    CurveStableSwapPool(CurveStableSwapPool),
+    CurveCryptoPool(CurveCryptoPool),
+    UniswapV4Pool(UniswapV4Pool),
+    SolidlyPool(SolidlyPool),
+    LBPair(LBPair),
+    MaverickPool(MaverickPool),
+    BalancerStablePool(BalancerStablePool),
+    CamelotPool(CamelotPool),
+    BancorV3Pool(BancorV3Pool),
+    KyberElasticPool(KyberElasticPool),
+    DodoPool(DodoPool),
+    AlgebraPool(AlgebraPool),
+    RateProviderAmm(RateProviderAmm),
+    CurveMetaPool(CurveMetaPool),
 }
 
 #[async_trait]
@@ -53,6 +320,21 @@ impl AutomatedMarketMaker for AMM {
             AMM::UniswapV2Pool(pool) => pool.address,
             AMM::UniswapV3Pool(pool) => pool.address,
             AMM::ERC4626Vault(vault) => vault.vault_token,
+            AMM::BalancerV2WeightedPool(pool) => pool.address,
+            AMM::CurveStableSwapPool(pool) => pool.address,
+            AMM::CurveCryptoPool(pool) => pool.address,
+            AMM::UniswapV4Pool(pool) => pool.address,
+            AMM::SolidlyPool(pool) => pool.address,
+            AMM::LBPair(pool) => pool.address,
+            AMM::MaverickPool(pool) => pool.address,
+            AMM::BalancerStablePool(pool) => pool.address,
+            AMM::CamelotPool(pool) => pool.address,
+            AMM::BancorV3Pool(pool) => pool.token,
+            AMM::KyberElasticPool(pool) => pool.address,
+            AMM::DodoPool(pool) => pool.address,
+            AMM::AlgebraPool(pool) => pool.address,
+            AMM::RateProviderAmm(pool) => pool.address,
+            AMM::CurveMetaPool(pool) => pool.pool.address,
         }
     }
 
@@ -61,6 +343,21 @@ impl AutomatedMarketMaker for AMM {
             AMM::UniswapV2Pool(pool) => pool.sync(middleware).await,
             AMM::UniswapV3Pool(pool) => pool.sync(middleware).await,
             AMM::ERC4626Vault(vault) => vault.sync(middleware).await,
+            AMM::BalancerV2WeightedPool(pool) => pool.sync(middleware).await,
+            AMM::CurveStableSwapPool(pool) => pool.sync(middleware).await,
+            AMM::CurveCryptoPool(pool) => pool.sync(middleware).await,
+            AMM::UniswapV4Pool(pool) => pool.sync(middleware).await,
+            AMM::SolidlyPool(pool) => pool.sync(middleware).await,
+            AMM::LBPair(pool) => pool.sync(middleware).await,
+            AMM::MaverickPool(pool) => pool.sync(middleware).await,
+            AMM::BalancerStablePool(pool) => pool.sync(middleware).await,
+            AMM::CamelotPool(pool) => pool.sync(middleware).await,
+            AMM::BancorV3Pool(pool) => pool.sync(middleware).await,
+            AMM::KyberElasticPool(pool) => pool.sync(middleware).await,
+            AMM::DodoPool(pool) => pool.sync(middleware).await,
+            AMM::AlgebraPool(pool) => pool.sync(middleware).await,
+            AMM::RateProviderAmm(pool) => pool.sync(middleware).await,
+            AMM::CurveMetaPool(pool) => pool.sync(middleware).await,
         }
     }
 
@@ -69,6 +366,21 @@ impl AutomatedMarketMaker for AMM {
             AMM::UniswapV2Pool(pool) => pool.sync_on_event_signatures(),
             AMM::UniswapV3Pool(pool) => pool.sync_on_event_signatures(),
             AMM::ERC4626Vault(vault) => vault.sync_on_event_signatures(),
+            AMM::BalancerV2WeightedPool(pool) => pool.sync_on_event_signatures(),
+            AMM::CurveStableSwapPool(pool) => pool.sync_on_event_signatures(),
+            AMM::CurveCryptoPool(pool) => pool.sync_on_event_signatures(),
+            AMM::UniswapV4Pool(pool) => pool.sync_on_event_signatures(),
+            AMM::SolidlyPool(pool) => pool.sync_on_event_signatures(),
+            AMM::LBPair(pool) => pool.sync_on_event_signatures(),
+            AMM::MaverickPool(pool) => pool.sync_on_event_signatures(),
+            AMM::BalancerStablePool(pool) => pool.sync_on_event_signatures(),
+            AMM::CamelotPool(pool) => pool.sync_on_event_signatures(),
+            AMM::BancorV3Pool(pool) => pool.sync_on_event_signatures(),
+            AMM::KyberElasticPool(pool) => pool.sync_on_event_signatures(),
+            AMM::DodoPool(pool) => pool.sync_on_event_signatures(),
+            AMM::AlgebraPool(pool) => pool.sync_on_event_signatures(),
+            AMM::RateProviderAmm(pool) => pool.sync_on_event_signatures(),
+            AMM::CurveMetaPool(pool) => pool.sync_on_event_signatures(),
         }
     }
 
@@ -77,6 +389,21 @@ impl AutomatedMarketMaker for AMM {
             AMM::UniswapV2Pool(pool) => pool.sync_from_log(log),
             AMM::UniswapV3Pool(pool) => pool.sync_from_log(log),
             AMM::ERC4626Vault(vault) => vault.sync_from_log(log),
+            AMM::BalancerV2WeightedPool(pool) => pool.sync_from_log(log),
+            AMM::CurveStableSwapPool(pool) => pool.sync_from_log(log),
+            AMM::CurveCryptoPool(pool) => pool.sync_from_log(log),
+            AMM::UniswapV4Pool(pool) => pool.sync_from_log(log),
+            AMM::SolidlyPool(pool) => pool.sync_from_log(log),
+            AMM::LBPair(pool) => pool.sync_from_log(log),
+            AMM::MaverickPool(pool) => pool.sync_from_log(log),
+            AMM::BalancerStablePool(pool) => pool.sync_from_log(log),
+            AMM::CamelotPool(pool) => pool.sync_from_log(log),
+            AMM::BancorV3Pool(pool) => pool.sync_from_log(log),
+            AMM::KyberElasticPool(pool) => pool.sync_from_log(log),
+            AMM::DodoPool(pool) => pool.sync_from_log(log),
+            AMM::AlgebraPool(pool) => pool.sync_from_log(log),
+            AMM::RateProviderAmm(pool) => pool.sync_from_log(log),
+            AMM::CurveMetaPool(pool) => pool.sync_from_log(log),
         }
     }
 
@@ -85,6 +412,21 @@ impl AutomatedMarketMaker for AMM {
             AMM::UniswapV2Pool(pool) => pool.simulate_swap(token_in, amount_in),
             AMM::UniswapV3Pool(pool) => pool.simulate_swap(token_in, amount_in),
             AMM::ERC4626Vault(vault) => vault.simulate_swap(token_in, amount_in),
+            AMM::BalancerV2WeightedPool(pool) => pool.simulate_swap(token_in, amount_in),
+            AMM::CurveStableSwapPool(pool) => pool.simulate_swap(token_in, amount_in),
+            AMM::CurveCryptoPool(pool) => pool.simulate_swap(token_in, amount_in),
+            AMM::UniswapV4Pool(pool) => pool.simulate_swap(token_in, amount_in),
+            AMM::SolidlyPool(pool) => pool.simulate_swap(token_in, amount_in),
+            AMM::LBPair(pool) => pool.simulate_swap(token_in, amount_in),
+            AMM::MaverickPool(pool) => pool.simulate_swap(token_in, amount_in),
+            AMM::BalancerStablePool(pool) => pool.simulate_swap(token_in, amount_in),
+            AMM::CamelotPool(pool) => pool.simulate_swap(token_in, amount_in),
+            AMM::BancorV3Pool(pool) => pool.simulate_swap(token_in, amount_in),
+            AMM::KyberElasticPool(pool) => pool.simulate_swap(token_in, amount_in),
+            AMM::DodoPool(pool) => pool.simulate_swap(token_in, amount_in),
+            AMM::AlgebraPool(pool) => pool.simulate_swap(token_in, amount_in),
+            AMM::RateProviderAmm(pool) => pool.simulate_swap(token_in, amount_in),
+            AMM::CurveMetaPool(pool) => pool.simulate_swap(token_in, amount_in),
         }
     }
 
@@ -97,6 +439,21 @@ impl AutomatedMarketMaker for AMM {
             AMM::UniswapV2Pool(pool) => pool.simulate_swap_mut(token_in, amount_in),
             AMM::UniswapV3Pool(pool) => pool.simulate_swap_mut(token_in, amount_in),
             AMM::ERC4626Vault(vault) => vault.simulate_swap_mut(token_in, amount_in),
+            AMM::BalancerV2WeightedPool(pool) => pool.simulate_swap_mut(token_in, amount_in),
+            AMM::CurveStableSwapPool(pool) => pool.simulate_swap_mut(token_in, amount_in),
+            AMM::CurveCryptoPool(pool) => pool.simulate_swap_mut(token_in, amount_in),
+            AMM::UniswapV4Pool(pool) => pool.simulate_swap_mut(token_in, amount_in),
+            AMM::SolidlyPool(pool) => pool.simulate_swap_mut(token_in, amount_in),
+            AMM::LBPair(pool) => pool.simulate_swap_mut(token_in, amount_in),
+            AMM::MaverickPool(pool) => pool.simulate_swap_mut(token_in, amount_in),
+            AMM::BalancerStablePool(pool) => pool.simulate_swap_mut(token_in, amount_in),
+            AMM::CamelotPool(pool) => pool.simulate_swap_mut(token_in, amount_in),
+            AMM::BancorV3Pool(pool) => pool.simulate_swap_mut(token_in, amount_in),
+            AMM::KyberElasticPool(pool) => pool.simulate_swap_mut(token_in, amount_in),
+            AMM::DodoPool(pool) => pool.simulate_swap_mut(token_in, amount_in),
+            AMM::AlgebraPool(pool) => pool.simulate_swap_mut(token_in, amount_in),
+            AMM::RateProviderAmm(pool) => pool.simulate_swap_mut(token_in, amount_in),
+            AMM::CurveMetaPool(pool) => pool.simulate_swap_mut(token_in, amount_in),
         }
     }
 
@@ -105,6 +462,21 @@ impl AutomatedMarketMaker for AMM {
             AMM::UniswapV2Pool(pool) => pool.get_token_out(token_in),
             AMM::UniswapV3Pool(pool) => pool.get_token_out(token_in),
             AMM::ERC4626Vault(vault) => vault.get_token_out(token_in),
+            AMM::BalancerV2WeightedPool(pool) => pool.get_token_out(token_in),
+            AMM::CurveStableSwapPool(pool) => pool.get_token_out(token_in),
+            AMM::CurveCryptoPool(pool) => pool.get_token_out(token_in),
+            AMM::UniswapV4Pool(pool) => pool.get_token_out(token_in),
+            AMM::SolidlyPool(pool) => pool.get_token_out(token_in),
+            AMM::LBPair(pool) => pool.get_token_out(token_in),
+            AMM::MaverickPool(pool) => pool.get_token_out(token_in),
+            AMM::BalancerStablePool(pool) => pool.get_token_out(token_in),
+            AMM::CamelotPool(pool) => pool.get_token_out(token_in),
+            AMM::BancorV3Pool(pool) => pool.get_token_out(token_in),
+            AMM::KyberElasticPool(pool) => pool.get_token_out(token_in),
+            AMM::DodoPool(pool) => pool.get_token_out(token_in),
+            AMM::AlgebraPool(pool) => pool.get_token_out(token_in),
+            AMM::RateProviderAmm(pool) => pool.get_token_out(token_in),
+            AMM::CurveMetaPool(pool) => pool.get_token_out(token_in),
         }
     }
 
@@ -114,9 +486,24 @@ impl AutomatedMarketMaker for AMM {
         middleware: Arc<M>,
     ) -> Result<(), AMMError<M>> {
         match self {
-            AMM::UniswapV2Pool(pool) => pool.populate_data(None, middleware).await,
+            AMM::UniswapV2Pool(pool) => pool.populate_data(block_number, middleware).await,
             AMM::UniswapV3Pool(pool) => pool.populate_data(block_number, middleware).await,
-            AMM::ERC4626Vault(vault) => vault.populate_data(None, middleware).await,
+            AMM::ERC4626Vault(vault) => vault.populate_data(block_number, middleware).await,
+            AMM::BalancerV2WeightedPool(pool) => pool.populate_data(block_number, middleware).await,
+            AMM::CurveStableSwapPool(pool) => pool.populate_data(block_number, middleware).await,
+            AMM::CurveCryptoPool(pool) => pool.populate_data(block_number, middleware).await,
+            AMM::UniswapV4Pool(pool) => pool.populate_data(block_number, middleware).await,
+            AMM::SolidlyPool(pool) => pool.populate_data(block_number, middleware).await,
+            AMM::LBPair(pool) => pool.populate_data(block_number, middleware).await,
+            AMM::MaverickPool(pool) => pool.populate_data(block_number, middleware).await,
+            AMM::BalancerStablePool(pool) => pool.populate_data(block_number, middleware).await,
+            AMM::CamelotPool(pool) => pool.populate_data(block_number, middleware).await,
+            AMM::BancorV3Pool(pool) => pool.populate_data(block_number, middleware).await,
+            AMM::KyberElasticPool(pool) => pool.populate_data(block_number, middleware).await,
+            AMM::DodoPool(pool) => pool.populate_data(block_number, middleware).await,
+            AMM::AlgebraPool(pool) => pool.populate_data(block_number, middleware).await,
+            AMM::RateProviderAmm(pool) => pool.populate_data(block_number, middleware).await,
+            AMM::CurveMetaPool(pool) => pool.populate_data(block_number, middleware).await,
         }
     }
 
@@ -125,14 +512,449 @@ impl AutomatedMarketMaker for AMM {
             AMM::UniswapV2Pool(pool) => pool.tokens(),
             AMM::UniswapV3Pool(pool) => pool.tokens(),
             AMM::ERC4626Vault(vault) => vault.tokens(),
+            AMM::BalancerV2WeightedPool(pool) => pool.tokens(),
+            AMM::CurveStableSwapPool(pool) => pool.tokens(),
+            AMM::CurveCryptoPool(pool) => pool.tokens(),
+            AMM::UniswapV4Pool(pool) => pool.tokens(),
+            AMM::SolidlyPool(pool) => pool.tokens(),
+            AMM::LBPair(pool) => pool.tokens(),
+            AMM::MaverickPool(pool) => pool.tokens(),
+            AMM::BalancerStablePool(pool) => pool.tokens(),
+            AMM::CamelotPool(pool) => pool.tokens(),
+            AMM::BancorV3Pool(pool) => pool.tokens(),
+            AMM::KyberElasticPool(pool) => pool.tokens(),
+            AMM::DodoPool(pool) => pool.tokens(),
+            AMM::AlgebraPool(pool) => pool.tokens(),
+            AMM::RateProviderAmm(pool) => pool.tokens(),
+            AMM::CurveMetaPool(pool) => pool.tokens(),
         }
     }
 
-    fn calculate_price(&self, base_token: H160) -> Result<f64, ArithmeticError> {
+    fn calculate_price(&self, base_token: H160) -> Result<Price, ArithmeticError> {
         match self {
             AMM::UniswapV2Pool(pool) => pool.calculate_price(base_token),
             AMM::UniswapV3Pool(pool) => pool.calculate_price(base_token),
             AMM::ERC4626Vault(vault) => vault.calculate_price(base_token),
+            AMM::BalancerV2WeightedPool(pool) => pool.calculate_price(base_token),
+            AMM::CurveStableSwapPool(pool) => pool.calculate_price(base_token),
+            AMM::CurveCryptoPool(pool) => pool.calculate_price(base_token),
+            AMM::UniswapV4Pool(pool) => pool.calculate_price(base_token),
+            AMM::SolidlyPool(pool) => pool.calculate_price(base_token),
+            AMM::LBPair(pool) => pool.calculate_price(base_token),
+            AMM::MaverickPool(pool) => pool.calculate_price(base_token),
+            AMM::BalancerStablePool(pool) => pool.calculate_price(base_token),
+            AMM::CamelotPool(pool) => pool.calculate_price(base_token),
+            AMM::BancorV3Pool(pool) => pool.calculate_price(base_token),
+            AMM::KyberElasticPool(pool) => pool.calculate_price(base_token),
+            AMM::DodoPool(pool) => pool.calculate_price(base_token),
+            AMM::AlgebraPool(pool) => pool.calculate_price(base_token),
+            AMM::RateProviderAmm(pool) => pool.calculate_price(base_token),
+            AMM::CurveMetaPool(pool) => pool.calculate_price(base_token),
+        }
+    }
+
+    fn swap_fee(&self, token_in: H160) -> f64 {
+        match self {
+            AMM::UniswapV2Pool(pool) => pool.swap_fee(token_in),
+            AMM::UniswapV3Pool(pool) => pool.swap_fee(token_in),
+            AMM::ERC4626Vault(vault) => vault.swap_fee(token_in),
+            AMM::BalancerV2WeightedPool(pool) => pool.swap_fee(token_in),
+            AMM::CurveStableSwapPool(pool) => pool.swap_fee(token_in),
+            AMM::CurveCryptoPool(pool) => pool.swap_fee(token_in),
+            AMM::UniswapV4Pool(pool) => pool.swap_fee(token_in),
+            AMM::SolidlyPool(pool) => pool.swap_fee(token_in),
+            AMM::LBPair(pool) => pool.swap_fee(token_in),
+            AMM::MaverickPool(pool) => pool.swap_fee(token_in),
+            AMM::BalancerStablePool(pool) => pool.swap_fee(token_in),
+            AMM::CamelotPool(pool) => pool.swap_fee(token_in),
+            AMM::BancorV3Pool(pool) => pool.swap_fee(token_in),
+            AMM::KyberElasticPool(pool) => pool.swap_fee(token_in),
+            AMM::DodoPool(pool) => pool.swap_fee(token_in),
+            AMM::AlgebraPool(pool) => pool.swap_fee(token_in),
+            AMM::RateProviderAmm(pool) => pool.swap_fee(token_in),
+            AMM::CurveMetaPool(pool) => pool.swap_fee(token_in),
+        }
+    }
+
+    //Curve crypto, Maverick and Algebra pools have an inherent fee method that would shadow the one of the trait
+    fn fee(&self) -> u32 {
+        match self {
+            AMM::UniswapV2Pool(pool) => pool.fee(),
+            AMM::UniswapV3Pool(pool) => pool.fee(),
+            AMM::ERC4626Vault(vault) => vault.fee(),
+            AMM::BalancerV2WeightedPool(pool) => pool.fee(),
+            AMM::CurveStableSwapPool(pool) => pool.fee(),
+            AMM::CurveCryptoPool(pool) => AutomatedMarketMaker::fee(pool),
+            AMM::UniswapV4Pool(pool) => pool.fee(),
+            AMM::SolidlyPool(pool) => pool.fee(),
+            AMM::LBPair(pool) => pool.fee(),
+            AMM::MaverickPool(pool) => AutomatedMarketMaker::fee(pool),
+            AMM::BalancerStablePool(pool) => pool.fee(),
+            AMM::CamelotPool(pool) => pool.fee(),
+            AMM::BancorV3Pool(pool) => pool.fee(),
+            AMM::KyberElasticPool(pool) => pool.fee(),
+            AMM::DodoPool(pool) => pool.fee(),
+            AMM::AlgebraPool(pool) => AutomatedMarketMaker::fee(pool),
+            AMM::RateProviderAmm(pool) => pool.fee(),
+            AMM::CurveMetaPool(pool) => pool.fee(),
+        }
+    }
+
+    fn token_decimals(&self) -> Vec<u8> {
+        match self {
+            AMM::UniswapV2Pool(pool) => pool.token_decimals(),
+            AMM::UniswapV3Pool(pool) => pool.token_decimals(),
+            AMM::ERC4626Vault(vault) => vault.token_decimals(),
+            AMM::BalancerV2WeightedPool(pool) => pool.token_decimals(),
+            AMM::CurveStableSwapPool(pool) => pool.token_decimals(),
+            AMM::CurveCryptoPool(pool) => pool.token_decimals(),
+            AMM::UniswapV4Pool(pool) => pool.token_decimals(),
+            AMM::SolidlyPool(pool) => pool.token_decimals(),
+            AMM::LBPair(pool) => pool.token_decimals(),
+            AMM::MaverickPool(pool) => pool.token_decimals(),
+            AMM::BalancerStablePool(pool) => pool.token_decimals(),
+            AMM::CamelotPool(pool) => pool.token_decimals(),
+            AMM::BancorV3Pool(pool) => pool.token_decimals(),
+            AMM::KyberElasticPool(pool) => pool.token_decimals(),
+            AMM::DodoPool(pool) => pool.token_decimals(),
+            AMM::AlgebraPool(pool) => pool.token_decimals(),
+            AMM::RateProviderAmm(pool) => pool.token_decimals(),
+            AMM::CurveMetaPool(pool) => pool.token_decimals(),
+        }
+    }
+
+    fn calculate_price_impact(
+        &self,
+        token_in: H160,
+        amount_in: U256,
+    ) -> Result<f64, SwapSimulationError> {
+        match self {
+            AMM::UniswapV2Pool(pool) => pool.calculate_price_impact(token_in, amount_in),
+            AMM::UniswapV3Pool(pool) => pool.calculate_price_impact(token_in, amount_in),
+            AMM::ERC4626Vault(vault) => vault.calculate_price_impact(token_in, amount_in),
+            AMM::BalancerV2WeightedPool(pool) => pool.calculate_price_impact(token_in, amount_in),
+            AMM::CurveStableSwapPool(pool) => pool.calculate_price_impact(token_in, amount_in),
+            AMM::CurveCryptoPool(pool) => pool.calculate_price_impact(token_in, amount_in),
+            AMM::UniswapV4Pool(pool) => pool.calculate_price_impact(token_in, amount_in),
+            AMM::SolidlyPool(pool) => pool.calculate_price_impact(token_in, amount_in),
+            AMM::LBPair(pool) => pool.calculate_price_impact(token_in, amount_in),
+            AMM::MaverickPool(pool) => pool.calculate_price_impact(token_in, amount_in),
+            AMM::BalancerStablePool(pool) => pool.calculate_price_impact(token_in, amount_in),
+            AMM::CamelotPool(pool) => pool.calculate_price_impact(token_in, amount_in),
+            AMM::BancorV3Pool(pool) => pool.calculate_price_impact(token_in, amount_in),
+            AMM::KyberElasticPool(pool) => pool.calculate_price_impact(token_in, amount_in),
+            AMM::DodoPool(pool) => pool.calculate_price_impact(token_in, amount_in),
+            AMM::AlgebraPool(pool) => pool.calculate_price_impact(token_in, amount_in),
+            AMM::RateProviderAmm(pool) => pool.calculate_price_impact(token_in, amount_in),
+            AMM::CurveMetaPool(pool) => pool.calculate_price_impact(token_in, amount_in),
+        }
+    }
+
+    fn liquidity_in_range(
+        &self,
+        price_lower: Price,
+        price_upper: Price,
+    ) -> Result<U256, ArithmeticError> {
+        match self {
+            AMM::UniswapV2Pool(pool) => pool.liquidity_in_range(price_lower, price_upper),
+            AMM::UniswapV3Pool(pool) => pool.liquidity_in_range(price_lower, price_upper),
+            AMM::ERC4626Vault(vault) => vault.liquidity_in_range(price_lower, price_upper),
+            AMM::BalancerV2WeightedPool(pool) => pool.liquidity_in_range(price_lower, price_upper),
+            AMM::CurveStableSwapPool(pool) => pool.liquidity_in_range(price_lower, price_upper),
+            AMM::CurveCryptoPool(pool) => pool.liquidity_in_range(price_lower, price_upper),
+            AMM::UniswapV4Pool(pool) => pool.liquidity_in_range(price_lower, price_upper),
+            AMM::SolidlyPool(pool) => pool.liquidity_in_range(price_lower, price_upper),
+            AMM::LBPair(pool) => pool.liquidity_in_range(price_lower, price_upper),
+            AMM::MaverickPool(pool) => pool.liquidity_in_range(price_lower, price_upper),
+            AMM::BalancerStablePool(pool) => pool.liquidity_in_range(price_lower, price_upper),
+            AMM::CamelotPool(pool) => pool.liquidity_in_range(price_lower, price_upper),
+            AMM::BancorV3Pool(pool) => pool.liquidity_in_range(price_lower, price_upper),
+            AMM::KyberElasticPool(pool) => pool.liquidity_in_range(price_lower, price_upper),
+            AMM::DodoPool(pool) => pool.liquidity_in_range(price_lower, price_upper),
+            AMM::AlgebraPool(pool) => pool.liquidity_in_range(price_lower, price_upper),
+            AMM::RateProviderAmm(pool) => pool.liquidity_in_range(price_lower, price_upper),
+            AMM::CurveMetaPool(pool) => pool.liquidity_in_range(price_lower, price_upper),
+        }
+    }
+
+    fn market_depth(&self, num_levels: usize) -> Result<MarketDepth, ArithmeticError> {
+        match self {
+            AMM::UniswapV2Pool(pool) => pool.market_depth(num_levels),
+            AMM::UniswapV3Pool(pool) => pool.market_depth(num_levels),
+            AMM::ERC4626Vault(vault) => vault.market_depth(num_levels),
+            AMM::BalancerV2WeightedPool(pool) => pool.market_depth(num_levels),
+            AMM::CurveStableSwapPool(pool) => pool.market_depth(num_levels),
+            AMM::CurveCryptoPool(pool) => pool.market_depth(num_levels),
+            AMM::UniswapV4Pool(pool) => pool.market_depth(num_levels),
+            AMM::SolidlyPool(pool) => pool.market_depth(num_levels),
+            AMM::LBPair(pool) => pool.market_depth(num_levels),
+            AMM::MaverickPool(pool) => pool.market_depth(num_levels),
+            AMM::BalancerStablePool(pool) => pool.market_depth(num_levels),
+            AMM::CamelotPool(pool) => pool.market_depth(num_levels),
+            AMM::BancorV3Pool(pool) => pool.market_depth(num_levels),
+            AMM::KyberElasticPool(pool) => pool.market_depth(num_levels),
+            AMM::DodoPool(pool) => pool.market_depth(num_levels),
+            AMM::AlgebraPool(pool) => pool.market_depth(num_levels),
+            AMM::RateProviderAmm(pool) => pool.market_depth(num_levels),
+            AMM::CurveMetaPool(pool) => pool.market_depth(num_levels),
+        }
+    }
+
+    fn get_amount_in(
+        &self,
+        token_out: H160,
+        amount_out: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        match self {
+            AMM::UniswapV2Pool(pool) => pool.get_amount_in(token_out, amount_out),
+            AMM::UniswapV3Pool(pool) => pool.get_amount_in(token_out, amount_out),
+            AMM::ERC4626Vault(vault) => vault.get_amount_in(token_out, amount_out),
+            AMM::BalancerV2WeightedPool(pool) => pool.get_amount_in(token_out, amount_out),
+            AMM::CurveStableSwapPool(pool) => pool.get_amount_in(token_out, amount_out),
+            AMM::CurveCryptoPool(pool) => pool.get_amount_in(token_out, amount_out),
+            AMM::UniswapV4Pool(pool) => pool.get_amount_in(token_out, amount_out),
+            AMM::SolidlyPool(pool) => pool.get_amount_in(token_out, amount_out),
+            AMM::LBPair(pool) => pool.get_amount_in(token_out, amount_out),
+            AMM::MaverickPool(pool) => pool.get_amount_in(token_out, amount_out),
+            AMM::BalancerStablePool(pool) => pool.get_amount_in(token_out, amount_out),
+            AMM::CamelotPool(pool) => pool.get_amount_in(token_out, amount_out),
+            AMM::BancorV3Pool(pool) => pool.get_amount_in(token_out, amount_out),
+            AMM::KyberElasticPool(pool) => pool.get_amount_in(token_out, amount_out),
+            AMM::DodoPool(pool) => pool.get_amount_in(token_out, amount_out),
+            AMM::AlgebraPool(pool) => pool.get_amount_in(token_out, amount_out),
+            AMM::RateProviderAmm(pool) => pool.get_amount_in(token_out, amount_out),
+            AMM::CurveMetaPool(pool) => pool.get_amount_in(token_out, amount_out),
+        }
+    }
+}
+
+impl AMM {
+    //AMMs that live in a singleton contract are identified by a pool id rather than by their own contract,
+    //for these AMMs `address()` is the pool contract or an address derived from the pool id
+    pub fn pool_id(&self) -> Option<H256> {
+        match self {
+            AMM::BalancerV2WeightedPool(pool) => Some(pool.pool_id),
+            AMM::BalancerStablePool(pool) => Some(pool.pool_id),
+            AMM::UniswapV4Pool(pool) => Some(pool.pool_id),
+            _ => None,
         }
     }
+
+    //Address of another contract whose logs the AMM also syncs from, i.e. the base pool of a Curve metapool
+    pub fn dependency(&self) -> Option<H160> {
+        match self {
+            AMM::CurveMetaPool(pool) => Some(pool.base_pool.address),
+            _ => None,
+        }
+    }
+
+    //Symbols of the tokens and the fee of the AMM for logs and CLI output, e.g. "USDC/WETH 0.3%"
+    pub fn display_name(&self, token_list: &TokenList) -> String {
+        let symbols = self
+            .tokens()
+            .into_iter()
+            .map(|token| token_list.symbol(token))
+            .collect::<Vec<String>>();
+
+        format!("{} {}%", symbols.join("/"), self.fee() as f64 / 10_000.0)
+    }
+
+    //Reserves of token a and token b for routing the AMM like a constant product pool. Constant product pools return their reserves,
+    //concentrated liquidity pools the virtual reserves of their active liquidity, see uniswap_v3::virtual_reserves_at_sqrt_price.
+    pub fn virtual_reserves(&self) -> Result<(U256, U256), ArithmeticError> {
+        match self {
+            AMM::UniswapV2Pool(pool) => {
+                Ok((U256::from(pool.reserve_0), U256::from(pool.reserve_1)))
+            }
+            AMM::SolidlyPool(pool) => Ok((pool.reserve_0, pool.reserve_1)),
+            AMM::CamelotPool(pool) => Ok((pool.reserve_0, pool.reserve_1)),
+            AMM::UniswapV3Pool(pool) => pool.virtual_reserves(),
+            AMM::UniswapV4Pool(pool) => {
+                uniswap_v3::virtual_reserves_at_sqrt_price(pool.sqrt_price, pool.liquidity)
+            }
+            AMM::AlgebraPool(pool) => {
+                uniswap_v3::virtual_reserves_at_sqrt_price(pool.sqrt_price, pool.liquidity)
+            }
+            AMM::KyberElasticPool(pool) => uniswap_v3::virtual_reserves_at_sqrt_price(
+                pool.sqrt_price,
+                pool.base_liquidity
+                    .saturating_add(pool.reinvestment_liquidity),
+            ),
+            _ => Err(ArithmeticError::VirtualReservesNotSupported),
+        }
+    }
+}
+
+pub(crate) fn u256_to_f64(x: U256) -> f64 {
+    (x >> 128).low_u128() as f64 * 2_f64.powi(128) + x.low_u128() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use ethers::types::{H160, U256};
+
+    use super::{
+        algebra::AlgebraPool, balancer_v2::stable::BalancerStablePool,
+        balancer_v2::BalancerV2WeightedPool, bancor_v3::BancorV3Pool, camelot::CamelotPool,
+        curve::crypto::CurveCryptoPool, curve::meta::CurveMetaPool, curve::CurveStableSwapPool,
+        dodo::DodoPool, erc_4626::ERC4626Vault, kyber_elastic::KyberElasticPool,
+        maverick::MaverickPool, rate_provider::RateProviderAmm, solidly::SolidlyPool,
+        trader_joe_lb::LBPair, uniswap_v2::UniswapV2Pool, uniswap_v3::Info,
+        uniswap_v3::UniswapV3Pool, uniswap_v4::UniswapV4Pool, AutomatedMarketMaker, AMM,
+    };
+
+    #[test]
+    fn test_amm_serde_round_trip() -> eyre::Result<()> {
+        let address = H160::from_low_u64_be(0xa);
+        let token_a = H160::from_low_u64_be(0xb);
+        let token_b = H160::from_low_u64_be(0xc);
+
+        let amms = vec![
+            AMM::UniswapV2Pool(UniswapV2Pool::new(
+                address, token_a, 18, token_b, 6, 1000, 2000, 300,
+            )),
+            AMM::UniswapV3Pool(UniswapV3Pool {
+                address,
+                token_a,
+                token_b,
+                liquidity: 1000,
+                sqrt_price: U256::one() << 96,
+                fee: 500,
+                tick: -10,
+                tick_spacing: 10,
+                tick_bitmap: HashMap::from([(-1, U256::from(3))]),
+                ticks: HashMap::from([(-10, Info::new(1000, -1000, true))]),
+                ..Default::default()
+            }),
+            AMM::ERC4626Vault(ERC4626Vault::new(
+                address,
+                18,
+                token_a,
+                6,
+                U256::from(1000),
+                U256::from(1100),
+                10,
+                20,
+            )),
+            AMM::BalancerV2WeightedPool(BalancerV2WeightedPool {
+                address,
+                tokens: vec![token_a, token_b],
+                ..Default::default()
+            }),
+            AMM::BalancerStablePool(BalancerStablePool {
+                address,
+                bpt_index: Some(1),
+                tokens: vec![token_a, address, token_b],
+                ..Default::default()
+            }),
+            AMM::CurveStableSwapPool(CurveStableSwapPool {
+                address,
+                tokens: vec![token_a, token_b],
+                balances: vec![U256::from(1), U256::from(2)],
+                ..Default::default()
+            }),
+            AMM::CurveCryptoPool(CurveCryptoPool {
+                address,
+                ..Default::default()
+            }),
+            AMM::UniswapV4Pool(UniswapV4Pool {
+                token_a,
+                token_b,
+                ..Default::default()
+            }),
+            AMM::SolidlyPool(SolidlyPool {
+                address,
+                stable: true,
+                ..Default::default()
+            }),
+            AMM::LBPair(LBPair {
+                address,
+                ..Default::default()
+            }),
+            AMM::MaverickPool(MaverickPool {
+                address,
+                ..Default::default()
+            }),
+            AMM::BancorV3Pool(BancorV3Pool {
+                token: token_a,
+                ..Default::default()
+            }),
+            AMM::CamelotPool(CamelotPool {
+                address,
+                token_0_fee_percent: 300,
+                token_1_fee_percent: 100,
+                ..Default::default()
+            }),
+            AMM::KyberElasticPool(KyberElasticPool {
+                address,
+                ..Default::default()
+            }),
+            AMM::DodoPool(DodoPool {
+                address,
+                ..Default::default()
+            }),
+            AMM::AlgebraPool(AlgebraPool {
+                address,
+                ..Default::default()
+            }),
+            AMM::RateProviderAmm(RateProviderAmm {
+                address,
+                underlying: token_a,
+                rate_function: "stEthPerToken()".to_string(),
+                ..Default::default()
+            }),
+            AMM::CurveMetaPool(CurveMetaPool {
+                pool: CurveStableSwapPool {
+                    address,
+                    tokens: vec![token_a, token_b],
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+        ];
+
+        for amm in amms {
+            let serialized = serde_json::to_string(&amm)?;
+            let deserialized: AMM = serde_json::from_str(&serialized)?;
+
+            assert_eq!(deserialized.address(), amm.address());
+            assert_eq!(deserialized.tokens(), amm.tokens());
+            assert_eq!(serde_json::to_string(&deserialized)?, serialized);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_amm_fee() {
+        let (token_a, token_b) = (H160::from_low_u64_be(0xb), H160::from_low_u64_be(0xc));
+
+        let uniswap_v2_pool = AMM::UniswapV2Pool(UniswapV2Pool {
+            fee: 300,
+            ..Default::default()
+        });
+        assert_eq!(uniswap_v2_pool.fee(), 3000);
+
+        let uniswap_v3_pool = AMM::UniswapV3Pool(UniswapV3Pool {
+            fee: 500,
+            ..Default::default()
+        });
+        assert_eq!(uniswap_v3_pool.fee(), 500);
+
+        //0.1% scaled by 1e18
+        let balancer_pool = AMM::BalancerV2WeightedPool(BalancerV2WeightedPool {
+            tokens: vec![token_a, token_b],
+            swap_fee: U256::exp10(15),
+            ..Default::default()
+        });
+        assert_eq!(balancer_pool.fee(), 1000);
+
+        let solidly_pool = AMM::SolidlyPool(SolidlyPool {
+            fee: 5,
+            ..Default::default()
+        });
+        assert_eq!(solidly_pool.fee(), 500);
+    }
 }
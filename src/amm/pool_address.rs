@@ -0,0 +1,137 @@
+use ethers::{
+    abi::{encode, Token},
+    types::{H160, H256, U256},
+    utils::{get_create2_address_from_hash, keccak256},
+};
+
+//Keccak256 of the creation code of the pairs deployed by the Uniswap V2 factory
+pub const UNISWAP_V2_INIT_CODE_HASH: H256 = H256([
+    0x96, 0xe8, 0xac, 0x42, 0x77, 0x19, 0x8f, 0xf8, 0xb6, 0xf7, 0x85, 0x47, 0x8a, 0xa9, 0xa3, 0x9f,
+    0x40, 0x3c, 0xb7, 0x68, 0xdd, 0x02, 0xcb, 0xee, 0x32, 0x6c, 0x3e, 0x7d, 0xa3, 0x48, 0x84, 0x5f,
+]);
+
+//Keccak256 of the creation code of the pairs deployed by the PancakeSwap V2 factory on BSC
+pub const PANCAKESWAP_V2_INIT_CODE_HASH: H256 = H256([
+    0x00, 0xfb, 0x7f, 0x63, 0x07, 0x66, 0xe6, 0xa7, 0x96, 0x04, 0x8e, 0xa8, 0x7d, 0x01, 0xac, 0xd3,
+    0x06, 0x8e, 0x8f, 0xf6, 0x7d, 0x07, 0x81, 0x48, 0xa3, 0xfa, 0x3f, 0x4a, 0x84, 0xf6, 0x9b, 0xd5,
+]);
+
+//Keccak256 of the creation code of the pools deployed by the Uniswap V3 factory
+pub const UNISWAP_V3_POOL_INIT_CODE_HASH: H256 = H256([
+    0xe3, 0x4f, 0x19, 0x9b, 0x19, 0xb2, 0xb4, 0xf4, 0x7f, 0x68, 0x44, 0x26, 0x19, 0xd5, 0x55, 0x52,
+    0x7d, 0x24, 0x4f, 0x78, 0xa3, 0x29, 0x7e, 0xa8, 0x93, 0x25, 0xf8, 0x43, 0xf8, 0x7b, 0x8b, 0x54,
+]);
+
+//Orders the tokens by address like the factories do before deploying a pool
+pub fn sort_tokens(token_a: H160, token_b: H160) -> (H160, H160) {
+    if token_a < token_b {
+        (token_a, token_b)
+    } else {
+        (token_b, token_a)
+    }
+}
+
+//Pairs are deployed with the salt keccak256(abi.encodePacked(token0, token1))
+pub fn compute_v2_pair_address(
+    factory: H160,
+    init_code_hash: H256,
+    token_a: H160,
+    token_b: H160,
+) -> H160 {
+    let (token_0, token_1) = sort_tokens(token_a, token_b);
+    let salt = keccak256([token_0.as_bytes(), token_1.as_bytes()].concat());
+
+    get_create2_address_from_hash(factory, salt, init_code_hash)
+}
+
+//Pools are deployed with the salt keccak256(abi.encode(token0, token1, fee)).
+//Forks that deploy pools from a separate deployer contract, such as PancakeSwap V3, must pass the deployer as `factory`.
+pub fn compute_v3_pool_address(
+    factory: H160,
+    init_code_hash: H256,
+    token_a: H160,
+    token_b: H160,
+    fee: u32,
+) -> H160 {
+    let (token_0, token_1) = sort_tokens(token_a, token_b);
+    let salt = keccak256(encode(&[
+        Token::Address(token_0),
+        Token::Address(token_1),
+        Token::Uint(U256::from(fee)),
+    ]));
+
+    get_create2_address_from_hash(factory, salt, init_code_hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use ethers::types::H160;
+
+    use crate::amm::{
+        uniswap_v2::factory::UniswapV2Factory, uniswap_v3::factory::UniswapV3Factory,
+    };
+
+    use super::{
+        compute_v2_pair_address, compute_v3_pool_address, PANCAKESWAP_V2_INIT_CODE_HASH,
+        UNISWAP_V2_INIT_CODE_HASH, UNISWAP_V3_POOL_INIT_CODE_HASH,
+    };
+
+    #[test]
+    fn test_compute_pool_address() -> eyre::Result<()> {
+        let usdc = H160::from_str("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48")?;
+        let weth = H160::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2")?;
+
+        //The order of the tokens does not change the address
+        for (token_a, token_b) in [(usdc, weth), (weth, usdc)] {
+            assert_eq!(
+                compute_v2_pair_address(
+                    H160::from_str("0x5C69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f")?,
+                    UNISWAP_V2_INIT_CODE_HASH,
+                    token_a,
+                    token_b,
+                ),
+                H160::from_str("0xB4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc")?
+            );
+
+            assert_eq!(
+                compute_v3_pool_address(
+                    H160::from_str("0x1F98431c8aD98523631AE4a59f267346ea31F984")?,
+                    UNISWAP_V3_POOL_INIT_CODE_HASH,
+                    token_a,
+                    token_b,
+                    500,
+                ),
+                H160::from_str("0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640")?
+            );
+        }
+
+        let factory = UniswapV3Factory::new(
+            H160::from_str("0x1F98431c8aD98523631AE4a59f267346ea31F984")?,
+            0,
+        );
+        assert_eq!(
+            factory.compute_pool_address(usdc, weth, 500),
+            H160::from_str("0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640")?
+        );
+
+        //WBNB/BUSD pair of PancakeSwap V2 on BSC, the factory is configured with the PancakeSwap init code hash
+        let factory = UniswapV2Factory::new_with_init_code_hash(
+            H160::from_str("0xcA143Ce32Fe78f1f7019d7d551a6402fC5350c73")?,
+            0,
+            250,
+            PANCAKESWAP_V2_INIT_CODE_HASH,
+        );
+        let wbnb = H160::from_str("0xbb4CdB9CBd36B01bD1cBaEBF2De08d9173bc095c")?;
+        let busd = H160::from_str("0xe9e7CEA3DedcA5984780Bafc599bD69ADd087D56")?;
+        let pair = H160::from_str("0x58F876857a02D6762E0101bb5C46A8c1ED44Dc16")?;
+        assert_eq!(factory.compute_pair_address(wbnb, busd), pair);
+
+        //The canonical init code hash derives a different address for the same factory
+        let factory = UniswapV2Factory::new(factory.address, 0, 250);
+        assert_ne!(factory.compute_pair_address(wbnb, busd), pair);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,247 @@
+use std::fmt;
+
+use ethers::types::U256;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::ArithmeticError;
+
+use super::uniswap_v2::q64_to_f64;
+
+pub const PRICE_FRACTIONAL_BITS: usize = 64;
+pub const Q64: U256 = U256([0, 1, 0, 0]);
+pub const Q128: U256 = U256([0, 0, 1, 0]);
+
+//Price of a base token denominated in a quote token, adjusted for decimals
+//Stored as an unsigned 64.64 fixed point number in a U256 so that the integer part is not limited to 64 bits
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct Price(pub U256);
+
+impl Price {
+    pub fn from_x64(x64: U256) -> Price {
+        Price(x64)
+    }
+
+    pub fn from_q64(q64: u128) -> Price {
+        Price(U256::from(q64))
+    }
+
+    //Price of one unit of the numerator per unit of the denominator, both amounts must already be adjusted for decimals
+    pub fn from_ratio(numerator: U256, denominator: U256) -> Result<Price, ArithmeticError> {
+        if denominator.is_zero() {
+            return Err(ArithmeticError::YIsZero);
+        }
+
+        Ok(Price(uniswap_v3_math::full_math::mul_div(
+            numerator,
+            Q64,
+            denominator,
+        )?))
+    }
+
+    //Price of token 0 denominated in token 1 from a Q64.96 square root price, as tracked by Uniswap V3 style pools
+    pub fn from_sqrt_price_x96(
+        sqrt_price: U256,
+        token_0_decimals: u8,
+        token_1_decimals: u8,
+    ) -> Result<Price, ArithmeticError> {
+        let shift = token_0_decimals as i32 - token_1_decimals as i32;
+        let scale = U256::from(10)
+            .checked_pow(U256::from(shift.unsigned_abs()))
+            .ok_or(ArithmeticError::SqrtPriceOverflow)?;
+
+        //sqrt_price^2 is a Q128.192, dividing by 2^128 leaves a Q64
+        let price = if shift >= 0 {
+            uniswap_v3_math::full_math::mul_div(
+                sqrt_price
+                    .checked_mul(scale)
+                    .ok_or(ArithmeticError::SqrtPriceOverflow)?,
+                sqrt_price,
+                Q128,
+            )?
+        } else {
+            uniswap_v3_math::full_math::mul_div(
+                sqrt_price,
+                sqrt_price,
+                Q128.checked_mul(scale)
+                    .ok_or(ArithmeticError::SqrtPriceOverflow)?,
+            )?
+        };
+
+        Ok(Price(price))
+    }
+
+    //Q64.96 square root price of a price of token 0 denominated in token 1, the inverse of from_sqrt_price_x96
+    pub fn to_sqrt_price_x96(
+        self,
+        token_0_decimals: u8,
+        token_1_decimals: u8,
+    ) -> Result<U256, ArithmeticError> {
+        let shift = token_0_decimals as i32 - token_1_decimals as i32;
+        let scale = U256::from(10)
+            .checked_pow(U256::from(shift.unsigned_abs()))
+            .ok_or(ArithmeticError::SqrtPriceOverflow)?;
+
+        //The square root of a Q64 shifted by 128 bits is a Q96, prices that are too large to shift lose the fractional bits of the root
+        let sqrt_price = if shift >= 0 {
+            match uniswap_v3_math::full_math::mul_div(self.0, Q128, scale) {
+                Ok(price_x192) => price_x192.integer_sqrt(),
+                Err(_) => (self.0 / scale).integer_sqrt() << 64,
+            }
+        } else {
+            let price = self
+                .0
+                .checked_mul(scale)
+                .ok_or(ArithmeticError::SqrtPriceOverflow)?;
+
+            match price.checked_mul(Q128) {
+                Some(price_x192) => price_x192.integer_sqrt(),
+                None => price.integer_sqrt() << 64,
+            }
+        };
+
+        Ok(sqrt_price)
+    }
+
+    //Converts a float price into a 64.64 fixed point without going through a decimal representation
+    pub fn from_f64(price: f64) -> Result<Price, ArithmeticError> {
+        if !price.is_finite() || price < 0.0 {
+            return Err(ArithmeticError::InvalidPrice);
+        }
+
+        let bits = price.to_bits();
+        let biased_exponent = ((bits >> 52) & 0x7ff) as i32;
+        let fraction = bits & ((1 << 52) - 1);
+
+        //Subnormal numbers do not have the implicit leading bit
+        let (mantissa, exponent) = if biased_exponent == 0 {
+            (fraction, -1074)
+        } else {
+            (fraction | 1 << 52, biased_exponent - 1075)
+        };
+
+        let shift = exponent + PRICE_FRACTIONAL_BITS as i32;
+        if shift >= 0 {
+            if shift > 256 - 53 {
+                return Err(ArithmeticError::InvalidPrice);
+            }
+
+            Ok(Price(U256::from(mantissa) << shift as usize))
+        } else if shift > -64 {
+            Ok(Price(U256::from(mantissa >> -shift)))
+        } else {
+            Ok(Price(U256::zero()))
+        }
+    }
+
+    //Price of the quote token denominated in the base token
+    pub fn invert(self) -> Result<Price, ArithmeticError> {
+        if self.0.is_zero() {
+            return Err(ArithmeticError::YIsZero);
+        }
+
+        //2^128 / x is the reciprocal of a Q64 and is itself a Q64
+        Ok(Price(Q128 / self.0))
+    }
+
+    pub fn to_f64(self) -> f64 {
+        if self.0 <= U256::from(u128::MAX) {
+            return q64_to_f64(self.0.as_u128());
+        }
+
+        ((self.0 >> 128).low_u128() as f64 * 2_f64.powi(128) + self.0.low_u128() as f64)
+            / 2_f64.powi(PRICE_FRACTIONAL_BITS as i32)
+    }
+}
+
+impl From<Price> for f64 {
+    fn from(price: Price) -> f64 {
+        price.to_f64()
+    }
+}
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::U256;
+
+    use super::{Price, Q64};
+
+    #[test]
+    fn test_from_sqrt_price_x96() -> eyre::Result<()> {
+        //A square root price of 1 is a price of 1 when both tokens have the same decimals
+        let sqrt_price = U256::one() << 96;
+        assert_eq!(Price::from_sqrt_price_x96(sqrt_price, 18, 18)?, Price(Q64));
+
+        //1 USDC (6 decimals) for 1e-3 WETH (18 decimals), 1e-3 * 1e18 / 1e6 = 1e9 raw units
+        let sqrt_price = U256::from(31622776601683_u64) << 96;
+        let sqrt_price = sqrt_price / U256::from(1000000000);
+        let price = Price::from_sqrt_price_x96(sqrt_price, 6, 18)?;
+        assert!((f64::from(price) - 0.001).abs() < 1e-12);
+        assert!((price.invert()?.to_f64() - 1000.0).abs() < 1e-6);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_sqrt_price_x96() -> eyre::Result<()> {
+        assert_eq!(Price(Q64).to_sqrt_price_x96(18, 18)?, U256::one() << 96);
+
+        for (sqrt_price, token_0_decimals, token_1_decimals) in [
+            (U256::from(31622776601683_u64) << 96, 6, 18),
+            (U256::from(79228162514264337593543950_u128), 18, 6),
+            (
+                U256::from_dec_str("1461446703485210103287273052203988822378723970341")?,
+                18,
+                18,
+            ),
+        ] {
+            let price = Price::from_sqrt_price_x96(sqrt_price, token_0_decimals, token_1_decimals)?;
+            let round_trip = price.to_sqrt_price_x96(token_0_decimals, token_1_decimals)?;
+
+            let difference = if round_trip > sqrt_price {
+                round_trip - sqrt_price
+            } else {
+                sqrt_price - round_trip
+            };
+            assert!(difference <= sqrt_price / U256::from(1_000_000_000));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_f64() -> eyre::Result<()> {
+        assert_eq!(Price::from_f64(1.0)?, Price(Q64));
+        assert_eq!(Price::from_f64(0.5)?, Price(Q64 / 2));
+        assert!((Price::from_f64(1658.3725965327264)?.to_f64() - 1658.3725965327264).abs() < 1e-12);
+        assert_eq!(Price::from_f64(1e40)?.to_f64(), 1e40);
+        assert_eq!(Price::from_f64(0.0)?, Price::default());
+
+        assert!(Price::from_f64(f64::NAN).is_err());
+        assert!(Price::from_f64(-1.0).is_err());
+        assert!(Price::from_f64(f64::INFINITY).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_ratio_and_display() -> eyre::Result<()> {
+        let price = Price::from_ratio(U256::from(3), U256::from(2))?;
+
+        assert_eq!(price.to_string(), "1.5");
+        assert_eq!(
+            price.invert()?,
+            Price::from_ratio(U256::from(2), U256::from(3))?
+        );
+        assert!(Price::from_ratio(U256::one(), U256::zero()).is_err());
+
+        Ok(())
+    }
+}
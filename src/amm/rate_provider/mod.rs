@@ -0,0 +1,313 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::{
+    prelude::abigen,
+    providers::Middleware,
+    types::{
+        transaction::eip2718::TypedTransaction, BlockId, Log, TransactionRequest, H160, H256, U256,
+        U512,
+    },
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    amm::{price::Price, AutomatedMarketMaker},
+    errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
+};
+
+abigen!(
+    IErc20,
+    r#"[
+        function decimals() external view returns (uint8)
+    ]"#;
+);
+
+//Rates are fixed point numbers with 18 decimals
+pub const RATE_PRECISION: U256 = U256([1_000_000_000_000_000_000, 0, 0, 0]);
+
+//Rate functions of common wrappers, wstETH exposes the stETH per wstETH and rETH the ETH per rETH
+pub const WSTETH_RATE_FUNCTION: &str = "stEthPerToken()";
+pub const RETH_RATE_FUNCTION: &str = "getExchangeRate()";
+//Rate providers used by Balancer pools all implement `getRate()`
+pub const RATE_PROVIDER_RATE_FUNCTION: &str = "getRate()";
+
+//A wrapped token that can be exchanged for its underlying token at an exchange rate read from a view call, i.e. wstETH/stETH or rETH/ETH.
+//The rate accrues without emitting an event, so the AMM does not sync from logs and is synced every block by the state space instead.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RateProviderAmm {
+    pub address: H160, // wrapped token
+    pub token_decimals: u8,
+    pub underlying: H160, // token the wrapped token is exchanged for
+    pub underlying_decimals: u8,
+    pub rate_provider: H160, // contract exposing the rate, usually the wrapped token itself
+    pub rate_function: String, // signature of a view function without arguments that returns the rate, i.e. `stEthPerToken()`
+    pub rate: U256,            // underlying tokens per wrapped token, scaled by RATE_PRECISION
+}
+
+#[async_trait]
+impl AutomatedMarketMaker for RateProviderAmm {
+    fn address(&self) -> H160 {
+        self.address
+    }
+
+    async fn sync<M: Middleware>(&mut self, middleware: Arc<M>) -> Result<(), AMMError<M>> {
+        self.rate = self.get_rate(None, middleware).await?;
+
+        Ok(())
+    }
+
+    fn sync_on_event_signatures(&self) -> Vec<H256> {
+        vec![]
+    }
+
+    fn tokens(&self) -> Vec<H160> {
+        vec![self.address, self.underlying]
+    }
+
+    //The rate is the price of the wrapped token, there is no fee or slippage
+    fn calculate_price(&self, base_token: H160) -> Result<Price, ArithmeticError> {
+        self.check_liquidity()?;
+
+        if base_token == self.address {
+            Price::from_ratio(self.rate, RATE_PRECISION)
+        } else {
+            Price::from_ratio(RATE_PRECISION, self.rate)
+        }
+    }
+
+    fn sync_from_log(&mut self, _log: Log) -> Result<(), EventLogError> {
+        Err(EventLogError::InvalidEventSignature)
+    }
+
+    async fn populate_data<M: Middleware>(
+        &mut self,
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        self.token_decimals = IErc20::new(self.address, middleware.clone())
+            .decimals()
+            .call()
+            .await?;
+
+        self.underlying_decimals = IErc20::new(self.underlying, middleware.clone())
+            .decimals()
+            .call()
+            .await?;
+
+        self.rate = self.get_rate(block_number, middleware).await?;
+
+        Ok(())
+    }
+
+    fn simulate_swap(&self, token_in: H160, amount_in: U256) -> Result<U256, SwapSimulationError> {
+        self.check_liquidity()?;
+
+        if token_in == self.address {
+            Ok(self.wrapped_to_underlying(amount_in))
+        } else {
+            self.underlying_to_wrapped(amount_in)
+        }
+    }
+
+    //Swaps do not move the rate
+    fn simulate_swap_mut(
+        &mut self,
+        token_in: H160,
+        amount_in: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        self.simulate_swap(token_in, amount_in)
+    }
+
+    fn get_token_out(&self, token_in: H160) -> H160 {
+        if token_in == self.address {
+            self.underlying
+        } else {
+            self.address
+        }
+    }
+
+    fn swap_fee(&self, _token_in: H160) -> f64 {
+        0.0
+    }
+
+    fn token_decimals(&self) -> Vec<u8> {
+        vec![self.token_decimals, self.underlying_decimals]
+    }
+}
+
+impl RateProviderAmm {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        address: H160,
+        token_decimals: u8,
+        underlying: H160,
+        underlying_decimals: u8,
+        rate_provider: H160,
+        rate_function: String,
+        rate: U256,
+    ) -> RateProviderAmm {
+        RateProviderAmm {
+            address,
+            token_decimals,
+            underlying,
+            underlying_decimals,
+            rate_provider,
+            rate_function,
+            rate,
+        }
+    }
+
+    pub async fn new_from_address<M: Middleware>(
+        address: H160,
+        underlying: H160,
+        rate_provider: H160,
+        rate_function: &str,
+        middleware: Arc<M>,
+    ) -> Result<Self, AMMError<M>> {
+        let mut amm = RateProviderAmm {
+            address,
+            underlying,
+            rate_provider,
+            rate_function: rate_function.to_string(),
+            ..Default::default()
+        };
+
+        amm.populate_data(None, middleware).await?;
+
+        if !amm.data_is_populated() {
+            return Err(AMMError::PoolDataError);
+        }
+
+        Ok(amm)
+    }
+
+    pub fn data_is_populated(&self) -> bool {
+        !(self.address.is_zero() || self.underlying.is_zero() || self.rate.is_zero())
+    }
+
+    //Calls the rate function on the rate provider, the function must return a single uint256
+    pub async fn get_rate<M: Middleware>(
+        &self,
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<U256, AMMError<M>> {
+        let tx: TypedTransaction = TransactionRequest::new()
+            .to(self.rate_provider)
+            .data(ethers::utils::id(&self.rate_function).to_vec())
+            .into();
+
+        let result = middleware
+            .call(&tx, block_number.map(BlockId::from))
+            .await
+            .map_err(AMMError::MiddlewareError)?;
+
+        if result.len() != 32 {
+            return Err(AMMError::BatchRequestError(self.rate_provider));
+        }
+
+        Ok(U256::from_big_endian(&result))
+    }
+
+    //The wrapper has no reserves to run out of, but a rate that was not read yet or was reset to zero can not be exchanged at
+    fn check_liquidity(&self) -> Result<(), ArithmeticError> {
+        if self.rate.is_zero() {
+            return Err(ArithmeticError::ZeroLiquidity(self.address));
+        }
+
+        Ok(())
+    }
+
+    //Both conversions round down like the wrappers do when wrapping and unwrapping
+    pub fn wrapped_to_underlying(&self, amount: U256) -> U256 {
+        mul_div(
+            amount,
+            self.rate * U256::exp10(self.underlying_decimals as usize),
+            RATE_PRECISION * U256::exp10(self.token_decimals as usize),
+        )
+    }
+
+    pub fn underlying_to_wrapped(&self, amount: U256) -> Result<U256, SwapSimulationError> {
+        if self.rate.is_zero() {
+            return Err(ArithmeticError::YIsZero.into());
+        }
+
+        Ok(mul_div(
+            amount,
+            RATE_PRECISION * U256::exp10(self.token_decimals as usize),
+            self.rate * U256::exp10(self.underlying_decimals as usize),
+        ))
+    }
+}
+
+fn mul_div(a: U256, b: U256, denominator: U256) -> U256 {
+    U256::try_from(a.full_mul(b) / U512::from(denominator)).unwrap_or(U256::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, sync::Arc};
+
+    use ethers::{
+        providers::{Http, Provider},
+        types::{H160, U256},
+    };
+
+    use crate::amm::AutomatedMarketMaker;
+
+    use super::{RateProviderAmm, RATE_PRECISION, RETH_RATE_FUNCTION, WSTETH_RATE_FUNCTION};
+
+    #[test]
+    fn test_simulate_swap() -> eyre::Result<()> {
+        let wrapped = H160::from_low_u64_be(1);
+        let underlying = H160::from_low_u64_be(2);
+        let amm = RateProviderAmm::new(
+            wrapped,
+            18,
+            underlying,
+            6,
+            wrapped,
+            RETH_RATE_FUNCTION.to_string(),
+            RATE_PRECISION * 11 / 10,
+        );
+
+        //One wrapped token is worth 1.1 underlying tokens
+        let amount_out = amm.simulate_swap(wrapped, U256::exp10(18))?;
+        assert_eq!(amount_out, U256::from(1_100_000));
+        assert_eq!(amm.get_token_out(wrapped), underlying);
+
+        let amount_out = amm.simulate_swap(underlying, U256::from(1_100_000))?;
+        assert_eq!(amount_out, U256::exp10(18));
+
+        assert!((amm.calculate_price(wrapped)?.to_f64() - 1.1).abs() < 1e-12);
+        assert!((amm.calculate_price(underlying)?.to_f64() - 1.0 / 1.1).abs() < 1e-12);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_new_from_address() -> eyre::Result<()> {
+        let rpc_endpoint = std::env::var("ETHEREUM_RPC_ENDPOINT")?;
+        let middleware = Arc::new(Provider::<Http>::try_from(rpc_endpoint)?);
+
+        let wsteth = H160::from_str("0x7f39C581F595B53c5cb19bD0b3f8dA6c935E2Ca0")?;
+        let steth = H160::from_str("0xae7ab96520DE3A18E5e111B5EaAb095312D7fE84")?;
+
+        let amm = RateProviderAmm::new_from_address(
+            wsteth,
+            steth,
+            wsteth,
+            WSTETH_RATE_FUNCTION,
+            middleware,
+        )
+        .await?;
+
+        assert_eq!(amm.token_decimals, 18);
+        assert_eq!(amm.underlying_decimals, 18);
+        //stETH per wstETH only grows from 1
+        assert!(amm.rate > RATE_PRECISION);
+        assert_eq!(amm.simulate_swap(wsteth, U256::exp10(18))?, amm.rate);
+
+        Ok(())
+    }
+}
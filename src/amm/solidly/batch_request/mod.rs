@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use ethers::{
+    abi::Token,
+    contract::Multicall,
+    providers::Middleware,
+    types::{BlockNumber, Bytes, H160, U256},
+};
+
+use crate::errors::AMMError;
+
+use super::{
+    factory::{ISolidlyFactory, ISolidlyV2Factory},
+    ISolidlyPair, SolidlyPool, DEFAULT_FEE,
+};
+
+//Solidly pairs expose their tokens, decimals, reserves and stable flag through `metadata`, the fee is set per pair type on the factory.
+//Velodrome V2 factories take the pool as well, so both getters are called and the one the factory implements is used.
+
+pub async fn get_solidly_pool_data_batch_request<M: Middleware>(
+    mut pools: Vec<&mut SolidlyPool>,
+    block_number: Option<u64>,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    let block = block_number
+        .map(BlockNumber::from)
+        .unwrap_or(BlockNumber::Latest);
+
+    let mut multicall = Multicall::new(middleware.clone(), None).await?.block(block);
+
+    for pool in pools.iter() {
+        let pair = ISolidlyPair::new(pool.address, middleware.clone());
+        multicall.add_call(pair.metadata(), true);
+        multicall.add_call(pair.factory(), true);
+    }
+
+    let results = multicall.call_raw().await?;
+    multicall.clear_calls();
+
+    //Pairs that do not implement the interface are left empty so that they are removed by remove_empty_amms
+    let mut populated_pools = vec![];
+    for (pool, results) in pools.iter_mut().zip(results.chunks(2)) {
+        if let Some(factory) = decode_metadata(pool, results) {
+            multicall.add_call(
+                ISolidlyFactory::new(factory, middleware.clone()).get_fee(pool.stable),
+                true,
+            );
+            multicall.add_call(
+                ISolidlyV2Factory::new(factory, middleware.clone())
+                    .get_fee(pool.address, pool.stable),
+                true,
+            );
+            populated_pools.push(pool);
+        } else {
+            tracing::debug!(?pool.address, "pair does not implement the solidly interface");
+        }
+    }
+
+    if populated_pools.is_empty() {
+        return Ok(());
+    }
+
+    let fees = multicall.call_raw().await?;
+    for (pool, fees) in populated_pools.into_iter().zip(fees.chunks(2)) {
+        pool.fee = fees
+            .iter()
+            .find_map(|fee| fee.clone().ok())
+            .and_then(|fee| fee.into_uint())
+            .map(|fee| fee.as_u32())
+            .unwrap_or(DEFAULT_FEE);
+    }
+
+    Ok(())
+}
+
+//Returns the factory of the pair if the metadata could be decoded
+fn decode_metadata(pool: &mut SolidlyPool, results: &[Result<Token, Bytes>]) -> Option<H160> {
+    let metadata = results.first()?.as_ref().ok()?.clone().into_tuple()?;
+    let factory = results.get(1)?.as_ref().ok()?.clone().into_address()?;
+
+    pool.token_a_decimals = decimals_from_scalar(metadata.first()?.clone().into_uint()?);
+    pool.token_b_decimals = decimals_from_scalar(metadata.get(1)?.clone().into_uint()?);
+    pool.reserve_0 = metadata.get(2)?.clone().into_uint()?;
+    pool.reserve_1 = metadata.get(3)?.clone().into_uint()?;
+    pool.stable = metadata.get(4)?.clone().into_bool()?;
+    pool.token_a = metadata.get(5)?.clone().into_address()?;
+    pool.token_b = metadata.get(6)?.clone().into_address()?;
+
+    Some(factory)
+}
+
+//The pair stores the decimals of its tokens as 10^decimals
+fn decimals_from_scalar(mut scalar: U256) -> u8 {
+    let mut decimals = 0;
+    while scalar >= U256::from(10) {
+        scalar /= U256::from(10);
+        decimals += 1;
+    }
+
+    decimals
+}
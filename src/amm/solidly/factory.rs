@@ -0,0 +1,185 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::{
+    abi::RawLog,
+    prelude::{abigen, EthEvent},
+    providers::Middleware,
+    types::{Log, H160, H256},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    amm::{
+        factory::{AutomatedMarketMakerFactory, Factory},
+        AMM,
+    },
+    errors::AMMError,
+};
+
+use super::{batch_request, SolidlyPool};
+
+abigen!(
+    ISolidlyFactory,
+    r#"[
+        function getPair(address tokenA, address tokenB, bool stable) external view returns (address)
+        function getFee(bool stable) external view returns (uint256)
+        function allPairs(uint256 index) external view returns (address)
+        function allPairsLength() external view returns (uint256)
+        event PairCreated(address indexed token0, address indexed token1, bool stable, address pair, uint256)
+    ]"#;
+
+    ISolidlyV2Factory,
+    r#"[
+        function getFee(address pool, bool stable) external view returns (uint256)
+        function allPools(uint256 index) external view returns (address)
+        function allPoolsLength() external view returns (uint256)
+        event PoolCreated(address indexed token0, address indexed token1, bool indexed stable, address pool, uint256)
+    ]"#;
+);
+
+pub const PAIR_CREATED_EVENT_SIGNATURE: H256 = H256([
+    196, 128, 86, 150, 198, 109, 124, 243, 82, 252, 29, 107, 182, 51, 173, 94, 232, 47, 108, 181,
+    119, 196, 83, 2, 75, 110, 14, 184, 48, 108, 111, 201,
+]);
+
+pub const POOL_CREATED_EVENT_SIGNATURE: H256 = H256([
+    33, 40, 216, 141, 20, 200, 12, 176, 129, 193, 37, 42, 90, 207, 247, 162, 100, 103, 27, 241,
+    153, 206, 34, 107, 83, 120, 143, 178, 96, 101, 0, 94,
+]);
+
+//Velodrome V2 and its forks such as Aerodrome emit PoolCreated with the stable flag indexed instead of PairCreated,
+//and enumerate their pools through allPools and allPoolsLength
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SolidlyFactoryVersion {
+    #[default]
+    V1,
+    V2,
+}
+
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SolidlyFactory {
+    pub address: H160,
+    pub creation_block: u64,
+    #[serde(default)]
+    pub version: SolidlyFactoryVersion,
+}
+
+#[async_trait]
+impl AutomatedMarketMakerFactory for SolidlyFactory {
+    fn address(&self) -> H160 {
+        self.address
+    }
+
+    fn creation_block(&self) -> u64 {
+        self.creation_block
+    }
+
+    fn amm_created_event_signature(&self) -> H256 {
+        match self.version {
+            SolidlyFactoryVersion::V1 => PAIR_CREATED_EVENT_SIGNATURE,
+            SolidlyFactoryVersion::V2 => POOL_CREATED_EVENT_SIGNATURE,
+        }
+    }
+
+    async fn new_amm_from_log<M: 'static + Middleware>(
+        &self,
+        log: Log,
+        middleware: Arc<M>,
+    ) -> Result<AMM, AMMError<M>> {
+        let pool = empty_pool_from_log(log)?;
+
+        Ok(AMM::SolidlyPool(
+            SolidlyPool::new_from_address(pool.address, middleware).await?,
+        ))
+    }
+
+    fn new_empty_amm_from_log(&self, log: Log) -> Result<AMM, ethers::abi::Error> {
+        Ok(AMM::SolidlyPool(empty_pool_from_log(log)?))
+    }
+
+    async fn get_all_amms<M: 'static + Middleware>(
+        &self,
+        to_block: Option<u64>,
+        middleware: Arc<M>,
+        step: u64,
+    ) -> Result<Vec<AMM>, AMMError<M>> {
+        if let Some(block) = to_block {
+            Factory::SolidlyFactory(*self)
+                .get_all_pools_from_logs(self.creation_block, block, step, middleware)
+                .await
+        } else {
+            return Err(AMMError::BlockNumberNotFound);
+        }
+    }
+
+    async fn populate_amm_data<M: Middleware>(
+        &self,
+        amms: &mut [AMM],
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        let step = 127; //Max batch size for call
+        for amm_chunk in amms.chunks_mut(step) {
+            let pools = amm_chunk
+                .iter_mut()
+                .filter_map(|amm| match amm {
+                    AMM::SolidlyPool(pool) => Some(pool),
+                    _ => None,
+                })
+                .collect::<Vec<&mut SolidlyPool>>();
+
+            batch_request::get_solidly_pool_data_batch_request(
+                pools,
+                block_number,
+                middleware.clone(),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SolidlyFactory {
+    pub fn new(address: H160, creation_block: u64) -> SolidlyFactory {
+        SolidlyFactory {
+            address,
+            creation_block,
+            version: SolidlyFactoryVersion::V1,
+        }
+    }
+
+    pub fn new_v2(address: H160, creation_block: u64) -> SolidlyFactory {
+        SolidlyFactory {
+            address,
+            creation_block,
+            version: SolidlyFactoryVersion::V2,
+        }
+    }
+}
+
+//Pool of either creation event with the tokens and the stable flag of the log, the version is told by the event signature
+fn empty_pool_from_log(log: Log) -> Result<SolidlyPool, ethers::abi::Error> {
+    if log.topics.first() == Some(&POOL_CREATED_EVENT_SIGNATURE) {
+        let pool_created_event = PoolCreatedFilter::decode_log(&RawLog::from(log))?;
+
+        Ok(SolidlyPool {
+            address: pool_created_event.pool,
+            token_a: pool_created_event.token_0,
+            token_b: pool_created_event.token_1,
+            stable: pool_created_event.stable,
+            ..Default::default()
+        })
+    } else {
+        let pair_created_event = PairCreatedFilter::decode_log(&RawLog::from(log))?;
+
+        Ok(SolidlyPool {
+            address: pair_created_event.pair,
+            token_a: pair_created_event.token_0,
+            token_b: pair_created_event.token_1,
+            stable: pair_created_event.stable,
+            ..Default::default()
+        })
+    }
+}
@@ -0,0 +1,466 @@
+pub mod batch_request;
+pub mod factory;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::{
+    abi::RawLog,
+    prelude::{abigen, EthEvent},
+    providers::Middleware,
+    types::{Log, H160, H256, U256},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    amm::{price::Price, u256_to_f64, AutomatedMarketMaker},
+    errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
+};
+
+abigen!(
+    ISolidlyPair,
+    r#"[
+        function metadata() external view returns (uint256 dec0, uint256 dec1, uint256 r0, uint256 r1, bool st, address t0, address t1)
+        function getReserves() external view returns (uint256 reserve0, uint256 reserve1, uint256 blockTimestampLast)
+        function getAmountOut(uint256 amountIn, address tokenIn) external view returns (uint256)
+        function factory() external view returns (address)
+        event Sync(uint256 reserve0, uint256 reserve1)
+    ]"#;
+);
+
+pub const SYNC_EVENT_SIGNATURE: H256 = H256([
+    207, 42, 165, 8, 118, 205, 251, 181, 65, 32, 111, 137, 175, 14, 231, 141, 68, 162, 171, 248,
+    211, 40, 227, 127, 164, 145, 127, 152, 33, 73, 132, 138,
+]);
+
+//Fees are expressed in basis points
+pub const FEE_DENOMINATOR: u32 = 10000;
+//The original Solidly pairs charge a fixed 0.01% fee and the factory does not expose a fee getter
+pub const DEFAULT_FEE: u32 = 1;
+//Maximum number of newton iterations in get_y, same as the pair contract
+pub const MAX_ITERATIONS: usize = 255;
+
+const PRECISION: U256 = U256([1000000000000000000, 0, 0, 0]);
+
+//Volatile pairs use the x * y = k invariant and stable pairs use x^3 * y + x * y^3 = k
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SolidlyPool {
+    pub address: H160,
+    pub token_a: H160,
+    pub token_a_decimals: u8,
+    pub token_b: H160,
+    pub token_b_decimals: u8,
+    pub reserve_0: U256,
+    pub reserve_1: U256,
+    pub stable: bool,
+    pub fee: u32,
+}
+
+#[async_trait]
+impl AutomatedMarketMaker for SolidlyPool {
+    fn address(&self) -> H160 {
+        self.address
+    }
+
+    async fn sync<M: Middleware>(&mut self, middleware: Arc<M>) -> Result<(), AMMError<M>> {
+        let pair = ISolidlyPair::new(self.address, middleware);
+        (self.reserve_0, self.reserve_1, _) = pair.get_reserves().call().await?;
+
+        Ok(())
+    }
+
+    async fn populate_data<M: Middleware>(
+        &mut self,
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        batch_request::get_solidly_pool_data_batch_request(vec![self], block_number, middleware)
+            .await
+    }
+
+    fn sync_on_event_signatures(&self) -> Vec<H256> {
+        vec![SYNC_EVENT_SIGNATURE]
+    }
+
+    fn sync_from_log(&mut self, log: Log) -> Result<(), EventLogError> {
+        let event_signature = log.topics[0];
+
+        if event_signature == SYNC_EVENT_SIGNATURE {
+            let sync_event = SyncFilter::decode_log(&RawLog::from(log))?;
+
+            self.reserve_0 = sync_event.reserve_0;
+            self.reserve_1 = sync_event.reserve_1;
+
+            Ok(())
+        } else {
+            Err(EventLogError::InvalidEventSignature)
+        }
+    }
+
+    //Calculates the marginal price of the base token in terms of the quote token
+    fn calculate_price(&self, base_token: H160) -> Result<Price, ArithmeticError> {
+        self.check_liquidity()?;
+
+        let x = u256_to_f64(self.reserve_0) / 10_f64.powi(self.token_a_decimals as i32);
+        let y = u256_to_f64(self.reserve_1) / 10_f64.powi(self.token_b_decimals as i32);
+
+        //-dy/dx of the invariant
+        let price = if self.stable {
+            (3.0 * x * x * y + y * y * y) / (x * x * x + 3.0 * x * y * y)
+        } else {
+            y / x
+        };
+
+        if base_token == self.token_a {
+            Price::from_f64(price)
+        } else {
+            Price::from_f64(1.0 / price)
+        }
+    }
+
+    fn tokens(&self) -> Vec<H160> {
+        vec![self.token_a, self.token_b]
+    }
+
+    fn simulate_swap(&self, token_in: H160, amount_in: U256) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        let amount_out = self.get_amount_out(amount_in, token_in)?;
+
+        tracing::trace!(?amount_out);
+
+        Ok(amount_out)
+    }
+
+    fn simulate_swap_mut(
+        &mut self,
+        token_in: H160,
+        amount_in: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        let amount_out = self.get_amount_out(amount_in, token_in)?;
+
+        tracing::trace!(?amount_out);
+        tracing::trace!(?self.reserve_0, ?self.reserve_1, "pool reserves before");
+
+        //The fee is moved out of the pair, so only the amount after fees is added to the reserves
+        let amount_in_after_fee = amount_in - self.fee_amount(amount_in);
+        if self.token_a == token_in {
+            self.reserve_0 += amount_in_after_fee;
+            self.reserve_1 -= amount_out;
+        } else {
+            self.reserve_0 -= amount_out;
+            self.reserve_1 += amount_in_after_fee;
+        }
+
+        tracing::trace!(?self.reserve_0, ?self.reserve_1, "pool reserves after");
+
+        Ok(amount_out)
+    }
+
+    fn swap_fee(&self, _token_in: H160) -> f64 {
+        self.fee as f64 / FEE_DENOMINATOR as f64
+    }
+
+    //The fee is in basis points
+    fn fee(&self) -> u32 {
+        self.fee * (1_000_000 / FEE_DENOMINATOR)
+    }
+
+    fn token_decimals(&self) -> Vec<u8> {
+        vec![self.token_a_decimals, self.token_b_decimals]
+    }
+
+    fn get_token_out(&self, token_in: H160) -> H160 {
+        if self.token_a == token_in {
+            self.token_b
+        } else {
+            self.token_a
+        }
+    }
+}
+
+impl SolidlyPool {
+    //Creates a new instance of the pool from the pair address, and syncs the pool data
+    pub async fn new_from_address<M: Middleware>(
+        pair_address: H160,
+        middleware: Arc<M>,
+    ) -> Result<Self, AMMError<M>> {
+        let mut pool = SolidlyPool {
+            address: pair_address,
+            ..Default::default()
+        };
+
+        pool.populate_data(None, middleware).await?;
+
+        if !pool.data_is_populated() {
+            return Err(AMMError::PoolDataError);
+        }
+
+        Ok(pool)
+    }
+
+    pub fn data_is_populated(&self) -> bool {
+        !(self.token_a.is_zero()
+            || self.token_b.is_zero()
+            || self.reserve_0.is_zero()
+            || self.reserve_1.is_zero())
+    }
+
+    fn fee_amount(&self, amount_in: U256) -> U256 {
+        amount_in * U256::from(self.fee) / U256::from(FEE_DENOMINATOR)
+    }
+
+    //A pair that was just created, or whose liquidity was removed, has no price and can not swap
+    fn check_liquidity(&self) -> Result<(), ArithmeticError> {
+        if self.reserve_0.is_zero() || self.reserve_1.is_zero() {
+            return Err(ArithmeticError::ZeroLiquidity(self.address));
+        }
+
+        Ok(())
+    }
+
+    //Mirrors `getAmountOut` of the pair contract
+    pub fn get_amount_out(
+        &self,
+        amount_in: U256,
+        token_in: H160,
+    ) -> Result<U256, SwapSimulationError> {
+        self.check_liquidity()?;
+
+        if amount_in.is_zero() {
+            return Ok(U256::zero());
+        }
+
+        let zero_for_one = token_in == self.token_a;
+        let amount_in = amount_in - self.fee_amount(amount_in);
+
+        if self.stable {
+            let decimals_0 = U256::exp10(self.token_a_decimals as usize);
+            let decimals_1 = U256::exp10(self.token_b_decimals as usize);
+
+            let xy = self.k(self.reserve_0, self.reserve_1);
+            let reserve_0 = self.reserve_0 * PRECISION / decimals_0;
+            let reserve_1 = self.reserve_1 * PRECISION / decimals_1;
+
+            let (reserve_in, reserve_out, decimals_in, decimals_out) = if zero_for_one {
+                (reserve_0, reserve_1, decimals_0, decimals_1)
+            } else {
+                (reserve_1, reserve_0, decimals_1, decimals_0)
+            };
+
+            let amount_in = amount_in * PRECISION / decimals_in;
+            let y = reserve_out - get_y(amount_in + reserve_in, xy, reserve_out)?;
+
+            Ok(y * decimals_out / PRECISION)
+        } else {
+            let (reserve_in, reserve_out) = if zero_for_one {
+                (self.reserve_0, self.reserve_1)
+            } else {
+                (self.reserve_1, self.reserve_0)
+            };
+
+            Ok(amount_in * reserve_out / (reserve_in + amount_in))
+        }
+    }
+
+    //Invariant of the pair, reserves of stable pairs are scaled to 18 decimals
+    pub fn k(&self, x: U256, y: U256) -> U256 {
+        if self.stable {
+            let x = x * PRECISION / U256::exp10(self.token_a_decimals as usize);
+            let y = y * PRECISION / U256::exp10(self.token_b_decimals as usize);
+            let a = x * y / PRECISION;
+            let b = x * x / PRECISION + y * y / PRECISION;
+
+            a * b / PRECISION
+        } else {
+            x * y
+        }
+    }
+}
+
+// x0 * y^3 + x0^3 * y
+fn f(x_0: U256, y: U256) -> U256 {
+    x_0 * (y * y / PRECISION * y / PRECISION) / PRECISION
+        + (x_0 * x_0 / PRECISION * x_0 / PRECISION) * y / PRECISION
+}
+
+// Derivative of f with respect to y
+fn d(x_0: U256, y: U256) -> U256 {
+    U256::from(3) * x_0 * (y * y / PRECISION) / PRECISION
+        + (x_0 * x_0 / PRECISION * x_0 / PRECISION)
+}
+
+//Solves f(x_0, y) = xy for y with newton's method, starting from the current reserve of the token out
+pub fn get_y(x_0: U256, xy: U256, mut y: U256) -> Result<U256, SwapSimulationError> {
+    for _ in 0..MAX_ITERATIONS {
+        let y_prev = y;
+        let k = f(x_0, y);
+        let d = d(x_0, y);
+
+        if d.is_zero() {
+            return Err(SwapSimulationError::DidNotConverge);
+        }
+
+        if k < xy {
+            y += (xy - k) * PRECISION / d;
+        } else {
+            y -= (k - xy) * PRECISION / d;
+        }
+
+        if abs_diff(y, y_prev) <= U256::one() {
+            return Ok(y);
+        }
+    }
+
+    //The pair contract returns the last approximation if the method did not converge
+    Ok(y)
+}
+
+fn abs_diff(a: U256, b: U256) -> U256 {
+    if a > b {
+        a - b
+    } else {
+        b - a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, sync::Arc};
+
+    use ethers::{
+        providers::{Http, Provider},
+        types::{H160, U256},
+    };
+
+    use crate::{
+        amm::AutomatedMarketMaker,
+        errors::{ArithmeticError, SwapSimulationError},
+    };
+
+    use super::{factory::ISolidlyFactory, ISolidlyPair, SolidlyPool};
+
+    fn usdc_dai_pool(stable: bool, fee: u32) -> SolidlyPool {
+        SolidlyPool {
+            address: H160::zero(),
+            token_a: H160::from_low_u64_be(1),
+            token_a_decimals: 6,
+            token_b: H160::from_low_u64_be(2),
+            token_b_decimals: 18,
+            reserve_0: U256::from(1_000_000) * U256::exp10(6),
+            reserve_1: U256::from(1_050_000) * U256::exp10(18),
+            stable,
+            fee,
+        }
+    }
+
+    #[test]
+    fn test_simulate_swap_stable() -> eyre::Result<()> {
+        let mut pool = usdc_dai_pool(true, 2);
+
+        let amount_out = pool.simulate_swap(pool.token_a, U256::from(1000) * U256::exp10(6))?;
+        assert_eq!(amount_out, U256::from_dec_str("999827318789763837201")?);
+
+        let amount_out = pool.simulate_swap(pool.token_b, U256::from(1000) * U256::exp10(18))?;
+        assert_eq!(amount_out, U256::from(999769200));
+
+        let amount_out_mut =
+            pool.simulate_swap_mut(pool.token_b, U256::from(1000) * U256::exp10(18))?;
+        assert_eq!(amount_out, amount_out_mut);
+        assert_eq!(
+            pool.reserve_1,
+            U256::from(1_050_000) * U256::exp10(18) + U256::from(9998) * U256::exp10(17)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulate_swap_volatile() -> eyre::Result<()> {
+        let pool = SolidlyPool {
+            token_a_decimals: 18,
+            token_b_decimals: 6,
+            reserve_0: U256::from(500) * U256::exp10(18),
+            reserve_1: U256::from(1_000_000) * U256::exp10(6),
+            ..usdc_dai_pool(false, 30)
+        };
+
+        let amount_out = pool.simulate_swap(pool.token_a, U256::exp10(18))?;
+        assert_eq!(amount_out, U256::from(1990031876));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_calculate_price() -> eyre::Result<()> {
+        let volatile_pool = usdc_dai_pool(false, 2);
+        let stable_pool = usdc_dai_pool(true, 2);
+
+        assert!(
+            (volatile_pool
+                .calculate_price(volatile_pool.token_a)?
+                .to_f64()
+                - 1.05)
+                .abs()
+                < 1e-12
+        );
+
+        //The stable curve is flatter around the peg
+        let stable_price = stable_pool.calculate_price(stable_pool.token_a)?.to_f64();
+        assert!(stable_price > 1.0 && stable_price < 1.05);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zero_liquidity() {
+        let pool = SolidlyPool {
+            reserve_1: U256::zero(),
+            ..usdc_dai_pool(true, 2)
+        };
+
+        assert!(matches!(
+            pool.calculate_price(pool.token_a),
+            Err(ArithmeticError::ZeroLiquidity(_))
+        ));
+        assert!(matches!(
+            pool.simulate_swap(pool.token_a, U256::exp10(6)),
+            Err(SwapSimulationError::ArithmeticError(
+                ArithmeticError::ZeroLiquidity(_)
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_simulate_swap_matches_pair() -> eyre::Result<()> {
+        let rpc_endpoint = std::env::var("OPTIMISM_RPC_ENDPOINT")?;
+        let middleware = Arc::new(Provider::<Http>::try_from(rpc_endpoint)?);
+
+        //Velodrome V1 sAMM-USDC/DAI
+        let usdc = H160::from_str("0x7F5c764cBc14f9669B88837ca1490cCa17c31607")?;
+        let dai = H160::from_str("0xDA10009cBd5D07dd0CeCc66161FC93D7c9000da1")?;
+        let factory = ISolidlyFactory::new(
+            H160::from_str("0x25CbdDb98b35ab1FF77413456B31EC81A6B6B746")?,
+            middleware.clone(),
+        );
+        let pair_address = factory.get_pair(usdc, dai, true).call().await?;
+
+        let pool = SolidlyPool::new_from_address(pair_address, middleware.clone()).await?;
+        assert!(pool.stable);
+
+        let pair = ISolidlyPair::new(pair_address, middleware.clone());
+        for (token_in, amount_in) in [
+            (usdc, U256::from(1000) * U256::exp10(6)),
+            (dai, U256::from(1000) * U256::exp10(18)),
+        ] {
+            let expected_amount_out = pair.get_amount_out(amount_in, token_in).call().await?;
+            let amount_out = pool.simulate_swap(token_in, amount_in)?;
+
+            assert!(super::abs_diff(amount_out, expected_amount_out) <= U256::one());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,182 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use ethers::{
+    abi::Token,
+    contract::Multicall,
+    providers::Middleware,
+    types::{BlockNumber, Bytes},
+};
+
+use crate::{amm::uniswap_v3::IErc20, errors::AMMError};
+
+use super::{
+    Bin, ILBPair, LBPair, StaticFeeParameters, VariableFeeParameters, BIN_WINDOW, MAX_BIN_ID,
+};
+
+pub async fn get_lb_pair_data_batch_request<M: Middleware>(
+    pool: &mut LBPair,
+    block_number: Option<u64>,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    //The pair data and the bins are read at the same block
+    let block_number = match block_number {
+        Some(block_number) => block_number,
+        None => middleware
+            .get_block_number()
+            .await
+            .map_err(AMMError::MiddlewareError)?
+            .as_u64(),
+    };
+
+    let pair = ILBPair::new(pool.address, middleware.clone());
+    let mut multicall = Multicall::new(middleware.clone(), None)
+        .await?
+        .block(BlockNumber::from(block_number));
+
+    multicall.add_call(pair.get_token_x(), false);
+    multicall.add_call(pair.get_token_y(), false);
+    multicall.add_call(pair.get_bin_step(), false);
+    multicall.add_call(pair.get_static_fee_parameters(), false);
+
+    let results = multicall.call_raw().await?;
+    decode_pair_data(pool, &results).ok_or(AMMError::BatchRequestError(pool.address))?;
+
+    multicall.clear_calls();
+    for token in [pool.token_x, pool.token_y] {
+        multicall.add_call(IErc20::new(token, middleware.clone()).decimals(), false);
+    }
+
+    let decimals = multicall
+        .call_raw()
+        .await?
+        .into_iter()
+        .map(|result| {
+            result
+                .ok()
+                .and_then(|decimals| decimals.into_uint())
+                .map(|decimals| decimals.as_u32() as u8)
+        })
+        .collect::<Option<Vec<u8>>>()
+        .ok_or(AMMError::BatchRequestError(pool.address))?;
+
+    pool.token_x_decimals = decimals[0];
+    pool.token_y_decimals = decimals[1];
+
+    sync_lb_pair_batch_request(pool, Some(block_number), middleware).await
+}
+
+//Syncs the active id, the variable fee parameters and the bins within `BIN_WINDOW` of the active bin
+pub async fn sync_lb_pair_batch_request<M: Middleware>(
+    pool: &mut LBPair,
+    block_number: Option<u64>,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    let block_number = match block_number {
+        Some(block_number) => block_number,
+        None => middleware
+            .get_block_number()
+            .await
+            .map_err(AMMError::MiddlewareError)?
+            .as_u64(),
+    };
+
+    let pair = ILBPair::new(pool.address, middleware.clone());
+    let mut multicall = Multicall::new(middleware.clone(), None)
+        .await?
+        .block(BlockNumber::from(block_number));
+
+    multicall.add_call(pair.get_active_id(), false);
+    multicall.add_call(pair.get_variable_fee_parameters(), false);
+
+    let results = multicall.call_raw().await?;
+    decode_active_bin_data(pool, &results).ok_or(AMMError::SyncError(pool.address))?;
+
+    let ids = pool.active_id.saturating_sub(BIN_WINDOW)
+        ..=pool.active_id.saturating_add(BIN_WINDOW).min(MAX_BIN_ID);
+
+    multicall.clear_calls();
+    for id in ids.clone() {
+        multicall.add_call(pair.get_bin(id), false);
+    }
+
+    let bins = ids
+        .zip(multicall.call_raw().await?)
+        .map(|(id, result)| {
+            let reserves = result.ok()?.into_tuple()?;
+            let reserve_x = reserves.first()?.clone().into_uint()?.as_u128();
+            let reserve_y = reserves.get(1)?.clone().into_uint()?.as_u128();
+
+            Some((id, Bin::new(reserve_x, reserve_y)))
+        })
+        .collect::<Option<BTreeMap<u32, Bin>>>()
+        .ok_or(AMMError::SyncError(pool.address))?;
+
+    pool.bins = bins;
+
+    Ok(())
+}
+
+fn decode_pair_data(pool: &mut LBPair, results: &[Result<Token, Bytes>]) -> Option<()> {
+    pool.token_x = results.first()?.as_ref().ok()?.clone().into_address()?;
+    pool.token_y = results.get(1)?.as_ref().ok()?.clone().into_address()?;
+    pool.bin_step = results.get(2)?.as_ref().ok()?.clone().into_uint()?.as_u32() as u16;
+
+    let static_fee_parameters = results
+        .get(3)?
+        .as_ref()
+        .ok()?
+        .clone()
+        .into_tuple()?
+        .into_iter()
+        .map(|parameter| parameter.into_uint().map(|parameter| parameter.as_u32()))
+        .collect::<Option<Vec<u32>>>()?;
+
+    if static_fee_parameters.len() != 7 {
+        return None;
+    }
+
+    pool.static_fee_parameters = StaticFeeParameters {
+        base_factor: static_fee_parameters[0] as u16,
+        filter_period: static_fee_parameters[1] as u16,
+        decay_period: static_fee_parameters[2] as u16,
+        reduction_factor: static_fee_parameters[3] as u16,
+        variable_fee_control: static_fee_parameters[4],
+        protocol_share: static_fee_parameters[5] as u16,
+        max_volatility_accumulator: static_fee_parameters[6],
+    };
+
+    Some(())
+}
+
+fn decode_active_bin_data(pool: &mut LBPair, results: &[Result<Token, Bytes>]) -> Option<()> {
+    pool.active_id = results
+        .first()?
+        .as_ref()
+        .ok()?
+        .clone()
+        .into_uint()?
+        .as_u32();
+
+    let variable_fee_parameters = results
+        .get(1)?
+        .as_ref()
+        .ok()?
+        .clone()
+        .into_tuple()?
+        .into_iter()
+        .map(|parameter| parameter.into_uint().map(|parameter| parameter.as_u64()))
+        .collect::<Option<Vec<u64>>>()?;
+
+    if variable_fee_parameters.len() != 4 {
+        return None;
+    }
+
+    pool.variable_fee_parameters = VariableFeeParameters {
+        volatility_accumulator: variable_fee_parameters[0] as u32,
+        volatility_reference: variable_fee_parameters[1] as u32,
+        id_reference: variable_fee_parameters[2] as u32,
+        time_of_last_update: variable_fee_parameters[3],
+    };
+
+    Some(())
+}
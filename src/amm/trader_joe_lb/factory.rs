@@ -0,0 +1,143 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::{
+    abi::RawLog,
+    prelude::{abigen, EthEvent},
+    providers::Middleware,
+    types::{Log, H160, H256, U256},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    amm::{
+        factory::{AutomatedMarketMakerFactory, Factory},
+        AMM,
+    },
+    errors::AMMError,
+};
+
+use super::{batch_request, LBPair};
+
+abigen!(
+    ILBFactory,
+    r#"[
+        function getNumberOfLBPairs() external view returns (uint256)
+        function getLBPairAtIndex(uint256 index) external view returns (address)
+    ]"#;
+);
+
+//Declared by hand so that the name of the generated type does not depend on how the acronym is cased
+#[derive(Clone, Debug, EthEvent)]
+#[ethevent(
+    name = "LBPairCreated",
+    abi = "LBPairCreated(address,address,uint256,address,uint256)"
+)]
+pub struct LBPairCreatedFilter {
+    #[ethevent(indexed)]
+    pub token_x: H160,
+    #[ethevent(indexed)]
+    pub token_y: H160,
+    #[ethevent(indexed)]
+    pub bin_step: U256,
+    pub pair: H160,
+    pub pid: U256,
+}
+
+pub const LB_PAIR_CREATED_EVENT_SIGNATURE: H256 = H256([
+    44, 141, 16, 75, 39, 198, 183, 244, 73, 32, 23, 166, 245, 207, 56, 3, 4, 54, 136, 147, 78, 188,
+    170, 106, 3, 84, 11, 238, 175, 151, 106, 255,
+]);
+
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct LBFactory {
+    pub address: H160,
+    pub creation_block: u64,
+}
+
+#[async_trait]
+impl AutomatedMarketMakerFactory for LBFactory {
+    fn address(&self) -> H160 {
+        self.address
+    }
+
+    fn creation_block(&self) -> u64 {
+        self.creation_block
+    }
+
+    fn amm_created_event_signature(&self) -> H256 {
+        LB_PAIR_CREATED_EVENT_SIGNATURE
+    }
+
+    async fn new_amm_from_log<M: 'static + Middleware>(
+        &self,
+        log: Log,
+        middleware: Arc<M>,
+    ) -> Result<AMM, AMMError<M>> {
+        let lb_pair_created_event = LBPairCreatedFilter::decode_log(&RawLog::from(log))?;
+
+        Ok(AMM::LBPair(
+            LBPair::new_from_address(lb_pair_created_event.pair, middleware).await?,
+        ))
+    }
+
+    fn new_empty_amm_from_log(&self, log: Log) -> Result<AMM, ethers::abi::Error> {
+        let lb_pair_created_event = LBPairCreatedFilter::decode_log(&RawLog::from(log))?;
+
+        Ok(AMM::LBPair(LBPair {
+            address: lb_pair_created_event.pair,
+            token_x: lb_pair_created_event.token_x,
+            token_y: lb_pair_created_event.token_y,
+            bin_step: bin_step(lb_pair_created_event.bin_step),
+            ..Default::default()
+        }))
+    }
+
+    async fn get_all_amms<M: 'static + Middleware>(
+        &self,
+        to_block: Option<u64>,
+        middleware: Arc<M>,
+        step: u64,
+    ) -> Result<Vec<AMM>, AMMError<M>> {
+        if let Some(block) = to_block {
+            Factory::LBFactory(*self)
+                .get_all_pools_from_logs(self.creation_block, block, step, middleware)
+                .await
+        } else {
+            return Err(AMMError::BlockNumberNotFound);
+        }
+    }
+
+    async fn populate_amm_data<M: Middleware>(
+        &self,
+        amms: &mut [AMM],
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        for amm in amms {
+            if let AMM::LBPair(pool) = amm {
+                batch_request::get_lb_pair_data_batch_request(
+                    pool,
+                    block_number,
+                    middleware.clone(),
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl LBFactory {
+    pub fn new(address: H160, creation_block: u64) -> LBFactory {
+        LBFactory {
+            address,
+            creation_block,
+        }
+    }
+}
+
+fn bin_step(bin_step: U256) -> u16 {
+    bin_step.low_u32() as u16
+}
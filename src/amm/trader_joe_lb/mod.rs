@@ -0,0 +1,690 @@
+pub mod batch_request;
+pub mod factory;
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use async_trait::async_trait;
+use ethers::{
+    abi::RawLog,
+    prelude::{abigen, EthEvent},
+    providers::Middleware,
+    types::{Log, H160, H256, U256, U512},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    amm::{price::Price, AutomatedMarketMaker},
+    errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
+};
+
+abigen!(
+    ILBPair,
+    r#"[
+        function getTokenX() external view returns (address)
+        function getTokenY() external view returns (address)
+        function getBinStep() external view returns (uint16)
+        function getActiveId() external view returns (uint24)
+        function getBin(uint24 id) external view returns (uint128 binReserveX, uint128 binReserveY)
+        function getStaticFeeParameters() external view returns (uint16 baseFactor, uint16 filterPeriod, uint16 decayPeriod, uint16 reductionFactor, uint24 variableFeeControl, uint16 protocolShare, uint24 maxVolatilityAccumulator)
+        function getVariableFeeParameters() external view returns (uint24 volatilityAccumulator, uint24 volatilityReference, uint24 idReference, uint40 timeOfLastUpdate)
+        function getSwapOut(uint128 amountIn, bool swapForY) external view returns (uint128 amountInLeft, uint128 amountOut, uint128 fee)
+        event Swap(address indexed sender, address indexed to, uint24 id, bytes32 amountsIn, bytes32 amountsOut, uint24 volatilityAccumulator, bytes32 totalFees, bytes32 protocolFees)
+        event DepositedToBins(address indexed sender, address indexed to, uint256[] ids, bytes32[] amounts)
+        event WithdrawnFromBins(address indexed sender, address indexed to, uint256[] ids, bytes32[] amounts)
+    ]"#;
+);
+
+pub const SWAP_EVENT_SIGNATURE: H256 = H256([
+    173, 125, 111, 151, 171, 245, 28, 225, 142, 23, 163, 143, 77, 112, 233, 117, 190, 156, 7, 8,
+    71, 73, 135, 187, 62, 38, 173, 33, 189, 147, 202, 112,
+]);
+
+pub const DEPOSITED_TO_BINS_EVENT_SIGNATURE: H256 = H256([
+    135, 241, 249, 220, 245, 232, 8, 154, 62, 0, 129, 27, 106, 0, 141, 143, 48, 41, 58, 61, 168,
+    120, 203, 31, 232, 201, 12, 163, 118, 64, 47, 138,
+]);
+
+pub const WITHDRAWN_FROM_BINS_EVENT_SIGNATURE: H256 = H256([
+    163, 46, 20, 104, 68, 214, 20, 74, 34, 233, 76, 88, 103, 21, 161, 49, 125, 88, 168, 170, 53,
+    129, 236, 51, 208, 64, 17, 61, 220, 178, 67, 80,
+]);
+
+//Bin prices are 128.128 fixed point numbers
+pub const SCALE_OFFSET: usize = 128;
+pub const SCALE: U256 = U256([0, 0, 1, 0]);
+pub const BASIS_POINT_MAX: u32 = 10_000;
+//Fees are expressed with 18 decimals
+pub const PRECISION: U256 = U256([1000000000000000000, 0, 0, 0]);
+//The bin with id 2^23 has a price of 1
+pub const REAL_ID_SHIFT: i32 = 1 << 23;
+pub const MAX_BIN_ID: u32 = (1 << 24) - 1;
+//Number of bins synced on each side of the active bin
+pub const BIN_WINDOW: u32 = 50;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bin {
+    pub reserve_x: u128,
+    pub reserve_y: u128,
+}
+
+impl Bin {
+    pub fn new(reserve_x: u128, reserve_y: u128) -> Bin {
+        Bin {
+            reserve_x,
+            reserve_y,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reserve_x == 0 && self.reserve_y == 0
+    }
+
+    fn reserve_out(&self, swap_for_y: bool) -> u128 {
+        if swap_for_y {
+            self.reserve_y
+        } else {
+            self.reserve_x
+        }
+    }
+
+    fn add(&mut self, (amount_x, amount_y): (u128, u128)) {
+        self.reserve_x = self.reserve_x.saturating_add(amount_x);
+        self.reserve_y = self.reserve_y.saturating_add(amount_y);
+    }
+
+    fn sub(&mut self, (amount_x, amount_y): (u128, u128)) {
+        self.reserve_x = self.reserve_x.saturating_sub(amount_x);
+        self.reserve_y = self.reserve_y.saturating_sub(amount_y);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct StaticFeeParameters {
+    pub base_factor: u16,
+    pub filter_period: u16,
+    pub decay_period: u16,
+    pub reduction_factor: u16,
+    pub variable_fee_control: u32,
+    pub protocol_share: u16,
+    pub max_volatility_accumulator: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct VariableFeeParameters {
+    pub volatility_accumulator: u32,
+    pub volatility_reference: u32,
+    pub id_reference: u32,
+    pub time_of_last_update: u64,
+}
+
+//Liquidity Book pairs split their liquidity into bins of constant price, only the bins within `BIN_WINDOW` of the active bin are tracked.
+//Swaps are simulated as if they happen in the block of the last update, the volatility references are only refreshed on sync.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LBPair {
+    pub address: H160,
+    pub token_x: H160,
+    pub token_x_decimals: u8,
+    pub token_y: H160,
+    pub token_y_decimals: u8,
+    pub bin_step: u16,
+    pub active_id: u32,
+    pub static_fee_parameters: StaticFeeParameters,
+    pub variable_fee_parameters: VariableFeeParameters,
+    pub bins: BTreeMap<u32, Bin>,
+}
+
+#[async_trait]
+impl AutomatedMarketMaker for LBPair {
+    fn address(&self) -> H160 {
+        self.address
+    }
+
+    async fn sync<M: Middleware>(&mut self, middleware: Arc<M>) -> Result<(), AMMError<M>> {
+        batch_request::sync_lb_pair_batch_request(self, None, middleware).await
+    }
+
+    async fn populate_data<M: Middleware>(
+        &mut self,
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        batch_request::get_lb_pair_data_batch_request(self, block_number, middleware).await
+    }
+
+    fn sync_on_event_signatures(&self) -> Vec<H256> {
+        vec![
+            SWAP_EVENT_SIGNATURE,
+            DEPOSITED_TO_BINS_EVENT_SIGNATURE,
+            WITHDRAWN_FROM_BINS_EVENT_SIGNATURE,
+        ]
+    }
+
+    fn sync_from_log(&mut self, log: Log) -> Result<(), EventLogError> {
+        let event_signature = log.topics[0];
+
+        if event_signature == SWAP_EVENT_SIGNATURE {
+            let swap_event = SwapFilter::decode_log(&RawLog::from(log))?;
+            self.sync_from_swap_event(swap_event);
+        } else if event_signature == DEPOSITED_TO_BINS_EVENT_SIGNATURE {
+            let deposit_event = DepositedToBinsFilter::decode_log(&RawLog::from(log))?;
+            for (id, amounts) in deposit_event.ids.iter().zip(deposit_event.amounts) {
+                if let Some(bin) = self.bins.get_mut(&id.as_u32()) {
+                    bin.add(decode_amounts(amounts));
+                }
+            }
+        } else if event_signature == WITHDRAWN_FROM_BINS_EVENT_SIGNATURE {
+            let withdraw_event = WithdrawnFromBinsFilter::decode_log(&RawLog::from(log))?;
+            for (id, amounts) in withdraw_event.ids.iter().zip(withdraw_event.amounts) {
+                if let Some(bin) = self.bins.get_mut(&id.as_u32()) {
+                    bin.sub(decode_amounts(amounts));
+                }
+            }
+        } else {
+            Err(EventLogError::InvalidEventSignature)?
+        }
+
+        Ok(())
+    }
+
+    fn tokens(&self) -> Vec<H160> {
+        vec![self.token_x, self.token_y]
+    }
+
+    //Price of the active bin
+    fn calculate_price(&self, base_token: H160) -> Result<Price, ArithmeticError> {
+        self.check_liquidity()?;
+
+        let exponent = self.active_id as i32 - REAL_ID_SHIFT;
+        let price = (1.0 + self.bin_step as f64 / BASIS_POINT_MAX as f64).powi(exponent)
+            * 10_f64.powi(self.token_x_decimals as i32 - self.token_y_decimals as i32);
+
+        if base_token == self.token_x {
+            Price::from_f64(price)
+        } else if base_token == self.token_y {
+            Price::from_f64(1.0 / price)
+        } else {
+            Err(ArithmeticError::TokenNotInPool(base_token))
+        }
+    }
+
+    fn simulate_swap(&self, token_in: H160, amount_in: U256) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        self.check_liquidity()?;
+
+        let (amount_out, _) = self.swap(self.swap_for_y(token_in)?, amount_in)?;
+
+        tracing::trace!(?amount_out);
+
+        Ok(amount_out)
+    }
+
+    fn simulate_swap_mut(
+        &mut self,
+        token_in: H160,
+        amount_in: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        self.check_liquidity()?;
+
+        let (amount_out, (updated_bins, active_id, volatility_accumulator)) =
+            self.swap(self.swap_for_y(token_in)?, amount_in)?;
+
+        tracing::trace!(?amount_out);
+        tracing::trace!(self.active_id, "active id before");
+
+        self.bins.extend(updated_bins);
+        self.active_id = active_id;
+        self.variable_fee_parameters.volatility_accumulator = volatility_accumulator;
+
+        tracing::trace!(self.active_id, "active id after");
+
+        Ok(amount_out)
+    }
+
+    //Fee of a swap in the active bin, the fee grows for each bin that a swap crosses
+    fn swap_fee(&self, _token_in: H160) -> f64 {
+        self.total_fee(self.volatility_accumulator_at(self.active_id))
+            .as_u128() as f64
+            / PRECISION.as_u128() as f64
+    }
+
+    fn token_decimals(&self) -> Vec<u8> {
+        vec![self.token_x_decimals, self.token_y_decimals]
+    }
+
+    fn get_token_out(&self, token_in: H160) -> H160 {
+        if self.token_x == token_in {
+            self.token_y
+        } else {
+            self.token_x
+        }
+    }
+}
+
+impl LBPair {
+    //Creates a new instance of the pair from the pair address, and syncs the pair data
+    pub async fn new_from_address<M: Middleware>(
+        pair_address: H160,
+        middleware: Arc<M>,
+    ) -> Result<Self, AMMError<M>> {
+        let mut pool = LBPair {
+            address: pair_address,
+            ..Default::default()
+        };
+
+        pool.populate_data(None, middleware).await?;
+
+        if !pool.data_is_populated() {
+            return Err(AMMError::PoolDataError);
+        }
+
+        Ok(pool)
+    }
+
+    pub fn data_is_populated(&self) -> bool {
+        !(self.token_x.is_zero() || self.token_y.is_zero() || self.bin_step == 0)
+    }
+
+    //Base fee plus the variable fee for the volatility accumulator, with 18 decimals
+    pub fn total_fee(&self, volatility_accumulator: u32) -> U256 {
+        let bin_step = U256::from(self.bin_step);
+        let base_fee =
+            U256::from(self.static_fee_parameters.base_factor) * bin_step * U256::exp10(10);
+
+        let variable_fee_control = U256::from(self.static_fee_parameters.variable_fee_control);
+        let variable_fee = if variable_fee_control.is_zero() {
+            U256::zero()
+        } else {
+            let prod = U256::from(volatility_accumulator) * bin_step;
+            (prod * prod * variable_fee_control + 99) / 100
+        };
+
+        base_fee + variable_fee
+    }
+
+    //Volatility accumulator of a swap crossing the bin, the references are assumed to be up to date
+    fn volatility_accumulator_at(&self, id: u32) -> u32 {
+        let delta_id = id.abs_diff(self.variable_fee_parameters.id_reference) as u64;
+        let volatility_accumulator = self.variable_fee_parameters.volatility_reference as u64
+            + delta_id * BASIS_POINT_MAX as u64;
+
+        volatility_accumulator.min(self.static_fee_parameters.max_volatility_accumulator as u64)
+            as u32
+    }
+
+    fn swap_for_y(&self, token_in: H160) -> Result<bool, SwapSimulationError> {
+        if token_in == self.token_x {
+            Ok(true)
+        } else if token_in == self.token_y {
+            Ok(false)
+        } else {
+            Err(SwapSimulationError::TokenNotInPool(token_in))
+        }
+    }
+
+    //The active id of a pair without any reserves in its bins is only the id that the pair was created at
+    fn check_liquidity(&self) -> Result<(), ArithmeticError> {
+        if self
+            .bins
+            .values()
+            .all(|bin| bin.reserve_x == 0 && bin.reserve_y == 0)
+        {
+            return Err(ArithmeticError::ZeroLiquidity(self.address));
+        }
+
+        Ok(())
+    }
+
+    //Walks the bins from the active bin like `swap` in the pair contract, returns the amount out along with the (updated bins, active id, volatility accumulator).
+    //If the tracked bins run out of liquidity the amount out of the bins that were crossed is returned, like `getSwapOut`.
+    #[allow(clippy::type_complexity)]
+    fn swap(
+        &self,
+        swap_for_y: bool,
+        amount_in: U256,
+    ) -> Result<(U256, (Vec<(u32, Bin)>, u32, u32)), SwapSimulationError> {
+        let mut active_id = self.active_id;
+        let mut volatility_accumulator = self.variable_fee_parameters.volatility_accumulator;
+        let mut updated_bins = vec![];
+
+        if amount_in > U256::from(u128::MAX) {
+            return Err(ArithmeticError::U128ConversionError)?;
+        }
+
+        let mut amount_in_left = amount_in;
+        let mut amount_out = U256::zero();
+
+        //X is sold into the bins below the active bin and Y into the bins above it
+        let bins: Box<dyn Iterator<Item = (&u32, &Bin)>> = if swap_for_y {
+            Box::new(self.bins.range(..=self.active_id).rev())
+        } else {
+            Box::new(self.bins.range(self.active_id..))
+        };
+
+        for (&id, bin) in bins {
+            if amount_in_left.is_zero() {
+                break;
+            }
+
+            if bin.reserve_out(swap_for_y) == 0 {
+                continue;
+            }
+
+            volatility_accumulator = self.volatility_accumulator_at(id);
+            let total_fee = self.total_fee(volatility_accumulator);
+
+            let (amount_in_with_fees, amount_out_of_bin, fee) = get_amounts(
+                bin,
+                self.bin_step,
+                swap_for_y,
+                id,
+                total_fee,
+                amount_in_left,
+            )?;
+
+            if amount_in_with_fees.is_zero() {
+                continue;
+            }
+
+            amount_in_left -= amount_in_with_fees;
+            amount_out += amount_out_of_bin;
+
+            //The protocol share of the fee is not added to the bin
+            let protocol_fee = fee * U256::from(self.static_fee_parameters.protocol_share)
+                / U256::from(BASIS_POINT_MAX);
+            let amount_in_to_bin = (amount_in_with_fees - protocol_fee).as_u128();
+            let amount_out_of_bin = amount_out_of_bin.as_u128();
+
+            let mut bin = *bin;
+            if swap_for_y {
+                bin.add((amount_in_to_bin, 0));
+                bin.sub((0, amount_out_of_bin));
+            } else {
+                bin.add((0, amount_in_to_bin));
+                bin.sub((amount_out_of_bin, 0));
+            }
+
+            updated_bins.push((id, bin));
+            active_id = id;
+        }
+
+        Ok((
+            amount_out,
+            (updated_bins, active_id, volatility_accumulator),
+        ))
+    }
+
+    fn sync_from_swap_event(&mut self, swap_event: SwapFilter) {
+        if let Some(bin) = self.bins.get_mut(&swap_event.id) {
+            let (amount_in_x, amount_in_y) = decode_amounts(swap_event.amounts_in);
+            let (protocol_fee_x, protocol_fee_y) = decode_amounts(swap_event.protocol_fees);
+
+            bin.add((amount_in_x - protocol_fee_x, amount_in_y - protocol_fee_y));
+            bin.sub(decode_amounts(swap_event.amounts_out));
+        }
+
+        self.active_id = swap_event.id;
+        self.variable_fee_parameters.volatility_accumulator = swap_event.volatility_accumulator;
+    }
+}
+
+//Amounts are packed as | y (128) | x (128) |
+pub fn decode_amounts(packed: [u8; 32]) -> (u128, u128) {
+    let packed = U256::from_big_endian(&packed);
+
+    (packed.low_u128(), (packed >> 128).low_u128())
+}
+
+pub fn get_price_from_id(id: u32, bin_step: u16) -> Result<U256, ArithmeticError> {
+    let base = SCALE + (U256::from(bin_step) << SCALE_OFFSET) / U256::from(BASIS_POINT_MAX);
+
+    pow(base, id as i32 - REAL_ID_SHIFT)
+}
+
+//Mirrors `Uint128x128Math.pow`, the rounding has to match the contract for the bin prices to be exact
+fn pow(x: U256, y: i32) -> Result<U256, ArithmeticError> {
+    if y == 0 {
+        return Ok(SCALE);
+    }
+
+    let mut invert = y < 0;
+    let abs_y = y.unsigned_abs();
+    let mut result = U256::zero();
+
+    if abs_y < 0x100000 {
+        result = SCALE;
+        let mut squared = x;
+
+        if x > U256::from(u128::MAX) {
+            squared = U256::MAX / squared;
+            invert = !invert;
+        }
+
+        for bit in 0..20 {
+            if abs_y & (1 << bit) != 0 {
+                result = (result * squared) >> SCALE_OFFSET;
+            }
+            squared = (squared * squared) >> SCALE_OFFSET;
+        }
+    }
+
+    if result.is_zero() {
+        return Err(ArithmeticError::PowUnderflow);
+    }
+
+    if invert {
+        Ok(U256::MAX / result)
+    } else {
+        Ok(result)
+    }
+}
+
+//Mirrors `BinHelper.getAmounts`, returns the (amount in with fees, amount out, fee) of swapping through a single bin
+fn get_amounts(
+    bin: &Bin,
+    bin_step: u16,
+    swap_for_y: bool,
+    id: u32,
+    total_fee: U256,
+    amount_in_left: U256,
+) -> Result<(U256, U256, U256), ArithmeticError> {
+    let price = get_price_from_id(id, bin_step)?;
+    let bin_reserve_out = U256::from(bin.reserve_out(swap_for_y));
+
+    let mut max_amount_in = if swap_for_y {
+        shift_div_round_up(bin_reserve_out, price)?
+    } else {
+        mul_shift_round_up(bin_reserve_out, price)?
+    };
+
+    let max_fee = get_fee_amount(max_amount_in, total_fee);
+    max_amount_in += max_fee;
+
+    if amount_in_left >= max_amount_in {
+        Ok((max_amount_in, bin_reserve_out, max_fee))
+    } else {
+        let fee = get_fee_amount_from(amount_in_left, total_fee);
+        let amount_in = amount_in_left - fee;
+
+        let amount_out = if swap_for_y {
+            mul_shift_round_down(amount_in, price)?
+        } else {
+            shift_div_round_down(amount_in, price)?
+        };
+
+        Ok((amount_in_left, amount_out.min(bin_reserve_out), fee))
+    }
+}
+
+//Fee to add to an amount that does not include fees
+fn get_fee_amount(amount: U256, total_fee: U256) -> U256 {
+    let denominator = PRECISION - total_fee;
+    (amount * total_fee + denominator - 1) / denominator
+}
+
+//Fee included in an amount with fees
+fn get_fee_amount_from(amount_with_fees: U256, total_fee: U256) -> U256 {
+    (amount_with_fees * total_fee + PRECISION - 1) / PRECISION
+}
+
+//The results of the fixed point helpers are checked to fit in 128 bits like in the pair contract
+fn to_u128_checked(value: U512) -> Result<U256, ArithmeticError> {
+    if value > U512::from(u128::MAX) {
+        Err(ArithmeticError::U128ConversionError)
+    } else {
+        Ok(U256::from(value.low_u128()))
+    }
+}
+
+fn mul_shift_round_down(x: U256, y: U256) -> Result<U256, ArithmeticError> {
+    to_u128_checked((U512::from(x) * U512::from(y)) >> SCALE_OFFSET)
+}
+
+fn mul_shift_round_up(x: U256, y: U256) -> Result<U256, ArithmeticError> {
+    let prod = U512::from(x) * U512::from(y);
+    let mut result = prod >> SCALE_OFFSET;
+
+    if !(prod & ((U512::one() << SCALE_OFFSET) - 1)).is_zero() {
+        result += U512::one();
+    }
+
+    to_u128_checked(result)
+}
+
+fn shift_div_round_down(x: U256, y: U256) -> Result<U256, ArithmeticError> {
+    if y.is_zero() {
+        return Err(ArithmeticError::YIsZero);
+    }
+
+    to_u128_checked((U512::from(x) << SCALE_OFFSET) / U512::from(y))
+}
+
+fn shift_div_round_up(x: U256, y: U256) -> Result<U256, ArithmeticError> {
+    if y.is_zero() {
+        return Err(ArithmeticError::YIsZero);
+    }
+
+    let numerator = U512::from(x) << SCALE_OFFSET;
+    let denominator = U512::from(y);
+    let mut result = numerator / denominator;
+
+    if !(numerator % denominator).is_zero() {
+        result += U512::one();
+    }
+
+    to_u128_checked(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use ethers::types::{H160, U256};
+
+    use crate::amm::AutomatedMarketMaker;
+
+    use super::{
+        decode_amounts, get_price_from_id, Bin, LBPair, StaticFeeParameters, VariableFeeParameters,
+        REAL_ID_SHIFT, SCALE,
+    };
+
+    const ACTIVE_ID: u32 = REAL_ID_SHIFT as u32;
+
+    fn pair(bins: Vec<(u32, Bin)>, variable_fee_control: u32) -> LBPair {
+        LBPair {
+            address: H160::repeat_byte(0x01),
+            token_x: H160::repeat_byte(0x02),
+            token_x_decimals: 18,
+            token_y: H160::repeat_byte(0x03),
+            token_y_decimals: 18,
+            bin_step: 25,
+            active_id: ACTIVE_ID,
+            static_fee_parameters: StaticFeeParameters {
+                base_factor: 5000,
+                filter_period: 30,
+                decay_period: 600,
+                reduction_factor: 5000,
+                variable_fee_control,
+                protocol_share: 1000,
+                max_volatility_accumulator: 350000,
+            },
+            variable_fee_parameters: VariableFeeParameters {
+                id_reference: ACTIVE_ID,
+                ..Default::default()
+            },
+            bins: BTreeMap::from_iter(bins),
+        }
+    }
+
+    #[test]
+    fn test_get_price_from_id() {
+        assert_eq!(get_price_from_id(ACTIVE_ID, 25).unwrap(), SCALE);
+
+        assert_eq!(
+            get_price_from_id(ACTIVE_ID + 100, 25).unwrap(),
+            U256::from_dec_str("436794915378552100798054128165989473614").unwrap()
+        );
+
+        assert_eq!(
+            get_price_from_id(ACTIVE_ID - 100, 25).unwrap(),
+            U256::from_dec_str("265094865257220261526334763469518204397").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_simulate_swap_single_bin() {
+        let pair = pair(vec![(ACTIVE_ID, Bin::new(0, 10_u128.pow(21)))], 0);
+
+        //0.125% base fee at a price of 1
+        assert_eq!(pair.total_fee(0), U256::from(1250000000000000_u64));
+
+        let amount_out = pair.simulate_swap(pair.token_x, U256::exp10(18)).unwrap();
+        assert_eq!(amount_out, U256::from(998750000000000000_u64));
+
+        //There is no X in the active bin or above it
+        let amount_out = pair.simulate_swap(pair.token_y, U256::exp10(18)).unwrap();
+        assert_eq!(amount_out, U256::zero());
+    }
+
+    #[test]
+    fn test_simulate_swap_mut_crosses_bins() {
+        let mut pair = pair(
+            vec![
+                (ACTIVE_ID - 1, Bin::new(0, 10_u128.pow(21))),
+                (ACTIVE_ID, Bin::new(0, 5 * 10_u128.pow(17))),
+            ],
+            40000,
+        );
+
+        let amount_out = pair
+            .simulate_swap_mut(pair.token_x, U256::exp10(18))
+            .unwrap();
+
+        assert_eq!(amount_out, U256::from(997493781191576752_u64));
+        assert_eq!(pair.active_id, ACTIVE_ID - 1);
+        assert_eq!(pair.variable_fee_parameters.volatility_accumulator, 10000);
+
+        assert_eq!(
+            pair.bins[&ACTIVE_ID],
+            Bin::new(500625782227784731 - 62578222778473, 0)
+        );
+        assert_eq!(
+            pair.bins[&(ACTIVE_ID - 1)],
+            Bin::new(
+                499374217772215269 - 63670212765957,
+                10_u128.pow(21) - 497493781191576752
+            )
+        );
+    }
+
+    #[test]
+    fn test_decode_amounts() {
+        let mut packed = [0u8; 32];
+        packed[15] = 2;
+        packed[31] = 1;
+
+        assert_eq!(decode_amounts(packed), (1, 2));
+    }
+}
@@ -1,18 +1,22 @@
 use ethers::{
-    abi::{ParamType, Token},
+    abi::{decode, ParamType, Token},
+    contract::Multicall,
     providers::Middleware,
-    types::{Bytes, H160, U256},
+    types::{BlockId, Bytes, H160, U256},
 };
 use std::sync::Arc;
 
 use crate::{
-    amm::{AutomatedMarketMaker, AMM},
+    amm::{
+        batch_backend::{aggregate_3, decode_decimals},
+        AutomatedMarketMaker, AMM,
+    },
     errors::AMMError,
 };
 
 use ethers::prelude::abigen;
 
-use super::UniswapV2Pool;
+use super::{factory::IUniswapV2Factory, IErc20, IUniswapV2Pair, UniswapV2Pool};
 
 abigen!(
 
@@ -41,6 +45,7 @@ pub async fn get_pairs_batch_request<M: Middleware>(
     factory: H160,
     from: U256,
     step: U256,
+    block_number: Option<u64>,
     middleware: Arc<M>,
 ) -> Result<Vec<H160>, AMMError<M>> {
     tracing::info!("getting pairs {}-{}", from, step);
@@ -54,7 +59,11 @@ pub async fn get_pairs_batch_request<M: Middleware>(
     ]);
 
     let deployer = IGetUniswapV2PairsBatchRequest::deploy(middleware, constructor_args)?;
-    let return_data: Bytes = deployer.call_raw().await?;
+    let return_data: Bytes = if let Some(block_number) = block_number {
+        deployer.block(block_number).call_raw().await?
+    } else {
+        deployer.call_raw().await?
+    };
 
     let return_data_tokens = ethers::abi::decode(
         &[ParamType::Array(Box::new(ParamType::Address))],
@@ -78,9 +87,10 @@ pub async fn get_pairs_batch_request<M: Middleware>(
 
 pub async fn get_amm_data_batch_request<M: Middleware>(
     amms: &mut [AMM],
+    block_number: Option<u64>,
     middleware: Arc<M>,
 ) -> Result<(), AMMError<M>> {
-    tracing::info!("getting data for {} AMMs", amms.len());
+    tracing::info!(block_number, "getting data for {} AMMs", amms.len());
 
     let mut target_addresses = vec![];
     for amm in amms.iter() {
@@ -91,7 +101,11 @@ pub async fn get_amm_data_batch_request<M: Middleware>(
 
     let deployer = IGetUniswapV2PoolDataBatchRequest::deploy(middleware.clone(), constructor_args)?;
 
-    let return_data: Bytes = deployer.call_raw().await?;
+    let return_data: Bytes = if let Some(block_number) = block_number {
+        deployer.block(block_number).call_raw().await?
+    } else {
+        deployer.call_raw().await?
+    };
     let return_data_tokens = ethers::abi::decode(
         &[ParamType::Array(Box::new(ParamType::Tuple(vec![
             ParamType::Address,   // token a
@@ -140,6 +154,7 @@ pub async fn get_amm_data_batch_request<M: Middleware>(
 
 pub async fn get_v2_pool_data_batch_request<M: Middleware>(
     pool: &mut UniswapV2Pool,
+    block_number: Option<u64>,
     middleware: Arc<M>,
 ) -> Result<(), AMMError<M>> {
     tracing::info!(?pool.address, "getting pool data");
@@ -147,7 +162,11 @@ pub async fn get_v2_pool_data_batch_request<M: Middleware>(
 
     let deployer = IGetUniswapV2PoolDataBatchRequest::deploy(middleware.clone(), constructor_args)?;
 
-    let return_data: Bytes = deployer.call_raw().await?;
+    let return_data: Bytes = if let Some(block_number) = block_number {
+        deployer.block(block_number).call_raw().await?
+    } else {
+        deployer.call_raw().await?
+    };
     let return_data_tokens = ethers::abi::decode(
         &[ParamType::Array(Box::new(ParamType::Tuple(vec![
             ParamType::Address,   // token a
@@ -175,3 +194,135 @@ pub async fn get_v2_pool_data_batch_request<M: Middleware>(
 
     Ok(())
 }
+
+//Same as get_pairs_batch_request, with the allPairs calls of [from, step) batched through the Multicall3 at `multicall_address`
+pub async fn get_pairs_multicall<M: Middleware>(
+    factory: H160,
+    from: U256,
+    step: U256,
+    block_number: Option<u64>,
+    multicall_address: H160,
+    middleware: Arc<M>,
+) -> Result<Vec<H160>, AMMError<M>> {
+    tracing::info!("getting pairs {}-{} through Multicall3", from, step);
+
+    let factory_contract = IUniswapV2Factory::new(factory, middleware.clone());
+    let mut multicall = Multicall::new(middleware, Some(multicall_address)).await?;
+
+    let mut idx = from;
+    while idx < step {
+        multicall.add_call(factory_contract.all_pairs(idx), false);
+        idx += U256::one();
+    }
+
+    let mut pairs = vec![];
+    for return_data in aggregate_3(&mut multicall, block_number.map(BlockId::from)).await? {
+        let pair = return_data
+            .and_then(|return_data| decode(&[ParamType::Address], &return_data).ok())
+            .and_then(|tokens| tokens.into_iter().next()?.into_address())
+            .ok_or(AMMError::BatchRequestError(factory))?;
+
+        if !pair.is_zero() {
+            pairs.push(pair);
+        }
+    }
+
+    Ok(pairs)
+}
+
+//Same as get_amm_data_batch_request, with the calls of the batch contract made through the Multicall3 at `multicall_address`.
+//The tokens and reserves of the pools are read first and then the decimals of their tokens. Pools that the batch contract
+//skips are left empty, as are pools whose calls revert.
+pub async fn get_amm_data_multicall<M: Middleware>(
+    amms: &mut [AMM],
+    block_number: Option<u64>,
+    multicall_address: H160,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    tracing::info!(
+        block_number,
+        "getting data for {} AMMs through Multicall3",
+        amms.len()
+    );
+
+    let block = block_number.map(BlockId::from);
+    let mut multicall = Multicall::new(middleware.clone(), Some(multicall_address)).await?;
+
+    for amm in amms.iter() {
+        let pair = IUniswapV2Pair::new(amm.address(), middleware.clone());
+        multicall.add_call(pair.token_0(), true);
+        multicall.add_call(pair.token_1(), true);
+        multicall.add_call(pair.get_reserves(), true);
+    }
+
+    let pools_data = aggregate_3(&mut multicall, block)
+        .await?
+        .chunks(3)
+        .map(|results| decode_tokens_and_reserves(&results[0], &results[1], &results[2]))
+        .collect::<Vec<Option<(H160, H160, u128, u128)>>>();
+
+    for (token_a, token_b, _, _) in pools_data.iter().flatten() {
+        multicall.add_call(IErc20::new(*token_a, middleware.clone()).decimals(), true);
+        multicall.add_call(IErc20::new(*token_b, middleware.clone()).decimals(), true);
+    }
+
+    if pools_data.iter().all(Option::is_none) {
+        return Ok(());
+    }
+
+    let mut decimals = aggregate_3(&mut multicall, block).await?.into_iter();
+
+    for (amm, pool_data) in amms.iter_mut().zip(pools_data) {
+        let Some((token_a, token_b, reserve_0, reserve_1)) = pool_data else {
+            continue;
+        };
+
+        let token_a_decimals = decode_decimals(decimals.next().flatten().as_deref());
+        let token_b_decimals = decode_decimals(decimals.next().flatten().as_deref());
+
+        if let (AMM::UniswapV2Pool(pool), Some(token_a_decimals), Some(token_b_decimals)) =
+            (amm, token_a_decimals, token_b_decimals)
+        {
+            pool.token_a = token_a;
+            pool.token_a_decimals = token_a_decimals;
+            pool.token_b = token_b;
+            pool.token_b_decimals = token_b_decimals;
+            pool.reserve_0 = reserve_0;
+            pool.reserve_1 = reserve_1;
+            tracing::trace!(?pool);
+        }
+    }
+
+    Ok(())
+}
+
+fn decode_tokens_and_reserves(
+    token_a: &Option<Bytes>,
+    token_b: &Option<Bytes>,
+    reserves: &Option<Bytes>,
+) -> Option<(H160, H160, u128, u128)> {
+    let token_a = decode(&[ParamType::Address], token_a.as_ref()?)
+        .ok()?
+        .pop()?
+        .into_address()?;
+    let token_b = decode(&[ParamType::Address], token_b.as_ref()?)
+        .ok()?
+        .pop()?
+        .into_address()?;
+    let reserves = decode(
+        &[
+            ParamType::Uint(112),
+            ParamType::Uint(112),
+            ParamType::Uint(32),
+        ],
+        reserves.as_ref()?,
+    )
+    .ok()?;
+
+    Some((
+        token_a,
+        token_b,
+        reserves.first()?.clone().into_uint()?.as_u128(),
+        reserves.get(1)?.clone().into_uint()?.as_u128(),
+    ))
+}
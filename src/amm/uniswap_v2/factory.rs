@@ -11,7 +11,7 @@ use ethers::{
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    amm::{factory::AutomatedMarketMakerFactory, AMM},
+    amm::{batch_backend::BatchBackend, factory::AutomatedMarketMakerFactory, pool_address, AMM},
     errors::AMMError,
 };
 
@@ -19,6 +19,10 @@ use super::{batch_request, UniswapV2Pool};
 
 use ethers::prelude::abigen;
 
+//Max number of pairs read by a single batch request when enumerating the pairs of a factory, until the code size of the
+//batch contract is too large
+pub const DEFAULT_PAIRS_BATCH_SIZE: usize = 766;
+
 abigen!(
     IUniswapV2Factory,
     r#"[
@@ -40,6 +44,9 @@ pub struct UniswapV2Factory {
     pub address: H160,
     pub creation_block: u64,
     pub fee: u32,
+    //Forks deploy pairs with a different creation code, the Uniswap V2 pair init code hash is used if it is not set
+    #[serde(default)]
+    pub init_code_hash: Option<H256>,
 }
 
 impl UniswapV2Factory {
@@ -48,21 +55,85 @@ impl UniswapV2Factory {
             address,
             creation_block,
             fee,
+            init_code_hash: None,
+        }
+    }
+
+    pub fn new_with_init_code_hash(
+        address: H160,
+        creation_block: u64,
+        fee: u32,
+        init_code_hash: H256,
+    ) -> UniswapV2Factory {
+        UniswapV2Factory {
+            address,
+            creation_block,
+            fee,
+            init_code_hash: Some(init_code_hash),
         }
     }
 
+    //Derives the CREATE2 address of the pair without checking that it was deployed
+    pub fn compute_pair_address(&self, token_a: H160, token_b: H160) -> H160 {
+        pool_address::compute_v2_pair_address(
+            self.address,
+            self.init_code_hash
+                .unwrap_or(pool_address::UNISWAP_V2_INIT_CODE_HASH),
+            token_a,
+            token_b,
+        )
+    }
+
+    //Pairs created up to `block_number`, or up to the latest block if it is not provided
     pub async fn get_all_pairs_via_batched_calls<M: Middleware>(
         &self,
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<Vec<AMM>, AMMError<M>> {
+        self.get_all_pairs_with_backend(block_number, BatchBackend::BatchContract, middleware)
+            .await
+    }
+
+    //Same as get_all_pairs_via_batched_calls, with the pairs read through `backend`
+    pub async fn get_all_pairs_with_backend<M: Middleware>(
+        &self,
+        block_number: Option<u64>,
+        backend: BatchBackend,
+        middleware: Arc<M>,
+    ) -> Result<Vec<AMM>, AMMError<M>> {
+        self.get_all_pairs_with_batch_size(
+            block_number,
+            backend,
+            DEFAULT_PAIRS_BATCH_SIZE,
+            middleware,
+        )
+        .await
+    }
+
+    //Same as get_all_pairs_with_backend, with `batch_size` pairs read by each request instead of DEFAULT_PAIRS_BATCH_SIZE
+    pub async fn get_all_pairs_with_batch_size<M: Middleware>(
+        &self,
+        block_number: Option<u64>,
+        backend: BatchBackend,
+        batch_size: usize,
         middleware: Arc<M>,
     ) -> Result<Vec<AMM>, AMMError<M>> {
+        if batch_size == 0 {
+            return Err(AMMError::ZeroBatchSize("batch_size"));
+        }
+
         let factory = IUniswapV2Factory::new(self.address, middleware.clone());
 
-        let pairs_length: U256 = factory.all_pairs_length().call().await?;
+        let mut pairs_length_call = factory.all_pairs_length();
+        if let Some(block_number) = block_number {
+            pairs_length_call = pairs_length_call.block(block_number);
+        }
+        let pairs_length: U256 = pairs_length_call.call().await?;
 
         tracing::trace!(?pairs_length, factory = ?self.address, "getting all pairs of factory via batched calls");
 
         let mut pairs = vec![];
-        let step = 766; //max batch size for this call until codesize is too large
+        let step = batch_size;
         let mut idx_from = U256::zero();
         let mut idx_to = if step > pairs_length.as_usize() {
             pairs_length
@@ -71,15 +142,30 @@ impl UniswapV2Factory {
         };
 
         for _ in (0..pairs_length.as_u128()).step_by(step) {
-            pairs.append(
-                &mut batch_request::get_pairs_batch_request(
-                    self.address,
-                    idx_from,
-                    idx_to,
-                    middleware.clone(),
-                )
-                .await?,
-            );
+            let mut batch = match backend {
+                BatchBackend::BatchContract => {
+                    batch_request::get_pairs_batch_request(
+                        self.address,
+                        idx_from,
+                        idx_to,
+                        block_number,
+                        middleware.clone(),
+                    )
+                    .await?
+                }
+                BatchBackend::Multicall3(multicall_address) => {
+                    batch_request::get_pairs_multicall(
+                        self.address,
+                        idx_from,
+                        idx_to,
+                        block_number,
+                        multicall_address,
+                        middleware.clone(),
+                    )
+                    .await?
+                }
+            };
+            pairs.append(&mut batch);
 
             idx_from = idx_to;
 
@@ -96,6 +182,7 @@ impl UniswapV2Factory {
         for addr in pairs {
             let amm = UniswapV2Pool {
                 address: addr,
+                fee: self.fee,
                 ..Default::default()
             };
 
@@ -139,28 +226,32 @@ impl AutomatedMarketMakerFactory for UniswapV2Factory {
             token_b_decimals: 0,
             reserve_0: 0,
             reserve_1: 0,
-            fee: 0,
+            fee: self.fee,
+            token_a_tax: 0,
+            token_b_tax: 0,
         }))
     }
 
     async fn get_all_amms<M: Middleware>(
         &self,
-        _to_block: Option<u64>,
+        to_block: Option<u64>,
         middleware: Arc<M>,
         _step: u64,
     ) -> Result<Vec<AMM>, AMMError<M>> {
-        self.get_all_pairs_via_batched_calls(middleware).await
+        self.get_all_pairs_via_batched_calls(to_block, middleware)
+            .await
     }
 
     async fn populate_amm_data<M: Middleware>(
         &self,
         amms: &mut [AMM],
-        _block_number: Option<u64>,
+        block_number: Option<u64>,
         middleware: Arc<M>,
     ) -> Result<(), AMMError<M>> {
         let step = 127; //Max batch size for call
         for amm_chunk in amms.chunks_mut(step) {
-            batch_request::get_amm_data_batch_request(amm_chunk, middleware.clone()).await?;
+            batch_request::get_amm_data_batch_request(amm_chunk, block_number, middleware.clone())
+                .await?;
         }
         Ok(())
     }
@@ -1,20 +1,27 @@
 pub mod batch_request;
 pub mod factory;
+pub mod transfer_tax;
 
 use std::sync::Arc;
 
 use async_trait::async_trait;
 use ethers::{
     abi::{ethabi::Bytes, RawLog, Token},
+    contract::Multicall,
     prelude::EthEvent,
     providers::Middleware,
-    types::{Log, H160, H256, U256},
+    types::{BlockId, Log, H160, H256, U256},
 };
 use num_bigfloat::BigFloat;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    amm::AutomatedMarketMaker,
+    amm::{
+        batch_backend::MULTICALL3_ADDRESS,
+        depth::{self, MarketDepth},
+        price::Price,
+        price_impact, u256_to_f64, AutomatedMarketMaker,
+    },
     errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
 };
 
@@ -40,6 +47,8 @@ abigen!(
 );
 
 pub const U128_0X10000000000000000: u128 = 18446744073709551616;
+//Pool fees are expressed in units of 1e-5, a fee of 300 is 0.3%
+pub const FEE_DENOMINATOR: u32 = 100000;
 pub const SYNC_EVENT_SIGNATURE: H256 = H256([
     28, 65, 30, 154, 150, 224, 113, 36, 28, 47, 33, 247, 114, 107, 23, 174, 137, 227, 202, 180,
     199, 139, 229, 14, 6, 43, 3, 169, 255, 251, 186, 209,
@@ -55,6 +64,11 @@ pub struct UniswapV2Pool {
     pub reserve_0: u128,
     pub reserve_1: u128,
     pub fee: u32,
+    //Transfer taxes of fee-on-transfer tokens in basis points, see transfer_tax::populate_transfer_taxes
+    #[serde(default)]
+    pub token_a_tax: u32,
+    #[serde(default)]
+    pub token_b_tax: u32,
 }
 
 #[async_trait]
@@ -71,10 +85,11 @@ impl AutomatedMarketMaker for UniswapV2Pool {
 
     async fn populate_data<M: Middleware>(
         &mut self,
-        _block_number: Option<u64>,
+        block_number: Option<u64>,
         middleware: Arc<M>,
     ) -> Result<(), AMMError<M>> {
-        batch_request::get_v2_pool_data_batch_request(self, middleware.clone()).await?;
+        batch_request::get_v2_pool_data_batch_request(self, block_number, middleware.clone())
+            .await?;
 
         Ok(())
     }
@@ -98,8 +113,8 @@ impl AutomatedMarketMaker for UniswapV2Pool {
         }
     }
     //Calculates base/quote, meaning the price of base token per quote (ie. exchange rate is X base per 1 quote)
-    fn calculate_price(&self, base_token: H160) -> Result<f64, ArithmeticError> {
-        Ok(q64_to_f64(self.calculate_price_64_x_64(base_token)?))
+    fn calculate_price(&self, base_token: H160) -> Result<Price, ArithmeticError> {
+        Ok(Price::from_q64(self.calculate_price_64_x_64(base_token)?))
     }
 
     fn tokens(&self) -> Vec<H160> {
@@ -109,18 +124,25 @@ impl AutomatedMarketMaker for UniswapV2Pool {
     fn simulate_swap(&self, token_in: H160, amount_in: U256) -> Result<U256, SwapSimulationError> {
         tracing::info!(?token_in, ?amount_in, "simulating swap");
 
+        self.check_liquidity()?;
+
+        //The pair only receives the amount in after the tax of the token in, and the recipient is taxed on the amount out
         if self.token_a == token_in {
-            Ok(self.get_amount_out(
-                amount_in,
+            let amount_out = self.get_amount_out(
+                apply_transfer_tax(amount_in, self.token_a_tax),
                 U256::from(self.reserve_0),
                 U256::from(self.reserve_1),
-            ))
+            );
+
+            Ok(apply_transfer_tax(amount_out, self.token_b_tax))
         } else {
-            Ok(self.get_amount_out(
-                amount_in,
+            let amount_out = self.get_amount_out(
+                apply_transfer_tax(amount_in, self.token_b_tax),
                 U256::from(self.reserve_1),
                 U256::from(self.reserve_0),
-            ))
+            );
+
+            Ok(apply_transfer_tax(amount_out, self.token_a_tax))
         }
     }
 
@@ -131,7 +153,10 @@ impl AutomatedMarketMaker for UniswapV2Pool {
     ) -> Result<U256, SwapSimulationError> {
         tracing::info!(?token_in, ?amount_in, "simulating swap");
 
+        self.check_liquidity()?;
+
         if self.token_a == token_in {
+            let amount_in = apply_transfer_tax(amount_in, self.token_a_tax);
             let amount_out = self.get_amount_out(
                 amount_in,
                 U256::from(self.reserve_0),
@@ -146,8 +171,9 @@ impl AutomatedMarketMaker for UniswapV2Pool {
 
             tracing::trace!(?self.reserve_0, ?self.reserve_1, "pool reserves after");
 
-            Ok(amount_out)
+            Ok(apply_transfer_tax(amount_out, self.token_b_tax))
         } else {
+            let amount_in = apply_transfer_tax(amount_in, self.token_b_tax);
             let amount_out = self.get_amount_out(
                 amount_in,
                 U256::from(self.reserve_1),
@@ -162,10 +188,25 @@ impl AutomatedMarketMaker for UniswapV2Pool {
 
             tracing::trace!(?self.reserve_0, ?self.reserve_1, "pool reserves after");
 
-            Ok(amount_out)
+            Ok(apply_transfer_tax(amount_out, self.token_a_tax))
         }
     }
 
+    //The fee is in hundredths of a basis point over ten, a fee of 300 is 0.3%
+    fn swap_fee(&self, _token_in: H160) -> f64 {
+        self.fee as f64 / FEE_DENOMINATOR as f64
+    }
+
+    //Hundredths of a basis point like the other AMMs, 3000 for the 0.3% of Uniswap V2. Breaking change: this replaced an inherent
+    //fee method that returned the `fee` field, 300 for 0.3%. Use fee_raw for the previous value.
+    fn fee(&self) -> u32 {
+        self.fee * 10
+    }
+
+    fn token_decimals(&self) -> Vec<u8> {
+        vec![self.token_a_decimals, self.token_b_decimals]
+    }
+
     fn get_token_out(&self, token_in: H160) -> H160 {
         if self.token_a == token_in {
             self.token_b
@@ -173,6 +214,110 @@ impl AutomatedMarketMaker for UniswapV2Pool {
             self.token_a
         }
     }
+
+    //The price of token in is reserve_out / reserve_in, after the swap the reserves are (reserve_in + amount_in, reserve_out - amount_out)
+    fn calculate_price_impact(
+        &self,
+        token_in: H160,
+        amount_in: U256,
+    ) -> Result<f64, SwapSimulationError> {
+        if amount_in.is_zero() {
+            return Err(SwapSimulationError::ZeroAmountIn);
+        }
+
+        let (reserve_in, reserve_out, tax) = if token_in == self.token_a {
+            (self.reserve_0, self.reserve_1, self.token_a_tax)
+        } else if token_in == self.token_b {
+            (self.reserve_1, self.reserve_0, self.token_b_tax)
+        } else {
+            return Err(SwapSimulationError::TokenNotInPool(token_in));
+        };
+
+        self.check_liquidity()?;
+
+        let amount_in = apply_transfer_tax(amount_in, tax);
+        let amount_out =
+            self.get_amount_out(amount_in, U256::from(reserve_in), U256::from(reserve_out));
+
+        let price_ratio = ((reserve_out - amount_out.as_u128()) as f64 / reserve_out as f64)
+            * (reserve_in as f64 / (reserve_in as f64 + u256_to_f64(amount_in)));
+
+        Ok(price_impact(1.0, price_ratio))
+    }
+
+    //All of the liquidity of the pair is full range, the virtual liquidity sqrt(reserve_0 * reserve_1) is active at every price
+    fn liquidity_in_range(
+        &self,
+        price_lower: Price,
+        price_upper: Price,
+    ) -> Result<U256, ArithmeticError> {
+        if price_lower > price_upper {
+            return Err(ArithmeticError::InvalidPriceRange);
+        }
+
+        Ok((U256::from(self.reserve_0) * U256::from(self.reserve_1)).integer_sqrt())
+    }
+
+    //Levels of the constant product curve of the reserves
+    fn market_depth(&self, num_levels: usize) -> Result<MarketDepth, ArithmeticError> {
+        depth::constant_product_depth(
+            U256::from(self.reserve_0),
+            U256::from(self.reserve_1),
+            self.token_a_decimals,
+            self.token_b_decimals,
+            num_levels,
+        )
+    }
+
+    //getAmountIn of the Uniswap V2 router, which rounds up so that the pair sends at least the amount out. The pair has to send the
+    //amount out before the tax of the token out and receives the amount in after the tax of the token in.
+    fn get_amount_in(
+        &self,
+        token_out: H160,
+        amount_out: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        let (reserve_in, reserve_out, tax_in, tax_out) = if token_out == self.token_b {
+            (
+                self.reserve_0,
+                self.reserve_1,
+                self.token_a_tax,
+                self.token_b_tax,
+            )
+        } else if token_out == self.token_a {
+            (
+                self.reserve_1,
+                self.reserve_0,
+                self.token_b_tax,
+                self.token_a_tax,
+            )
+        } else {
+            return Err(SwapSimulationError::TokenNotInPool(token_out));
+        };
+
+        self.check_liquidity()?;
+
+        if amount_out.is_zero() {
+            return Ok(U256::zero());
+        }
+
+        let reserve_out = U256::from(reserve_out);
+        let fee = FEE_DENOMINATOR - self.fee.min(FEE_DENOMINATOR);
+        let insufficient_output = SwapSimulationError::InsufficientOutput {
+            pool: self.address,
+            requested: amount_out,
+            available: apply_transfer_tax(reserve_out - 1, tax_out),
+        };
+
+        let pair_amount_out = match remove_transfer_tax(amount_out, tax_out) {
+            Some(pair_amount_out) if pair_amount_out < reserve_out && fee > 0 => pair_amount_out,
+            _ => return Err(insufficient_output),
+        };
+
+        let numerator = U256::from(reserve_in) * pair_amount_out * U256::from(FEE_DENOMINATOR);
+        let denominator = (reserve_out - pair_amount_out) * U256::from(fee);
+
+        remove_transfer_tax(numerator / denominator + 1, tax_in).ok_or(insufficient_output)
+    }
 }
 
 impl UniswapV2Pool {
@@ -196,6 +341,8 @@ impl UniswapV2Pool {
             reserve_0,
             reserve_1,
             fee,
+            token_a_tax: 0,
+            token_b_tax: 0,
         }
     }
 
@@ -205,18 +352,82 @@ impl UniswapV2Pool {
         fee: u32,
         middleware: Arc<M>,
     ) -> Result<Self, AMMError<M>> {
-        let mut pool = UniswapV2Pool {
-            address: pair_address,
-            token_a: H160::zero(),
-            token_a_decimals: 0,
-            token_b: H160::zero(),
-            token_b_decimals: 0,
-            reserve_0: 0,
-            reserve_1: 0,
-            fee,
-        };
+        let mut pool = UniswapV2Pool::populate(pair_address, middleware).await?;
+        pool.fee = fee;
 
-        pool.populate_data(None, middleware.clone()).await?;
+        Ok(pool)
+    }
+
+    //The fee in the unit of the `fee` field, thousandths of a percent where a fee of 300 is 0.3%. AutomatedMarketMaker::fee is in
+    //hundredths of a basis point, ten times this value. This is what the removed inherent fee method returned.
+    pub fn fee_raw(&self) -> u32 {
+        self.fee
+    }
+
+    //Fetches the tokens and reserves of the pair in a single Multicall3 call, and the decimals of the tokens in a second one
+    //since they depend on the tokens. Both calls read the same block. On chains without Multicall3 the pair is read through the
+    //batch contract instead. The fee is the 0.3% of Uniswap V2, forks with other fees have to set it afterwards.
+    pub async fn populate<M: Middleware>(
+        address: H160,
+        middleware: Arc<M>,
+    ) -> Result<Self, AMMError<M>> {
+        let block_number = middleware
+            .get_block_number()
+            .await
+            .map_err(AMMError::MiddlewareError)?;
+
+        let multicall_code = middleware
+            .get_code(MULTICALL3_ADDRESS, Some(BlockId::from(block_number)))
+            .await
+            .map_err(AMMError::MiddlewareError)?;
+
+        if multicall_code.is_empty() {
+            let mut pool = UniswapV2Pool {
+                address,
+                fee: 300,
+                ..Default::default()
+            };
+            pool.populate_data(Some(block_number.as_u64()), middleware)
+                .await?;
+
+            if !pool.data_is_populated() {
+                return Err(AMMError::PoolDataError);
+            }
+
+            return Ok(pool);
+        }
+
+        let pair = IUniswapV2Pair::new(address, middleware.clone());
+
+        let mut multicall = Multicall::new(middleware.clone(), Some(MULTICALL3_ADDRESS))
+            .await?
+            .block(block_number);
+        multicall
+            .add_call(pair.get_reserves(), false)
+            .add_call(pair.token_0(), false)
+            .add_call(pair.token_1(), false);
+
+        let ((reserve_0, reserve_1, _), token_a, token_b): ((u128, u128, u32), H160, H160) =
+            multicall.call().await?;
+
+        multicall.clear_calls();
+        multicall
+            .add_call(IErc20::new(token_a, middleware.clone()).decimals(), false)
+            .add_call(IErc20::new(token_b, middleware.clone()).decimals(), false);
+
+        let (token_a_decimals, token_b_decimals): (u8, u8) = multicall.call().await?;
+
+        let pool = UniswapV2Pool {
+            address,
+            token_a,
+            token_a_decimals,
+            token_b,
+            token_b_decimals,
+            reserve_0,
+            reserve_1,
+            fee: 300,
+            ..Default::default()
+        };
 
         if !pool.data_is_populated() {
             return Err(AMMError::PoolDataError);
@@ -254,16 +465,14 @@ impl UniswapV2Pool {
                 reserve_0: 0,
                 reserve_1: 0,
                 fee: 0,
+                token_a_tax: 0,
+                token_b_tax: 0,
             })
         } else {
             Err(EventLogError::InvalidEventSignature)?
         }
     }
 
-    pub fn fee(&self) -> u32 {
-        self.fee
-    }
-
     pub fn data_is_populated(&self) -> bool {
         !(self.token_a.is_zero()
             || self.token_b.is_zero()
@@ -340,6 +549,8 @@ impl UniswapV2Pool {
     }
 
     pub fn calculate_price_64_x_64(&self, base_token: H160) -> Result<u128, ArithmeticError> {
+        self.check_liquidity()?;
+
         let decimal_shift = self.token_a_decimals as i8 - self.token_b_decimals as i8;
 
         let (r_0, r_1) = if decimal_shift < 0 {
@@ -356,28 +567,33 @@ impl UniswapV2Pool {
         };
 
         if base_token == self.token_a {
-            if r_0.is_zero() {
-                Ok(U128_0X10000000000000000)
-            } else {
-                div_uu(r_1, r_0)
-            }
-        } else if r_1.is_zero() {
-            Ok(U128_0X10000000000000000)
+            div_uu(r_1, r_0)
         } else {
             div_uu(r_0, r_1)
         }
     }
 
+    //A pair that was just created, or whose liquidity was removed, has no price and can not swap
+    fn check_liquidity(&self) -> Result<(), ArithmeticError> {
+        if self.reserve_0 == 0 || self.reserve_1 == 0 {
+            return Err(ArithmeticError::ZeroLiquidity(self.address));
+        }
+
+        Ok(())
+    }
+
     pub fn get_amount_out(&self, amount_in: U256, reserve_in: U256, reserve_out: U256) -> U256 {
         tracing::trace!(?amount_in, ?reserve_in, ?reserve_out);
 
         if amount_in.is_zero() || reserve_in.is_zero() || reserve_out.is_zero() {
             return U256::zero();
         }
-        let fee = (10000 - (self.fee / 10)) / 10; //Fee of 300 => (10,000 - 30) / 10  = 997
+        //Fees are scaled by 1e5 so that forks with fees that are not a multiple of 10 bps are quoted exactly,
+        //a fee of 300 => 99,700 / 100,000 matches the 997 / 1000 of the Uniswap V2 router, 250 => 9975 / 10,000 of PancakeSwap
+        let fee = FEE_DENOMINATOR - self.fee.min(FEE_DENOMINATOR);
         let amount_in_with_fee = amount_in * U256::from(fee);
         let numerator = amount_in_with_fee * reserve_out;
-        let denominator = reserve_in * U256::from(1000) + amount_in_with_fee;
+        let denominator = reserve_in * U256::from(FEE_DENOMINATOR) + amount_in_with_fee;
 
         tracing::trace!(?fee, ?amount_in_with_fee, ?numerator, ?denominator);
 
@@ -404,6 +620,28 @@ impl UniswapV2Pool {
     }
 }
 
+//Amount that arrives after a transfer of a token with a tax in basis points, untaxed transfers are returned unchanged
+pub fn apply_transfer_tax(amount: U256, tax: u32) -> U256 {
+    if tax == 0 {
+        return amount;
+    }
+
+    amount * U256::from(10000_u32.saturating_sub(tax)) / U256::from(10000)
+}
+
+//Smallest amount that is at least `amount` after the tax, None for a token whose transfers revert
+fn remove_transfer_tax(amount: U256, tax: u32) -> Option<U256> {
+    if tax == 0 {
+        return Some(amount);
+    }
+
+    let remaining = 10000_u32
+        .checked_sub(tax)
+        .filter(|remaining| *remaining > 0)?;
+    let scaled = amount * U256::from(10000);
+    Some((scaled + U256::from(remaining - 1)) / U256::from(remaining))
+}
+
 pub const U256_0XFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF: U256 = U256([
     18446744073709551615,
     18446744073709551615,
@@ -522,14 +760,39 @@ mod tests {
     use std::{str::FromStr, sync::Arc};
 
     use ethers::{
+        abi::{encode, Token},
+        prelude::abigen,
         providers::{Http, Provider},
-        types::{H160, U256},
+        types::{Bytes, H160, U256, U64},
     };
 
-    use crate::amm::AutomatedMarketMaker;
+    use crate::{
+        amm::{price::Price, search_amount_in, AutomatedMarketMaker},
+        errors::{ArithmeticError, SwapSimulationError},
+    };
 
     use super::UniswapV2Pool;
 
+    abigen!(
+        IPancakeRouter,
+        r#"[
+            function getAmountsOut(uint256 amountIn, address[] path) external view returns (uint256[] amounts)
+        ]"#;
+    );
+
+    #[test]
+    fn test_fee_units() {
+        let pool = UniswapV2Pool {
+            fee: 300,
+            ..Default::default()
+        };
+
+        //The fee field and fee_raw are 300 for 0.3%, the fee of the trait is 3000 for 0.3% like the fee of a Uniswap V3 pool
+        assert_eq!(pool.fee_raw(), pool.fee);
+        assert_eq!(AutomatedMarketMaker::fee(&pool), pool.fee * 10);
+        assert_eq!(AutomatedMarketMaker::fee(&pool), 3000);
+    }
+
     #[test]
     fn test_swap_calldata() -> eyre::Result<()> {
         let uniswap_v2_pool = UniswapV2Pool::default();
@@ -544,6 +807,134 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_calculate_price_impact() -> eyre::Result<()> {
+        let mut pool = UniswapV2Pool {
+            token_a: H160::from_low_u64_be(1),
+            token_a_decimals: 18,
+            token_b: H160::from_low_u64_be(2),
+            token_b_decimals: 6,
+            reserve_0: 10_u128.pow(21),
+            reserve_1: 10_u128.pow(9),
+            ..Default::default()
+        };
+
+        //Without a fee, swapping a tenth of the reserve in multiplies it by 1.1 and divides the reserve out by 1.1
+        let price_impact = pool.calculate_price_impact(pool.token_a, U256::exp10(20))?;
+        assert!((price_impact - (1.0 - 1.0 / 1.21)).abs() < 1e-9);
+
+        let price_impact = pool.calculate_price_impact(pool.token_b, U256::exp10(6))?;
+        assert!(price_impact > 0.0 && price_impact < 0.01);
+
+        assert!(matches!(
+            pool.calculate_price_impact(pool.token_a, U256::zero()),
+            Err(SwapSimulationError::ZeroAmountIn)
+        ));
+
+        pool.reserve_1 = 0;
+        assert!(matches!(
+            pool.calculate_price_impact(pool.token_a, U256::exp10(18)),
+            Err(SwapSimulationError::ArithmeticError(
+                ArithmeticError::ZeroLiquidity(_)
+            ))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zero_liquidity() {
+        let mut pool = UniswapV2Pool {
+            address: H160::from_low_u64_be(3),
+            token_a: H160::from_low_u64_be(1),
+            token_b: H160::from_low_u64_be(2),
+            reserve_0: 10_u128.pow(18),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            pool.calculate_price(pool.token_a),
+            Err(ArithmeticError::ZeroLiquidity(address)) if address == pool.address
+        ));
+        assert!(matches!(
+            pool.calculate_price(pool.token_b),
+            Err(ArithmeticError::ZeroLiquidity(_))
+        ));
+        assert!(matches!(
+            pool.simulate_swap(pool.token_a, U256::exp10(18)),
+            Err(SwapSimulationError::ArithmeticError(
+                ArithmeticError::ZeroLiquidity(_)
+            ))
+        ));
+        assert!(matches!(
+            pool.simulate_swap_mut(pool.token_b, U256::exp10(18)),
+            Err(SwapSimulationError::ArithmeticError(
+                ArithmeticError::ZeroLiquidity(_)
+            ))
+        ));
+        assert_eq!(pool.reserve_0, 10_u128.pow(18));
+    }
+
+    #[test]
+    fn test_get_amount_in() -> eyre::Result<()> {
+        let mut pool = UniswapV2Pool {
+            address: H160::from_low_u64_be(3),
+            token_a: H160::from_low_u64_be(1),
+            token_b: H160::from_low_u64_be(2),
+            reserve_0: 10_u128.pow(21),
+            reserve_1: 2 * 10_u128.pow(21),
+            fee: 300,
+            ..Default::default()
+        };
+
+        for amount_out in [U256::one(), U256::exp10(15), U256::exp10(21)] {
+            let amount_in = pool.get_amount_in(pool.token_b, amount_out)?;
+            assert!(pool.simulate_swap(pool.token_a, amount_in)? >= amount_out);
+
+            //The router rounds up by one, the search finds the smallest amount in
+            let searched_amount_in = search_amount_in(pool.address, amount_out, |amount_in| {
+                pool.simulate_swap(pool.token_a, amount_in)
+            })?;
+            assert!(amount_in - searched_amount_in <= U256::one());
+            assert!(pool.simulate_swap(pool.token_a, searched_amount_in - 1)? < amount_out);
+        }
+
+        //A taxed token in has to cover the tax before it reaches the pair
+        pool.token_a_tax = 500;
+        let amount_out = U256::exp10(18);
+        let amount_in = pool.get_amount_in(pool.token_b, amount_out)?;
+        assert!(pool.simulate_swap(pool.token_a, amount_in)? >= amount_out);
+
+        assert!(matches!(
+            pool.get_amount_in(pool.token_b, U256::from(pool.reserve_1)),
+            Err(SwapSimulationError::InsufficientOutput { .. })
+        ));
+        assert!(matches!(
+            pool.get_amount_in(H160::zero(), amount_out),
+            Err(SwapSimulationError::TokenNotInPool(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_liquidity_in_range() -> eyre::Result<()> {
+        let pool = UniswapV2Pool {
+            reserve_0: 10_u128.pow(21),
+            reserve_1: 4 * 10_u128.pow(9),
+            ..Default::default()
+        };
+
+        let liquidity = pool.liquidity_in_range(Price::from_f64(1.0)?, Price::from_f64(1000.0)?)?;
+        assert_eq!(liquidity, U256::from(2 * 10_u128.pow(15)));
+
+        assert!(pool
+            .liquidity_in_range(Price::from_f64(2.0)?, Price::from_f64(1.0)?)
+            .is_err());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_get_new_from_address() -> eyre::Result<()> {
         let rpc_endpoint = std::env::var("ETHEREUM_RPC_ENDPOINT")?;
@@ -575,6 +966,74 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_populate() -> eyre::Result<()> {
+        let rpc_endpoint = std::env::var("ETHEREUM_RPC_ENDPOINT")?;
+        let middleware = Arc::new(Provider::<Http>::try_from(rpc_endpoint)?);
+        let pair_address = H160::from_str("0xB4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc")?;
+
+        let pool = UniswapV2Pool::populate(pair_address, middleware.clone()).await?;
+
+        assert_eq!(
+            pool.token_a,
+            H160::from_str("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48")?
+        );
+        assert_eq!(pool.token_a_decimals, 6);
+        assert_eq!(
+            pool.token_b,
+            H160::from_str("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2")?
+        );
+        assert_eq!(pool.token_b_decimals, 18);
+        assert_eq!(pool.fee, 300);
+
+        //The reserves can change between the two requests, so only the tokens are compared to the ones of the batch request
+        let mut batch_pool = UniswapV2Pool {
+            address: pair_address,
+            ..Default::default()
+        };
+        batch_pool.populate_data(None, middleware.clone()).await?;
+        assert!(pool.reserve_0 > 0 && pool.reserve_1 > 0);
+        assert_eq!(pool.token_a, batch_pool.token_a);
+        assert_eq!(pool.token_b, batch_pool.token_b);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_populate_without_multicall3() -> eyre::Result<()> {
+        let pair_address = H160::from_low_u64_be(1);
+        let token_a = H160::from_low_u64_be(2);
+        let token_b = H160::from_low_u64_be(3);
+
+        //Responses are returned last in first out. Multicall3 has no code at the block, so the pair is read through the batch contract.
+        let (provider, mock) = Provider::mocked();
+        mock.push(Bytes::from(encode(&[Token::Array(vec![Token::Tuple(
+            vec![
+                Token::Address(token_a),
+                Token::Uint(U256::from(6)),
+                Token::Address(token_b),
+                Token::Uint(U256::from(18)),
+                Token::Uint(U256::from(1000)),
+                Token::Uint(U256::from(2000)),
+            ],
+        )])])))?;
+        mock.push(Bytes::new())?;
+        mock.push(U64::from(100))?;
+
+        let pool = UniswapV2Pool::populate(pair_address, Arc::new(provider)).await?;
+
+        assert_eq!(pool.address, pair_address);
+        assert_eq!(pool.token_a, token_a);
+        assert_eq!(pool.token_a_decimals, 6);
+        assert_eq!(pool.token_b, token_b);
+        assert_eq!(pool.token_b_decimals, 18);
+        assert_eq!(pool.reserve_0, 1000);
+        assert_eq!(pool.reserve_1, 2000);
+        assert_eq!(pool.fee, 300);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_get_pool_data() -> eyre::Result<()> {
         let rpc_endpoint = std::env::var("ETHEREUM_RPC_ENDPOINT")?;
@@ -618,13 +1077,107 @@ mod tests {
             reserve_0: 23595096345912178729927,
             reserve_1: 154664232014390554564,
             fee: 300,
+            ..Default::default()
+        };
+
+        assert!(x.calculate_price(token_a)?.to_f64() != 0.0);
+        assert!(x.calculate_price(token_b)?.to_f64() != 0.0);
+
+        Ok(())
+    }
+    #[test]
+    fn test_simulate_swap_with_details() -> eyre::Result<()> {
+        let pool = UniswapV2Pool {
+            token_a: H160::repeat_byte(0x01),
+            token_a_decimals: 18,
+            token_b: H160::repeat_byte(0x02),
+            token_b_decimals: 18,
+            reserve_0: 10_u128.pow(21),
+            reserve_1: 10_u128.pow(21),
+            fee: 300,
+            ..Default::default()
+        };
+
+        let amount_in = U256::exp10(18);
+        let swap_result = pool.simulate_swap_with_details(pool.token_a, amount_in)?;
+
+        assert_eq!(
+            swap_result.amount_out,
+            pool.simulate_swap(pool.token_a, amount_in)?
+        );
+        assert!(swap_result.fee_paid.as_u128().abs_diff(3 * 10_u128.pow(15)) <= 1);
+
+        //Swapping 0.1% of the reserves moves the price by about 0.1% on top of the 0.3% fee
+        assert!((swap_result.effective_price - 0.99601).abs() < 1e-5);
+        assert!((swap_result.price_impact - 0.001).abs() < 1e-5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_amount_out_with_fee() -> eyre::Result<()> {
+        let reserve_in = U256::from(10_u128.pow(21));
+        let reserve_out = U256::from(3 * 10_u128.pow(23));
+        let amount_in = U256::exp10(18);
+
+        //A fee of 25 bps is quoted with the 9975 / 10,000 of the PancakeSwap V2 router
+        let pool = UniswapV2Pool {
+            fee: 250,
+            ..Default::default()
+        };
+        let amount_in_with_fee = amount_in * 9975;
+        let expected = amount_in_with_fee * reserve_out / (reserve_in * 10000 + amount_in_with_fee);
+        assert_eq!(
+            pool.get_amount_out(amount_in, reserve_in, reserve_out),
+            expected
+        );
+
+        //The default fee still matches the 997 / 1000 of the Uniswap V2 router
+        let pool = UniswapV2Pool {
+            fee: 300,
+            ..Default::default()
         };
+        let amount_in_with_fee = amount_in * 997;
+        let expected = amount_in_with_fee * reserve_out / (reserve_in * 1000 + amount_in_with_fee);
+        assert_eq!(
+            pool.get_amount_out(amount_in, reserve_in, reserve_out),
+            expected
+        );
+
+        Ok(())
+    }
 
-        assert!(x.calculate_price(token_a)? != 0.0);
-        assert!(x.calculate_price(token_b)? != 0.0);
+    #[tokio::test]
+    async fn test_pancake_swap_quote_matches_router() -> eyre::Result<()> {
+        let rpc_endpoint = std::env::var("BSC_RPC_ENDPOINT")?;
+        let middleware = Arc::new(Provider::<Http>::try_from(rpc_endpoint)?);
+
+        //WBNB/BUSD PancakeSwap V2 pair
+        let pool = UniswapV2Pool::new_from_address(
+            H160::from_str("0x58F876857a02D6762E0101bb5C46A8c1ED44Dc16")?,
+            250,
+            middleware.clone(),
+        )
+        .await?;
+
+        let router = IPancakeRouter::new(
+            H160::from_str("0x10ED43C718714eb63d5aA57B78B54704E256024E")?,
+            middleware,
+        );
+
+        let amount_in = U256::exp10(18);
+        for (token_in, token_out) in [(pool.token_a, pool.token_b), (pool.token_b, pool.token_a)] {
+            let amounts = router
+                .get_amounts_out(amount_in, vec![token_in, token_out])
+                .call()
+                .await?;
+
+            assert_eq!(pool.simulate_swap(token_in, amount_in)?, amounts[1]);
+        }
 
         Ok(())
     }
+
     #[tokio::test]
     async fn test_calculate_price() -> eyre::Result<()> {
         let rpc_endpoint = std::env::var("ETHEREUM_RPC_ENDPOINT")?;
@@ -640,9 +1193,9 @@ mod tests {
         pool.reserve_0 = 47092140895915;
         pool.reserve_1 = 28396598565590008529300;
 
-        let price_a_64_x = pool.calculate_price(pool.token_a)?;
+        let price_a_64_x = pool.calculate_price(pool.token_a)?.to_f64();
 
-        let price_b_64_x = pool.calculate_price(pool.token_b)?;
+        let price_b_64_x = pool.calculate_price(pool.token_b)?.to_f64();
 
         assert_eq!(1658.3725965327264, price_b_64_x); //No precision loss: 30591574867092394336528 / 2**64
         assert_eq!(0.0006030007985483893, price_a_64_x); //Precision loss: 11123401407064628 / 2**64
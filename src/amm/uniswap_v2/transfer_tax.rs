@@ -0,0 +1,289 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use ethers::{
+    abi::{encode, Token},
+    providers::{Middleware, MiddlewareError},
+    types::{
+        transaction::eip2718::TypedTransaction, BlockId, BlockNumber, Bytes, TransactionRequest,
+        H160, U256,
+    },
+};
+use futures::{stream, StreamExt};
+
+use crate::{
+    amm::{uniswap_v2::UniswapV2Pool, AMM},
+    errors::AMMError,
+};
+
+//Creation code that flash swaps `amount0Out` and `amount1Out` out of the pair to a helper contract, which reverts from the swap callback
+//with the balance it received. The code returns that balance, or nothing if the swap reverted before the callback, i.e. because the
+//transfer of the token reverted or returned false and the pair rejected it. The abi encoded (pair, token, amount0Out, amount1Out) are
+//appended to the code. The probe is a plain eth_call without a state override, so it goes through the middleware stack.
+pub const TRANSFER_TAX_PROBE_CODE: &str =
+    "0x61003f61007e60003960206060380361003f3961005f60006000f063022c0d9f60e01b60005260206040380360043960206020380360243960445260806064526001608452600160a453602060803803610100396000600060c460006000610100515af1503d60201461007157600080f35b6020600060003e60206000f3605480600b6000396000f36370a0823160e01b6000523060045260206020380360403960206060602460006040515afa1561002f5760206060fd5b600080fd";
+
+//Tax of a token whose transfers revert, swaps through the pair always return zero
+pub const MAX_TRANSFER_TAX: u32 = 10000;
+
+//Number of tokens populate_transfer_taxes probes at a time if no concurrency is provided
+pub const DEFAULT_TRANSFER_TAX_CONCURRENCY: usize = 8;
+
+//Detects the transfer tax of a token of the pool in basis points by simulating a transfer of `amount` out of the pair.
+//The pair must hold at least `amount` of the token, a small fraction of the reserve avoids max transaction limits.
+//A failed transfer is a tax of MAX_TRANSFER_TAX, other errors of the middleware, e.g. rate limits, are returned.
+pub async fn get_transfer_tax<M: Middleware>(
+    pool: &UniswapV2Pool,
+    token: H160,
+    amount: U256,
+    block_number: Option<u64>,
+    middleware: Arc<M>,
+) -> Result<u32, AMMError<M>> {
+    if amount.is_zero() {
+        return Ok(0);
+    }
+
+    let block: BlockId = block_number
+        .map(BlockId::from)
+        .unwrap_or(BlockId::Number(BlockNumber::Latest));
+
+    let (amount_0_out, amount_1_out) = if token == pool.token_a {
+        (amount, U256::zero())
+    } else {
+        (U256::zero(), amount)
+    };
+
+    let mut calldata = TRANSFER_TAX_PROBE_CODE
+        .parse::<Bytes>()
+        .map_err(|_| AMMError::FromHexError)?
+        .to_vec();
+    calldata.extend(encode(&[
+        Token::Address(pool.address),
+        Token::Address(token),
+        Token::Uint(amount_0_out),
+        Token::Uint(amount_1_out),
+    ]));
+
+    //A transaction without a recipient runs the creation code and returns what it returns
+    let tx: TypedTransaction = TransactionRequest::new().data(calldata).into();
+
+    let received = match middleware.call(&tx, Some(block)).await {
+        Ok(result) if result.len() == 32 => U256::from_big_endian(&result),
+        //Tokens that revert or return false on transfers out of the pair can not be bought through it
+        Ok(result) if result.is_empty() => {
+            tracing::debug!(?token, ?pool.address, "transfer out of the pair failed");
+            return Ok(MAX_TRANSFER_TAX);
+        }
+        Ok(_) => return Err(AMMError::BatchRequestError(token)),
+        Err(error) if is_execution_revert(&error) => {
+            tracing::debug!(?token, ?pool.address, %error, "transfer tax probe reverted");
+            return Ok(MAX_TRANSFER_TAX);
+        }
+        Err(error) => return Err(AMMError::MiddlewareError(error)),
+    };
+
+    if received >= amount {
+        return Ok(0);
+    }
+
+    //Rounded up so that the simulated amount out never exceeds the amount received
+    let tax = ((amount - received) * U256::from(MAX_TRANSFER_TAX) + amount - 1) / amount;
+
+    Ok(tax.as_u32())
+}
+
+//A revert carries revert data or an "execution reverted" message, rate limits and invalid params are other JSON-RPC errors
+fn is_execution_revert<E: MiddlewareError>(error: &E) -> bool {
+    error.as_error_response().map_or(false, |response| {
+        response
+            .message
+            .to_lowercase()
+            .contains("execution reverted")
+            || response
+                .data
+                .as_ref()
+                .and_then(|data| data.as_str())
+                .map_or(false, |data| data.starts_with("0x"))
+    })
+}
+
+//Populates `token_a_tax` and `token_b_tax` of every Uniswap V2 pool, each token is probed once through the first pool it is found in.
+//The probe amount is a thousandth of the reserve of the token, pools with other AMM variants are ignored. Up to `concurrency` tokens,
+//DEFAULT_TRANSFER_TAX_CONCURRENCY if it is not provided, are probed at a time.
+pub async fn populate_transfer_taxes<M: Middleware>(
+    amms: &mut [AMM],
+    block_number: Option<u64>,
+    concurrency: Option<usize>,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    let concurrency = concurrency
+        .unwrap_or(DEFAULT_TRANSFER_TAX_CONCURRENCY)
+        .max(1);
+
+    let mut probed_tokens = HashSet::new();
+    let mut probes = vec![];
+    for amm in amms.iter() {
+        if let AMM::UniswapV2Pool(pool) = amm {
+            for (token, reserve) in [
+                (pool.token_a, pool.reserve_0),
+                (pool.token_b, pool.reserve_1),
+            ] {
+                if probed_tokens.insert(token) {
+                    probes.push((pool, token, U256::from(reserve / 1000)));
+                }
+            }
+        }
+    }
+
+    let taxes = stream::iter(probes)
+        .map(|(pool, token, amount)| {
+            let middleware = middleware.clone();
+            async move {
+                let tax = get_transfer_tax(pool, token, amount, block_number, middleware).await?;
+                Ok::<_, AMMError<M>>((token, tax))
+            }
+        })
+        .buffer_unordered(concurrency)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<HashMap<H160, u32>, AMMError<M>>>()?;
+
+    for amm in amms.iter_mut() {
+        if let AMM::UniswapV2Pool(pool) = amm {
+            pool.token_a_tax = taxes[&pool.token_a];
+            pool.token_b_tax = taxes[&pool.token_b];
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, sync::Arc};
+
+    use ethers::{
+        abi::{encode, Token},
+        providers::{Http, JsonRpcError, MockResponse, Provider},
+        types::{Bytes, H160, U256},
+    };
+
+    use crate::{
+        amm::{uniswap_v2::UniswapV2Pool, AutomatedMarketMaker},
+        errors::AMMError,
+    };
+
+    use super::{get_transfer_tax, MAX_TRANSFER_TAX};
+
+    #[test]
+    fn test_simulate_swap_with_transfer_tax() -> eyre::Result<()> {
+        let mut pool = UniswapV2Pool::new(
+            H160::repeat_byte(0x03),
+            H160::repeat_byte(0x01),
+            18,
+            H160::repeat_byte(0x02),
+            18,
+            10_u128.pow(21),
+            10_u128.pow(21),
+            300,
+        );
+
+        let amount_in = U256::exp10(18);
+        let untaxed_amount_out = pool.simulate_swap(pool.token_a, amount_in)?;
+
+        //A 5% tax on token a is taken from the amount in, a 2% tax on token b from the amount out
+        pool.token_a_tax = 500;
+        pool.token_b_tax = 200;
+
+        let expected = pool.get_amount_out(
+            amount_in * 95 / 100,
+            U256::from(pool.reserve_0),
+            U256::from(pool.reserve_1),
+        ) * 98
+            / 100;
+        assert_eq!(pool.simulate_swap(pool.token_a, amount_in)?, expected);
+        assert!(expected < untaxed_amount_out);
+
+        let reserve_1 = pool.reserve_1;
+        let amount_out = pool.simulate_swap_mut(pool.token_a, amount_in)?;
+        assert_eq!(amount_out, expected);
+        assert_eq!(pool.reserve_0, 10_u128.pow(21) + 95 * 10_u128.pow(16));
+        assert!(reserve_1 - pool.reserve_1 > amount_out.as_u128());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_transfer_tax() -> eyre::Result<()> {
+        let rpc_endpoint = std::env::var("ETHEREUM_RPC_ENDPOINT")?;
+        let middleware = Arc::new(Provider::<Http>::try_from(rpc_endpoint)?);
+
+        //USDC/WETH pair, neither token takes a transfer tax
+        let pool = UniswapV2Pool {
+            address: H160::from_str("0xB4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc")?,
+            token_a: H160::from_str("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48")?,
+            token_b: H160::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2")?,
+            ..Default::default()
+        };
+
+        for token in [pool.token_a, pool.token_b] {
+            let tax = get_transfer_tax(&pool, token, U256::from(1000000), None, middleware.clone())
+                .await?;
+            assert_eq!(tax, 0);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_transfer_tax_errors() -> eyre::Result<()> {
+        let pool = UniswapV2Pool {
+            address: H160::from_low_u64_be(1),
+            token_a: H160::from_low_u64_be(10),
+            token_b: H160::from_low_u64_be(11),
+            ..Default::default()
+        };
+        let amount = U256::from(1000);
+        let error = |code: i64, message: &str| {
+            MockResponse::Error(JsonRpcError {
+                code,
+                message: message.to_string(),
+                data: None,
+            })
+        };
+
+        //Responses are returned last in first out
+        let (provider, mock) = Provider::mocked();
+        mock.push_response(error(-32005, "rate limit exceeded"));
+        mock.push_response(error(3, "execution reverted"));
+        mock.push(Bytes::default())?;
+        mock.push(Bytes::from(encode(&[Token::Uint(U256::from(950))])))?;
+        let provider = Arc::new(provider);
+
+        assert_eq!(
+            get_transfer_tax(&pool, pool.token_a, amount, None, provider.clone()).await?,
+            500
+        );
+
+        //The transfer out of the pair failed inside the probe, or the probe itself reverted
+        assert_eq!(
+            get_transfer_tax(&pool, pool.token_b, amount, None, provider.clone()).await?,
+            MAX_TRANSFER_TAX
+        );
+        assert_eq!(
+            get_transfer_tax(&pool, pool.token_b, amount, None, provider.clone()).await?,
+            MAX_TRANSFER_TAX
+        );
+
+        //A rate limited probe says nothing about the token
+        assert!(matches!(
+            get_transfer_tax(&pool, pool.token_b, amount, None, provider).await,
+            Err(AMMError::MiddlewareError(_))
+        ));
+
+        Ok(())
+    }
+}
@@ -1,17 +1,24 @@
-use std::{sync::Arc, vec};
+use std::{collections::HashMap, sync::Arc, vec};
 
 use ethers::{
-    abi::{ParamType, Token},
+    abi::{decode, ParamType, Token},
+    contract::Multicall,
     providers::Middleware,
-    types::{Bytes, I256, U256, U64},
+    types::{BlockId, Bytes, H160, I256, U256, U64},
 };
 
 use crate::{
-    amm::{AutomatedMarketMaker, AMM},
+    amm::{
+        batch_backend::{aggregate_3, decode_decimals},
+        AutomatedMarketMaker, AMM,
+    },
     errors::AMMError,
 };
 
-use super::UniswapV3Pool;
+use super::{
+    tick_data::{word_position, TICK_DATA_CHUNK_SIZE},
+    IErc20, IUniswapV3Pool, UniswapV3Pool, MAX_TICK, MIN_TICK,
+};
 
 use ethers::prelude::abigen;
 
@@ -299,3 +306,275 @@ pub async fn get_amm_data_batch_request<M: Middleware>(
 
     Ok(())
 }
+
+//Same as get_amm_data_batch_request, with the calls of the batch contract made through the Multicall3 at `multicall_address`.
+//The tokens and state of the pools are read first and then the decimals of their tokens. Pools that the batch contract
+//skips are left empty, as are pools whose calls revert.
+pub async fn get_amm_data_multicall<M: Middleware>(
+    amms: &mut [AMM],
+    block_number: u64,
+    multicall_address: H160,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    tracing::info!(
+        block_number,
+        "getting data for {} AMMs through Multicall3",
+        amms.len()
+    );
+
+    let block = Some(BlockId::from(block_number));
+    let mut multicall = Multicall::new(middleware.clone(), Some(multicall_address)).await?;
+
+    for amm in amms.iter() {
+        let v3_pool = IUniswapV3Pool::new(amm.address(), middleware.clone());
+        multicall.add_call(v3_pool.token_0(), true);
+        multicall.add_call(v3_pool.token_1(), true);
+        multicall.add_call(v3_pool.slot_0(), true);
+        multicall.add_call(v3_pool.liquidity(), true);
+        multicall.add_call(v3_pool.tick_spacing(), true);
+        multicall.add_call(v3_pool.fee(), true);
+    }
+
+    let pools_data = aggregate_3(&mut multicall, block)
+        .await?
+        .chunks(6)
+        .map(decode_pool_state)
+        .collect::<Vec<Option<V3PoolState>>>();
+
+    if pools_data.iter().all(Option::is_none) {
+        return Ok(());
+    }
+
+    for state in pools_data.iter().flatten() {
+        multicall.add_call(
+            IErc20::new(state.token_a, middleware.clone()).decimals(),
+            true,
+        );
+        multicall.add_call(
+            IErc20::new(state.token_b, middleware.clone()).decimals(),
+            true,
+        );
+    }
+
+    let mut decimals = aggregate_3(&mut multicall, block).await?.into_iter();
+
+    for (amm, state) in amms.iter_mut().zip(pools_data) {
+        let Some(state) = state else {
+            continue;
+        };
+
+        let token_a_decimals = decode_decimals(decimals.next().flatten().as_deref());
+        let token_b_decimals = decode_decimals(decimals.next().flatten().as_deref());
+
+        if let (AMM::UniswapV3Pool(pool), Some(token_a_decimals), Some(token_b_decimals)) =
+            (amm, token_a_decimals, token_b_decimals)
+        {
+            pool.token_a = state.token_a;
+            pool.token_a_decimals = token_a_decimals;
+            pool.token_b = state.token_b;
+            pool.token_b_decimals = token_b_decimals;
+            pool.liquidity = state.liquidity;
+            pool.sqrt_price = state.sqrt_price;
+            pool.tick = state.tick;
+            pool.tick_spacing = state.tick_spacing;
+            pool.fee = state.fee;
+            tracing::trace!(?pool);
+        }
+    }
+
+    Ok(())
+}
+
+struct V3PoolState {
+    token_a: H160,
+    token_b: H160,
+    sqrt_price: U256,
+    tick: i32,
+    liquidity: u128,
+    tick_spacing: i32,
+    fee: u32,
+}
+
+//Return data of token0, token1, slot0, liquidity, tickSpacing and fee
+fn decode_pool_state(results: &[Option<Bytes>]) -> Option<V3PoolState> {
+    let decode_word = |index: usize, param: ParamType| {
+        decode(&[param], results.get(index)?.as_ref()?).ok()?.pop()
+    };
+
+    let slot_0 = decode(
+        &[
+            ParamType::Uint(160),
+            ParamType::Int(24),
+            ParamType::Uint(16),
+            ParamType::Uint(16),
+            ParamType::Uint(16),
+            ParamType::Uint(32),
+            ParamType::Bool,
+        ],
+        results.get(2)?.as_ref()?,
+    )
+    .ok()?;
+
+    Some(V3PoolState {
+        token_a: decode_word(0, ParamType::Address)?.into_address()?,
+        token_b: decode_word(1, ParamType::Address)?.into_address()?,
+        sqrt_price: slot_0.first()?.clone().into_uint()?,
+        tick: I256::from_raw(slot_0.get(1)?.clone().into_int()?).as_i32(),
+        liquidity: decode_word(3, ParamType::Uint(128))?.into_uint()?.as_u128(),
+        tick_spacing: I256::from_raw(decode_word(4, ParamType::Int(24))?.into_int()?).as_i32(),
+        fee: decode_word(5, ParamType::Uint(24))?.into_uint()?.as_u32(),
+    })
+}
+
+//Same as get_uniswap_v3_tick_data_batch_request, with the tickBitmap and ticks calls of the batch contract made through the
+//Multicall3 at `multicall_address`. Each step of the walk reads at most the next word, so the `num_ticks` words in the direction
+//of the walk are read first, the walk is replayed on them and then the ticks it stepped to are read. The block number is read
+//with the first words if it is not provided so that every call is made at the same block.
+pub async fn get_uniswap_v3_tick_data_multicall<M: Middleware>(
+    pool: &UniswapV3Pool,
+    tick_start: i32,
+    zero_for_one: bool,
+    num_ticks: u16,
+    block_number: Option<U64>,
+    multicall_address: H160,
+    middleware: Arc<M>,
+) -> Result<(Vec<UniswapV3TickData>, U64), AMMError<M>> {
+    let v3_pool = IUniswapV3Pool::new(pool.address, middleware.clone());
+    let mut multicall = Multicall::new(middleware.clone(), Some(multicall_address)).await?;
+
+    //Searching upwards starts at the next compressed tick, which can be in the next word
+    let (first_word, _) = if zero_for_one {
+        word_position(tick_start, pool.tick_spacing)
+    } else {
+        word_position(tick_start + pool.tick_spacing, pool.tick_spacing)
+    };
+
+    let words = (0..num_ticks as i32)
+        .map(|step| {
+            if zero_for_one {
+                first_word as i32 - step
+            } else {
+                first_word as i32 + step
+            }
+        })
+        .filter_map(|word| i16::try_from(word).ok())
+        .collect::<Vec<i16>>();
+
+    let mut block_number = block_number;
+    let mut tick_bitmap = HashMap::new();
+    for chunk in words.chunks(TICK_DATA_CHUNK_SIZE) {
+        for word in chunk {
+            multicall.add_call(v3_pool.tick_bitmap(*word), false);
+        }
+
+        let read_block_number = block_number.is_none();
+        if read_block_number {
+            multicall.add_get_block_number();
+        }
+
+        let mut results = aggregate_3(&mut multicall, block_number.map(BlockId::from)).await?;
+        if read_block_number {
+            let current_block = results
+                .pop()
+                .flatten()
+                .and_then(|return_data| decode_uint(&return_data))
+                .ok_or(AMMError::BatchRequestError(pool.address))?;
+
+            block_number = Some(U64::from(current_block.as_u64()));
+        }
+
+        for (word, result) in chunk.iter().zip(results) {
+            let bitmap = result
+                .and_then(|return_data| decode_uint(&return_data))
+                .ok_or(AMMError::BatchRequestError(pool.address))?;
+
+            tick_bitmap.insert(*word, bitmap);
+        }
+    }
+
+    //The batch contract stops at the first tick past MIN_TICK or MAX_TICK and reports it as MIN_TICK
+    let mut steps = vec![];
+    let mut current_tick = tick_start;
+    while steps.len() < num_ticks as usize {
+        let (next_tick, initialized) =
+            uniswap_v3_math::tick_bitmap::next_initialized_tick_within_one_word(
+                &tick_bitmap,
+                current_tick,
+                pool.tick_spacing,
+                zero_for_one,
+            )?;
+
+        steps.push((next_tick, initialized));
+        if !(MIN_TICK..=MAX_TICK).contains(&next_tick) {
+            break;
+        }
+
+        current_tick = if zero_for_one {
+            next_tick - 1
+        } else {
+            next_tick
+        };
+    }
+
+    let mut tick_data = vec![];
+    for chunk in steps.chunks(TICK_DATA_CHUNK_SIZE) {
+        for (next_tick, _) in chunk {
+            multicall.add_call(v3_pool.ticks(*next_tick), false);
+        }
+
+        let results = aggregate_3(&mut multicall, block_number.map(BlockId::from)).await?;
+        for ((next_tick, initialized), result) in chunk.iter().zip(results) {
+            let liquidity_net = result
+                .and_then(|return_data| decode_liquidity_net(&return_data))
+                .ok_or(AMMError::BatchRequestError(pool.address))?;
+
+            tick_data.push(UniswapV3TickData {
+                initialized: *initialized,
+                tick: if (MIN_TICK..=MAX_TICK).contains(next_tick) {
+                    *next_tick
+                } else {
+                    MIN_TICK
+                },
+                liquidity_net,
+            });
+        }
+    }
+
+    //The ticks that the walk did not reach are returned empty
+    tick_data.resize_with(num_ticks as usize, || UniswapV3TickData {
+        initialized: false,
+        tick: 0,
+        liquidity_net: 0,
+    });
+
+    Ok((
+        tick_data,
+        block_number.ok_or(AMMError::BatchRequestError(pool.address))?,
+    ))
+}
+
+fn decode_uint(return_data: &[u8]) -> Option<U256> {
+    decode(&[ParamType::Uint(256)], return_data)
+        .ok()?
+        .pop()?
+        .into_uint()
+}
+
+fn decode_liquidity_net(return_data: &[u8]) -> Option<i128> {
+    let info = decode(
+        &[
+            ParamType::Uint(128),
+            ParamType::Int(128),
+            ParamType::Uint(256),
+            ParamType::Uint(256),
+            ParamType::Int(56),
+            ParamType::Uint(160),
+            ParamType::Uint(32),
+            ParamType::Bool,
+        ],
+        return_data,
+    )
+    .ok()?;
+
+    Some(I256::from_raw(info.get(1)?.clone().into_int()?).as_i128())
+}
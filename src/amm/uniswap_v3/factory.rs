@@ -8,7 +8,7 @@ use ethers::{
     abi::RawLog,
     prelude::{abigen, EthEvent},
     providers::Middleware,
-    types::{BlockNumber, Filter, Log, H160, H256, U256, U64},
+    types::{Filter, Log, H160, H256, U256, U64},
 };
 use serde::{Deserialize, Serialize};
 use tokio::task::JoinHandle;
@@ -16,9 +16,10 @@ use tokio::task::JoinHandle;
 use crate::{
     amm::{
         factory::{AutomatedMarketMakerFactory, TASK_LIMIT},
-        AutomatedMarketMaker, AMM,
+        pool_address, AutomatedMarketMaker, AMM,
     },
     errors::{AMMError, EventLogError},
+    middleware::logs::{get_logs_with_adaptive_range, MAX_LOG_RANGE_SPLITS},
 };
 
 use super::{batch_request, UniswapV3Pool, BURN_EVENT_SIGNATURE, MINT_EVENT_SIGNATURE};
@@ -38,10 +39,19 @@ pub const POOL_CREATED_EVENT_SIGNATURE: H256 = H256([
     53, 122, 46, 139, 29, 155, 43, 78, 107, 113, 24,
 ]);
 
-#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize)]
+//Fee tiers enabled on the PancakeSwap V3 factory
+pub const PANCAKE_V3_FEE_TIERS: [u32; 4] = [100, 500, 2500, 10000];
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct UniswapV3Factory {
     pub address: H160,
     pub creation_block: u64,
+    //Pools created with a fee outside of these tiers are skipped, an empty set accepts every fee tier
+    #[serde(default)]
+    pub fee_tiers: Vec<u32>,
+    //Forks deploy pools with a different creation code, the Uniswap V3 pool init code hash is used if it is not set
+    #[serde(default)]
+    pub init_code_hash: Option<H256>,
 }
 
 #[async_trait]
@@ -130,6 +140,7 @@ impl AutomatedMarketMakerFactory for UniswapV3Factory {
             tick: 0,
             tick_bitmap: HashMap::new(),
             ticks: HashMap::new(),
+            tick_data_provider: None,
         }))
     }
 }
@@ -139,16 +150,65 @@ impl UniswapV3Factory {
         UniswapV3Factory {
             address,
             creation_block,
+            fee_tiers: vec![],
+            init_code_hash: None,
+        }
+    }
+
+    //Forks such as PancakeSwap V3 enable fee tiers that are not part of the canonical deployment
+    pub fn new_with_fee_tiers(
+        address: H160,
+        creation_block: u64,
+        fee_tiers: Vec<u32>,
+    ) -> UniswapV3Factory {
+        UniswapV3Factory {
+            address,
+            creation_block,
+            fee_tiers,
+            init_code_hash: None,
+        }
+    }
+
+    pub fn new_with_init_code_hash(
+        address: H160,
+        creation_block: u64,
+        init_code_hash: H256,
+    ) -> UniswapV3Factory {
+        UniswapV3Factory {
+            address,
+            creation_block,
+            fee_tiers: vec![],
+            init_code_hash: Some(init_code_hash),
         }
     }
 
+    //Derives the CREATE2 address of the pool without checking that it was deployed
+    pub fn compute_pool_address(&self, token_a: H160, token_b: H160, fee: u32) -> H160 {
+        pool_address::compute_v3_pool_address(
+            self.address,
+            self.init_code_hash
+                .unwrap_or(pool_address::UNISWAP_V3_POOL_INIT_CODE_HASH),
+            token_a,
+            token_b,
+            fee,
+        )
+    }
+
+    pub fn supports_fee_tier(&self, fee: u32) -> bool {
+        self.fee_tiers.is_empty() || self.fee_tiers.contains(&fee)
+    }
+
     //Function to get all pair created events for a given Dex factory address and sync pool data
     pub async fn get_all_pools_from_logs<M: 'static + Middleware>(
-        self,
+        &self,
         to_block: u64,
         step: u64,
         middleware: Arc<M>,
     ) -> Result<Vec<AMM>, AMMError<M>> {
+        if step == 0 {
+            return Err(AMMError::ZeroBatchSize("step"));
+        }
+
         //Unwrap can be used here because the creation block was verified within `Dex::new()`
         let mut from_block = self.creation_block;
         let mut aggregated_amms: HashMap<H160, AMM> = HashMap::new();
@@ -168,19 +228,19 @@ impl UniswapV3Factory {
             }
 
             handles.push(tokio::spawn(async move {
-                let logs = middleware
-                    .get_logs(
-                        &Filter::new()
-                            .topic0(vec![
-                                POOL_CREATED_EVENT_SIGNATURE,
-                                BURN_EVENT_SIGNATURE,
-                                MINT_EVENT_SIGNATURE,
-                            ])
-                            .from_block(BlockNumber::Number(U64([from_block])))
-                            .to_block(BlockNumber::Number(U64([target_block]))),
-                    )
-                    .await
-                    .map_err(AMMError::MiddlewareError)?;
+                let logs = get_logs_with_adaptive_range(
+                    middleware.as_ref(),
+                    &Filter::new().topic0(vec![
+                        POOL_CREATED_EVENT_SIGNATURE,
+                        BURN_EVENT_SIGNATURE,
+                        MINT_EVENT_SIGNATURE,
+                    ]),
+                    from_block,
+                    target_block,
+                    MAX_LOG_RANGE_SPLITS,
+                )
+                .await
+                .map_err(AMMError::MiddlewareError)?;
 
                 Ok::<Vec<Log>, AMMError<M>>(logs)
             }));
@@ -210,6 +270,10 @@ impl UniswapV3Factory {
                         let mut new_pool = self.new_empty_amm_from_log(log)?;
 
                         if let AMM::UniswapV3Pool(ref mut pool) = new_pool {
+                            if !self.supports_fee_tier(pool.fee) {
+                                continue;
+                            }
+
                             pool.tick_spacing = pool.get_tick_spacing(middleware.clone()).await?;
                         }
 
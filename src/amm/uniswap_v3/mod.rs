@@ -1,19 +1,24 @@
 pub mod batch_request;
 pub mod factory;
+pub mod tick_data;
 
 use std::{
-    cmp::Ordering,
     collections::{BTreeMap, HashMap},
     sync::Arc,
 };
 
 use crate::{
-    amm::AutomatedMarketMaker,
+    amm::{
+        depth::{self, MarketDepth},
+        price::Price,
+        price_impact, u256_to_f64, AutomatedMarketMaker, SwapResult,
+    },
     errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
 };
 use async_trait::async_trait;
 use ethers::{
     abi::{ethabi::Bytes, RawLog, Token},
+    contract::Multicall,
     prelude::{AbiError, EthEvent},
     providers::Middleware,
     types::{BlockNumber, Filter, Log, H160, H256, I256, U256, U64},
@@ -24,7 +29,10 @@ use serde::{Deserialize, Serialize};
 use ethers::prelude::abigen;
 use tokio::task::JoinHandle;
 
-use self::factory::POOL_CREATED_EVENT_SIGNATURE;
+use self::{
+    factory::POOL_CREATED_EVENT_SIGNATURE,
+    tick_data::{LocalTickDataProvider, TickDataProvider, TickDataStats},
+};
 
 use super::factory::TASK_LIMIT;
 
@@ -41,7 +49,7 @@ abigen!(
         function token0() external view returns (address)
         function token1() external view returns (address)
         function liquidity() external view returns (uint128)
-        function slot0() external view returns (uint160, int24, uint16, uint16, uint16, uint8, bool)
+        function slot0() external view returns (uint160, int24, uint16, uint16, uint16, uint32, bool)
         function fee() external view returns (uint24)
         function tickSpacing() external view returns (int24)
         function ticks(int24 tick) external view returns (uint128, int128, uint256, uint256, int56, uint160, uint32, bool)
@@ -69,6 +77,32 @@ pub const SWAP_EVENT_SIGNATURE: H256 = H256([
     235, 100, 254, 216, 0, 78, 17, 95, 188, 202, 103,
 ]);
 
+//PancakeSwap V3 pools append the protocol fees of the swap to the Swap event
+pub const PANCAKE_SWAP_EVENT_SIGNATURE: H256 = H256([
+    25, 180, 114, 121, 37, 107, 42, 35, 161, 102, 92, 129, 12, 141, 85, 161, 117, 137, 64, 238, 9,
+    55, 125, 79, 141, 38, 73, 122, 53, 119, 220, 131,
+]);
+
+//Declared by hand so that the generated type does not collide with the canonical `SwapFilter`
+#[derive(Clone, Debug, EthEvent)]
+#[ethevent(
+    name = "Swap",
+    abi = "Swap(address,address,int256,int256,uint160,uint128,int24,uint128,uint128)"
+)]
+pub struct PancakeSwapFilter {
+    #[ethevent(indexed)]
+    pub sender: H160,
+    #[ethevent(indexed)]
+    pub recipient: H160,
+    pub amount_0: I256,
+    pub amount_1: I256,
+    pub sqrt_price_x96: U256,
+    pub liquidity: u128,
+    pub tick: i32,
+    pub protocol_fees_token_0: u128,
+    pub protocol_fees_token_1: u128,
+}
+
 // Burn event signature
 pub const BURN_EVENT_SIGNATURE: H256 = H256([
     12, 57, 108, 217, 137, 163, 159, 68, 89, 181, 250, 26, 237, 106, 154, 141, 205, 188, 69, 144,
@@ -98,6 +132,9 @@ pub struct UniswapV3Pool {
     pub tick_spacing: i32,
     pub tick_bitmap: HashMap<i16, U256>,
     pub ticks: HashMap<i32, Info>,
+    //Tick data fetched through Multicall for simulate_swap_with_ticks, dropped when a mint or burn changes the ticks
+    #[serde(skip)]
+    pub tick_data_provider: Option<LocalTickDataProvider>,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -132,6 +169,7 @@ impl AutomatedMarketMaker for UniswapV3Pool {
     fn sync_on_event_signatures(&self) -> Vec<H256> {
         vec![
             SWAP_EVENT_SIGNATURE,
+            PANCAKE_SWAP_EVENT_SIGNATURE,
             MINT_EVENT_SIGNATURE,
             BURN_EVENT_SIGNATURE,
         ]
@@ -144,7 +182,9 @@ impl AutomatedMarketMaker for UniswapV3Pool {
             self.sync_from_burn_log(log)?;
         } else if event_signature == MINT_EVENT_SIGNATURE {
             self.sync_from_mint_log(log)?;
-        } else if event_signature == SWAP_EVENT_SIGNATURE {
+        } else if event_signature == SWAP_EVENT_SIGNATURE
+            || event_signature == PANCAKE_SWAP_EVENT_SIGNATURE
+        {
             self.sync_from_swap_log(log)?;
         } else {
             Err(EventLogError::InvalidEventSignature)?
@@ -157,20 +197,19 @@ impl AutomatedMarketMaker for UniswapV3Pool {
         vec![self.token_a, self.token_b]
     }
 
-    fn calculate_price(&self, base_token: H160) -> Result<f64, ArithmeticError> {
-        let tick = uniswap_v3_math::tick_math::get_tick_at_sqrt_ratio(self.sqrt_price)?;
-        let shift = self.token_a_decimals as i8 - self.token_b_decimals as i8;
+    fn calculate_price(&self, base_token: H160) -> Result<Price, ArithmeticError> {
+        self.check_liquidity()?;
 
-        let price = match shift.cmp(&0) {
-            Ordering::Less => 1.0001_f64.powi(tick) / 10_f64.powi(-shift as i32),
-            Ordering::Greater => 1.0001_f64.powi(tick) * 10_f64.powi(shift as i32),
-            Ordering::Equal => 1.0001_f64.powi(tick),
-        };
+        let price = Price::from_sqrt_price_x96(
+            self.sqrt_price,
+            self.token_a_decimals,
+            self.token_b_decimals,
+        )?;
 
         if base_token == self.token_a {
             Ok(price)
         } else {
-            Ok(1.0 / price)
+            price.invert()
         }
     }
     // NOTE: This function will not populate the tick_bitmap and ticks, if you want to populate those, you must call populate_tick_data on an initialized pool
@@ -187,6 +226,8 @@ impl AutomatedMarketMaker for UniswapV3Pool {
     fn simulate_swap(&self, token_in: H160, amount_in: U256) -> Result<U256, SwapSimulationError> {
         tracing::info!(?token_in, ?amount_in, "simulating swap");
 
+        self.check_liquidity()?;
+
         if amount_in.is_zero() {
             return Ok(U256::zero());
         }
@@ -200,116 +241,13 @@ impl AutomatedMarketMaker for UniswapV3Pool {
             MAX_SQRT_RATIO - 1
         };
 
-        //Initialize a mutable state state struct to hold the dynamic simulated state of the pool
-        let mut current_state = CurrentState {
-            sqrt_price_x_96: self.sqrt_price, //Active price on the pool
-            amount_calculated: I256::zero(),  //Amount of token_out that has been calculated
-            amount_specified_remaining: I256::from_raw(amount_in), //Amount of token_in that has not been swapped
-            tick: self.tick,                                       //Current i24 tick of the pool
-            liquidity: self.liquidity, //Current available liquidity in the tick range
-        };
-
-        while current_state.amount_specified_remaining != I256::zero()
-            && current_state.sqrt_price_x_96 != sqrt_price_limit_x_96
-        {
-            //Initialize a new step struct to hold the dynamic state of the pool at each step
-            let mut step = StepComputations {
-                sqrt_price_start_x_96: current_state.sqrt_price_x_96, //Set the sqrt_price_start_x_96 to the current sqrt_price_x_96
-                ..Default::default()
-            };
-
-            //Get the next tick from the current tick
-            (step.tick_next, step.initialized) =
-                uniswap_v3_math::tick_bitmap::next_initialized_tick_within_one_word(
-                    &self.tick_bitmap,
-                    current_state.tick,
-                    self.tick_spacing,
-                    zero_for_one,
-                )?;
-
-            // ensure that we do not overshoot the min/max tick, as the tick bitmap is not aware of these bounds
-            //Note: this could be removed as we are clamping in the batch contract
-            step.tick_next = step.tick_next.clamp(MIN_TICK, MAX_TICK);
-
-            //Get the next sqrt price from the input amount
-            step.sqrt_price_next_x96 =
-                uniswap_v3_math::tick_math::get_sqrt_ratio_at_tick(step.tick_next)?;
-
-            //Target spot price
-            let swap_target_sqrt_ratio = if zero_for_one {
-                if step.sqrt_price_next_x96 < sqrt_price_limit_x_96 {
-                    sqrt_price_limit_x_96
-                } else {
-                    step.sqrt_price_next_x96
-                }
-            } else if step.sqrt_price_next_x96 > sqrt_price_limit_x_96 {
-                sqrt_price_limit_x_96
-            } else {
-                step.sqrt_price_next_x96
-            };
-
-            //Compute swap step and update the current state
-            (
-                current_state.sqrt_price_x_96,
-                step.amount_in,
-                step.amount_out,
-                step.fee_amount,
-            ) = uniswap_v3_math::swap_math::compute_swap_step(
-                current_state.sqrt_price_x_96,
-                swap_target_sqrt_ratio,
-                current_state.liquidity,
-                current_state.amount_specified_remaining,
-                self.fee,
-            )?;
-
-            //Decrement the amount remaining to be swapped and amount received from the step
-            current_state.amount_specified_remaining = current_state
-                .amount_specified_remaining
-                .overflowing_sub(I256::from_raw(
-                    step.amount_in.overflowing_add(step.fee_amount).0,
-                ))
-                .0;
-
-            current_state.amount_calculated -= I256::from_raw(step.amount_out);
-
-            //If the price moved all the way to the next price, recompute the liquidity change for the next iteration
-            if current_state.sqrt_price_x_96 == step.sqrt_price_next_x96 {
-                if step.initialized {
-                    let mut liquidity_net = if let Some(info) = self.ticks.get(&step.tick_next) {
-                        info.liquidity_net
-                    } else {
-                        0
-                    };
-
-                    // we are on a tick boundary, and the next tick is initialized, so we must charge a protocol fee
-                    if zero_for_one {
-                        liquidity_net = -liquidity_net;
-                    }
-
-                    current_state.liquidity = if liquidity_net < 0 {
-                        if current_state.liquidity < (-liquidity_net as u128) {
-                            return Err(SwapSimulationError::LiquidityUnderflow);
-                        } else {
-                            current_state.liquidity - (-liquidity_net as u128)
-                        }
-                    } else {
-                        current_state.liquidity + (liquidity_net as u128)
-                    };
-                }
-                //Increment the current tick
-                current_state.tick = if zero_for_one {
-                    step.tick_next.wrapping_sub(1)
-                } else {
-                    step.tick_next
-                }
-                //If the current_state sqrt price is not equal to the step sqrt price, then we are not on the same tick.
-                //Update the current_state.tick to the tick at the current_state.sqrt_price_x_96
-            } else if current_state.sqrt_price_x_96 != step.sqrt_price_start_x_96 {
-                current_state.tick = uniswap_v3_math::tick_math::get_tick_at_sqrt_ratio(
-                    current_state.sqrt_price_x_96,
-                )?;
-            }
-        }
+        let current_state = self.compute_swap(
+            self,
+            zero_for_one,
+            I256::from_raw(amount_in),
+            sqrt_price_limit_x_96,
+        )?;
+        self.check_amount_swapped(amount_in, &current_state)?;
 
         let amount_out = (-current_state.amount_calculated).into_raw();
 
@@ -325,6 +263,8 @@ impl AutomatedMarketMaker for UniswapV3Pool {
     ) -> Result<U256, SwapSimulationError> {
         tracing::info!(?token_in, ?amount_in, "simulating swap");
 
+        self.check_liquidity()?;
+
         if amount_in.is_zero() {
             return Ok(U256::zero());
         }
@@ -338,127 +278,143 @@ impl AutomatedMarketMaker for UniswapV3Pool {
             MAX_SQRT_RATIO - 1
         };
 
-        //Initialize a mutable state state struct to hold the dynamic simulated state of the pool
-        let mut current_state = CurrentState {
-            sqrt_price_x_96: self.sqrt_price, //Active price on the pool
-            amount_calculated: I256::zero(),  //Amount of token_out that has been calculated
-            amount_specified_remaining: I256::from_raw(amount_in), //Amount of token_in that has not been swapped
-            tick: self.tick,                                       //Current i24 tick of the pool
-            liquidity: self.liquidity, //Current available liquidity in the tick range
-        };
+        let current_state = self.compute_swap(
+            self,
+            zero_for_one,
+            I256::from_raw(amount_in),
+            sqrt_price_limit_x_96,
+        )?;
+        self.check_amount_swapped(amount_in, &current_state)?;
 
-        while current_state.amount_specified_remaining != I256::zero()
-            && current_state.sqrt_price_x_96 != sqrt_price_limit_x_96
-        {
-            //Initialize a new step struct to hold the dynamic state of the pool at each step
-            let mut step = StepComputations {
-                sqrt_price_start_x_96: current_state.sqrt_price_x_96, //Set the sqrt_price_start_x_96 to the current sqrt_price_x_96
-                ..Default::default()
-            };
+        //Update the pool state
+        self.liquidity = current_state.liquidity;
+        self.sqrt_price = current_state.sqrt_price_x_96;
+        self.tick = current_state.tick;
 
-            //Get the next tick from the current tick
-            (step.tick_next, step.initialized) =
-                uniswap_v3_math::tick_bitmap::next_initialized_tick_within_one_word(
-                    &self.tick_bitmap,
-                    current_state.tick,
-                    self.tick_spacing,
-                    zero_for_one,
-                )?;
+        let amount_out = (-current_state.amount_calculated).into_raw();
 
-            // ensure that we do not overshoot the min/max tick, as the tick bitmap is not aware of these bounds
-            //Note: this could be removed as we are clamping in the batch contract
-            step.tick_next = step.tick_next.clamp(MIN_TICK, MAX_TICK);
+        tracing::trace!(?amount_out);
 
-            //Get the next sqrt price from the input amount
-            step.sqrt_price_next_x96 =
-                uniswap_v3_math::tick_math::get_sqrt_ratio_at_tick(step.tick_next)?;
+        Ok(amount_out)
+    }
 
-            //Target spot price
-            let swap_target_sqrt_ratio = if zero_for_one {
-                if step.sqrt_price_next_x96 < sqrt_price_limit_x_96 {
-                    sqrt_price_limit_x_96
-                } else {
-                    step.sqrt_price_next_x96
-                }
-            } else if step.sqrt_price_next_x96 > sqrt_price_limit_x_96 {
-                sqrt_price_limit_x_96
-            } else {
-                step.sqrt_price_next_x96
-            };
+    fn swap_fee(&self, _token_in: H160) -> f64 {
+        self.fee as f64 / 1e6
+    }
 
-            //Compute swap step and update the current state
-            (
-                current_state.sqrt_price_x_96,
-                step.amount_in,
-                step.amount_out,
-                step.fee_amount,
-            ) = uniswap_v3_math::swap_math::compute_swap_step(
-                current_state.sqrt_price_x_96,
-                swap_target_sqrt_ratio,
-                current_state.liquidity,
-                current_state.amount_specified_remaining,
-                self.fee,
-            )?;
+    fn fee(&self) -> u32 {
+        self.fee
+    }
 
-            //Decrement the amount remaining to be swapped and amount received from the step
-            current_state.amount_specified_remaining = current_state
-                .amount_specified_remaining
-                .overflowing_sub(I256::from_raw(
-                    step.amount_in.overflowing_add(step.fee_amount).0,
-                ))
-                .0;
+    //Liquidity at the lower price plus the liquidity that the initialized ticks up to the upper price add, i.e. the liquidity of
+    //every position that is active somewhere in the range. Ticks are read from the tick data synced from the mint and burn logs.
+    fn liquidity_in_range(
+        &self,
+        price_lower: Price,
+        price_upper: Price,
+    ) -> Result<U256, ArithmeticError> {
+        if price_lower > price_upper {
+            return Err(ArithmeticError::InvalidPriceRange);
+        }
 
-            current_state.amount_calculated -= I256::from_raw(step.amount_out);
+        let tick_lower = self.tick_at_price(price_lower)?;
+        let tick_upper = self.tick_at_price(price_upper)?;
+
+        let mut ticks = self
+            .ticks
+            .iter()
+            .filter(|(_, info)| info.initialized)
+            .map(|(tick, info)| (*tick, info.liquidity_net))
+            .collect::<Vec<(i32, i128)>>();
+        ticks.sort_unstable_by_key(|(tick, _)| *tick);
+
+        //The liquidity of the current tick includes the liquidity net of every tick at or below it
+        let mut liquidity = self.liquidity as i128;
+        for (tick, liquidity_net) in &ticks {
+            if *tick > tick_lower && *tick <= self.tick {
+                liquidity = liquidity.saturating_sub(*liquidity_net);
+            } else if *tick > self.tick && *tick <= tick_lower {
+                liquidity = liquidity.saturating_add(*liquidity_net);
+            }
+        }
 
-            //If the price moved all the way to the next price, recompute the liquidity change for the next iteration
-            if current_state.sqrt_price_x_96 == step.sqrt_price_next_x96 {
-                if step.initialized {
-                    let mut liquidity_net = if let Some(info) = self.ticks.get(&step.tick_next) {
-                        info.liquidity_net
-                    } else {
-                        0
-                    };
+        let mut liquidity_in_range = U256::from(liquidity.max(0) as u128);
+        for (tick, liquidity_net) in ticks {
+            if tick > tick_lower && tick <= tick_upper && liquidity_net > 0 {
+                liquidity_in_range += U256::from(liquidity_net as u128);
+            }
+        }
 
-                    // we are on a tick boundary, and the next tick is initialized, so we must charge a protocol fee
-                    if zero_for_one {
-                        liquidity_net = -liquidity_net;
-                    }
+        Ok(liquidity_in_range)
+    }
 
-                    current_state.liquidity = if liquidity_net < 0 {
-                        if current_state.liquidity < (-liquidity_net as u128) {
-                            return Err(SwapSimulationError::LiquidityUnderflow);
-                        } else {
-                            current_state.liquidity - (-liquidity_net as u128)
-                        }
-                    } else {
-                        current_state.liquidity + (liquidity_net as u128)
-                    };
-                }
-                //Increment the current tick
-                current_state.tick = if zero_for_one {
-                    step.tick_next.wrapping_sub(1)
-                } else {
-                    step.tick_next
-                }
-                //If the current_state sqrt price is not equal to the step sqrt price, then we are not on the same tick.
-                //Update the current_state.tick to the tick at the current_state.sqrt_price_x_96
-            } else if current_state.sqrt_price_x_96 != step.sqrt_price_start_x_96 {
-                current_state.tick = uniswap_v3_math::tick_math::get_tick_at_sqrt_ratio(
-                    current_state.sqrt_price_x_96,
-                )?;
-            }
+    //Levels between the initialized ticks of the tick data, liquidity outside of the synced ticks is not included
+    fn market_depth(&self, num_levels: usize) -> Result<MarketDepth, ArithmeticError> {
+        let ticks = self
+            .ticks
+            .iter()
+            .filter(|(_, info)| info.initialized)
+            .map(|(tick, info)| (*tick, info.liquidity_net))
+            .collect::<Vec<(i32, i128)>>();
+
+        depth::concentrated_liquidity_depth(
+            self.sqrt_price,
+            self.liquidity,
+            self.tick,
+            &ticks,
+            self.token_a_decimals,
+            self.token_b_decimals,
+            num_levels,
+        )
+    }
+
+    //The swap crosses the initialized ticks up to the min or max price
+    fn calculate_price_impact(
+        &self,
+        token_in: H160,
+        amount_in: U256,
+    ) -> Result<f64, SwapSimulationError> {
+        if amount_in.is_zero() {
+            return Err(SwapSimulationError::ZeroAmountIn);
         }
 
-        //Update the pool state
-        self.liquidity = current_state.liquidity;
-        self.sqrt_price = current_state.sqrt_price_x_96;
-        self.tick = current_state.tick;
+        if token_in != self.token_a && token_in != self.token_b {
+            return Err(SwapSimulationError::TokenNotInPool(token_in));
+        }
 
-        let amount_out = (-current_state.amount_calculated).into_raw();
+        self.check_liquidity()?;
 
-        tracing::trace!(?amount_out);
+        let zero_for_one = token_in == self.token_a;
+        let sqrt_price_limit_x_96 = if zero_for_one {
+            MIN_SQRT_RATIO + 1
+        } else {
+            MAX_SQRT_RATIO - 1
+        };
 
-        Ok(amount_out)
+        let current_state = self.compute_swap(
+            self,
+            zero_for_one,
+            I256::from_raw(amount_in),
+            sqrt_price_limit_x_96,
+        )?;
+        self.check_amount_swapped(amount_in, &current_state)?;
+
+        //The price of token a is the square of the sqrt price, the decimals of the tokens cancel out
+        let (sqrt_price_before, sqrt_price_after) = (
+            u256_to_f64(self.sqrt_price),
+            u256_to_f64(current_state.sqrt_price_x_96),
+        );
+        let sqrt_price_ratio = if zero_for_one {
+            sqrt_price_after / sqrt_price_before
+        } else {
+            sqrt_price_before / sqrt_price_after
+        };
+
+        Ok(price_impact(1.0, sqrt_price_ratio.powi(2)))
+    }
+
+    fn token_decimals(&self) -> Vec<u8> {
+        vec![self.token_a_decimals, self.token_b_decimals]
     }
 
     fn get_token_out(&self, token_in: H160) -> H160 {
@@ -468,6 +424,49 @@ impl AutomatedMarketMaker for UniswapV3Pool {
             self.token_a
         }
     }
+
+    //Swaps an exact amount out through the ticks towards the min or max price, like exactOutputSingle of the quoter
+    fn get_amount_in(
+        &self,
+        token_out: H160,
+        amount_out: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        if token_out != self.token_a && token_out != self.token_b {
+            return Err(SwapSimulationError::TokenNotInPool(token_out));
+        }
+
+        self.check_liquidity()?;
+
+        if amount_out.is_zero() {
+            return Ok(U256::zero());
+        }
+
+        let insufficient_output = |available: U256| SwapSimulationError::InsufficientOutput {
+            pool: self.address,
+            requested: amount_out,
+            available,
+        };
+
+        let zero_for_one = token_out == self.token_b;
+        let sqrt_price_limit_x_96 = if zero_for_one {
+            MIN_SQRT_RATIO + 1
+        } else {
+            MAX_SQRT_RATIO - 1
+        };
+
+        let amount_specified =
+            -I256::try_from(amount_out).map_err(|_| insufficient_output(U256::zero()))?;
+        let current_state =
+            self.compute_swap(self, zero_for_one, amount_specified, sqrt_price_limit_x_96)?;
+
+        //The amount remaining is the part of the amount out that the liquidity up to the price limit could not swap
+        let amount_remaining = (-current_state.amount_specified_remaining).into_raw();
+        if !amount_remaining.is_zero() {
+            return Err(insufficient_output(amount_out - amount_remaining));
+        }
+
+        Ok(current_state.amount_calculated.into_raw())
+    }
 }
 
 impl UniswapV3Pool {
@@ -499,6 +498,7 @@ impl UniswapV3Pool {
             tick_spacing,
             tick_bitmap,
             ticks,
+            tick_data_provider: None,
         }
     }
 
@@ -521,6 +521,7 @@ impl UniswapV3Pool {
             fee: 0,
             tick_bitmap: HashMap::new(),
             ticks: HashMap::new(),
+            tick_data_provider: None,
         };
 
         //We need to get tick spacing before populating tick data because tick spacing can not be uninitialized when syncing burn and mint logs
@@ -583,6 +584,7 @@ impl UniswapV3Pool {
                 tick: 0,
                 tick_bitmap: HashMap::new(),
                 ticks: HashMap::new(),
+                tick_data_provider: None,
             })
         } else {
             Err(EventLogError::InvalidEventSignature)
@@ -676,10 +678,6 @@ impl UniswapV3Pool {
         Ok(())
     }
 
-    pub fn fee(&self) -> u32 {
-        self.fee
-    }
-
     pub fn data_is_populated(&self) -> bool {
         !(self.token_a.is_zero() || self.token_b.is_zero())
     }
@@ -745,6 +743,54 @@ impl UniswapV3Pool {
         Ok(tick_info.1)
     }
 
+    //(tick, liquidity net) of every initialized tick in [tick_lower, tick_upper] in ascending order, e.g. to draw the liquidity depth
+    //of the pool. The tick bitmap words of the range and their ticks are fetched through Multicall at the latest block.
+    pub async fn get_liquidity_net_for_tick_range<M: Middleware>(
+        &self,
+        tick_lower: i32,
+        tick_upper: i32,
+        middleware: Arc<M>,
+    ) -> Result<Vec<(i32, i128)>, AMMError<M>> {
+        let (tick_lower, tick_upper) = (tick_lower.max(MIN_TICK), tick_upper.min(MAX_TICK));
+        if tick_lower > tick_upper {
+            return Ok(vec![]);
+        }
+
+        let tick_spacing = if self.tick_spacing == 0 {
+            self.get_tick_spacing(middleware.clone()).await?
+        } else {
+            self.tick_spacing
+        };
+
+        let (min_word, _) = tick_data::word_position(tick_lower, tick_spacing);
+        let (max_word, _) = tick_data::word_position(tick_upper, tick_spacing);
+
+        let mut multicall = Multicall::new(middleware.clone(), None).await?;
+        let (_, initialized_ticks) = tick_data::fetch_tick_bitmap(
+            self.address,
+            tick_spacing,
+            min_word,
+            max_word,
+            &mut multicall,
+            middleware.clone(),
+        )
+        .await?;
+
+        //The first and last word can contain ticks outside of the range
+        let initialized_ticks = initialized_ticks
+            .into_iter()
+            .filter(|tick| (tick_lower..=tick_upper).contains(tick))
+            .collect::<Vec<i32>>();
+        let ticks =
+            tick_data::fetch_ticks(self.address, &initialized_ticks, &mut multicall, middleware)
+                .await?;
+
+        Ok(initialized_ticks
+            .into_iter()
+            .filter_map(|tick| ticks.get(&tick).map(|info| (tick, info.liquidity_net)))
+            .collect())
+    }
+
     pub async fn get_initialized<M: Middleware>(
         &self,
         tick: i32,
@@ -805,6 +851,7 @@ impl UniswapV3Pool {
         //We are only using this function when a mint or burn event is emitted,
         //therefore we do not need to checkTicks as that has happened before the event is emitted
         self.update_position(tick_lower, tick_upper, liquidity_delta);
+        self.tick_data_provider = None;
 
         if liquidity_delta != 0 {
             //if the tick is between the tick lower and tick upper, update the liquidity between the ticks
@@ -893,12 +940,27 @@ impl UniswapV3Pool {
         }
     }
 
+    //Both the canonical and the PancakeSwap V3 layout are accepted, the protocol fees do not affect the pool state
     pub fn sync_from_swap_log(&mut self, log: Log) -> Result<(), AbiError> {
-        let swap_event = SwapFilter::decode_log(&RawLog::from(log))?;
+        let (sqrt_price, liquidity, tick) = if log.topics[0] == PANCAKE_SWAP_EVENT_SIGNATURE {
+            let swap_event = PancakeSwapFilter::decode_log(&RawLog::from(log))?;
+            (
+                swap_event.sqrt_price_x96,
+                swap_event.liquidity,
+                swap_event.tick,
+            )
+        } else {
+            let swap_event = SwapFilter::decode_log(&RawLog::from(log))?;
+            (
+                swap_event.sqrt_price_x96,
+                swap_event.liquidity,
+                swap_event.tick,
+            )
+        };
 
-        self.sqrt_price = swap_event.sqrt_price_x96;
-        self.liquidity = swap_event.liquidity;
-        self.tick = swap_event.tick;
+        self.sqrt_price = sqrt_price;
+        self.liquidity = liquidity;
+        self.tick = tick;
 
         Ok(())
     }
@@ -949,43 +1011,354 @@ impl UniswapV3Pool {
     ) -> Result<H160, AMMError<M>> {
         let v3_pool = IUniswapV3Pool::new(self.address, middleware);
 
-        let token_1 = match v3_pool.token_1().call().await {
-            Ok(result) => result,
-            Err(contract_error) => return Err(AMMError::ContractError(contract_error)),
-        };
+        let token_1 = match v3_pool.token_1().call().await {
+            Ok(result) => result,
+            Err(contract_error) => return Err(AMMError::ContractError(contract_error)),
+        };
+
+        Ok(token_1)
+    }
+    /* Legend:
+       sqrt(price) = sqrt(y/x)
+       L = sqrt(x*y)
+       ==> x = L^2/price
+       ==> y = L^2*price
+    */
+    pub fn calculate_virtual_reserves(&self) -> Result<(u128, u128), ArithmeticError> {
+        let tick = uniswap_v3_math::tick_math::get_tick_at_sqrt_ratio(self.sqrt_price)?;
+        let price = 1.0001_f64.powi(tick);
+
+        let sqrt_price = BigFloat::from_f64(price.sqrt());
+        let liquidity = BigFloat::from_u128(self.liquidity);
+
+        let (reserve_0, reserve_1) = if !sqrt_price.is_zero() {
+            let reserve_x = liquidity.div(&sqrt_price);
+            let reserve_y = liquidity.mul(&sqrt_price);
+
+            (reserve_x, reserve_y)
+        } else {
+            (BigFloat::from(0), BigFloat::from(0))
+        };
+
+        Ok((
+            reserve_0
+                .to_u128()
+                .ok_or(ArithmeticError::U128ConversionError)?,
+            reserve_1
+                .to_u128()
+                .ok_or(ArithmeticError::U128ConversionError)?,
+        ))
+    }
+
+    //Exact version of calculate_virtual_reserves computed from the sqrt price instead of the tick, see virtual_reserves_at_sqrt_price
+    pub fn virtual_reserves(&self) -> Result<(U256, U256), ArithmeticError> {
+        virtual_reserves_at_sqrt_price(self.sqrt_price, self.liquidity)
+    }
+
+    //Fetches the tick bitmap words that contain [min_tick, max_tick] and their initialized ticks at the block into `tick_bitmap` and
+    //`ticks`, replacing the tick data of those words. The words also become the tick data provider of the pool, so that a swap that
+    //leaves them returns an error instead of treating the ticks outside of them as uninitialized.
+    pub async fn populate_tick_data_range<M: Middleware>(
+        &mut self,
+        min_tick: i32,
+        max_tick: i32,
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<TickDataStats, AMMError<M>> {
+        if self.tick_spacing == 0 {
+            self.tick_spacing = self.get_tick_spacing(middleware.clone()).await?;
+        }
+
+        let tick_data = LocalTickDataProvider::fetch_tick_range(
+            self,
+            min_tick,
+            max_tick,
+            block_number,
+            middleware,
+        )
+        .await?;
+
+        let words = tick_data.min_word..=tick_data.max_word;
+        let tick_spacing = self.tick_spacing;
+        self.tick_bitmap.retain(|word, _| !words.contains(word));
+        self.ticks
+            .retain(|tick, _| !words.contains(&tick_data::word_position(*tick, tick_spacing).0));
+        self.tick_bitmap.extend(tick_data.tick_bitmap.clone());
+        self.ticks.extend(tick_data.ticks.clone());
+
+        let stats = tick_data.stats();
+        tracing::debug!(?self.address, ?stats, "populated tick data");
+        self.tick_data_provider = Some(tick_data);
+
+        Ok(stats)
+    }
+
+    //Tick data of the whole pool, see populate_tick_data_range. Pools with a small tick spacing have thousands of words to fetch.
+    pub async fn populate_full_tick_data<M: Middleware>(
+        &mut self,
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<TickDataStats, AMMError<M>> {
+        self.populate_tick_data_range(MIN_TICK, MAX_TICK, block_number, middleware)
+            .await
+    }
+
+    //Fetches the `word_range` tick bitmap words on each side of the current tick and their initialized ticks at the block,
+    //DEFAULT_TICK_WORD_RANGE is enough for most swaps that do not move the price by more than a few percent
+    pub async fn populate_tick_data_provider<M: Middleware>(
+        &mut self,
+        word_range: i16,
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        self.tick_data_provider =
+            Some(LocalTickDataProvider::fetch(self, word_range, block_number, middleware).await?);
+
+        Ok(())
+    }
+
+    //Tick that contains the price of token a denominated in token b, prices outside of the ticks of the pool are moved to the nearest tick
+    pub fn tick_at_price(&self, price: Price) -> Result<i32, ArithmeticError> {
+        let sqrt_price = price
+            .to_sqrt_price_x96(self.token_a_decimals, self.token_b_decimals)?
+            .max(MIN_SQRT_RATIO)
+            .min(MAX_SQRT_RATIO - 1);
+
+        Ok(uniswap_v3_math::tick_math::get_tick_at_sqrt_ratio(
+            sqrt_price,
+        )?)
+    }
+
+    //Simulates a swap up to the sqrt price limit without any RPC calls. Ticks are read from the tick data provider if it is
+    //populated, otherwise from the tick data synced from the mint and burn logs of the pool.
+    //The amount of token in that is not swapped when the price reaches the limit is excluded from the effective price.
+    pub fn simulate_swap_with_ticks(
+        &self,
+        token_in: H160,
+        amount_in: U256,
+        sqrt_price_limit: U256,
+    ) -> Result<SwapResult, SwapSimulationError> {
+        if token_in != self.token_a && token_in != self.token_b {
+            return Err(SwapSimulationError::TokenNotInPool(token_in));
+        }
+
+        self.check_liquidity()?;
+
+        let zero_for_one = token_in == self.token_a;
+
+        //A zero limit swaps the whole amount like the quoter does
+        let sqrt_price_limit = if zero_for_one {
+            if sqrt_price_limit.is_zero() {
+                MIN_SQRT_RATIO + 1
+            } else {
+                sqrt_price_limit
+                    .max(MIN_SQRT_RATIO + 1)
+                    .min(self.sqrt_price)
+            }
+        } else if sqrt_price_limit.is_zero() {
+            MAX_SQRT_RATIO - 1
+        } else {
+            sqrt_price_limit
+                .min(MAX_SQRT_RATIO - 1)
+                .max(self.sqrt_price)
+        };
+
+        let current_state = match &self.tick_data_provider {
+            Some(tick_data_provider) => self.compute_swap(
+                tick_data_provider,
+                zero_for_one,
+                I256::from_raw(amount_in),
+                sqrt_price_limit,
+            )?,
+            None => self.compute_swap(
+                self,
+                zero_for_one,
+                I256::from_raw(amount_in),
+                sqrt_price_limit,
+            )?,
+        };
+
+        let amount_swapped = amount_in - current_state.amount_specified_remaining.into_raw();
+        let amount_out = (-current_state.amount_calculated).into_raw();
+
+        let (token_in_decimals, token_out_decimals) = if zero_for_one {
+            (self.token_a_decimals, self.token_b_decimals)
+        } else {
+            (self.token_b_decimals, self.token_a_decimals)
+        };
+
+        Ok(SwapResult::new(
+            amount_swapped,
+            amount_out,
+            token_in_decimals,
+            token_out_decimals,
+            self.swap_fee(token_in),
+            self.calculate_price(token_in)?.to_f64(),
+        ))
+    }
+
+    //A pool that is not initialized has no price, a pool without active liquidity or initialized ticks has no liquidity to swap through
+    fn check_liquidity(&self) -> Result<(), ArithmeticError> {
+        if self.sqrt_price.is_zero()
+            || (self.liquidity == 0 && self.ticks.is_empty() && self.tick_data_provider.is_none())
+        {
+            return Err(ArithmeticError::ZeroLiquidity(self.address));
+        }
+
+        Ok(())
+    }
+
+    //Amount in that is left once a swap reaches the min or max price can not be swapped through the liquidity of the pool
+    fn check_amount_swapped(
+        &self,
+        amount_in: U256,
+        current_state: &CurrentState,
+    ) -> Result<(), SwapSimulationError> {
+        let amount_remaining = current_state.amount_specified_remaining.into_raw();
+        if amount_remaining.is_zero() {
+            return Ok(());
+        }
+
+        Err(SwapSimulationError::InsufficientLiquidity {
+            pool: self.address,
+            requested: amount_in,
+            available: amount_in - amount_remaining,
+        })
+    }
+
+    //Steps through the initialized ticks of the provider until the amount specified is swapped or the price reaches the limit.
+    //As in the pool contract, a positive amount specified is an exact amount in and a negative one an exact amount out.
+    fn compute_swap<T: TickDataProvider>(
+        &self,
+        tick_data_provider: &T,
+        zero_for_one: bool,
+        amount_specified: I256,
+        sqrt_price_limit_x_96: U256,
+    ) -> Result<CurrentState, SwapSimulationError> {
+        let exact_input = amount_specified > I256::zero();
+
+        //Initialize a mutable state state struct to hold the dynamic simulated state of the pool
+        let mut current_state = CurrentState {
+            sqrt_price_x_96: self.sqrt_price, //Active price on the pool
+            amount_calculated: I256::zero(),  //Amount of token_out that has been calculated
+            amount_specified_remaining: amount_specified, //Amount of token_in that has not been swapped
+            tick: self.tick,                              //Current i24 tick of the pool
+            liquidity: self.liquidity, //Current available liquidity in the tick range
+        };
+
+        while current_state.amount_specified_remaining != I256::zero()
+            && current_state.sqrt_price_x_96 != sqrt_price_limit_x_96
+        {
+            //Initialize a new step struct to hold the dynamic state of the pool at each step
+            let mut step = StepComputations {
+                sqrt_price_start_x_96: current_state.sqrt_price_x_96, //Set the sqrt_price_start_x_96 to the current sqrt_price_x_96
+                ..Default::default()
+            };
+
+            //Get the next tick from the current tick
+            (step.tick_next, step.initialized) = tick_data_provider
+                .next_initialized_tick_within_one_word(
+                    current_state.tick,
+                    self.tick_spacing,
+                    zero_for_one,
+                )?;
+
+            // ensure that we do not overshoot the min/max tick, as the tick bitmap is not aware of these bounds
+            //Note: this could be removed as we are clamping in the batch contract
+            step.tick_next = step.tick_next.clamp(MIN_TICK, MAX_TICK);
+
+            //Get the next sqrt price from the input amount
+            step.sqrt_price_next_x96 =
+                uniswap_v3_math::tick_math::get_sqrt_ratio_at_tick(step.tick_next)?;
+
+            //Target spot price
+            let swap_target_sqrt_ratio = if zero_for_one {
+                if step.sqrt_price_next_x96 < sqrt_price_limit_x_96 {
+                    sqrt_price_limit_x_96
+                } else {
+                    step.sqrt_price_next_x96
+                }
+            } else if step.sqrt_price_next_x96 > sqrt_price_limit_x_96 {
+                sqrt_price_limit_x_96
+            } else {
+                step.sqrt_price_next_x96
+            };
+
+            //Compute swap step and update the current state
+            (
+                current_state.sqrt_price_x_96,
+                step.amount_in,
+                step.amount_out,
+                step.fee_amount,
+            ) = uniswap_v3_math::swap_math::compute_swap_step(
+                current_state.sqrt_price_x_96,
+                swap_target_sqrt_ratio,
+                current_state.liquidity,
+                current_state.amount_specified_remaining,
+                self.fee,
+            )?;
 
-        Ok(token_1)
-    }
-    /* Legend:
-       sqrt(price) = sqrt(y/x)
-       L = sqrt(x*y)
-       ==> x = L^2/price
-       ==> y = L^2*price
-    */
-    pub fn calculate_virtual_reserves(&self) -> Result<(u128, u128), ArithmeticError> {
-        let tick = uniswap_v3_math::tick_math::get_tick_at_sqrt_ratio(self.sqrt_price)?;
-        let price = 1.0001_f64.powi(tick);
+            //Decrement the amount remaining to be swapped and amount received from the step
+            if exact_input {
+                current_state.amount_specified_remaining = current_state
+                    .amount_specified_remaining
+                    .overflowing_sub(I256::from_raw(
+                        step.amount_in.overflowing_add(step.fee_amount).0,
+                    ))
+                    .0;
+
+                current_state.amount_calculated -= I256::from_raw(step.amount_out);
+            } else {
+                current_state.amount_specified_remaining = current_state
+                    .amount_specified_remaining
+                    .overflowing_add(I256::from_raw(step.amount_out))
+                    .0;
 
-        let sqrt_price = BigFloat::from_f64(price.sqrt());
-        let liquidity = BigFloat::from_u128(self.liquidity);
+                current_state.amount_calculated +=
+                    I256::from_raw(step.amount_in.overflowing_add(step.fee_amount).0);
+            }
 
-        let (reserve_0, reserve_1) = if !sqrt_price.is_zero() {
-            let reserve_x = liquidity.div(&sqrt_price);
-            let reserve_y = liquidity.mul(&sqrt_price);
+            //If the price moved all the way to the next price, recompute the liquidity change for the next iteration
+            if current_state.sqrt_price_x_96 == step.sqrt_price_next_x96 {
+                if step.initialized {
+                    let mut liquidity_net =
+                        if let Some(info) = tick_data_provider.get_tick(step.tick_next) {
+                            info.liquidity_net
+                        } else {
+                            0
+                        };
 
-            (reserve_x, reserve_y)
-        } else {
-            (BigFloat::from(0), BigFloat::from(0))
-        };
+                    // we are on a tick boundary, and the next tick is initialized, so we must charge a protocol fee
+                    if zero_for_one {
+                        liquidity_net = -liquidity_net;
+                    }
 
-        Ok((
-            reserve_0
-                .to_u128()
-                .ok_or(ArithmeticError::U128ConversionError)?,
-            reserve_1
-                .to_u128()
-                .ok_or(ArithmeticError::U128ConversionError)?,
-        ))
+                    current_state.liquidity = if liquidity_net < 0 {
+                        if current_state.liquidity < (-liquidity_net as u128) {
+                            return Err(SwapSimulationError::LiquidityUnderflow);
+                        } else {
+                            current_state.liquidity - (-liquidity_net as u128)
+                        }
+                    } else {
+                        current_state.liquidity + (liquidity_net as u128)
+                    };
+                }
+                //Increment the current tick
+                current_state.tick = if zero_for_one {
+                    step.tick_next.wrapping_sub(1)
+                } else {
+                    step.tick_next
+                }
+                //If the current_state sqrt price is not equal to the step sqrt price, then we are not on the same tick.
+                //Update the current_state.tick to the tick at the current_state.sqrt_price_x_96
+            } else if current_state.sqrt_price_x_96 != step.sqrt_price_start_x_96 {
+                current_state.tick = uniswap_v3_math::tick_math::get_tick_at_sqrt_ratio(
+                    current_state.sqrt_price_x_96,
+                )?;
+            }
+        }
+
+        Ok(current_state)
     }
 
     pub fn calculate_compressed(&self, tick: i32) -> i32 {
@@ -1041,8 +1414,8 @@ pub struct StepComputations {
     pub fee_amount: U256,
 }
 
-const MIN_TICK: i32 = -887272;
-const MAX_TICK: i32 = 887272;
+pub const MIN_TICK: i32 = -887272;
+pub const MAX_TICK: i32 = 887272;
 
 pub struct Tick {
     pub liquidity_gross: u128,
@@ -1055,8 +1428,29 @@ pub struct Tick {
     pub initialized: bool,
 }
 
+//Reserves of a constant product pool with the same price and depth as the active liquidity of a concentrated liquidity pool,
+//x = L / sqrt(P) and y = L * sqrt(P). The reserves ignore the liquidity outside of the active tick range, so they only
+//quote swaps that do not cross a tick. Pools without a price have no reserves.
+pub fn virtual_reserves_at_sqrt_price(
+    sqrt_price_x96: U256,
+    liquidity: u128,
+) -> Result<(U256, U256), ArithmeticError> {
+    if sqrt_price_x96.is_zero() {
+        return Ok((U256::zero(), U256::zero()));
+    }
+
+    let liquidity = U256::from(liquidity);
+    let reserve_0 = (liquidity << 96) / sqrt_price_x96;
+    let reserve_1 = U256::try_from(liquidity.full_mul(sqrt_price_x96) >> 96)
+        .map_err(|_| ArithmeticError::SqrtPriceOverflow)?;
+
+    Ok((reserve_0, reserve_1))
+}
+
 #[cfg(test)]
 mod test {
+    use super::batch_request;
+    use super::tick_data;
     use super::IUniswapV3Pool;
     #[allow(unused)]
     #[allow(unused)]
@@ -1889,6 +2283,29 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_virtual_reserves() -> eyre::Result<()> {
+        let liquidity = 10_u128.pow(18);
+
+        //A sqrt price of 2 is a price of 4 token b per token a
+        let pool = UniswapV3Pool {
+            sqrt_price: U256::one() << 97,
+            liquidity,
+            ..Default::default()
+        };
+        assert_eq!(
+            pool.virtual_reserves()?,
+            (U256::from(liquidity / 2), U256::from(liquidity * 2))
+        );
+
+        assert_eq!(
+            UniswapV3Pool::default().virtual_reserves()?,
+            (U256::zero(), U256::zero())
+        );
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_calculate_price() -> eyre::Result<()> {
         let rpc_endpoint = std::env::var("ETHEREUM_RPC_ENDPOINT")?;
@@ -1909,11 +2326,432 @@ mod test {
         let sqrt_price = block_pool.slot_0().block(16515398).call().await?.0;
         pool.sqrt_price = sqrt_price;
 
-        let float_price_a = pool.calculate_price(pool.token_a)?;
-        let float_price_b = pool.calculate_price(pool.token_b)?;
+        let float_price_a = pool.calculate_price(pool.token_a)?.to_f64();
+        let float_price_b = pool.calculate_price(pool.token_b)?.to_f64();
+
+        //The price is derived from the sqrt price, which is within one tick of the price at the current tick
+        assert!((float_price_a - 0.0006081236083117488).abs() / 0.0006081236083117488 < 1e-4);
+        assert!((float_price_b - 1644.4025299004006).abs() / 1644.4025299004006 < 1e-4);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_simulate_swap_with_ticks() -> eyre::Result<()> {
+        let rpc_endpoint = std::env::var("ETHEREUM_RPC_ENDPOINT")?;
+        let middleware = Arc::new(Provider::<Http>::try_from(rpc_endpoint)?);
+
+        //Only the ticks around the current tick are fetched instead of syncing every mint and burn since the pool was created
+        let mut pool = UniswapV3Pool {
+            address: H160::from_str("0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640")?,
+            ..Default::default()
+        };
+        let synced_block = middleware.get_block_number().await?.as_u64();
+        pool.populate_data(Some(synced_block), middleware.clone())
+            .await?;
+        pool.populate_tick_data_provider(
+            tick_data::DEFAULT_TICK_WORD_RANGE,
+            Some(synced_block),
+            middleware.clone(),
+        )
+        .await?;
+
+        let quoter = IQuoter::new(
+            H160::from_str("0xb27308f9f90d607463bb33ea1bebb41c27ce5ab6")?,
+            middleware.clone(),
+        );
+        let amount_in = U256::from_dec_str("1000000000000")?; // 1_000_000 USDC
+
+        let swap_result = pool.simulate_swap_with_ticks(pool.token_a, amount_in, U256::zero())?;
+        let expected_amount_out = quoter
+            .quote_exact_input_single(
+                pool.token_a,
+                pool.token_b,
+                pool.fee,
+                amount_in,
+                U256::zero(),
+            )
+            .block(synced_block)
+            .call()
+            .await?;
+        assert_eq!(swap_result.amount_out, expected_amount_out);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_populate_tick_data_range() -> eyre::Result<()> {
+        let rpc_endpoint = std::env::var("ETHEREUM_RPC_ENDPOINT")?;
+        let middleware = Arc::new(Provider::<Http>::try_from(rpc_endpoint)?);
+
+        let mut pool = UniswapV3Pool {
+            address: H160::from_str("0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640")?,
+            ..Default::default()
+        };
+        let synced_block = middleware.get_block_number().await?.as_u64();
+        pool.populate_data(Some(synced_block), middleware.clone())
+            .await?;
+
+        //A tick spacing of 10 puts 2560 ticks in a word
+        let stats = pool
+            .populate_tick_data_range(
+                pool.tick - 25600,
+                pool.tick + 25600,
+                Some(synced_block),
+                middleware.clone(),
+            )
+            .await?;
+        assert!(stats.words >= 20 && stats.words <= 21);
+        assert_eq!(stats.ticks, pool.ticks.len());
+        assert!(stats.initialized_words > 0);
+
+        //The whole pool includes the range
+        let full_stats = pool
+            .populate_full_tick_data(Some(synced_block), middleware.clone())
+            .await?;
+        assert!(full_stats.words > stats.words && full_stats.ticks >= stats.ticks);
+        assert_eq!(full_stats.ticks, pool.ticks.len());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tick_data_multicall() -> eyre::Result<()> {
+        let rpc_endpoint = std::env::var("ETHEREUM_RPC_ENDPOINT")?;
+        let middleware = Arc::new(Provider::<Http>::try_from(rpc_endpoint)?);
+
+        let mut pool = UniswapV3Pool {
+            address: H160::from_str("0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640")?,
+            ..Default::default()
+        };
+        let synced_block = middleware.get_block_number().await?;
+        pool.populate_data(Some(synced_block.as_u64()), middleware.clone())
+            .await?;
+
+        //Both backends step through the same ticks at the same block
+        for zero_for_one in [true, false] {
+            let (batch_contract_ticks, batch_contract_block) =
+                batch_request::get_uniswap_v3_tick_data_batch_request(
+                    &pool,
+                    pool.tick,
+                    zero_for_one,
+                    10,
+                    Some(synced_block),
+                    middleware.clone(),
+                )
+                .await?;
+            let (multicall_ticks, multicall_block) =
+                batch_request::get_uniswap_v3_tick_data_multicall(
+                    &pool,
+                    pool.tick,
+                    zero_for_one,
+                    10,
+                    Some(synced_block),
+                    crate::amm::batch_backend::MULTICALL3_ADDRESS,
+                    middleware.clone(),
+                )
+                .await?;
+
+            assert_eq!(multicall_block, batch_contract_block);
+            assert_eq!(
+                multicall_ticks
+                    .iter()
+                    .map(|tick| (tick.initialized, tick.tick, tick.liquidity_net))
+                    .collect::<Vec<_>>(),
+                batch_contract_ticks
+                    .iter()
+                    .map(|tick| (tick.initialized, tick.tick, tick.liquidity_net))
+                    .collect::<Vec<_>>()
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_liquidity_net_for_tick_range() -> eyre::Result<()> {
+        let rpc_endpoint = std::env::var("ETHEREUM_RPC_ENDPOINT")?;
+        let middleware = Arc::new(Provider::<Http>::try_from(rpc_endpoint)?);
+
+        let mut pool = UniswapV3Pool {
+            address: H160::from_str("0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640")?,
+            ..Default::default()
+        };
+        pool.populate_data(None, middleware.clone()).await?;
+
+        let (tick_lower, tick_upper) = (
+            pool.tick - 100 * pool.tick_spacing,
+            pool.tick + 100 * pool.tick_spacing,
+        );
+        let liquidity_net = pool
+            .get_liquidity_net_for_tick_range(tick_lower, tick_upper, middleware.clone())
+            .await?;
+
+        //The most liquid pool on mainnet has positions ending around its current tick
+        assert!(!liquidity_net.is_empty());
+        assert!(liquidity_net
+            .windows(2)
+            .all(|ticks| ticks[0].0 < ticks[1].0));
+        assert!(liquidity_net
+            .iter()
+            .all(|(tick, _)| (tick_lower..=tick_upper).contains(tick)
+                && tick % pool.tick_spacing == 0));
+
+        let (tick, net) = liquidity_net[0];
+        assert_eq!(pool.get_liquidity_net(tick, middleware.clone()).await?, net);
+
+        assert!(pool
+            .get_liquidity_net_for_tick_range(tick_upper, tick_lower, middleware)
+            .await?
+            .is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_calculate_price_impact() -> eyre::Result<()> {
+        use crate::errors::SwapSimulationError;
+
+        //Pool at tick 0 with liquidity in [-600, 600) only
+        let mut pool = UniswapV3Pool {
+            token_a: H160::from_low_u64_be(1),
+            token_a_decimals: 18,
+            token_b: H160::from_low_u64_be(2),
+            token_b_decimals: 6,
+            sqrt_price: U256::one() << 96,
+            fee: 3000,
+            tick_spacing: 60,
+            ..Default::default()
+        };
+        pool.modify_position(-600, 600, 10_i128.pow(18));
+
+        for token_in in [pool.token_a, pool.token_b] {
+            let amount_in = U256::exp10(15);
+            let price_impact = pool.calculate_price_impact(token_in, amount_in)?;
+
+            let mut swapped_pool = pool.clone();
+            swapped_pool.simulate_swap_mut(token_in, amount_in)?;
+            let expected_price_impact = 1.0
+                - swapped_pool.calculate_price(token_in)?.to_f64()
+                    / pool.calculate_price(token_in)?.to_f64();
+
+            assert!(price_impact > 0.0);
+            assert!((price_impact - expected_price_impact).abs() < 1e-9);
+        }
+
+        //There is no liquidity below tick -600 to swap the rest of the amount through
+        assert!(matches!(
+            pool.calculate_price_impact(pool.token_a, U256::exp10(20)),
+            Err(SwapSimulationError::InsufficientLiquidity { .. })
+        ));
+        assert!(matches!(
+            pool.simulate_swap(pool.token_a, U256::exp10(20)),
+            Err(SwapSimulationError::InsufficientLiquidity { requested, available, .. })
+                if requested == U256::exp10(20) && !available.is_zero() && available < requested
+        ));
+        assert!(matches!(
+            pool.calculate_price_impact(pool.token_a, U256::zero()),
+            Err(SwapSimulationError::ZeroAmountIn)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_amount_in() -> eyre::Result<()> {
+        use crate::{amm::search_amount_in, errors::SwapSimulationError};
+
+        //Pool at tick 0 with liquidity in [-600, 600) only
+        let mut pool = UniswapV3Pool {
+            token_a: H160::from_low_u64_be(1),
+            token_b: H160::from_low_u64_be(2),
+            sqrt_price: U256::one() << 96,
+            fee: 3000,
+            tick_spacing: 60,
+            ..Default::default()
+        };
+        pool.modify_position(-600, 600, 10_i128.pow(18));
+
+        for token_out in [pool.token_a, pool.token_b] {
+            let token_in = pool.get_token_out(token_out);
+            let amount_out = U256::exp10(15);
+            let amount_in = pool.get_amount_in(token_out, amount_out)?;
+
+            //Swapping the amount in back out of the same state returns the amount out up to the rounding of the steps
+            let swapped = pool.simulate_swap(token_in, amount_in)?;
+            assert!(swapped.max(amount_out) - swapped.min(amount_out) <= U256::from(1000));
+
+            let searched_amount_in = search_amount_in(pool.address, amount_out, |amount_in| {
+                pool.simulate_swap(token_in, amount_in)
+            })?;
+            assert!(
+                amount_in.max(searched_amount_in) - amount_in.min(searched_amount_in)
+                    <= U256::from(1000)
+            );
+        }
+
+        //There is no liquidity below tick -600 to swap more of token b out
+        assert!(matches!(
+            pool.get_amount_in(pool.token_b, U256::exp10(20)),
+            Err(SwapSimulationError::InsufficientOutput { requested, available, .. })
+                if requested == U256::exp10(20) && !available.is_zero() && available < requested
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zero_liquidity() -> eyre::Result<()> {
+        use crate::errors::{ArithmeticError, SwapSimulationError};
+
+        //A pool that was created but not initialized has no sqrt price
+        let mut pool = UniswapV3Pool {
+            token_a: H160::from_low_u64_be(1),
+            token_b: H160::from_low_u64_be(2),
+            tick_spacing: 60,
+            ..Default::default()
+        };
+        assert!(matches!(
+            pool.calculate_price(pool.token_a),
+            Err(ArithmeticError::ZeroLiquidity(_))
+        ));
+
+        //An initialized pool without any position
+        pool.sqrt_price = U256::one() << 96;
+        assert!(matches!(
+            pool.calculate_price(pool.token_b),
+            Err(ArithmeticError::ZeroLiquidity(_))
+        ));
+        assert!(matches!(
+            pool.simulate_swap(pool.token_a, U256::exp10(18)),
+            Err(SwapSimulationError::ArithmeticError(
+                ArithmeticError::ZeroLiquidity(_)
+            ))
+        ));
+        assert!(matches!(
+            pool.simulate_swap_mut(pool.token_b, U256::exp10(18)),
+            Err(SwapSimulationError::ArithmeticError(
+                ArithmeticError::ZeroLiquidity(_)
+            ))
+        ));
+
+        //Liquidity outside of the current tick can be swapped into
+        pool.modify_position(600, 1200, 10_i128.pow(18));
+        assert_eq!(pool.liquidity, 0);
+        assert_eq!(pool.calculate_price(pool.token_a)?.to_f64(), 1.0);
+        assert!(!pool.simulate_swap(pool.token_b, U256::exp10(15))?.is_zero());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_liquidity_in_range() -> eyre::Result<()> {
+        use crate::amm::price::Price;
+
+        let mut pool = UniswapV3Pool {
+            token_a_decimals: 18,
+            token_b_decimals: 18,
+            sqrt_price: U256::one() << 96,
+            tick_spacing: 60,
+            ..Default::default()
+        };
+        pool.modify_position(-600, 600, 10_i128.pow(18));
+        pool.modify_position(-1200, -600, 10_i128.pow(18));
+        pool.modify_position(600, 1200, 2 * 10_i128.pow(18));
+
+        let price_at_tick = |tick: i32| -> eyre::Result<Price> {
+            Ok(Price::from_sqrt_price_x96(
+                uniswap_v3_math::tick_math::get_sqrt_ratio_at_tick(tick)?,
+                18,
+                18,
+            )?)
+        };
+
+        //Prices at the boundary of a tick can be rounded into the tick below
+        assert!((pool.tick_at_price(price_at_tick(-300)?)? + 300).abs() <= 1);
+
+        //Only the position around the current tick
+        let liquidity = pool.liquidity_in_range(price_at_tick(-100)?, price_at_tick(100)?)?;
+        assert_eq!(liquidity, U256::exp10(18));
+
+        //The position above the current tick adds its liquidity
+        let liquidity = pool.liquidity_in_range(price_at_tick(-100)?, price_at_tick(900)?)?;
+        assert_eq!(liquidity, U256::exp10(18) * 2);
+
+        //Lower prices are walked down from the current tick
+        let liquidity = pool.liquidity_in_range(price_at_tick(-900)?, price_at_tick(-700)?)?;
+        assert_eq!(liquidity, U256::exp10(18));
+
+        assert!(pool
+            .liquidity_in_range(price_at_tick(1300)?, price_at_tick(1400)?)?
+            .is_zero());
+        assert!(pool
+            .liquidity_in_range(price_at_tick(100)?, price_at_tick(-100)?)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_liquidity_in_range_usdc_weth() -> eyre::Result<()> {
+        use crate::amm::price::Price;
+
+        let rpc_endpoint = std::env::var("ETHEREUM_RPC_ENDPOINT")?;
+        let middleware = Arc::new(Provider::<Http>::try_from(rpc_endpoint)?);
+
+        let (pool, _synced_block) = initialize_usdc_weth_pool(middleware.clone()).await?;
+
+        //Every position that is active at the current tick is active in a range around the current price
+        let price = pool.calculate_price(pool.token_a)?.to_f64();
+        let liquidity = pool.liquidity_in_range(
+            Price::from_f64(price * 0.99)?,
+            Price::from_f64(price * 1.01)?,
+        )?;
+        assert!(liquidity >= U256::from(pool.liquidity));
+
+        //The whole range includes every position of the pool
+        let full_range_liquidity =
+            pool.liquidity_in_range(Price::default(), Price::from_f64(1e30)?)?;
+        assert!(full_range_liquidity >= liquidity);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_from_pancake_swap_log() -> eyre::Result<()> {
+        use ethers::{
+            abi::{encode, Token},
+            types::{Log, H256, I256},
+        };
+
+        let mut pool = UniswapV3Pool::default();
+
+        let sqrt_price = U256::from_dec_str("79228162514264337593543950336")?;
+        let data = encode(&[
+            Token::Int(I256::from(1000).into_raw()),
+            Token::Int(I256::from(-997).into_raw()),
+            Token::Uint(sqrt_price),
+            Token::Uint(U256::from(5000000000_u64)),
+            Token::Int(I256::from(-5).into_raw()),
+            Token::Uint(U256::from(3)),
+            Token::Uint(U256::zero()),
+        ]);
+
+        let log = Log {
+            topics: vec![
+                super::PANCAKE_SWAP_EVENT_SIGNATURE,
+                H256::zero(),
+                H256::zero(),
+            ],
+            data: data.into(),
+            ..Default::default()
+        };
+
+        pool.sync_from_log(log)?;
 
-        assert_eq!(float_price_a, 0.0006081236083117488);
-        assert_eq!(float_price_b, 1644.4025299004006);
+        assert_eq!(pool.sqrt_price, sqrt_price);
+        assert_eq!(pool.liquidity, 5000000000);
+        assert_eq!(pool.tick, -5);
 
         Ok(())
     }
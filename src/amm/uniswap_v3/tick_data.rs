@@ -0,0 +1,383 @@
+use std::{collections::HashMap, sync::Arc};
+
+use ethers::{
+    contract::Multicall,
+    providers::Middleware,
+    types::{BlockNumber, H160, I256, U256},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{AMMError, ArithmeticError, SwapSimulationError};
+
+use super::{IUniswapV3Pool, Info, UniswapV3Pool, MAX_TICK, MIN_TICK};
+
+//Max number of `ticks` calls batched in a single multicall
+pub const TICK_DATA_CHUNK_SIZE: usize = 500;
+//Max number of `tickBitmap` calls batched in a single multicall, the full range of a pool with a tick spacing of 1 has 3466 words
+pub const TICK_BITMAP_CHUNK_SIZE: i16 = 500;
+//Words fetched on each side of the word of the current tick, e.g. for populate_tick_data_provider
+pub const DEFAULT_TICK_WORD_RANGE: i16 = 2;
+
+//Size of fetched tick data, a word is a U256 and a tick an Info
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TickDataStats {
+    //Words that were fetched, including the words without initialized ticks that are not stored
+    pub words: usize,
+    pub initialized_words: usize,
+    pub ticks: usize,
+}
+
+//Source of the initialized ticks a swap steps through
+pub trait TickDataProvider {
+    fn get_tick(&self, tick: i32) -> Option<&Info>;
+
+    //Returns the next initialized tick within the word of `tick` or the word boundary, and whether it is initialized
+    fn next_initialized_tick_within_one_word(
+        &self,
+        tick: i32,
+        tick_spacing: i32,
+        lte: bool,
+    ) -> Result<(i32, bool), SwapSimulationError>;
+}
+
+//The tick data of a pool that was synced from its mint and burn logs
+impl TickDataProvider for UniswapV3Pool {
+    fn get_tick(&self, tick: i32) -> Option<&Info> {
+        self.ticks.get(&tick)
+    }
+
+    fn next_initialized_tick_within_one_word(
+        &self,
+        tick: i32,
+        tick_spacing: i32,
+        lte: bool,
+    ) -> Result<(i32, bool), SwapSimulationError> {
+        Ok(
+            uniswap_v3_math::tick_bitmap::next_initialized_tick_within_one_word(
+                &self.tick_bitmap,
+                tick,
+                tick_spacing,
+                lte,
+            )?,
+        )
+    }
+}
+
+//Tick bitmap words around the current tick of a pool and the ticks initialized in them, fetched at a block through Multicall.
+//Swaps that step out of the fetched words return `TickWordNotCached` instead of treating the missing ticks as uninitialized.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LocalTickDataProvider {
+    pub tick_bitmap: HashMap<i16, U256>,
+    pub ticks: HashMap<i32, Info>,
+    pub min_word: i16,
+    pub max_word: i16,
+}
+
+impl LocalTickDataProvider {
+    pub fn new(
+        tick_bitmap: HashMap<i16, U256>,
+        ticks: HashMap<i32, Info>,
+        min_word: i16,
+        max_word: i16,
+    ) -> LocalTickDataProvider {
+        LocalTickDataProvider {
+            tick_bitmap,
+            ticks,
+            min_word,
+            max_word,
+        }
+    }
+
+    //Fetches the `word_range` words on each side of the word of the current tick of the pool
+    pub async fn fetch<M: Middleware>(
+        pool: &UniswapV3Pool,
+        word_range: i16,
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<LocalTickDataProvider, AMMError<M>> {
+        let (min_tick_word, _) = word_position(MIN_TICK, pool.tick_spacing);
+        let (max_tick_word, _) = word_position(MAX_TICK, pool.tick_spacing);
+        let (current_word, _) = word_position(pool.tick, pool.tick_spacing);
+
+        let min_word = current_word.saturating_sub(word_range).max(min_tick_word);
+        let max_word = current_word.saturating_add(word_range).min(max_tick_word);
+
+        LocalTickDataProvider::fetch_words(pool, min_word, max_word, block_number, middleware).await
+    }
+
+    //Fetches every word that contains a tick in [min_tick, max_tick], ticks outside of [MIN_TICK, MAX_TICK] are ignored.
+    //Use MIN_TICK and MAX_TICK to fetch the tick data of the whole pool.
+    pub async fn fetch_tick_range<M: Middleware>(
+        pool: &UniswapV3Pool,
+        min_tick: i32,
+        max_tick: i32,
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<LocalTickDataProvider, AMMError<M>> {
+        let (min_tick, max_tick) = (min_tick.max(MIN_TICK), max_tick.min(MAX_TICK));
+        if min_tick > max_tick {
+            return Err(ArithmeticError::InvalidPriceRange.into());
+        }
+
+        let (min_word, _) = word_position(min_tick, pool.tick_spacing);
+        let (max_word, _) = word_position(max_tick, pool.tick_spacing);
+
+        LocalTickDataProvider::fetch_words(pool, min_word, max_word, block_number, middleware).await
+    }
+
+    pub fn stats(&self) -> TickDataStats {
+        TickDataStats {
+            words: (self.max_word as i32 - self.min_word as i32 + 1).max(0) as usize,
+            initialized_words: self.tick_bitmap.len(),
+            ticks: self.ticks.len(),
+        }
+    }
+
+    //The words are fetched in chunks of TICK_BITMAP_CHUNK_SIZE and then their ticks in chunks of TICK_DATA_CHUNK_SIZE
+    async fn fetch_words<M: Middleware>(
+        pool: &UniswapV3Pool,
+        min_word: i16,
+        max_word: i16,
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<LocalTickDataProvider, AMMError<M>> {
+        let block = block_number
+            .map(BlockNumber::from)
+            .unwrap_or(BlockNumber::Latest);
+
+        let mut multicall = Multicall::new(middleware.clone(), None).await?.block(block);
+
+        let mut tick_bitmap = HashMap::new();
+        let mut initialized_ticks = vec![];
+        let mut chunk_start = min_word;
+        loop {
+            let chunk_end = chunk_start
+                .saturating_add(TICK_BITMAP_CHUNK_SIZE - 1)
+                .min(max_word);
+
+            let (words, ticks) = fetch_tick_bitmap(
+                pool.address,
+                pool.tick_spacing,
+                chunk_start,
+                chunk_end,
+                &mut multicall,
+                middleware.clone(),
+            )
+            .await?;
+            tick_bitmap.extend(words);
+            initialized_ticks.extend(ticks);
+
+            if chunk_end >= max_word {
+                break;
+            }
+            chunk_start = chunk_end + 1;
+        }
+
+        let ticks =
+            fetch_ticks(pool.address, &initialized_ticks, &mut multicall, middleware).await?;
+
+        Ok(LocalTickDataProvider::new(
+            tick_bitmap,
+            ticks,
+            min_word,
+            max_word,
+        ))
+    }
+}
+
+impl TickDataProvider for LocalTickDataProvider {
+    fn get_tick(&self, tick: i32) -> Option<&Info> {
+        self.ticks.get(&tick)
+    }
+
+    fn next_initialized_tick_within_one_word(
+        &self,
+        tick: i32,
+        tick_spacing: i32,
+        lte: bool,
+    ) -> Result<(i32, bool), SwapSimulationError> {
+        //Searching upwards starts at the next compressed tick, which can be in the next word
+        let (word, _) = if lte {
+            word_position(tick, tick_spacing)
+        } else {
+            word_position(tick + tick_spacing, tick_spacing)
+        };
+
+        if word < self.min_word || word > self.max_word {
+            return Err(SwapSimulationError::TickWordNotCached(word));
+        }
+
+        Ok(
+            uniswap_v3_math::tick_bitmap::next_initialized_tick_within_one_word(
+                &self.tick_bitmap,
+                tick,
+                tick_spacing,
+                lte,
+            )?,
+        )
+    }
+}
+
+//Tick bitmap words in [min_word, max_word] that have initialized ticks, and the initialized ticks in ascending order
+pub(crate) async fn fetch_tick_bitmap<M: Middleware>(
+    pool_address: H160,
+    tick_spacing: i32,
+    min_word: i16,
+    max_word: i16,
+    multicall: &mut Multicall<M>,
+    middleware: Arc<M>,
+) -> Result<(HashMap<i16, U256>, Vec<i32>), AMMError<M>> {
+    let v3_pool = IUniswapV3Pool::new(pool_address, middleware);
+
+    multicall.clear_calls();
+    for word in min_word..=max_word {
+        multicall.add_call(v3_pool.tick_bitmap(word), false);
+    }
+
+    let mut tick_bitmap = HashMap::new();
+    let mut initialized_ticks = vec![];
+    for (word, result) in (min_word..=max_word).zip(multicall.call_raw().await?) {
+        let bitmap = result
+            .ok()
+            .and_then(|bitmap| bitmap.into_uint())
+            .ok_or(AMMError::BatchRequestError(pool_address))?;
+
+        if bitmap.is_zero() {
+            continue;
+        }
+
+        for bit in 0..256 {
+            if bitmap.bit(bit) {
+                initialized_ticks.push(((word as i32) * 256 + bit as i32) * tick_spacing);
+            }
+        }
+
+        tick_bitmap.insert(word, bitmap);
+    }
+
+    Ok((tick_bitmap, initialized_ticks))
+}
+
+//Liquidity of the ticks, in chunks of TICK_DATA_CHUNK_SIZE calls
+pub(crate) async fn fetch_ticks<M: Middleware>(
+    pool_address: H160,
+    initialized_ticks: &[i32],
+    multicall: &mut Multicall<M>,
+    middleware: Arc<M>,
+) -> Result<HashMap<i32, Info>, AMMError<M>> {
+    let v3_pool = IUniswapV3Pool::new(pool_address, middleware);
+
+    let mut ticks = HashMap::new();
+    for chunk in initialized_ticks.chunks(TICK_DATA_CHUNK_SIZE) {
+        multicall.clear_calls();
+        for tick in chunk {
+            multicall.add_call(v3_pool.ticks(*tick), false);
+        }
+
+        for (tick, result) in chunk.iter().zip(multicall.call_raw().await?) {
+            let info = result
+                .ok()
+                .and_then(|info| info.into_tuple())
+                .ok_or(AMMError::BatchRequestError(pool_address))?;
+
+            let liquidity_gross = info
+                .first()
+                .and_then(|token| token.clone().into_uint())
+                .ok_or(AMMError::BatchRequestError(pool_address))?;
+            let liquidity_net = info
+                .get(1)
+                .and_then(|token| token.clone().into_int())
+                .ok_or(AMMError::BatchRequestError(pool_address))?;
+
+            ticks.insert(
+                *tick,
+                Info::new(
+                    liquidity_gross.as_u128(),
+                    I256::from_raw(liquidity_net).as_i128(),
+                    true,
+                ),
+            );
+        }
+    }
+
+    Ok(ticks)
+}
+
+//Word and bit position of the compressed tick, rounding towards negative infinity like the pool does
+pub(crate) fn word_position(tick: i32, tick_spacing: i32) -> (i16, u8) {
+    let mut compressed = tick / tick_spacing;
+    if tick < 0 && tick % tick_spacing != 0 {
+        compressed -= 1;
+    }
+
+    uniswap_v3_math::tick_bitmap::position(compressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::{H160, U256};
+
+    use crate::{
+        amm::{uniswap_v3::UniswapV3Pool, AutomatedMarketMaker},
+        errors::SwapSimulationError,
+    };
+
+    use super::LocalTickDataProvider;
+
+    //Pool at tick 0 with liquidity in [-600, 600) and [-1200, -600)
+    fn pool() -> UniswapV3Pool {
+        let mut pool = UniswapV3Pool {
+            token_a: H160::from_low_u64_be(1),
+            token_a_decimals: 18,
+            token_b: H160::from_low_u64_be(2),
+            token_b_decimals: 18,
+            sqrt_price: U256::one() << 96,
+            fee: 3000,
+            tick_spacing: 60,
+            ..Default::default()
+        };
+
+        pool.modify_position(-600, 600, 10_i128.pow(18));
+        pool.modify_position(-1200, -600, 10_i128.pow(18));
+
+        pool
+    }
+
+    #[test]
+    fn test_simulate_swap_with_ticks() -> eyre::Result<()> {
+        let mut pool = pool();
+        let amount_in = U256::exp10(16) * 5;
+
+        //Crosses the tick at -600
+        let expected_amount_out = pool.simulate_swap(pool.token_a, amount_in)?;
+        let swap_result = pool.simulate_swap_with_ticks(pool.token_a, amount_in, U256::zero())?;
+        assert_eq!(swap_result.amount_out, expected_amount_out);
+
+        pool.tick_data_provider = Some(LocalTickDataProvider::new(
+            pool.tick_bitmap.clone(),
+            pool.ticks.clone(),
+            -1,
+            0,
+        ));
+        let swap_result = pool.simulate_swap_with_ticks(pool.token_a, amount_in, U256::zero())?;
+        assert_eq!(swap_result.amount_out, expected_amount_out);
+
+        //The swap stops at the limit and only part of the amount in is swapped
+        let sqrt_price_limit = uniswap_v3_math::tick_math::get_sqrt_ratio_at_tick(-300)?;
+        let limited_result =
+            pool.simulate_swap_with_ticks(pool.token_a, amount_in, sqrt_price_limit)?;
+        assert!(limited_result.amount_out < expected_amount_out);
+
+        //Swaps that leave the cached words can not be simulated
+        assert!(matches!(
+            pool.simulate_swap_with_ticks(pool.token_a, U256::exp10(18), U256::zero()),
+            Err(SwapSimulationError::TickWordNotCached(-2))
+        ));
+
+        //Mints and burns drop the cached tick data
+        pool.modify_position(-60, 60, 1000);
+        assert!(pool.tick_data_provider.is_none());
+
+        Ok(())
+    }
+}
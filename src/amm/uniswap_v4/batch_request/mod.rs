@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use ethers::{
+    abi::Token,
+    contract::Multicall,
+    providers::Middleware,
+    types::{BlockNumber, U256},
+};
+
+use crate::{amm::uniswap_v3::IErc20, errors::AMMError};
+
+use super::{
+    pool_state_slot, IPoolManager, UniswapV4Pool, LIQUIDITY_OFFSET, NATIVE_CURRENCY_DECIMALS,
+};
+
+//The PoolManager does not expose getters for the pool state, so slot0 and the liquidity are read from storage through `extsload`
+
+pub async fn get_v4_pool_data_batch_request<M: Middleware>(
+    pools: Vec<&mut UniswapV4Pool>,
+    block_number: Option<u64>,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    let block = block_number
+        .map(BlockNumber::from)
+        .unwrap_or(BlockNumber::Latest);
+
+    let mut multicall = Multicall::new(middleware.clone(), None).await?.block(block);
+
+    for pool in pools.iter() {
+        let pool_manager = IPoolManager::new(pool.pool_manager, middleware.clone());
+        multicall.add_call(pool_manager.extsload(pool_state_slots(pool)), false);
+
+        for token in [pool.token_a, pool.token_b] {
+            if !token.is_zero() {
+                multicall.add_call(IErc20::new(token, middleware.clone()).decimals(), false);
+            }
+        }
+    }
+
+    let mut results = multicall.call_raw().await?.into_iter();
+
+    for pool in pools {
+        let words = results
+            .next()
+            .and_then(|result| result.ok())
+            .and_then(decode_words)
+            .ok_or(AMMError::BatchRequestError(pool.address))?;
+
+        decode_pool_state(pool, &words).ok_or(AMMError::BatchRequestError(pool.address))?;
+
+        let mut token_decimals = [NATIVE_CURRENCY_DECIMALS; 2];
+        for (i, token) in [pool.token_a, pool.token_b].iter().enumerate() {
+            if !token.is_zero() {
+                token_decimals[i] = results
+                    .next()
+                    .and_then(|result| result.ok())
+                    .and_then(|token| token.into_uint())
+                    .ok_or(AMMError::BatchRequestError(pool.address))?
+                    .as_u32() as u8;
+            }
+        }
+
+        pool.token_a_decimals = token_decimals[0];
+        pool.token_b_decimals = token_decimals[1];
+    }
+
+    Ok(())
+}
+
+pub async fn sync_v4_pool_batch_request<M: Middleware>(
+    pool: &mut UniswapV4Pool,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    let pool_manager = IPoolManager::new(pool.pool_manager, middleware);
+
+    let words = pool_manager
+        .extsload(pool_state_slots(pool))
+        .call()
+        .await?
+        .iter()
+        .map(|word| U256::from_big_endian(word))
+        .collect::<Vec<U256>>();
+
+    decode_pool_state(pool, &words).ok_or(AMMError::SyncError(pool.address))?;
+
+    Ok(())
+}
+
+//Slots of slot0 and the liquidity of the pool
+fn pool_state_slots(pool: &UniswapV4Pool) -> Vec<[u8; 32]> {
+    let state_slot = pool_state_slot(pool.pool_id);
+
+    [state_slot, state_slot.overflowing_add(LIQUIDITY_OFFSET).0]
+        .iter()
+        .map(|slot| {
+            let mut word = [0u8; 32];
+            slot.to_big_endian(&mut word);
+            word
+        })
+        .collect()
+}
+
+fn decode_words(token: Token) -> Option<Vec<U256>> {
+    token
+        .into_array()?
+        .into_iter()
+        .map(|word| {
+            word.into_fixed_bytes()
+                .map(|word| U256::from_big_endian(&word))
+        })
+        .collect()
+}
+
+//slot0 is packed as | lpFee (24) | protocolFee (24) | tick (24) | sqrtPriceX96 (160) |
+fn decode_pool_state(pool: &mut UniswapV4Pool, words: &[U256]) -> Option<()> {
+    let slot_0 = *words.first()?;
+    let liquidity = *words.get(1)?;
+
+    pool.sqrt_price = slot_0 & ((U256::one() << 160) - 1);
+    //Sign extend the 24 bit tick
+    pool.tick = ((((slot_0 >> 160).low_u32() & 0xFFFFFF) << 8) as i32) >> 8;
+    //The protocol fee is not accounted for in simulations
+    pool.fee = (slot_0 >> 208).low_u32() & 0xFFFFFF;
+    pool.liquidity = liquidity.low_u128();
+
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::U256;
+
+    use super::{decode_pool_state, UniswapV4Pool};
+
+    #[test]
+    fn test_decode_pool_state() {
+        let sqrt_price = U256::one() << 96;
+        let tick = -200_i32;
+        let lp_fee = 3000_u32;
+
+        let slot_0 = (U256::from(lp_fee) << 208)
+            | (U256::from((tick as u32) & 0xFFFFFF) << 160)
+            | sqrt_price;
+
+        let mut pool = UniswapV4Pool::default();
+        decode_pool_state(&mut pool, &[slot_0, U256::from(1000)]).unwrap();
+
+        assert_eq!(pool.sqrt_price, sqrt_price);
+        assert_eq!(pool.tick, tick);
+        assert_eq!(pool.fee, lp_fee);
+        assert_eq!(pool.liquidity, 1000);
+    }
+}
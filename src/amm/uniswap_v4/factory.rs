@@ -0,0 +1,241 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use ethers::{
+    providers::Middleware,
+    types::{Filter, Log, H160, H256, U64},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    amm::{
+        factory::{AutomatedMarketMakerFactory, TASK_LIMIT},
+        AMM,
+    },
+    errors::{AMMError, EventLogError},
+    middleware::logs::{get_logs_with_adaptive_range, MAX_LOG_RANGE_SPLITS},
+};
+
+use super::{
+    batch_request, hooks::HookPattern, process_logs_from_handles, UniswapV4Pool,
+    INITIALIZE_EVENT_SIGNATURE, MODIFY_LIQUIDITY_EVENT_SIGNATURE,
+};
+
+//Uniswap V4 does not deploy a contract per pool, every pool is initialized in the PoolManager singleton.
+//Only pools whose hooks match one of the `hook_patterns` are synced, if no patterns are supplied only pools without hooks are synced.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct UniswapV4PoolManager {
+    pub address: H160,
+    pub creation_block: u64,
+    pub hook_patterns: Vec<HookPattern>,
+}
+
+#[async_trait]
+impl AutomatedMarketMakerFactory for UniswapV4PoolManager {
+    fn address(&self) -> H160 {
+        self.address
+    }
+
+    fn creation_block(&self) -> u64 {
+        self.creation_block
+    }
+
+    fn amm_created_event_signature(&self) -> H256 {
+        INITIALIZE_EVENT_SIGNATURE
+    }
+
+    async fn new_amm_from_log<M: 'static + Middleware>(
+        &self,
+        log: Log,
+        middleware: Arc<M>,
+    ) -> Result<AMM, AMMError<M>> {
+        Ok(AMM::UniswapV4Pool(
+            UniswapV4Pool::new_from_log(self.address, log, middleware).await?,
+        ))
+    }
+
+    async fn get_all_amms<M: 'static + Middleware>(
+        &self,
+        to_block: Option<u64>,
+        middleware: Arc<M>,
+        step: u64,
+    ) -> Result<Vec<AMM>, AMMError<M>> {
+        if let Some(block) = to_block {
+            self.get_all_pools_from_logs(block, step, middleware).await
+        } else {
+            return Err(AMMError::BlockNumberNotFound);
+        }
+    }
+
+    async fn populate_amm_data<M: Middleware>(
+        &self,
+        amms: &mut [AMM],
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        let step = 127; //Max batch size for call
+        for amm_chunk in amms.chunks_mut(step) {
+            let pools = amm_chunk
+                .iter_mut()
+                .filter_map(|amm| match amm {
+                    AMM::UniswapV4Pool(pool) => Some(pool),
+                    _ => None,
+                })
+                .collect::<Vec<&mut UniswapV4Pool>>();
+
+            batch_request::get_v4_pool_data_batch_request(pools, block_number, middleware.clone())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    fn new_empty_amm_from_log(&self, log: Log) -> Result<AMM, ethers::abi::Error> {
+        let pool_manager = log.address;
+
+        match UniswapV4Pool::new_empty_pool_from_log(pool_manager, log) {
+            Ok(pool) => Ok(AMM::UniswapV4Pool(pool)),
+            Err(EventLogError::EthABIError(err)) => Err(err),
+            Err(_) => Err(ethers::abi::Error::InvalidData),
+        }
+    }
+}
+
+impl UniswapV4PoolManager {
+    pub fn new(
+        address: H160,
+        creation_block: u64,
+        hook_patterns: Vec<HookPattern>,
+    ) -> UniswapV4PoolManager {
+        UniswapV4PoolManager {
+            address,
+            creation_block,
+            hook_patterns,
+        }
+    }
+
+    pub fn hooks_match(&self, hooks: H160) -> bool {
+        if self.hook_patterns.is_empty() {
+            hooks.is_zero()
+        } else {
+            self.hook_patterns
+                .iter()
+                .any(|pattern| pattern.matches(hooks))
+        }
+    }
+
+    //Gets all initialize events from the PoolManager and replays the liquidity modifications of each pool to build its tick data
+    pub async fn get_all_pools_from_logs<M: 'static + Middleware>(
+        &self,
+        to_block: u64,
+        step: u64,
+        middleware: Arc<M>,
+    ) -> Result<Vec<AMM>, AMMError<M>> {
+        if step == 0 {
+            return Err(AMMError::ZeroBatchSize("step"));
+        }
+
+        let pool_manager = self.address;
+        let mut from_block = self.creation_block;
+        let mut aggregated_pools: HashMap<H256, UniswapV4Pool> = HashMap::new();
+        let mut ordered_logs: BTreeMap<U64, Vec<Log>> = BTreeMap::new();
+
+        tracing::info!(from_block, to_block, step, "getting all pools from logs");
+
+        let mut handles = vec![];
+
+        let mut tasks = 0;
+        while from_block < to_block {
+            let middleware = middleware.clone();
+
+            let mut target_block = from_block + step - 1;
+            if target_block > to_block {
+                target_block = to_block;
+            }
+
+            handles.push(tokio::spawn(async move {
+                let logs = get_logs_with_adaptive_range(
+                    middleware.as_ref(),
+                    &Filter::new()
+                        .topic0(vec![
+                            INITIALIZE_EVENT_SIGNATURE,
+                            MODIFY_LIQUIDITY_EVENT_SIGNATURE,
+                        ])
+                        .address(pool_manager),
+                    from_block,
+                    target_block,
+                    MAX_LOG_RANGE_SPLITS,
+                )
+                .await
+                .map_err(AMMError::MiddlewareError)?;
+
+                Ok::<Vec<Log>, AMMError<M>>(logs)
+            }));
+
+            from_block += step;
+
+            tasks += 1;
+            //Here we are limiting the number of green threads that can be spun up to not have the node time out
+            if tasks == TASK_LIMIT {
+                process_logs_from_handles(handles, &mut ordered_logs).await?;
+                handles = vec![];
+                tasks = 0;
+            }
+        }
+
+        process_logs_from_handles(handles, &mut ordered_logs).await?;
+
+        for (_, log_group) in ordered_logs {
+            for log in log_group {
+                if log.topics[0] == INITIALIZE_EVENT_SIGNATURE {
+                    let pool = UniswapV4Pool::new_empty_pool_from_log(pool_manager, log)?;
+                    if self.hooks_match(pool.hooks()) {
+                        aggregated_pools.insert(pool.pool_id, pool);
+                    }
+                } else if let Some(pool) = log
+                    .topics
+                    .get(1)
+                    .and_then(|pool_id| aggregated_pools.get_mut(pool_id))
+                {
+                    pool.sync_from_modify_liquidity_log(log)?;
+                }
+            }
+        }
+
+        Ok(aggregated_pools
+            .into_values()
+            .map(AMM::UniswapV4Pool)
+            .collect::<Vec<AMM>>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::H160;
+
+    use crate::amm::uniswap_v4::hooks::{HookPattern, BEFORE_SWAP_FLAG, SWAP_HOOK_FLAGS};
+
+    use super::UniswapV4PoolManager;
+
+    #[test]
+    fn test_hooks_match() {
+        let swap_hooks = H160::from_low_u64_be(0xaaaa_0000 | BEFORE_SWAP_FLAG as u64);
+        let other_hooks = H160::from_low_u64_be(0xbbbb_0000);
+
+        let pool_manager = UniswapV4PoolManager::new(H160::zero(), 0, vec![]);
+        assert!(pool_manager.hooks_match(H160::zero()));
+        assert!(!pool_manager.hooks_match(other_hooks));
+
+        let pool_manager = UniswapV4PoolManager::new(
+            H160::zero(),
+            0,
+            vec![HookPattern::permissions(0, SWAP_HOOK_FLAGS)],
+        );
+        assert!(pool_manager.hooks_match(H160::zero()));
+        assert!(pool_manager.hooks_match(other_hooks));
+        assert!(!pool_manager.hooks_match(swap_hooks));
+    }
+}
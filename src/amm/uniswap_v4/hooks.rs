@@ -0,0 +1,108 @@
+use ethers::types::H160;
+use serde::{Deserialize, Serialize};
+
+//The PoolManager decides which hooks to call from the lowest 14 bits of the hooks address
+pub const BEFORE_INITIALIZE_FLAG: u16 = 1 << 13;
+pub const AFTER_INITIALIZE_FLAG: u16 = 1 << 12;
+pub const BEFORE_ADD_LIQUIDITY_FLAG: u16 = 1 << 11;
+pub const AFTER_ADD_LIQUIDITY_FLAG: u16 = 1 << 10;
+pub const BEFORE_REMOVE_LIQUIDITY_FLAG: u16 = 1 << 9;
+pub const AFTER_REMOVE_LIQUIDITY_FLAG: u16 = 1 << 8;
+pub const BEFORE_SWAP_FLAG: u16 = 1 << 7;
+pub const AFTER_SWAP_FLAG: u16 = 1 << 6;
+pub const BEFORE_DONATE_FLAG: u16 = 1 << 5;
+pub const AFTER_DONATE_FLAG: u16 = 1 << 4;
+pub const BEFORE_SWAP_RETURNS_DELTA_FLAG: u16 = 1 << 3;
+pub const AFTER_SWAP_RETURNS_DELTA_FLAG: u16 = 1 << 2;
+pub const AFTER_ADD_LIQUIDITY_RETURNS_DELTA_FLAG: u16 = 1 << 1;
+pub const AFTER_REMOVE_LIQUIDITY_RETURNS_DELTA_FLAG: u16 = 1;
+
+//Hooks with none of these permissions can not change the outcome of a swap
+pub const SWAP_HOOK_FLAGS: u16 = BEFORE_SWAP_FLAG
+    | AFTER_SWAP_FLAG
+    | BEFORE_SWAP_RETURNS_DELTA_FLAG
+    | AFTER_SWAP_RETURNS_DELTA_FLAG;
+
+//Matches a hooks address against `value` on the bits that are set in `mask`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HookPattern {
+    pub mask: H160,
+    pub value: H160,
+}
+
+impl HookPattern {
+    pub fn new(mask: H160, value: H160) -> HookPattern {
+        HookPattern { mask, value }
+    }
+
+    //Matches a single hooks contract
+    pub fn address(hooks: H160) -> HookPattern {
+        HookPattern::new(H160::repeat_byte(0xff), hooks)
+    }
+
+    //Matches pools without hooks
+    pub fn no_hooks() -> HookPattern {
+        HookPattern::address(H160::zero())
+    }
+
+    //Matches hooks that have all of the `required` permissions and none of the `forbidden` permissions
+    pub fn permissions(required: u16, forbidden: u16) -> HookPattern {
+        HookPattern::new(
+            permission_bits(required | forbidden),
+            permission_bits(required),
+        )
+    }
+
+    pub fn matches(&self, hooks: H160) -> bool {
+        hooks
+            .as_bytes()
+            .iter()
+            .zip(self.mask.as_bytes())
+            .zip(self.value.as_bytes())
+            .all(|((hooks, mask), value)| hooks & mask == value & mask)
+    }
+}
+
+pub fn permission_bits(flags: u16) -> H160 {
+    let mut address = H160::zero();
+    address.0[18..].copy_from_slice(&flags.to_be_bytes());
+    address
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::H160;
+
+    use super::{
+        permission_bits, HookPattern, AFTER_SWAP_FLAG, BEFORE_INITIALIZE_FLAG, BEFORE_SWAP_FLAG,
+        SWAP_HOOK_FLAGS,
+    };
+
+    #[test]
+    fn test_hook_pattern() {
+        let hooks = H160::repeat_byte(0x11);
+        let swap_hooks =
+            H160::from_low_u64_be(0xaaaa_0000 | (BEFORE_SWAP_FLAG | AFTER_SWAP_FLAG) as u64);
+        let initialize_hooks = H160::from_low_u64_be(0xbbbb_0000 | BEFORE_INITIALIZE_FLAG as u64);
+
+        assert!(HookPattern::no_hooks().matches(H160::zero()));
+        assert!(!HookPattern::no_hooks().matches(hooks));
+
+        assert!(HookPattern::address(hooks).matches(hooks));
+        assert!(!HookPattern::address(hooks).matches(swap_hooks));
+
+        let no_swap_hooks = HookPattern::permissions(0, SWAP_HOOK_FLAGS);
+        assert!(no_swap_hooks.matches(H160::zero()));
+        assert!(no_swap_hooks.matches(initialize_hooks));
+        assert!(!no_swap_hooks.matches(swap_hooks));
+
+        let before_swap = HookPattern::permissions(BEFORE_SWAP_FLAG, 0);
+        assert!(before_swap.matches(swap_hooks));
+        assert!(!before_swap.matches(initialize_hooks));
+
+        assert_eq!(
+            permission_bits(BEFORE_INITIALIZE_FLAG),
+            H160::from_low_u64_be(1 << 13)
+        );
+    }
+}
@@ -0,0 +1,758 @@
+pub mod batch_request;
+pub mod factory;
+pub mod hooks;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use ethers::{
+    abi::{self, RawLog, Token},
+    prelude::{abigen, AbiError, EthEvent},
+    providers::Middleware,
+    types::{BlockNumber, Filter, Log, H160, H256, I256, U256, U64},
+    utils::keccak256,
+};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+
+use crate::{
+    amm::{price::Price, AutomatedMarketMaker},
+    errors::{AMMError, ArithmeticError, EventLogError, SwapSimulationError},
+};
+
+use super::{
+    factory::TASK_LIMIT,
+    uniswap_v3::{
+        Info, StepComputations, MAX_SQRT_RATIO, MAX_TICK, MIN_SQRT_RATIO, MIN_TICK,
+        POPULATE_TICK_DATA_STEP,
+    },
+};
+
+abigen!(
+    IPoolManager,
+    r#"[
+        function extsload(bytes32[] slots) external view returns (bytes32[])
+        event Initialize(bytes32 indexed id, address indexed currency0, address indexed currency1, uint24 fee, int24 tickSpacing, address hooks, uint160 sqrtPriceX96, int24 tick)
+        event Swap(bytes32 indexed id, address indexed sender, int128 amount0, int128 amount1, uint160 sqrtPriceX96, uint128 liquidity, int24 tick, uint24 fee)
+        event ModifyLiquidity(bytes32 indexed id, address indexed sender, int24 tickLower, int24 tickUpper, int256 liquidityDelta, bytes32 salt)
+    ]"#;
+);
+
+pub const INITIALIZE_EVENT_SIGNATURE: H256 = H256([
+    221, 70, 110, 103, 78, 165, 87, 245, 98, 149, 226, 208, 33, 138, 18, 94, 164, 180, 240, 246,
+    243, 48, 123, 149, 248, 94, 97, 16, 131, 141, 100, 56,
+]);
+
+pub const SWAP_EVENT_SIGNATURE: H256 = H256([
+    64, 233, 206, 203, 159, 95, 31, 28, 91, 156, 151, 222, 194, 145, 123, 126, 233, 46, 87, 186,
+    85, 99, 112, 141, 172, 169, 77, 216, 74, 215, 17, 47,
+]);
+
+pub const MODIFY_LIQUIDITY_EVENT_SIGNATURE: H256 = H256([
+    242, 8, 244, 145, 39, 130, 253, 37, 199, 241, 20, 202, 55, 35, 162, 213, 221, 111, 59, 204, 58,
+    200, 219, 90, 246, 59, 170, 133, 247, 17, 213, 236,
+]);
+
+//Storage slot of the `pools` mapping in the PoolManager
+pub const POOLS_SLOT: U256 = U256([6, 0, 0, 0]);
+//Offset of the liquidity from the pool state slot, slot0 is stored at the pool state slot itself
+pub const LIQUIDITY_OFFSET: U256 = U256([3, 0, 0, 0]);
+
+//The native currency is represented by the zero address
+pub const NATIVE_CURRENCY_DECIMALS: u8 = 18;
+
+//Pools initialized with this fee let their hooks set the lp fee, the current fee is read from slot0
+pub const DYNAMIC_FEE_FLAG: u32 = 0x800000;
+
+//Identifies a pool in the PoolManager, the pool id is the keccak256 hash of the abi encoded key
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolKey {
+    pub currency_0: H160,
+    pub currency_1: H160,
+    pub fee: u32,
+    pub tick_spacing: i32,
+    pub hooks: H160,
+}
+
+impl PoolKey {
+    pub fn new(
+        currency_0: H160,
+        currency_1: H160,
+        fee: u32,
+        tick_spacing: i32,
+        hooks: H160,
+    ) -> PoolKey {
+        PoolKey {
+            currency_0,
+            currency_1,
+            fee,
+            tick_spacing,
+            hooks,
+        }
+    }
+
+    pub fn id(&self) -> H256 {
+        H256::from(keccak256(abi::encode(&[
+            Token::Address(self.currency_0),
+            Token::Address(self.currency_1),
+            Token::Uint(U256::from(self.fee)),
+            Token::Int(I256::from(self.tick_spacing).into_raw()),
+            Token::Address(self.hooks),
+        ])))
+    }
+
+    pub fn is_dynamic_fee(&self) -> bool {
+        self.fee == DYNAMIC_FEE_FLAG
+    }
+}
+
+//Uniswap V4 pools all live in the PoolManager singleton and are identified by the keccak256 hash of their pool key.
+//`address` is a virtual address derived from the pool id so that a pool can be keyed like any other AMM,
+//use `pool_manager` when calling into the pool.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UniswapV4Pool {
+    pub address: H160,
+    pub pool_manager: H160,
+    pub pool_id: H256,
+    pub token_a: H160,
+    pub token_a_decimals: u8,
+    pub token_b: H160,
+    pub token_b_decimals: u8,
+    pub liquidity: u128,
+    pub sqrt_price: U256,
+    pub fee: u32,
+    pub tick: i32,
+    pub pool_key: PoolKey,
+    pub tick_bitmap: HashMap<i16, U256>,
+    pub ticks: HashMap<i32, Info>,
+}
+
+#[async_trait]
+impl AutomatedMarketMaker for UniswapV4Pool {
+    fn address(&self) -> H160 {
+        self.address
+    }
+
+    async fn sync<M: Middleware>(&mut self, middleware: Arc<M>) -> Result<(), AMMError<M>> {
+        batch_request::sync_v4_pool_batch_request(self, middleware).await
+    }
+
+    fn sync_on_event_signatures(&self) -> Vec<H256> {
+        vec![SWAP_EVENT_SIGNATURE, MODIFY_LIQUIDITY_EVENT_SIGNATURE]
+    }
+
+    fn sync_from_log(&mut self, log: Log) -> Result<(), EventLogError> {
+        let event_signature = log.topics[0];
+
+        //Every pool emits its events from the PoolManager, so only logs for this pool id are applied
+        if log.topics.get(1) != Some(&self.pool_id) {
+            return Ok(());
+        }
+
+        if event_signature == SWAP_EVENT_SIGNATURE {
+            self.sync_from_swap_log(log)?;
+        } else if event_signature == MODIFY_LIQUIDITY_EVENT_SIGNATURE {
+            self.sync_from_modify_liquidity_log(log)?;
+        } else {
+            Err(EventLogError::InvalidEventSignature)?
+        }
+
+        Ok(())
+    }
+
+    fn tokens(&self) -> Vec<H160> {
+        vec![self.token_a, self.token_b]
+    }
+
+    fn calculate_price(&self, base_token: H160) -> Result<Price, ArithmeticError> {
+        self.check_liquidity()?;
+
+        let price = Price::from_sqrt_price_x96(
+            self.sqrt_price,
+            self.token_a_decimals,
+            self.token_b_decimals,
+        )?;
+
+        if base_token == self.token_a {
+            Ok(price)
+        } else {
+            price.invert()
+        }
+    }
+
+    // NOTE: This function will not populate the tick_bitmap and ticks, if you want to populate those, you must call populate_tick_data on an initialized pool
+    async fn populate_data<M: Middleware>(
+        &mut self,
+        block_number: Option<u64>,
+        middleware: Arc<M>,
+    ) -> Result<(), AMMError<M>> {
+        batch_request::get_v4_pool_data_batch_request(vec![self], block_number, middleware).await
+    }
+
+    fn simulate_swap(&self, token_in: H160, amount_in: U256) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        self.check_liquidity()?;
+
+        let (amount_out, _) = self.swap(token_in == self.token_a, amount_in)?;
+
+        tracing::trace!(?amount_out);
+
+        Ok(amount_out)
+    }
+
+    fn simulate_swap_mut(
+        &mut self,
+        token_in: H160,
+        amount_in: U256,
+    ) -> Result<U256, SwapSimulationError> {
+        tracing::info!(?token_in, ?amount_in, "simulating swap");
+
+        self.check_liquidity()?;
+
+        let (amount_out, (sqrt_price, tick, liquidity)) =
+            self.swap(token_in == self.token_a, amount_in)?;
+
+        //Update the pool state
+        self.sqrt_price = sqrt_price;
+        self.tick = tick;
+        self.liquidity = liquidity;
+
+        tracing::trace!(?amount_out);
+
+        Ok(amount_out)
+    }
+
+    fn swap_fee(&self, _token_in: H160) -> f64 {
+        self.fee as f64 / 1e6
+    }
+
+    fn fee(&self) -> u32 {
+        self.fee
+    }
+
+    fn token_decimals(&self) -> Vec<u8> {
+        vec![self.token_a_decimals, self.token_b_decimals]
+    }
+
+    fn get_token_out(&self, token_in: H160) -> H160 {
+        if self.token_a == token_in {
+            self.token_b
+        } else {
+            self.token_a
+        }
+    }
+}
+
+impl UniswapV4Pool {
+    pub fn new_empty_pool_from_log(pool_manager: H160, log: Log) -> Result<Self, EventLogError> {
+        let event_signature = log.topics[0];
+
+        if event_signature == INITIALIZE_EVENT_SIGNATURE {
+            let initialize_event = InitializeFilter::decode_log(&RawLog::from(log))?;
+            let pool_id = H256::from(initialize_event.id);
+            let pool_key = PoolKey::new(
+                initialize_event.currency_0,
+                initialize_event.currency_1,
+                initialize_event.fee,
+                initialize_event.tick_spacing,
+                initialize_event.hooks,
+            );
+
+            Ok(UniswapV4Pool {
+                address: pool_address_from_pool_id(pool_id),
+                pool_manager,
+                pool_id,
+                token_a: initialize_event.currency_0,
+                token_a_decimals: 0,
+                token_b: initialize_event.currency_1,
+                token_b_decimals: 0,
+                liquidity: 0,
+                sqrt_price: initialize_event.sqrt_price_x96,
+                //The lp fee of a dynamic fee pool is only known once slot0 is populated
+                fee: if pool_key.is_dynamic_fee() {
+                    0
+                } else {
+                    pool_key.fee
+                },
+                tick: initialize_event.tick,
+                pool_key,
+                tick_bitmap: HashMap::new(),
+                ticks: HashMap::new(),
+            })
+        } else {
+            Err(EventLogError::InvalidEventSignature)
+        }
+    }
+
+    pub async fn new_from_log<M: 'static + Middleware>(
+        pool_manager: H160,
+        log: Log,
+        middleware: Arc<M>,
+    ) -> Result<Self, AMMError<M>> {
+        let creation_block = log
+            .block_number
+            .ok_or(EventLogError::LogBlockNumberNotFound)?
+            .as_u64();
+
+        let mut pool = UniswapV4Pool::new_empty_pool_from_log(pool_manager, log)?;
+
+        let synced_block = pool
+            .populate_tick_data(creation_block, middleware.clone())
+            .await?;
+
+        pool.populate_data(Some(synced_block), middleware).await?;
+
+        if !pool.data_is_populated() {
+            return Err(AMMError::PoolDataError);
+        }
+
+        Ok(pool)
+    }
+
+    pub fn pool_id(&self) -> H256 {
+        self.pool_id
+    }
+
+    pub fn pool_key(&self) -> PoolKey {
+        self.pool_key
+    }
+
+    pub fn hooks(&self) -> H160 {
+        self.pool_key.hooks
+    }
+
+    //Hooks can change the outcome of a swap, so simulations are only exact for pools without hooks
+    pub fn has_hooks(&self) -> bool {
+        !self.pool_key.hooks.is_zero()
+    }
+
+    //The native currency is the zero address and can only be token_a
+    pub fn data_is_populated(&self) -> bool {
+        !self.token_b.is_zero() && !self.sqrt_price.is_zero()
+    }
+
+    pub async fn populate_tick_data<M: 'static + Middleware>(
+        &mut self,
+        mut from_block: u64,
+        middleware: Arc<M>,
+    ) -> Result<u64, AMMError<M>> {
+        let current_block = middleware
+            .get_block_number()
+            .await
+            .map_err(AMMError::MiddlewareError)?
+            .as_u64();
+        let mut ordered_logs: BTreeMap<U64, Vec<Log>> = BTreeMap::new();
+
+        let pool_manager = self.pool_manager;
+        let pool_id = self.pool_id;
+
+        let mut handles = vec![];
+        let mut tasks = 0;
+
+        while from_block < current_block {
+            let middleware = middleware.clone();
+
+            let mut target_block = from_block + POPULATE_TICK_DATA_STEP - 1;
+            if target_block > current_block {
+                target_block = current_block;
+            }
+
+            handles.push(tokio::spawn(async move {
+                let logs = middleware
+                    .get_logs(
+                        &Filter::new()
+                            .topic0(MODIFY_LIQUIDITY_EVENT_SIGNATURE)
+                            .topic1(pool_id)
+                            .address(pool_manager)
+                            .from_block(BlockNumber::Number(U64([from_block])))
+                            .to_block(BlockNumber::Number(U64([target_block]))),
+                    )
+                    .await
+                    .map_err(AMMError::MiddlewareError)?;
+
+                Ok::<Vec<Log>, AMMError<M>>(logs)
+            }));
+
+            from_block += POPULATE_TICK_DATA_STEP;
+            tasks += 1;
+            //Here we are limiting the number of green threads that can be spun up to not have the node time out
+            if tasks == TASK_LIMIT {
+                process_logs_from_handles(handles, &mut ordered_logs).await?;
+                handles = vec![];
+                tasks = 0;
+            }
+        }
+
+        process_logs_from_handles(handles, &mut ordered_logs).await?;
+
+        for (_, log_group) in ordered_logs {
+            for log in log_group {
+                self.sync_from_log(log)?;
+            }
+        }
+
+        Ok(current_block)
+    }
+
+    pub fn sync_from_swap_log(&mut self, log: Log) -> Result<(), AbiError> {
+        let swap_event = SwapFilter::decode_log(&RawLog::from(log))?;
+
+        self.sqrt_price = swap_event.sqrt_price_x96;
+        self.liquidity = swap_event.liquidity;
+        self.tick = swap_event.tick;
+
+        Ok(())
+    }
+
+    pub fn sync_from_modify_liquidity_log(&mut self, log: Log) -> Result<(), AbiError> {
+        let modify_liquidity_event = ModifyLiquidityFilter::decode_log(&RawLog::from(log))?;
+
+        self.modify_position(
+            modify_liquidity_event.tick_lower,
+            modify_liquidity_event.tick_upper,
+            modify_liquidity_event.liquidity_delta.as_i128(),
+        );
+
+        Ok(())
+    }
+
+    pub fn modify_position(&mut self, tick_lower: i32, tick_upper: i32, liquidity_delta: i128) {
+        if liquidity_delta == 0 {
+            return;
+        }
+
+        let flipped_lower = self.update_tick(tick_lower, liquidity_delta, false);
+        let flipped_upper = self.update_tick(tick_upper, liquidity_delta, true);
+
+        if flipped_lower {
+            self.flip_tick(tick_lower);
+        }
+        if flipped_upper {
+            self.flip_tick(tick_upper);
+        }
+
+        if liquidity_delta < 0 {
+            if flipped_lower {
+                self.ticks.remove(&tick_lower);
+            }
+            if flipped_upper {
+                self.ticks.remove(&tick_upper);
+            }
+        }
+
+        //The position is only active if the current tick is within [tick_lower, tick_upper)
+        if self.tick >= tick_lower && self.tick < tick_upper {
+            self.liquidity = self.liquidity.saturating_add_signed(liquidity_delta);
+        }
+    }
+
+    //Returns true if the tick was flipped from initialized to uninitialized or vice versa
+    fn update_tick(&mut self, tick: i32, liquidity_delta: i128, upper: bool) -> bool {
+        let info = self.ticks.entry(tick).or_default();
+
+        let liquidity_gross_before = info.liquidity_gross;
+        let liquidity_gross_after = liquidity_gross_before.saturating_add_signed(liquidity_delta);
+
+        info.liquidity_gross = liquidity_gross_after;
+        info.initialized = liquidity_gross_after != 0;
+        info.liquidity_net = if upper {
+            info.liquidity_net - liquidity_delta
+        } else {
+            info.liquidity_net + liquidity_delta
+        };
+
+        (liquidity_gross_after == 0) != (liquidity_gross_before == 0)
+    }
+
+    fn flip_tick(&mut self, tick: i32) {
+        let (word_pos, bit_pos) =
+            uniswap_v3_math::tick_bitmap::position(tick / self.pool_key.tick_spacing);
+        let mask = U256::one() << bit_pos;
+
+        *self.tick_bitmap.entry(word_pos).or_default() ^= mask;
+    }
+
+    //A pool that is not initialized has no price, a pool without active liquidity or initialized ticks has no liquidity to swap through
+    fn check_liquidity(&self) -> Result<(), ArithmeticError> {
+        if self.sqrt_price.is_zero() || (self.liquidity == 0 && self.ticks.is_empty()) {
+            return Err(ArithmeticError::ZeroLiquidity(self.address));
+        }
+
+        Ok(())
+    }
+
+    //Runs the concentrated liquidity swap loop and returns the amount out along with the resulting (sqrt_price, tick, liquidity)
+    fn swap(
+        &self,
+        zero_for_one: bool,
+        amount_in: U256,
+    ) -> Result<(U256, (U256, i32, u128)), SwapSimulationError> {
+        let mut sqrt_price_x_96 = self.sqrt_price;
+        let mut tick = self.tick;
+        let mut liquidity = self.liquidity;
+
+        if amount_in.is_zero() {
+            return Ok((U256::zero(), (sqrt_price_x_96, tick, liquidity)));
+        }
+
+        //Set sqrt_price_limit_x_96 to the max or min sqrt price in the pool depending on zero_for_one
+        let sqrt_price_limit_x_96 = if zero_for_one {
+            MIN_SQRT_RATIO + 1
+        } else {
+            MAX_SQRT_RATIO - 1
+        };
+
+        let mut amount_specified_remaining = I256::from_raw(amount_in);
+        let mut amount_calculated = I256::zero();
+
+        while amount_specified_remaining != I256::zero() && sqrt_price_x_96 != sqrt_price_limit_x_96
+        {
+            let mut step = StepComputations {
+                sqrt_price_start_x_96: sqrt_price_x_96,
+                ..Default::default()
+            };
+
+            (step.tick_next, step.initialized) =
+                uniswap_v3_math::tick_bitmap::next_initialized_tick_within_one_word(
+                    &self.tick_bitmap,
+                    tick,
+                    self.pool_key.tick_spacing,
+                    zero_for_one,
+                )?;
+
+            // ensure that we do not overshoot the min/max tick, as the tick bitmap is not aware of these bounds
+            step.tick_next = step.tick_next.clamp(MIN_TICK, MAX_TICK);
+
+            step.sqrt_price_next_x96 =
+                uniswap_v3_math::tick_math::get_sqrt_ratio_at_tick(step.tick_next)?;
+
+            let swap_target_sqrt_ratio = if zero_for_one {
+                step.sqrt_price_next_x96.max(sqrt_price_limit_x_96)
+            } else {
+                step.sqrt_price_next_x96.min(sqrt_price_limit_x_96)
+            };
+
+            (
+                sqrt_price_x_96,
+                step.amount_in,
+                step.amount_out,
+                step.fee_amount,
+            ) = uniswap_v3_math::swap_math::compute_swap_step(
+                sqrt_price_x_96,
+                swap_target_sqrt_ratio,
+                liquidity,
+                amount_specified_remaining,
+                self.fee,
+            )?;
+
+            amount_specified_remaining = amount_specified_remaining
+                .overflowing_sub(I256::from_raw(
+                    step.amount_in.overflowing_add(step.fee_amount).0,
+                ))
+                .0;
+
+            amount_calculated -= I256::from_raw(step.amount_out);
+
+            //If the price moved all the way to the next price, cross the tick and apply the liquidity change
+            if sqrt_price_x_96 == step.sqrt_price_next_x96 {
+                if step.initialized {
+                    let mut liquidity_net = self
+                        .ticks
+                        .get(&step.tick_next)
+                        .map(|info| info.liquidity_net)
+                        .unwrap_or_default();
+
+                    if zero_for_one {
+                        liquidity_net = -liquidity_net;
+                    }
+
+                    liquidity = liquidity
+                        .checked_add_signed(liquidity_net)
+                        .ok_or(SwapSimulationError::LiquidityUnderflow)?;
+                }
+
+                tick = if zero_for_one {
+                    step.tick_next.wrapping_sub(1)
+                } else {
+                    step.tick_next
+                };
+            } else if sqrt_price_x_96 != step.sqrt_price_start_x_96 {
+                tick = uniswap_v3_math::tick_math::get_tick_at_sqrt_ratio(sqrt_price_x_96)?;
+            }
+        }
+
+        Ok((
+            (-amount_calculated).into_raw(),
+            (sqrt_price_x_96, tick, liquidity),
+        ))
+    }
+}
+
+//The state space is keyed by address, V4 pools use the first 20 bytes of their pool id as a virtual address
+pub fn pool_address_from_pool_id(pool_id: H256) -> H160 {
+    H160::from_slice(&pool_id[..20])
+}
+
+//Slot of the `Pool.State` struct for the pool id in the PoolManager storage
+pub fn pool_state_slot(pool_id: H256) -> U256 {
+    let mut preimage = [0u8; 64];
+    preimage[..32].copy_from_slice(pool_id.as_bytes());
+    POOLS_SLOT.to_big_endian(&mut preimage[32..]);
+
+    U256::from_big_endian(&keccak256(preimage))
+}
+
+pub(crate) async fn process_logs_from_handles<M: Middleware>(
+    handles: Vec<JoinHandle<Result<Vec<Log>, AMMError<M>>>>,
+    ordered_logs: &mut BTreeMap<U64, Vec<Log>>,
+) -> Result<(), AMMError<M>> {
+    // group the logs from each thread by block number and then sync the logs in chronological order
+    for handle in handles {
+        let logs = handle.await??;
+
+        for log in logs {
+            if let Some(log_block_number) = log.block_number {
+                ordered_logs.entry(log_block_number).or_default().push(log);
+            } else {
+                return Err(EventLogError::LogBlockNumberNotFound)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, str::FromStr};
+
+    use ethers::types::{H160, H256, U256};
+
+    use crate::{
+        amm::AutomatedMarketMaker,
+        errors::{ArithmeticError, SwapSimulationError},
+    };
+
+    use super::{pool_address_from_pool_id, PoolKey, UniswapV4Pool};
+
+    fn vanilla_pool() -> UniswapV4Pool {
+        let pool_id = H256::repeat_byte(0xab);
+
+        UniswapV4Pool {
+            address: pool_address_from_pool_id(pool_id),
+            pool_manager: H160::repeat_byte(0x01),
+            pool_id,
+            token_a: H160::zero(),
+            token_a_decimals: 18,
+            token_b: H160::repeat_byte(0x02),
+            token_b_decimals: 18,
+            liquidity: 0,
+            //sqrt(1) * 2^96
+            sqrt_price: U256::one() << 96,
+            fee: 3000,
+            tick: 0,
+            pool_key: PoolKey::new(
+                H160::zero(),
+                H160::repeat_byte(0x02),
+                3000,
+                60,
+                H160::zero(),
+            ),
+            tick_bitmap: HashMap::new(),
+            ticks: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_simulate_swap() {
+        let mut pool = vanilla_pool();
+        pool.modify_position(-600, 600, 10_u128.pow(24) as i128);
+
+        assert_eq!(pool.liquidity, 10_u128.pow(24));
+        assert!(!pool.has_hooks());
+
+        let amount_in = U256::exp10(18);
+        let amount_out = pool.simulate_swap(pool.token_a, amount_in).unwrap();
+
+        //At a price of 1 the output is the input minus the 0.3% fee and a small amount of slippage
+        assert!(amount_out < U256::from(997) * U256::exp10(15));
+        assert!(amount_out > U256::from(996) * U256::exp10(15));
+
+        let amount_out_mut = pool.simulate_swap_mut(pool.token_a, amount_in).unwrap();
+        assert_eq!(amount_out, amount_out_mut);
+        assert!(pool.sqrt_price < U256::one() << 96);
+        assert!(pool.tick < 0);
+    }
+
+    #[test]
+    fn test_zero_liquidity() {
+        let mut pool = vanilla_pool();
+
+        assert!(matches!(
+            pool.calculate_price(pool.token_a),
+            Err(ArithmeticError::ZeroLiquidity(address)) if address == pool.address
+        ));
+        assert!(matches!(
+            pool.simulate_swap(pool.token_a, U256::exp10(18)),
+            Err(SwapSimulationError::ArithmeticError(
+                ArithmeticError::ZeroLiquidity(_)
+            ))
+        ));
+
+        pool.sqrt_price = U256::zero();
+        pool.modify_position(-600, 600, 1000);
+        assert!(matches!(
+            pool.simulate_swap_mut(pool.token_b, U256::exp10(18)),
+            Err(SwapSimulationError::ArithmeticError(
+                ArithmeticError::ZeroLiquidity(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_modify_position() {
+        let mut pool = vanilla_pool();
+
+        pool.modify_position(-120, 120, 1000);
+        pool.modify_position(60, 180, 500);
+        assert_eq!(pool.liquidity, 1000);
+        assert_eq!(pool.ticks[&120].liquidity_net, -1000);
+        assert_eq!(pool.ticks[&60].liquidity_net, 500);
+
+        pool.modify_position(-120, 120, -1000);
+        assert_eq!(pool.liquidity, 0);
+        assert!(!pool.ticks.contains_key(&-120));
+        assert!(!pool.ticks.contains_key(&120));
+        assert!(pool.ticks.contains_key(&60));
+    }
+
+    #[test]
+    fn test_pool_address_from_pool_id() {
+        let pool = vanilla_pool();
+
+        assert_eq!(pool.address(), H160::repeat_byte(0xab));
+        assert_eq!(pool.pool_id(), H256::repeat_byte(0xab));
+    }
+
+    #[test]
+    fn test_pool_key_id() {
+        //ETH/USDC 0.05% pool on mainnet
+        let pool_key = PoolKey::new(
+            H160::zero(),
+            H160::from_str("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48").unwrap(),
+            500,
+            10,
+            H160::zero(),
+        );
+
+        assert_eq!(
+            pool_key.id(),
+            H256::from_str("0x21c67e77068de97969ba93d4aab21826d33ca12bb9f565d8496e8fda8a82ca27")
+                .unwrap()
+        );
+        assert!(!pool_key.is_dynamic_fee());
+    }
+}
@@ -0,0 +1,169 @@
+use std::{fs, path::PathBuf, sync::Arc};
+
+use amms::{
+    amm::{
+        factory::Factory,
+        uniswap_v2::{factory::UniswapV2Factory, UniswapV2Pool},
+        uniswap_v3::{factory::UniswapV3Factory, UniswapV3Pool},
+        AutomatedMarketMaker, AMM,
+    },
+    discovery::factory::{discover_factories, DiscoverableFactory},
+    sync,
+    token_list::TokenList,
+};
+use clap::{Parser, Subcommand, ValueEnum};
+use ethers::{
+    providers::{Http, Provider},
+    types::H160,
+};
+
+/// Discovers factories, dumps the pools of a factory and prints the price of a pool without writing any Rust
+#[derive(Parser)]
+#[command(name = "amms", version, about)]
+struct Cli {
+    #[arg(long, env = "ETHEREUM_RPC_ENDPOINT", global = true)]
+    rpc_url: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Prints the factories with at least `threshold` AMMs as JSON
+    DiscoverFactories {
+        #[arg(long, value_enum, value_delimiter = ',', default_values_t = [Kind::UniswapV2, Kind::UniswapV3])]
+        kinds: Vec<Kind>,
+        #[arg(long, default_value_t = 100_000)]
+        step: u64,
+        #[arg(long, default_value_t = 200)]
+        threshold: u64,
+        /// Drops factories that do not implement the interface of their kind, costs a few requests per factory
+        #[arg(long)]
+        verify: bool,
+    },
+    /// Syncs every pool of a factory and writes them to `out` as JSON
+    DumpPools {
+        #[arg(long)]
+        factory: H160,
+        #[arg(long, value_enum, default_value_t = Kind::UniswapV2)]
+        kind: Kind,
+        #[arg(long, default_value_t = 0)]
+        creation_block: u64,
+        /// Fee of the pools of a Uniswap V2 factory, 300 is 0.3%
+        #[arg(long, default_value_t = 300)]
+        fee: u32,
+        #[arg(long, default_value_t = 10_000)]
+        step: u64,
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Syncs a pool at the latest block and prints the price of `base_token`, token a of the pool if it is not provided
+    SyncPrice {
+        #[arg(long)]
+        pool: H160,
+        #[arg(long, value_enum, default_value_t = Kind::UniswapV2)]
+        kind: Kind,
+        #[arg(long)]
+        base_token: Option<H160>,
+        /// URL of a token list to print the symbols of the tokens of the pool, e.g. https://tokens.uniswap.org
+        #[arg(long)]
+        token_list: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Kind {
+    UniswapV2,
+    UniswapV3,
+}
+
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    tracing_subscriber::fmt::init();
+
+    let cli = Cli::parse();
+    let rpc_url = cli
+        .rpc_url
+        .ok_or_else(|| eyre::eyre!("--rpc-url or ETHEREUM_RPC_ENDPOINT must be set"))?;
+    let middleware = Arc::new(Provider::<Http>::try_from(rpc_url)?);
+
+    match cli.command {
+        Command::DiscoverFactories {
+            kinds,
+            step,
+            threshold,
+            verify,
+        } => {
+            let factories = kinds
+                .into_iter()
+                .map(|kind| match kind {
+                    Kind::UniswapV2 => DiscoverableFactory::UniswapV2Factory,
+                    Kind::UniswapV3 => DiscoverableFactory::UniswapV3Factory,
+                })
+                .collect();
+
+            let factories =
+                discover_factories(factories, threshold, middleware, step, None, None, verify)
+                    .await?;
+            println!("{}", serde_json::to_string_pretty(&factories)?);
+        }
+
+        Command::DumpPools {
+            factory,
+            kind,
+            creation_block,
+            fee,
+            step,
+            out,
+        } => {
+            let factory = match kind {
+                Kind::UniswapV2 => {
+                    Factory::UniswapV2Factory(UniswapV2Factory::new(factory, creation_block, fee))
+                }
+                Kind::UniswapV3 => {
+                    Factory::UniswapV3Factory(UniswapV3Factory::new(factory, creation_block))
+                }
+            };
+
+            let (amms, synced_block) =
+                sync::sync_amms(vec![factory], middleware, None, step).await?;
+            fs::write(&out, serde_json::to_string_pretty(&amms)?)?;
+
+            eprintln!(
+                "wrote {} pools synced at block {} to {}",
+                amms.len(),
+                synced_block,
+                out.display()
+            );
+        }
+
+        Command::SyncPrice {
+            pool,
+            kind,
+            base_token,
+            token_list,
+        } => {
+            let mut amm = match kind {
+                Kind::UniswapV2 => AMM::UniswapV2Pool(UniswapV2Pool {
+                    address: pool,
+                    ..Default::default()
+                }),
+                Kind::UniswapV3 => AMM::UniswapV3Pool(UniswapV3Pool {
+                    address: pool,
+                    ..Default::default()
+                }),
+            };
+            amm.populate_data(None, middleware).await?;
+
+            if let Some(url) = token_list {
+                eprintln!("{}", amm.display_name(&TokenList::from_url(&url).await?));
+            }
+
+            let base_token = base_token.unwrap_or(amm.tokens()[0]);
+            println!("{}", amm.calculate_price(base_token)?.to_f64());
+        }
+    }
+
+    Ok(())
+}
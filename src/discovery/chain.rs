@@ -0,0 +1,69 @@
+use ethers::types::Chain;
+
+//Block ranges used to scan the logs of a chain. `default_step` is a step that works with most providers,
+//`max_block_range` is the largest range a single `eth_getLogs` request is allowed to span.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainConfig {
+    pub chain_id: u64,
+    pub default_step: u64,
+    pub max_block_range: u64,
+}
+
+//12 second blocks
+pub const ETHEREUM_MAINNET: ChainConfig = ChainConfig::new(1, 50_000, 100_000);
+//Blocks are produced every 250ms, so a range covers a fraction of the time it does on mainnet
+pub const ARBITRUM_ONE: ChainConfig = ChainConfig::new(42161, 500_000, 2_000_000);
+//2 second blocks
+pub const OPTIMISM: ChainConfig = ChainConfig::new(10, 250_000, 1_000_000);
+//2 second blocks
+pub const BASE: ChainConfig = ChainConfig::new(8453, 250_000, 1_000_000);
+
+impl ChainConfig {
+    pub const fn new(chain_id: u64, default_step: u64, max_block_range: u64) -> ChainConfig {
+        ChainConfig {
+            chain_id,
+            default_step,
+            max_block_range,
+        }
+    }
+
+    pub fn for_chain(chain: Chain) -> Option<ChainConfig> {
+        match chain {
+            Chain::Mainnet => Some(ETHEREUM_MAINNET),
+            Chain::Arbitrum => Some(ARBITRUM_ONE),
+            Chain::Optimism => Some(OPTIMISM),
+            Chain::Base => Some(BASE),
+            _ => None,
+        }
+    }
+
+    //Caps the step at the max block range of the chain
+    pub fn block_step(&self, step: u64) -> u64 {
+        step.min(self.max_block_range)
+    }
+}
+
+impl Default for ChainConfig {
+    fn default() -> Self {
+        ETHEREUM_MAINNET
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::Chain;
+
+    use super::{ChainConfig, ARBITRUM_ONE, ETHEREUM_MAINNET};
+
+    #[test]
+    fn test_block_step() {
+        assert_eq!(ChainConfig::default(), ETHEREUM_MAINNET);
+        assert_eq!(ChainConfig::for_chain(Chain::Arbitrum), Some(ARBITRUM_ONE));
+        assert_eq!(ChainConfig::for_chain(Chain::Goerli), None);
+
+        assert_eq!(ETHEREUM_MAINNET.block_step(0), 0);
+        assert_eq!(ETHEREUM_MAINNET.block_step(10_000), 10_000);
+        assert_eq!(ETHEREUM_MAINNET.block_step(1_000_000), 100_000);
+        assert_eq!(ARBITRUM_ONE.block_step(1_000_000), 1_000_000);
+    }
+}
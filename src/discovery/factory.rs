@@ -1,118 +1,1118 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::read_to_string,
+    path::Path,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use ethers::{
-    providers::Middleware,
-    types::{Filter, H160, H256},
+    contract::ContractError,
+    providers::{Middleware, MiddlewareError},
+    types::{Chain, Filter, Log, H160, H256},
+};
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{
+    mpsc::{self, Receiver, Sender},
+    watch,
 };
 
 use crate::{
-    amm::{self, factory::Factory},
-    errors::AMMError,
+    amm::{
+        self,
+        factory::{AutomatedMarketMakerFactory, Factory},
+        uniswap_v2::factory::IUniswapV2Factory,
+        uniswap_v3::factory::IUniswapV3Factory,
+    },
+    errors::{AMMError, CheckpointError},
+    middleware::{
+        logs::{get_logs_with_adaptive_range, get_logs_with_max_results, MAX_LOG_RANGE_SPLITS},
+        retry::{RetryConfig, RetryMiddleware},
+    },
 };
 
+use super::{chain::ChainConfig, pancakeswap, sushiswap, velodrome};
+
+//Number of block ranges discovery fetches at a time by default
+pub const DEFAULT_DISCOVERY_CONCURRENCY: usize = 8;
+
 pub enum DiscoverableFactory {
     UniswapV2Factory,
     UniswapV3Factory,
+    CurveFactory,
+    BalancerV2Vault,
+    UniswapV4PoolManager,
+    SolidlyFactory,
+    LBFactory,
+    MaverickFactory,
+    AlgebraFactory,
+    DodoDVMFactory,
+    DodoDPPFactory,
+    SushiSwapV2Factory,
+    SushiSwapV3Factory,
+    PancakeSwapV2Factory,
+    PancakeSwapV3Factory,
+    VelodromeFactory,
+    AerodromeFactory,
+    /// A factory kind that is not built in, e.g. a private fork that emits its own creation event.
+    /// `builder` creates the empty factory that emitted a log with the event signature, or returns `None` to skip the log.
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    ///
+    /// use amms::{
+    ///     amm::{factory::Factory, solidly::factory::SolidlyFactory},
+    ///     discovery::factory::DiscoverableFactory,
+    /// };
+    /// use ethers::{types::H256, utils::keccak256};
+    ///
+    /// let event_signature = H256::from(keccak256("PoolDeployed(address,address,bool,address)"));
+    /// let factory = DiscoverableFactory::Custom {
+    ///     event_signature,
+    ///     builder: Arc::new(|log| {
+    ///         let creation_block = log.block_number?.as_u64();
+    ///         Some(Factory::SolidlyFactory(SolidlyFactory::new(
+    ///             log.address,
+    ///             creation_block,
+    ///         )))
+    ///     }),
+    /// };
+    ///
+    /// assert_eq!(factory.discovery_event_signature(), event_signature);
+    /// ```
+    Custom {
+        event_signature: H256,
+        builder: FactoryBuilder,
+    },
 }
 
+//Creates the empty factory that emitted a log, the factory must have the address and creation block of the log
+pub type FactoryBuilder = Arc<dyn Fn(&Log) -> Option<Factory> + Send + Sync>;
+
 impl DiscoverableFactory {
     pub fn discovery_event_signature(&self) -> H256 {
         match self {
-            DiscoverableFactory::UniswapV2Factory => {
+            DiscoverableFactory::UniswapV2Factory
+            | DiscoverableFactory::SushiSwapV2Factory
+            | DiscoverableFactory::PancakeSwapV2Factory => {
                 amm::uniswap_v2::factory::PAIR_CREATED_EVENT_SIGNATURE
             }
 
-            DiscoverableFactory::UniswapV3Factory => {
+            DiscoverableFactory::UniswapV3Factory
+            | DiscoverableFactory::SushiSwapV3Factory
+            | DiscoverableFactory::PancakeSwapV3Factory => {
                 amm::uniswap_v3::factory::POOL_CREATED_EVENT_SIGNATURE
             }
+
+            DiscoverableFactory::CurveFactory => amm::curve::factory::POOL_ADDED_EVENT_SIGNATURE,
+
+            DiscoverableFactory::BalancerV2Vault => {
+                amm::balancer_v2::factory::POOL_REGISTERED_EVENT_SIGNATURE
+            }
+
+            DiscoverableFactory::UniswapV4PoolManager => {
+                amm::uniswap_v4::INITIALIZE_EVENT_SIGNATURE
+            }
+
+            DiscoverableFactory::SolidlyFactory => {
+                amm::solidly::factory::PAIR_CREATED_EVENT_SIGNATURE
+            }
+
+            DiscoverableFactory::VelodromeFactory | DiscoverableFactory::AerodromeFactory => {
+                amm::solidly::factory::POOL_CREATED_EVENT_SIGNATURE
+            }
+
+            DiscoverableFactory::LBFactory => {
+                amm::trader_joe_lb::factory::LB_PAIR_CREATED_EVENT_SIGNATURE
+            }
+
+            DiscoverableFactory::MaverickFactory => {
+                amm::maverick::factory::POOL_CREATED_EVENT_SIGNATURE
+            }
+
+            DiscoverableFactory::AlgebraFactory => amm::algebra::factory::POOL_EVENT_SIGNATURE,
+
+            DiscoverableFactory::DodoDVMFactory => amm::dodo::factory::NEW_DVM_EVENT_SIGNATURE,
+
+            DiscoverableFactory::DodoDPPFactory => amm::dodo::factory::NEW_DPP_EVENT_SIGNATURE,
+
+            DiscoverableFactory::Custom {
+                event_signature, ..
+            } => *event_signature,
+        }
+    }
+
+    //Solidly factories emit either PairCreated or, from Velodrome V2 on, PoolCreated, see SolidlyFactoryVersion
+    pub fn discovery_event_signatures(&self) -> Vec<H256> {
+        match self {
+            DiscoverableFactory::SolidlyFactory => vec![
+                amm::solidly::factory::PAIR_CREATED_EVENT_SIGNATURE,
+                amm::solidly::factory::POOL_CREATED_EVENT_SIGNATURE,
+            ],
+            _ => vec![self.discovery_event_signature()],
+        }
+    }
+
+    //Forks that share the event signature of the protocol they forked are identified by their hardcoded factory addresses instead of by scanning logs
+    pub fn has_known_factories(&self) -> bool {
+        matches!(
+            self,
+            DiscoverableFactory::SushiSwapV2Factory
+                | DiscoverableFactory::SushiSwapV3Factory
+                | DiscoverableFactory::PancakeSwapV2Factory
+                | DiscoverableFactory::PancakeSwapV3Factory
+                | DiscoverableFactory::VelodromeFactory
+                | DiscoverableFactory::AerodromeFactory
+        )
+    }
+
+    //Pool counts of the known factories on the chain, see Factory::pool_count
+    pub async fn pool_counts<M: Middleware>(
+        &self,
+        chain: Chain,
+        middleware: Arc<M>,
+    ) -> Result<Vec<(H160, u64)>, AMMError<M>> {
+        let mut pool_counts = vec![];
+        for factory in self.known_factories(chain) {
+            let pool_count = factory.pool_count(middleware.clone()).await?;
+            pool_counts.push((factory.address(), pool_count));
+        }
+
+        Ok(pool_counts)
+    }
+
+    pub fn known_factories(&self, chain: Chain) -> Vec<Factory> {
+        match self {
+            DiscoverableFactory::SushiSwapV2Factory => sushiswap::sushiswap_v2_factories(chain),
+            DiscoverableFactory::SushiSwapV3Factory => sushiswap::sushiswap_v3_factories(chain),
+            DiscoverableFactory::PancakeSwapV2Factory => {
+                pancakeswap::pancakeswap_v2_factories(chain)
+            }
+            DiscoverableFactory::PancakeSwapV3Factory => {
+                pancakeswap::pancakeswap_v3_factories(chain)
+            }
+            DiscoverableFactory::VelodromeFactory => velodrome::velodrome_factories(chain),
+            DiscoverableFactory::AerodromeFactory => velodrome::aerodrome_factories(chain),
+            _ => vec![],
         }
     }
 }
 
-// Returns a vec of empty factories that match one of the Factory interfaces specified by each DiscoverableFactory
+// Returns a vec of empty factories that match one of the Factory interfaces specified by each DiscoverableFactory.
+// The step is capped at the max block range of the chain config, which defaults to the mainnet config. A step of zero returns an error.
+// The logs of up to `concurrency` ranges of `step` blocks are fetched at a time, DEFAULT_DISCOVERY_CONCURRENCY if it is not provided.
 pub async fn discover_factories<M: Middleware>(
     factories: Vec<DiscoverableFactory>,
     number_of_amms_threshold: u64,
     middleware: Arc<M>,
     step: u64,
+    concurrency: Option<usize>,
+    chain_config: Option<ChainConfig>,
+    verify: bool,
 ) -> Result<Vec<Factory>, AMMError<M>> {
-    tracing::info!(number_of_amms_threshold, step, "discovering new factories",);
+    let step = chain_config.unwrap_or_default().block_step(step);
 
-    let mut event_signatures = vec![];
+    let (identified_factories, known_factories) = scan_discovery_logs(
+        factories,
+        number_of_amms_threshold,
+        middleware.clone(),
+        step,
+        None,
+        None,
+        HashMap::new(),
+        None,
+        None,
+        concurrency,
+    )
+    .await?;
+
+    let filtered_factories =
+        filter_factories_by_threshold(identified_factories, number_of_amms_threshold);
+    let factories = merge_known_factories(filtered_factories, known_factories);
+
+    tracing::info!("all factories discovered");
 
+    if verify {
+        verify_factories(factories, middleware).await
+    } else {
+        Ok(factories)
+    }
+}
+
+//Discovery matches logs by their event signature only, so contracts that emit the same event without being a factory are discovered too.
+//Drops the factories without code and the factories whose kind specific getter reverts or returns nonsense, i.e. allPairsLength of a
+//Uniswap V2 factory or feeAmountTickSpacing of a Uniswap V3 factory. Other errors of the middleware are returned.
+pub async fn verify_factories<M: Middleware>(
+    factories: Vec<Factory>,
+    middleware: Arc<M>,
+) -> Result<Vec<Factory>, AMMError<M>> {
+    let mut verified_factories = vec![];
     for factory in factories {
-        event_signatures.push(factory.discovery_event_signature());
+        match verification_failure(&factory, middleware.clone()).await? {
+            Some(reason) => {
+                tracing::debug!(address = ?factory.address(), reason, "dropping factory that failed verification");
+            }
+            None => verified_factories.push(factory),
+        }
+    }
+
+    Ok(verified_factories)
+}
+
+//Reason the factory does not implement the interface of its kind, if any
+async fn verification_failure<M: Middleware>(
+    factory: &Factory,
+    middleware: Arc<M>,
+) -> Result<Option<&'static str>, AMMError<M>> {
+    let code = middleware
+        .get_code(factory.address(), None)
+        .await
+        .map_err(AMMError::MiddlewareError)?;
+
+    if code.is_empty() {
+        return Ok(Some("no contract code"));
+    }
+
+    let reason = match factory {
+        Factory::UniswapV2Factory(factory) => {
+            match IUniswapV2Factory::new(factory.address, middleware)
+                .all_pairs_length()
+                .call()
+                .await
+            {
+                Ok(pairs_length) if pairs_length.is_zero() => Some("allPairsLength returned zero"),
+                Ok(_) => None,
+                Err(err) if is_interface_error(&err) => Some("allPairsLength reverted"),
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Factory::UniswapV3Factory(factory) => {
+            match IUniswapV3Factory::new(factory.address, middleware)
+                .fee_amount_tick_spacing(500)
+                .call()
+                .await
+            {
+                Ok(tick_spacing) if tick_spacing < 0 => {
+                    Some("feeAmountTickSpacing returned a negative tick spacing")
+                }
+                Ok(_) => None,
+                Err(err) if is_interface_error(&err) => Some("feeAmountTickSpacing reverted"),
+                Err(err) => return Err(err.into()),
+            }
+        }
+        _ => None,
+    };
+
+    Ok(reason)
+}
+
+//Errors of a call to a contract that does not implement the function, as opposed to errors of the middleware
+fn is_interface_error<M: Middleware>(err: &ContractError<M>) -> bool {
+    match err {
+        ContractError::Revert(_)
+        | ContractError::DecodingError(_)
+        | ContractError::AbiError(_)
+        | ContractError::DetokenizationError(_) => true,
+        ContractError::MiddlewareError { e } => e
+            .as_error_response()
+            .is_some_and(|response| response.message.contains("revert")),
+        ContractError::ProviderError { e } => e
+            .as_error_response()
+            .is_some_and(|response| response.message.contains("revert")),
+        _ => false,
+    }
+}
+
+//Settings of discover_factories_with_config, the scan covers every block from genesis to the current block and retries are disabled by default
+#[derive(Debug, Clone)]
+pub struct DiscoveryConfig {
+    pub number_of_amms_threshold: u64,
+    pub step: u64,
+    pub start_block: Option<u64>,
+    pub end_block: Option<u64>,
+    pub retry: RetryConfig,
+    pub verify: bool,
+    pub max_logs_per_request: Option<usize>,
+    //Number of block ranges fetched at a time, DEFAULT_DISCOVERY_CONCURRENCY if it is not provided
+    pub concurrency: Option<usize>,
+}
+
+impl DiscoveryConfig {
+    pub fn new(number_of_amms_threshold: u64, step: u64) -> DiscoveryConfig {
+        DiscoveryConfig {
+            number_of_amms_threshold,
+            step,
+            start_block: None,
+            end_block: None,
+            retry: RetryConfig::default(),
+            verify: false,
+            max_logs_per_request: None,
+            concurrency: None,
+        }
+    }
+
+    //Drops the discovered factories that fail verify_factories
+    pub fn with_verification(mut self) -> DiscoveryConfig {
+        self.verify = true;
+        self
+    }
+
+    pub fn with_start_block(mut self, start_block: u64) -> DiscoveryConfig {
+        self.start_block = Some(start_block);
+        self
+    }
+
+    //Both blocks are inclusive, an end block past the current block is capped at the current block
+    pub fn with_block_range(mut self, start_block: u64, end_block: u64) -> DiscoveryConfig {
+        self.start_block = Some(start_block);
+        self.end_block = Some(end_block);
+        self
+    }
+
+    //Requests that are rate limited or dropped are retried up to `max_attempts` times in total, waiting an exponentially
+    //increasing, jittered delay starting at `base_delay` between attempts
+    pub fn with_retry(mut self, max_attempts: u32, base_delay: Duration) -> DiscoveryConfig {
+        self.retry = RetryConfig::new(max_attempts, base_delay);
+        self
+    }
+
+    pub fn with_retry_config(mut self, retry: RetryConfig) -> DiscoveryConfig {
+        self.retry = retry;
+        self
+    }
+
+    //Cap of the provider on the logs returned by a single eth_getLogs request, e.g. 10,000 for Alchemy. Block ranges that return
+    //that many logs may have been truncated and are scanned again in halves. Ranges rejected for returning too many logs are
+    //always scanned in halves.
+    pub fn with_max_logs_per_request(mut self, max_logs_per_request: usize) -> DiscoveryConfig {
+        self.max_logs_per_request = Some(max_logs_per_request);
+        self
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> DiscoveryConfig {
+        self.concurrency = Some(concurrency);
+        self
+    }
+}
+
+// Same as discover_factories, but every request goes through a RetryMiddleware so that a throttled request does not abort the scan
+pub async fn discover_factories_with_config<M: Middleware>(
+    factories: Vec<DiscoverableFactory>,
+    config: DiscoveryConfig,
+    middleware: Arc<M>,
+) -> Result<Vec<Factory>, AMMError<RetryMiddleware<M>>> {
+    let middleware = config.retry.wrap(middleware);
+
+    let (identified_factories, known_factories) = scan_discovery_logs(
+        factories,
+        config.number_of_amms_threshold,
+        middleware.clone(),
+        config.step,
+        config.start_block,
+        config.end_block,
+        HashMap::new(),
+        None,
+        config.max_logs_per_request,
+        config.concurrency,
+    )
+    .await?;
+
+    let filtered_factories =
+        filter_factories_by_threshold(identified_factories, config.number_of_amms_threshold);
+    let factories = merge_known_factories(filtered_factories, known_factories);
+
+    if config.verify {
+        verify_factories(factories, middleware).await
+    } else {
+        Ok(factories)
+    }
+}
+
+//Discovery progress persisted by discover_factories_from_checkpoint, serialized like the sync checkpoints
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiscoveryCheckpoint {
+    pub timestamp: usize,
+    pub last_scanned_block: u64,
+    pub identified_factories: HashMap<H160, (Factory, u64)>,
+}
+
+// Same as discover_factories, but writes the progress to the checkpoint at `checkpoint_path` after every block range and resumes
+// from the last scanned block of the checkpoint if the file exists. A checkpoint that can not be read or parsed returns an error
+// instead of restarting from genesis.
+pub async fn discover_factories_from_checkpoint<M: Middleware>(
+    factories: Vec<DiscoverableFactory>,
+    number_of_amms_threshold: u64,
+    middleware: Arc<M>,
+    step: u64,
+    checkpoint_path: &str,
+) -> Result<Vec<Factory>, AMMError<M>> {
+    let (start_block, identified_factories) = if Path::new(checkpoint_path).exists() {
+        let checkpoint = deconstruct_discovery_checkpoint(checkpoint_path)?;
+        tracing::info!(
+            checkpoint_path,
+            checkpoint.last_scanned_block,
+            "resuming discovery from checkpoint"
+        );
+
+        (
+            Some(checkpoint.last_scanned_block + 1),
+            checkpoint.identified_factories,
+        )
+    } else {
+        (None, HashMap::new())
+    };
+
+    let (progress_sender, mut progress_receiver) = watch::channel(DiscoveryProgress::default());
+
+    //Progress sent while a checkpoint is being written is coalesced into the next write
+    let write_checkpoints = async {
+        while progress_receiver.changed().await.is_ok() {
+            let progress = progress_receiver.borrow_and_update().clone();
+            construct_discovery_checkpoint(&progress, checkpoint_path)?;
+        }
+
+        Ok::<_, AMMError<M>>(())
+    };
+
+    //A checkpoint that can not be written stops the discovery
+    let (factories, _) = tokio::try_join!(
+        resume_factory_discovery(
+            factories,
+            number_of_amms_threshold,
+            middleware,
+            step,
+            start_block,
+            None,
+            identified_factories,
+            Some(progress_sender),
+        ),
+        write_checkpoints
+    )?;
+
+    Ok(factories)
+}
+
+//The checkpoint is written to a temporary file first so that an interrupted write does not corrupt the previous checkpoint
+pub fn construct_discovery_checkpoint(
+    progress: &DiscoveryProgress,
+    checkpoint_path: &str,
+) -> Result<(), CheckpointError> {
+    let checkpoint = DiscoveryCheckpoint {
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64() as usize,
+        last_scanned_block: progress.last_scanned_block,
+        identified_factories: progress.identified_factories.clone(),
+    };
+
+    let temporary_path = format!("{checkpoint_path}.tmp");
+    std::fs::write(&temporary_path, serde_json::to_string_pretty(&checkpoint)?)?;
+    std::fs::rename(temporary_path, checkpoint_path)?;
+
+    Ok(())
+}
+
+pub fn deconstruct_discovery_checkpoint(
+    checkpoint_path: &str,
+) -> Result<DiscoveryCheckpoint, CheckpointError> {
+    Ok(serde_json::from_str(
+        read_to_string(checkpoint_path)?.as_str(),
+    )?)
+}
+
+//State of a factory discovery after a block range has been scanned, can be persisted and passed back into resume_factory_discovery
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiscoveryProgress {
+    pub last_scanned_block: u64,
+    pub identified_factories: HashMap<H160, (Factory, u64)>,
+    //First block of the range that was scanned last, the range ends at `last_scanned_block`
+    #[serde(default)]
+    pub range_start_block: u64,
+    //Blocks the whole discovery scans, both inclusive
+    #[serde(default)]
+    pub start_block: u64,
+    #[serde(default)]
+    pub end_block: u64,
+    //Number of logs processed since the start of the discovery
+    #[serde(default)]
+    pub logs_processed: u64,
+}
+
+impl DiscoveryProgress {
+    pub fn percent_complete(&self) -> f64 {
+        if self.end_block <= self.start_block || self.last_scanned_block >= self.end_block {
+            return 100.0;
+        }
+
+        let scanned_blocks = self.last_scanned_block.saturating_sub(self.start_block) + 1;
+        scanned_blocks as f64 / (self.end_block - self.start_block + 1) as f64 * 100.0
+    }
+
+    //Factories with at least one pool, including the factories below the threshold of the discovery
+    pub fn factories_found(&self) -> usize {
+        self.identified_factories.len()
     }
+}
+
+// Same as discover_factories, but sends a DiscoveryProgress through `progress` after every block range.
+// The receiver only sees the latest progress, persist `last_scanned_block` and `identified_factories` from it to resume with resume_factory_discovery.
+pub async fn discover_factories_with_progress<M: Middleware>(
+    factories: Vec<DiscoverableFactory>,
+    number_of_amms_threshold: u64,
+    middleware: Arc<M>,
+    step: u64,
+    progress: watch::Sender<DiscoveryProgress>,
+) -> Result<Vec<Factory>, AMMError<M>> {
+    resume_factory_discovery(
+        factories,
+        number_of_amms_threshold,
+        middleware,
+        step,
+        None,
+        None,
+        HashMap::new(),
+        Some(progress),
+    )
+    .await
+}
+
+// Same as discover_factories, but starts scanning at `start_block` and adds the AMMs found to the `identified_factories` of a previous run.
+// To resume an interrupted discovery, pass `last_scanned_block + 1` and the `identified_factories` of the last DiscoveryProgress.
+// The scan stops at `end_block` if it is provided, otherwise at the current block.
+// If a `progress` sender is provided, the progress is sent after every block range.
+#[allow(clippy::too_many_arguments)]
+pub async fn resume_factory_discovery<M: Middleware>(
+    factories: Vec<DiscoverableFactory>,
+    number_of_amms_threshold: u64,
+    middleware: Arc<M>,
+    step: u64,
+    start_block: Option<u64>,
+    end_block: Option<u64>,
+    identified_factories: HashMap<H160, (Factory, u64)>,
+    progress: Option<watch::Sender<DiscoveryProgress>>,
+) -> Result<Vec<Factory>, AMMError<M>> {
+    let (identified_factories, known_factories) = scan_discovery_logs(
+        factories,
+        number_of_amms_threshold,
+        middleware,
+        step,
+        start_block,
+        end_block,
+        identified_factories,
+        progress,
+        None,
+        None,
+    )
+    .await?;
+
+    let filtered_factories =
+        filter_factories_by_threshold(identified_factories, number_of_amms_threshold);
+
+    tracing::info!("all factories discovered");
+    Ok(merge_known_factories(filtered_factories, known_factories))
+}
+
+// Same as discover_factories, but returns the number of AMMs found for each factory, sorted in descending order so that the top
+// factories can be picked instead of using a fixed threshold. Factories below the threshold are included if `include_below_threshold` is set.
+// Known factories are not counted by their logs and have a count of zero unless the protocol they forked is discovered too.
+pub async fn discover_factories_with_counts<M: Middleware>(
+    factories: Vec<DiscoverableFactory>,
+    number_of_amms_threshold: u64,
+    middleware: Arc<M>,
+    step: u64,
+    chain_config: Option<ChainConfig>,
+    include_below_threshold: bool,
+) -> Result<Vec<(Factory, u64)>, AMMError<M>> {
+    let step = chain_config.unwrap_or_default().block_step(step);
+
+    let (identified_factories, known_factories) = scan_discovery_logs(
+        factories,
+        number_of_amms_threshold,
+        middleware,
+        step,
+        None,
+        None,
+        HashMap::new(),
+        None,
+        None,
+        None,
+    )
+    .await?;
+
+    let (factories, amm_counts): (Vec<Factory>, Vec<u64>) = if include_below_threshold {
+        identified_factories.into_values().unzip()
+    } else {
+        identified_factories
+            .into_values()
+            .filter(|(_, amms_length)| *amms_length >= number_of_amms_threshold)
+            .unzip()
+    };
+
+    //Known factories that were not discovered by their logs are appended after the discovered factories
+    let mut factories = merge_known_factories(factories, known_factories)
+        .into_iter()
+        .enumerate()
+        .map(|(i, factory)| (factory, amm_counts.get(i).copied().unwrap_or_default()))
+        .collect::<Vec<(Factory, u64)>>();
+    factories.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+    for (factory, amms_length) in factories.iter() {
+        tracing::trace!(address = ?factory.address(), amms_length, "factory AMM count");
+    }
+
+    tracing::info!("all factories discovered");
+    Ok(factories)
+}
+
+//Scans the logs of the factories from `start_block` and returns the identified factories with their AMM counts along with the known factories
+#[allow(clippy::too_many_arguments)]
+async fn scan_discovery_logs<M: Middleware>(
+    factories: Vec<DiscoverableFactory>,
+    number_of_amms_threshold: u64,
+    middleware: Arc<M>,
+    step: u64,
+    start_block: Option<u64>,
+    end_block: Option<u64>,
+    mut identified_factories: HashMap<H160, (Factory, u64)>,
+    progress: Option<watch::Sender<DiscoveryProgress>>,
+    max_logs_per_request: Option<usize>,
+    concurrency: Option<usize>,
+) -> Result<(HashMap<H160, (Factory, u64)>, Vec<Factory>), AMMError<M>> {
+    if step == 0 {
+        return Err(AMMError::ZeroBatchSize("step"));
+    }
+
+    let concurrency = concurrency.unwrap_or(DEFAULT_DISCOVERY_CONCURRENCY).max(1);
+
+    let mut from_block = start_block.unwrap_or(0);
+
+    if let Some(end_block) = end_block {
+        if from_block > end_block {
+            return Err(AMMError::InvalidBlockRange(from_block, end_block));
+        }
+    }
+
+    tracing::info!(
+        number_of_amms_threshold,
+        step,
+        from_block,
+        concurrency,
+        "discovering new factories",
+    );
+
+    let (event_signatures, builders, known_factories) =
+        partition_factories(factories, &middleware).await?;
     tracing::trace!(?event_signatures);
 
+    let scan_logs = !event_signatures.is_empty();
     let block_filter = Filter::new().topic0(event_signatures);
 
-    let mut from_block = 0;
     let current_block = middleware
         .get_block_number()
         .await
         .map_err(AMMError::MiddlewareError)?
         .as_u64();
 
-    //For each block within the range, get all pairs asynchronously
-    // let step = 100000;
+    let current_block = match end_block {
+        Some(end_block) => end_block.min(current_block),
+        None => current_block,
+    };
 
-    //Set up filter and events to filter each block you are searching by
-    let mut identified_factories: HashMap<H160, (Factory, u64)> = HashMap::new();
+    let start_block = from_block;
+    let mut block_ranges = vec![];
+    while scan_logs && from_block <= current_block {
+        let target_block = (from_block + step - 1).min(current_block);
+        block_ranges.push((from_block, target_block));
+        from_block += step;
+    }
+
+    // `buffer_unordered` caps the number of in flight requests, a slow range does not hold back the ranges after it
+    let mut logs_stream = stream::iter(block_ranges)
+        .map(|(from_block, target_block)| {
+            let middleware = middleware.clone();
+            let block_filter = block_filter.clone();
+
+            async move {
+                tracing::info!("searching blocks {}-{}", from_block, target_block);
+                get_logs_with_max_results(
+                    middleware.as_ref(),
+                    &block_filter,
+                    from_block,
+                    target_block,
+                    MAX_LOG_RANGE_SPLITS,
+                    max_logs_per_request,
+                )
+                .await
+                .map(|logs| (from_block, target_block, logs))
+            }
+        })
+        .buffer_unordered(concurrency);
+
+    //Ranges that arrive early wait for the ranges before them, so that a progress only covers the blocks up to `last_scanned_block`
+    //and resuming from it does not count a log twice
+    let mut arrived_ranges: BTreeMap<u64, (u64, Vec<Log>)> = BTreeMap::new();
+    let mut next_block = start_block;
+    let mut logs_processed = 0;
+    while let Some(range) = logs_stream.next().await {
+        let (from_block, target_block, logs) = range.map_err(AMMError::MiddlewareError)?;
+        arrived_ranges.insert(from_block, (target_block, logs));
+
+        while let Some((target_block, logs)) = arrived_ranges.remove(&next_block) {
+            logs_processed += logs.len() as u64;
+            //Keeps the earliest creation block of a factory whose logs are spread over several ranges
+            process_discovery_logs(logs, &builders, &mut identified_factories)?;
+
+            if let Some(progress) = &progress {
+                progress.send_replace(DiscoveryProgress {
+                    last_scanned_block: target_block,
+                    identified_factories: identified_factories.clone(),
+                    range_start_block: next_block,
+                    start_block,
+                    end_block: current_block,
+                    logs_processed,
+                });
+            }
+
+            next_block = target_block + 1;
+        }
+    }
+
+    Ok((identified_factories, known_factories))
+}
+
+// Same as discover_factories, but scans in a spawned task and sends each factory through the returned channel as soon as it reaches the `number_of_amms_threshold`.
+// If the scan fails, the error is sent as the last message. The scan stops early once the receiver is dropped.
+pub fn discover_factories_stream<M: 'static + Middleware>(
+    factories: Vec<DiscoverableFactory>,
+    number_of_amms_threshold: u64,
+    middleware: Arc<M>,
+    step: u64,
+    buffer: usize,
+) -> Receiver<Result<Factory, AMMError<M>>> {
+    let (factory_sender, factory_receiver) = mpsc::channel(buffer.max(1));
 
-    //TODO: make this async
-    while from_block < current_block {
-        //Get pair created event logs within the block range
-        let mut target_block = from_block + step - 1;
-        if target_block > current_block {
-            target_block = current_block;
+    tokio::spawn(async move {
+        if let Err(err) = stream_discovered_factories(
+            factories,
+            number_of_amms_threshold,
+            middleware,
+            step,
+            &factory_sender,
+        )
+        .await
+        {
+            let _ = factory_sender.send(Err(err)).await;
+        }
+    });
+
+    factory_receiver
+}
+
+async fn stream_discovered_factories<M: Middleware>(
+    factories: Vec<DiscoverableFactory>,
+    number_of_amms_threshold: u64,
+    middleware: Arc<M>,
+    step: u64,
+    factory_sender: &Sender<Result<Factory, AMMError<M>>>,
+) -> Result<(), AMMError<M>> {
+    if step == 0 {
+        return Err(AMMError::ZeroBatchSize("step"));
+    }
+
+    tracing::info!(
+        number_of_amms_threshold,
+        step,
+        "streaming newly discovered factories",
+    );
+
+    let (event_signatures, builders, known_factories) =
+        partition_factories(factories, &middleware).await?;
+    tracing::trace!(?event_signatures);
+
+    let mut sent_factories: HashSet<H160> = HashSet::new();
+    for factory in known_factories {
+        sent_factories.insert(factory.address());
+        if factory_sender.send(Ok(factory)).await.is_err() {
+            tracing::debug!("factory receiver dropped, stopping discovery");
+            return Ok(());
         }
+    }
+
+    if event_signatures.is_empty() {
+        return Ok(());
+    }
+
+    let block_filter = Filter::new().topic0(event_signatures);
+
+    let current_block = middleware
+        .get_block_number()
+        .await
+        .map_err(AMMError::MiddlewareError)?
+        .as_u64();
+
+    let mut identified_factories: HashMap<H160, (Factory, u64)> = HashMap::new();
+
+    let mut from_block = 0;
+    while from_block <= current_block {
+        let target_block = (from_block + step - 1).min(current_block);
 
         tracing::info!("searching blocks {}-{}", from_block, target_block);
 
-        let block_filter = block_filter.clone();
-        let logs = middleware
-            .get_logs(&block_filter.from_block(from_block).to_block(target_block))
+        let logs = get_logs_with_adaptive_range(
+            middleware.as_ref(),
+            &block_filter,
+            from_block,
+            target_block,
+            MAX_LOG_RANGE_SPLITS,
+        )
+        .await
+        .map_err(AMMError::MiddlewareError)?;
+
+        process_discovery_logs(logs, &builders, &mut identified_factories)?;
+
+        for (address, (factory, amms_length)) in identified_factories.iter() {
+            if *amms_length >= number_of_amms_threshold && sent_factories.insert(*address) {
+                tracing::trace!("factory {} has {} AMMs => sending", address, amms_length);
+
+                if factory_sender.send(Ok(factory.clone())).await.is_err() {
+                    tracing::debug!("factory receiver dropped, stopping discovery");
+                    return Ok(());
+                }
+            }
+        }
+
+        from_block += step;
+    }
+
+    tracing::info!("all factories discovered");
+    Ok(())
+}
+
+//Splits the factories into the event signatures to scan the logs for, the builders of custom factories and the known factories on the chain of the middleware
+async fn partition_factories<M: Middleware>(
+    factories: Vec<DiscoverableFactory>,
+    middleware: &Arc<M>,
+) -> Result<(Vec<H256>, HashMap<H256, FactoryBuilder>, Vec<Factory>), AMMError<M>> {
+    let chain = if factories
+        .iter()
+        .any(|factory| factory.has_known_factories())
+    {
+        let chain_id = middleware
+            .get_chainid()
             .await
             .map_err(AMMError::MiddlewareError)?;
 
-        for log in logs {
-            tracing::trace!("found matching event at factory {}", log.address);
-            if let Some((_, amms_length)) = identified_factories.get_mut(&log.address) {
-                *amms_length += 1;
-                tracing::trace!(
-                    "increasing factory {} AMMs to {}",
-                    log.address,
-                    *amms_length
-                );
-            } else {
-                let mut factory = Factory::try_from(log.topics[0])?;
-
-                match &mut factory {
-                    Factory::UniswapV2Factory(uniswap_v2_factory) => {
-                        uniswap_v2_factory.address = log.address;
-                        uniswap_v2_factory.creation_block = log
-                            .block_number
-                            .ok_or(AMMError::BlockNumberNotFound)?
-                            .as_u64();
+        let chain = Chain::try_from(chain_id).ok();
+        if chain.is_none() {
+            tracing::warn!(?chain_id, "no known factories on chain");
+        }
+
+        chain
+    } else {
+        None
+    };
+
+    let mut event_signatures = vec![];
+    let mut builders = HashMap::new();
+    let mut known_factories = vec![];
+    for factory in factories {
+        if factory.has_known_factories() {
+            if let Some(chain) = chain {
+                known_factories.extend(factory.known_factories(chain));
+            }
+        } else {
+            event_signatures.extend(factory.discovery_event_signatures());
+
+            if let DiscoverableFactory::Custom {
+                event_signature,
+                builder,
+            } = factory
+            {
+                builders.insert(event_signature, builder);
+            }
+        }
+    }
+
+    Ok((event_signatures, builders, known_factories))
+}
+
+//Known factories are added regardless of the number of AMMs threshold
+fn merge_known_factories(
+    mut factories: Vec<Factory>,
+    known_factories: Vec<Factory>,
+) -> Vec<Factory> {
+    for known_factory in known_factories {
+        //A fork that shares the event signature of the protocol it forked is also discovered by its logs, the registry
+        //entry replaces it because it is configured for the fork, i.e. with the fee of the PancakeSwap V2 pairs.
+        //The creation block of the logs is kept where the registry does not know it.
+        match factories
+            .iter_mut()
+            .find(|factory| factory.address() == known_factory.address())
+        {
+            Some(factory) => {
+                let creation_block = factory.creation_block();
+                *factory = known_factory;
+
+                match factory {
+                    Factory::UniswapV2Factory(factory) if factory.creation_block == 0 => {
+                        factory.creation_block = creation_block;
+                    }
+                    Factory::UniswapV3Factory(factory) if factory.creation_block == 0 => {
+                        factory.creation_block = creation_block;
                     }
-                    Factory::UniswapV3Factory(uniswap_v3_factory) => {
-                        uniswap_v3_factory.address = log.address;
-                        uniswap_v3_factory.creation_block = log
-                            .block_number
-                            .ok_or(AMMError::BlockNumberNotFound)?
-                            .as_u64();
+                    Factory::SolidlyFactory(factory) if factory.creation_block == 0 => {
+                        factory.creation_block = creation_block;
                     }
+                    _ => {}
                 }
+            }
+            None => factories.push(known_factory),
+        }
+    }
+
+    factories
+}
+
+//Ranges can be processed in any order, the creation block of a factory is the block of the earliest log that was processed
+fn process_discovery_logs<M: Middleware>(
+    logs: Vec<Log>,
+    builders: &HashMap<H256, FactoryBuilder>,
+    identified_factories: &mut HashMap<H160, (Factory, u64)>,
+) -> Result<(), AMMError<M>> {
+    for log in logs {
+        tracing::trace!("found matching event at factory {}", log.address);
+        if let Some((factory, amms_length)) = identified_factories.get_mut(&log.address) {
+            *amms_length += 1;
+            tracing::trace!(
+                "increasing factory {} AMMs to {}",
+                log.address,
+                *amms_length
+            );
 
-                tracing::info!(address = ?log.address, "discovered new factory");
-                identified_factories.insert(log.address, (factory, 0));
+            let block_number = log
+                .block_number
+                .ok_or(AMMError::BlockNumberNotFound)?
+                .as_u64();
+            if block_number < factory.creation_block() {
+                if let Some(earlier_factory) = build_factory(&log, builders)? {
+                    *factory = earlier_factory;
+                }
             }
+        } else if let Some(factory) = build_factory(&log, builders)? {
+            //The log that identifies the factory is the creation event of its first AMM
+            tracing::info!(address = ?log.address, "discovered new factory");
+            identified_factories.insert(log.address, (factory, 1));
+        } else {
+            tracing::trace!(address = ?log.address, "log was not emitted by a custom factory");
         }
+    }
 
-        from_block += step;
+    Ok(())
+}
+
+//Custom builders take precedence over the built in factories that share their event signature
+fn build_factory<M: Middleware>(
+    log: &Log,
+    builders: &HashMap<H256, FactoryBuilder>,
+) -> Result<Option<Factory>, AMMError<M>> {
+    match builders.get(&log.topics[0]) {
+        Some(builder) => Ok(builder(log)),
+        None => Ok(Some(new_factory_from_log(log)?)),
     }
+}
 
+//Empty factory of the kind that emits the log, created at the block of the log
+fn new_factory_from_log<M: Middleware>(log: &Log) -> Result<Factory, AMMError<M>> {
+    let mut factory = Factory::try_from(log.topics[0])?;
+
+    match &mut factory {
+        Factory::UniswapV2Factory(uniswap_v2_factory) => {
+            uniswap_v2_factory.address = log.address;
+            uniswap_v2_factory.creation_block = log
+                .block_number
+                .ok_or(AMMError::BlockNumberNotFound)?
+                .as_u64();
+        }
+        Factory::UniswapV3Factory(uniswap_v3_factory) => {
+            uniswap_v3_factory.address = log.address;
+            uniswap_v3_factory.creation_block = log
+                .block_number
+                .ok_or(AMMError::BlockNumberNotFound)?
+                .as_u64();
+        }
+        Factory::BalancerV2Factory(balancer_v2_factory) => {
+            balancer_v2_factory.address = log.address;
+            balancer_v2_factory.creation_block = log
+                .block_number
+                .ok_or(AMMError::BlockNumberNotFound)?
+                .as_u64();
+        }
+        Factory::BalancerV2Vault(balancer_v2_vault) => {
+            balancer_v2_vault.address = log.address;
+            balancer_v2_vault.creation_block = log
+                .block_number
+                .ok_or(AMMError::BlockNumberNotFound)?
+                .as_u64();
+        }
+        Factory::CurveFactory(curve_factory) => {
+            curve_factory.address = log.address;
+            curve_factory.creation_block = log
+                .block_number
+                .ok_or(AMMError::BlockNumberNotFound)?
+                .as_u64();
+        }
+        Factory::UniswapV4PoolManager(pool_manager) => {
+            pool_manager.address = log.address;
+            pool_manager.creation_block = log
+                .block_number
+                .ok_or(AMMError::BlockNumberNotFound)?
+                .as_u64();
+        }
+        Factory::SolidlyFactory(solidly_factory) => {
+            solidly_factory.address = log.address;
+            solidly_factory.creation_block = log
+                .block_number
+                .ok_or(AMMError::BlockNumberNotFound)?
+                .as_u64();
+        }
+        Factory::LBFactory(lb_factory) => {
+            lb_factory.address = log.address;
+            lb_factory.creation_block = log
+                .block_number
+                .ok_or(AMMError::BlockNumberNotFound)?
+                .as_u64();
+        }
+        Factory::MaverickFactory(maverick_factory) => {
+            maverick_factory.address = log.address;
+            maverick_factory.creation_block = log
+                .block_number
+                .ok_or(AMMError::BlockNumberNotFound)?
+                .as_u64();
+        }
+        Factory::AlgebraFactory(algebra_factory) => {
+            algebra_factory.address = log.address;
+            algebra_factory.creation_block = log
+                .block_number
+                .ok_or(AMMError::BlockNumberNotFound)?
+                .as_u64();
+        }
+        Factory::DodoFactory(dodo_factory) => {
+            dodo_factory.address = log.address;
+            dodo_factory.creation_block = log
+                .block_number
+                .ok_or(AMMError::BlockNumberNotFound)?
+                .as_u64();
+        }
+        //Elastic and Camelot factories share the Uniswap V3 and V2 creation events and are never identified by their logs
+        Factory::KyberElasticFactory(_) | Factory::CamelotFactory(_) => {}
+    }
+
+    Ok(factory)
+}
+
+fn filter_factories_by_threshold(
+    identified_factories: HashMap<H160, (Factory, u64)>,
+    number_of_amms_threshold: u64,
+) -> Vec<Factory> {
     let mut filtered_factories = vec![];
     tracing::trace!(number_of_amms_threshold, "checking threshold");
     for (address, (factory, amms_length)) in identified_factories {
@@ -124,6 +1124,563 @@ pub async fn discover_factories<M: Middleware>(
         }
     }
 
-    tracing::info!("all factories discovered");
-    Ok(filtered_factories)
+    filtered_factories
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, str::FromStr, sync::Arc, time::Duration};
+
+    use async_trait::async_trait;
+    use ethers::{
+        abi::{encode, Token},
+        providers::{Http, Middleware, MockProvider, Provider, ProviderError},
+        types::{Bytes, Chain, Filter, Log, H160, H256, U256, U64},
+    };
+    use tokio::sync::watch;
+
+    use crate::{
+        amm::{
+            factory::{AutomatedMarketMakerFactory, Factory},
+            solidly::factory::{
+                SolidlyFactory, SolidlyFactoryVersion, POOL_CREATED_EVENT_SIGNATURE,
+            },
+            uniswap_v2::factory::{UniswapV2Factory, PAIR_CREATED_EVENT_SIGNATURE},
+            uniswap_v3::factory::UniswapV3Factory,
+            AMM,
+        },
+        discovery::pancakeswap::PANCAKESWAP_V2_FEE,
+        errors::AMMError,
+    };
+
+    use super::{
+        construct_discovery_checkpoint, deconstruct_discovery_checkpoint, discover_factories,
+        discover_factories_from_checkpoint, discover_factories_stream,
+        discover_factories_with_counts, discover_factories_with_progress, merge_known_factories,
+        process_discovery_logs, resume_factory_discovery, verify_factories, DiscoverableFactory,
+        DiscoveryProgress, FactoryBuilder,
+    };
+
+    #[test]
+    fn test_process_discovery_logs_out_of_order() -> eyre::Result<()> {
+        let address = H160::from_low_u64_be(1);
+        let log = |block_number: u64| Log {
+            address,
+            topics: vec![PAIR_CREATED_EVENT_SIGNATURE],
+            block_number: Some(block_number.into()),
+            ..Default::default()
+        };
+
+        //The later range is processed first
+        let mut identified_factories: HashMap<H160, (Factory, u64)> = HashMap::new();
+        process_discovery_logs::<Provider<Http>>(
+            vec![log(200), log(250)],
+            &HashMap::new(),
+            &mut identified_factories,
+        )?;
+        process_discovery_logs::<Provider<Http>>(
+            vec![log(100)],
+            &HashMap::new(),
+            &mut identified_factories,
+        )?;
+
+        let (factory, amms_length) = &identified_factories[&address];
+        assert_eq!(factory.creation_block(), 100);
+        assert_eq!(factory.address(), address);
+        assert_eq!(*amms_length, 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_discover_factories_at_threshold() -> eyre::Result<()> {
+        let (provider, mock) = Provider::mocked();
+        let address = H160::from_low_u64_be(1);
+        let number_of_amms_threshold = 3;
+
+        //Responses are returned last in first out, the block number is requested before the logs
+        let logs = (0..number_of_amms_threshold)
+            .map(|i| Log {
+                address,
+                topics: vec![PAIR_CREATED_EVENT_SIGNATURE],
+                block_number: Some(U64::from(100 + i)),
+                ..Default::default()
+            })
+            .collect::<Vec<Log>>();
+        mock.push(logs)?;
+        mock.push(U64::from(500))?;
+
+        let factories = discover_factories(
+            vec![DiscoverableFactory::UniswapV2Factory],
+            number_of_amms_threshold,
+            Arc::new(provider),
+            1000,
+            None,
+            None,
+            false,
+        )
+        .await?;
+
+        assert_eq!(factories.len(), 1);
+        assert_eq!(factories[0].address(), address);
+        assert_eq!(factories[0].creation_block(), 100);
+
+        Ok(())
+    }
+
+    //Answers get_logs with the logs in the block range of the filter, ranges that start later are answered sooner so that the ranges
+    //of a concurrent scan arrive out of order
+    #[derive(Debug)]
+    struct LogRangeMiddleware {
+        inner: Provider<MockProvider>,
+        head: u64,
+        logs: Vec<Log>,
+    }
+
+    #[async_trait]
+    impl Middleware for LogRangeMiddleware {
+        type Error = ProviderError;
+        type Provider = MockProvider;
+        type Inner = Provider<MockProvider>;
+
+        fn inner(&self) -> &Provider<MockProvider> {
+            &self.inner
+        }
+
+        async fn get_block_number(&self) -> Result<U64, ProviderError> {
+            Ok(U64::from(self.head))
+        }
+
+        async fn get_logs(&self, filter: &Filter) -> Result<Vec<Log>, ProviderError> {
+            let from_block = filter.get_from_block().unwrap_or_default().as_u64();
+            let to_block = filter.get_to_block().unwrap_or_default().as_u64();
+            tokio::time::sleep(Duration::from_millis(self.head - from_block)).await;
+
+            Ok(self
+                .logs
+                .iter()
+                .filter(|log| {
+                    log.block_number
+                        .is_some_and(|block| (from_block..=to_block).contains(&block.as_u64()))
+                })
+                .cloned()
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_discover_factories_concurrent_matches_sequential() -> eyre::Result<()> {
+        let address = H160::from_low_u64_be(1);
+        let log = |block_number: u64| Log {
+            address,
+            topics: vec![PAIR_CREATED_EVENT_SIGNATURE],
+            block_number: Some(U64::from(block_number)),
+            ..Default::default()
+        };
+
+        //The third pair is created in the head block
+        let middleware = Arc::new(LogRangeMiddleware {
+            inner: Provider::mocked().0,
+            head: 20,
+            logs: vec![log(5), log(15), log(20)],
+        });
+
+        for step in [1, 7, 10, 50] {
+            let sequential = discover_factories(
+                vec![DiscoverableFactory::UniswapV2Factory],
+                3,
+                middleware.clone(),
+                step,
+                Some(1),
+                None,
+                false,
+            )
+            .await?;
+            let concurrent = discover_factories(
+                vec![DiscoverableFactory::UniswapV2Factory],
+                3,
+                middleware.clone(),
+                step,
+                Some(4),
+                None,
+                false,
+            )
+            .await?;
+
+            let mut streamed = vec![];
+            let mut factory_receiver = discover_factories_stream(
+                vec![DiscoverableFactory::UniswapV2Factory],
+                3,
+                middleware.clone(),
+                step,
+                1,
+            );
+            while let Some(factory) = factory_receiver.recv().await {
+                streamed.push(factory?);
+            }
+
+            //The earliest creation block is kept although the range of the first pair arrives last
+            assert_eq!(sequential.len(), 1);
+            assert_eq!(sequential[0].address(), address);
+            assert_eq!(sequential[0].creation_block(), 5);
+            assert_eq!(
+                serde_json::to_string(&concurrent)?,
+                serde_json::to_string(&sequential)?
+            );
+            assert_eq!(
+                serde_json::to_string(&streamed)?,
+                serde_json::to_string(&sequential)?
+            );
+
+            //Progress is only sent for the blocks before the ranges that have not arrived yet
+            let (sender, receiver) = watch::channel(DiscoveryProgress::default());
+            discover_factories_with_progress(
+                vec![DiscoverableFactory::UniswapV2Factory],
+                3,
+                middleware.clone(),
+                step,
+                sender,
+            )
+            .await?;
+            let progress = receiver.borrow().clone();
+            assert_eq!(progress.last_scanned_block, 20);
+            assert_eq!(progress.logs_processed, 3);
+        }
+
+        //A zero step is rejected before any request is sent
+        assert!(matches!(
+            discover_factories(
+                vec![DiscoverableFactory::UniswapV2Factory],
+                3,
+                Arc::new(Provider::mocked().0),
+                0,
+                None,
+                None,
+                false,
+            )
+            .await,
+            Err(AMMError::ZeroBatchSize("step"))
+        ));
+        let mut factory_receiver = discover_factories_stream(
+            vec![DiscoverableFactory::UniswapV2Factory],
+            3,
+            Arc::new(Provider::mocked().0),
+            0,
+            10,
+        );
+        assert!(matches!(
+            factory_receiver.recv().await,
+            Some(Err(AMMError::ZeroBatchSize("step")))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_discover_factories_with_counts() -> eyre::Result<()> {
+        let (top_factory, small_factory) = (H160::from_low_u64_be(1), H160::from_low_u64_be(2));
+        let logs = [top_factory, small_factory, top_factory, top_factory]
+            .into_iter()
+            .enumerate()
+            .map(|(i, address)| Log {
+                address,
+                topics: vec![PAIR_CREATED_EVENT_SIGNATURE],
+                block_number: Some(U64::from(100 + i)),
+                ..Default::default()
+            })
+            .collect::<Vec<Log>>();
+
+        for include_below_threshold in [false, true] {
+            //Responses are returned last in first out, the block number is requested before the logs
+            let (provider, mock) = Provider::mocked();
+            mock.push(logs.clone())?;
+            mock.push(U64::from(500))?;
+
+            let factories = discover_factories_with_counts(
+                vec![DiscoverableFactory::UniswapV2Factory],
+                2,
+                Arc::new(provider),
+                1000,
+                None,
+                include_below_threshold,
+            )
+            .await?;
+
+            let counts = factories
+                .iter()
+                .map(|(factory, amms_length)| (factory.address(), *amms_length))
+                .collect::<Vec<(H160, u64)>>();
+
+            if include_below_threshold {
+                assert_eq!(counts, vec![(top_factory, 3), (small_factory, 1)]);
+            } else {
+                assert_eq!(counts, vec![(top_factory, 3)]);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_discover_aerodrome_factory() -> eyre::Result<()> {
+        let (provider, mock) = Provider::mocked();
+        let aerodrome_factory = H160::from_str("0x420DD381b31aEf6683db6B902084cB0FFECe40Da")?;
+        let weth = H160::from_str("0x4200000000000000000000000000000000000006")?;
+        let usdc = H160::from_str("0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913")?;
+
+        //PoolCreated logs of the first pools of the Aerodrome factory on Base, the stable flag is the last topic
+        let pool_created_log = |block_number: u64, pool: u64, stable: bool| Log {
+            address: aerodrome_factory,
+            topics: vec![
+                POOL_CREATED_EVENT_SIGNATURE,
+                H256::from(weth),
+                H256::from(usdc),
+                H256::from_low_u64_be(stable as u64),
+            ],
+            data: Bytes::from(encode(&[
+                Token::Address(H160::from_low_u64_be(pool)),
+                Token::Uint(U256::from(pool)),
+            ])),
+            block_number: Some(U64::from(block_number)),
+            ..Default::default()
+        };
+
+        //Responses are returned last in first out, the block number is requested before the logs
+        mock.push(vec![
+            pool_created_log(3200601, 1, false),
+            pool_created_log(3200668, 2, true),
+        ])?;
+        mock.push(U64::from(3209999))?;
+
+        let factories = resume_factory_discovery(
+            vec![DiscoverableFactory::SolidlyFactory],
+            2,
+            Arc::new(provider),
+            10000,
+            Some(3200000),
+            Some(3209999),
+            HashMap::new(),
+            None,
+        )
+        .await?;
+
+        let factory = match &factories[..] {
+            [Factory::SolidlyFactory(factory)] => factory,
+            _ => panic!("expected the Aerodrome factory"),
+        };
+        assert_eq!(factory.address, aerodrome_factory);
+        assert_eq!(factory.creation_block, 3200601);
+        assert_eq!(factory.version, SolidlyFactoryVersion::V2);
+
+        let pool = factory.new_empty_amm_from_log(pool_created_log(3200668, 2, true))?;
+        match pool {
+            AMM::SolidlyPool(pool) => {
+                assert!(pool.stable);
+                assert_eq!(pool.address, H160::from_low_u64_be(2));
+                assert_eq!((pool.token_a, pool.token_b), (weth, usdc));
+            }
+            _ => panic!("expected a Solidly pool"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_discovery_logs_custom_builder() -> eyre::Result<()> {
+        let custom_factory = H160::from_low_u64_be(2);
+        let log = |address: H160| Log {
+            address,
+            topics: vec![PAIR_CREATED_EVENT_SIGNATURE],
+            block_number: Some(100.into()),
+            ..Default::default()
+        };
+
+        //Only logs of the custom factory are kept
+        let builder: FactoryBuilder = Arc::new(move |log| {
+            (log.address == custom_factory).then(|| {
+                Factory::SolidlyFactory(SolidlyFactory::new(
+                    log.address,
+                    log.block_number.unwrap_or_default().as_u64(),
+                ))
+            })
+        });
+        let builders = HashMap::from([(PAIR_CREATED_EVENT_SIGNATURE, builder)]);
+
+        let mut identified_factories: HashMap<H160, (Factory, u64)> = HashMap::new();
+        process_discovery_logs::<Provider<Http>>(
+            vec![log(H160::from_low_u64_be(1)), log(custom_factory)],
+            &builders,
+            &mut identified_factories,
+        )?;
+
+        assert_eq!(identified_factories.len(), 1);
+        assert!(matches!(
+            identified_factories[&custom_factory].0,
+            Factory::SolidlyFactory(_)
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_factories() -> eyre::Result<()> {
+        let (provider, mock) = Provider::mocked();
+        let factory = H160::from_low_u64_be(1);
+        let spam_contract = H160::from_low_u64_be(2);
+
+        //Responses are returned last in first out, the code of the spam contract is empty so it is not called
+        mock.push(Bytes::new())?;
+        mock.push(Bytes::from(encode(&[Token::Uint(U256::from(5))])))?;
+        mock.push(Bytes::from(vec![0x60, 0x80]))?;
+
+        let factories = verify_factories(
+            vec![
+                Factory::UniswapV2Factory(UniswapV2Factory::new(factory, 0, 300)),
+                Factory::UniswapV2Factory(UniswapV2Factory::new(spam_contract, 0, 300)),
+            ],
+            Arc::new(provider),
+        )
+        .await?;
+
+        assert_eq!(factories.len(), 1);
+        assert_eq!(factories[0].address(), factory);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_known_factories() {
+        let known_factories =
+            DiscoverableFactory::PancakeSwapV3Factory.known_factories(Chain::Mainnet);
+        let address = known_factories[0].address();
+
+        //The logs of the PancakeSwap V3 factory identify it as a Uniswap V3 factory
+        let discovered_factories = vec![
+            Factory::UniswapV3Factory(UniswapV3Factory::new(address, 16950686)),
+            Factory::UniswapV3Factory(UniswapV3Factory::new(H160::from_low_u64_be(1), 100)),
+        ];
+
+        let factories = merge_known_factories(discovered_factories, known_factories);
+        assert_eq!(factories.len(), 2);
+        assert_eq!(factories[0].address(), address);
+        assert_eq!(factories[0].creation_block(), 16950686);
+
+        if let [Factory::UniswapV2Factory(factory)] = &merge_known_factories(
+            vec![],
+            DiscoverableFactory::PancakeSwapV2Factory.known_factories(Chain::BinanceSmartChain),
+        )[..]
+        {
+            assert_eq!(factory.fee, PANCAKESWAP_V2_FEE);
+        } else {
+            panic!("expected the PancakeSwap V2 factory");
+        }
+    }
+
+    #[test]
+    fn test_percent_complete() {
+        let mut progress = DiscoveryProgress {
+            start_block: 100,
+            end_block: 199,
+            range_start_block: 100,
+            last_scanned_block: 124,
+            ..Default::default()
+        };
+        assert_eq!(progress.percent_complete(), 25.0);
+
+        progress.last_scanned_block = 199;
+        assert_eq!(progress.percent_complete(), 100.0);
+
+        //A discovery of a single block is complete once the block is scanned
+        assert_eq!(DiscoveryProgress::default().percent_complete(), 100.0);
+    }
+
+    #[tokio::test]
+    async fn test_discover_factories_from_checkpoint() -> eyre::Result<()> {
+        let checkpoint_path = std::env::temp_dir().join("amms_discovery_checkpoint_test.json");
+        let checkpoint_path = checkpoint_path.to_str().unwrap_or_default();
+
+        let address = H160::from_low_u64_be(1);
+        let mut identified_factories = HashMap::new();
+        process_discovery_logs::<Provider<Http>>(
+            vec![Log {
+                address,
+                topics: vec![PAIR_CREATED_EVENT_SIGNATURE],
+                block_number: Some(U64::from(100)),
+                ..Default::default()
+            }],
+            &HashMap::new(),
+            &mut identified_factories,
+        )?;
+
+        let progress = DiscoveryProgress {
+            last_scanned_block: 499,
+            identified_factories,
+            ..Default::default()
+        };
+        construct_discovery_checkpoint(&progress, checkpoint_path)?;
+
+        //Only the blocks after the checkpoint are scanned, the logs found before are kept
+        let (provider, mock) = Provider::mocked();
+        mock.push(vec![Log {
+            address,
+            topics: vec![PAIR_CREATED_EVENT_SIGNATURE],
+            block_number: Some(U64::from(700)),
+            ..Default::default()
+        }])?;
+        mock.push(U64::from(1000))?;
+
+        let factories = discover_factories_from_checkpoint(
+            vec![DiscoverableFactory::UniswapV2Factory],
+            2,
+            Arc::new(provider),
+            1000,
+            checkpoint_path,
+        )
+        .await?;
+        assert_eq!(factories.len(), 1);
+        assert_eq!(factories[0].creation_block(), 100);
+
+        let checkpoint = deconstruct_discovery_checkpoint(checkpoint_path)?;
+        assert_eq!(checkpoint.last_scanned_block, 1000);
+        assert_eq!(checkpoint.identified_factories[&address].1, 2);
+
+        //A partially written checkpoint is an error rather than a restart from genesis
+        std::fs::write(checkpoint_path, "{\"timestamp\": 1, \"last_scanned")?;
+        let (provider, _mock) = Provider::mocked();
+        let result = discover_factories_from_checkpoint(
+            vec![DiscoverableFactory::UniswapV2Factory],
+            2,
+            Arc::new(provider),
+            1000,
+            checkpoint_path,
+        )
+        .await;
+        assert!(matches!(result, Err(AMMError::CheckpointError(_))));
+
+        std::fs::remove_file(checkpoint_path)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invalid_block_range() -> eyre::Result<()> {
+        //The range is validated before any request is sent
+        let middleware = Arc::new(Provider::<Http>::try_from("http://localhost:8545")?);
+
+        let result = resume_factory_discovery(
+            vec![DiscoverableFactory::UniswapV2Factory],
+            0,
+            middleware,
+            1000,
+            Some(10),
+            Some(5),
+            HashMap::new(),
+            None,
+        )
+        .await;
+
+        assert!(matches!(result, Err(AMMError::InvalidBlockRange(10, 5))));
+
+        Ok(())
+    }
 }
@@ -1,2 +1,27 @@
+pub mod chain;
 pub mod erc_4626;
 pub mod factory;
+pub mod pancakeswap;
+pub mod pools;
+pub mod sushiswap;
+pub mod velodrome;
+
+use std::str::FromStr;
+
+use ethers::types::{Chain, H160};
+
+//Address and creation block of the factories of a registry that are deployed on the chain
+fn factories_on_chain(
+    factories: &'static [(Chain, &'static str, u64)],
+    chain: Chain,
+) -> impl Iterator<Item = (H160, u64)> {
+    factories
+        .iter()
+        .filter(move |(factory_chain, _, _)| *factory_chain == chain)
+        .map(|(_, address, creation_block)| {
+            (
+                H160::from_str(address).expect("invalid factory address"),
+                *creation_block,
+            )
+        })
+}
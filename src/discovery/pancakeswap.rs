@@ -0,0 +1,102 @@
+use ethers::types::Chain;
+
+use crate::amm::{
+    factory::Factory, uniswap_v2::factory::UniswapV2Factory, uniswap_v3::factory::UniswapV3Factory,
+};
+
+use super::factories_on_chain;
+
+//PancakeSwap V2 pairs charge 0.25% instead of the 0.3% of Uniswap V2 pairs
+pub const PANCAKESWAP_V2_FEE: u32 = 250;
+
+//Fee tiers of PancakeSwap V3 pools in hundredths of a basis point, Uniswap V3 has a 0.3% tier instead of the 0.25% tier
+pub const PANCAKESWAP_V3_FEE_TIERS: [u32; 4] = [100, 500, 2500, 10000];
+
+//(chain, factory address, creation block), the creation block is 0 where it is not known so that the factory is synced from genesis
+const PANCAKESWAP_V2_FACTORIES: &[(Chain, &str, u64)] = &[
+    (
+        Chain::BinanceSmartChain,
+        "0xcA143Ce32Fe78f1f7019d7d551a6402fC5350c73",
+        6809737,
+    ),
+    (
+        Chain::Mainnet,
+        "0x1097053Fd2ea711dad45caCcc45EfF7548fCB362",
+        0,
+    ),
+    (
+        Chain::Arbitrum,
+        "0x02a84c1b3BBD7401a5f7fa98a384EBC70bB5749E",
+        0,
+    ),
+    (Chain::Base, "0x02a84c1b3BBD7401a5f7fa98a384EBC70bB5749E", 0),
+];
+
+//PancakeSwap V3 pools emit the same PoolCreated event as Uniswap V3 pools, so the factories can only be told apart by their address
+const PANCAKESWAP_V3_FACTORIES: &[(Chain, &str, u64)] = &[
+    (
+        Chain::BinanceSmartChain,
+        "0x0BFbCF9fa4f9C56B0F40a671Ad40E0805A091865",
+        0,
+    ),
+    (
+        Chain::Mainnet,
+        "0x0BFbCF9fa4f9C56B0F40a671Ad40E0805A091865",
+        0,
+    ),
+    (
+        Chain::Arbitrum,
+        "0x0BFbCF9fa4f9C56B0F40a671Ad40E0805A091865",
+        0,
+    ),
+    (Chain::Base, "0x0BFbCF9fa4f9C56B0F40a671Ad40E0805A091865", 0),
+];
+
+pub fn pancakeswap_v2_factories(chain: Chain) -> Vec<Factory> {
+    factories_on_chain(PANCAKESWAP_V2_FACTORIES, chain)
+        .map(|(address, creation_block)| {
+            Factory::UniswapV2Factory(UniswapV2Factory::new(
+                address,
+                creation_block,
+                PANCAKESWAP_V2_FEE,
+            ))
+        })
+        .collect()
+}
+
+pub fn pancakeswap_v3_factories(chain: Chain) -> Vec<Factory> {
+    factories_on_chain(PANCAKESWAP_V3_FACTORIES, chain)
+        .map(|(address, creation_block)| {
+            Factory::UniswapV3Factory(UniswapV3Factory::new(address, creation_block))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::Chain;
+
+    use crate::amm::factory::{AutomatedMarketMakerFactory, Factory};
+
+    use super::{pancakeswap_v2_factories, pancakeswap_v3_factories, PANCAKESWAP_V2_FEE};
+
+    #[test]
+    fn test_pancakeswap_factories() {
+        let v2_factories = pancakeswap_v2_factories(Chain::BinanceSmartChain);
+        assert_eq!(v2_factories.len(), 1);
+        assert_eq!(v2_factories[0].creation_block(), 6809737);
+
+        if let Factory::UniswapV2Factory(factory) = &v2_factories[0] {
+            assert_eq!(factory.fee, PANCAKESWAP_V2_FEE);
+        } else {
+            panic!("expected a Uniswap V2 factory");
+        }
+
+        assert!(matches!(
+            pancakeswap_v3_factories(Chain::Mainnet)[..],
+            [Factory::UniswapV3Factory(_)]
+        ));
+
+        assert!(pancakeswap_v3_factories(Chain::Goerli).is_empty());
+    }
+}
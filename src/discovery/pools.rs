@@ -0,0 +1,316 @@
+use std::{collections::HashMap, sync::Arc};
+
+use ethers::{
+    providers::{Middleware, PubsubClient, StreamExt},
+    types::{Filter, H160, H256},
+};
+use futures::{channel::mpsc, Stream};
+use tokio::sync::oneshot;
+
+use crate::{
+    amm::{
+        factory::{AutomatedMarketMakerFactory, Factory},
+        AutomatedMarketMaker, AMM,
+    },
+    errors::{AMMError, EventLogError},
+    middleware::logs::{get_logs_with_adaptive_range, MAX_LOG_RANGE_SPLITS},
+    sync,
+};
+
+//Fee of the pairs found by their PairCreated logs, the log does not tell which fork created the pair
+pub const DISCOVERED_V2_POOL_FEE: u32 = 300;
+
+//Empty AMMs found by discover_pools, ordered by creation block. `amms` can be passed to populate_amm_data as is.
+#[derive(Debug, Clone, Default)]
+pub struct DiscoveredPools {
+    pub amms: Vec<AMM>,
+    pub creation_blocks: HashMap<H160, u64>,
+}
+
+// Returns every pool created in [from_block, to_block] by a log with one of the creation event `signatures`, regardless of the factory that emitted it.
+// Pools are parsed from their creation logs only and have to be populated before they can be simulated. A pool that is found in more than one
+// range is returned once with the block of its earliest log. Signatures that no factory emits return an InvalidEventSignature error.
+pub async fn discover_pools<M: Middleware>(
+    signatures: Vec<H256>,
+    from_block: u64,
+    to_block: u64,
+    middleware: Arc<M>,
+    step: u64,
+) -> Result<DiscoveredPools, AMMError<M>> {
+    if step == 0 {
+        return Err(AMMError::ZeroBatchSize("step"));
+    }
+
+    if from_block > to_block {
+        return Err(AMMError::InvalidBlockRange(from_block, to_block));
+    }
+
+    let mut factories = HashMap::new();
+    for signature in signatures.iter() {
+        factories.insert(*signature, discovery_factory(*signature)?);
+    }
+
+    let filter = Filter::new().topic0(signatures);
+    let mut pools: HashMap<H160, (AMM, u64)> = HashMap::new();
+
+    let mut range_start = from_block;
+    while range_start <= to_block {
+        let range_end = range_start.saturating_add(step - 1).min(to_block);
+        tracing::info!("searching blocks {}-{}", range_start, range_end);
+
+        let logs = get_logs_with_adaptive_range(
+            middleware.as_ref(),
+            &filter,
+            range_start,
+            range_end,
+            MAX_LOG_RANGE_SPLITS,
+        )
+        .await
+        .map_err(AMMError::MiddlewareError)?;
+
+        for log in logs {
+            let Some(factory) = log.topics.first().and_then(|topic| factories.get(topic)) else {
+                continue;
+            };
+            let creation_block = log
+                .block_number
+                .ok_or(EventLogError::LogBlockNumberNotFound)?
+                .as_u64();
+
+            //Contracts that emit a creation event with different parameters are not factories
+            let amm = match factory.new_empty_amm_from_log(log) {
+                Ok(amm) => amm,
+                Err(err) => {
+                    tracing::trace!(?err, "skipping creation log that could not be decoded");
+                    continue;
+                }
+            };
+
+            pools
+                .entry(amm.address())
+                .and_modify(|(_, block)| *block = (*block).min(creation_block))
+                .or_insert((amm, creation_block));
+        }
+
+        range_start = range_end + 1;
+    }
+
+    let mut pools = pools.into_values().collect::<Vec<(AMM, u64)>>();
+    pools.sort_by_key(|(amm, creation_block)| (*creation_block, amm.address()));
+
+    let creation_blocks = pools
+        .iter()
+        .map(|(amm, creation_block)| (amm.address(), *creation_block))
+        .collect();
+
+    Ok(DiscoveredPools {
+        amms: pools.into_iter().map(|(amm, _)| amm).collect(),
+        creation_blocks,
+    })
+}
+
+//Pools of a known factory read from its pool array instead of its creation logs, e.g. when the provider only keeps logs for a
+//short window. The pool addresses are read in Multicall3 requests of `chunk_size` calls and populated at the same block, pools that
+//could not be populated are removed. Factories without a pool array return PoolEnumerationNotSupported.
+pub async fn discover_pools_from_factory<M: Middleware>(
+    factory: &Factory,
+    middleware: Arc<M>,
+    chunk_size: usize,
+) -> Result<Vec<AMM>, AMMError<M>> {
+    let amms = factory
+        .get_all_pools_via_multicall_with_chunk_size(middleware, None, chunk_size)
+        .await?;
+
+    Ok(sync::remove_empty_amms(amms))
+}
+
+//Streaming counterpart of discover_pools_from_factory, subscribes to the creation logs of the factory and yields each new pool
+//populated at the latest block. Pools that fail to populate are yielded as errors and the stream keeps going. Creation logs removed by
+//a reorg are skipped. The subscription is dropped when the stream is dropped.
+pub async fn new_pool_stream<M>(
+    factory: &Factory,
+    middleware: Arc<M>,
+) -> Result<impl Stream<Item = Result<AMM, AMMError<M>>>, AMMError<M>>
+where
+    M: 'static + Middleware,
+    M::Provider: PubsubClient,
+{
+    let factory = factory.clone();
+    let filter = new_pool_filter(&factory);
+    let (pool_tx, pool_rx) = mpsc::unbounded();
+    let (subscribed_tx, subscribed_rx) = oneshot::channel();
+
+    //The subscription borrows the provider of the middleware, so it lives in a task that owns the middleware
+    let handle = tokio::spawn(async move {
+        let mut log_stream = match middleware.subscribe_logs(&filter).await {
+            Ok(log_stream) => {
+                let _ = subscribed_tx.send(Ok(()));
+                log_stream
+            }
+            Err(error) => {
+                let _ = subscribed_tx.send(Err(AMMError::MiddlewareError(error)));
+                return;
+            }
+        };
+
+        while let Some(log) = log_stream.next().await {
+            if log.removed == Some(true) {
+                continue;
+            }
+
+            let amm = factory.new_amm_from_log(log, middleware.clone()).await;
+            if pool_tx.unbounded_send(amm).is_err() {
+                break;
+            }
+        }
+    });
+
+    match subscribed_rx.await {
+        Ok(subscribed) => subscribed?,
+        //The task only drops the sender without sending when it panics
+        Err(_) => {
+            return Err(handle
+                .await
+                .expect_err("task ended before subscribing")
+                .into())
+        }
+    }
+
+    Ok(pool_rx)
+}
+
+fn new_pool_filter(factory: &Factory) -> Filter {
+    Filter::new()
+        .address(factory.address())
+        .topic0(factory.amm_created_event_signature())
+}
+
+//Factory that parses the creation logs with the signature into AMMs
+fn discovery_factory<M: Middleware>(signature: H256) -> Result<Factory, AMMError<M>> {
+    let mut factory = Factory::try_from(signature)?;
+    if let Factory::UniswapV2Factory(factory) = &mut factory {
+        factory.fee = DISCOVERED_V2_POOL_FEE;
+    }
+
+    Ok(factory)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ethers::{
+        abi::{encode, Token},
+        providers::Provider,
+        types::{Bytes, Log, ValueOrArray, H160, H256, U256, U64},
+    };
+
+    use crate::{
+        amm::{
+            factory::Factory,
+            uniswap_v2::factory::PAIR_CREATED_EVENT_SIGNATURE,
+            uniswap_v3::factory::{UniswapV3Factory, POOL_CREATED_EVENT_SIGNATURE},
+            AutomatedMarketMaker, AMM,
+        },
+        errors::AMMError,
+    };
+
+    use super::{
+        discover_pools, discover_pools_from_factory, new_pool_filter, DISCOVERED_V2_POOL_FEE,
+    };
+
+    #[tokio::test]
+    async fn test_discover_pools() -> eyre::Result<()> {
+        let (token_a, token_b) = (H160::from_low_u64_be(0xa), H160::from_low_u64_be(0xb));
+        let (v2_pool, v3_pool) = (H160::from_low_u64_be(2), H160::from_low_u64_be(3));
+
+        let pair_created_log = |block_number: u64| Log {
+            address: H160::from_low_u64_be(1),
+            topics: vec![
+                PAIR_CREATED_EVENT_SIGNATURE,
+                H256::from(token_a),
+                H256::from(token_b),
+            ],
+            data: Bytes::from(encode(&[Token::Address(v2_pool), Token::Uint(U256::one())])),
+            block_number: Some(U64::from(block_number)),
+            ..Default::default()
+        };
+        let pool_created_log = Log {
+            address: H160::from_low_u64_be(1),
+            topics: vec![
+                POOL_CREATED_EVENT_SIGNATURE,
+                H256::from(token_a),
+                H256::from(token_b),
+                H256::from_low_u64_be(500),
+            ],
+            data: Bytes::from(encode(&[
+                Token::Int(U256::from(10)),
+                Token::Address(v3_pool),
+            ])),
+            block_number: Some(U64::from(130)),
+            ..Default::default()
+        };
+
+        //Responses are returned last in first out, the second range finds the V2 pair again
+        let (provider, mock) = Provider::mocked();
+        mock.push(vec![pair_created_log(180)])?;
+        mock.push(vec![pair_created_log(120), pool_created_log.clone()])?;
+
+        let pools = discover_pools(
+            vec![PAIR_CREATED_EVENT_SIGNATURE, POOL_CREATED_EVENT_SIGNATURE],
+            100,
+            199,
+            Arc::new(provider),
+            50,
+        )
+        .await?;
+
+        assert_eq!(pools.amms.len(), 2);
+        assert_eq!(pools.amms[0].address(), v2_pool);
+        assert_eq!(pools.creation_blocks[&v2_pool], 120);
+        assert_eq!(pools.creation_blocks[&v3_pool], 130);
+
+        match &pools.amms[0] {
+            AMM::UniswapV2Pool(pool) => assert_eq!(pool.fee, DISCOVERED_V2_POOL_FEE),
+            _ => panic!("expected a Uniswap V2 pool"),
+        }
+        match &pools.amms[1] {
+            AMM::UniswapV3Pool(pool) => {
+                assert_eq!((pool.token_a, pool.token_b), (token_a, token_b));
+                assert_eq!(pool.fee, 500);
+            }
+            _ => panic!("expected a Uniswap V3 pool"),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_discover_pools_from_uniswap_v3_factory() -> eyre::Result<()> {
+        //Uniswap V3 factories keep their pools in a mapping that can not be enumerated
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(100))?;
+
+        let factory = Factory::UniswapV3Factory(UniswapV3Factory::new(H160::from_low_u64_be(1), 0));
+        assert!(matches!(
+            discover_pools_from_factory(&factory, Arc::new(provider), 100).await,
+            Err(AMMError::PoolEnumerationNotSupported(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_new_pool_filter() {
+        let address = H160::from_low_u64_be(1);
+        let filter = new_pool_filter(&Factory::UniswapV3Factory(UniswapV3Factory::new(
+            address, 0,
+        )));
+
+        assert_eq!(filter.address, Some(ValueOrArray::Value(address)));
+        assert_eq!(
+            filter.topics[0],
+            Some(ValueOrArray::Value(Some(POOL_CREATED_EVENT_SIGNATURE)))
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use ethers::types::Chain;
+
+use crate::amm::{
+    factory::Factory, uniswap_v2::factory::UniswapV2Factory, uniswap_v3::factory::UniswapV3Factory,
+};
+
+use super::factories_on_chain;
+
+//SushiSwap V2 pairs charge the same 0.3% fee as Uniswap V2 pairs
+pub const SUSHISWAP_V2_FEE: u32 = 300;
+
+//(chain, factory address, creation block), the creation block is 0 where it is not known so that the factory is synced from genesis
+const SUSHISWAP_V2_FACTORIES: &[(Chain, &str, u64)] = &[
+    (
+        Chain::Mainnet,
+        "0xC0AEe478e3658e2610c5F7A4A2E1777cE9e4f2Ac",
+        10794229,
+    ),
+    (
+        Chain::Arbitrum,
+        "0xc35DADB65012eC5796536bD9864eD8773aBc74C4",
+        70,
+    ),
+    (
+        Chain::Polygon,
+        "0xc35DADB65012eC5796536bD9864eD8773aBc74C4",
+        11333218,
+    ),
+    (
+        Chain::Optimism,
+        "0xFbc12984689e5f15626Bad03Ad60160Fe98B303C",
+        0,
+    ),
+    (Chain::Base, "0x71524B4f93c58fcbF659783284E38825f0622859", 0),
+    (
+        Chain::BinanceSmartChain,
+        "0xc35DADB65012eC5796536bD9864eD8773aBc74C4",
+        0,
+    ),
+    (
+        Chain::Avalanche,
+        "0xc35DADB65012eC5796536bD9864eD8773aBc74C4",
+        0,
+    ),
+];
+
+const SUSHISWAP_V3_FACTORIES: &[(Chain, &str, u64)] = &[
+    (
+        Chain::Mainnet,
+        "0xbACEB8eC6b9355Dfc0269C18bac9d6E2Bdc29C4F",
+        16955547,
+    ),
+    (
+        Chain::Arbitrum,
+        "0x1af415a1EbA07a4986a52B6f2e7dE7003D82231e",
+        0,
+    ),
+    (
+        Chain::Polygon,
+        "0x917933899c6a5F8E37F31E19f92CdBFF7e8FF0e2",
+        0,
+    ),
+    (
+        Chain::Optimism,
+        "0x9c6522117e2ed1fE5bdb72bb0eD5E3f2bdE7DBe0",
+        0,
+    ),
+    (Chain::Base, "0xc35DADB65012eC5796536bD9864eD8773aBc74C4", 0),
+    (
+        Chain::BinanceSmartChain,
+        "0x126555dd55a39328F69400d6aE4F782Bd4C34ABb",
+        0,
+    ),
+    (
+        Chain::Avalanche,
+        "0x3e603C14aF37EBdaD31709C4f848Fc6aD5BEc715",
+        0,
+    ),
+];
+
+pub fn sushiswap_v2_factories(chain: Chain) -> Vec<Factory> {
+    factories_on_chain(SUSHISWAP_V2_FACTORIES, chain)
+        .map(|(address, creation_block)| {
+            Factory::UniswapV2Factory(UniswapV2Factory::new(
+                address,
+                creation_block,
+                SUSHISWAP_V2_FEE,
+            ))
+        })
+        .collect()
+}
+
+pub fn sushiswap_v3_factories(chain: Chain) -> Vec<Factory> {
+    factories_on_chain(SUSHISWAP_V3_FACTORIES, chain)
+        .map(|(address, creation_block)| {
+            Factory::UniswapV3Factory(UniswapV3Factory::new(address, creation_block))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::Chain;
+
+    use crate::amm::factory::{AutomatedMarketMakerFactory, Factory};
+
+    use super::{sushiswap_v2_factories, sushiswap_v3_factories, SUSHISWAP_V2_FEE};
+
+    #[test]
+    fn test_sushiswap_factories() {
+        let v2_factories = sushiswap_v2_factories(Chain::Mainnet);
+        assert_eq!(v2_factories.len(), 1);
+        assert_eq!(v2_factories[0].creation_block(), 10794229);
+
+        if let Factory::UniswapV2Factory(factory) = &v2_factories[0] {
+            assert_eq!(factory.fee, SUSHISWAP_V2_FEE);
+        } else {
+            panic!("expected a Uniswap V2 factory");
+        }
+
+        assert!(matches!(
+            sushiswap_v3_factories(Chain::Arbitrum)[..],
+            [Factory::UniswapV3Factory(_)]
+        ));
+
+        assert!(sushiswap_v2_factories(Chain::Goerli).is_empty());
+    }
+}
@@ -0,0 +1,64 @@
+use ethers::types::Chain;
+
+use crate::amm::{factory::Factory, solidly::factory::SolidlyFactory};
+
+use super::factories_on_chain;
+
+//Velodrome V2 and Aerodrome pools are Solidly pools, volatile pools use x * y = k and stable pools x^3 * y + x * y^3 = k.
+//Their factories emit PoolCreated and charge a fee per pool, see SolidlyFactoryVersion::V2.
+
+//(chain, factory address, creation block), the creation block is 0 where it is not known so that the factory is synced from genesis
+const VELODROME_FACTORIES: &[(Chain, &str, u64)] = &[(
+    Chain::Optimism,
+    "0xF1046053aa5682b4F9a81b5481394DA16BE5FF5a",
+    0,
+)];
+
+const AERODROME_FACTORIES: &[(Chain, &str, u64)] =
+    &[(Chain::Base, "0x420DD381b31aEf6683db6B902084cB0FFECe40Da", 0)];
+
+pub fn velodrome_factories(chain: Chain) -> Vec<Factory> {
+    factories_on_chain(VELODROME_FACTORIES, chain)
+        .map(|(address, creation_block)| {
+            Factory::SolidlyFactory(SolidlyFactory::new_v2(address, creation_block))
+        })
+        .collect()
+}
+
+pub fn aerodrome_factories(chain: Chain) -> Vec<Factory> {
+    factories_on_chain(AERODROME_FACTORIES, chain)
+        .map(|(address, creation_block)| {
+            Factory::SolidlyFactory(SolidlyFactory::new_v2(address, creation_block))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::Chain;
+
+    use crate::amm::{
+        factory::{AutomatedMarketMakerFactory, Factory},
+        solidly::factory::{SolidlyFactoryVersion, POOL_CREATED_EVENT_SIGNATURE},
+    };
+
+    use super::{aerodrome_factories, velodrome_factories};
+
+    #[test]
+    fn test_velodrome_factories() {
+        match &velodrome_factories(Chain::Optimism)[..] {
+            [Factory::SolidlyFactory(factory)] => {
+                assert_eq!(factory.version, SolidlyFactoryVersion::V2);
+                assert_eq!(
+                    factory.amm_created_event_signature(),
+                    POOL_CREATED_EVENT_SIGNATURE
+                );
+            }
+            _ => panic!("expected the Velodrome V2 factory"),
+        }
+
+        assert_eq!(aerodrome_factories(Chain::Base).len(), 1);
+        assert!(velodrome_factories(Chain::Base).is_empty());
+        assert!(aerodrome_factories(Chain::Mainnet).is_empty());
+    }
+}
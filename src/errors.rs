@@ -1,3 +1,4 @@
+use ethers::contract::MulticallError;
 use ethers::prelude::{AbiError, ContractError};
 use ethers::providers::{Middleware, ProviderError};
 use ethers::types::{H160, U256};
@@ -59,6 +60,14 @@ where
     BatchRequestError(H160),
     #[error("Checkpoint error")]
     CheckpointError(#[from] CheckpointError),
+    #[error("Multicall error")]
+    MulticallError(#[from] MulticallError<M>),
+    #[error("Factory does not expose its pools by index")]
+    PoolEnumerationNotSupported(H160),
+    #[error("Invalid block range, from block {0} is after to block {1}")]
+    InvalidBlockRange(u64, u64),
+    #[error("{0} must be greater than zero")]
+    ZeroBatchSize(&'static str),
 }
 
 #[derive(Error, Debug)]
@@ -75,6 +84,26 @@ pub enum ArithmeticError {
     U128ConversionError,
     #[error("Uniswap v3 math error")]
     UniswapV3MathError(#[from] UniswapV3MathError),
+    #[error("Token is not in pool")]
+    TokenNotInPool(H160),
+    #[error("Token index is out of bounds")]
+    InvalidTokenIndex,
+    #[error("Pow underflow")]
+    PowUnderflow,
+    #[error("Price can not be represented as a 64.64 fixed point number")]
+    InvalidPrice,
+    #[error("Lower price is above the upper price")]
+    InvalidPriceRange,
+    #[error("Liquidity in a price range can not be computed for this AMM")]
+    LiquidityInRangeNotSupported,
+    #[error("Virtual reserves can not be computed for this AMM")]
+    VirtualReservesNotSupported,
+    #[error("Market depth can not be computed for this AMM")]
+    MarketDepthNotSupported,
+    #[error("AMM {0:?} has no liquidity")]
+    ZeroLiquidity(H160),
+    #[error("Tokens with {0} decimals can not be normalized to 18 decimals")]
+    UnsupportedDecimals(u8),
 }
 
 #[derive(Error, Debug)]
@@ -87,6 +116,8 @@ pub enum EventLogError {
     EthABIError(#[from] ethers::abi::Error),
     #[error("ABI error")]
     ABIError(#[from] AbiError),
+    #[error("Swap simulation error")]
+    SwapSimulationError(#[from] SwapSimulationError),
 }
 
 #[derive(Error, Debug)]
@@ -97,6 +128,38 @@ pub enum SwapSimulationError {
     UniswapV3MathError(#[from] UniswapV3MathError),
     #[error("Liquidity underflow")]
     LiquidityUnderflow,
+    #[error("Token is not in pool")]
+    TokenNotInPool(H160),
+    #[error("Amount in exceeds max in ratio")]
+    MaxInRatioExceeded,
+    #[error("Newton's method did not converge")]
+    DidNotConverge,
+    #[error("Arithmetic error")]
+    ArithmeticError(#[from] ArithmeticError),
+    #[error("Swaps through bins of kind {0} can not be simulated")]
+    UnsupportedBinKind(u8),
+    #[error("Swap path must have one more token than the number of AMMs")]
+    InvalidPathLength,
+    #[error("AMM does not swap {0:?} for {1:?}")]
+    InvalidHop(H160, H160),
+    #[error("Tick bitmap word {0} is not cached")]
+    TickWordNotCached(i16),
+    #[error("Amount in is zero")]
+    ZeroAmountIn,
+    //`available` is the largest amount in that the pool can swap before its reserves are exhausted
+    #[error("Amount in {requested} exceeds the {available} that {pool:?} can swap")]
+    InsufficientLiquidity {
+        pool: H160,
+        requested: U256,
+        available: U256,
+    },
+    //`available` is the largest amount out that the pool can swap, as found by get_amount_in
+    #[error("Amount out {requested} exceeds the {available} that {pool:?} can swap")]
+    InsufficientOutput {
+        pool: H160,
+        requested: U256,
+        available: U256,
+    },
 }
 
 #[derive(Error, Debug)]
@@ -108,3 +171,35 @@ pub enum CheckpointError {
     #[error("IO error")]
     IOError(#[from] std::io::Error),
 }
+
+#[derive(Error, Debug)]
+pub enum TokenListError {
+    #[error("HTTP error")]
+    HTTPError(#[from] reqwest::Error),
+    #[error("Serde json error")]
+    SerdeJsonError(#[from] serde_json::error::Error),
+}
+
+#[cfg(feature = "bincode")]
+#[derive(Error, Debug)]
+pub enum SnapshotError {
+    #[error("Bincode error")]
+    BincodeError(#[from] bincode::Error),
+    #[error("IO error")]
+    IOError(#[from] std::io::Error),
+    #[error("File is not a pool state snapshot")]
+    InvalidSnapshot,
+    #[error("Snapshot record version {0} is not supported")]
+    UnsupportedVersion(u16),
+}
+
+#[cfg(feature = "postgres")]
+#[derive(Error, Debug)]
+pub enum StorageError {
+    #[error("Sqlx error")]
+    SqlxError(#[from] sqlx::Error),
+    #[error("Migration error")]
+    MigrateError(#[from] sqlx::migrate::MigrateError),
+    #[error("Serde json error")]
+    SerdeJsonError(#[from] serde_json::error::Error),
+}
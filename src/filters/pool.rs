@@ -0,0 +1,240 @@
+use std::{collections::HashSet, sync::Arc};
+
+use ethers::types::{H160, H256, U256};
+
+use crate::amm::{
+    pool_address::{
+        compute_v2_pair_address, compute_v3_pool_address, PANCAKESWAP_V2_INIT_CODE_HASH,
+        UNISWAP_V2_INIT_CODE_HASH, UNISWAP_V3_POOL_INIT_CODE_HASH,
+    },
+    AutomatedMarketMaker, AMM,
+};
+
+//Value of one whole token, i.e. 10^decimals of its smallest unit, in the unit of the min TVL. Tokens without a price are worth nothing.
+pub type TokenPriceOracle = Arc<dyn Fn(H160) -> Option<U256> + Send + Sync>;
+
+const V2_INIT_CODE_HASHES: [H256; 2] = [UNISWAP_V2_INIT_CODE_HASH, PANCAKESWAP_V2_INIT_CODE_HASH];
+
+//Narrows down a set of AMMs, e.g. the pools of the discovered factories. An AMM is kept if it contains every token,
+//has one of the fees and was deployed by one of the factories that were added, and if its TVL is at least the min TVL.
+#[derive(Clone, Default)]
+pub struct PoolFilter {
+    pub tokens: Vec<H160>,
+    pub fees: HashSet<u32>,
+    pub factories: HashSet<H160>,
+    pub min_tvl: Option<U256>,
+    pub price_oracle: Option<TokenPriceOracle>,
+}
+
+impl PoolFilter {
+    pub fn new() -> PoolFilter {
+        PoolFilter::default()
+    }
+
+    pub fn with_token(mut self, token: H160) -> PoolFilter {
+        self.tokens.push(token);
+        self
+    }
+
+    //The fee in hundredths of a basis point, see AutomatedMarketMaker::fee
+    pub fn with_fee(mut self, fee: u32) -> PoolFilter {
+        self.fees.insert(fee);
+        self
+    }
+
+    //Pools are matched to their factory by their CREATE2 address, which is only known for the Uniswap V2, PancakeSwap V2 and
+    //Uniswap V3 init code hashes. Pools of other factories are never kept once a factory is added.
+    pub fn with_factory(mut self, factory: H160) -> PoolFilter {
+        self.factories.insert(factory);
+        self
+    }
+
+    //The TVL is the sum of the reserves of the AMM valued with the price oracle. AMMs without reserves to value, e.g. Uniswap V3 pools,
+    //do not reach any min TVL above zero.
+    pub fn with_min_tvl(mut self, min_tvl: U256) -> PoolFilter {
+        self.min_tvl = Some(min_tvl);
+        self
+    }
+
+    pub fn with_price_oracle(
+        mut self,
+        price_oracle: impl Fn(H160) -> Option<U256> + Send + Sync + 'static,
+    ) -> PoolFilter {
+        self.price_oracle = Some(Arc::new(price_oracle));
+        self
+    }
+
+    pub fn apply(&self, pools: Vec<AMM>) -> Vec<AMM> {
+        pools
+            .into_iter()
+            .filter(|pool| self.matches(pool))
+            .collect()
+    }
+
+    pub fn matches(&self, pool: &AMM) -> bool {
+        let tokens = pool.tokens();
+        if !self.tokens.iter().all(|token| tokens.contains(token)) {
+            return false;
+        }
+
+        if !self.fees.is_empty() && !self.fees.contains(&pool.fee()) {
+            return false;
+        }
+
+        if !self.factories.is_empty()
+            && !self
+                .factories
+                .iter()
+                .any(|factory| is_deployed_by(pool, *factory))
+        {
+            return false;
+        }
+
+        match self.min_tvl {
+            Some(min_tvl) => self.estimate_tvl(pool) >= min_tvl,
+            None => true,
+        }
+    }
+
+    //Sum of reserve * price / 10^decimals over the tokens of the AMM
+    pub fn estimate_tvl(&self, pool: &AMM) -> U256 {
+        let (Some(price_oracle), Some(reserves)) = (&self.price_oracle, reserves(pool)) else {
+            return U256::zero();
+        };
+
+        pool.tokens()
+            .into_iter()
+            .zip(pool.token_decimals())
+            .zip(reserves)
+            .fold(U256::zero(), |tvl, ((token, decimals), reserve)| {
+                let value = price_oracle(token)
+                    .map(|price| reserve.full_mul(price) / U256::exp10(decimals as usize))
+                    .and_then(|value| U256::try_from(value).ok())
+                    .unwrap_or_default();
+
+                tvl.saturating_add(value)
+            })
+    }
+}
+
+//Reserves of the AMMs that hold their tokens in balances, in the same order as `tokens`
+fn reserves(pool: &AMM) -> Option<Vec<U256>> {
+    match pool {
+        AMM::UniswapV2Pool(pool) => {
+            Some(vec![U256::from(pool.reserve_0), U256::from(pool.reserve_1)])
+        }
+        AMM::SolidlyPool(pool) => Some(vec![pool.reserve_0, pool.reserve_1]),
+        AMM::CamelotPool(pool) => Some(vec![pool.reserve_0, pool.reserve_1]),
+        AMM::BalancerV2WeightedPool(pool) => Some(pool.balances.clone()),
+        AMM::CurveStableSwapPool(pool) => Some(pool.balances.clone()),
+        _ => None,
+    }
+}
+
+fn is_deployed_by(pool: &AMM, factory: H160) -> bool {
+    match pool {
+        AMM::UniswapV2Pool(pool) => V2_INIT_CODE_HASHES.iter().any(|init_code_hash| {
+            compute_v2_pair_address(factory, *init_code_hash, pool.token_a, pool.token_b)
+                == pool.address
+        }),
+        AMM::UniswapV3Pool(pool) => {
+            compute_v3_pool_address(
+                factory,
+                UNISWAP_V3_POOL_INIT_CODE_HASH,
+                pool.token_a,
+                pool.token_b,
+                pool.fee,
+            ) == pool.address
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use ethers::types::{H160, U256};
+
+    use crate::amm::{uniswap_v2::UniswapV2Pool, uniswap_v3::UniswapV3Pool, AMM};
+
+    use super::PoolFilter;
+
+    #[test]
+    fn test_pool_filter() -> eyre::Result<()> {
+        let uniswap_v2_factory = H160::from_str("0x5C69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f")?;
+        let weth = H160::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2")?;
+        let usdc = H160::from_str("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48")?;
+        let dai = H160::from_str("0x6B175474E89094C44Da98b946EeCD2cD5B0E26d9")?;
+
+        //The USDC/WETH pair of the Uniswap V2 factory with 1000 WETH and 2,000,000 USDC
+        let usdc_weth_pair = AMM::UniswapV2Pool(UniswapV2Pool {
+            address: H160::from_str("0xB4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc")?,
+            token_a: usdc,
+            token_a_decimals: 6,
+            token_b: weth,
+            token_b_decimals: 18,
+            reserve_0: 2_000_000 * 10_u128.pow(6),
+            reserve_1: 1000 * 10_u128.pow(18),
+            fee: 300,
+            ..Default::default()
+        });
+        let dai_weth_pool = AMM::UniswapV3Pool(UniswapV3Pool {
+            address: H160::from_low_u64_be(1),
+            token_a: dai,
+            token_b: weth,
+            fee: 500,
+            ..Default::default()
+        });
+        let pools = vec![usdc_weth_pair, dai_weth_pool];
+
+        assert_eq!(
+            PoolFilter::new()
+                .with_token(weth)
+                .apply(pools.clone())
+                .len(),
+            2
+        );
+        assert_eq!(
+            PoolFilter::new().with_token(dai).apply(pools.clone()).len(),
+            1
+        );
+        assert_eq!(
+            PoolFilter::new().with_fee(3000).apply(pools.clone()).len(),
+            1
+        );
+        assert_eq!(
+            PoolFilter::new()
+                .with_factory(uniswap_v2_factory)
+                .apply(pools.clone())
+                .len(),
+            1
+        );
+
+        //Prices in whole dollars, the pair holds $4,000,000
+        let filter = PoolFilter::new().with_price_oracle(move |token| {
+            if token == weth {
+                Some(U256::from(2000))
+            } else if token == usdc {
+                Some(U256::one())
+            } else {
+                None
+            }
+        });
+        assert_eq!(filter.estimate_tvl(&pools[0]), U256::from(4_000_000));
+
+        let filtered_pools = filter
+            .clone()
+            .with_min_tvl(U256::from(1_000_000))
+            .apply(pools.clone());
+        assert_eq!(filtered_pools.len(), 1);
+        assert!(matches!(filtered_pools[0], AMM::UniswapV2Pool(_)));
+
+        assert!(filter
+            .with_min_tvl(U256::from(5_000_000))
+            .apply(pools)
+            .is_empty());
+
+        Ok(())
+    }
+}
@@ -28,7 +28,7 @@ pub async fn filter_amms_below_usd_threshold<M: Middleware>(
 ) -> Result<Vec<AMM>, AMMError<M>> {
     tracing::info!("filtering AMMs below USD threshold");
 
-    let weth_usd_price = usd_weth_pool.calculate_price(weth)?;
+    let weth_usd_price = usd_weth_pool.calculate_price(weth)?.to_f64();
 
     //Init a new vec to hold the filtered AMMs
     let mut filtered_amms = vec![];
@@ -150,12 +150,20 @@ async fn get_weth_value_in_amm_batch_request<M: Middleware>(
         .map(|a| Token::Address(a.address()))
         .collect::<Vec<Token>>();
 
-    let factory_is_uni_v3 = factories
+    //The batch contract can only find token/weth pools through Uniswap V2 and V3 style factories
+    let factories = factories
         .iter()
-        .map(|d| match d {
-            Factory::UniswapV2Factory(_) => Token::Bool(false),
-            Factory::UniswapV3Factory(_) => Token::Bool(true),
+        .filter(|f| {
+            matches!(
+                f,
+                Factory::UniswapV2Factory(_) | Factory::UniswapV3Factory(_)
+            )
         })
+        .collect::<Vec<&Factory>>();
+
+    let factory_is_uni_v3 = factories
+        .iter()
+        .map(|f| Token::Bool(matches!(f, Factory::UniswapV3Factory(_))))
         .collect::<Vec<Token>>();
 
     let factories = factories
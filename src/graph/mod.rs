@@ -0,0 +1,156 @@
+use std::{collections::HashMap, sync::Arc};
+
+use ethers::types::H160;
+
+use crate::amm::{AutomatedMarketMaker, AMM};
+
+//Maps each token to the AMMs it can be swapped through. A swap of a token through an AMM goes to `get_token_out`,
+//so the paths found can be passed to `simulate_multihop_swap` as is.
+#[derive(Debug, Clone, Default)]
+pub struct TokenGraph {
+    pub adjacency: HashMap<H160, Vec<Arc<AMM>>>,
+}
+
+impl TokenGraph {
+    pub fn build(amms: &[AMM]) -> TokenGraph {
+        let mut adjacency: HashMap<H160, Vec<Arc<AMM>>> = HashMap::new();
+
+        for amm in amms {
+            let amm = Arc::new(amm.clone());
+            for token in amm.tokens() {
+                adjacency.entry(token).or_default().push(amm.clone());
+            }
+        }
+
+        TokenGraph { adjacency }
+    }
+
+    pub fn amms(&self, token: H160) -> &[Arc<AMM>] {
+        self.adjacency
+            .get(&token)
+            .map(|amms| amms.as_slice())
+            .unwrap_or_default()
+    }
+
+    //Every path of at most `max_hops` AMMs from `from` to `to` that does not go through a token or an AMM twice.
+    //If `from` and `to` are the same token the paths are cycles, i.e. arbitrage paths that start and end at `from`.
+    pub fn find_paths(&self, from: H160, to: H160, max_hops: usize) -> Vec<Vec<Arc<AMM>>> {
+        let mut paths = vec![];
+        let mut path = vec![];
+        let mut tokens = vec![from];
+
+        self.extend_paths(to, max_hops, &mut tokens, &mut path, &mut paths);
+
+        paths
+    }
+
+    //Token path of the AMMs of a path returned by find_paths
+    pub fn path_tokens(from: H160, path: &[Arc<AMM>]) -> Vec<H160> {
+        let mut tokens = vec![from];
+        for amm in path {
+            let token_in = tokens[tokens.len() - 1];
+            tokens.push(amm.get_token_out(token_in));
+        }
+
+        tokens
+    }
+
+    //Depth first search from the last token of `tokens`
+    fn extend_paths(
+        &self,
+        to: H160,
+        max_hops: usize,
+        tokens: &mut Vec<H160>,
+        path: &mut Vec<Arc<AMM>>,
+        paths: &mut Vec<Vec<Arc<AMM>>>,
+    ) {
+        if path.len() == max_hops {
+            return;
+        }
+
+        let token_in = tokens[tokens.len() - 1];
+        for amm in self.amms(token_in) {
+            if path.iter().any(|hop| Arc::ptr_eq(hop, amm)) {
+                continue;
+            }
+
+            let token_out = amm.get_token_out(token_in);
+            if token_out == token_in {
+                continue;
+            }
+
+            path.push(amm.clone());
+
+            if token_out == to {
+                paths.push(path.clone());
+            } else if !tokens.contains(&token_out) {
+                tokens.push(token_out);
+                self.extend_paths(to, max_hops, tokens, path, paths);
+                tokens.pop();
+            }
+
+            path.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::{H160, U256};
+
+    use crate::{
+        amm::{uniswap_v2::UniswapV2Pool, AMM},
+        router::simulate_multihop_swap,
+    };
+
+    use super::TokenGraph;
+
+    fn pool(address: u64, token_a: H160, token_b: H160) -> AMM {
+        AMM::UniswapV2Pool(UniswapV2Pool {
+            address: H160::from_low_u64_be(address),
+            token_a,
+            token_a_decimals: 18,
+            token_b,
+            token_b_decimals: 18,
+            reserve_0: 10_u128.pow(21),
+            reserve_1: 10_u128.pow(21),
+            fee: 300,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_find_paths() -> eyre::Result<()> {
+        let (token_a, token_b, token_c) = (
+            H160::repeat_byte(0x01),
+            H160::repeat_byte(0x02),
+            H160::repeat_byte(0x03),
+        );
+        let amms = vec![
+            pool(1, token_a, token_b),
+            pool(2, token_b, token_c),
+            pool(3, token_a, token_c),
+        ];
+        let graph = TokenGraph::build(&amms);
+
+        //a -> c directly and through b
+        let paths = graph.find_paths(token_a, token_c, 2);
+        assert_eq!(paths.len(), 2);
+        assert!(paths.iter().any(|path| path.len() == 1));
+        assert_eq!(graph.find_paths(token_a, token_c, 1).len(), 1);
+
+        for path in &paths {
+            let tokens = TokenGraph::path_tokens(token_a, path);
+            assert_eq!(tokens[tokens.len() - 1], token_c);
+
+            let amms = path.iter().map(|amm| (**amm).clone()).collect::<Vec<AMM>>();
+            simulate_multihop_swap(&amms, &tokens, U256::exp10(18))?;
+        }
+
+        //Cycles through all three pools in both directions
+        assert_eq!(graph.find_paths(token_a, token_a, 3).len(), 2);
+        assert!(graph.find_paths(token_a, H160::zero(), 3).is_empty());
+
+        Ok(())
+    }
+}
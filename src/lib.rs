@@ -2,5 +2,13 @@ pub mod amm;
 pub mod discovery;
 pub mod errors;
 pub mod filters;
+pub mod graph;
+pub mod middleware;
+pub mod oracles;
+pub mod router;
+#[cfg(feature = "bincode")]
+pub mod snapshot;
 pub mod state_space;
+pub mod storage;
 pub mod sync;
+pub mod token_list;
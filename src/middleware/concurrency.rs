@@ -0,0 +1,151 @@
+use std::{future::Future, sync::Arc};
+
+use async_trait::async_trait;
+use ethers::{
+    providers::{Middleware, MiddlewareError},
+    types::{
+        transaction::eip2718::TypedTransaction, Block, BlockId, Bytes, Filter, Log, NameOrAddress,
+        TxHash, U256, U64,
+    },
+};
+use thiserror::Error;
+use tokio::sync::Semaphore;
+
+//Bounds the number of requests in flight through the middleware. Clones of the middleware Arc share the limit,
+//so tasks that sync different factories through the same middleware are bounded together.
+#[derive(Debug)]
+pub struct ConcurrencyLimitMiddleware<M> {
+    inner: Arc<M>,
+    semaphore: Arc<Semaphore>,
+    pub max_concurrent_requests: usize,
+}
+
+#[derive(Error, Debug)]
+pub enum ConcurrencyLimitMiddlewareError<M: Middleware> {
+    #[error(transparent)]
+    MiddlewareError(M::Error),
+}
+
+impl<M: Middleware> MiddlewareError for ConcurrencyLimitMiddlewareError<M> {
+    type Inner = M::Error;
+
+    fn from_err(src: M::Error) -> Self {
+        ConcurrencyLimitMiddlewareError::MiddlewareError(src)
+    }
+
+    fn as_inner(&self) -> Option<&Self::Inner> {
+        match self {
+            ConcurrencyLimitMiddlewareError::MiddlewareError(error) => Some(error),
+        }
+    }
+}
+
+impl<M: Middleware> ConcurrencyLimitMiddleware<M> {
+    //At least one request is allowed in flight
+    pub fn new(inner: Arc<M>, max_concurrent_requests: usize) -> ConcurrencyLimitMiddleware<M> {
+        let max_concurrent_requests = max_concurrent_requests.clamp(1, Semaphore::MAX_PERMITS);
+
+        ConcurrencyLimitMiddleware {
+            inner,
+            semaphore: Arc::new(Semaphore::new(max_concurrent_requests)),
+            max_concurrent_requests,
+        }
+    }
+
+    //Number of requests that can be sent before a request has to wait for another one to complete
+    pub fn available_permits(&self) -> usize {
+        self.semaphore.available_permits()
+    }
+
+    async fn limit<T, Fut>(&self, request: Fut) -> Result<T, ConcurrencyLimitMiddlewareError<M>>
+    where
+        Fut: Future<Output = Result<T, M::Error>>,
+    {
+        //The semaphore is never closed, the permit is released when the request completes
+        let _permit = self.semaphore.acquire().await.ok();
+
+        request
+            .await
+            .map_err(ConcurrencyLimitMiddlewareError::MiddlewareError)
+    }
+}
+
+#[async_trait]
+impl<M: Middleware> Middleware for ConcurrencyLimitMiddleware<M> {
+    type Error = ConcurrencyLimitMiddlewareError<M>;
+    type Provider = M::Provider;
+    type Inner = M;
+
+    fn inner(&self) -> &M {
+        &self.inner
+    }
+
+    async fn get_block_number(&self) -> Result<U64, Self::Error> {
+        self.limit(self.inner.get_block_number()).await
+    }
+
+    async fn get_block<T: Into<BlockId> + Send + Sync>(
+        &self,
+        block_hash_or_number: T,
+    ) -> Result<Option<Block<TxHash>>, Self::Error> {
+        self.limit(self.inner.get_block(block_hash_or_number.into()))
+            .await
+    }
+
+    async fn get_chainid(&self) -> Result<U256, Self::Error> {
+        self.limit(self.inner.get_chainid()).await
+    }
+
+    async fn get_code<T: Into<NameOrAddress> + Send + Sync>(
+        &self,
+        at: T,
+        block: Option<BlockId>,
+    ) -> Result<Bytes, Self::Error> {
+        self.limit(self.inner.get_code(at.into(), block)).await
+    }
+
+    async fn call(
+        &self,
+        tx: &TypedTransaction,
+        block: Option<BlockId>,
+    ) -> Result<Bytes, Self::Error> {
+        self.limit(self.inner.call(tx, block)).await
+    }
+
+    async fn get_logs(&self, filter: &Filter) -> Result<Vec<Log>, Self::Error> {
+        self.limit(self.inner.get_logs(filter)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ethers::{
+        providers::{Middleware, Provider},
+        types::U64,
+    };
+
+    use super::ConcurrencyLimitMiddleware;
+
+    #[tokio::test]
+    async fn test_concurrency_limit_middleware() -> eyre::Result<()> {
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(100))?;
+
+        let middleware = ConcurrencyLimitMiddleware::new(Arc::new(provider), 2);
+        assert_eq!(middleware.get_block_number().await?, U64::from(100));
+
+        //Permits are returned once the requests complete, including failed ones
+        assert!(middleware.get_block_number().await.is_err());
+        assert_eq!(middleware.available_permits(), 2);
+
+        assert_eq!(
+            ConcurrencyLimitMiddleware::new(Arc::new(Provider::mocked().0), 0)
+                .max_concurrent_requests,
+            1
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+use ethers::{
+    providers::{Middleware, MiddlewareError},
+    types::{Filter, Log},
+};
+
+//Messages of providers that reject a log query because the block range or the number of results is too large
+const LOG_RANGE_ERROR_MESSAGES: [&str; 7] = [
+    "query returned more than",
+    "block range",
+    "range is too large",
+    "too many results",
+    "log response size exceeded",
+    "exceed maximum block range",
+    "limited to a",
+];
+
+//Max number of times a block range is halved before the error of the provider is returned
+pub const MAX_LOG_RANGE_SPLITS: u32 = 16;
+
+pub fn is_log_range_error<E: MiddlewareError>(error: &E) -> bool {
+    let message = match error.as_error_response() {
+        Some(response) => response.message.to_lowercase(),
+        None => error.to_string().to_lowercase(),
+    };
+
+    LOG_RANGE_ERROR_MESSAGES
+        .iter()
+        .any(|pattern| message.contains(pattern))
+}
+
+//Gets the logs matching the filter in [from_block, to_block]. Ranges that are rejected by the provider for being too large
+//are halved and both halves are queried in turn, until they succeed, are a single block or were split `max_splits` times.
+//Logs are returned in block order.
+pub async fn get_logs_with_adaptive_range<M: Middleware>(
+    middleware: &M,
+    filter: &Filter,
+    from_block: u64,
+    to_block: u64,
+    max_splits: u32,
+) -> Result<Vec<Log>, M::Error> {
+    get_logs_with_max_results(middleware, filter, from_block, to_block, max_splits, None).await
+}
+
+//Same as get_logs_with_adaptive_range, but ranges that return at least `max_logs_per_request` logs are halved as well, for providers
+//that truncate the response to their cap instead of rejecting the query
+pub async fn get_logs_with_max_results<M: Middleware>(
+    middleware: &M,
+    filter: &Filter,
+    from_block: u64,
+    to_block: u64,
+    max_splits: u32,
+    max_logs_per_request: Option<usize>,
+) -> Result<Vec<Log>, M::Error> {
+    let mut logs = vec![];
+
+    //Ranges that are still to be queried, the next range is at the end
+    let mut ranges = vec![(from_block, to_block, 0)];
+    while let Some((from_block, to_block, splits)) = ranges.pop() {
+        match middleware
+            .get_logs(&filter.clone().from_block(from_block).to_block(to_block))
+            .await
+        {
+            Ok(range_logs)
+                if from_block < to_block
+                    && splits < max_splits
+                    && max_logs_per_request
+                        .is_some_and(|max_logs| range_logs.len() >= max_logs) =>
+            {
+                let middle_block = from_block + (to_block - from_block) / 2;
+                tracing::trace!(
+                    from_block,
+                    to_block,
+                    middle_block,
+                    splits,
+                    logs = range_logs.len(),
+                    "log response may be truncated, splitting the range in half"
+                );
+
+                ranges.push((middle_block + 1, to_block, splits + 1));
+                ranges.push((from_block, middle_block, splits + 1));
+            }
+            Ok(range_logs) => logs.extend(range_logs),
+            Err(error)
+                if from_block < to_block && splits < max_splits && is_log_range_error(&error) =>
+            {
+                let middle_block = from_block + (to_block - from_block) / 2;
+                tracing::trace!(
+                    from_block,
+                    to_block,
+                    middle_block,
+                    splits,
+                    %error,
+                    "log range rejected by the provider, splitting it in half"
+                );
+
+                ranges.push((middle_block + 1, to_block, splits + 1));
+                ranges.push((from_block, middle_block, splits + 1));
+            }
+            Err(error) => return Err(error),
+        }
+    }
+
+    Ok(logs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use ethers::{
+        providers::{JsonRpcError, MockResponse, Provider, ProviderError},
+        types::{Filter, Log, U64},
+    };
+
+    use crate::middleware::retry::RetryMiddleware;
+
+    use super::{get_logs_with_max_results, is_log_range_error};
+
+    #[test]
+    fn test_is_log_range_error() {
+        assert!(is_log_range_error(&ProviderError::CustomError(
+            "query returned more than 10000 results".to_string()
+        )));
+        assert!(is_log_range_error(&ProviderError::CustomError(
+            "eth_getLogs is limited to a 10,000 block range".to_string()
+        )));
+        assert!(!is_log_range_error(&ProviderError::CustomError(
+            "execution reverted".to_string()
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_get_logs_with_max_results() -> eyre::Result<()> {
+        let log = |block_number: u64| Log {
+            block_number: Some(U64::from(block_number)),
+            ..Default::default()
+        };
+
+        //Responses are returned last in first out. Blocks 0-7 are truncated to the cap, blocks 0-3 are rejected
+        //and blocks 4-7 are truncated again before both of their halves are below the cap.
+        let (provider, mock) = Provider::mocked();
+        mock.push(vec![log(6), log(7)])?;
+        mock.push(vec![log(4), log(5)])?;
+        mock.push(vec![log(4), log(5), log(6)])?;
+        mock.push(Vec::<Log>::new())?;
+        mock.push(vec![log(0)])?;
+        mock.push_response(MockResponse::Error(JsonRpcError {
+            code: -32005,
+            message: "query returned more than 10000 results".to_string(),
+            data: None,
+        }));
+        mock.push(vec![log(0), log(4), log(5)])?;
+
+        let logs = get_logs_with_max_results(&provider, &Filter::new(), 0, 7, 16, Some(3)).await?;
+        let blocks = logs
+            .iter()
+            .filter_map(|log| log.block_number)
+            .map(|block_number| block_number.as_u64())
+            .collect::<Vec<u64>>();
+        assert_eq!(blocks, vec![0, 4, 5, 6, 7]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_logs_with_max_results_behind_retry_middleware() -> eyre::Result<()> {
+        let log = |block_number: u64| Log {
+            block_number: Some(U64::from(block_number)),
+            ..Default::default()
+        };
+
+        //Responses are returned last in first out. Blocks 0-7 are rejected with the code Infura also uses for its rate limit,
+        //a retry would take the response of blocks 0-3 for the whole range and wait for the backoff
+        let (provider, mock) = Provider::mocked();
+        mock.push(vec![log(5)])?;
+        mock.push(vec![log(1)])?;
+        mock.push_response(MockResponse::Error(JsonRpcError {
+            code: -32005,
+            message: "query returned more than 10000 results".to_string(),
+            data: None,
+        }));
+
+        let middleware = RetryMiddleware::new(Arc::new(provider), 3, Duration::from_secs(10));
+        let logs = tokio::time::timeout(
+            Duration::from_secs(1),
+            get_logs_with_max_results(&middleware, &Filter::new(), 0, 7, 16, None),
+        )
+        .await??;
+        let blocks = logs
+            .iter()
+            .filter_map(|log| log.block_number)
+            .map(|block_number| block_number.as_u64())
+            .collect::<Vec<u64>>();
+        assert_eq!(blocks, vec![1, 5]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,3 @@
+pub mod concurrency;
+pub mod logs;
+pub mod retry;
@@ -0,0 +1,315 @@
+use std::{
+    collections::hash_map::RandomState,
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    sync::Arc,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use ethers::{
+    providers::{Middleware, MiddlewareError, ProviderError},
+    types::{
+        transaction::eip2718::TypedTransaction, Block, BlockId, Bytes, Filter, Log, NameOrAddress,
+        TxHash, U256, U64,
+    },
+};
+use thiserror::Error;
+
+use super::logs::is_log_range_error;
+
+//JSON-RPC error codes used by providers to signal that a request was rate limited
+const RATE_LIMITED_ERROR_CODES: [i64; 2] = [429, -32005];
+const RATE_LIMITED_ERROR_MESSAGES: [&str; 3] = [
+    "rate limit",
+    "too many requests",
+    "exceeded the compute units",
+];
+
+//Jitter of RetryMiddleware::new, the delay of each attempt is drawn from [delay / 2, delay]
+pub const DEFAULT_JITTER: f64 = 0.5;
+
+//Retry policy of the requests that discovery and sync make. The default makes a single attempt, i.e. does not retry.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryConfig {
+    //Includes the first request
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    //Fraction of the delay that is randomized, 0 waits exactly the exponential backoff
+    pub jitter: f64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig::new(1, Duration::ZERO)
+    }
+}
+
+impl RetryConfig {
+    pub fn new(max_attempts: u32, base_delay: Duration) -> RetryConfig {
+        RetryConfig {
+            max_attempts: max_attempts.max(1),
+            base_delay,
+            jitter: DEFAULT_JITTER,
+        }
+    }
+
+    pub fn with_jitter(mut self, jitter: f64) -> RetryConfig {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn wrap<M: Middleware>(&self, middleware: Arc<M>) -> Arc<RetryMiddleware<M>> {
+        Arc::new(RetryMiddleware::with_config(middleware, *self))
+    }
+}
+
+//Retries requests that failed because of rate limiting or a transport error with an exponential backoff.
+//Deterministic errors like reverts are returned on the first attempt.
+#[derive(Debug)]
+pub struct RetryMiddleware<M> {
+    inner: Arc<M>,
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub jitter: f64,
+}
+
+#[derive(Error, Debug)]
+pub enum RetryMiddlewareError<M: Middleware> {
+    #[error(transparent)]
+    MiddlewareError(M::Error),
+    #[error("Request was rate limited or dropped after {0} attempts")]
+    RetriesExhausted(u32, #[source] M::Error),
+}
+
+impl<M: Middleware> MiddlewareError for RetryMiddlewareError<M> {
+    type Inner = M::Error;
+
+    fn from_err(src: M::Error) -> Self {
+        RetryMiddlewareError::MiddlewareError(src)
+    }
+
+    fn as_inner(&self) -> Option<&Self::Inner> {
+        match self {
+            RetryMiddlewareError::MiddlewareError(error) => Some(error),
+            RetryMiddlewareError::RetriesExhausted(_, error) => Some(error),
+        }
+    }
+}
+
+impl<M: Middleware> RetryMiddleware<M> {
+    //`max_attempts` includes the first request, a value of 1 disables retries
+    pub fn new(inner: Arc<M>, max_attempts: u32, base_delay: Duration) -> RetryMiddleware<M> {
+        RetryMiddleware {
+            inner,
+            max_attempts: max_attempts.max(1),
+            base_delay,
+            jitter: DEFAULT_JITTER,
+        }
+    }
+
+    pub fn with_config(inner: Arc<M>, config: RetryConfig) -> RetryMiddleware<M> {
+        RetryMiddleware {
+            jitter: config.jitter,
+            ..RetryMiddleware::new(inner, config.max_attempts, config.base_delay)
+        }
+    }
+
+    async fn retry<T, F, Fut>(&self, request: F) -> Result<T, RetryMiddlewareError<M>>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, M::Error>>,
+    {
+        let mut attempt = 1;
+
+        loop {
+            match request().await {
+                Ok(response) => return Ok(response),
+                Err(error) if is_transient_error(&error) => {
+                    if attempt >= self.max_attempts {
+                        return Err(RetryMiddlewareError::RetriesExhausted(attempt, error));
+                    }
+
+                    let delay = backoff_delay_with_jitter(self.base_delay, attempt, self.jitter);
+                    tracing::warn!(attempt, ?delay, %error, "retrying request");
+
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(error) => return Err(RetryMiddlewareError::MiddlewareError(error)),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<M: Middleware> Middleware for RetryMiddleware<M> {
+    type Error = RetryMiddlewareError<M>;
+    type Provider = M::Provider;
+    type Inner = M;
+
+    fn inner(&self) -> &M {
+        &self.inner
+    }
+
+    async fn get_block_number(&self) -> Result<U64, Self::Error> {
+        self.retry(|| self.inner.get_block_number()).await
+    }
+
+    async fn get_block<T: Into<BlockId> + Send + Sync>(
+        &self,
+        block_hash_or_number: T,
+    ) -> Result<Option<Block<TxHash>>, Self::Error> {
+        let block = block_hash_or_number.into();
+        self.retry(|| self.inner.get_block(block)).await
+    }
+
+    async fn get_chainid(&self) -> Result<U256, Self::Error> {
+        self.retry(|| self.inner.get_chainid()).await
+    }
+
+    async fn get_code<T: Into<NameOrAddress> + Send + Sync>(
+        &self,
+        at: T,
+        block: Option<BlockId>,
+    ) -> Result<Bytes, Self::Error> {
+        let at = at.into();
+        self.retry(|| self.inner.get_code(at.clone(), block)).await
+    }
+
+    async fn call(
+        &self,
+        tx: &TypedTransaction,
+        block: Option<BlockId>,
+    ) -> Result<Bytes, Self::Error> {
+        self.retry(|| self.inner.call(tx, block)).await
+    }
+
+    async fn get_logs(&self, filter: &Filter) -> Result<Vec<Log>, Self::Error> {
+        self.retry(|| self.inner.get_logs(filter)).await
+    }
+}
+
+//Rate limits, timeouts and dropped connections are worth retrying, JSON-RPC errors like reverts are not
+pub fn is_transient_error<E: MiddlewareError>(error: &E) -> bool {
+    //Infura answers a log query with too many results with -32005 as well, retrying it returns the same error
+    if is_log_range_error(error) {
+        return false;
+    }
+
+    if let Some(response) = error.as_error_response() {
+        let message = response.message.to_lowercase();
+
+        return RATE_LIMITED_ERROR_CODES.contains(&response.code)
+            || RATE_LIMITED_ERROR_MESSAGES
+                .iter()
+                .any(|pattern| message.contains(pattern));
+    }
+
+    //Providers often answer a rate limited request with a body that is not valid JSON-RPC
+    let message = error.to_string().to_lowercase();
+    if RATE_LIMITED_ERROR_MESSAGES
+        .iter()
+        .any(|pattern| message.contains(pattern))
+    {
+        return true;
+    }
+
+    match error.as_provider_error() {
+        Some(ProviderError::HTTPError(error)) => {
+            error.is_timeout()
+                || error.is_connect()
+                || error.status().map_or(false, |status| {
+                    status.as_u16() == 429 || status.is_server_error()
+                })
+        }
+        //Transport errors, e.g. a websocket that was closed by the provider
+        Some(ProviderError::JsonRpcClientError(error)) => error.as_serde_error().is_none(),
+        _ => false,
+    }
+}
+
+//Exponential backoff with jitter, the delay of each attempt is drawn from [delay / 2, delay]
+pub fn backoff_delay(base_delay: Duration, attempt: u32) -> Duration {
+    backoff_delay_with_jitter(base_delay, attempt, DEFAULT_JITTER)
+}
+
+//The delay of each attempt is drawn from [delay * (1 - jitter), delay]
+pub fn backoff_delay_with_jitter(base_delay: Duration, attempt: u32, jitter: f64) -> Duration {
+    let delay = base_delay.saturating_mul(2_u32.saturating_pow(attempt.saturating_sub(1)));
+    let random = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+
+    delay.mul_f64(1.0 - jitter.clamp(0.0, 1.0) * (1.0 - random))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use ethers::providers::{
+        JsonRpcError, Middleware, MockProvider, MockResponse, Provider, ProviderError,
+    };
+
+    use super::{backoff_delay, backoff_delay_with_jitter, is_transient_error};
+
+    #[test]
+    fn test_backoff_delay() {
+        let base_delay = Duration::from_millis(100);
+
+        for attempt in 1..=5 {
+            let max_delay = base_delay * 2_u32.pow(attempt - 1);
+            let delay = backoff_delay(base_delay, attempt);
+
+            assert!(delay >= max_delay / 2 && delay <= max_delay);
+            assert_eq!(
+                backoff_delay_with_jitter(base_delay, attempt, 0.0),
+                max_delay
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_transient_error() {
+        assert!(is_transient_error(&ProviderError::CustomError(
+            "429 Too Many Requests".to_string()
+        )));
+        assert!(is_transient_error(&ProviderError::CustomError(
+            "daily request count exceeded, request rate limited".to_string()
+        )));
+        assert!(!is_transient_error(&ProviderError::CustomError(
+            "execution reverted".to_string()
+        )));
+        assert!(!is_transient_error(&ProviderError::EnsError(
+            "vitalik.eth".to_string()
+        )));
+    }
+
+    async fn json_rpc_error(code: i64, message: &str) -> ProviderError {
+        let (provider, mock) = Provider::<MockProvider>::mocked();
+        mock.push_response(MockResponse::Error(JsonRpcError {
+            code,
+            message: message.to_string(),
+            data: None,
+        }));
+
+        provider.get_block_number().await.unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn test_is_transient_error_json_rpc() {
+        assert!(is_transient_error(
+            &json_rpc_error(429, "Too Many Requests").await
+        ));
+        assert!(is_transient_error(
+            &json_rpc_error(-32005, "daily request count exceeded, request rate limited").await
+        ));
+
+        //Infura rejects a log query with too many results with the code of its rate limit
+        assert!(!is_transient_error(
+            &json_rpc_error(-32005, "query returned more than 10000 results").await
+        ));
+        assert!(!is_transient_error(
+            &json_rpc_error(3, "execution reverted").await
+        ));
+    }
+}
@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use ethers::{providers::Middleware, types::H160};
+
+use crate::{
+    amm::{AutomatedMarketMaker, AMM},
+    errors::{AMMError, ArithmeticError},
+};
+
+//Mainnet tokens of the reference pools
+pub const WETH: H160 = H160([
+    0xc0, 0x2a, 0xaa, 0x39, 0xb2, 0x23, 0xfe, 0x8d, 0x0a, 0x0e, 0x5c, 0x4f, 0x27, 0xea, 0xd9, 0x08,
+    0x3c, 0x75, 0x6c, 0xc2,
+]);
+pub const USDC: H160 = H160([
+    0xa0, 0xb8, 0x69, 0x91, 0xc6, 0x21, 0x8b, 0x36, 0xc1, 0xd1, 0x9d, 0x4a, 0x2e, 0x9e, 0xb0, 0xce,
+    0x36, 0x06, 0xeb, 0x48,
+]);
+pub const USDT: H160 = H160([
+    0xda, 0xc1, 0x7f, 0x95, 0x8d, 0x2e, 0xe5, 0x23, 0xa2, 0x20, 0x62, 0x06, 0x99, 0x45, 0x97, 0xc1,
+    0x3d, 0x83, 0x1e, 0xc7,
+]);
+pub const DAI: H160 = H160([
+    0x6b, 0x17, 0x54, 0x74, 0xe8, 0x90, 0x94, 0xc4, 0x4d, 0xa9, 0x8b, 0x95, 0x4e, 0xed, 0xea, 0xc4,
+    0x95, 0x27, 0x1d, 0x0f,
+]);
+
+//Mainnet WETH/stablecoin pools with the most liquidity, Uniswap V3 pools are the 0.05% fee tier
+pub const UNISWAP_V2_USDC_WETH: H160 = H160([
+    0xb4, 0xe1, 0x6d, 0x01, 0x68, 0xe5, 0x2d, 0x35, 0xca, 0xcd, 0x2c, 0x61, 0x85, 0xb4, 0x42, 0x81,
+    0xec, 0x28, 0xc9, 0xdc,
+]);
+pub const UNISWAP_V2_WETH_USDT: H160 = H160([
+    0x0d, 0x4a, 0x11, 0xd5, 0xee, 0xaa, 0xc2, 0x8e, 0xc3, 0xf6, 0x1d, 0x10, 0x0d, 0xaf, 0x4d, 0x40,
+    0x47, 0x1f, 0x18, 0x52,
+]);
+pub const UNISWAP_V3_USDC_WETH: H160 = H160([
+    0x88, 0xe6, 0xa0, 0xc2, 0xdd, 0xd2, 0x6f, 0xee, 0xb6, 0x4f, 0x03, 0x9a, 0x2c, 0x41, 0x29, 0x6f,
+    0xcb, 0x3f, 0x56, 0x40,
+]);
+pub const UNISWAP_V3_WETH_USDT: H160 = H160([
+    0x11, 0xb8, 0x15, 0xef, 0xb8, 0xf5, 0x81, 0x19, 0x4a, 0xe7, 0x90, 0x06, 0xd2, 0x4e, 0x0d, 0x81,
+    0x4b, 0x76, 0x97, 0xf6,
+]);
+
+//Price of ETH in USD from a populated pool of WETH and a stablecoin, e.g. a Uniswap V2 pair or a Uniswap V3 pool.
+//The stablecoin is assumed to be worth one dollar. The price is cached and only changes when the oracle is synced.
+#[derive(Debug, Clone)]
+pub struct EthPriceOracle {
+    pub pool: AMM,
+    pub weth: H160,
+    pub price: f64,
+}
+
+impl EthPriceOracle {
+    //`weth` is the wrapped native token of the chain of the pool
+    pub fn new(pool: AMM, weth: H160) -> Result<EthPriceOracle, ArithmeticError> {
+        if !pool.tokens().contains(&weth) {
+            return Err(ArithmeticError::TokenNotInPool(weth));
+        }
+
+        let price = pool.calculate_price(weth)?.to_f64();
+        Ok(EthPriceOracle { pool, weth, price })
+    }
+
+    //Same as new, with the mainnet WETH
+    pub fn from_pool(pool: AMM) -> Result<EthPriceOracle, ArithmeticError> {
+        EthPriceOracle::new(pool, WETH)
+    }
+
+    pub fn eth_price_usd(&self) -> f64 {
+        self.price
+    }
+
+    //Recomputes the cached price from the current state of the pool, for pools that are synced from logs
+    pub fn update_price(&mut self) -> Result<f64, ArithmeticError> {
+        self.price = self.pool.calculate_price(self.weth)?.to_f64();
+        Ok(self.price)
+    }
+
+    //Syncs the pool at the latest block and refreshes the cached price
+    pub async fn sync<M: Middleware>(&mut self, middleware: Arc<M>) -> Result<f64, AMMError<M>> {
+        self.pool.sync(middleware).await?;
+        Ok(self.update_price()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        amm::{uniswap_v2::UniswapV2Pool, AMM},
+        errors::ArithmeticError,
+    };
+
+    use super::{EthPriceOracle, DAI, USDC, WETH};
+
+    #[test]
+    fn test_eth_price_oracle() -> eyre::Result<()> {
+        //1000 WETH against 3,000,000 USDC
+        let pool = AMM::UniswapV2Pool(UniswapV2Pool {
+            token_a: USDC,
+            token_a_decimals: 6,
+            token_b: WETH,
+            token_b_decimals: 18,
+            reserve_0: 3_000_000 * 10_u128.pow(6),
+            reserve_1: 1000 * 10_u128.pow(18),
+            fee: 300,
+            ..Default::default()
+        });
+
+        let mut oracle = EthPriceOracle::from_pool(pool.clone())?;
+        assert!((oracle.eth_price_usd() - 3000.0).abs() < 1e-6);
+
+        //The cached price only changes when the oracle is refreshed
+        if let AMM::UniswapV2Pool(pool) = &mut oracle.pool {
+            pool.reserve_0 = 2_000_000 * 10_u128.pow(6);
+        }
+        assert!((oracle.eth_price_usd() - 3000.0).abs() < 1e-6);
+        assert!((oracle.update_price()? - 2000.0).abs() < 1e-6);
+        assert!((oracle.eth_price_usd() - 2000.0).abs() < 1e-6);
+
+        assert!(matches!(
+            EthPriceOracle::new(pool, DAI),
+            Err(ArithmeticError::TokenNotInPool(_))
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+use ethers::types::U256;
+
+use crate::amm::{u256_to_f64, AMM};
+
+//Additional gas of each initialized tick or bin that a swap crosses in AMMs with concentrated liquidity
+pub const TICK_CROSSING_GAS: u64 = 20_000;
+
+//Rough gas cost of swaps for routing, it includes the token transfers but not the base cost of the transaction.
+//The actual cost depends on the tokens, e.g. a token with a transfer tax or a cold storage slot costs more.
+pub struct GasEstimator;
+
+impl GasEstimator {
+    //Gas of a swap through the AMM that does not cross an initialized tick
+    pub fn swap_gas(amm: &AMM) -> u64 {
+        match amm {
+            AMM::UniswapV2Pool(_) => 60_000,
+            AMM::UniswapV3Pool(_) => 120_000,
+            AMM::ERC4626Vault(_) => 80_000,
+            AMM::BalancerV2WeightedPool(_) => 110_000,
+            AMM::CurveStableSwapPool(_) => 130_000,
+            AMM::CurveCryptoPool(_) => 180_000,
+            AMM::UniswapV4Pool(_) => 110_000,
+            AMM::SolidlyPool(_) => 90_000,
+            AMM::LBPair(_) => 100_000,
+            AMM::MaverickPool(_) => 120_000,
+            AMM::BalancerStablePool(_) => 130_000,
+            AMM::CamelotPool(_) => 70_000,
+            AMM::BancorV3Pool(_) => 200_000,
+            AMM::KyberElasticPool(_) => 130_000,
+            AMM::DodoPool(_) => 120_000,
+            AMM::AlgebraPool(_) => 130_000,
+            AMM::RateProviderAmm(_) => 80_000,
+            AMM::CurveMetaPool(_) => 250_000,
+        }
+    }
+
+    //Gas of a swap that crosses `tick_crossings` initialized ticks or bins, AMMs without ticks ignore the crossings.
+    //A Uniswap V3 swap costs ~120k gas without crossing a tick and ~180k gas when it crosses three ticks.
+    pub fn swap_gas_with_tick_crossings(amm: &AMM, tick_crossings: u32) -> u64 {
+        let crossing_gas = match amm {
+            AMM::UniswapV3Pool(_)
+            | AMM::UniswapV4Pool(_)
+            | AMM::KyberElasticPool(_)
+            | AMM::AlgebraPool(_)
+            | AMM::LBPair(_)
+            | AMM::MaverickPool(_) => TICK_CROSSING_GAS * tick_crossings as u64,
+            _ => 0,
+        };
+
+        GasEstimator::swap_gas(amm) + crossing_gas
+    }
+
+    //USD cost of a swap through the AMM that does not cross an initialized tick, `gas_price` is in wei
+    pub fn estimate_swap_cost_usd(amm: &AMM, gas_price: U256, eth_price_usd: f64) -> f64 {
+        gas_cost_usd(GasEstimator::swap_gas(amm), gas_price, eth_price_usd)
+    }
+
+    pub fn estimate_swap_cost_usd_with_tick_crossings(
+        amm: &AMM,
+        tick_crossings: u32,
+        gas_price: U256,
+        eth_price_usd: f64,
+    ) -> f64 {
+        gas_cost_usd(
+            GasEstimator::swap_gas_with_tick_crossings(amm, tick_crossings),
+            gas_price,
+            eth_price_usd,
+        )
+    }
+
+    //USD cost of swapping through every AMM of a path, e.g. to skip paths that cost more gas than they return
+    pub fn estimate_path_cost_usd(amms: &[AMM], gas_price: U256, eth_price_usd: f64) -> f64 {
+        let gas = amms.iter().map(GasEstimator::swap_gas).sum();
+        gas_cost_usd(gas, gas_price, eth_price_usd)
+    }
+}
+
+fn gas_cost_usd(gas: u64, gas_price: U256, eth_price_usd: f64) -> f64 {
+    let gas_cost = U256::from(gas).saturating_mul(gas_price);
+    u256_to_f64(gas_cost) / 1e18 * eth_price_usd
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::U256;
+
+    use crate::amm::{uniswap_v2::UniswapV2Pool, uniswap_v3::UniswapV3Pool, AMM};
+
+    use super::GasEstimator;
+
+    #[test]
+    fn test_estimate_swap_cost_usd() {
+        let v2_pool = AMM::UniswapV2Pool(UniswapV2Pool::default());
+        let v3_pool = AMM::UniswapV3Pool(UniswapV3Pool::default());
+
+        //60k gas at 50 gwei is 0.003 ETH
+        let gas_price = U256::exp10(9) * 50;
+        let cost = GasEstimator::estimate_swap_cost_usd(&v2_pool, gas_price, 2000.0);
+        assert!((cost - 6.0).abs() < 1e-9);
+
+        assert_eq!(
+            GasEstimator::swap_gas_with_tick_crossings(&v3_pool, 3),
+            180_000
+        );
+        assert_eq!(
+            GasEstimator::swap_gas_with_tick_crossings(&v2_pool, 3),
+            60_000
+        );
+
+        let path_cost =
+            GasEstimator::estimate_path_cost_usd(&[v2_pool, v3_pool], gas_price, 2000.0);
+        assert!((path_cost - 18.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,124 @@
+pub mod gas;
+
+use ethers::types::{H160, U256};
+
+use crate::{
+    amm::{AutomatedMarketMaker, AMM},
+    errors::SwapSimulationError,
+};
+
+//Swaps `amount_in` of `path[0]` through each AMM in order, `amms[i]` swaps `path[i]` for `path[i + 1]`.
+//Returns the amount of the last token in the path.
+pub fn simulate_multihop_swap(
+    amms: &[AMM],
+    path: &[H160],
+    amount_in: U256,
+) -> Result<U256, SwapSimulationError> {
+    validate_path(amms, path)?;
+
+    let mut amount = amount_in;
+    for (amm, token_in) in amms.iter().zip(path) {
+        amount = amm.simulate_swap(*token_in, amount)?;
+    }
+
+    Ok(amount)
+}
+
+//Same as `simulate_multihop_swap` but updates the state of each AMM, so a path can go through the same AMM more than once
+pub fn simulate_multihop_swap_mut(
+    amms: &mut [AMM],
+    path: &[H160],
+    amount_in: U256,
+) -> Result<U256, SwapSimulationError> {
+    validate_path(amms, path)?;
+
+    let mut amount = amount_in;
+    for (amm, token_in) in amms.iter_mut().zip(path) {
+        amount = amm.simulate_swap_mut(*token_in, amount)?;
+    }
+
+    Ok(amount)
+}
+
+//AMMs with more than two tokens swap into `get_token_out`, so each hop has to match it
+fn validate_path(amms: &[AMM], path: &[H160]) -> Result<(), SwapSimulationError> {
+    if amms.is_empty() || path.len() != amms.len() + 1 {
+        return Err(SwapSimulationError::InvalidPathLength);
+    }
+
+    for (amm, hop) in amms.iter().zip(path.windows(2)) {
+        let (token_in, token_out) = (hop[0], hop[1]);
+
+        if !amm.tokens().contains(&token_in) {
+            return Err(SwapSimulationError::TokenNotInPool(token_in));
+        }
+
+        if amm.get_token_out(token_in) != token_out {
+            return Err(SwapSimulationError::InvalidHop(token_in, token_out));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::{H160, U256};
+
+    use crate::{
+        amm::{uniswap_v2::UniswapV2Pool, AutomatedMarketMaker, AMM},
+        errors::SwapSimulationError,
+    };
+
+    use super::{simulate_multihop_swap, simulate_multihop_swap_mut};
+
+    fn pool(token_a: H160, token_b: H160) -> AMM {
+        AMM::UniswapV2Pool(UniswapV2Pool {
+            token_a,
+            token_a_decimals: 18,
+            token_b,
+            token_b_decimals: 18,
+            reserve_0: 10_u128.pow(21),
+            reserve_1: 2 * 10_u128.pow(21),
+            fee: 300,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_simulate_multihop_swap() {
+        let (token_a, token_b, token_c) = (
+            H160::repeat_byte(0x01),
+            H160::repeat_byte(0x02),
+            H160::repeat_byte(0x03),
+        );
+        let mut amms = vec![pool(token_a, token_b), pool(token_b, token_c)];
+        let amount_in = U256::exp10(18);
+
+        let first_hop = amms[0].simulate_swap(token_a, amount_in).unwrap();
+        let second_hop = amms[1].simulate_swap(token_b, first_hop).unwrap();
+
+        let path = [token_a, token_b, token_c];
+        assert_eq!(
+            simulate_multihop_swap(&amms, &path, amount_in).unwrap(),
+            second_hop
+        );
+        assert_eq!(
+            simulate_multihop_swap_mut(&mut amms, &path, amount_in).unwrap(),
+            second_hop
+        );
+
+        assert!(matches!(
+            simulate_multihop_swap(&amms, &[token_a, token_b], amount_in),
+            Err(SwapSimulationError::InvalidPathLength)
+        ));
+        assert!(matches!(
+            simulate_multihop_swap(&amms, &[token_c, token_b, token_c], amount_in),
+            Err(SwapSimulationError::TokenNotInPool(token)) if token == token_c
+        ));
+        assert!(matches!(
+            simulate_multihop_swap(&amms, &[token_a, token_c, token_b], amount_in),
+            Err(SwapSimulationError::InvalidHop(_, _))
+        ));
+    }
+}
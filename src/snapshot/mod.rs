@@ -0,0 +1,181 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{amm::AMM, errors::SnapshotError};
+
+//Identifies a pool state snapshot written by BincodeSnapshot::save
+pub const SNAPSHOT_MAGIC: [u8; 4] = *b"AMMS";
+//Schema version of the AMM variants. Bump when a field is added to a pool struct, fields must only be appended
+//to the end of a struct so that records written by newer versions can still be decoded by older versions.
+//Version 3 appended the transfer taxes to UniswapV2Pool.
+pub const SNAPSHOT_VERSION: u16 = 3;
+
+//Binary pool state snapshots, considerably faster to write and read than a JSON checkpoint for large state spaces.
+//Each pool is stored as a length prefixed record tagged with the schema version it was written with.
+pub struct BincodeSnapshot;
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotRecord {
+    version: u16,
+    payload: Vec<u8>,
+}
+
+impl BincodeSnapshot {
+    pub fn save(pools: &[AMM], path: &Path) -> Result<(), SnapshotError> {
+        let records = pools
+            .iter()
+            .map(|amm| {
+                Ok(SnapshotRecord {
+                    version: SNAPSHOT_VERSION,
+                    payload: bincode::serialize(amm)?,
+                })
+            })
+            .collect::<Result<Vec<SnapshotRecord>, SnapshotError>>()?;
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&SNAPSHOT_MAGIC)?;
+        bincode::serialize_into(&mut writer, &records)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Vec<AMM>, SnapshotError> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut magic = [0_u8; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != SNAPSHOT_MAGIC {
+            return Err(SnapshotError::InvalidSnapshot);
+        }
+
+        let records: Vec<SnapshotRecord> = bincode::deserialize_from(reader)?;
+
+        records.iter().map(decode_record).collect()
+    }
+}
+
+//Records from newer versions only differ by appended fields, which are left as trailing bytes of the payload and ignored.
+//Records from older versions are missing fields of the current layout and can not be decoded without a migration.
+fn decode_record(record: &SnapshotRecord) -> Result<AMM, SnapshotError> {
+    if record.version < SNAPSHOT_VERSION {
+        return Err(SnapshotError::UnsupportedVersion(record.version));
+    }
+
+    Ok(bincode::deserialize(&record.payload)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::{H160, U256};
+
+    use crate::{
+        amm::{uniswap_v2::UniswapV2Pool, uniswap_v3::UniswapV3Pool, AutomatedMarketMaker, AMM},
+        errors::SnapshotError,
+    };
+
+    use super::{decode_record, BincodeSnapshot, SnapshotRecord, SNAPSHOT_VERSION};
+
+    #[test]
+    fn test_save_and_load() -> eyre::Result<()> {
+        let pools = vec![
+            AMM::UniswapV2Pool(UniswapV2Pool::new(
+                H160::from_low_u64_be(1),
+                H160::from_low_u64_be(2),
+                18,
+                H160::from_low_u64_be(3),
+                6,
+                1000,
+                2000,
+                300,
+            )),
+            AMM::UniswapV3Pool(UniswapV3Pool {
+                address: H160::from_low_u64_be(4),
+                sqrt_price: U256::one() << 96,
+                liquidity: 1000,
+                ..Default::default()
+            }),
+        ];
+
+        let path = std::env::temp_dir().join("amms_bincode_snapshot_test.bin");
+        BincodeSnapshot::save(&pools, &path)?;
+        let loaded = BincodeSnapshot::load(&path)?;
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(loaded.len(), pools.len());
+        for (loaded, pool) in loaded.iter().zip(pools.iter()) {
+            assert_eq!(loaded.address(), pool.address());
+            assert_eq!(serde_json::to_string(loaded)?, serde_json::to_string(pool)?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_record_versions() -> eyre::Result<()> {
+        let pool = AMM::UniswapV2Pool(UniswapV2Pool {
+            address: H160::from_low_u64_be(1),
+            ..Default::default()
+        });
+
+        //A field appended by a newer version is ignored
+        let mut payload = bincode::serialize(&pool)?;
+        payload.extend_from_slice(&[1, 2, 3, 4]);
+        let record = SnapshotRecord {
+            version: SNAPSHOT_VERSION + 1,
+            payload,
+        };
+        assert_eq!(decode_record(&record)?.address(), pool.address());
+
+        let record = SnapshotRecord {
+            version: SNAPSHOT_VERSION - 1,
+            payload: bincode::serialize(&pool)?,
+        };
+        assert!(matches!(
+            decode_record(&record),
+            Err(SnapshotError::UnsupportedVersion(version)) if version == SNAPSHOT_VERSION - 1
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_transfer_taxes() -> eyre::Result<()> {
+        let pool = AMM::UniswapV2Pool(UniswapV2Pool {
+            address: H160::from_low_u64_be(1),
+            token_a_tax: 500,
+            token_b_tax: 100,
+            ..Default::default()
+        });
+
+        let path = std::env::temp_dir().join("amms_bincode_snapshot_taxes_test.bin");
+        BincodeSnapshot::save(&[pool], &path)?;
+        let loaded = BincodeSnapshot::load(&path)?;
+        std::fs::remove_file(&path)?;
+
+        match &loaded[..] {
+            [AMM::UniswapV2Pool(pool)] => {
+                assert_eq!((pool.token_a_tax, pool.token_b_tax), (500, 100));
+            }
+            _ => panic!("expected a Uniswap V2 pool"),
+        }
+
+        //Version 2 pools end before the transfer taxes and are not decoded into the current layout
+        assert_eq!(SNAPSHOT_VERSION, 3);
+        let record = SnapshotRecord {
+            version: 2,
+            payload: vec![],
+        };
+        assert!(matches!(
+            decode_record(&record),
+            Err(SnapshotError::UnsupportedVersion(2))
+        ));
+
+        Ok(())
+    }
+}
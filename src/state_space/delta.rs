@@ -0,0 +1,123 @@
+use ethers::types::{H160, U256};
+
+use crate::amm::AMM;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Delta<T> {
+    pub before: T,
+    pub after: T,
+}
+
+impl<T: PartialEq> Delta<T> {
+    pub fn new(before: T, after: T) -> Delta<T> {
+        Delta { before, after }
+    }
+
+    pub fn changed(&self) -> bool {
+        self.before != self.after
+    }
+}
+
+//Fields of a pool that the state change of a block moved, values of a field that did not change are equal
+#[derive(Debug, Clone)]
+pub enum StateDelta {
+    UniswapV2Pool {
+        reserve_0: Delta<u128>,
+        reserve_1: Delta<u128>,
+    },
+    UniswapV3Pool {
+        sqrt_price: Delta<U256>,
+        liquidity: Delta<u128>,
+        tick: Delta<i32>,
+    },
+    ERC4626Vault {
+        vault_reserve: Delta<U256>,
+        asset_reserve: Delta<U256>,
+    },
+    //The pool changed but its fields have to be compared by the consumer, i.e. other variants or pools without a cached prior state.
+    //The variant of the pool is the variant of `after`.
+    Other {
+        before: Option<Box<AMM>>,
+        after: Box<AMM>,
+    },
+}
+
+impl StateDelta {
+    pub fn new(before: &AMM, after: &AMM) -> StateDelta {
+        match (before, after) {
+            (AMM::UniswapV2Pool(before), AMM::UniswapV2Pool(after)) => StateDelta::UniswapV2Pool {
+                reserve_0: Delta::new(before.reserve_0, after.reserve_0),
+                reserve_1: Delta::new(before.reserve_1, after.reserve_1),
+            },
+            (AMM::UniswapV3Pool(before), AMM::UniswapV3Pool(after)) => StateDelta::UniswapV3Pool {
+                sqrt_price: Delta::new(before.sqrt_price, after.sqrt_price),
+                liquidity: Delta::new(before.liquidity, after.liquidity),
+                tick: Delta::new(before.tick, after.tick),
+            },
+            (AMM::ERC4626Vault(before), AMM::ERC4626Vault(after)) => StateDelta::ERC4626Vault {
+                vault_reserve: Delta::new(before.vault_reserve, after.vault_reserve),
+                asset_reserve: Delta::new(before.asset_reserve, after.asset_reserve),
+            },
+            _ => StateDelta::Other {
+                before: Some(Box::new(before.clone())),
+                after: Box::new(after.clone()),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AmmStateDelta {
+    pub address: H160,
+    pub delta: StateDelta,
+}
+
+//State changes of the blocks up to `block_number` that were processed for a new head, the prior state of each pool is its state
+//before the first of these blocks
+#[derive(Debug, Clone)]
+pub struct StateChangeEvent {
+    pub block_number: u64,
+    pub changes: Vec<AmmStateDelta>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::amm::{erc_4626::ERC4626Vault, uniswap_v2::UniswapV2Pool, AMM};
+
+    use super::{Delta, StateDelta};
+
+    #[test]
+    fn test_state_delta() {
+        let pool = |reserve_0: u128| {
+            AMM::UniswapV2Pool(UniswapV2Pool {
+                reserve_0,
+                reserve_1: 5,
+                ..Default::default()
+            })
+        };
+
+        match StateDelta::new(&pool(1), &pool(2)) {
+            StateDelta::UniswapV2Pool {
+                reserve_0,
+                reserve_1,
+            } => {
+                assert_eq!(reserve_0, Delta::new(1, 2));
+                assert!(reserve_0.changed());
+                assert!(!reserve_1.changed());
+            }
+            _ => panic!("expected a Uniswap V2 delta"),
+        }
+
+        //Both states are kept when the variants of the states differ
+        match StateDelta::new(&pool(1), &AMM::ERC4626Vault(ERC4626Vault::default())) {
+            StateDelta::Other {
+                before: Some(before),
+                after,
+            } => {
+                assert!(matches!(*before, AMM::UniswapV2Pool(_)));
+                assert!(matches!(*after, AMM::ERC4626Vault(_)));
+            }
+            _ => panic!("expected an other delta"),
+        }
+    }
+}
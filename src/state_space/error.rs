@@ -8,7 +8,7 @@ use ethers::signers::WalletError;
 use ethers::types::{Block, H160, H256};
 use thiserror::Error;
 
-use super::state::MiddlewarePubsub;
+use super::{delta::StateChangeEvent, state::MiddlewarePubsub};
 
 #[derive(Error, Debug)]
 pub enum StateSpaceError<M, P>
@@ -44,10 +44,14 @@ where
     BlockNumberNotFound,
     #[error("Could not send state changes through channel")]
     StateChangeSendError(#[from] tokio::sync::mpsc::error::SendError<Vec<H160>>),
+    #[error("Could not send state change event through channel")]
+    StateChangeEventSendError(#[from] tokio::sync::mpsc::error::SendError<StateChangeEvent>),
     #[error("Could not send block through channel")]
     BlockSendError(#[from] tokio::sync::mpsc::error::SendError<Block<H256>>),
     #[error("Already listening for state changes")]
     AlreadyListeningForStateChanges,
+    #[error("Reorg of at least {depth} blocks at block {block_number} is deeper than the blocks that can be unwound")]
+    ReorgTooDeep { block_number: u64, depth: u64 },
 }
 
 #[derive(Error, Debug)]
@@ -58,6 +62,13 @@ pub enum StateChangeError {
     PopFrontError,
     #[error("State change cache capacity error")]
     CapacityError,
+    #[error(
+        "Can not unwind block {block_number}, the oldest cached block is {oldest_block_number}"
+    )]
+    UnwindPastCache {
+        block_number: u64,
+        oldest_block_number: u64,
+    },
     #[error("Event log error")]
     EventLogError(#[from] EventLogError),
 }
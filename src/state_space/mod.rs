@@ -1,2 +1,4 @@
+pub mod delta;
 pub mod error;
+pub mod sharded;
 pub mod state;
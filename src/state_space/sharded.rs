@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+
+use ethers::types::H160;
+use tokio::sync::{RwLock, RwLockWriteGuard};
+
+use crate::amm::{AutomatedMarketMaker, AMM};
+
+use super::state::StateSpace;
+
+//Number of shards of a ShardedStateSpace, AMMs are assigned to a shard by the first byte of their address
+pub const STATE_SPACE_SHARDS: usize = 64;
+
+//State space split into shards that are locked separately, so that applying the logs of a block to some AMMs does not block reads of
+//the AMMs in other shards. The block lock is held while a block is applied for `snapshot` to read the state at a block boundary.
+#[derive(Debug)]
+pub struct ShardedStateSpace {
+    shards: Vec<RwLock<StateSpace>>,
+    block: RwLock<()>,
+}
+
+impl Default for ShardedStateSpace {
+    fn default() -> Self {
+        ShardedStateSpace {
+            shards: (0..STATE_SPACE_SHARDS)
+                .map(|_| RwLock::new(HashMap::new()))
+                .collect(),
+            block: RwLock::new(()),
+        }
+    }
+}
+
+impl ShardedStateSpace {
+    pub fn new(amms: Vec<AMM>) -> ShardedStateSpace {
+        let mut shards = vec![HashMap::new(); STATE_SPACE_SHARDS];
+        for amm in amms {
+            shards[shard_index(amm.address())].insert(amm.address(), amm);
+        }
+
+        ShardedStateSpace {
+            shards: shards.into_iter().map(RwLock::new).collect(),
+            block: RwLock::new(()),
+        }
+    }
+
+    fn shard(&self, address: H160) -> &RwLock<StateSpace> {
+        &self.shards[shard_index(address)]
+    }
+
+    pub async fn get(&self, address: H160) -> Option<AMM> {
+        self.shard(address).read().await.get(&address).cloned()
+    }
+
+    pub async fn contains(&self, address: H160) -> bool {
+        self.shard(address).read().await.contains_key(&address)
+    }
+
+    //Returns the previous state of the AMM
+    pub async fn insert(&self, amm: AMM) -> Option<AMM> {
+        self.shard(amm.address())
+            .write()
+            .await
+            .insert(amm.address(), amm)
+    }
+
+    //Applies `f` to the AMM under the lock of its shard, None if the AMM is not in the state space
+    pub async fn update<R>(&self, address: H160, f: impl FnOnce(&mut AMM) -> R) -> Option<R> {
+        self.shard(address).write().await.get_mut(&address).map(f)
+    }
+
+    pub async fn extend(&self, amms: impl IntoIterator<Item = AMM>) {
+        for amm in amms {
+            self.insert(amm).await;
+        }
+    }
+
+    pub async fn len(&self) -> usize {
+        let mut len = 0;
+        for shard in &self.shards {
+            len += shard.read().await.len();
+        }
+
+        len
+    }
+
+    pub async fn is_empty(&self) -> bool {
+        self.len().await == 0
+    }
+
+    //Visits the AMMs shard by shard, AMMs of a shard that is already visited can change before the other shards are visited
+    pub async fn for_each(&self, mut f: impl FnMut(&AMM)) {
+        for shard in &self.shards {
+            shard.read().await.values().for_each(&mut f);
+        }
+    }
+
+    //Clones the AMMs that match the predicate
+    pub async fn filter(&self, mut predicate: impl FnMut(&AMM) -> bool) -> Vec<AMM> {
+        let mut amms = vec![];
+        self.for_each(|amm| {
+            if predicate(amm) {
+                amms.push(amm.clone());
+            }
+        })
+        .await;
+
+        amms
+    }
+
+    //Copy of the state space between two blocks, waits for the block that is being applied if there is one
+    pub async fn snapshot(&self) -> StateSpace {
+        let _block = self.block.read().await;
+
+        let mut state_space = HashMap::new();
+        for shard in &self.shards {
+            state_space.extend(
+                shard
+                    .read()
+                    .await
+                    .iter()
+                    .map(|(address, amm)| (*address, amm.clone())),
+            );
+        }
+
+        state_space
+    }
+
+    //Held while the state changes of a block are applied, the lock is not reentrant
+    pub(crate) async fn lock_block(&self) -> RwLockWriteGuard<'_, ()> {
+        self.block.write().await
+    }
+}
+
+impl From<StateSpace> for ShardedStateSpace {
+    fn from(state_space: StateSpace) -> Self {
+        ShardedStateSpace::new(state_space.into_values().collect())
+    }
+}
+
+fn shard_index(address: H160) -> usize {
+    address.0[0] as usize % STATE_SPACE_SHARDS
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::H160;
+
+    use crate::amm::{uniswap_v2::UniswapV2Pool, AutomatedMarketMaker, AMM};
+
+    use super::ShardedStateSpace;
+
+    #[tokio::test]
+    async fn test_sharded_state_space() {
+        //The first byte of the address picks the shard
+        let address = |first_byte: u8| H160([first_byte; 20]);
+        let pool = |address: H160, reserve_0: u128| {
+            AMM::UniswapV2Pool(UniswapV2Pool {
+                address,
+                reserve_0,
+                ..Default::default()
+            })
+        };
+
+        let state = ShardedStateSpace::new(vec![pool(address(1), 10), pool(address(2), 20)]);
+        assert_eq!(state.len().await, 2);
+        assert!(state.contains(address(1)).await);
+        assert!(state.get(address(3)).await.is_none());
+
+        let updated = state
+            .update(address(1), |amm| {
+                if let AMM::UniswapV2Pool(pool) = amm {
+                    pool.reserve_0 = 11;
+                }
+            })
+            .await;
+        assert!(updated.is_some());
+        assert!(state.update(address(3), |_| ()).await.is_none());
+
+        state.insert(pool(address(65), 30)).await;
+        let snapshot = state.snapshot().await;
+        assert_eq!(snapshot.len(), 3);
+        match snapshot.get(&address(1)) {
+            Some(AMM::UniswapV2Pool(pool)) => assert_eq!(pool.reserve_0, 11),
+            _ => panic!("expected a Uniswap V2 pool"),
+        }
+
+        let filtered = state.filter(|amm| amm.address() != address(2)).await;
+        assert_eq!(filtered.len(), 2);
+    }
+}
@@ -1,11 +1,14 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     sync::Arc,
+    time::Duration,
 };
 
 use crate::{
-    amm::{AutomatedMarketMaker, AMM},
+    amm::{balancer_v2, bancor_v3, uniswap_v4, AutomatedMarketMaker, AMM},
     errors::EventLogError,
+    middleware::retry::{backoff_delay_with_jitter, RetryConfig},
+    sync,
 };
 use arraydeque::ArrayDeque;
 use ethers::{
@@ -20,10 +23,21 @@ use tokio::{
     task::JoinHandle,
 };
 
-use super::error::{StateChangeError, StateSpaceError};
+use super::{
+    delta::{AmmStateDelta, StateChangeEvent, StateDelta},
+    error::{StateChangeError, StateSpaceError},
+    sharded::ShardedStateSpace,
+};
+
+//Number of blocks whose state changes are cached, blocks can not be unwound past the oldest cached block
+pub const STATE_CHANGE_CACHE_SIZE: usize = 150;
+
+//Subscription attempts after the block stream ends before the listeners give up, with an exponential backoff from one second
+pub const DEFAULT_RECONNECT_ATTEMPTS: u32 = 10;
+pub const DEFAULT_RECONNECT_DELAY: Duration = Duration::from_secs(1);
 
 pub type StateSpace = HashMap<H160, AMM>;
-pub type StateChangeCache = ArrayDeque<StateChange, 150>;
+pub type StateChangeCache = ArrayDeque<StateChange, STATE_CHANGE_CACHE_SIZE>;
 
 pub trait MiddlewarePubsub: Middleware {
     type PubsubProvider: 'static + PubsubClient;
@@ -43,10 +57,22 @@ where
     M: 'static + Middleware,
     P: 'static + MiddlewarePubsub,
 {
-    pub state: Arc<RwLock<StateSpace>>,
+    //Breaking change: this was an Arc<RwLock<StateSpace>>, see CHANGELOG.md. AMMs are read and written through the ShardedStateSpace
+    //accessors, which only lock the shard of the AMM, and `snapshot` copies the whole state space between two blocks.
+    pub state: Arc<ShardedStateSpace>,
     pub state_change_cache: Arc<RwLock<StateChangeCache>>,
     pub middleware: Arc<M>,
     pub stream_middleware: Arc<P>,
+    //Number of processed blocks whose hashes are kept to detect reorgs, a reorg past the oldest of these blocks is a
+    //StateSpaceError::ReorgTooDeep. At most STATE_CHANGE_CACHE_SIZE.
+    pub reorg_depth: usize,
+    //Number of blocks whose state changes are cached and can be unwound, at most STATE_CHANGE_CACHE_SIZE
+    pub state_change_cache_depth: usize,
+    //Backoff of the attempts to subscribe to new blocks again when the block stream ends, the attempts are counted from the last
+    //streamed block
+    pub reconnect: RetryConfig,
+    //AMMs that the listeners keep up to date, all AMMs of the state space when None
+    pub watched_amms: Arc<RwLock<Option<HashSet<H160>>>>,
 }
 
 impl<M, P> StateSpaceManager<M, P>
@@ -55,44 +81,171 @@ where
     P: MiddlewarePubsub,
 {
     pub fn new(amms: Vec<AMM>, middleware: Arc<M>, stream_middleware: Arc<P>) -> Self {
-        let state: HashMap<H160, AMM> = amms
-            .into_iter()
-            .map(|amm| (amm.address(), amm))
-            .collect::<HashMap<H160, AMM>>();
-
         Self {
-            state: Arc::new(RwLock::new(state)),
+            state: Arc::new(ShardedStateSpace::new(amms)),
             state_change_cache: Arc::new(RwLock::new(ArrayDeque::new())),
             middleware,
             stream_middleware,
+            reorg_depth: STATE_CHANGE_CACHE_SIZE,
+            state_change_cache_depth: STATE_CHANGE_CACHE_SIZE,
+            reconnect: RetryConfig::new(DEFAULT_RECONNECT_ATTEMPTS, DEFAULT_RECONNECT_DELAY),
+            watched_amms: Arc::new(RwLock::new(None)),
         }
     }
 
-    pub async fn get_block_filter(&self) -> Filter {
-        let mut event_signatures: Vec<H256> = vec![];
-        let mut amm_variants = HashSet::new();
-
-        for amm in self.state.read().await.values() {
-            let variant = match amm {
-                AMM::UniswapV2Pool(_) => 0,
-                AMM::UniswapV3Pool(_) => 1,
-                AMM::ERC4626Vault(_) => 2,
-            };
+    pub fn with_reorg_depth(mut self, reorg_depth: usize) -> Self {
+        self.reorg_depth = reorg_depth.min(STATE_CHANGE_CACHE_SIZE);
+        self
+    }
 
-            if !amm_variants.contains(&variant) {
-                amm_variants.insert(variant);
-                event_signatures.extend(amm.sync_on_event_signatures());
-            }
+    pub fn with_reconnect(mut self, reconnect: RetryConfig) -> Self {
+        self.reconnect = reconnect;
+        self
+    }
+
+    //Restricts the logs that the listeners fetch and the state changes that they send to the AMMs, the other AMMs of the state space
+    //are no longer updated. Takes effect from the next block without restarting the listeners. AMMs that are watched again are
+    //not caught up on the blocks they missed and should be synced first.
+    pub async fn watch_amms(&self, amms: impl IntoIterator<Item = H160>) {
+        *self.watched_amms.write().await = Some(amms.into_iter().collect());
+    }
+
+    pub async fn watch_all_amms(&self) {
+        *self.watched_amms.write().await = None;
+    }
+
+    pub fn with_state_change_cache_depth(mut self, state_change_cache_depth: usize) -> Self {
+        self.state_change_cache_depth = state_change_cache_depth.min(STATE_CHANGE_CACHE_SIZE);
+        self
+    }
+
+    //Restores every AMM to its state at the end of `block_number` by unwinding the cached state changes of the later blocks, returns the
+    //AMMs that were restored. The listeners do not replay unwound blocks, so the state space should not be unwound while listening.
+    pub async fn unwind_to_block(&self, block_number: u64) -> Result<Vec<H160>, StateChangeError> {
+        let _block = self.state.lock_block().await;
+        unwind_state_changes(
+            self.state.clone(),
+            self.state_change_cache.clone(),
+            block_number + 1,
+        )
+        .await
+    }
+
+    //Unwinds the `num_blocks` most recent cached blocks
+    pub async fn unwind_last(&self, num_blocks: u64) -> Result<Vec<H160>, StateChangeError> {
+        if num_blocks == 0 {
+            return Ok(vec![]);
         }
 
+        let newest_block_number = self
+            .state_change_cache
+            .read()
+            .await
+            .front()
+            .map(|state_change| state_change.block_number)
+            .ok_or(StateChangeError::NoStateChangesInCache)?;
+
+        let _block = self.state.lock_block().await;
+        unwind_state_changes(
+            self.state.clone(),
+            self.state_change_cache.clone(),
+            (newest_block_number + 1).saturating_sub(num_blocks),
+        )
+        .await
+    }
+
+    pub async fn get_block_filter(&self) -> Filter {
+        let mut event_signatures: HashSet<H256> = HashSet::new();
+
+        //Event signatures can differ between AMMs of the same variant (ex. Curve pools with a different number of coins)
+        self.state
+            .for_each(|amm| event_signatures.extend(amm.sync_on_event_signatures()))
+            .await;
+
         //Create a new filter
-        Filter::new().topic0(event_signatures)
+        Filter::new().topic0(event_signatures.into_iter().collect::<Vec<H256>>())
+    }
+
+    async fn block_processor(&self, last_synced_block: u64) -> BlockProcessor<M> {
+        BlockProcessor {
+            watched_amms: self.watched_amms.clone(),
+            ..BlockProcessor::new(
+                self.state.clone(),
+                self.state_change_cache.clone(),
+                self.middleware.clone(),
+                self.get_block_filter().await,
+                last_synced_block,
+                self.reorg_depth,
+                self.state_change_cache_depth,
+            )
+        }
+    }
+
+    fn subscribe_blocks(
+        &self,
+        channel_buffer: usize,
+    ) -> (
+        Receiver<Block<H256>>,
+        JoinHandle<Result<(), StateSpaceError<M, P>>>,
+    )
+    where
+        <P as Middleware>::Provider: PubsubClient,
+    {
+        let stream_middleware: Arc<P> = self.stream_middleware.clone();
+        let reconnect = self.reconnect;
+
+        let (stream_tx, stream_rx): (Sender<Block<H256>>, Receiver<Block<H256>>) =
+            tokio::sync::mpsc::channel(channel_buffer);
+
+        //Blocks missed while the stream is down are caught up by the block processor from the next streamed head
+        let stream_handle = tokio::spawn(async move {
+            let mut failed_attempts = 0;
+
+            loop {
+                let error = match stream_middleware.subscribe_blocks().await {
+                    Ok(mut block_stream) => {
+                        while let Some(block) = block_stream.next().await {
+                            failed_attempts = 0;
+                            stream_tx.send(block).await?;
+                        }
+
+                        None
+                    }
+                    Err(error) => Some(error),
+                };
+
+                failed_attempts += 1;
+                if failed_attempts >= reconnect.max_attempts {
+                    tracing::error!(failed_attempts, "could not resubscribe to new blocks");
+
+                    return match error {
+                        Some(error) => Err(StateSpaceError::PubsubClientError(error)),
+                        None => Ok(()),
+                    };
+                }
+
+                let delay = backoff_delay_with_jitter(
+                    reconnect.base_delay,
+                    failed_attempts,
+                    reconnect.jitter,
+                );
+                tracing::warn!(
+                    failed_attempts,
+                    ?delay,
+                    ?error,
+                    "block stream ended, resubscribing"
+                );
+                tokio::time::sleep(delay).await;
+            }
+        });
+
+        (stream_rx, stream_handle)
     }
 
     /// Listens to new blocks and handles state changes, sending an H256 block hash when a new block is produced.
     pub async fn listen_for_new_blocks(
         &self,
-        mut last_synced_block: u64,
+        last_synced_block: u64,
         channel_buffer: usize,
     ) -> Result<
         (
@@ -110,89 +263,18 @@ where
             "listening for new blocks"
         );
 
-        let state = self.state.clone();
-        let middleware = self.middleware.clone();
-        let stream_middleware: Arc<P> = self.stream_middleware.clone();
-        let filter = self.get_block_filter().await;
-
-        let (stream_tx, mut stream_rx): (Sender<Block<H256>>, Receiver<Block<H256>>) =
-            tokio::sync::mpsc::channel(channel_buffer);
-
-        let stream_handle = tokio::spawn(async move {
-            let mut block_stream = stream_middleware
-                .subscribe_blocks()
-                .await
-                .map_err(StateSpaceError::PubsubClientError)?;
-
-            while let Some(block) = block_stream.next().await {
-                stream_tx.send(block).await?;
-            }
-
-            Ok::<(), StateSpaceError<M, P>>(())
-        });
+        let mut block_processor = self.block_processor(last_synced_block).await;
+        let (mut stream_rx, stream_handle) = self.subscribe_blocks(channel_buffer);
 
         let (new_block_tx, new_block_rx) = tokio::sync::mpsc::channel(channel_buffer);
 
-        let state_change_cache = self.state_change_cache.clone();
         let new_block_handle: JoinHandle<Result<(), StateSpaceError<M, P>>> =
             tokio::spawn(async move {
                 while let Some(block) = stream_rx.recv().await {
                     tracing::info!(?block, "received new block");
-                    if let Some(chain_head_block_number) = block.number {
-                        let chain_head_block_number = chain_head_block_number.as_u64();
-
-                        //If there is a reorg, unwind state changes from last_synced block to the chain head block number
-                        if chain_head_block_number <= last_synced_block {
-                            tracing::trace!(
-                                chain_head_block_number,
-                                last_synced_block,
-                                "reorg detected, unwinding state changes"
-                            );
-                            unwind_state_changes(
-                                state.clone(),
-                                state_change_cache.clone(),
-                                chain_head_block_number,
-                            )
-                            .await?;
+                    block_processor.process_block::<P>(&block).await?;
 
-                            last_synced_block = chain_head_block_number - 1;
-                        }
-
-                        let from_block: u64 = last_synced_block + 1;
-                        let logs = middleware
-                            .get_logs(
-                                &filter
-                                    .clone()
-                                    .from_block(from_block)
-                                    .to_block(chain_head_block_number),
-                            )
-                            .await
-                            .map_err(StateSpaceError::MiddlewareError)?;
-
-                        if logs.is_empty() {
-                            for block_number in from_block..=chain_head_block_number {
-                                add_state_change_to_cache(
-                                    state_change_cache.clone(),
-                                    StateChange::new(None, block_number),
-                                )
-                                .await?;
-                            }
-                        } else {
-                            handle_state_changes_from_logs(
-                                state.clone(),
-                                state_change_cache.clone(),
-                                logs,
-                                middleware.clone(),
-                            )
-                            .await?;
-                        }
-
-                        last_synced_block = chain_head_block_number;
-
-                        new_block_tx.send(block).await?;
-                    } else {
-                        return Err(StateSpaceError::BlockNumberNotFound);
-                    }
+                    new_block_tx.send(block).await?;
                 }
 
                 Ok::<(), StateSpaceError<M, P>>(())
@@ -204,7 +286,7 @@ where
     /// Listens to new blocks and handles state changes, sending a Vec<H160> containing each AMM address that incurred a state change in the block.
     pub async fn listen_for_state_changes(
         &self,
-        mut last_synced_block: u64,
+        last_synced_block: u64,
         channel_buffer: usize,
     ) -> Result<
         (
@@ -222,103 +304,78 @@ where
             "listening for state changes"
         );
 
-        let state = self.state.clone();
-        let middleware = self.middleware.clone();
-        let stream_middleware: Arc<P> = self.stream_middleware.clone();
-        let filter = self.get_block_filter().await;
+        let mut block_processor = self.block_processor(last_synced_block).await;
+        let (mut stream_rx, stream_handle) = self.subscribe_blocks(channel_buffer);
 
-        let (stream_tx, mut stream_rx): (Sender<Block<H256>>, Receiver<Block<H256>>) =
-            tokio::sync::mpsc::channel(channel_buffer);
+        let (amms_updated_tx, amms_updated_rx) = tokio::sync::mpsc::channel(channel_buffer);
 
-        let stream_handle = tokio::spawn(async move {
-            let mut block_stream = stream_middleware
-                .subscribe_blocks()
-                .await
-                .map_err(StateSpaceError::PubsubClientError)?;
+        let updated_amms_handle: JoinHandle<Result<(), StateSpaceError<M, P>>> =
+            tokio::spawn(async move {
+                while let Some(block) = stream_rx.recv().await {
+                    tracing::info!(?block, "received new block");
+                    let amms_updated = block_processor.process_block::<P>(&block).await?;
 
-            while let Some(block) = block_stream.next().await {
-                stream_tx.send(block).await?;
-            }
+                    if !amms_updated.is_empty() {
+                        amms_updated_tx.send(amms_updated).await?;
+                    }
+                }
 
-            Ok::<(), StateSpaceError<M, P>>(())
-        });
+                Ok::<(), StateSpaceError<M, P>>(())
+            });
 
-        let (amms_updated_tx, amms_updated_rx) = tokio::sync::mpsc::channel(channel_buffer);
+        Ok((amms_updated_rx, vec![stream_handle, updated_amms_handle]))
+    }
 
-        let state_change_cache = self.state_change_cache.clone();
+    /// Listens to new blocks and handles state changes, sending a StateChangeEvent with the prior and new values of each AMM that incurred a state change.
+    /// listen_for_state_changes only sends the addresses and skips reading the prior states from the cache.
+    pub async fn listen_for_state_change_events(
+        &self,
+        last_synced_block: u64,
+        channel_buffer: usize,
+    ) -> Result<
+        (
+            Receiver<StateChangeEvent>,
+            Vec<JoinHandle<Result<(), StateSpaceError<M, P>>>>,
+        ),
+        StateSpaceError<M, P>,
+    >
+    where
+        <P as Middleware>::Provider: PubsubClient,
+    {
+        tracing::info!(
+            last_synced_block,
+            channel_buffer,
+            "listening for state change events"
+        );
 
-        let updated_amms_handle: JoinHandle<Result<(), StateSpaceError<M, P>>> =
+        let mut block_processor = self.block_processor(last_synced_block).await;
+        let (mut stream_rx, stream_handle) = self.subscribe_blocks(channel_buffer);
+
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(channel_buffer);
+
+        let event_handle: JoinHandle<Result<(), StateSpaceError<M, P>>> =
             tokio::spawn(async move {
                 while let Some(block) = stream_rx.recv().await {
                     tracing::info!(?block, "received new block");
-                    if let Some(chain_head_block_number) = block.number {
-                        let chain_head_block_number = chain_head_block_number.as_u64();
-
-                        //If there is a reorg, unwind state changes from last_synced block to the chain head block number
-                        if chain_head_block_number <= last_synced_block {
-                            tracing::trace!(
-                                chain_head_block_number,
-                                last_synced_block,
-                                "reorg detected, unwinding state changes"
-                            );
-                            unwind_state_changes(
-                                state.clone(),
-                                state_change_cache.clone(),
-                                chain_head_block_number,
-                            )
-                            .await?;
-
-                            //set the last synced block to the head block number
-                            last_synced_block = chain_head_block_number - 1;
-                        }
+                    let amms_updated = block_processor.process_block::<P>(&block).await?;
 
-                        let from_block: u64 = last_synced_block + 1;
-                        let logs = middleware
-                            .get_logs(
-                                &filter
-                                    .clone()
-                                    .from_block(from_block)
-                                    .to_block(chain_head_block_number),
-                            )
-                            .await
-                            .map_err(StateSpaceError::MiddlewareError)?;
-
-                        if logs.is_empty() {
-                            for block_number in from_block..=chain_head_block_number {
-                                add_state_change_to_cache(
-                                    state_change_cache.clone(),
-                                    StateChange::new(None, block_number),
-                                )
-                                .await?;
-                            }
-                        } else {
-                            let amms_updated = handle_state_changes_from_logs(
-                                state.clone(),
-                                state_change_cache.clone(),
-                                logs,
-                                middleware.clone(),
-                            )
+                    if !amms_updated.is_empty() {
+                        event_tx
+                            .send(block_processor.state_change_event(amms_updated).await)
                             .await?;
-
-                            amms_updated_tx.send(amms_updated).await?;
-                        }
-
-                        last_synced_block = chain_head_block_number;
-                    } else {
-                        return Err(StateSpaceError::BlockNumberNotFound);
                     }
                 }
 
                 Ok::<(), StateSpaceError<M, P>>(())
             });
 
-        Ok((amms_updated_rx, vec![stream_handle, updated_amms_handle]))
+        Ok((event_rx, vec![stream_handle, event_handle]))
     }
 
     /// Listens to new blocks and handles state changes without sending notifications through a channel when AMMs are updated.
     pub async fn listen_for_updates(
         &self,
-        mut last_synced_block: u64,
+        last_synced_block: u64,
         channel_buffer: usize,
     ) -> Result<Vec<JoinHandle<Result<(), StateSpaceError<M, P>>>>, StateSpaceError<M, P>>
     where
@@ -326,91 +383,350 @@ where
     {
         tracing::info!(last_synced_block, channel_buffer, "listening for updates");
 
-        let state = self.state.clone();
-        let middleware = self.middleware.clone();
-        let stream_middleware: Arc<P> = self.stream_middleware.clone();
-        let filter = self.get_block_filter().await;
-
-        let (stream_tx, mut stream_rx): (Sender<Block<H256>>, Receiver<Block<H256>>) =
-            tokio::sync::mpsc::channel(channel_buffer);
-
-        let stream_handle = tokio::spawn(async move {
-            let mut block_stream = stream_middleware
-                .subscribe_blocks()
-                .await
-                .map_err(StateSpaceError::PubsubClientError)?;
-
-            while let Some(block) = block_stream.next().await {
-                stream_tx.send(block).await?;
-            }
-
-            Ok::<(), StateSpaceError<M, P>>(())
-        });
+        let mut block_processor = self.block_processor(last_synced_block).await;
+        let (mut stream_rx, stream_handle) = self.subscribe_blocks(channel_buffer);
 
-        let state_change_cache = self.state_change_cache.clone();
         let new_block_handle: JoinHandle<Result<(), StateSpaceError<M, P>>> =
             tokio::spawn(async move {
                 while let Some(block) = stream_rx.recv().await {
                     tracing::info!(?block, "received new block");
-                    if let Some(chain_head_block_number) = block.number {
-                        let chain_head_block_number = chain_head_block_number.as_u64();
-
-                        //If there is a reorg, unwind state changes from last_synced block to the chain head block number
-                        if chain_head_block_number <= last_synced_block {
-                            tracing::trace!(
-                                chain_head_block_number,
-                                last_synced_block,
-                                "reorg detected, unwinding state changes"
-                            );
-                            unwind_state_changes(
-                                state.clone(),
-                                state_change_cache.clone(),
-                                chain_head_block_number,
-                            )
-                            .await?;
+                    block_processor.process_block::<P>(&block).await?;
+                }
 
-                            last_synced_block = chain_head_block_number - 1;
-                        }
+                Ok::<(), StateSpaceError<M, P>>(())
+            });
 
-                        let from_block: u64 = last_synced_block + 1;
-                        let logs = middleware
-                            .get_logs(
-                                &filter
-                                    .clone()
-                                    .from_block(from_block)
-                                    .to_block(chain_head_block_number),
-                            )
-                            .await
-                            .map_err(StateSpaceError::MiddlewareError)?;
-
-                        if logs.is_empty() {
-                            for block_number in from_block..=chain_head_block_number {
-                                add_state_change_to_cache(
-                                    state_change_cache.clone(),
-                                    StateChange::new(None, block_number),
-                                )
-                                .await?;
-                            }
-                        } else {
-                            handle_state_changes_from_logs(
-                                state.clone(),
-                                state_change_cache.clone(),
-                                logs,
-                                middleware.clone(),
-                            )
-                            .await?;
-                        }
+        Ok(vec![stream_handle, new_block_handle])
+    }
+}
 
-                        last_synced_block = chain_head_block_number;
-                    } else {
-                        return Err(StateSpaceError::BlockNumberNotFound);
-                    }
+//Applies the state changes of each new head to the state space. The hashes of the last `reorg_depth` processed blocks are kept so
+//that a head whose parent is not a processed block is detected as a reorg, in which case the blocks after the common ancestor of both
+//chains are unwound and their logs are applied again from the new chain.
+struct BlockProcessor<M: Middleware> {
+    state: Arc<ShardedStateSpace>,
+    state_change_cache: Arc<RwLock<StateChangeCache>>,
+    middleware: Arc<M>,
+    filter: Filter,
+    last_synced_block: u64,
+    //First block whose state changes were applied for the last head, later than the head if the state space was repopulated
+    processed_from_block: u64,
+    //Newest block first
+    block_hashes: VecDeque<(u64, H256)>,
+    reorg_depth: usize,
+    state_change_cache_depth: usize,
+    watched_amms: Arc<RwLock<Option<HashSet<H160>>>>,
+}
+
+impl<M: Middleware> BlockProcessor<M> {
+    fn new(
+        state: Arc<ShardedStateSpace>,
+        state_change_cache: Arc<RwLock<StateChangeCache>>,
+        middleware: Arc<M>,
+        filter: Filter,
+        last_synced_block: u64,
+        reorg_depth: usize,
+        state_change_cache_depth: usize,
+    ) -> Self {
+        Self {
+            state,
+            state_change_cache,
+            middleware,
+            filter,
+            last_synced_block,
+            processed_from_block: last_synced_block + 1,
+            block_hashes: VecDeque::new(),
+            reorg_depth,
+            state_change_cache_depth,
+            watched_amms: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    //Returns the AMMs that incurred a state change in the blocks from the last synced block to the head
+    async fn process_block<P: MiddlewarePubsub>(
+        &mut self,
+        block: &Block<H256>,
+    ) -> Result<Vec<H160>, StateSpaceError<M, P>> {
+        let chain_head_block_number = block
+            .number
+            .ok_or(StateSpaceError::BlockNumberNotFound)?
+            .as_u64();
+
+        let missed_blocks = chain_head_block_number.saturating_sub(self.last_synced_block + 1);
+        if missed_blocks > 0 {
+            tracing::warn!(
+                missed_blocks,
+                last_synced_block = self.last_synced_block,
+                chain_head_block_number,
+                "catching up on missed blocks"
+            );
+        }
+
+        //The state changes of a gap this long can not all be cached, so the blocks of the gap could not be unwound
+        if missed_blocks >= self.state_change_cache_depth as u64 {
+            return self.repopulate::<P>(block, chain_head_block_number).await;
+        }
+
+        let common_ancestor = self
+            .find_common_ancestor(chain_head_block_number, block.parent_hash)
+            .await?;
+
+        //The blocks after the common ancestor are unwound below, their logs are fetched again from the new chain
+        let from_block: u64 = self.last_synced_block.min(common_ancestor) + 1;
+        let watched_amms = self.watched_amms.read().await.clone();
+        let filter = match &watched_amms {
+            Some(watched_amms) => {
+                let mut amms = vec![];
+                for address in watched_amms {
+                    amms.extend(self.state.get(*address).await);
                 }
 
-                Ok::<(), StateSpaceError<M, P>>(())
+                watched_amms_filter(&amms)
+            }
+            None => Some(self.filter.clone()),
+        };
+
+        let mut logs = match filter {
+            Some(filter) => self
+                .middleware
+                .get_logs(
+                    &filter
+                        .from_block(from_block)
+                        .to_block(chain_head_block_number),
+                )
+                .await
+                .map_err(StateSpaceError::MiddlewareError)?,
+            None => vec![],
+        };
+
+        //Singleton contracts emit the logs of watched and unwatched AMMs alike
+        if let Some(watched_amms) = &watched_amms {
+            let dependent_amms = get_sharded_dependent_amms(&self.state).await;
+            logs.retain(|log| {
+                get_amm_addresses_from_log(log)
+                    .iter()
+                    .chain(dependent_amms.get(&log.address).into_iter().flatten())
+                    .any(|address| watched_amms.contains(address))
             });
+        }
 
-        Ok(vec![stream_handle, new_block_handle])
+        //AMMs without events are synced before the block is applied, the state space is only locked while the state changes are written
+        let synced_amms =
+            sync_amms_without_events_at_head::<M, P>(&self.state, self.middleware.clone()).await?;
+
+        //Snapshots of the state space wait until the block is applied
+        let _block = self.state.lock_block().await;
+
+        //If there is a reorg, unwind state changes from the last synced block to the common ancestor of both chains
+        if common_ancestor < self.last_synced_block {
+            tracing::warn!(
+                chain_head_block_number,
+                common_ancestor,
+                last_synced_block = self.last_synced_block,
+                "reorg detected, unwinding state changes"
+            );
+
+            match unwind_state_changes(
+                self.state.clone(),
+                self.state_change_cache.clone(),
+                common_ancestor + 1,
+            )
+            .await
+            {
+                Err(
+                    StateChangeError::NoStateChangesInCache
+                    | StateChangeError::UnwindPastCache { .. },
+                ) => {
+                    return Err(StateSpaceError::ReorgTooDeep {
+                        block_number: chain_head_block_number,
+                        depth: chain_head_block_number - common_ancestor,
+                    })
+                }
+                result => {
+                    result?;
+                }
+            }
+
+            self.block_hashes
+                .retain(|(block_number, _)| *block_number <= common_ancestor);
+            self.last_synced_block = common_ancestor;
+        }
+
+        self.processed_from_block = from_block;
+        let mut amms_updated = if logs.is_empty() {
+            for block_number in from_block..=chain_head_block_number {
+                add_state_change_to_cache(
+                    self.state_change_cache.clone(),
+                    StateChange::new(None, block_number),
+                )
+                .await?;
+            }
+
+            vec![]
+        } else {
+            handle_state_changes_from_logs(
+                self.state.clone(),
+                self.state_change_cache.clone(),
+                logs,
+                self.middleware.clone(),
+            )
+            .await?
+        };
+
+        amms_updated.extend(
+            apply_amms_without_events(
+                self.state.clone(),
+                self.state_change_cache.clone(),
+                synced_amms,
+                chain_head_block_number,
+            )
+            .await?,
+        );
+
+        if let Some(watched_amms) = &watched_amms {
+            amms_updated.retain(|address| watched_amms.contains(address));
+        }
+
+        self.last_synced_block = chain_head_block_number;
+        if let Some(block_hash) = block.hash {
+            self.block_hashes
+                .push_front((chain_head_block_number, block_hash));
+            self.block_hashes.truncate(self.reorg_depth);
+        }
+
+        let mut state_change_cache = self.state_change_cache.write().await;
+        while state_change_cache.len() > self.state_change_cache_depth {
+            state_change_cache.pop_back();
+        }
+
+        Ok(amms_updated)
+    }
+
+    //Populates every AMM again at the head instead of applying the logs of the missed blocks. The cached state changes and block
+    //hashes are from before the gap and are cleared, so the blocks up to the head can not be unwound afterwards.
+    async fn repopulate<P: MiddlewarePubsub>(
+        &mut self,
+        block: &Block<H256>,
+        chain_head_block_number: u64,
+    ) -> Result<Vec<H160>, StateSpaceError<M, P>> {
+        tracing::warn!(
+            chain_head_block_number,
+            "gap exceeds the state change cache, repopulating the state space"
+        );
+
+        let watched_amms = self.watched_amms.read().await.clone();
+        let amms = self
+            .state
+            .filter(|amm| {
+                watched_amms
+                    .as_ref()
+                    .map_or(true, |watched_amms| watched_amms.contains(&amm.address()))
+            })
+            .await;
+
+        let mut amms_updated = vec![];
+        let mut populated_amms = vec![];
+        for mut amms in sync::checkpoint::sort_amms(amms) {
+            sync::populate_amms(&mut amms, chain_head_block_number, self.middleware.clone())
+                .await?;
+
+            amms_updated.extend(amms.iter().map(|amm| amm.address()));
+            populated_amms.extend(amms);
+        }
+
+        let _block = self.state.lock_block().await;
+        self.state.extend(populated_amms).await;
+
+        self.state_change_cache.write().await.clear();
+        self.block_hashes.clear();
+        self.processed_from_block = chain_head_block_number + 1;
+        if let Some(block_hash) = block.hash {
+            self.block_hashes
+                .push_front((chain_head_block_number, block_hash));
+        }
+        self.last_synced_block = chain_head_block_number;
+
+        Ok(amms_updated)
+    }
+
+    //Deltas of the AMMs updated by the last head, from their state before the first processed block in the cache to their current state
+    async fn state_change_event(&self, amms_updated: Vec<H160>) -> StateChangeEvent {
+        let mut prior_states = HashMap::new();
+        for state_change in self.state_change_cache.read().await.iter() {
+            if state_change.block_number < self.processed_from_block {
+                break;
+            }
+
+            //Blocks are visited from the newest and the states of a block from the last, the earliest state is inserted last
+            for amm in state_change.state_change.iter().flatten().rev() {
+                prior_states.insert(amm.address(), amm.clone());
+            }
+        }
+
+        let mut changes = vec![];
+        for address in amms_updated {
+            let Some(after) = self.state.get(address).await else {
+                continue;
+            };
+
+            let delta = match prior_states.get(&address) {
+                Some(before) => StateDelta::new(before, &after),
+                None => StateDelta::Other {
+                    before: None,
+                    after: Box::new(after),
+                },
+            };
+
+            changes.push(AmmStateDelta { address, delta });
+        }
+
+        StateChangeEvent {
+            block_number: self.last_synced_block,
+            changes,
+        }
+    }
+
+    //Walks the parents of the new head back to the newest processed block that is on the same chain. Heads that skip blocks are
+    //walked back to the processed blocks, the walk fails when the oldest processed block is not on the chain of the head.
+    async fn find_common_ancestor<P: MiddlewarePubsub>(
+        &self,
+        chain_head_block_number: u64,
+        parent_hash: H256,
+    ) -> Result<u64, StateSpaceError<M, P>> {
+        let Some(&(oldest_block_number, _)) = self.block_hashes.back() else {
+            //Without processed blocks, a head at or below the last synced block is treated as a reorg to its parent
+            return Ok(self
+                .last_synced_block
+                .min(chain_head_block_number.saturating_sub(1)));
+        };
+
+        let mut block_number = chain_head_block_number.saturating_sub(1);
+        let mut block_hash = parent_hash;
+        loop {
+            if self
+                .block_hashes
+                .iter()
+                .any(|processed_block| *processed_block == (block_number, block_hash))
+            {
+                return Ok(block_number);
+            }
+
+            if block_number <= oldest_block_number {
+                return Err(StateSpaceError::ReorgTooDeep {
+                    block_number: chain_head_block_number,
+                    depth: chain_head_block_number - block_number,
+                });
+            }
+
+            block_hash = self
+                .middleware
+                .get_block(block_hash)
+                .await
+                .map_err(StateSpaceError::MiddlewareError)?
+                .ok_or(StateSpaceError::BlockNumberNotFound)?
+                .parent_hash;
+            block_number -= 1;
+        }
     }
 }
 
@@ -435,38 +751,56 @@ impl StateChange {
     }
 }
 
-//Unwinds the state changes cache for every block from the most recent state change cache back to the block to unwind -1
+//Unwinds the state changes cache for every block from the most recent state change cache back to the block to unwind, returns the
+//addresses of the restored AMMs. Nothing is unwound if the cache does not reach back to the block to unwind.
 async fn unwind_state_changes(
-    state: Arc<RwLock<StateSpace>>,
+    state: Arc<ShardedStateSpace>,
     state_change_cache: Arc<RwLock<StateChangeCache>>,
     block_to_unwind: u64,
-) -> Result<(), StateChangeError> {
+) -> Result<Vec<H160>, StateChangeError> {
     let mut state_change_cache = state_change_cache.write().await;
 
-    loop {
-        //check if the most recent state change block is >= the block to unwind,
-        if let Some(state_change) = state_change_cache.get(0) {
-            if state_change.block_number >= block_to_unwind {
-                if let Some(option_state_changes) = state_change_cache.pop_front() {
-                    if let Some(state_changes) = option_state_changes.state_change {
-                        for amm_state in state_changes {
-                            state.write().await.insert(amm_state.address(), amm_state);
-                        }
-                    }
-                } else {
-                    //We know that there is a state change from state_change_cache.get(0) so when we pop front without returning a value, there is an issue
-                    return Err(StateChangeError::PopFrontError);
+    //We return an error here because we never want to be unwinding past where we have state changes.
+    //For example, if you initialize a state space that syncs to block 100, then immediately after there is a chain reorg to 95, we can not roll back the state
+    //changes for an accurate state space. In this case, we return an error
+    match state_change_cache.back() {
+        None => return Err(StateChangeError::NoStateChangesInCache),
+        Some(oldest_state_change) if oldest_state_change.block_number > block_to_unwind => {
+            return Err(StateChangeError::UnwindPastCache {
+                block_number: block_to_unwind,
+                oldest_block_number: oldest_state_change.block_number,
+            })
+        }
+        Some(_) => {}
+    }
+
+    let mut unwound_amms_set = HashSet::new();
+    let mut unwound_amms = vec![];
+
+    //check if the most recent state change block is >= the block to unwind,
+    while let Some(state_change) = state_change_cache.front() {
+        if state_change.block_number < block_to_unwind {
+            break;
+        }
+
+        //We know that there is a state change from state_change_cache.front() so when we pop front without returning a value, there is an issue
+        let option_state_changes = state_change_cache
+            .pop_front()
+            .ok_or(StateChangeError::PopFrontError)?;
+
+        //A block can hold several states of an AMM, the state before its first change is restored last
+        if let Some(state_changes) = option_state_changes.state_change {
+            for amm_state in state_changes.into_iter().rev() {
+                if unwound_amms_set.insert(amm_state.address()) {
+                    unwound_amms.push(amm_state.address());
                 }
-            } else {
-                return Ok(());
+
+                state.insert(amm_state).await;
             }
-        } else {
-            //We return an error here because we never want to be unwinding past where we have state changes.
-            //For example, if you initialize a state space that syncs to block 100, then immediately after there is a chain reorg to 95, we can not roll back the state
-            //changes for an accurate state space. In this case, we return an error
-            return Err(StateChangeError::NoStateChangesInCache);
         }
     }
+
+    Ok(unwound_amms)
 }
 
 async fn add_state_change_to_cache(
@@ -489,7 +823,7 @@ async fn add_state_change_to_cache(
 }
 
 pub async fn handle_state_changes_from_logs<M: Middleware>(
-    state: Arc<RwLock<StateSpace>>,
+    state: Arc<ShardedStateSpace>,
     state_change_cache: Arc<RwLock<StateChangeCache>>,
     logs: Vec<Log>,
     _middleware: Arc<M>,
@@ -497,6 +831,7 @@ pub async fn handle_state_changes_from_logs<M: Middleware>(
     let mut updated_amms_set = HashSet::new();
     let mut updated_amms = vec![];
     let mut state_changes = vec![];
+    let dependent_amms = get_sharded_dependent_amms(&state).await;
 
     let mut last_log_block_number = if let Some(log) = logs.get(0) {
         get_block_number_from_log(log)?
@@ -507,18 +842,7 @@ pub async fn handle_state_changes_from_logs<M: Middleware>(
     for log in logs.into_iter() {
         let log_block_number = get_block_number_from_log(&log)?;
 
-        // check if the log is from an amm in the state space
-        if let Some(amm) = state.write().await.get_mut(&log.address) {
-            if !updated_amms_set.contains(&log.address) {
-                updated_amms_set.insert(log.address);
-                updated_amms.push(log.address);
-            }
-
-            state_changes.push(amm.clone());
-            amm.sync_from_log(log)?;
-        }
-
-        //Commit state changes if the block has changed since last log
+        //Commit state changes if the block has changed since last log, before the log changes the state of the new block
         if log_block_number != last_log_block_number {
             if state_changes.is_empty() {
                 add_state_change_to_cache(
@@ -537,6 +861,30 @@ pub async fn handle_state_changes_from_logs<M: Middleware>(
 
             last_log_block_number = log_block_number;
         }
+
+        let mut amm_addresses = get_amm_addresses_from_log(&log);
+        if let Some(dependents) = dependent_amms.get(&log.address) {
+            amm_addresses.extend(dependents);
+        }
+
+        for amm_address in amm_addresses {
+            // check if the log is from an amm in the state space
+            let previous_state = state
+                .update(amm_address, |amm| {
+                    let previous_state = amm.clone();
+                    amm.sync_from_log(log.clone()).map(|_| previous_state)
+                })
+                .await;
+
+            if let Some(previous_state) = previous_state {
+                if !updated_amms_set.contains(&amm_address) {
+                    updated_amms_set.insert(amm_address);
+                    updated_amms.push(amm_address);
+                }
+
+                state_changes.push(previous_state?);
+            }
+        }
     }
 
     if state_changes.is_empty() {
@@ -556,6 +904,184 @@ pub async fn handle_state_changes_from_logs<M: Middleware>(
     Ok(updated_amms)
 }
 
+//AMMs that do not sync from logs, ex. rate providers whose rate accrues without an event, are synced at every new block.
+//Their previous states are added to the state change of the block so that they are restored when the block is unwound
+pub async fn sync_amms_without_events<M: Middleware, P: MiddlewarePubsub>(
+    state: Arc<ShardedStateSpace>,
+    state_change_cache: Arc<RwLock<StateChangeCache>>,
+    block_number: u64,
+    middleware: Arc<M>,
+) -> Result<Vec<H160>, StateSpaceError<M, P>> {
+    let synced_amms = sync_amms_without_events_at_head::<M, P>(&state, middleware).await?;
+
+    Ok(apply_amms_without_events(state, state_change_cache, synced_amms, block_number).await?)
+}
+
+//Syncs copies of the AMMs that do not sync from logs at the head, the state space is not written to
+async fn sync_amms_without_events_at_head<M: Middleware, P: MiddlewarePubsub>(
+    state: &ShardedStateSpace,
+    middleware: Arc<M>,
+) -> Result<Vec<AMM>, StateSpaceError<M, P>> {
+    let mut amms = state
+        .filter(|amm| amm.sync_on_event_signatures().is_empty())
+        .await;
+
+    for amm in amms.iter_mut() {
+        amm.sync(middleware.clone()).await?;
+    }
+
+    Ok(amms)
+}
+
+//Writes the AMMs synced by sync_amms_without_events_at_head to the state space and adds the states they replace to the state change
+//of the block
+async fn apply_amms_without_events(
+    state: Arc<ShardedStateSpace>,
+    state_change_cache: Arc<RwLock<StateChangeCache>>,
+    synced_amms: Vec<AMM>,
+    block_number: u64,
+) -> Result<Vec<H160>, StateChangeError> {
+    if synced_amms.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut updated_amms = vec![];
+    let mut state_changes = vec![];
+    for amm in synced_amms {
+        updated_amms.push(amm.address());
+        state_changes.extend(state.insert(amm).await);
+    }
+
+    //Logs of the block may already have added a state change for it
+    if let Some(state_change) = state_change_cache.write().await.front_mut() {
+        if state_change.block_number == block_number {
+            state_change
+                .state_change
+                .get_or_insert_with(Vec::new)
+                .extend(state_changes);
+
+            return Ok(updated_amms);
+        }
+    }
+
+    add_state_change_to_cache(
+        state_change_cache,
+        StateChange::new(Some(state_changes), block_number),
+    )
+    .await?;
+
+    Ok(updated_amms)
+}
+
+//Returns the address of the AMM that the log belongs to. AMMs that live in a singleton vault emit their events from the vault,
+//in which case the AMM address is derived from the pool id in the log topics
+pub fn get_amm_address_from_log(log: &Log) -> H160 {
+    let event_signature = log.topics[0];
+
+    if (event_signature == balancer_v2::SWAP_EVENT_SIGNATURE
+        || event_signature == balancer_v2::POOL_BALANCE_CHANGED_EVENT_SIGNATURE)
+        && log.topics.len() > 1
+    {
+        balancer_v2::pool_address_from_pool_id(log.topics[1])
+    } else if (event_signature == uniswap_v4::SWAP_EVENT_SIGNATURE
+        || event_signature == uniswap_v4::MODIFY_LIQUIDITY_EVENT_SIGNATURE)
+        && log.topics.len() > 1
+    {
+        uniswap_v4::pool_address_from_pool_id(log.topics[1])
+    } else {
+        log.address
+    }
+}
+
+//Contracts that emit the logs the AMM syncs from, None when the emitter is not known from the state of the AMM, i.e. the Bancor V3
+//network that the pools do not keep the address of
+pub fn get_amm_log_addresses(amm: &AMM) -> Option<Vec<H160>> {
+    let mut addresses = match amm {
+        AMM::BalancerV2WeightedPool(pool) => vec![pool.vault],
+        AMM::BalancerStablePool(pool) => vec![pool.vault],
+        AMM::UniswapV4Pool(pool) => vec![pool.pool_manager],
+        AMM::BancorV3Pool(_) => return None,
+        amm => vec![amm.address()],
+    };
+    addresses.extend(amm.dependency());
+
+    Some(addresses)
+}
+
+//Filter over the sync events of the watched AMMs, restricted to the contracts that emit them when all of them are known.
+//None if no watched AMM syncs from logs, an empty topic would match the logs of every event.
+fn watched_amms_filter(watched_amms: &[AMM]) -> Option<Filter> {
+    let mut event_signatures: HashSet<H256> = HashSet::new();
+    let mut addresses: Option<HashSet<H160>> = Some(HashSet::new());
+
+    for amm in watched_amms {
+        let amm_event_signatures = amm.sync_on_event_signatures();
+        if amm_event_signatures.is_empty() {
+            continue;
+        }
+        event_signatures.extend(amm_event_signatures);
+
+        match (get_amm_log_addresses(amm), addresses.as_mut()) {
+            (Some(amm_addresses), Some(addresses)) => addresses.extend(amm_addresses),
+            _ => addresses = None,
+        }
+    }
+
+    if event_signatures.is_empty() {
+        return None;
+    }
+
+    let filter = Filter::new().topic0(event_signatures.into_iter().collect::<Vec<H256>>());
+    Some(match addresses {
+        Some(addresses) => filter.address(addresses.into_iter().collect::<Vec<H160>>()),
+        None => filter,
+    })
+}
+
+//Same as get_dependent_amms, over the AMMs of a sharded state space
+pub async fn get_sharded_dependent_amms(state: &ShardedStateSpace) -> HashMap<H160, Vec<H160>> {
+    let mut dependent_amms: HashMap<H160, Vec<H160>> = HashMap::new();
+
+    state
+        .for_each(|amm| {
+            if let Some(dependency) = amm.dependency() {
+                dependent_amms
+                    .entry(dependency)
+                    .or_default()
+                    .push(amm.address());
+            }
+        })
+        .await;
+
+    dependent_amms
+}
+
+//Returns the addresses of all AMMs that the log belongs to. A Bancor V3 trade between two base tokens goes through the pools of both tokens,
+//the pools are keyed by their base token and the BNT side of single hop trades is not in the state space
+pub fn get_amm_addresses_from_log(log: &Log) -> Vec<H160> {
+    if log.topics[0] == bancor_v3::TOKENS_TRADED_EVENT_SIGNATURE && log.topics.len() > 3 {
+        vec![H160::from(log.topics[2]), H160::from(log.topics[3])]
+    } else {
+        vec![get_amm_address_from_log(log)]
+    }
+}
+
+//Maps each contract that AMMs also sync from to the addresses of these AMMs, i.e. a Curve base pool to its metapools
+pub fn get_dependent_amms<'a>(amms: impl IntoIterator<Item = &'a AMM>) -> HashMap<H160, Vec<H160>> {
+    let mut dependent_amms: HashMap<H160, Vec<H160>> = HashMap::new();
+
+    for amm in amms {
+        if let Some(dependency) = amm.dependency() {
+            dependent_amms
+                .entry(dependency)
+                .or_default()
+                .push(amm.address());
+        }
+    }
+
+    dependent_amms
+}
+
 pub fn get_block_number_from_log(log: &Log) -> Result<u64, EventLogError> {
     if let Some(block_number) = log.block_number {
         Ok(block_number.as_u64())
@@ -566,18 +1092,32 @@ pub fn get_block_number_from_log(log: &Log) -> Result<u64, EventLogError> {
 
 #[cfg(test)]
 mod tests {
-    use std::{default, sync::Arc};
-
-    use crate::amm::{uniswap_v2::UniswapV2Pool, AMM};
+    use std::{collections::HashSet, default, sync::Arc, time::Duration};
+
+    use crate::{
+        amm::{
+            uniswap_v2::{UniswapV2Pool, SYNC_EVENT_SIGNATURE},
+            AutomatedMarketMaker, AMM,
+        },
+        state_space::error::StateSpaceError,
+    };
+    use async_trait::async_trait;
     use ethers::{
-        providers::{Http, Provider, Ws},
-        types::H160,
+        abi::{encode, Token},
+        providers::{Http, Middleware, MockProvider, Provider, ProviderError, Ws},
+        types::{Block, Bytes, Filter, Log, ValueOrArray, H160, H256, U256, U64},
     };
     use tokio::sync::RwLock;
 
-    use super::StateSpaceManager;
-    use crate::state_space::state::{
-        add_state_change_to_cache, unwind_state_changes, StateChange, StateChangeCache,
+    use super::{watched_amms_filter, BlockProcessor, StateSpaceManager};
+    use crate::state_space::{
+        delta::{AmmStateDelta, Delta, StateDelta},
+        error::StateChangeError,
+        sharded::ShardedStateSpace,
+        state::{
+            add_state_change_to_cache, unwind_state_changes, StateChange, StateChangeCache,
+            STATE_CHANGE_CACHE_SIZE,
+        },
     };
 
     #[tokio::test]
@@ -654,6 +1194,63 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_unwind_state_changes_to_block() -> eyre::Result<()> {
+        let pool = |address: u64, reserve_0: u128| {
+            AMM::UniswapV2Pool(UniswapV2Pool {
+                address: H160::from_low_u64_be(address),
+                reserve_0,
+                ..default::Default::default()
+            })
+        };
+        let state = Arc::new(ShardedStateSpace::new(vec![pool(1, 12), pool(2, 22)]));
+        let state_change_cache = Arc::new(RwLock::new(StateChangeCache::new()));
+
+        //Pool 1 changes in blocks 11 and 12, pool 2 in block 12, each state change holds the state before the change
+        for (block_number, state_change) in [
+            (10, None),
+            (11, Some(vec![pool(1, 10)])),
+            (12, Some(vec![pool(1, 11), pool(2, 20)])),
+        ] {
+            add_state_change_to_cache(
+                state_change_cache.clone(),
+                StateChange::new(state_change, block_number),
+            )
+            .await?;
+        }
+
+        //The cache does not reach back to block 9, nothing is unwound
+        assert!(matches!(
+            unwind_state_changes(state.clone(), state_change_cache.clone(), 9).await,
+            Err(StateChangeError::UnwindPastCache {
+                block_number: 9,
+                oldest_block_number: 10,
+            })
+        ));
+        assert_eq!(state_change_cache.read().await.len(), 3);
+
+        let unwound_amms =
+            unwind_state_changes(state.clone(), state_change_cache.clone(), 12).await?;
+        assert_eq!(
+            unwound_amms,
+            vec![H160::from_low_u64_be(2), H160::from_low_u64_be(1)]
+        );
+
+        let unwound_amms =
+            unwind_state_changes(state.clone(), state_change_cache.clone(), 11).await?;
+        assert_eq!(unwound_amms, vec![H160::from_low_u64_be(1)]);
+
+        for (address, reserve_0) in [(1, 10), (2, 20)] {
+            match state.get(H160::from_low_u64_be(address)).await {
+                Some(AMM::UniswapV2Pool(pool)) => assert_eq!(pool.reserve_0, reserve_0),
+                _ => panic!("expected a Uniswap V2 pool"),
+            }
+        }
+        assert_eq!(state_change_cache.read().await.len(), 1);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_add_empty_state_changes() -> eyre::Result<()> {
         let last_synced_block = 0;
@@ -674,4 +1271,277 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_process_block_after_gap() -> eyre::Result<()> {
+        let state_change_cache = Arc::new(RwLock::new(StateChangeCache::new()));
+        for block_number in 90..=100 {
+            add_state_change_to_cache(
+                state_change_cache.clone(),
+                StateChange::new(None, block_number),
+            )
+            .await?;
+        }
+
+        let (provider, _mock) = Provider::mocked();
+        let mut block_processor = BlockProcessor::new(
+            Arc::new(ShardedStateSpace::new(vec![])),
+            state_change_cache.clone(),
+            Arc::new(provider),
+            Filter::new(),
+            100,
+            10,
+            5,
+        );
+
+        //Blocks 101 to 105 were missed, the state changes of the gap would not fit in the cache
+        let head = Block::<H256> {
+            number: Some(U64::from(106)),
+            hash: Some(H256::from_low_u64_be(106)),
+            parent_hash: H256::from_low_u64_be(105),
+            ..default::Default::default()
+        };
+        block_processor.process_block::<Provider<Ws>>(&head).await?;
+
+        assert_eq!(block_processor.last_synced_block, 106);
+        assert_eq!(
+            block_processor.block_hashes,
+            vec![(106, H256::from_low_u64_be(106))]
+        );
+        assert!(state_change_cache.read().await.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_block_watched_amms() -> eyre::Result<()> {
+        let (watched, unwatched) = (H160::from_low_u64_be(1), H160::from_low_u64_be(2));
+        let pool = |address: H160| {
+            AMM::UniswapV2Pool(UniswapV2Pool {
+                address,
+                ..default::Default::default()
+            })
+        };
+        let state = Arc::new(ShardedStateSpace::new(vec![pool(watched), pool(unwatched)]));
+
+        let filter = watched_amms_filter(&state.filter(|amm| amm.address() == watched).await)
+            .expect("the watched pool syncs from logs");
+        assert_eq!(filter.address, Some(ValueOrArray::Array(vec![watched])));
+        assert!(watched_amms_filter(&[]).is_none());
+
+        let sync_log = |address: H160| Log {
+            address,
+            topics: vec![SYNC_EVENT_SIGNATURE],
+            data: Bytes::from(encode(&[
+                Token::Uint(U256::one()),
+                Token::Uint(U256::one()),
+            ])),
+            block_number: Some(U64::from(101)),
+            ..default::Default::default()
+        };
+
+        let (provider, mock) = Provider::mocked();
+        let mut block_processor = BlockProcessor {
+            watched_amms: Arc::new(RwLock::new(Some(HashSet::from([watched])))),
+            ..BlockProcessor::new(
+                state.clone(),
+                Arc::new(RwLock::new(StateChangeCache::new())),
+                Arc::new(provider),
+                Filter::new(),
+                100,
+                10,
+                STATE_CHANGE_CACHE_SIZE,
+            )
+        };
+
+        //Logs of unwatched pools are dropped even if the node returns them
+        mock.push(vec![sync_log(watched), sync_log(unwatched)])?;
+        let amms_updated = block_processor
+            .process_block::<Provider<Ws>>(&Block {
+                number: Some(U64::from(101)),
+                ..default::Default::default()
+            })
+            .await?;
+        assert_eq!(amms_updated, vec![watched]);
+
+        for (address, reserve_0) in [(watched, 1), (unwatched, 0)] {
+            match state.get(address).await {
+                Some(AMM::UniswapV2Pool(pool)) => assert_eq!(pool.reserve_0, reserve_0),
+                _ => panic!("expected a Uniswap V2 pool"),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_block_reorg() -> eyre::Result<()> {
+        let pool_address = H160::from_low_u64_be(1);
+        let state = Arc::new(ShardedStateSpace::new(vec![AMM::UniswapV2Pool(
+            UniswapV2Pool {
+                address: pool_address,
+                reserve_0: 1,
+                reserve_1: 1,
+                ..default::Default::default()
+            },
+        )]));
+
+        let block = |number: u64, hash: u64, parent_hash: u64| Block::<H256> {
+            number: Some(U64::from(number)),
+            hash: Some(H256::from_low_u64_be(hash)),
+            parent_hash: H256::from_low_u64_be(parent_hash),
+            ..default::Default::default()
+        };
+        let sync_log = |block_number: u64, reserve: u64| Log {
+            address: pool_address,
+            topics: vec![SYNC_EVENT_SIGNATURE],
+            data: Bytes::from(encode(&[
+                Token::Uint(U256::from(reserve)),
+                Token::Uint(U256::from(reserve)),
+            ])),
+            block_number: Some(U64::from(block_number)),
+            ..default::Default::default()
+        };
+        let reserve_0 = |state: &super::StateSpace| match state.get(&pool_address) {
+            Some(AMM::UniswapV2Pool(pool)) => pool.reserve_0,
+            _ => panic!("expected the Uniswap V2 pool"),
+        };
+
+        let (provider, mock) = Provider::mocked();
+        let mut block_processor = BlockProcessor::new(
+            state.clone(),
+            Arc::new(RwLock::new(StateChangeCache::new())),
+            Arc::new(provider),
+            Filter::new(),
+            100,
+            10,
+            STATE_CHANGE_CACHE_SIZE,
+        );
+
+        //Blocks 101 and 102 extend the synced chain, 102 updates the pool twice
+        mock.push(vec![sync_log(101, 10)])?;
+        block_processor
+            .process_block::<Provider<Ws>>(&block(101, 101, 100))
+            .await?;
+        mock.push(vec![sync_log(102, 15), sync_log(102, 20)])?;
+        block_processor
+            .process_block::<Provider<Ws>>(&block(102, 102, 101))
+            .await?;
+        assert_eq!(reserve_0(&state.snapshot().await), 20);
+
+        //The delta of 102 goes from the state before its first log
+        let event = block_processor.state_change_event(vec![pool_address]).await;
+        assert_eq!(event.block_number, 102);
+        match &event.changes[..] {
+            [AmmStateDelta {
+                delta: StateDelta::UniswapV2Pool { reserve_0, .. },
+                ..
+            }] => assert_eq!(*reserve_0, Delta::new(10, 20)),
+            _ => panic!("expected a Uniswap V2 delta"),
+        }
+
+        //Block 102 is replaced by a block on a fork of 101, the pool is back at its state of 101 before the fork is applied
+        mock.push(vec![sync_log(102, 30)])?;
+        let amms_updated = block_processor
+            .process_block::<Provider<Ws>>(&block(102, 1002, 101))
+            .await?;
+        assert_eq!(amms_updated, vec![pool_address]);
+        assert_eq!(reserve_0(&state.snapshot().await), 30);
+
+        //A fork of 101 that only extends the fork without logs for the pool
+        mock.push(Vec::<Log>::new())?;
+        block_processor
+            .process_block::<Provider<Ws>>(&block(103, 1003, 1002))
+            .await?;
+        assert_eq!(reserve_0(&state.snapshot().await), 30);
+
+        //The parents of a fork that branches off below 101 are walked back past the oldest processed block, the logs of the fork are
+        //fetched before the blocks are unwound
+        mock.push(Vec::<Log>::new())?;
+        mock.push(block(102, 2002, 2001))?;
+        mock.push(block(103, 2003, 2002))?;
+        assert!(matches!(
+            block_processor
+                .process_block::<Provider<Ws>>(&block(104, 2004, 2003))
+                .await,
+            Err(StateSpaceError::ReorgTooDeep {
+                block_number: 104,
+                ..
+            })
+        ));
+        assert_eq!(reserve_0(&state.snapshot().await), 30);
+
+        Ok(())
+    }
+
+    //Fails a log request if the state space can not be snapshot while the logs are fetched
+    #[derive(Debug)]
+    struct SnapshotCheckingMiddleware {
+        inner: Provider<MockProvider>,
+        state: Arc<ShardedStateSpace>,
+    }
+
+    #[async_trait]
+    impl Middleware for SnapshotCheckingMiddleware {
+        type Error = ProviderError;
+        type Provider = MockProvider;
+        type Inner = Provider<MockProvider>;
+
+        fn inner(&self) -> &Provider<MockProvider> {
+            &self.inner
+        }
+
+        async fn get_logs(&self, filter: &Filter) -> Result<Vec<Log>, ProviderError> {
+            tokio::time::timeout(Duration::from_secs(1), self.state.snapshot())
+                .await
+                .map_err(|_| ProviderError::CustomError("the state space is locked".to_string()))?;
+
+            self.inner.get_logs(filter).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_process_block_does_not_lock_while_fetching() -> eyre::Result<()> {
+        let pool_address = H160::from_low_u64_be(1);
+        let state = Arc::new(ShardedStateSpace::new(vec![AMM::UniswapV2Pool(
+            UniswapV2Pool {
+                address: pool_address,
+                ..default::Default::default()
+            },
+        )]));
+
+        let (provider, mock) = Provider::mocked();
+        let mut block_processor = BlockProcessor::new(
+            state.clone(),
+            Arc::new(RwLock::new(StateChangeCache::new())),
+            Arc::new(SnapshotCheckingMiddleware {
+                inner: provider,
+                state: state.clone(),
+            }),
+            Filter::new(),
+            100,
+            10,
+            STATE_CHANGE_CACHE_SIZE,
+        );
+
+        mock.push(vec![Log {
+            address: pool_address,
+            topics: vec![SYNC_EVENT_SIGNATURE],
+            data: Bytes::from(encode(&[
+                Token::Uint(U256::from(10)),
+                Token::Uint(U256::from(10)),
+            ])),
+            block_number: Some(U64::from(101)),
+            ..default::Default::default()
+        }])?;
+        let amms_updated = block_processor
+            .process_block::<Provider<Ws>>(&Block {
+                number: Some(U64::from(101)),
+                ..default::Default::default()
+            })
+            .await?;
+        assert_eq!(amms_updated, vec![pool_address]);
+
+        Ok(())
+    }
 }
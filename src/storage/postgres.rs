@@ -0,0 +1,126 @@
+use sqlx::{types::Json, PgPool};
+
+use crate::{
+    amm::{
+        factory::{AutomatedMarketMakerFactory, Factory},
+        AutomatedMarketMaker, AMM,
+    },
+    errors::StorageError,
+};
+
+//Creates the `factories` and `amms` tables from the migrations under `migrations/`
+pub async fn run_migrations(pool: &PgPool) -> Result<(), StorageError> {
+    sqlx::migrate!("./migrations").run(pool).await?;
+
+    Ok(())
+}
+
+//Inserts the factories, factories that were already saved are replaced
+pub async fn save_factories(pool: &PgPool, factories: &[Factory]) -> Result<(), StorageError> {
+    let mut tx = pool.begin().await?;
+
+    for factory in factories {
+        sqlx::query(
+            "INSERT INTO factories (address, kind, creation_block, data) VALUES ($1, $2, $3, $4)
+             ON CONFLICT (address) DO UPDATE SET kind = EXCLUDED.kind, creation_block = EXCLUDED.creation_block,
+             data = EXCLUDED.data, updated_at = now()",
+        )
+        .bind(format!("{:?}", factory.address()))
+        .bind(variant_name(&serde_json::to_value(factory)?))
+        .bind(factory.creation_block() as i64)
+        .bind(Json(factory))
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub async fn load_factories(pool: &PgPool) -> Result<Vec<Factory>, StorageError> {
+    let rows: Vec<(Json<Factory>,)> =
+        sqlx::query_as("SELECT data FROM factories ORDER BY creation_block, address")
+            .fetch_all(pool)
+            .await?;
+
+    Ok(rows.into_iter().map(|(Json(factory),)| factory).collect())
+}
+
+//Inserts the AMMs, AMMs that were already saved are replaced with their latest state
+pub async fn save_amms(pool: &PgPool, amms: &[AMM]) -> Result<(), StorageError> {
+    let mut tx = pool.begin().await?;
+
+    for amm in amms {
+        sqlx::query(
+            "INSERT INTO amms (id, kind, address, data) VALUES ($1, $2, $3, $4)
+             ON CONFLICT (id) DO UPDATE SET kind = EXCLUDED.kind, address = EXCLUDED.address,
+             data = EXCLUDED.data, updated_at = now()",
+        )
+        .bind(amm_id(amm))
+        .bind(variant_name(&serde_json::to_value(amm)?))
+        .bind(format!("{:?}", amm.address()))
+        .bind(Json(amm))
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub async fn load_amms(pool: &PgPool) -> Result<Vec<AMM>, StorageError> {
+    let rows: Vec<(Json<AMM>,)> = sqlx::query_as("SELECT data FROM amms ORDER BY kind, id")
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows.into_iter().map(|(Json(amm),)| amm).collect())
+}
+
+//AMMs that share a singleton contract are keyed by their pool id since their address is not unique
+fn amm_id(amm: &AMM) -> String {
+    match amm.pool_id() {
+        Some(pool_id) => format!("{:?}", pool_id),
+        None => format!("{:?}", amm.address()),
+    }
+}
+
+//Factories and AMMs are externally tagged enums, the tag is stored alongside the data so rows can be filtered by kind
+fn variant_name(value: &serde_json::Value) -> String {
+    value
+        .as_object()
+        .and_then(|object| object.keys().next().cloned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::{H160, H256};
+
+    use crate::amm::{
+        factory::Factory, uniswap_v2::factory::UniswapV2Factory, uniswap_v4::UniswapV4Pool, AMM,
+    };
+
+    use super::{amm_id, variant_name};
+
+    #[test]
+    fn test_row_keys() -> eyre::Result<()> {
+        let factory =
+            Factory::UniswapV2Factory(UniswapV2Factory::new(H160::from_low_u64_be(1), 0, 300));
+        assert_eq!(
+            variant_name(&serde_json::to_value(&factory)?),
+            "UniswapV2Factory"
+        );
+
+        let pool_id = H256::from_low_u64_be(2);
+        let amm = AMM::UniswapV4Pool(UniswapV4Pool {
+            pool_id,
+            ..Default::default()
+        });
+        assert_eq!(amm_id(&amm), format!("{:?}", pool_id));
+        assert_eq!(variant_name(&serde_json::to_value(&amm)?), "UniswapV4Pool");
+
+        Ok(())
+    }
+}
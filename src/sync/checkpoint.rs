@@ -1,11 +1,15 @@
 use std::{
+    collections::{HashMap, HashSet},
     fs::read_to_string,
     panic::resume_unwind,
     sync::Arc,
     time::{SystemTime, UNIX_EPOCH},
 };
 
-use ethers::{providers::Middleware, types::H160};
+use ethers::{
+    providers::Middleware,
+    types::{Filter, Log, H160, H256},
+};
 
 use serde::{Deserialize, Serialize};
 
@@ -13,12 +17,24 @@ use tokio::task::JoinHandle;
 
 use crate::{
     amm::{
+        algebra::factory::AlgebraFactory,
+        balancer_v2::factory::{BalancerV2Factory, BalancerV2Vault},
+        camelot::factory::CamelotFactory,
+        curve::factory::CurveFactory,
+        dodo::factory::DodoFactory,
         factory::{AutomatedMarketMakerFactory, Factory},
+        kyber_elastic::factory::KyberElasticFactory,
+        maverick::factory::MaverickFactory,
+        solidly::factory::SolidlyFactory,
+        trader_joe_lb::factory::LBFactory,
         uniswap_v2::factory::UniswapV2Factory,
         uniswap_v3::factory::UniswapV3Factory,
-        AMM,
+        uniswap_v4::factory::UniswapV4PoolManager,
+        AutomatedMarketMaker, AMM,
     },
     errors::{AMMError, CheckpointError},
+    middleware::logs::{get_logs_with_adaptive_range, MAX_LOG_RANGE_SPLITS},
+    state_space::state::{get_amm_addresses_from_log, get_dependent_amms},
     sync,
 };
 
@@ -54,6 +70,21 @@ pub async fn sync_amms_from_checkpoint<M: 'static + Middleware>(
     step: u64,
     middleware: Arc<M>,
 ) -> Result<(Vec<Factory>, Vec<AMM>), AMMError<M>> {
+    let (factories, amms, _) =
+        sync_amms_from_checkpoint_with_report(path_to_checkpoint, step, middleware).await?;
+
+    Ok((factories, amms))
+}
+
+//Same as sync_amms_from_checkpoint, and also returns the number of pools that each factory created after the checkpoint block, keyed
+//by the address of the factory. Only the pools created from the block after the checkpoint block to the head are populated on top of
+//the checkpointed AMMs. The block of the checkpoint is the last block the factories are synced to, their creation blocks are kept so
+//that they can still be synced from genesis.
+pub async fn sync_amms_from_checkpoint_with_report<M: 'static + Middleware>(
+    path_to_checkpoint: &str,
+    step: u64,
+    middleware: Arc<M>,
+) -> Result<(Vec<Factory>, Vec<AMM>, HashMap<H160, usize>), AMMError<M>> {
     let current_block = middleware
         .get_block_number()
         .await
@@ -63,55 +94,29 @@ pub async fn sync_amms_from_checkpoint<M: 'static + Middleware>(
     let checkpoint: Checkpoint =
         serde_json::from_str(read_to_string(path_to_checkpoint)?.as_str())?;
 
-    //Sort all of the pools from the checkpoint into uniswap_v2_pools and uniswap_v3_pools pools so we can sync them concurrently
-    let (uniswap_v2_pools, uniswap_v3_pools, erc_4626_pools) = sort_amms(checkpoint.amms);
-
     let mut aggregated_amms = vec![];
     let mut handles = vec![];
 
-    //Sync all uniswap v2 pools from checkpoint
-    if !uniswap_v2_pools.is_empty() {
-        handles.push(
-            batch_sync_amms_from_checkpoint(
-                uniswap_v2_pools,
-                Some(current_block),
-                middleware.clone(),
-            )
-            .await,
-        );
-    }
-
-    //Sync all uniswap v3 pools from checkpoint
-    if !uniswap_v3_pools.is_empty() {
+    //Sort all of the pools from the checkpoint by AMM variant so we can sync them concurrently
+    for amms in sort_amms(checkpoint.amms) {
         handles.push(
-            batch_sync_amms_from_checkpoint(
-                uniswap_v3_pools,
-                Some(current_block),
-                middleware.clone(),
-            )
-            .await,
-        );
-    }
-
-    if !erc_4626_pools.is_empty() {
-        // TODO: Batch sync erc4626 pools from checkpoint
-        todo!(
-            r#"""This function will produce an incorrect state if ERC4626 pools are present in the checkpoint. 
-            This logic needs to be implemented into batch_sync_amms_from_checkpoint"""#
+            batch_sync_amms_from_checkpoint(amms, Some(current_block), middleware.clone()).await,
         );
     }
 
-    //Sync all pools from the since synced block
-    handles.extend(
+    //Sync all pools created since the checkpoint block
+    let new_amm_handles = if current_block > checkpoint.block_number {
         get_new_amms_from_range(
             checkpoint.factories.clone(),
-            checkpoint.block_number,
+            checkpoint.block_number + 1,
             current_block,
             step,
             middleware.clone(),
         )
-        .await,
-    );
+        .await
+    } else {
+        vec![]
+    };
 
     for handle in handles {
         match handle.await {
@@ -127,6 +132,40 @@ pub async fn sync_amms_from_checkpoint<M: 'static + Middleware>(
         }
     }
 
+    //Pools created since the checkpoint can also be in the checkpoint if a factory was configured twice
+    let mut amm_addresses = aggregated_amms
+        .iter()
+        .map(|amm| amm.address())
+        .collect::<HashSet<H160>>();
+    let mut new_pools = HashMap::new();
+
+    for (factory, handle) in checkpoint.factories.iter().zip(new_amm_handles) {
+        match handle.await {
+            Ok(new_amms) => {
+                let new_amms = new_amms?
+                    .into_iter()
+                    .filter(|amm| amm_addresses.insert(amm.address()))
+                    .collect::<Vec<AMM>>();
+
+                *new_pools.entry(factory.address()).or_insert(0) += new_amms.len();
+                aggregated_amms.extend(new_amms);
+            }
+            Err(err) => {
+                if err.is_panic() {
+                    // Resume the panic on the main task
+                    resume_unwind(err.into_panic());
+                }
+            }
+        }
+    }
+
+    let aggregated_amms = sync::dedupe_amms(aggregated_amms);
+    tracing::info!(
+        current_block,
+        ?new_pools,
+        "synced AMMs created since the checkpoint"
+    );
+
     //update the sync checkpoint
     construct_checkpoint(
         checkpoint.factories.clone(),
@@ -135,7 +174,146 @@ pub async fn sync_amms_from_checkpoint<M: 'static + Middleware>(
         path_to_checkpoint,
     )?;
 
-    Ok((checkpoint.factories, aggregated_amms))
+    Ok((checkpoint.factories, aggregated_amms, new_pools))
+}
+
+//Brings the checkpoint up to `confirmations` blocks behind the head without populating the checkpointed AMMs again. The sync events
+//emitted since the checkpoint block are applied to the AMMs in the order they were emitted and the pools created by the factories since
+//then are added. AMMs that do not sync from events are populated at the new block. Returns the AMMs and the block they are synced to,
+//which is written to the checkpoint. A checkpoint that is already at or past that block is returned as is.
+pub async fn sync_amms_from_checkpoint_incremental<M: 'static + Middleware>(
+    path_to_checkpoint: &str,
+    step: u64,
+    confirmations: u64,
+    middleware: Arc<M>,
+) -> Result<(Vec<AMM>, u64), AMMError<M>> {
+    if step == 0 {
+        return Err(AMMError::ZeroBatchSize("step"));
+    }
+
+    let to_block = middleware
+        .get_block_number()
+        .await
+        .map_err(AMMError::MiddlewareError)?
+        .as_u64()
+        .saturating_sub(confirmations);
+
+    let checkpoint: Checkpoint =
+        serde_json::from_str(read_to_string(path_to_checkpoint)?.as_str())?;
+    if to_block <= checkpoint.block_number {
+        return Ok((checkpoint.amms, checkpoint.block_number));
+    }
+
+    let from_block = checkpoint.block_number + 1;
+    let mut amms = checkpoint.amms;
+
+    let event_signatures = amms
+        .iter()
+        .flat_map(|amm| amm.sync_on_event_signatures())
+        .collect::<HashSet<H256>>();
+
+    if !event_signatures.is_empty() {
+        let filter = Filter::new().topic0(event_signatures.into_iter().collect::<Vec<H256>>());
+
+        let mut logs = vec![];
+        let mut range_start = from_block;
+        while range_start <= to_block {
+            let range_end = range_start.saturating_add(step - 1).min(to_block);
+            logs.extend(
+                get_logs_with_adaptive_range(
+                    middleware.as_ref(),
+                    &filter,
+                    range_start,
+                    range_end,
+                    MAX_LOG_RANGE_SPLITS,
+                )
+                .await
+                .map_err(AMMError::MiddlewareError)?,
+            );
+
+            range_start = range_end + 1;
+        }
+
+        sync_amms_from_logs(&mut amms, logs)?;
+    }
+
+    for amm in amms
+        .iter_mut()
+        .filter(|amm| amm.sync_on_event_signatures().is_empty())
+    {
+        amm.populate_data(Some(to_block), middleware.clone())
+            .await?;
+    }
+
+    let mut amm_addresses = amms
+        .iter()
+        .map(|amm| amm.address())
+        .collect::<HashSet<H160>>();
+
+    for handle in get_new_amms_from_range(
+        checkpoint.factories.clone(),
+        from_block,
+        to_block,
+        step,
+        middleware.clone(),
+    )
+    .await
+    {
+        match handle.await {
+            Ok(new_amms) => amms.extend(
+                new_amms?
+                    .into_iter()
+                    .filter(|amm| amm_addresses.insert(amm.address())),
+            ),
+            Err(err) => {
+                if err.is_panic() {
+                    // Resume the panic on the main task
+                    resume_unwind(err.into_panic());
+                }
+            }
+        }
+    }
+
+    construct_checkpoint(checkpoint.factories, &amms, to_block, path_to_checkpoint)?;
+
+    Ok((amms, to_block))
+}
+
+//Applies the logs to the AMMs they belong to, the logs have to be ordered by block and log index
+fn sync_amms_from_logs<M: Middleware>(amms: &mut [AMM], logs: Vec<Log>) -> Result<(), AMMError<M>> {
+    let amm_indices = amms
+        .iter()
+        .enumerate()
+        .map(|(index, amm)| (amm.address(), index))
+        .collect::<HashMap<H160, usize>>();
+    let dependent_amms = get_dependent_amms(amms.iter());
+
+    for log in logs {
+        let Some(event_signature) = log.topics.first().copied() else {
+            continue;
+        };
+
+        let mut amm_addresses = get_amm_addresses_from_log(&log);
+        if let Some(dependents) = dependent_amms.get(&log.address) {
+            amm_addresses.extend(dependents);
+        }
+
+        for amm_address in amm_addresses {
+            let Some(amm) = amm_indices
+                .get(&amm_address)
+                .and_then(|index| amms.get_mut(*index))
+            else {
+                continue;
+            };
+
+            //Skips the events of the address that the AMM does not sync from
+            if amm.sync_on_event_signatures().contains(&event_signature) {
+                amm.sync_from_log(log.clone())?;
+            }
+        }
+    }
+
+    Ok(())
 }
 
 pub async fn get_new_amms_from_range<M: 'static + Middleware>(
@@ -189,44 +367,107 @@ pub async fn batch_sync_amms_from_checkpoint<M: 'static + Middleware>(
             0,
         ))),
 
-        AMM::ERC4626Vault(_) => None,
+        //AMMs that are not created by a factory are populated by populate_amms instead
+        AMM::ERC4626Vault(_) | AMM::BancorV3Pool(_) | AMM::RateProviderAmm(_) => None,
+
+        AMM::BalancerV2WeightedPool(_) => Some(Factory::BalancerV2Factory(BalancerV2Factory::new(
+            H160::zero(),
+            0,
+        ))),
+        AMM::BalancerStablePool(_) => Some(Factory::BalancerV2Vault(BalancerV2Vault::new(
+            H160::zero(),
+            0,
+        ))),
+        AMM::CurveStableSwapPool(_) | AMM::CurveCryptoPool(_) | AMM::CurveMetaPool(_) => {
+            Some(Factory::CurveFactory(CurveFactory::new(H160::zero(), 0)))
+        }
+
+        AMM::UniswapV4Pool(_) => {
+            let pool_manager = UniswapV4PoolManager::new(H160::zero(), 0, vec![]);
+            Some(Factory::UniswapV4PoolManager(pool_manager))
+        }
+
+        AMM::SolidlyPool(_) => Some(Factory::SolidlyFactory(SolidlyFactory::new(
+            H160::zero(),
+            0,
+        ))),
+
+        AMM::LBPair(_) => Some(Factory::LBFactory(LBFactory::new(H160::zero(), 0))),
+
+        AMM::MaverickPool(_) => Some(Factory::MaverickFactory(MaverickFactory::new(
+            H160::zero(),
+            0,
+        ))),
+
+        AMM::AlgebraPool(_) => Some(Factory::AlgebraFactory(AlgebraFactory::new(
+            H160::zero(),
+            0,
+        ))),
+
+        AMM::DodoPool(ref pool) => Some(Factory::DodoFactory(DodoFactory::new(
+            H160::zero(),
+            0,
+            pool.pool_type,
+        ))),
+
+        AMM::KyberElasticPool(_) => {
+            let factory = KyberElasticFactory::new(H160::zero(), 0);
+            Some(Factory::KyberElasticFactory(factory))
+        }
+
+        AMM::CamelotPool(_) => Some(Factory::CamelotFactory(CamelotFactory::new(
+            H160::zero(),
+            0,
+        ))),
     };
 
     //Spawn a new thread to get all pools and sync data for each dex
     tokio::spawn(async move {
+        if !amms_are_congruent(&amms) {
+            return Err(AMMError::IncongruentAMMs);
+        }
+
+        //Get all pool data via batched calls
         if let Some(factory) = factory {
-            if amms_are_congruent(&amms) {
-                //Get all pool data via batched calls
-                factory
-                    .populate_amm_data(&mut amms, block_number, middleware)
-                    .await?;
-
-                //Clean empty pools
-                amms = sync::remove_empty_amms(amms);
-
-                Ok::<_, AMMError<M>>(amms)
-            } else {
-                Err(AMMError::IncongruentAMMs)
-            }
+            factory
+                .populate_amm_data(&mut amms, block_number, middleware)
+                .await?;
         } else {
-            Ok::<_, AMMError<M>>(vec![])
+            let block_number = match block_number {
+                Some(block_number) => block_number,
+                None => middleware
+                    .get_block_number()
+                    .await
+                    .map_err(AMMError::MiddlewareError)?
+                    .as_u64(),
+            };
+
+            sync::populate_amms(&mut amms, block_number, middleware).await?;
         }
+
+        //Clean empty pools
+        amms = sync::remove_empty_amms(amms);
+
+        Ok::<_, AMMError<M>>(amms)
     })
 }
 
-pub fn sort_amms(amms: Vec<AMM>) -> (Vec<AMM>, Vec<AMM>, Vec<AMM>) {
-    let mut uniswap_v2_pools = vec![];
-    let mut uniswap_v3_pools = vec![];
-    let mut erc_4626_vaults = vec![];
+//Groups the AMMs by variant, preserving the order in which each variant first appears
+pub fn sort_amms(amms: Vec<AMM>) -> Vec<Vec<AMM>> {
+    let mut sorted_amms: Vec<Vec<AMM>> = vec![];
+
     for amm in amms {
-        match amm {
-            AMM::UniswapV2Pool(_) => uniswap_v2_pools.push(amm),
-            AMM::UniswapV3Pool(_) => uniswap_v3_pools.push(amm),
-            AMM::ERC4626Vault(_) => erc_4626_vaults.push(amm),
+        if let Some(group) = sorted_amms
+            .iter_mut()
+            .find(|group| std::mem::discriminant(&group[0]) == std::mem::discriminant(&amm))
+        {
+            group.push(amm);
+        } else {
+            sorted_amms.push(vec![amm]);
         }
     }
 
-    (uniswap_v2_pools, uniswap_v3_pools, erc_4626_vaults)
+    sorted_amms
 }
 
 pub async fn get_new_pools_from_range<M: 'static + Middleware>(
@@ -286,3 +527,299 @@ pub fn deconstruct_checkpoint(checkpoint_path: &str) -> Result<(Vec<AMM>, u64),
     let checkpoint: Checkpoint = serde_json::from_str(read_to_string(checkpoint_path)?.as_str())?;
     Ok((checkpoint.amms, checkpoint.block_number))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{HashMap, VecDeque},
+        sync::{Arc, Mutex},
+    };
+
+    use async_trait::async_trait;
+    use ethers::{
+        abi::{encode, Token},
+        contract::MULTICALL_ADDRESS,
+        providers::{Middleware, MockProvider, Provider, ProviderError},
+        types::{transaction::eip2718::TypedTransaction, BlockId, Bytes, Log, H160, U256, U64},
+    };
+
+    use crate::amm::{
+        bancor_v3::BancorV3Pool,
+        factory::Factory,
+        rate_provider::{RateProviderAmm, RATE_PRECISION, WSTETH_RATE_FUNCTION},
+        uniswap_v2::{
+            factory::{UniswapV2Factory, PAIR_CREATED_EVENT_SIGNATURE},
+            UniswapV2Pool, SYNC_EVENT_SIGNATURE,
+        },
+        AutomatedMarketMaker, AMM,
+    };
+
+    use super::{
+        construct_checkpoint, deconstruct_checkpoint, sync_amms_from_checkpoint_incremental,
+        sync_amms_from_checkpoint_with_report,
+    };
+
+    //Answers each eth_call with the next response queued for the called address, so that the AMM groups that are synced concurrently
+    //do not take each other's responses. Other requests are answered by the mock provider.
+    #[derive(Debug)]
+    struct RoutedMiddleware {
+        inner: Provider<MockProvider>,
+        responses: Mutex<HashMap<H160, VecDeque<Bytes>>>,
+    }
+
+    #[async_trait]
+    impl Middleware for RoutedMiddleware {
+        type Error = ProviderError;
+        type Provider = MockProvider;
+        type Inner = Provider<MockProvider>;
+
+        fn inner(&self) -> &Provider<MockProvider> {
+            &self.inner
+        }
+
+        async fn call(
+            &self,
+            tx: &TypedTransaction,
+            _block: Option<BlockId>,
+        ) -> Result<Bytes, ProviderError> {
+            let to = tx.to_addr().copied().unwrap_or_default();
+
+            self.responses
+                .lock()
+                .expect("lock is not poisoned")
+                .get_mut(&to)
+                .and_then(|responses| responses.pop_front())
+                .ok_or_else(|| ProviderError::CustomError(format!("no response for {to:?}")))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_amms_from_checkpoint_without_factory() -> eyre::Result<()> {
+        let checkpoint_path = std::env::temp_dir().join("amms_factoryless_checkpoint_test.json");
+        let checkpoint_path = checkpoint_path.to_str().unwrap_or_default();
+
+        //The checkpointed pool holds the liquidity of the checkpoint block
+        let bancor_pool = BancorV3Pool {
+            token: H160::from_low_u64_be(1),
+            token_decimals: 18,
+            bnt: H160::from_low_u64_be(2),
+            network_info: H160::from_low_u64_be(3),
+            bnt_trading_liquidity: U256::from(100),
+            base_token_trading_liquidity: U256::from(100),
+            trading_fee: 2000,
+            network_fee: 200_000,
+        };
+        let rate_provider_amm = RateProviderAmm {
+            address: H160::from_low_u64_be(5),
+            token_decimals: 18,
+            underlying: H160::from_low_u64_be(6),
+            underlying_decimals: 18,
+            rate_provider: H160::from_low_u64_be(7),
+            rate_function: WSTETH_RATE_FUNCTION.to_string(),
+            rate: RATE_PRECISION,
+        };
+        construct_checkpoint(
+            vec![],
+            &[
+                AMM::BancorV3Pool(bancor_pool.clone()),
+                AMM::RateProviderAmm(rate_provider_amm.clone()),
+            ],
+            100,
+            checkpoint_path,
+        )?;
+
+        let word = |token: Token| Token::Bytes(encode(&[token]));
+        let success = |return_data: Token| Token::Tuple(vec![Token::Bool(true), return_data]);
+        let aggregate_3 = |results: Vec<Token>| {
+            Bytes::from(encode(&[Token::Array(
+                results.into_iter().map(success).collect(),
+            )]))
+        };
+
+        //The pool data is read through Multicall3, then the network fee from the network settings
+        let mut responses = HashMap::new();
+        responses.insert(
+            MULTICALL_ADDRESS,
+            VecDeque::from(vec![
+                aggregate_3(vec![
+                    word(Token::Address(bancor_pool.bnt)),
+                    word(Token::Address(H160::from_low_u64_be(4))),
+                    word(Token::Uint(U256::from(18))),
+                    Token::Bytes(encode(&[
+                        Token::Uint(U256::from(1000)),
+                        Token::Uint(U256::from(2000)),
+                    ])),
+                    word(Token::Uint(U256::from(2000))),
+                ]),
+                aggregate_3(vec![word(Token::Uint(U256::from(200_000)))]),
+            ]),
+        );
+
+        //The rate provider AMM reads the decimals of both tokens and the rate that accrued since the checkpoint
+        let rate = RATE_PRECISION * 11 / 10;
+        for (address, response) in [
+            (rate_provider_amm.address, U256::from(18)),
+            (rate_provider_amm.underlying, U256::from(18)),
+            (rate_provider_amm.rate_provider, rate),
+        ] {
+            responses.insert(
+                address,
+                VecDeque::from(vec![Bytes::from(encode(&[Token::Uint(response)]))]),
+            );
+        }
+
+        //The head is the checkpoint block, so no pools are created since the checkpoint. The chain id resolves the Multicall3 address.
+        let (provider, mock) = Provider::mocked();
+        mock.push(U256::one())?;
+        mock.push(U64::from(100))?;
+
+        let middleware = Arc::new(RoutedMiddleware {
+            inner: provider,
+            responses: Mutex::new(responses),
+        });
+        let (_, amms, _) =
+            sync_amms_from_checkpoint_with_report(checkpoint_path, 1000, middleware).await?;
+
+        match &amms[..] {
+            [AMM::BancorV3Pool(pool), AMM::RateProviderAmm(rate_provider)] => {
+                assert_eq!(pool.token, bancor_pool.token);
+                assert_eq!(pool.bnt_trading_liquidity, U256::from(1000));
+                assert_eq!(pool.base_token_trading_liquidity, U256::from(2000));
+                assert_eq!(rate_provider.address, rate_provider_amm.address);
+                assert_eq!(rate_provider.rate, rate);
+            }
+            _ => panic!("expected the checkpointed Bancor V3 pool and rate provider AMM"),
+        }
+
+        let (checkpointed_amms, checkpoint_block) = deconstruct_checkpoint(checkpoint_path)?;
+        assert_eq!(checkpoint_block, 100);
+        assert_eq!(
+            checkpointed_amms
+                .iter()
+                .map(|amm| amm.address())
+                .collect::<Vec<H160>>(),
+            vec![bancor_pool.token, rate_provider_amm.address]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_amms_from_checkpoint_with_report() -> eyre::Result<()> {
+        let checkpoint_path = std::env::temp_dir().join("amms_fast_forward_checkpoint_test.json");
+        let checkpoint_path = checkpoint_path.to_str().unwrap_or_default();
+
+        let factory_address = H160::from_low_u64_be(100);
+        let factory = Factory::UniswapV2Factory(UniswapV2Factory::new(factory_address, 0, 300));
+        construct_checkpoint(vec![factory], &[], 100, checkpoint_path)?;
+
+        let pair = H160::from_low_u64_be(1);
+        let pair_created_log = Log {
+            address: factory_address,
+            topics: vec![
+                PAIR_CREATED_EVENT_SIGNATURE,
+                H160::from_low_u64_be(2).into(),
+                H160::from_low_u64_be(3).into(),
+            ],
+            data: Bytes::from(encode(&[Token::Address(pair), Token::Uint(U256::one())])),
+            block_number: Some(U64::from(150)),
+            ..Default::default()
+        };
+        let pair_data = Bytes::from(encode(&[Token::Array(vec![Token::Tuple(vec![
+            Token::Address(H160::from_low_u64_be(2)),
+            Token::Uint(U256::from(18)),
+            Token::Address(H160::from_low_u64_be(3)),
+            Token::Uint(U256::from(18)),
+            Token::Uint(U256::from(1000)),
+            Token::Uint(U256::from(2000)),
+        ])])]));
+
+        //The head, the pair created since the checkpoint and its data, responses are popped from the back
+        let (provider, mock) = Provider::mocked();
+        mock.push(pair_data)?;
+        mock.push(vec![pair_created_log])?;
+        mock.push(U64::from(200))?;
+
+        let (factories, amms, new_pools) =
+            sync_amms_from_checkpoint_with_report(checkpoint_path, 1000, Arc::new(provider))
+                .await?;
+        assert_eq!(factories.len(), 1);
+        assert_eq!(
+            amms.iter().map(|amm| amm.address()).collect::<Vec<H160>>(),
+            vec![pair]
+        );
+        assert_eq!(new_pools.get(&factory_address), Some(&1));
+
+        let (checkpointed_amms, checkpoint_block) = deconstruct_checkpoint(checkpoint_path)?;
+        assert_eq!((checkpointed_amms.len(), checkpoint_block), (1, 200));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_amms_from_checkpoint_incremental() -> eyre::Result<()> {
+        let checkpoint_path = std::env::temp_dir().join("amms_incremental_checkpoint_test.json");
+        let checkpoint_path = checkpoint_path.to_str().unwrap_or_default();
+
+        let pool = UniswapV2Pool {
+            address: H160::from_low_u64_be(1),
+            token_a: H160::from_low_u64_be(2),
+            token_b: H160::from_low_u64_be(3),
+            reserve_0: 100,
+            reserve_1: 100,
+            fee: 300,
+            ..Default::default()
+        };
+        construct_checkpoint(
+            vec![],
+            &[AMM::UniswapV2Pool(pool.clone())],
+            100,
+            checkpoint_path,
+        )?;
+
+        let sync_log = |address: H160, reserve_0: u64, reserve_1: u64| Log {
+            address,
+            topics: vec![SYNC_EVENT_SIGNATURE],
+            data: Bytes::from(encode(&[
+                Token::Uint(U256::from(reserve_0)),
+                Token::Uint(U256::from(reserve_1)),
+            ])),
+            block_number: Some(U64::from(150)),
+            ..Default::default()
+        };
+
+        //The last sync event of the pool is applied, the events of other pairs are skipped
+        let (provider, mock) = Provider::mocked();
+        mock.push(vec![
+            sync_log(pool.address, 150, 70),
+            sync_log(H160::from_low_u64_be(4), 1, 1),
+            sync_log(pool.address, 200, 50),
+        ])?;
+        mock.push(U64::from(210))?;
+
+        let (amms, synced_block) =
+            sync_amms_from_checkpoint_incremental(checkpoint_path, 1000, 10, Arc::new(provider))
+                .await?;
+        assert_eq!(synced_block, 200);
+
+        match &amms[..] {
+            [AMM::UniswapV2Pool(synced_pool)] => {
+                assert_eq!((synced_pool.reserve_0, synced_pool.reserve_1), (200, 50));
+            }
+            _ => panic!("expected the checkpointed pool"),
+        }
+
+        let (checkpointed_amms, checkpoint_block) = deconstruct_checkpoint(checkpoint_path)?;
+        assert_eq!((checkpointed_amms.len(), checkpoint_block), (1, 200));
+
+        //Nothing is synced until the head is `confirmations` blocks past the checkpoint
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(205))?;
+        let (_, synced_block) =
+            sync_amms_from_checkpoint_incremental(checkpoint_path, 1000, 10, Arc::new(provider))
+                .await?;
+        assert_eq!(synced_block, 200);
+
+        Ok(())
+    }
+}
@@ -1,15 +1,141 @@
 use crate::{
     amm::{
+        algebra::AlgebraPool,
+        balancer_v2::{stable::BalancerStablePool, BalancerV2WeightedPool},
+        bancor_v3,
+        batch_backend::BatchBackend,
+        camelot::{self, CamelotPool},
+        curve::{crypto::CurveCryptoPool, CurveStableSwapPool},
+        erc_4626::ERC4626Vault,
         factory::{AutomatedMarketMakerFactory, Factory},
-        uniswap_v2, uniswap_v3, AutomatedMarketMaker, AMM,
+        kyber_elastic::KyberElasticPool,
+        maverick::MaverickPool,
+        solidly::{self, SolidlyPool},
+        trader_joe_lb::LBPair,
+        uniswap_v2::{self, factory::DEFAULT_PAIRS_BATCH_SIZE, UniswapV2Pool},
+        uniswap_v3::{self, UniswapV3Pool},
+        uniswap_v4, AutomatedMarketMaker, AMM,
     },
     errors::AMMError,
+    middleware::{
+        concurrency::ConcurrencyLimitMiddleware,
+        logs::{get_logs_with_adaptive_range, MAX_LOG_RANGE_SPLITS},
+        retry::{RetryConfig, RetryMiddleware},
+    },
+    state_space::state::{get_amm_addresses_from_log, get_dependent_amms},
+};
+
+use ethers::{
+    abi::{decode, ParamType},
+    contract::Multicall,
+    providers::Middleware,
+    types::{BlockId, Filter, Log, H160, H256, I256},
 };
 
-use ethers::providers::Middleware;
+use std::{
+    collections::{HashMap, HashSet},
+    panic::resume_unwind,
+    sync::Arc,
+    time::Instant,
+};
+use tokio::sync::mpsc::Sender;
+
+use self::progress::{send_progress, SyncProgress};
 
-use std::{panic::resume_unwind, sync::Arc};
 pub mod checkpoint;
+pub mod progress;
+pub mod syncer;
+
+//Max number of V2 like pools read by a single batch request, i.e. Uniswap V2, Uniswap V4, Solidly, Camelot and Bancor V3 pools
+pub const DEFAULT_BATCH_SIZE: usize = 127;
+//Max number of Uniswap V3 pools read by a single batch request
+pub const DEFAULT_V3_BATCH_SIZE: usize = 76;
+//Batch requests between two PoolsPopulated progress events
+const PROGRESS_BATCHES: usize = 10;
+//Max number of addresses in the filter of a single log request of resync_amms, providers limit the size of a filter
+pub const RESYNC_ADDRESSES_PER_FILTER: usize = 500;
+
+//Bounds the load that sync_amms_with_config puts on the node. SyncConfig::new does not limit the number of requests in flight
+//and uses the batch sizes of sync_amms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncConfig {
+    //Block range of each log request
+    pub step: u64,
+    //Requests in flight across all factories, including the batch requests and the log requests
+    pub max_concurrent_requests: usize,
+    //Pools read by each batch request, DEFAULT_BATCH_SIZE and DEFAULT_V3_BATCH_SIZE if it is not set. Every pool adds its 32 byte
+    //address to the calldata and roughly 200 bytes to the return data of the request, and the batch contract reads the pools in its
+    //constructor so the gas used grows linearly with the batch size. Lower it for eth_call backends with a low gas or size limit.
+    pub batch_size: Option<usize>,
+    //Pairs read by each batch request when enumerating the pairs of a Uniswap V2 factory, DEFAULT_PAIRS_BATCH_SIZE if it is not set
+    pub pairs_batch_size: Option<usize>,
+    //Reads the Uniswap V2 pairs of the factories and the data of the Uniswap V2 and V3 pools through Multicall3 instead of the batch
+    //contracts if it is BatchBackend::Multicall3, the other variants are read the same way with either backend
+    pub batch_backend: BatchBackend,
+    //Detects the transfer taxes of the tokens of the Uniswap V2 pools after they are populated, see
+    //uniswap_v2::transfer_tax::populate_transfer_taxes. Each token costs an eth_call that simulates a flash swap, so it is off by default.
+    pub detect_transfer_taxes: bool,
+}
+
+impl SyncConfig {
+    pub fn new(step: u64) -> SyncConfig {
+        SyncConfig {
+            step,
+            max_concurrent_requests: tokio::sync::Semaphore::MAX_PERMITS,
+            batch_size: None,
+            pairs_batch_size: None,
+            batch_backend: BatchBackend::BatchContract,
+            detect_transfer_taxes: false,
+        }
+    }
+
+    pub fn with_max_concurrent_requests(mut self, max_concurrent_requests: usize) -> SyncConfig {
+        self.max_concurrent_requests = max_concurrent_requests;
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> SyncConfig {
+        self.batch_size = Some(batch_size);
+        self
+    }
+
+    pub fn with_pairs_batch_size(mut self, pairs_batch_size: usize) -> SyncConfig {
+        self.pairs_batch_size = Some(pairs_batch_size);
+        self
+    }
+
+    pub fn with_batch_backend(mut self, batch_backend: BatchBackend) -> SyncConfig {
+        self.batch_backend = batch_backend;
+        self
+    }
+
+    pub fn with_transfer_taxes(mut self, detect_transfer_taxes: bool) -> SyncConfig {
+        self.detect_transfer_taxes = detect_transfer_taxes;
+        self
+    }
+
+    //Zero values are rejected before any request is sent, a zero step or batch size would never make progress and no permits
+    //would block every request
+    pub fn validate<M: Middleware>(&self) -> Result<(), AMMError<M>> {
+        if self.step == 0 {
+            return Err(AMMError::ZeroBatchSize("step"));
+        }
+
+        if self.max_concurrent_requests == 0 {
+            return Err(AMMError::ZeroBatchSize("max_concurrent_requests"));
+        }
+
+        if self.batch_size == Some(0) {
+            return Err(AMMError::ZeroBatchSize("batch_size"));
+        }
+
+        if self.pairs_batch_size == Some(0) {
+            return Err(AMMError::ZeroBatchSize("pairs_batch_size"));
+        }
+
+        Ok(())
+    }
+}
 
 pub async fn sync_amms<M: 'static + Middleware>(
     factories: Vec<Factory>,
@@ -17,6 +143,250 @@ pub async fn sync_amms<M: 'static + Middleware>(
     checkpoint_path: Option<&str>,
     step: u64,
 ) -> Result<(Vec<AMM>, u64), AMMError<M>> {
+    let (report, synced_block) = sync_factories(
+        factories,
+        middleware,
+        checkpoint_path,
+        SyncConfig::new(step),
+        true,
+        None,
+    )
+    .await?;
+
+    Ok((report.synced, synced_block))
+}
+
+//Same as sync_amms, but the progress of each factory is sent to `progress`. Progress is dropped when the channel is full or closed.
+pub async fn sync_amms_with_progress<M: 'static + Middleware>(
+    factories: Vec<Factory>,
+    middleware: Arc<M>,
+    checkpoint_path: Option<&str>,
+    step: u64,
+    progress: Sender<SyncProgress>,
+) -> Result<(Vec<AMM>, u64), AMMError<M>> {
+    let (report, synced_block) = sync_factories(
+        factories,
+        middleware,
+        checkpoint_path,
+        SyncConfig::new(step),
+        true,
+        Some(progress),
+    )
+    .await?;
+
+    Ok((report.synced, synced_block))
+}
+
+//AMMs of a sync that could be populated and the AMMs that were dropped because populating them failed
+#[derive(Debug)]
+pub struct SyncReport<M: Middleware> {
+    pub synced: Vec<AMM>,
+    pub failed: Vec<(H160, AMMError<M>)>,
+}
+
+impl<M: Middleware> Default for SyncReport<M> {
+    fn default() -> Self {
+        SyncReport {
+            synced: vec![],
+            failed: vec![],
+        }
+    }
+}
+
+impl<M: Middleware> SyncReport<M> {
+    pub fn extend(&mut self, report: SyncReport<M>) {
+        self.synced.extend(report.synced);
+        self.failed.extend(report.failed);
+    }
+}
+
+//Same as sync_amms, but AMMs that can not be populated, e.g. pools of a self destructed token, are dropped and reported instead
+//of aborting the sync. Errors while getting the AMMs of a factory still abort the sync.
+pub async fn sync_amms_with_report<M: 'static + Middleware>(
+    factories: Vec<Factory>,
+    middleware: Arc<M>,
+    checkpoint_path: Option<&str>,
+    step: u64,
+) -> Result<(SyncReport<M>, u64), AMMError<M>> {
+    sync_factories(
+        factories,
+        middleware,
+        checkpoint_path,
+        SyncConfig::new(step),
+        false,
+        None,
+    )
+    .await
+}
+
+//Kind of pool at an address passed to sync_amms_from_addresses, the AMMs that can be populated from their address alone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AmmVariant {
+    UniswapV2,
+    UniswapV3,
+    ERC4626Vault,
+    BalancerV2Weighted,
+    BalancerStable,
+    CurveStableSwap,
+    CurveCrypto,
+    Solidly,
+    LBPair,
+    Maverick,
+    Camelot,
+    KyberElastic,
+    Algebra,
+}
+
+impl AmmVariant {
+    //AMM of the variant at the address that still has to be populated
+    pub fn new_empty_amm(&self, address: H160) -> AMM {
+        match self {
+            AmmVariant::UniswapV2 => AMM::UniswapV2Pool(UniswapV2Pool {
+                address,
+                fee: 300,
+                ..Default::default()
+            }),
+            AmmVariant::UniswapV3 => AMM::UniswapV3Pool(UniswapV3Pool {
+                address,
+                ..Default::default()
+            }),
+            AmmVariant::ERC4626Vault => AMM::ERC4626Vault(ERC4626Vault {
+                vault_token: address,
+                ..Default::default()
+            }),
+            AmmVariant::BalancerV2Weighted => AMM::BalancerV2WeightedPool(BalancerV2WeightedPool {
+                address,
+                ..Default::default()
+            }),
+            AmmVariant::BalancerStable => AMM::BalancerStablePool(BalancerStablePool {
+                address,
+                ..Default::default()
+            }),
+            AmmVariant::CurveStableSwap => AMM::CurveStableSwapPool(CurveStableSwapPool {
+                address,
+                ..Default::default()
+            }),
+            AmmVariant::CurveCrypto => AMM::CurveCryptoPool(CurveCryptoPool {
+                address,
+                ..Default::default()
+            }),
+            AmmVariant::Solidly => AMM::SolidlyPool(SolidlyPool {
+                address,
+                ..Default::default()
+            }),
+            AmmVariant::LBPair => AMM::LBPair(LBPair {
+                address,
+                ..Default::default()
+            }),
+            AmmVariant::Maverick => AMM::MaverickPool(MaverickPool {
+                address,
+                ..Default::default()
+            }),
+            AmmVariant::Camelot => AMM::CamelotPool(CamelotPool {
+                address,
+                ..Default::default()
+            }),
+            AmmVariant::KyberElastic => AMM::KyberElasticPool(KyberElasticPool {
+                address,
+                ..Default::default()
+            }),
+            AmmVariant::Algebra => AMM::AlgebraPool(AlgebraPool {
+                address,
+                ..Default::default()
+            }),
+        }
+    }
+}
+
+//Populates the pools at the addresses without enumerating the pools of their factories. The pools of each variant are populated
+//through the batch requests of the variant at `block`, or the latest block if it is not provided. Addresses that are not a pool of
+//their variant are reported as failed with PoolDataError instead of being returned with zero tokens. Uniswap V2 pools are given
+//the 0.3% fee of the Uniswap V2 factory.
+pub async fn sync_amms_from_addresses<M: Middleware>(
+    addresses: Vec<(H160, AmmVariant)>,
+    middleware: Arc<M>,
+    block: Option<u64>,
+) -> Result<(SyncReport<M>, u64), AMMError<M>> {
+    let block_number = match block {
+        Some(block_number) => block_number,
+        None => middleware
+            .get_block_number()
+            .await
+            .map_err(AMMError::MiddlewareError)?
+            .as_u64(),
+    };
+
+    let amms = addresses
+        .into_iter()
+        .map(|(address, variant)| variant.new_empty_amm(address))
+        .collect::<Vec<AMM>>();
+
+    let mut report = SyncReport::default();
+    for amms in checkpoint::sort_amms(amms) {
+        let populated = populate_amms_with_report(
+            amms,
+            block_number,
+            None,
+            BatchBackend::BatchContract,
+            middleware.clone(),
+        )
+        .await;
+
+        report.failed.extend(populated.failed);
+        for amm in populated.synced {
+            if amm_is_populated(&amm) {
+                report.synced.push(amm);
+            } else {
+                tracing::warn!(address = ?amm.address(), "address is not a pool of its variant");
+                report.failed.push((amm.address(), AMMError::PoolDataError));
+            }
+        }
+    }
+
+    Ok((report, block_number))
+}
+
+//Same as sync_amms, but the requests of all factories share a limit of `config.max_concurrent_requests` requests in flight
+pub async fn sync_amms_with_config<M: 'static + Middleware>(
+    factories: Vec<Factory>,
+    middleware: Arc<M>,
+    checkpoint_path: Option<&str>,
+    config: SyncConfig,
+) -> Result<(Vec<AMM>, u64), AMMError<ConcurrencyLimitMiddleware<M>>> {
+    config.validate()?;
+
+    let middleware = Arc::new(ConcurrencyLimitMiddleware::new(
+        middleware,
+        config.max_concurrent_requests,
+    ));
+
+    let (report, synced_block) =
+        sync_factories(factories, middleware, checkpoint_path, config, true, None).await?;
+
+    Ok((report.synced, synced_block))
+}
+
+//Fails on the first AMM that can not be populated if `strict`, otherwise reports it
+async fn sync_factories<M: 'static + Middleware>(
+    factories: Vec<Factory>,
+    middleware: Arc<M>,
+    checkpoint_path: Option<&str>,
+    config: SyncConfig,
+    strict: bool,
+    progress: Option<Sender<SyncProgress>>,
+) -> Result<(SyncReport<M>, u64), AMMError<M>> {
+    config.validate()?;
+
+    let start = Instant::now();
+    let SyncConfig {
+        step,
+        batch_size,
+        pairs_batch_size,
+        batch_backend,
+        detect_transfer_taxes,
+        ..
+    } = config;
+
     tracing::info!(
         step,
         checkpoint_path,
@@ -31,43 +401,145 @@ pub async fn sync_amms<M: 'static + Middleware>(
         .as_u64();
 
     tracing::trace!(current_block);
+    send_progress(
+        progress.as_ref(),
+        SyncProgress::Started {
+            block_number: current_block,
+            factories: factories.len(),
+        },
+    );
 
     //Aggregate the populated pools from each thread
-    let mut aggregated_amms: Vec<AMM> = vec![];
+    let mut aggregated_report = SyncReport::default();
     let mut handles = vec![];
 
     //For each dex supplied, get all pair created events and get reserve values
     for factory in factories.clone() {
         let middleware = middleware.clone();
+        let progress = progress.clone();
 
         //Spawn a new thread to get all pools and sync data for each dex
         handles.push(tokio::spawn(async move {
+            let factory_start = Instant::now();
             tracing::info!("syncing factory {}", factory.address());
             //Get all of the amms from the factory
-            let mut amms: Vec<AMM> = factory
-                .get_all_amms(Some(current_block), middleware.clone(), step)
-                .await?;
-            populate_amms(&mut amms, current_block, middleware.clone()).await?;
+            let amms: Vec<AMM> = match &factory {
+                Factory::UniswapV2Factory(factory) => {
+                    factory
+                        .get_all_pairs_with_batch_size(
+                            Some(current_block),
+                            batch_backend,
+                            pairs_batch_size.unwrap_or(DEFAULT_PAIRS_BATCH_SIZE),
+                            middleware.clone(),
+                        )
+                        .await?
+                }
+                _ => {
+                    factory
+                        .get_all_amms(Some(current_block), middleware.clone(), step)
+                        .await?
+                }
+            };
+
+            //Without a progress channel the AMMs are populated in a single batch as before
+            let total = amms.len();
+            let chunk_size = if let Some(progress) = progress.as_ref() {
+                let pool_count = factory.pool_count(middleware.clone()).await.ok();
+                send_progress(
+                    Some(progress),
+                    SyncProgress::PoolsEnumerated {
+                        factory: factory.address(),
+                        pools: total,
+                        total: pool_count,
+                    },
+                );
+
+                batch_size.unwrap_or(DEFAULT_BATCH_SIZE) * PROGRESS_BATCHES
+            } else {
+                total.max(1)
+            };
+
+            let mut amms = amms.into_iter().peekable();
+            let mut report = SyncReport::default();
+            while amms.peek().is_some() {
+                let mut batch = amms.by_ref().take(chunk_size).collect::<Vec<AMM>>();
+
+                if strict {
+                    populate_amms_with_backend(
+                        &mut batch,
+                        current_block,
+                        batch_size,
+                        batch_backend,
+                        middleware.clone(),
+                    )
+                    .await?;
+                    report.synced.extend(batch);
+                } else {
+                    report.extend(
+                        populate_amms_with_report(
+                            batch,
+                            current_block,
+                            batch_size,
+                            batch_backend,
+                            middleware.clone(),
+                        )
+                        .await,
+                    );
+                }
+
+                send_progress(
+                    progress.as_ref(),
+                    SyncProgress::PoolsPopulated {
+                        factory: factory.address(),
+                        populated: report.synced.len() + report.failed.len(),
+                        total,
+                    },
+                );
+            }
 
             //Clean empty pools
-            amms = remove_empty_amms(amms);
+            report.synced = remove_empty_amms(report.synced);
 
             //If the factory is UniswapV2, set the fee for each pool according to the factory fee
-            if let Factory::UniswapV2Factory(factory) = factory {
-                for amm in amms.iter_mut() {
+            if let Factory::UniswapV2Factory(factory) = &factory {
+                for amm in report.synced.iter_mut() {
                     if let AMM::UniswapV2Pool(ref mut pool) = amm {
                         pool.fee = factory.fee;
                     }
                 }
             }
 
-            Ok::<_, AMMError<M>>(amms)
+            if detect_transfer_taxes {
+                uniswap_v2::transfer_tax::populate_transfer_taxes(
+                    &mut report.synced,
+                    Some(current_block),
+                    None,
+                    middleware.clone(),
+                )
+                .await?;
+            }
+
+            send_progress(
+                progress.as_ref(),
+                SyncProgress::FactorySynced {
+                    factory: factory.address(),
+                    pools: report.synced.len(),
+                    elapsed: factory_start.elapsed(),
+                },
+            );
+
+            Ok::<_, AMMError<M>>((factory.address(), report))
         }));
     }
 
+    let mut pools_per_factory = vec![];
     for handle in handles {
         match handle.await {
-            Ok(sync_result) => aggregated_amms.extend(sync_result?),
+            Ok(sync_result) => {
+                let (factory, report) = sync_result?;
+                pools_per_factory.push((factory, report.synced.len()));
+                aggregated_report.extend(report);
+            }
             Err(err) => {
                 {
                     if err.is_panic() {
@@ -79,21 +551,43 @@ pub async fn sync_amms<M: 'static + Middleware>(
         }
     }
 
+    //The same factory can be configured twice, i.e. by hand and by discover_factories
+    aggregated_report.synced = dedupe_amms(aggregated_report.synced);
+
     //Save a checkpoint if a path is provided
 
     if let Some(checkpoint_path) = checkpoint_path {
         checkpoint::construct_checkpoint(
             factories,
-            &aggregated_amms,
+            &aggregated_report.synced,
             current_block,
             checkpoint_path,
         )?;
     }
 
-    tracing::info!("AMMs synced");
+    tracing::info!(failed = aggregated_report.failed.len(), "AMMs synced");
+    send_progress(
+        progress.as_ref(),
+        SyncProgress::Finished {
+            block_number: current_block,
+            pools_per_factory,
+            elapsed: start.elapsed(),
+        },
+    );
 
     //Return the populated aggregated amms vec
-    Ok((aggregated_amms, current_block))
+    Ok((aggregated_report, current_block))
+}
+
+//Same as sync_amms, but requests that are rate limited or dropped are retried according to `retry` instead of aborting the sync
+pub async fn sync_amms_with_retry<M: 'static + Middleware>(
+    factories: Vec<Factory>,
+    middleware: Arc<M>,
+    checkpoint_path: Option<&str>,
+    step: u64,
+    retry: RetryConfig,
+) -> Result<(Vec<AMM>, u64), AMMError<RetryMiddleware<M>>> {
+    sync_amms(factories, retry.wrap(middleware), checkpoint_path, step).await
 }
 
 pub fn amms_are_congruent(amms: &[AMM]) -> bool {
@@ -113,35 +607,263 @@ pub async fn populate_amms<M: Middleware>(
     block_number: u64,
     middleware: Arc<M>,
 ) -> Result<(), AMMError<M>> {
+    populate_amms_with_batch_size(amms, block_number, None, middleware).await
+}
+
+//Same as populate_amms_with_batch_size, but a batch that fails is split in half until the AMMs that can not be populated are isolated.
+//These AMMs are reported with their error instead of failing the other AMMs of the batch.
+pub async fn populate_amms_with_report<M: Middleware>(
+    amms: Vec<AMM>,
+    block_number: u64,
+    batch_size: Option<usize>,
+    backend: BatchBackend,
+    middleware: Arc<M>,
+) -> SyncReport<M> {
+    let mut report = SyncReport::default();
+
+    if batch_size == Some(0) {
+        report.failed = amms
+            .iter()
+            .map(|amm| (amm.address(), AMMError::ZeroBatchSize("batch_size")))
+            .collect();
+        return report;
+    }
+
+    //Batches are populated from the back of the stack so that the AMMs keep their order
+    let mut batches = amms
+        .chunks(batch_size.unwrap_or(DEFAULT_BATCH_SIZE))
+        .rev()
+        .map(|batch| batch.to_vec())
+        .collect::<Vec<Vec<AMM>>>();
+
+    while let Some(mut batch) = batches.pop() {
+        match populate_amms_with_backend(
+            &mut batch,
+            block_number,
+            batch_size,
+            backend,
+            middleware.clone(),
+        )
+        .await
+        {
+            Ok(()) => report.synced.extend(batch),
+            Err(err) if batch.len() == 1 => {
+                tracing::warn!(address = ?batch[0].address(), ?err, "dropping AMM that could not be populated");
+                report.failed.push((batch[0].address(), err));
+            }
+            Err(err) => {
+                tracing::debug!(
+                    ?err,
+                    batch_size = batch.len(),
+                    "splitting batch that could not be populated"
+                );
+
+                let second_half = batch.split_off(batch.len() / 2);
+                batches.push(second_half);
+                batches.push(batch);
+            }
+        }
+    }
+
+    report
+}
+
+//Same as populate_amms, with `batch_size` pools in each batch request instead of the default batch size of the variant
+pub async fn populate_amms_with_batch_size<M: Middleware>(
+    amms: &mut [AMM],
+    block_number: u64,
+    batch_size: Option<usize>,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    populate_amms_with_backend(
+        amms,
+        block_number,
+        batch_size,
+        BatchBackend::BatchContract,
+        middleware,
+    )
+    .await
+}
+
+//Same as populate_amms_with_batch_size, with the Uniswap V2 and V3 pools read through `backend`
+pub async fn populate_amms_with_backend<M: Middleware>(
+    amms: &mut [AMM],
+    block_number: u64,
+    batch_size: Option<usize>,
+    backend: BatchBackend,
+    middleware: Arc<M>,
+) -> Result<(), AMMError<M>> {
+    if batch_size == Some(0) {
+        return Err(AMMError::ZeroBatchSize("batch_size"));
+    }
+
+    let step = batch_size.unwrap_or(DEFAULT_BATCH_SIZE);
+
     if amms_are_congruent(amms) {
         match amms[0] {
             AMM::UniswapV2Pool(_) => {
-                let step = 127; //Max batch size for call
                 for amm_chunk in amms.chunks_mut(step) {
-                    uniswap_v2::batch_request::get_amm_data_batch_request(
-                        amm_chunk,
+                    match backend {
+                        BatchBackend::BatchContract => {
+                            uniswap_v2::batch_request::get_amm_data_batch_request(
+                                amm_chunk,
+                                Some(block_number),
+                                middleware.clone(),
+                            )
+                            .await?
+                        }
+                        BatchBackend::Multicall3(multicall_address) => {
+                            uniswap_v2::batch_request::get_amm_data_multicall(
+                                amm_chunk,
+                                Some(block_number),
+                                multicall_address,
+                                middleware.clone(),
+                            )
+                            .await?
+                        }
+                    }
+                }
+            }
+
+            AMM::UniswapV3Pool(_) => {
+                let step = batch_size.unwrap_or(DEFAULT_V3_BATCH_SIZE);
+                for amm_chunk in amms.chunks_mut(step) {
+                    match backend {
+                        BatchBackend::BatchContract => {
+                            uniswap_v3::batch_request::get_amm_data_batch_request(
+                                amm_chunk,
+                                block_number,
+                                middleware.clone(),
+                            )
+                            .await?
+                        }
+                        BatchBackend::Multicall3(multicall_address) => {
+                            uniswap_v3::batch_request::get_amm_data_multicall(
+                                amm_chunk,
+                                block_number,
+                                multicall_address,
+                                middleware.clone(),
+                            )
+                            .await?
+                        }
+                    }
+                }
+            }
+
+            // TODO: Implement batch request
+            AMM::ERC4626Vault(_) => {
+                for amm in amms {
+                    amm.populate_data(Some(block_number), middleware.clone())
+                        .await?;
+                }
+            }
+
+            // TODO: Implement batch request
+            AMM::BalancerV2WeightedPool(_) => {
+                for amm in amms {
+                    //Pools discovered through the vault can be of any pool type, pools that are not weighted pools are left empty
+                    if let AMM::BalancerV2WeightedPool(pool) = amm {
+                        if pool
+                            .populate_data(Some(block_number), middleware.clone())
+                            .await
+                            .is_err()
+                        {
+                            tracing::debug!(?pool.address, "pool is not a weighted pool, skipping");
+                            pool.tokens = vec![];
+                        }
+                    }
+                }
+            }
+
+            AMM::CurveStableSwapPool(_)
+            | AMM::BalancerStablePool(_)
+            | AMM::CurveCryptoPool(_)
+            | AMM::LBPair(_)
+            | AMM::MaverickPool(_)
+            | AMM::AlgebraPool(_)
+            | AMM::RateProviderAmm(_)
+            | AMM::CurveMetaPool(_)
+            | AMM::DodoPool(_)
+            | AMM::KyberElasticPool(_) => {
+                for amm in amms {
+                    amm.populate_data(Some(block_number), middleware.clone())
+                        .await?;
+                }
+            }
+
+            AMM::UniswapV4Pool(_) => {
+                for amm_chunk in amms.chunks_mut(step) {
+                    let pools = amm_chunk
+                        .iter_mut()
+                        .filter_map(|amm| match amm {
+                            AMM::UniswapV4Pool(pool) => Some(pool),
+                            _ => None,
+                        })
+                        .collect();
+
+                    uniswap_v4::batch_request::get_v4_pool_data_batch_request(
+                        pools,
+                        Some(block_number),
                         middleware.clone(),
                     )
                     .await?;
                 }
             }
 
-            AMM::UniswapV3Pool(_) => {
-                let step = 76; //Max batch size for call
+            AMM::SolidlyPool(_) => {
                 for amm_chunk in amms.chunks_mut(step) {
-                    uniswap_v3::batch_request::get_amm_data_batch_request(
-                        amm_chunk,
-                        block_number,
+                    let pools = amm_chunk
+                        .iter_mut()
+                        .filter_map(|amm| match amm {
+                            AMM::SolidlyPool(pool) => Some(pool),
+                            _ => None,
+                        })
+                        .collect();
+
+                    solidly::batch_request::get_solidly_pool_data_batch_request(
+                        pools,
+                        Some(block_number),
                         middleware.clone(),
                     )
                     .await?;
                 }
             }
 
-            // TODO: Implement batch request
-            AMM::ERC4626Vault(_) => {
-                for amm in amms {
-                    amm.populate_data(None, middleware.clone()).await?;
+            AMM::CamelotPool(_) => {
+                for amm_chunk in amms.chunks_mut(step) {
+                    let pools = amm_chunk
+                        .iter_mut()
+                        .filter_map(|amm| match amm {
+                            AMM::CamelotPool(pool) => Some(pool),
+                            _ => None,
+                        })
+                        .collect();
+
+                    camelot::batch_request::get_camelot_pool_data_batch_request(
+                        pools,
+                        Some(block_number),
+                        middleware.clone(),
+                    )
+                    .await?;
+                }
+            }
+
+            AMM::BancorV3Pool(_) => {
+                for amm_chunk in amms.chunks_mut(step) {
+                    let pools = amm_chunk
+                        .iter_mut()
+                        .filter_map(|amm| match amm {
+                            AMM::BancorV3Pool(pool) => Some(pool),
+                            _ => None,
+                        })
+                        .collect();
+
+                    bancor_v3::batch_request::get_bancor_v3_pool_data_batch_request(
+                        pools,
+                        Some(block_number),
+                        middleware.clone(),
+                    )
+                    .await?;
                 }
             }
         }
@@ -154,27 +876,953 @@ pub async fn populate_amms<M: Middleware>(
 }
 
 pub fn remove_empty_amms(amms: Vec<AMM>) -> Vec<AMM> {
-    let mut cleaned_amms = vec![];
+    amms.into_iter().filter(amm_is_populated).collect()
+}
 
-    for amm in amms.into_iter() {
-        match amm {
-            AMM::UniswapV2Pool(ref uniswap_v2_pool) => {
-                if !uniswap_v2_pool.token_a.is_zero() && !uniswap_v2_pool.token_b.is_zero() {
-                    cleaned_amms.push(amm)
+//Keeps one AMM per address so that state changes are not applied twice. The last instance of an address, which is the one that
+//was populated most recently, replaces the first instance in the order of the AMMs.
+pub fn dedupe_amms(amms: Vec<AMM>) -> Vec<AMM> {
+    let mut indices: HashMap<H160, usize> = HashMap::new();
+    let mut deduped_amms: Vec<AMM> = vec![];
+    let mut duplicates = vec![];
+
+    for amm in amms {
+        match indices.get(&amm.address()) {
+            Some(&index) => {
+                duplicates.push(amm.address());
+                deduped_amms[index] = amm;
+            }
+            None => {
+                indices.insert(amm.address(), deduped_amms.len());
+                deduped_amms.push(amm);
+            }
+        }
+    }
+
+    if !duplicates.is_empty() {
+        tracing::warn!(?duplicates, "removed {} duplicate AMMs", duplicates.len());
+    }
+
+    deduped_amms
+}
+
+//Whether the tokens of the AMM were populated, AMMs whose batch request returned empty data have zero tokens
+pub fn amm_is_populated(amm: &AMM) -> bool {
+    match amm {
+        AMM::UniswapV2Pool(uniswap_v2_pool) => {
+            !uniswap_v2_pool.token_a.is_zero() && !uniswap_v2_pool.token_b.is_zero()
+        }
+        AMM::UniswapV3Pool(uniswap_v3_pool) => {
+            !uniswap_v3_pool.token_a.is_zero() && !uniswap_v3_pool.token_b.is_zero()
+        }
+        AMM::ERC4626Vault(erc4626_vault) => {
+            !erc4626_vault.vault_token.is_zero() && !erc4626_vault.asset_token.is_zero()
+        }
+        AMM::BalancerV2WeightedPool(balancer_v2_pool) => {
+            !balancer_v2_pool.tokens.is_empty()
+                && balancer_v2_pool.tokens.iter().all(|token| !token.is_zero())
+        }
+        AMM::BalancerStablePool(balancer_stable_pool) => balancer_stable_pool.data_is_populated(),
+        AMM::CurveStableSwapPool(curve_pool) => {
+            !curve_pool.tokens.is_empty() && curve_pool.tokens.iter().all(|token| !token.is_zero())
+        }
+        AMM::CurveCryptoPool(curve_crypto_pool) => {
+            !curve_crypto_pool.tokens.is_empty()
+                && curve_crypto_pool
+                    .tokens
+                    .iter()
+                    .all(|token| !token.is_zero())
+        }
+        //Pools with hooks are filtered by the hook patterns of the PoolManager when they are discovered
+        AMM::UniswapV4Pool(uniswap_v4_pool) => uniswap_v4_pool.data_is_populated(),
+        AMM::SolidlyPool(solidly_pool) => {
+            !solidly_pool.token_a.is_zero() && !solidly_pool.token_b.is_zero()
+        }
+        AMM::LBPair(lb_pair) => !lb_pair.token_x.is_zero() && !lb_pair.token_y.is_zero(),
+        AMM::MaverickPool(maverick_pool) => maverick_pool.data_is_populated(),
+        AMM::AlgebraPool(algebra_pool) => algebra_pool.data_is_populated(),
+        AMM::RateProviderAmm(rate_provider_amm) => rate_provider_amm.data_is_populated(),
+        AMM::CurveMetaPool(curve_meta_pool) => curve_meta_pool.data_is_populated(),
+        AMM::DodoPool(dodo_pool) => dodo_pool.data_is_populated(),
+        AMM::KyberElasticPool(kyber_elastic_pool) => kyber_elastic_pool.data_is_populated(),
+        AMM::CamelotPool(camelot_pool) => camelot_pool.data_is_populated(),
+        AMM::BancorV3Pool(bancor_v3_pool) => bancor_v3_pool.data_is_populated(),
+    }
+}
+
+//Syncs the reserves of Uniswap V2 pools and the price, tick and liquidity of Uniswap V3 pools at `block` through Multicall3,
+//batching the calls of `chunk_size` pools per request. Pools of other variants are left unchanged, as are pools whose calls revert.
+pub async fn sync_pools<M: Middleware>(
+    pools: &mut [AMM],
+    middleware: Arc<M>,
+    block: BlockId,
+    chunk_size: usize,
+) -> Result<(), AMMError<M>> {
+    if chunk_size == 0 {
+        return Err(AMMError::ZeroBatchSize("chunk_size"));
+    }
+
+    let mut pools = pools
+        .iter_mut()
+        .filter(|amm| matches!(amm, AMM::UniswapV2Pool(_) | AMM::UniswapV3Pool(_)))
+        .collect::<Vec<&mut AMM>>();
+
+    tracing::info!(pools = pools.len(), chunk_size, ?block, "syncing pools");
+
+    for chunk in pools.chunks_mut(chunk_size) {
+        let mut multicall = Multicall::new(middleware.clone(), None).await?;
+
+        for amm in chunk.iter() {
+            match amm {
+                AMM::UniswapV2Pool(pool) => {
+                    let pair = uniswap_v2::IUniswapV2Pair::new(pool.address, middleware.clone());
+                    multicall.add_call(pair.get_reserves(), true);
                 }
+                AMM::UniswapV3Pool(pool) => {
+                    let pool = uniswap_v3::IUniswapV3Pool::new(pool.address, middleware.clone());
+                    multicall.add_call(pool.slot_0(), true);
+                    multicall.add_call(pool.liquidity(), true);
+                }
+                _ => unreachable!(),
             }
-            AMM::UniswapV3Pool(ref uniswap_v3_pool) => {
-                if !uniswap_v3_pool.token_a.is_zero() && !uniswap_v3_pool.token_b.is_zero() {
-                    cleaned_amms.push(amm)
+        }
+
+        let mut results = multicall
+            .as_aggregate_3()
+            .block(block)
+            .call()
+            .await?
+            .into_iter()
+            .map(|result| result.success.then_some(result.return_data));
+
+        for amm in chunk.iter_mut() {
+            let synced = match amm {
+                AMM::UniswapV2Pool(pool) => {
+                    decode_reserves(pool, results.next().flatten().as_deref())
                 }
+                AMM::UniswapV3Pool(pool) => decode_slot_0_and_liquidity(
+                    pool,
+                    results.next().flatten().as_deref(),
+                    results.next().flatten().as_deref(),
+                ),
+                _ => unreachable!(),
+            };
+
+            if synced.is_none() {
+                tracing::warn!(address = ?amm.address(), "could not sync pool, leaving it unchanged");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+//Brings AMMs that were synced up to `from_block - 1` up to date with the logs in [from_block, to_block] instead of populating them again.
+//The logs of the event signatures that the AMMs sync on are fetched for the addresses that emit them, in chunks of
+//RESYNC_ADDRESSES_PER_FILTER addresses, and applied in block and log index order. Returns the number of logs applied to each AMM.
+pub async fn resync_amms<M: Middleware>(
+    amms: &mut [AMM],
+    from_block: u64,
+    to_block: u64,
+    middleware: Arc<M>,
+) -> Result<HashMap<H160, usize>, AMMError<M>> {
+    if from_block > to_block {
+        return Err(AMMError::InvalidBlockRange(from_block, to_block));
+    }
+
+    let mut applied_logs = amms
+        .iter()
+        .map(|amm| (amm.address(), 0))
+        .collect::<HashMap<H160, usize>>();
+
+    //AMMs in a singleton vault sync from the logs of the vault, metapools also from the logs of their base pool
+    let mut addresses = HashSet::new();
+    let mut event_signatures = HashSet::new();
+    for amm in amms.iter() {
+        addresses.insert(amm.address());
+        addresses.extend(amm.dependency());
+        match amm {
+            AMM::BalancerV2WeightedPool(pool) => {
+                addresses.insert(pool.vault);
+            }
+            AMM::BalancerStablePool(pool) => {
+                addresses.insert(pool.vault);
+            }
+            AMM::UniswapV4Pool(pool) => {
+                addresses.insert(pool.pool_manager);
             }
-            AMM::ERC4626Vault(ref erc4626_vault) => {
-                if !erc4626_vault.vault_token.is_zero() && !erc4626_vault.asset_token.is_zero() {
-                    cleaned_amms.push(amm)
+            _ => {}
+        }
+
+        event_signatures.extend(amm.sync_on_event_signatures());
+    }
+
+    if event_signatures.is_empty() {
+        return Ok(applied_logs);
+    }
+
+    let addresses = addresses.into_iter().collect::<Vec<H160>>();
+    let event_signatures = event_signatures.into_iter().collect::<Vec<H256>>();
+
+    tracing::info!(
+        amms = amms.len(),
+        addresses = addresses.len(),
+        from_block,
+        to_block,
+        "resyncing AMMs"
+    );
+
+    let mut logs = vec![];
+    for chunk in addresses.chunks(RESYNC_ADDRESSES_PER_FILTER) {
+        let filter = Filter::new()
+            .address(chunk.to_vec())
+            .topic0(event_signatures.clone());
+
+        logs.extend(
+            get_logs_with_adaptive_range(
+                middleware.as_ref(),
+                &filter,
+                from_block,
+                to_block,
+                MAX_LOG_RANGE_SPLITS,
+            )
+            .await
+            .map_err(AMMError::MiddlewareError)?,
+        );
+    }
+
+    //Logs of different chunks are interleaved in the range
+    logs.sort_by_key(|log: &Log| (log.block_number, log.log_index));
+
+    let dependent_amms = get_dependent_amms(amms.iter());
+    let indices = amms
+        .iter()
+        .enumerate()
+        .map(|(index, amm)| (amm.address(), index))
+        .collect::<HashMap<H160, usize>>();
+
+    for log in logs {
+        if log.topics.is_empty() {
+            continue;
+        }
+
+        let mut amm_addresses = get_amm_addresses_from_log(&log);
+        if let Some(dependents) = dependent_amms.get(&log.address) {
+            amm_addresses.extend(dependents);
+        }
+
+        for amm_address in amm_addresses {
+            let Some(&index) = indices.get(&amm_address) else {
+                continue;
+            };
+
+            //Contracts can emit events that other AMMs sync on, i.e. the vault of a pool of another variant
+            let amm = &mut amms[index];
+            if amm.sync_on_event_signatures().contains(&log.topics[0]) {
+                amm.sync_from_log(log.clone())?;
+                *applied_logs.entry(amm_address).or_default() += 1;
+            }
+        }
+    }
+
+    Ok(applied_logs)
+}
+
+fn decode_reserves(pool: &mut uniswap_v2::UniswapV2Pool, reserves: Option<&[u8]>) -> Option<()> {
+    let reserves = decode(
+        &[
+            ParamType::Uint(112),
+            ParamType::Uint(112),
+            ParamType::Uint(32),
+        ],
+        reserves?,
+    )
+    .ok()?;
+
+    pool.reserve_0 = reserves.first()?.clone().into_uint()?.as_u128();
+    pool.reserve_1 = reserves.get(1)?.clone().into_uint()?.as_u128();
+
+    Some(())
+}
+
+fn decode_slot_0_and_liquidity(
+    pool: &mut uniswap_v3::UniswapV3Pool,
+    slot_0: Option<&[u8]>,
+    liquidity: Option<&[u8]>,
+) -> Option<()> {
+    let slot_0 = decode(
+        &[
+            ParamType::Uint(160),
+            ParamType::Int(24),
+            ParamType::Uint(16),
+            ParamType::Uint(16),
+            ParamType::Uint(16),
+            ParamType::Uint(32),
+            ParamType::Bool,
+        ],
+        slot_0?,
+    )
+    .ok()?;
+    let liquidity = decode(&[ParamType::Uint(128)], liquidity?).ok()?;
+
+    pool.sqrt_price = slot_0.first()?.clone().into_uint()?;
+    pool.tick = I256::from_raw(slot_0.get(1)?.clone().into_int()?).as_i32();
+    pool.liquidity = liquidity.first()?.clone().into_uint()?.as_u128();
+
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        str::FromStr,
+        sync::{Arc, Mutex},
+    };
+
+    use async_trait::async_trait;
+    use ethers::{
+        abi::{encode, Token},
+        providers::{
+            Http, JsonRpcError, Middleware, MockProvider, MockResponse, Provider, ProviderError,
+        },
+        types::{
+            transaction::eip2718::TypedTransaction, BlockId, Bytes, Log, H160, I256, U256, U64,
+        },
+    };
+    use tokio::sync::mpsc;
+
+    use crate::{
+        amm::{
+            batch_backend::BatchBackend,
+            erc_4626::ERC4626Vault,
+            factory::{AutomatedMarketMakerFactory, Factory},
+            uniswap_v2::{
+                factory::{UniswapV2Factory, PAIR_CREATED_EVENT_SIGNATURE},
+                UniswapV2Pool, SYNC_EVENT_SIGNATURE,
+            },
+            uniswap_v3::UniswapV3Pool,
+            AutomatedMarketMaker, AMM,
+        },
+        errors::AMMError,
+    };
+
+    use super::{
+        amm_is_populated, decode_reserves, decode_slot_0_and_liquidity, dedupe_amms, populate_amms,
+        populate_amms_with_backend, populate_amms_with_report, progress::SyncProgress, resync_amms,
+        sync_amms, sync_amms_from_addresses, sync_amms_with_config, sync_amms_with_progress,
+        sync_pools, AmmVariant, SyncConfig,
+    };
+
+    //Records the block of every eth_call
+    #[derive(Debug)]
+    struct BlockRecordingMiddleware {
+        inner: Provider<MockProvider>,
+        blocks: Mutex<Vec<Option<BlockId>>>,
+    }
+
+    #[async_trait]
+    impl Middleware for BlockRecordingMiddleware {
+        type Error = ProviderError;
+        type Provider = MockProvider;
+        type Inner = Provider<MockProvider>;
+
+        fn inner(&self) -> &Provider<MockProvider> {
+            &self.inner
+        }
+
+        async fn call(
+            &self,
+            tx: &TypedTransaction,
+            block: Option<BlockId>,
+        ) -> Result<Bytes, ProviderError> {
+            self.blocks
+                .lock()
+                .expect("lock is not poisoned")
+                .push(block);
+            self.inner.call(tx, block).await
+        }
+    }
+
+    #[test]
+    fn test_decode_pool_state() {
+        let mut pool = UniswapV2Pool::default();
+        let reserves = encode(&[
+            Token::Uint(U256::from(100)),
+            Token::Uint(U256::from(200)),
+            Token::Uint(U256::from(1)),
+        ]);
+        assert!(decode_reserves(&mut pool, Some(&reserves)).is_some());
+        assert_eq!((pool.reserve_0, pool.reserve_1), (100, 200));
+
+        //Reverted calls leave the pool unchanged
+        assert!(decode_reserves(&mut pool, None).is_none());
+        assert_eq!((pool.reserve_0, pool.reserve_1), (100, 200));
+
+        let mut pool = UniswapV3Pool::default();
+        let slot_0 = encode(&[
+            Token::Uint(U256::one() << 96),
+            Token::Int(I256::from(-887272).into_raw()),
+            Token::Uint(U256::zero()),
+            Token::Uint(U256::zero()),
+            Token::Uint(U256::zero()),
+            Token::Uint(U256::zero()),
+            Token::Bool(true),
+        ]);
+        let liquidity = encode(&[Token::Uint(U256::from(1000))]);
+        assert!(decode_slot_0_and_liquidity(&mut pool, Some(&slot_0), Some(&liquidity)).is_some());
+        assert_eq!(pool.sqrt_price, U256::one() << 96);
+        assert_eq!(pool.tick, -887272);
+        assert_eq!(pool.liquidity, 1000);
+    }
+
+    #[tokio::test]
+    async fn test_sync_pools() -> eyre::Result<()> {
+        let rpc_endpoint = std::env::var("ETHEREUM_RPC_ENDPOINT")?;
+        let middleware = Arc::new(Provider::<Http>::try_from(rpc_endpoint)?);
+
+        let mut pools = vec![
+            //USDC/WETH Uniswap V2 pair
+            AMM::UniswapV2Pool(UniswapV2Pool {
+                address: H160::from_str("0xB4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc")?,
+                ..Default::default()
+            }),
+            //USDC/WETH 0.05% Uniswap V3 pool
+            AMM::UniswapV3Pool(UniswapV3Pool {
+                address: H160::from_str("0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640")?,
+                ..Default::default()
+            }),
+        ];
+
+        let block_number = middleware.get_block_number().await?;
+        sync_pools(&mut pools, middleware, BlockId::from(block_number), 1).await?;
+
+        if let AMM::UniswapV2Pool(pool) = &pools[0] {
+            assert!(pool.reserve_0 > 0 && pool.reserve_1 > 0);
+        }
+
+        if let AMM::UniswapV3Pool(pool) = &pools[1] {
+            assert!(!pool.sqrt_price.is_zero());
+            assert!(pool.liquidity > 0);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_populate_amms_with_multicall3() -> eyre::Result<()> {
+        let pools = || {
+            vec![
+                AMM::UniswapV2Pool(UniswapV2Pool {
+                    address: H160::from_low_u64_be(1),
+                    ..Default::default()
+                }),
+                AMM::UniswapV2Pool(UniswapV2Pool {
+                    address: H160::from_low_u64_be(2),
+                    ..Default::default()
+                }),
+            ]
+        };
+        let word = |token: Token| Token::Bytes(encode(&[token]));
+        let success = |return_data: Token| Token::Tuple(vec![Token::Bool(true), return_data]);
+
+        //The batch contract returns the first pool and an empty second pool
+        let (provider, mock) = Provider::mocked();
+        mock.push(Bytes::from(encode(&[Token::Array(vec![
+            Token::Tuple(vec![
+                Token::Address(H160::from_low_u64_be(10)),
+                Token::Uint(U256::from(18)),
+                Token::Address(H160::from_low_u64_be(11)),
+                Token::Uint(U256::from(6)),
+                Token::Uint(U256::from(1000)),
+                Token::Uint(U256::from(2000)),
+            ]),
+            Token::Tuple(vec![
+                Token::Address(H160::zero()),
+                Token::Uint(U256::zero()),
+                Token::Address(H160::zero()),
+                Token::Uint(U256::zero()),
+                Token::Uint(U256::zero()),
+                Token::Uint(U256::zero()),
+            ]),
+        ])])))?;
+
+        let mut batch_contract_pools = pools();
+        populate_amms_with_backend(
+            &mut batch_contract_pools,
+            100,
+            None,
+            BatchBackend::BatchContract,
+            Arc::new(provider),
+        )
+        .await?;
+
+        //Through Multicall3 the token0 call of the second pool reverts, the decimals are only read for the first pool
+        let (provider, mock) = Provider::mocked();
+        mock.push(Bytes::from(encode(&[Token::Array(vec![
+            success(word(Token::Uint(U256::from(18)))),
+            success(word(Token::Uint(U256::from(6)))),
+        ])])))?;
+        mock.push(Bytes::from(encode(&[Token::Array(vec![
+            success(word(Token::Address(H160::from_low_u64_be(10)))),
+            success(word(Token::Address(H160::from_low_u64_be(11)))),
+            success(Token::Bytes(encode(&[
+                Token::Uint(U256::from(1000)),
+                Token::Uint(U256::from(2000)),
+                Token::Uint(U256::zero()),
+            ]))),
+            Token::Tuple(vec![Token::Bool(false), Token::Bytes(vec![])]),
+            success(word(Token::Address(H160::from_low_u64_be(12)))),
+            success(Token::Bytes(encode(&[
+                Token::Uint(U256::zero()),
+                Token::Uint(U256::zero()),
+                Token::Uint(U256::zero()),
+            ]))),
+        ])])))?;
+
+        let mut multicall_pools = pools();
+        populate_amms_with_backend(
+            &mut multicall_pools,
+            100,
+            None,
+            BatchBackend::multicall3(),
+            Arc::new(provider),
+        )
+        .await?;
+
+        assert_eq!(
+            serde_json::to_string(&multicall_pools)?,
+            serde_json::to_string(&batch_contract_pools)?
+        );
+        assert!(amm_is_populated(&multicall_pools[0]));
+        assert!(!amm_is_populated(&multicall_pools[1]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedupe_amms() -> eyre::Result<()> {
+        let pair_created_log = |pair: u64| Log {
+            topics: vec![
+                PAIR_CREATED_EVENT_SIGNATURE,
+                H160::from_low_u64_be(pair * 10).into(),
+                H160::from_low_u64_be(pair * 10 + 1).into(),
+            ],
+            data: Bytes::from(encode(&[
+                Token::Address(H160::from_low_u64_be(pair)),
+                Token::Uint(U256::from(pair)),
+            ])),
+            ..Default::default()
+        };
+
+        //The same factory configured by hand and discovered, with the pairs 2 and 3 in both pool sets
+        let factory = UniswapV2Factory::new(H160::from_low_u64_be(100), 0, 300);
+        let discovered_factory = UniswapV2Factory::new(H160::from_low_u64_be(100), 0, 250);
+
+        let mut amms = vec![];
+        for pair in [1, 2, 3] {
+            amms.push(factory.new_empty_amm_from_log(pair_created_log(pair))?);
+        }
+        for pair in [2, 3, 4] {
+            amms.push(discovered_factory.new_empty_amm_from_log(pair_created_log(pair))?);
+        }
+
+        let amms = dedupe_amms(amms);
+        assert_eq!(
+            amms.iter().map(|amm| amm.address()).collect::<Vec<H160>>(),
+            (1..=4).map(H160::from_low_u64_be).collect::<Vec<H160>>()
+        );
+
+        //The instances of the factory that came last are kept
+        let fees = amms
+            .iter()
+            .filter_map(|amm| match amm {
+                AMM::UniswapV2Pool(pool) => Some(pool.fee),
+                _ => None,
+            })
+            .collect::<Vec<u32>>();
+        assert_eq!(fees, vec![300, 250, 250, 250]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resync_amms() -> eyre::Result<()> {
+        let sync_log = |pool: u64, reserve: u64, block_number: u64, log_index: u64| Log {
+            address: H160::from_low_u64_be(pool),
+            topics: vec![SYNC_EVENT_SIGNATURE],
+            data: Bytes::from(encode(&[
+                Token::Uint(U256::from(reserve)),
+                Token::Uint(U256::from(reserve)),
+            ])),
+            block_number: Some(U64::from(block_number)),
+            log_index: Some(U256::from(log_index)),
+            ..Default::default()
+        };
+
+        let mut amms = (1..=3)
+            .map(|address| {
+                AMM::UniswapV2Pool(UniswapV2Pool {
+                    address: H160::from_low_u64_be(address),
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<AMM>>();
+
+        //The provider returns the logs out of order, the last log of the first pool is at block 11
+        let (provider, mock) = Provider::mocked();
+        mock.push(vec![
+            sync_log(1, 300, 11, 0),
+            sync_log(2, 200, 10, 1),
+            sync_log(1, 100, 10, 0),
+        ])?;
+
+        let applied_logs = resync_amms(&mut amms, 10, 20, Arc::new(provider)).await?;
+        assert_eq!(applied_logs[&H160::from_low_u64_be(1)], 2);
+        assert_eq!(applied_logs[&H160::from_low_u64_be(2)], 1);
+        assert_eq!(applied_logs[&H160::from_low_u64_be(3)], 0);
+
+        if let AMM::UniswapV2Pool(pool) = &amms[0] {
+            assert_eq!(pool.reserve_0, 300);
+        }
+        if let AMM::UniswapV2Pool(pool) = &amms[2] {
+            assert_eq!(pool.reserve_0, 0);
+        }
+
+        assert!(
+            resync_amms(&mut amms, 20, 10, Arc::new(Provider::mocked().0))
+                .await
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_populate_amms_with_report() -> eyre::Result<()> {
+        let pool = |address: u64| {
+            AMM::UniswapV2Pool(UniswapV2Pool {
+                address: H160::from_low_u64_be(address),
+                ..Default::default()
+            })
+        };
+        let pool_data = |token_a: u64| {
+            Bytes::from(encode(&[Token::Array(vec![Token::Tuple(vec![
+                Token::Address(H160::from_low_u64_be(token_a)),
+                Token::Uint(U256::from(18)),
+                Token::Address(H160::from_low_u64_be(token_a + 1)),
+                Token::Uint(U256::from(18)),
+                Token::Uint(U256::from(1000)),
+                Token::Uint(U256::from(2000)),
+            ])])]))
+        };
+        let revert = || {
+            MockResponse::Error(JsonRpcError {
+                code: 3,
+                message: "execution reverted".to_string(),
+                data: None,
+            })
+        };
+
+        //The batch of all three pools and the batch of the last two revert, the first two pools come back populated
+        let (provider, mock) = Provider::mocked();
+        mock.push_response(revert());
+        mock.push(pool_data(20))?;
+        mock.push_response(revert());
+        mock.push(pool_data(10))?;
+        mock.push_response(revert());
+
+        let report = populate_amms_with_report(
+            vec![pool(1), pool(2), pool(3)],
+            100,
+            None,
+            BatchBackend::BatchContract,
+            Arc::new(provider),
+        )
+        .await;
+
+        assert_eq!(report.synced.len(), 2);
+        for (amm, token_a) in report.synced.iter().zip([10, 20]) {
+            match amm {
+                AMM::UniswapV2Pool(pool) => {
+                    assert_eq!(pool.token_a, H160::from_low_u64_be(token_a));
+                    assert_eq!((pool.reserve_0, pool.reserve_1), (1000, 2000));
                 }
+                _ => panic!("expected a Uniswap V2 pool"),
+            }
+        }
+
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, H160::from_low_u64_be(3));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_zero_batch_size() -> eyre::Result<()> {
+        assert!(SyncConfig::new(1000)
+            .with_batch_size(100)
+            .validate::<Provider<MockProvider>>()
+            .is_ok());
+        for config in [
+            SyncConfig::new(0),
+            SyncConfig::new(1000).with_max_concurrent_requests(0),
+            SyncConfig::new(1000).with_batch_size(0),
+            SyncConfig::new(1000).with_pairs_batch_size(0),
+        ] {
+            assert!(matches!(
+                config.validate::<Provider<MockProvider>>(),
+                Err(AMMError::ZeroBatchSize(_))
+            ));
+        }
+
+        //No responses are mocked, the zero values are rejected before any request is sent
+        let (provider, _mock) = Provider::mocked();
+        let middleware = Arc::new(provider);
+        let factory = Factory::UniswapV2Factory(UniswapV2Factory::new(H160::zero(), 0, 300));
+        assert!(matches!(
+            sync_amms_with_config(
+                vec![factory],
+                middleware.clone(),
+                None,
+                SyncConfig::new(1000).with_max_concurrent_requests(0),
+            )
+            .await,
+            Err(AMMError::ZeroBatchSize("max_concurrent_requests"))
+        ));
+
+        let mut amms = vec![AMM::UniswapV2Pool(UniswapV2Pool::default())];
+        assert!(matches!(
+            populate_amms_with_backend(
+                &mut amms,
+                100,
+                Some(0),
+                BatchBackend::BatchContract,
+                middleware.clone(),
+            )
+            .await,
+            Err(AMMError::ZeroBatchSize("batch_size"))
+        ));
+
+        let report = populate_amms_with_report(
+            amms,
+            100,
+            Some(0),
+            BatchBackend::BatchContract,
+            middleware.clone(),
+        )
+        .await;
+        assert!(report.synced.is_empty());
+        assert_eq!(report.failed.len(), 1);
+
+        let mut pools = vec![AMM::UniswapV2Pool(UniswapV2Pool::default())];
+        assert!(matches!(
+            sync_pools(&mut pools, middleware, BlockId::from(100_u64), 0).await,
+            Err(AMMError::ZeroBatchSize("chunk_size"))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_amms_from_addresses() -> eyre::Result<()> {
+        let (pair, not_a_pair) = (H160::from_low_u64_be(1), H160::from_low_u64_be(2));
+
+        //The batch request returns zeroed data for addresses that are not pairs
+        let pool_data = |token_a: H160, token_b: H160, reserve: u64| {
+            Token::Tuple(vec![
+                Token::Address(token_a),
+                Token::Uint(U256::from(18)),
+                Token::Address(token_b),
+                Token::Uint(U256::from(18)),
+                Token::Uint(U256::from(reserve)),
+                Token::Uint(U256::from(reserve)),
+            ])
+        };
+        let (provider, mock) = Provider::mocked();
+        mock.push(Bytes::from(encode(&[Token::Array(vec![
+            pool_data(H160::from_low_u64_be(10), H160::from_low_u64_be(11), 1000),
+            pool_data(H160::zero(), H160::zero(), 0),
+        ])])))?;
+
+        let (report, synced_block) = sync_amms_from_addresses(
+            vec![
+                (pair, AmmVariant::UniswapV2),
+                (not_a_pair, AmmVariant::UniswapV2),
+            ],
+            Arc::new(provider),
+            Some(100),
+        )
+        .await?;
+        assert_eq!(synced_block, 100);
+
+        match &report.synced[..] {
+            [AMM::UniswapV2Pool(pool)] => {
+                assert_eq!(pool.address, pair);
+                assert_eq!((pool.reserve_0, pool.fee), (1000, 300));
             }
+            _ => panic!("expected the Uniswap V2 pair"),
         }
+
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, not_a_pair);
+
+        Ok(())
     }
 
-    cleaned_amms
+    #[tokio::test]
+    async fn test_sync_amms_pins_calls_to_synced_block() -> eyre::Result<()> {
+        let pair = H160::from_low_u64_be(1);
+
+        //Responses are returned last in first out: the block number, the number of pairs, the pairs and the pair data
+        let (provider, mock) = Provider::mocked();
+        mock.push(Bytes::from(encode(&[Token::Array(vec![Token::Tuple(
+            vec![
+                Token::Address(H160::from_low_u64_be(10)),
+                Token::Uint(U256::from(18)),
+                Token::Address(H160::from_low_u64_be(11)),
+                Token::Uint(U256::from(18)),
+                Token::Uint(U256::from(1000)),
+                Token::Uint(U256::from(2000)),
+            ],
+        )])])))?;
+        mock.push(Bytes::from(encode(&[Token::Array(vec![Token::Address(
+            pair,
+        )])])))?;
+        mock.push(Bytes::from(encode(&[Token::Uint(U256::one())])))?;
+        mock.push(U64::from(100))?;
+
+        let middleware = Arc::new(BlockRecordingMiddleware {
+            inner: provider,
+            blocks: Mutex::new(vec![]),
+        });
+        let factory =
+            Factory::UniswapV2Factory(UniswapV2Factory::new(H160::from_low_u64_be(2), 0, 300));
+
+        let (amms, synced_block) = sync_amms(vec![factory], middleware.clone(), None, 1000).await?;
+        assert_eq!(synced_block, 100);
+        assert_eq!(amms.len(), 1);
+        assert_eq!(amms[0].address(), pair);
+
+        //The pair count, the pairs and the pair data are all read at the synced block
+        assert_eq!(
+            *middleware.blocks.lock().expect("lock is not poisoned"),
+            vec![Some(BlockId::from(100_u64)); 3]
+        );
+
+        //AMMs that are populated one by one are also read at the block they are populated at
+        mock.push(Bytes::from(encode(&[Token::Array(vec![Token::Tuple(
+            vec![
+                Token::Address(H160::from_low_u64_be(20)),
+                Token::Uint(U256::from(18)),
+                Token::Address(H160::from_low_u64_be(21)),
+                Token::Uint(U256::from(18)),
+                Token::Uint(U256::from(1000)),
+                Token::Uint(U256::from(2000)),
+                Token::Uint(U256::zero()),
+                Token::Uint(U256::zero()),
+                Token::Uint(U256::from(200)),
+                Token::Uint(U256::zero()),
+                Token::Uint(U256::zero()),
+                Token::Uint(U256::from(50)),
+            ],
+        )])])))?;
+
+        let mut vaults = vec![AMM::ERC4626Vault(ERC4626Vault {
+            vault_token: H160::from_low_u64_be(20),
+            ..Default::default()
+        })];
+        populate_amms(&mut vaults, 100, middleware.clone()).await?;
+        assert_eq!(
+            middleware
+                .blocks
+                .lock()
+                .expect("lock is not poisoned")
+                .last(),
+            Some(&Some(BlockId::from(100_u64)))
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_amms_with_transfer_taxes() -> eyre::Result<()> {
+        let pair = H160::from_low_u64_be(1);
+        let (token_a, token_b) = (H160::from_low_u64_be(10), H160::from_low_u64_be(11));
+
+        //Responses are returned last in first out: the block number, the number of pairs, the pairs, the pair data and the amounts
+        //received by the transfer tax probes of token a and token b. The probes transfer a thousandth of the reserves.
+        let (provider, mock) = Provider::mocked();
+        mock.push(Bytes::from(encode(&[Token::Uint(U256::from(2000))])))?;
+        mock.push(Bytes::from(encode(&[Token::Uint(U256::from(950))])))?;
+        mock.push(Bytes::from(encode(&[Token::Array(vec![Token::Tuple(
+            vec![
+                Token::Address(token_a),
+                Token::Uint(U256::from(18)),
+                Token::Address(token_b),
+                Token::Uint(U256::from(18)),
+                Token::Uint(U256::from(1_000_000)),
+                Token::Uint(U256::from(2_000_000)),
+            ],
+        )])])))?;
+        mock.push(Bytes::from(encode(&[Token::Array(vec![Token::Address(
+            pair,
+        )])])))?;
+        mock.push(Bytes::from(encode(&[Token::Uint(U256::one())])))?;
+        mock.push(U64::from(100))?;
+
+        let factory =
+            Factory::UniswapV2Factory(UniswapV2Factory::new(H160::from_low_u64_be(2), 0, 300));
+        let (amms, _) = sync_amms_with_config(
+            vec![factory],
+            Arc::new(provider),
+            None,
+            SyncConfig::new(1000).with_transfer_taxes(true),
+        )
+        .await?;
+
+        match &amms[..] {
+            [AMM::UniswapV2Pool(pool)] => {
+                assert_eq!((pool.token_a_tax, pool.token_b_tax), (500, 0));
+            }
+            _ => panic!("expected the Uniswap V2 pair"),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_amms_with_progress() -> eyre::Result<()> {
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(100))?;
+
+        let (sender, mut receiver) = mpsc::channel(16);
+        let (amms, synced_block) =
+            sync_amms_with_progress(vec![], Arc::new(provider), None, 1000, sender).await?;
+        assert!(amms.is_empty());
+        assert_eq!(synced_block, 100);
+
+        assert_eq!(
+            receiver.recv().await,
+            Some(SyncProgress::Started {
+                block_number: 100,
+                factories: 0
+            })
+        );
+        match receiver.recv().await {
+            Some(SyncProgress::Finished {
+                block_number,
+                pools_per_factory,
+                ..
+            }) => {
+                assert_eq!(block_number, 100);
+                assert!(pools_per_factory.is_empty());
+            }
+            event => panic!("expected the sync to finish, got {event:?}"),
+        }
+
+        //The sender is dropped once the sync returns
+        assert!(receiver.recv().await.is_none());
+
+        Ok(())
+    }
 }
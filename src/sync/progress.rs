@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use ethers::types::H160;
+use tokio::sync::mpsc::Sender;
+
+//Progress of sync_amms_with_progress, the events of the factories are interleaved since the factories are synced concurrently
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncProgress {
+    //The AMMs are synced at `block_number`
+    Started {
+        block_number: u64,
+        factories: usize,
+    },
+    //`total` is the length of the pool array of the factory, it is not known for factories that do not store their pools in an array
+    PoolsEnumerated {
+        factory: H160,
+        pools: usize,
+        total: Option<u64>,
+    },
+    PoolsPopulated {
+        factory: H160,
+        populated: usize,
+        total: usize,
+    },
+    //`pools` excludes the pools that were removed because they could not be populated
+    FactorySynced {
+        factory: H160,
+        pools: usize,
+        elapsed: Duration,
+    },
+    Finished {
+        block_number: u64,
+        pools_per_factory: Vec<(H160, usize)>,
+        elapsed: Duration,
+    },
+}
+
+//Progress is dropped if the channel is full or closed, so a slow or dropped receiver never blocks or fails the sync
+pub fn send_progress(progress: Option<&Sender<SyncProgress>>, event: SyncProgress) {
+    if let Some(progress) = progress {
+        if let Err(err) = progress.try_send(event) {
+            tracing::trace!(%err, "dropping sync progress");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::H160;
+    use tokio::sync::mpsc;
+
+    use super::{send_progress, SyncProgress};
+
+    #[test]
+    fn test_send_progress() {
+        let event = |populated| SyncProgress::PoolsPopulated {
+            factory: H160::zero(),
+            populated,
+            total: 2,
+        };
+
+        let (sender, mut receiver) = mpsc::channel(1);
+        send_progress(Some(&sender), event(1));
+
+        //The channel is full
+        send_progress(Some(&sender), event(2));
+        assert_eq!(receiver.try_recv().ok(), Some(event(1)));
+        assert!(receiver.try_recv().is_err());
+
+        drop(receiver);
+        send_progress(Some(&sender), event(2));
+        send_progress(None, event(2));
+    }
+}
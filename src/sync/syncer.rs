@@ -0,0 +1,192 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use ethers::{
+    providers::{Middleware, PubsubClient, StreamExt},
+    types::{Filter, Log, H160, H256},
+};
+use thiserror::Error;
+use tokio::{sync::RwLock, task::JoinHandle};
+
+use crate::{
+    amm::{AutomatedMarketMaker, AMM},
+    errors::EventLogError,
+    state_space::state::{get_amm_addresses_from_log, get_dependent_amms},
+};
+
+#[derive(Error, Debug)]
+pub enum PoolSyncerError<M: Middleware> {
+    #[error("Pubsub client error")]
+    PubsubClientError(M::Error),
+    #[error("Log subscription ended")]
+    SubscriptionEnded,
+    #[error("Could not sync pool {0:?} from log")]
+    EventLogError(H160, #[source] EventLogError),
+}
+
+pub type PoolSyncerErrorHandler<M> = Arc<dyn Fn(PoolSyncerError<M>) + Send + Sync>;
+
+//Keeps the state of a set of pools up to date by applying every log of the events each pool syncs on,
+//e.g. `Sync` for Uniswap V2 pools and `Swap`/`Mint`/`Burn` for Uniswap V3 pools, as soon as it is streamed by the node
+pub struct PoolSyncer<M: Middleware> {
+    pools: HashMap<H160, Arc<RwLock<AMM>>>,
+    dependent_amms: HashMap<H160, Vec<H160>>,
+    error_handler: PoolSyncerErrorHandler<M>,
+}
+
+impl<M> PoolSyncer<M>
+where
+    M: 'static + Middleware,
+    M::Provider: PubsubClient,
+{
+    //Errors while processing a log are passed to the error handler, the syncer keeps processing the following logs
+    pub fn new<F>(amms: Vec<AMM>, error_handler: F) -> PoolSyncer<M>
+    where
+        F: Fn(PoolSyncerError<M>) + Send + Sync + 'static,
+    {
+        PoolSyncer {
+            dependent_amms: get_dependent_amms(&amms),
+            pools: amms
+                .into_iter()
+                .map(|amm| (amm.address(), Arc::new(RwLock::new(amm))))
+                .collect(),
+            error_handler: Arc::new(error_handler),
+        }
+    }
+
+    pub fn get_pool(&self, address: H160) -> Option<Arc<RwLock<AMM>>> {
+        self.pools.get(&address).cloned()
+    }
+
+    //Subscribes to the logs of all pools and applies them until the subscription ends
+    pub fn start(&self, middleware: Arc<M>) -> JoinHandle<()> {
+        let pools = self.pools.clone();
+        let dependent_amms = self.dependent_amms.clone();
+        let error_handler = self.error_handler.clone();
+
+        tokio::spawn(async move {
+            let filter = log_filter(&pools).await;
+
+            let mut log_stream = match middleware.subscribe_logs(&filter).await {
+                Ok(log_stream) => log_stream,
+                Err(error) => {
+                    error_handler(PoolSyncerError::PubsubClientError(error));
+                    return;
+                }
+            };
+
+            tracing::info!(pools = pools.len(), "subscribed to pool logs");
+
+            while let Some(log) = log_stream.next().await {
+                handle_log(&pools, &dependent_amms, log, &error_handler).await;
+            }
+
+            error_handler(PoolSyncerError::SubscriptionEnded);
+        })
+    }
+}
+
+//Filters by event signature only, pools that live in a singleton contract emit their events from that contract
+async fn log_filter(pools: &HashMap<H160, Arc<RwLock<AMM>>>) -> Filter {
+    let mut event_signatures: HashSet<H256> = HashSet::new();
+
+    for pool in pools.values() {
+        event_signatures.extend(pool.read().await.sync_on_event_signatures());
+    }
+
+    Filter::new().topic0(event_signatures.into_iter().collect::<Vec<H256>>())
+}
+
+async fn handle_log<M: Middleware>(
+    pools: &HashMap<H160, Arc<RwLock<AMM>>>,
+    dependent_amms: &HashMap<H160, Vec<H160>>,
+    log: Log,
+    error_handler: &PoolSyncerErrorHandler<M>,
+) {
+    //Logs that were removed by a reorg are streamed again, applying them would double count deltas
+    if log.removed == Some(true) {
+        tracing::warn!(?log.transaction_hash, "skipping log removed by a reorg");
+        return;
+    }
+
+    let mut addresses = get_amm_addresses_from_log(&log);
+    if let Some(dependents) = dependent_amms.get(&log.address) {
+        addresses.extend(dependents);
+    }
+
+    for address in addresses {
+        if let Some(pool) = pools.get(&address) {
+            if let Err(error) = pool.write().await.sync_from_log(log.clone()) {
+                error_handler(PoolSyncerError::EventLogError(address, error));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use ethers::{
+        abi::{encode, Token},
+        providers::{Provider, Ws},
+        types::{Log, H160, H256, U256},
+    };
+    use tokio::sync::RwLock;
+
+    use crate::amm::{
+        uniswap_v2::{UniswapV2Pool, SYNC_EVENT_SIGNATURE},
+        AMM,
+    };
+
+    use super::{handle_log, PoolSyncerError, PoolSyncerErrorHandler};
+
+    #[tokio::test]
+    async fn test_handle_log() -> eyre::Result<()> {
+        let address = H160::from_low_u64_be(1);
+        let pools = HashMap::from([(
+            address,
+            Arc::new(RwLock::new(AMM::UniswapV2Pool(UniswapV2Pool {
+                address,
+                ..Default::default()
+            }))),
+        )]);
+
+        let errors = Arc::new(Mutex::new(vec![]));
+        let handler_errors = errors.clone();
+        let error_handler: PoolSyncerErrorHandler<Provider<Ws>> =
+            Arc::new(move |error: PoolSyncerError<Provider<Ws>>| {
+                if let PoolSyncerError::EventLogError(address, _) = error {
+                    handler_errors.lock().unwrap().push(address);
+                }
+            });
+
+        let sync_log = Log {
+            address,
+            topics: vec![SYNC_EVENT_SIGNATURE],
+            data: encode(&[Token::Uint(U256::from(100)), Token::Uint(U256::from(200))]).into(),
+            ..Default::default()
+        };
+        handle_log(&pools, &HashMap::new(), sync_log, &error_handler).await;
+
+        if let AMM::UniswapV2Pool(pool) = &*pools[&address].read().await {
+            assert_eq!((pool.reserve_0, pool.reserve_1), (100, 200));
+        }
+
+        //Logs of other pools are ignored and logs that fail to decode are passed to the error handler
+        let invalid_log = Log {
+            address,
+            topics: vec![H256::zero()],
+            ..Default::default()
+        };
+        handle_log(&pools, &HashMap::new(), invalid_log, &error_handler).await;
+        assert_eq!(*errors.lock().unwrap(), vec![address]);
+
+        Ok(())
+    }
+}
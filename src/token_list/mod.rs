@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+
+use ethers::types::H160;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::TokenListError;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenInfo {
+    pub chain_id: u64,
+    pub symbol: String,
+    pub decimals: u8,
+    pub logo_uri: Option<String>,
+}
+
+//Symbols and decimals of the tokens of a token list in the Uniswap token list format, e.g. https://tokens.uniswap.org.
+//Lists that contain a token on more than one chain keep the first entry of the token.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TokenList {
+    pub name: String,
+    pub tokens: HashMap<H160, TokenInfo>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TokenListJson {
+    #[serde(default)]
+    name: String,
+    tokens: Vec<TokenJson>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TokenJson {
+    chain_id: u64,
+    address: H160,
+    symbol: String,
+    decimals: u8,
+    #[serde(rename = "logoURI")]
+    logo_uri: Option<String>,
+}
+
+impl TokenList {
+    pub async fn from_url(url: &str) -> Result<TokenList, TokenListError> {
+        let json = reqwest::get(url).await?.error_for_status()?.text().await?;
+        TokenList::from_json(&json)
+    }
+
+    pub fn from_json(json: &str) -> Result<TokenList, TokenListError> {
+        let token_list: TokenListJson = serde_json::from_str(json)?;
+
+        let mut tokens = HashMap::new();
+        for token in token_list.tokens {
+            tokens.entry(token.address).or_insert(TokenInfo {
+                chain_id: token.chain_id,
+                symbol: token.symbol,
+                decimals: token.decimals,
+                logo_uri: token.logo_uri,
+            });
+        }
+
+        Ok(TokenList {
+            name: token_list.name,
+            tokens,
+        })
+    }
+
+    //Only keeps the tokens on the chain, lists often contain the bridged tokens of several chains
+    pub fn for_chain(mut self, chain_id: u64) -> TokenList {
+        self.tokens.retain(|_, token| token.chain_id == chain_id);
+        self
+    }
+
+    pub fn resolve(&self, address: H160) -> Option<TokenInfo> {
+        self.tokens.get(&address).cloned()
+    }
+
+    //Symbol of the token, or its abbreviated address if it is not in the list
+    pub fn symbol(&self, address: H160) -> String {
+        self.tokens
+            .get(&address)
+            .map_or_else(|| address.to_string(), |token| token.symbol.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use ethers::types::H160;
+
+    use crate::amm::{uniswap_v2::UniswapV2Pool, uniswap_v3::UniswapV3Pool, AMM};
+
+    use super::TokenList;
+
+    const TOKEN_LIST: &str = r#"{
+        "name": "Test List",
+        "tokens": [
+            {
+                "chainId": 1,
+                "address": "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48",
+                "name": "USD Coin",
+                "symbol": "USDC",
+                "decimals": 6,
+                "logoURI": "https://example.com/usdc.png"
+            },
+            {
+                "chainId": 1,
+                "address": "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2",
+                "name": "Wrapped Ether",
+                "symbol": "WETH",
+                "decimals": 18
+            },
+            {
+                "chainId": 10,
+                "address": "0x4200000000000000000000000000000000000006",
+                "name": "Wrapped Ether",
+                "symbol": "WETH",
+                "decimals": 18
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_token_list() -> eyre::Result<()> {
+        let usdc = H160::from_str("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48")?;
+        let weth = H160::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2")?;
+
+        let token_list = TokenList::from_json(TOKEN_LIST)?;
+        assert_eq!(token_list.name, "Test List");
+        assert_eq!(token_list.tokens.len(), 3);
+
+        let usdc_info = token_list.resolve(usdc).expect("USDC is in the list");
+        assert_eq!((usdc_info.symbol.as_str(), usdc_info.decimals), ("USDC", 6));
+        assert_eq!(
+            usdc_info.logo_uri.as_deref(),
+            Some("https://example.com/usdc.png")
+        );
+        assert!(token_list.resolve(H160::zero()).is_none());
+
+        let pair = AMM::UniswapV2Pool(UniswapV2Pool {
+            token_a: usdc,
+            token_b: weth,
+            fee: 300,
+            ..Default::default()
+        });
+        assert_eq!(pair.display_name(&token_list), "USDC/WETH 0.3%");
+
+        //Tokens that are not in the list are shown by their address
+        let pool = AMM::UniswapV3Pool(UniswapV3Pool {
+            token_a: H160::zero(),
+            token_b: weth,
+            fee: 500,
+            ..Default::default()
+        });
+        assert_eq!(
+            pool.display_name(&token_list.for_chain(1)),
+            format!("{}/WETH 0.05%", H160::zero())
+        );
+
+        Ok(())
+    }
+}